@@ -0,0 +1,10 @@
+-- Responses of completed mutating actions, indexed by the idempotency key provided by
+-- the clients. Used to return the original response when a request is retried.
+
+CREATE TABLE idempotency_key_t(
+  idempotency_key       TEXT    PRIMARY KEY,
+  response              BYTEA   NOT NULL,
+  creation_unix_tstamp  BIGINT  NOT NULL
+);
+
+CREATE INDEX idx_idempotency_key_creation ON idempotency_key_t(creation_unix_tstamp);
@@ -0,0 +1,4 @@
+-- Track when each chunk was written, the most recent chunk gives the last write time
+-- of a topic. Chunks written before this migration have no timestamp.
+
+ALTER TABLE chunk_t ADD COLUMN creation_unix_tstamp BIGINT;
@@ -0,0 +1,4 @@
+-- Content hash of the serialized chunk, used to detect chunks resent by retried uploads.
+-- Chunks written before this migration have no hash.
+
+ALTER TABLE chunk_t ADD COLUMN content_hash BIGINT;
@@ -0,0 +1,4 @@
+-- Unit of the timestamps stored in the topic data.
+-- Topics created before this migration have no precision, their data is in nanoseconds.
+
+ALTER TABLE topic_t ADD COLUMN time_precision TEXT;
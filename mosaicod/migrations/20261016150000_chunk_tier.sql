@@ -0,0 +1,5 @@
+-- Track the last read of each chunk and the storage tier holding its datafile.
+-- Chunks never read since this migration have no access timestamp, their write time is used.
+
+ALTER TABLE chunk_t ADD COLUMN last_access_unix_tstamp BIGINT;
+ALTER TABLE chunk_t ADD COLUMN tier TEXT NOT NULL DEFAULT 'warm';
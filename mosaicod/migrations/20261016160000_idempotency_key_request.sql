@@ -0,0 +1,7 @@
+-- Keys are reserved before the execution of the action, so that concurrent retries don't
+-- execute it twice. The response is missing until the action completes. The action name and
+-- the hash of the request body detect a key reused for a different request.
+
+ALTER TABLE idempotency_key_t ALTER COLUMN response DROP NOT NULL;
+ALTER TABLE idempotency_key_t ADD COLUMN action TEXT NOT NULL DEFAULT '';
+ALTER TABLE idempotency_key_t ADD COLUMN body_hash BIGINT NOT NULL DEFAULT 0;
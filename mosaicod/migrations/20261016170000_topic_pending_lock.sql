@@ -0,0 +1,12 @@
+-- Topics whose lock is deferred by the upload grace period. The pending locks are persisted
+-- so that they are not lost if the server restarts before the grace period elapses.
+
+CREATE TABLE topic_pending_lock_t(
+  topic_id              INTEGER PRIMARY KEY,
+  scheduled_unix_tstamp BIGINT NOT NULL,
+
+  CONSTRAINT fk_topic
+    FOREIGN KEY (topic_id)
+    REFERENCES topic_t(topic_id)
+    ON DELETE CASCADE
+);
@@ -0,0 +1,4 @@
+-- Content hash of the chunks as a SHA-256 digest. The previous 64-bit hashes are dropped,
+-- the chunks written before this migration have no hash.
+
+ALTER TABLE chunk_t ALTER COLUMN content_hash TYPE BYTEA USING NULL;
@@ -2,7 +2,7 @@ use std::collections::VecDeque;
 use std::sync::Arc;
 
 use arrow::array::{ArrayRef, AsArray, RecordBatch, StructArray};
-use arrow::datatypes::{DataType, Field, FieldRef, SchemaRef};
+use arrow::datatypes::{DataType, Field, FieldRef, Schema, SchemaRef};
 use arrow::error::ArrowError;
 
 use crate::{params, traits::SquashedIterator, types};
@@ -14,6 +14,12 @@ pub enum SchemaError {
     MissingTimestampInSchema,
     #[error("wrong timestamp field type, expected int64")]
     WrongTimestampType,
+    /// Returned when the time column declared by the topic is missing in the provided schema.
+    #[error("missing time column `{0}` in schema")]
+    MissingTimeColumn(String),
+    /// Returned when the schema holds both the declared time column and the timestamp field.
+    #[error("time column `{0}` conflicts with the timestamp field")]
+    TimeColumnConflict(String),
 }
 
 /// Validates that the provided Arrow schema meets certain structural requirements.
@@ -36,6 +42,52 @@ pub fn check_schema(schema: &SchemaRef) -> Result<(), SchemaError> {
     Ok(())
 }
 
+/// Returns the schema with the `time_column` field renamed to the timestamp field, so that data
+/// declaring its own time column is stored following the platform conventions.
+///
+/// # Errors
+///
+/// Returns [`SchemaError`] if `time_column` is missing or the schema already holds a timestamp
+/// field.
+pub fn rename_time_column(schema: &SchemaRef, time_column: &str) -> Result<SchemaRef, SchemaError> {
+    if time_column == params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP {
+        return Ok(schema.clone());
+    }
+    if schema
+        .field_with_name(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP)
+        .is_ok()
+    {
+        return Err(SchemaError::TimeColumnConflict(time_column.to_owned()));
+    }
+
+    let index = schema
+        .index_of(time_column)
+        .map_err(|_| SchemaError::MissingTimeColumn(time_column.to_owned()))?;
+
+    let fields: Vec<FieldRef> = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            if idx == index {
+                Arc::new(
+                    field
+                        .as_ref()
+                        .clone()
+                        .with_name(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP),
+                )
+            } else {
+                field.clone()
+            }
+        })
+        .collect();
+
+    Ok(Arc::new(Schema::new_with_metadata(
+        fields,
+        schema.metadata().clone(),
+    )))
+}
+
 /// Checks if the given Arrow [`DataType`] is considered numeric
 #[must_use]
 pub fn is_numeric(data_type: &DataType) -> bool {
@@ -348,6 +400,28 @@ mod tests {
         assert!(result.is_err());
     }
 
+    /// A schema declaring its own time column passes validation once renamed.
+    #[test]
+    fn renamed_time_column() {
+        let schema = create_schema(vec![
+            Field::new("t", DataType::Int64, false),
+            Field::new("value", DataType::Float64, true),
+        ]);
+
+        let renamed = rename_time_column(&schema, "t").unwrap();
+        assert!(check_schema(&renamed).is_ok());
+        assert_eq!(renamed.field(1), schema.field(1));
+
+        assert!(matches!(
+            rename_time_column(&schema, "missing"),
+            Err(SchemaError::MissingTimeColumn(_))
+        ));
+        assert!(matches!(
+            rename_time_column(&renamed, "value"),
+            Err(SchemaError::TimeColumnConflict(_))
+        ));
+    }
+
     // Helper function to create a simplified schema reference
     fn create_schema_ref(fields: Vec<Field>) -> Arc<Schema> {
         Arc::new(Schema::new(fields))
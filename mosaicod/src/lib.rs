@@ -7,6 +7,7 @@
 
 pub mod arrow;
 pub mod marshal;
+pub mod ontology;
 pub mod params;
 pub mod query;
 pub mod repo;
@@ -1,14 +1,19 @@
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
-use std::{env, sync::Arc, thread, time::Instant};
+use std::{
+    env,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
 
 use clap::{Args, Parser, Subcommand};
 
 use dotenv::dotenv;
 
 use log::{debug, error, info, trace};
-use mosaicod::{params, repo, server, store, utils::print};
+use mosaicod::{ontology, params, repo, server, store, utils::print};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -78,6 +83,7 @@ fn load_remote_store_vars() -> Result<store::S3Config, Box<dyn std::error::Error
         bucket: store_bucket,
         secret_key: store_secret_key,
         access_key: store_access_key,
+        key_scheme: params::configurables().store_key_scheme,
     };
 
     debug!("{:#?}", vars);
@@ -92,6 +98,11 @@ fn run(startup_time: &Instant) -> Result<(), Box<dyn std::error::Error>> {
 
     let vars = load_env_variables()?;
 
+    if let Some(path) = &params::configurables().ontology_registry {
+        info!("loading ontology registry from {}", path.display());
+        ontology::init_registry(ontology::OntologyRegistry::from_file(path)?);
+    }
+
     match args.cmd {
         Commands::Run(args) => {
             let store = get_store(&args)?;
@@ -131,18 +142,26 @@ fn run(startup_time: &Instant) -> Result<(), Box<dyn std::error::Error>> {
 }
 
 fn get_store(cmds: &CommandRun) -> Result<store::StoreRef, Box<dyn std::error::Error>> {
-    if let Some(path) = &cmds.local_store {
+    let store = if let Some(path) = &cmds.local_store {
         info!("initializing filesystem store");
-        Ok(Arc::new(store::Store::try_from_filesystem(path)?))
+        store::Store::try_from_filesystem(path)?
     } else {
         info!("initializing s3-compatible store");
 
         let s3_config = load_remote_store_vars()?;
 
-        let store = Arc::new(store::Store::try_from_s3_store(s3_config)?);
+        store::Store::try_from_s3_store(s3_config)?
+    };
 
-        Ok(store)
-    }
+    let timeout = Duration::from_secs(params::configurables().store_timeout_secs);
+
+    Ok(Arc::new(
+        store
+            .with_timeout(timeout)
+            .with_fsync_policy(params::configurables().store_fsync_policy)
+            .with_read_after_write(params::configurables().store_read_after_write)
+            .with_error_context(params::configurables().store_error_context),
+    ))
 }
 
 /// Returns the name to display on the console for the current in use store
@@ -1,4 +1,5 @@
 use super::{requests, responses};
+use crate::utils;
 use serde::Serialize;
 use thiserror::Error;
 
@@ -11,6 +12,10 @@ pub enum ActionError {
     #[error("no available action for string `{0}`")]
     MissingAction(String),
 
+    /// The request body exceeds the configured maximum size.
+    #[error("body of {size} bytes exceeds the limit of {limit} bytes")]
+    BodyTooLarge { size: usize, limit: usize },
+
     /// Failed to deserialize the request body.
     #[error("body deserialization error: {0}")]
     BodyDeserializationError(#[from] serde_json::Error),
@@ -18,6 +23,10 @@ pub enum ActionError {
     /// Failed to serialize the response.
     #[error("response serialization error: {0}")]
     ResponseSerializationError(String),
+
+    /// The requested response encoding is not supported.
+    #[error("unsupported response encoding `{0}`")]
+    UnsupportedEncoding(String),
 }
 
 /// Represents the list of actions allowed in the system.
@@ -70,9 +79,23 @@ pub enum ActionRequest {
     /// Deletes all notifications associated with a sequence
     SequenceNotifyPurge(requests::ResourceLocator),
 
+    /// Asks for the manifest of a sequence, listing its topics with the size and checksum of
+    /// their datafiles
+    SequenceManifest(requests::ResourceLocator),
+
+    /// Validates a sequence (e.g. restored from a backup) against its manifest
+    SequenceVerifyManifest(requests::SequenceVerifyManifest),
+
+    /// Clones a sequence with all its topics data and metadata to a new sequence
+    SequenceClone(requests::SequenceClone),
+
     /// Creates a new topic in the system without any data.
     TopicCreate(requests::TopicCreate),
 
+    /// Creates a new topic in the system without any data, the topic name is
+    /// generated by the server.
+    TopicCreateAuto(requests::TopicCreateAuto),
+
     /// Deletes an unlocked topic from the system.
     TopicDelete(requests::ResourceLocator),
 
@@ -88,8 +111,51 @@ pub enum ActionRequest {
     /// Ask for system informations about the topic
     TopicSystemInfo(requests::ResourceLocator),
 
+    /// Ask for system informations about multiple topics, topics not found are reported
+    /// without failing the whole request
+    TopicSystemInfoBatch(requests::TopicSystemInfoBatch),
+
+    /// Validates the last chunk of a topic, removing it if corrupted
+    /// (e.g. a partial write left by a crash during the upload).
+    TopicRepair(requests::ResourceLocator),
+
+    /// Rewrites the chunks of a topic sorted by timestamp, so that their time ranges
+    /// do not overlap
+    TopicReorder(requests::TopicReorder),
+
+    /// Compares the chunks registered for a topic against the datafiles found in the store
+    TopicConsistencyCheck(requests::ResourceLocator),
+
+    /// Moves the chunks of a topic not accessed for the configured idle period to the cold
+    /// storage class, the chunks remain queryable
+    TopicTier(requests::ResourceLocator),
+
     Query(requests::Query),
 
+    /// Validates a query (fields, ranges, formats) without executing it
+    QueryValidate(requests::QueryValidate),
+
+    /// Asks for the last rows of a topic by timestamp
+    QueryTail(requests::QueryTail),
+
+    /// Asks for the row of a topic at a timestamp
+    QueryAt(requests::QueryAt),
+
+    /// Asks for the statistics of each column of a topic, without the data
+    QueryColumnStats(requests::QueryColumnStats),
+
+    /// Asks for the intervals of a topic where no data was recorded
+    QueryGaps(requests::QueryGaps),
+
+    /// Asks for the distinct values of a column of a topic
+    QueryDistinct(requests::QueryDistinct),
+
+    /// Asks for aggregations (count, min, max, ...) of the columns of a topic
+    QueryAggregate(requests::QueryAggregate),
+
+    /// Cancels the data stream of a query tagged by a client request token
+    QueryCancel(requests::QueryCancel),
+
     /// Creates a new layer in the repository
     LayerCreate(requests::LayerCreate),
 
@@ -101,6 +167,21 @@ pub enum ActionRequest {
 
     /// Ask for the list of existing layers in the system
     LayerList(requests::Empty),
+
+    /// Ask for the server version and capabilities
+    SystemInfo(requests::Empty),
+
+    /// Deletes all the sequences and topics matching a name prefix
+    DeletePrefix(requests::DeletePrefix),
+
+    /// Copies all the resources to another store
+    SystemMigrate(requests::SystemMigrate),
+
+    /// Removes the orphaned staging files left in the store by interrupted writes
+    SystemVacuum(requests::SystemVacuum),
+
+    /// Ask for the JSON schema of the body expected by an action
+    DescribeAction(requests::DescribeAction),
 }
 
 /// Internal macro used to parse action requests
@@ -111,6 +192,23 @@ macro_rules! parse_action_req {
 }
 
 impl ActionRequest {
+    /// Same as [`ActionRequest::try_new`] but rejects bodies larger than `max_body_bytes`
+    /// before deserializing them. The body is already buffered, the server rejects the larger
+    /// actions while receiving them.
+    pub fn try_new_bounded(
+        value: &str,
+        body: &[u8],
+        max_body_bytes: usize,
+    ) -> Result<Self, ActionError> {
+        if body.len() > max_body_bytes {
+            return Err(ActionError::BodyTooLarge {
+                size: body.len(),
+                limit: max_body_bytes,
+            });
+        }
+        Self::try_new(value, body)
+    }
+
     pub fn try_new(value: &str, body: &[u8]) -> Result<Self, ActionError> {
         match value {
             "sequence_create" => parse_action_req!(SequenceCreate, body),
@@ -121,13 +219,22 @@ impl ActionRequest {
             "sequence_notify_create" => parse_action_req!(SequenceNotifyCreate, body),
             "sequence_notify_list" => parse_action_req!(SequenceNotifyList, body),
             "sequence_notify_purge" => parse_action_req!(SequenceNotifyPurge, body),
+            "sequence_manifest" => parse_action_req!(SequenceManifest, body),
+            "sequence_verify_manifest" => parse_action_req!(SequenceVerifyManifest, body),
+            "sequence_clone" => parse_action_req!(SequenceClone, body),
 
             "topic_create" => parse_action_req!(TopicCreate, body),
+            "topic_create_auto" => parse_action_req!(TopicCreateAuto, body),
             "topic_delete" => parse_action_req!(TopicDelete, body),
             "topic_system_info" => parse_action_req!(TopicSystemInfo, body),
+            "topic_system_info_batch" => parse_action_req!(TopicSystemInfoBatch, body),
             "topic_notify_create" => parse_action_req!(TopicNotifyCreate, body),
             "topic_notify_list" => parse_action_req!(TopicNotifyList, body),
             "topic_notify_purge" => parse_action_req!(TopicNotifyPurge, body),
+            "topic_repair" => parse_action_req!(TopicRepair, body),
+            "topic_reorder" => parse_action_req!(TopicReorder, body),
+            "topic_consistency_check" => parse_action_req!(TopicConsistencyCheck, body),
+            "topic_tier" => parse_action_req!(TopicTier, body),
 
             "layer_create" => parse_action_req!(LayerCreate, body),
             "layer_delete" => parse_action_req!(LayerDelete, body),
@@ -135,10 +242,202 @@ impl ActionRequest {
             "layer_list" => parse_action_req!(LayerList, body),
 
             "query" => parse_action_req!(Query, body),
+            "query_validate" => parse_action_req!(QueryValidate, body),
+            "query_tail" => parse_action_req!(QueryTail, body),
+            "query_at" => parse_action_req!(QueryAt, body),
+            "query_column_stats" => parse_action_req!(QueryColumnStats, body),
+            "query_gaps" => parse_action_req!(QueryGaps, body),
+            "query_distinct" => parse_action_req!(QueryDistinct, body),
+            "query_aggregate" => parse_action_req!(QueryAggregate, body),
+            "query_cancel" => parse_action_req!(QueryCancel, body),
+
+            "system_info" => parse_action_req!(SystemInfo, body),
+            "delete_prefix" => parse_action_req!(DeletePrefix, body),
+            "system_migrate" => parse_action_req!(SystemMigrate, body),
+            "system_vacuum" => parse_action_req!(SystemVacuum, body),
+            "describe_action" => parse_action_req!(DescribeAction, body),
 
             _ => Err(ActionError::MissingAction(value.to_owned())),
         }
     }
+
+    /// Returns true if the action modifies the state of the system.
+    ///
+    /// Mutating actions are listed explicitly, so that a new action is handled as read-only
+    /// (e.g. it takes no idempotency key) until it is added here.
+    pub fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            Self::SequenceCreate(_)
+                | Self::SequenceDelete(_)
+                | Self::SequenceAbort(_)
+                | Self::SequenceFinalize(_)
+                | Self::SequenceNotifyCreate(_)
+                | Self::SequenceNotifyPurge(_)
+                | Self::SequenceClone(_)
+                | Self::TopicCreate(_)
+                | Self::TopicCreateAuto(_)
+                | Self::TopicDelete(_)
+                | Self::TopicNotifyCreate(_)
+                | Self::TopicNotifyPurge(_)
+                | Self::TopicRepair(_)
+                | Self::TopicReorder(_)
+                | Self::TopicTier(_)
+                | Self::LayerCreate(_)
+                | Self::LayerDelete(_)
+                | Self::LayerUpdate(_)
+                | Self::DeletePrefix(_)
+                | Self::SystemMigrate(_)
+                | Self::SystemVacuum(_)
+        )
+    }
+}
+
+/// Kind of access to a resource required by an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// The resource is only read
+    Read,
+    /// The resource is created, modified or deleted
+    Write,
+    /// The whole system is administered (e.g. the store is migrated or vacuumed), reserved to
+    /// the administrators
+    Admin,
+}
+
+impl std::fmt::Display for Access {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read => write!(f, "read"),
+            Self::Write => write!(f, "modify"),
+            Self::Admin => write!(f, "administer"),
+        }
+    }
+}
+
+impl ActionRequest {
+    /// Returns the names of the resources targeted by the action, each with the access the
+    /// action requires on it. Actions not targeting a specific resource return a single
+    /// [`None`] target, with [`Access::Admin`] for the actions administering the system. For actions on resources sharing a prefix (e.g. `delete_prefix`) the
+    /// returned name is the common prefix.
+    pub fn targets(&self) -> Vec<(Option<&str>, Access)> {
+        let access = if self.is_mutating() {
+            Access::Write
+        } else {
+            Access::Read
+        };
+
+        let name = match self {
+            Self::SequenceCreate(data) => &data.name,
+            Self::SequenceDelete(data)
+            | Self::SequenceSystemInfo(data)
+            | Self::SequenceNotifyList(data)
+            | Self::SequenceNotifyPurge(data)
+            | Self::SequenceManifest(data)
+            | Self::TopicDelete(data)
+            | Self::TopicNotifyList(data)
+            | Self::TopicNotifyPurge(data)
+            | Self::TopicSystemInfo(data)
+            | Self::TopicRepair(data)
+            | Self::TopicConsistencyCheck(data)
+            | Self::TopicTier(data) => &data.name,
+            Self::SequenceAbort(data) | Self::SequenceFinalize(data) => &data.name,
+            Self::SequenceVerifyManifest(data) => &data.manifest.sequence,
+            Self::TopicSystemInfoBatch(data) => {
+                return data
+                    .names
+                    .iter()
+                    .map(|name| (Some(name.as_str()), access))
+                    .collect();
+            }
+            Self::SequenceClone(data) => {
+                return vec![
+                    (Some(data.source.as_str()), Access::Read),
+                    (Some(data.dest.as_str()), Access::Write),
+                ];
+            }
+            Self::SequenceNotifyCreate(data) | Self::TopicNotifyCreate(data) => &data.name,
+            Self::TopicCreate(data) => &data.name,
+            Self::TopicCreateAuto(data) => &data.sequence_name,
+            Self::TopicReorder(data) => &data.name,
+            Self::QueryTail(data) => &data.locator,
+            Self::QueryAt(data) => &data.locator,
+            Self::QueryColumnStats(data) => &data.locator,
+            Self::QueryGaps(data) => &data.locator,
+            Self::QueryDistinct(data) => &data.locator,
+            Self::QueryAggregate(data) => &data.locator,
+            Self::LayerCreate(data) => &data.name,
+            Self::LayerDelete(data) => &data.name,
+            Self::LayerUpdate(data) => &data.prev_name,
+            Self::DeletePrefix(data) => &data.prefix,
+            Self::Query(_)
+            | Self::QueryValidate(_)
+            | Self::QueryCancel(_)
+            | Self::LayerList(_)
+            | Self::SystemInfo(_)
+            | Self::DescribeAction(_) => {
+                return vec![(None, access)];
+            }
+            Self::SystemMigrate(_) | Self::SystemVacuum(_) => {
+                return vec![(None, Access::Admin)];
+            }
+        };
+        vec![(Some(name.as_str()), access)]
+    }
+}
+
+/// Idempotency key attached to the body of an action, along with the fingerprint of the
+/// request, used to detect a key reused for a different request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdempotentRequest {
+    pub key: String,
+    /// Name of the action
+    pub action: String,
+    /// Hash of the parsed action body, see [`request_hash`]
+    pub body_hash: u64,
+}
+
+/// Extracts the optional idempotency key from the body of the action `action`
+pub fn idempotency_key(
+    action: &str,
+    body: &[u8],
+) -> Result<Option<IdempotentRequest>, ActionError> {
+    let body: serde_json::Value = serde_json::from_slice(body)?;
+    let key: requests::IdempotencyKey = serde_json::from_value(body.clone())?;
+    Ok(key.idempotency_key.map(|key| IdempotentRequest {
+        key,
+        action: action.to_owned(),
+        body_hash: request_hash(body),
+    }))
+}
+
+/// Hashes the parsed body of a request, so that bodies differing only by formatting, field
+/// order or explicit null fields share the same hash.
+fn request_hash(body: serde_json::Value) -> u64 {
+    fn canonical(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => {
+                let mut fields: Vec<_> = map
+                    .into_iter()
+                    .filter(|(_, value)| !value.is_null())
+                    .collect();
+                fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+                serde_json::Value::Object(
+                    fields
+                        .into_iter()
+                        .map(|(name, value)| (name, canonical(value)))
+                        .collect(),
+                )
+            }
+            serde_json::Value::Array(values) => {
+                serde_json::Value::Array(values.into_iter().map(canonical).collect())
+            }
+            value => value,
+        }
+    }
+
+    let canonical = serde_json::to_vec(&canonical(body)).expect("JSON values always serialize");
+    utils::hash::fnv1a(&canonical)
 }
 
 #[derive(Serialize)]
@@ -147,29 +446,55 @@ pub enum ActionResponse {
     SequenceCreate(responses::ResourceKey),
     SequenceSystemInfo(responses::SequenceSystemInfo),
     SequenceNotifyList(responses::NotifyList),
+    SequenceManifest(responses::SequenceManifest),
+    SequenceVerifyManifest(responses::ManifestValidation),
+    SequenceClone(responses::SequenceClone),
 
     TopicCreate(responses::ResourceKey),
+    TopicCreateAuto(responses::TopicCreateAuto),
     TopicSystemInfo(responses::TopicSystemInfo),
+    TopicSystemInfoBatch(responses::TopicSystemInfoBatch),
     TopicNotifyList(responses::NotifyList),
+    TopicRepair(responses::TopicRepair),
+    TopicReorder(responses::TopicReorder),
+    TopicConsistencyCheck(responses::TopicConsistencyReport),
+    TopicTier(responses::TopicTier),
 
     LayerList(responses::LayerList),
 
     Query(responses::Query),
+    QueryCount(responses::QueryCount),
+    QuerySchema(responses::QuerySchema),
+    QueryValidate(responses::QueryValidate),
+    QueryTail(responses::QueryTail),
+    QueryAt(responses::QueryAt),
+    QueryColumnStats(responses::QueryColumnStats),
+    QueryGaps(responses::QueryGaps),
+    QueryDistinct(responses::QueryDistinct),
+    QueryAggregate(responses::QueryAggregate),
+
+    SystemInfo(responses::SystemInfo),
+    DeletePrefix(responses::DeletePrefix),
+    SystemMigrate(responses::SystemMigrate),
+    SystemVacuum(responses::SystemVacuum),
+    DescribeAction(responses::DescribeAction),
 
     // Empty response, no data to send
     Empty,
 }
 
 impl ActionResponse {
-    /// Converts to bytes the action response
+    /// Converts to bytes the action response, wrapped in a JSON-encoded
+    /// [`super::ResponseEnvelope`]
     pub fn bytes(&self) -> Result<Vec<u8>, ActionError> {
-        serde_json::to_vec(self).map_err(|e| ActionError::ResponseSerializationError(e.to_string()))
+        serde_json::to_vec(&super::ResponseEnvelope::new(self))
+            .map_err(|e| ActionError::ResponseSerializationError(e.to_string()))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::ActionRequest;
+    use super::{ActionError, ActionRequest, idempotency_key};
     use crate::rw;
     use serde::Deserialize;
 
@@ -179,6 +504,54 @@ mod tests {
         driver: String,
     }
 
+    /// Bodies over the limit are rejected before being deserialized, even if malformed
+    #[test]
+    fn request_body_too_large() {
+        let body = vec![b'x'; 1024];
+
+        assert!(matches!(
+            ActionRequest::try_new_bounded("sequence_create", &body, 1023),
+            Err(ActionError::BodyTooLarge {
+                size: 1024,
+                limit: 1023
+            })
+        ));
+        assert!(matches!(
+            ActionRequest::try_new_bounded("sequence_create", &body, 1024),
+            Err(ActionError::BodyDeserializationError(_))
+        ));
+    }
+
+    /// The request fingerprint depends on the parsed body and not on its formatting
+    #[test]
+    fn idempotency_key_request_hash() {
+        let hash = |body: &str| {
+            idempotency_key("sequence_create", body.as_bytes())
+                .unwrap()
+                .unwrap()
+                .body_hash
+        };
+
+        let body =
+            hash(r#"{"name": "seq", "user_metadata": {"a": 1, "b": 2}, "idempotency_key": "k"}"#);
+        assert_eq!(
+            body,
+            hash(
+                r#"{ "idempotency_key":"k","user_metadata":{"b":2,"a":1},"name":"seq","extra":null }"#
+            )
+        );
+        assert_ne!(
+            body,
+            hash(r#"{"name": "seq", "user_metadata": {"a": 1, "b": 3}, "idempotency_key": "k"}"#)
+        );
+
+        assert!(
+            idempotency_key("sequence_create", br#"{"name": "seq"}"#)
+                .unwrap()
+                .is_none()
+        );
+    }
+
     /// Ensure that user_metadata field in [`RequestTopicCreate`] is serialized
     /// correctly as a string and can be converted to a parsable json if required.
     #[test]
@@ -202,7 +575,7 @@ mod tests {
         if let ActionRequest::TopicCreate(action) = action {
             assert_eq!(action.name, "test_topic");
             assert_eq!(action.sequence_key, "some_uuid");
-            assert_eq!(action.serialization_format, rw::Format::Default);
+            assert_eq!(action.serialization_format, Some(rw::Format::Default));
             assert_eq!(action.ontology_tag, "my_sensor");
             let raw_json = action
                 .user_metadata()
@@ -0,0 +1,133 @@
+use serde::Serialize;
+
+use super::{ActionError, ActionResponse};
+
+/// Version of the structure of the action responses, increased on breaking changes
+pub const RESPONSE_ENVELOPE_VERSION: u32 = 1;
+
+/// Request metadata key used by the clients to select the [`ResponseEncoding`]
+pub const RESPONSE_ENCODING_METADATA_KEY: &str = "x-mosaico-response-encoding";
+
+/// Envelope wrapping the response of every action.
+///
+/// The response is serialized as
+/// `{"version": <version>, "action": <action name>, "response": <response body>}`,
+/// the `response` field is missing for actions without a response body.
+#[derive(Serialize)]
+pub struct ResponseEnvelope<'a> {
+    pub version: u32,
+    #[serde(flatten)]
+    pub response: &'a ActionResponse,
+}
+
+impl<'a> ResponseEnvelope<'a> {
+    pub fn new(response: &'a ActionResponse) -> Self {
+        Self {
+            version: RESPONSE_ENVELOPE_VERSION,
+            response,
+        }
+    }
+}
+
+/// Encoding of the action responses sent to the clients
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseEncoding {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl ResponseEncoding {
+    /// Returns the encoding selected by the value of the
+    /// [`RESPONSE_ENCODING_METADATA_KEY`] request metadata, JSON if missing.
+    pub fn from_header(value: Option<&str>) -> Result<Self, ActionError> {
+        match value.map(str::trim) {
+            None | Some("json") | Some("application/json") => Ok(Self::Json),
+            Some("msgpack") | Some("application/msgpack") => Ok(Self::MessagePack),
+            Some(other) => Err(ActionError::UnsupportedEncoding(other.to_owned())),
+        }
+    }
+
+    /// Converts a JSON-encoded response envelope (see [`ActionResponse::bytes`]) to this
+    /// encoding.
+    ///
+    /// Responses are produced (and recorded for idempotent retries) as JSON, so the encoding
+    /// can be chosen independently on each request.
+    pub fn encode(&self, json: Vec<u8>) -> Result<Vec<u8>, ActionError> {
+        match self {
+            Self::Json => Ok(json),
+            Self::MessagePack => {
+                let value: serde_json::Value = serde_json::from_slice(&json)
+                    .map_err(|e| ActionError::ResponseSerializationError(e.to_string()))?;
+                rmp_serde::to_vec_named(&value)
+                    .map_err(|e| ActionError::ResponseSerializationError(e.to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::marshal::responses;
+
+    fn envelope_json() -> serde_json::Value {
+        serde_json::json!({
+            "version": RESPONSE_ENVELOPE_VERSION,
+            "action": "query_gaps",
+            "response": { "gaps": [[10, 20]] },
+        })
+    }
+
+    #[test]
+    fn envelope_json_roundtrip() {
+        let response = ActionResponse::QueryGaps(responses::QueryGaps {
+            gaps: vec![(10, 20)],
+        });
+
+        let bytes = ResponseEncoding::Json
+            .encode(response.bytes().unwrap())
+            .unwrap();
+        let decoded: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        assert_eq!(decoded, envelope_json());
+    }
+
+    #[test]
+    fn envelope_msgpack_roundtrip() {
+        let response = ActionResponse::QueryGaps(responses::QueryGaps {
+            gaps: vec![(10, 20)],
+        });
+
+        let bytes = ResponseEncoding::MessagePack
+            .encode(response.bytes().unwrap())
+            .unwrap();
+        let decoded: serde_json::Value = rmp_serde::from_slice(&bytes).unwrap();
+
+        assert_eq!(decoded, envelope_json());
+
+        // Responses without body only carry the version and the action name
+        let bytes = ResponseEncoding::MessagePack
+            .encode(ActionResponse::Empty.bytes().unwrap())
+            .unwrap();
+        let decoded: serde_json::Value = rmp_serde::from_slice(&bytes).unwrap();
+
+        assert_eq!(
+            decoded,
+            serde_json::json!({ "version": RESPONSE_ENVELOPE_VERSION, "action": "empty" })
+        );
+    }
+
+    #[test]
+    fn encoding_from_header() {
+        assert_eq!(
+            ResponseEncoding::from_header(None).unwrap(),
+            ResponseEncoding::Json
+        );
+        assert_eq!(
+            ResponseEncoding::from_header(Some("msgpack")).unwrap(),
+            ResponseEncoding::MessagePack
+        );
+        assert!(ResponseEncoding::from_header(Some("xml")).is_err());
+    }
+}
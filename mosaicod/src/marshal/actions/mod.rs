@@ -1,7 +1,12 @@
 mod core;
 pub use core::*;
 
+mod envelope;
+pub use envelope::*;
+
 pub mod requests;
 
 pub mod responses;
 pub use responses::*;
+
+pub mod schemas;
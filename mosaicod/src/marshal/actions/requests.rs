@@ -1,16 +1,26 @@
 use serde::Deserialize;
 
-use crate::rw;
+use crate::{rw, types};
 
 use super::ActionError;
 
 #[derive(Deserialize, Debug)]
 pub struct Empty {}
 
+/// Optional idempotency key that can be attached to the body of any mutating action
+#[derive(Deserialize, Debug)]
+pub struct IdempotencyKey {
+    pub idempotency_key: Option<String>,
+}
+
 /// Specialized message used to create a new sequence in the platform
 #[derive(Deserialize, Debug)]
 pub struct SequenceCreate {
     pub name: String,
+    /// Time column inherited by the topics of the sequence not declaring one
+    pub time_column: Option<String>,
+    /// Time precision inherited by the topics of the sequence not declaring one
+    pub time_precision: Option<types::TimePrecision>,
     user_metadata: serde_json::Value,
 }
 
@@ -25,8 +35,34 @@ impl SequenceCreate {
 pub struct TopicCreate {
     pub name: String,
     pub sequence_key: String,
-    pub serialization_format: rw::Format,
+    /// If not provided the server default format is used
+    pub serialization_format: Option<rw::Format>,
     pub ontology_tag: String,
+    /// If true uploads with a schema different from the established one are rejected
+    #[serde(default)]
+    pub schema_locked: bool,
+    /// Unit of the data timestamps, if not provided the sequence default is used, falling back
+    /// to nanoseconds
+    pub time_precision: Option<types::TimePrecision>,
+    /// Name of the column holding the data timestamps, if not provided the sequence default is
+    /// used, falling back to `timestamp_ns`
+    pub time_column: Option<String>,
+    /// Naming scheme of the topic datafiles, if not provided datafiles are named after the
+    /// chunk index only
+    #[serde(default)]
+    pub chunk_naming: types::ChunkNaming,
+    /// Ordering enforced on the uploaded rows, if not provided rows can be in any order
+    #[serde(default)]
+    pub ordering: types::RowOrdering,
+    /// If true the datafiles include the Parquet page index of every column
+    #[serde(default)]
+    pub page_index: bool,
+    /// Quality level of the compression codec of the datafiles, if not provided the format
+    /// default level is used
+    pub compression_level: Option<i32>,
+    /// Schema of the topic data, if not provided the schema is established by the first
+    /// upload
+    pub schema: Option<crate::marshal::JsonArrowSchema>,
 
     user_metadata: serde_json::Value,
 }
@@ -37,12 +73,65 @@ impl TopicCreate {
     }
 }
 
+/// Strategy used by the server to name a resource created without an explicit name
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum NameStrategy {
+    /// Name after the creation datetime
+    Timestamp,
+    /// Name with a random UUID
+    Uuid,
+}
+
+impl From<NameStrategy> for Box<dyn types::NameStrategy> {
+    fn from(value: NameStrategy) -> Self {
+        match value {
+            NameStrategy::Timestamp => Box::new(types::TimestampNameStrategy),
+            NameStrategy::Uuid => Box::new(types::UuidNameStrategy),
+        }
+    }
+}
+
+/// Message used to create a new topic whose name is assigned by the server
+#[derive(Deserialize, Debug)]
+pub struct TopicCreateAuto {
+    /// Name of the parent sequence
+    pub sequence_name: String,
+    pub name_strategy: NameStrategy,
+    pub sequence_key: String,
+    /// If not provided the server default format is used
+    pub serialization_format: Option<rw::Format>,
+    pub ontology_tag: String,
+
+    user_metadata: serde_json::Value,
+}
+
+impl TopicCreateAuto {
+    pub fn user_metadata(&self) -> Result<String, ActionError> {
+        Ok(serde_json::to_string(&self.user_metadata)?)
+    }
+}
+
 /// Request used to locate a specific resource by name.
 #[derive(Deserialize, Debug)]
 pub struct ResourceLocator {
     pub name: String,
 }
 
+/// Rewrites the chunks of a topic so that their time ranges do not overlap
+#[derive(Deserialize, Debug)]
+pub struct TopicReorder {
+    pub name: String,
+    /// Maximum number of chunks read concurrently, if not provided the server default is used
+    pub parallelism: Option<usize>,
+}
+
+/// Asks for the system informations of multiple topics at once
+#[derive(Deserialize, Debug)]
+pub struct TopicSystemInfoBatch {
+    pub names: Vec<String>,
+}
+
 /// Request used to locate a resource deterministically,
 /// typically by combining the resource name and a unique key.
 /// Used for topics, sequences, or other keyed resources.
@@ -83,7 +172,136 @@ pub struct LayerUpdate {
 
 #[derive(Deserialize, Debug)]
 pub struct Query {
+    /// If true only the number of data rows matching the query is returned
+    #[serde(default)]
+    pub count_only: bool,
+    /// If true only the schema of the matching topics is returned, as zero-row batches
+    #[serde(default)]
+    pub schema_only: bool,
     #[serde(flatten)]
     /// Query filter used to find matches in the system
     pub query: serde_json::Value,
 }
+
+/// Asks for the validation of a query, without executing it
+#[derive(Deserialize, Debug)]
+pub struct QueryValidate {
+    #[serde(flatten)]
+    /// Query filter to validate
+    pub query: serde_json::Value,
+}
+
+/// Asks for the last `n` rows of a topic by timestamp
+#[derive(Deserialize, Debug)]
+pub struct QueryTail {
+    pub locator: String,
+    pub n: usize,
+}
+
+/// Asks for the row of a topic at a timestamp
+#[derive(Deserialize, Debug)]
+pub struct QueryAt {
+    pub locator: String,
+    /// Timestamp looked up (in nanoseconds)
+    pub timestamp: i64,
+    /// Row returned if no row is exactly at the timestamp
+    pub mode: types::LookupMode,
+}
+
+/// Asks for the statistics of each column of a topic
+#[derive(Deserialize, Debug)]
+pub struct QueryColumnStats {
+    pub locator: String,
+    /// Restricts the statistics to the `[start, end]` range of data timestamps (in
+    /// nanoseconds)
+    pub range: Option<[i64; 2]>,
+}
+
+/// Asks for the intervals of a topic where data is missing
+#[derive(Deserialize, Debug)]
+pub struct QueryGaps {
+    pub locator: String,
+    /// `[start, end]` range of data timestamps searched for gaps (in nanoseconds)
+    pub range: [i64; 2],
+    /// Expected time between consecutive samples (in nanoseconds), longer intervals
+    /// without samples are reported as gaps
+    pub expected_interval: i64,
+}
+
+/// Asks for the distinct values of a column of a topic
+#[derive(Deserialize, Debug)]
+pub struct QueryDistinct {
+    pub locator: String,
+    pub column: String,
+    /// Restricts the values to the `[start, end]` range of data timestamps (in nanoseconds)
+    pub range: Option<[i64; 2]>,
+    /// Maximum number of values returned
+    pub limit: usize,
+}
+
+/// Asks for aggregations of the columns of a topic
+#[derive(Deserialize, Debug)]
+pub struct QueryAggregate {
+    pub locator: String,
+    pub aggregates: Vec<crate::query::Aggregate>,
+    /// Restricts the aggregations to the `[start, end]` range of data timestamps (in
+    /// nanoseconds)
+    pub range: Option<[i64; 2]>,
+    /// Engine computing the aggregations, defaults to the configured one
+    pub engine: Option<crate::query::QueryEngine>,
+}
+
+/// Cancels the data stream of the query tagged by `token`
+#[derive(Deserialize, Debug)]
+pub struct QueryCancel {
+    pub token: String,
+}
+
+/// Deletes all the sequences and topics whose name starts with `prefix`
+#[derive(Deserialize, Debug)]
+pub struct DeletePrefix {
+    pub prefix: String,
+    /// If true the resources are not deleted, only the list of matching resources is returned
+    #[serde(default)]
+    pub dry_run: bool,
+    /// If true locked resources are deleted as well
+    #[serde(default)]
+    pub force: bool,
+    /// Required to accept an empty prefix, which matches every resource in the system
+    #[serde(default)]
+    pub allow_empty_prefix: bool,
+}
+
+/// Copies all the resources to the store located at `target`, e.g. `file:///data/mosaico`
+/// or `s3://bucket`
+#[derive(Deserialize, Debug)]
+pub struct SystemMigrate {
+    pub target: String,
+}
+
+/// Removes the orphaned staging files of the store not modified for `older_than_secs`,
+/// defaulting to the configured vacuum age
+#[derive(Deserialize, Debug)]
+pub struct SystemVacuum {
+    pub older_than_secs: Option<u64>,
+}
+
+/// Validates a sequence against a manifest generated by the `sequence_manifest` action, the
+/// sequence validated is the one named in the manifest
+#[derive(Deserialize, Debug)]
+pub struct SequenceVerifyManifest {
+    pub manifest: super::responses::SequenceManifest,
+}
+
+/// Clones the sequence `source` with all its topics to the new sequence `dest`
+#[derive(Deserialize, Debug)]
+pub struct SequenceClone {
+    pub source: String,
+    pub dest: String,
+}
+
+/// Asks for the JSON schema of the body expected by the action `name`
+#[derive(Deserialize, Debug)]
+pub struct DescribeAction {
+    pub name: String,
+}
@@ -1,8 +1,26 @@
 //! This module defines the formatting structure for
 //! responses.
-use serde::Serialize;
-
-use crate::types::{self, Resource};
+use arrow::array::RecordBatch;
+use arrow::datatypes::SchemaRef;
+use arrow::ipc::writer::StreamWriter;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde::{Deserialize, Serialize};
+
+use super::ActionError;
+use crate::{
+    params, query, rw,
+    types::{self, Resource},
+};
+
+/// Formats a datetime for the clients, `None` values (timestamps outside the
+/// representable range) are reported as `null`.
+fn display_datetime(datetime: Option<types::DateTime>) -> Option<String> {
+    let datetime = datetime?;
+    Some(match params::configurables().display_timezone {
+        Some(tz) => datetime.to_local_string(tz),
+        None => datetime.to_string(),
+    })
+}
 
 /// Generic response message used to provide to clients the key
 /// of a resource
@@ -19,6 +37,147 @@ impl From<types::ResourceId> for ResourceKey {
     }
 }
 
+/// Response to the creation of a topic named by the server
+#[derive(Serialize, Debug)]
+pub struct TopicCreateAuto {
+    /// Name assigned to the topic
+    pub name: String,
+    pub key: String,
+}
+
+/// Version and capabilities of the server, used by clients to negotiate features.
+#[derive(Serialize, Debug)]
+pub struct SystemInfo {
+    /// Version of the server
+    pub version: String,
+    /// Serialization formats accepted when creating topics
+    pub formats: Vec<rw::Format>,
+    /// Maximum size in bytes of a single message exchanged with the server
+    pub max_message_size_in_bytes: usize,
+    /// Optional features enabled on the server
+    pub features: Vec<String>,
+}
+
+/// JSON schema of the body expected by an action
+#[derive(Serialize, Debug)]
+pub struct DescribeAction {
+    pub name: String,
+    pub schema: serde_json::Value,
+}
+
+/// Manifest of a sequence, also accepted by the `sequence_verify_manifest` action
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SequenceManifest {
+    pub sequence: String,
+    pub topics: Vec<TopicManifest>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TopicManifest {
+    pub name: String,
+    pub serialization_format: rw::Format,
+    pub ontology_tag: String,
+    /// Informative, the datafiles listed in `chunks` are verified
+    #[serde(default)]
+    pub chunks_number: usize,
+    /// Informative, the datafiles listed in `chunks` are verified
+    #[serde(default)]
+    pub total_size_bytes: usize,
+    pub chunks: Vec<ChunkManifest>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChunkManifest {
+    pub datafile: String,
+    pub size_bytes: usize,
+    /// Hex encoded SHA-256 digest of the datafile content
+    pub checksum: String,
+}
+
+impl From<types::SequenceManifest> for SequenceManifest {
+    fn from(value: types::SequenceManifest) -> Self {
+        Self {
+            sequence: value.sequence,
+            topics: value
+                .topics
+                .into_iter()
+                .map(|topic| TopicManifest {
+                    chunks_number: topic.chunks.len(),
+                    total_size_bytes: topic.total_size_bytes(),
+                    name: topic.name,
+                    serialization_format: topic.properties.serialization_format,
+                    ontology_tag: topic.properties.ontology_tag,
+                    chunks: topic
+                        .chunks
+                        .into_iter()
+                        .map(|chunk| ChunkManifest {
+                            datafile: chunk.datafile,
+                            size_bytes: chunk.size_bytes,
+                            checksum: chunk.checksum,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<SequenceManifest> for types::SequenceManifest {
+    fn from(value: SequenceManifest) -> Self {
+        Self {
+            sequence: value.sequence,
+            topics: value
+                .topics
+                .into_iter()
+                .map(|topic| types::TopicManifest {
+                    name: topic.name,
+                    properties: types::TopicProperties::new(
+                        topic.serialization_format,
+                        topic.ontology_tag,
+                    ),
+                    chunks: topic
+                        .chunks
+                        .into_iter()
+                        .map(|chunk| types::ChunkManifest {
+                            datafile: chunk.datafile,
+                            size_bytes: chunk.size_bytes,
+                            checksum: chunk.checksum,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Outcome of the validation of a sequence against its manifest
+#[derive(Serialize, Debug)]
+pub struct ManifestValidation {
+    pub is_valid: bool,
+    /// Differences found between the sequence and the manifest
+    pub mismatches: Vec<String>,
+}
+
+impl From<Vec<String>> for ManifestValidation {
+    fn from(mismatches: Vec<String>) -> Self {
+        Self {
+            is_valid: mismatches.is_empty(),
+            mismatches,
+        }
+    }
+}
+
+/// Outcome of the clone of a sequence
+#[derive(Serialize, Debug)]
+pub struct SequenceClone {
+    /// Key of the cloned sequence
+    pub key: String,
+    /// Number of topics cloned
+    pub topics_cloned: usize,
+    /// Number of chunks copied over all the topics
+    pub chunks_cloned: usize,
+}
+
 #[derive(Serialize, Debug)]
 pub struct TopicSystemInfo {
     /// Number of chunks in the topic
@@ -29,7 +188,9 @@ pub struct TopicSystemInfo {
     /// True if topic is locked
     pub is_locked: bool,
     /// Datetime of the topic creation
-    pub created_datetime: String,
+    pub created_datetime: Option<String>,
+    /// Datetime of the last chunk written in the topic
+    pub last_write_datetime: Option<String>,
 }
 
 impl From<types::TopicSystemInfo> for TopicSystemInfo {
@@ -38,7 +199,93 @@ impl From<types::TopicSystemInfo> for TopicSystemInfo {
             chunks_number: value.chunks_number,
             total_size_bytes: value.total_size_bytes,
             is_locked: value.is_locked,
-            created_datetime: value.created_datetime.to_string(),
+            created_datetime: display_datetime(value.created_datetime),
+            last_write_datetime: display_datetime(value.last_write_datetime),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TopicSystemInfoBatch {
+    /// One entry for each requested topic, in request order
+    pub topics: Vec<TopicSystemInfoEntry>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TopicSystemInfoEntry {
+    pub name: String,
+    /// System informations of the topic, omitted if the topic does not exist
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info: Option<TopicSystemInfo>,
+    /// True if the topic does not exist
+    pub not_found: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TopicRepair {
+    /// Location of the removed chunk, omitted if the topic was not corrupted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removed_chunk: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TopicReorder {
+    /// Number of chunks written, zero if the topic did not need to be reordered
+    pub chunks_written: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TopicTier {
+    /// Number of chunks moved to the cold storage class
+    pub chunks_moved: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DeletePrefix {
+    /// Names of the resources deleted, or to be deleted if `dry_run` is set
+    pub deleted: Vec<String>,
+    pub dry_run: bool,
+}
+
+/// Outcome of the migration of the resources to another store
+#[derive(Serialize, Debug)]
+pub struct SystemMigrate {
+    /// Number of sequences migrated, along with their topics
+    pub sequences_migrated: usize,
+    /// Number of objects copied to the target store
+    pub objects_copied: usize,
+    /// Number of objects skipped since already present in the target store
+    pub objects_skipped: usize,
+}
+
+/// Outcome of the vacuum of the store
+#[derive(Serialize, Debug)]
+pub struct SystemVacuum {
+    /// Number of orphaned staging files removed
+    pub files_removed: usize,
+    /// Total size in bytes of the removed files
+    pub bytes_reclaimed: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TopicConsistencyReport {
+    /// True if the repository and the store agree on the topic chunks
+    pub is_consistent: bool,
+    /// Chunks registered in the repository but missing from the store
+    pub missing_chunks: Vec<String>,
+    /// Datafiles found in the store but not registered in the repository
+    pub extra_chunks: Vec<String>,
+    /// Chunks whose size in the store differs from the registered size
+    pub size_mismatched_chunks: Vec<String>,
+}
+
+impl From<types::TopicConsistencyReport> for TopicConsistencyReport {
+    fn from(value: types::TopicConsistencyReport) -> Self {
+        Self {
+            is_consistent: value.is_consistent(),
+            missing_chunks: value.missing_chunks,
+            extra_chunks: value.extra_chunks,
+            size_mismatched_chunks: value.size_mismatched_chunks,
         }
     }
 }
@@ -51,7 +298,7 @@ pub struct SequenceSystemInfo {
     /// True if sequence is locked
     pub is_locked: bool,
     /// Datetime of the sequence creation
-    pub created_datetime: String,
+    pub created_datetime: Option<String>,
 }
 
 impl From<types::SequenceSystemInfo> for SequenceSystemInfo {
@@ -59,7 +306,7 @@ impl From<types::SequenceSystemInfo> for SequenceSystemInfo {
         Self {
             total_size_bytes: value.total_size_bytes,
             is_locked: value.is_locked,
-            created_datetime: value.created_datetime.to_string(),
+            created_datetime: display_datetime(value.created_datetime),
         }
     }
 }
@@ -73,7 +320,7 @@ pub struct ResponseNotifyItem {
     pub name: String,
     pub notify_type: String,
     pub msg: String,
-    pub created_datetime: String,
+    pub created_datetime: Option<String>,
 }
 
 impl From<types::Notify> for ResponseNotifyItem {
@@ -82,7 +329,7 @@ impl From<types::Notify> for ResponseNotifyItem {
             name: value.target.name().to_string(),
             notify_type: value.notify_type.to_string(),
             msg: value.msg.unwrap_or_default(),
-            created_datetime: value.created_at.to_string(),
+            created_datetime: display_datetime(value.created_at),
         }
     }
 }
@@ -141,6 +388,249 @@ pub struct Query {
     pub items: Vec<ResponseQueryItem>,
 }
 
+/// Number of data rows matching a query
+#[derive(Serialize, Debug)]
+pub struct QueryCount {
+    pub row_count: usize,
+}
+
+/// Last rows of a topic
+#[derive(Serialize, Debug)]
+pub struct QueryTail {
+    pub row_count: usize,
+    /// Rows encoded as a base64 Arrow IPC stream, [`None`] if the topic has no data
+    pub data: Option<String>,
+}
+
+impl QueryTail {
+    pub fn try_from_batch(batch: Option<RecordBatch>) -> Result<Self, ActionError> {
+        let Some(batch) = batch else {
+            return Ok(Self {
+                row_count: 0,
+                data: None,
+            });
+        };
+
+        Ok(Self {
+            row_count: batch.num_rows(),
+            data: Some(encode_batch(&batch)?),
+        })
+    }
+}
+
+/// Row of a topic at a timestamp
+#[derive(Serialize, Debug)]
+pub struct QueryAt {
+    pub row_count: usize,
+    /// Row encoded as a base64 Arrow IPC stream, [`None`] if no row matches
+    pub data: Option<String>,
+}
+
+impl QueryAt {
+    pub fn try_from_batch(batch: Option<RecordBatch>) -> Result<Self, ActionError> {
+        let Some(batch) = batch else {
+            return Ok(Self {
+                row_count: 0,
+                data: None,
+            });
+        };
+
+        Ok(Self {
+            row_count: batch.num_rows(),
+            data: Some(encode_batch(&batch)?),
+        })
+    }
+}
+
+/// Statistics of each column of a topic
+#[derive(Serialize, Debug)]
+pub struct QueryColumnStats {
+    pub columns: Vec<ColumnStats>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ColumnStats {
+    pub column: String,
+    pub min: Option<serde_json::Value>,
+    pub max: Option<serde_json::Value>,
+    pub null_count: Option<u64>,
+    pub distinct_estimate: Option<u64>,
+}
+
+impl From<rw::ColumnProfile> for ColumnStats {
+    fn from(value: rw::ColumnProfile) -> Self {
+        Self {
+            column: value.column,
+            min: value.min.map(query_value_to_json),
+            max: value.max.map(query_value_to_json),
+            null_count: value.null_count,
+            distinct_estimate: value.distinct_estimate,
+        }
+    }
+}
+
+impl From<Vec<rw::ColumnProfile>> for QueryColumnStats {
+    fn from(value: Vec<rw::ColumnProfile>) -> Self {
+        Self {
+            columns: value.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// Intervals of a topic where data is missing, each gap is reported as the `[start, end]`
+/// range of data timestamps surrounding it
+#[derive(Serialize, Debug)]
+pub struct QueryGaps {
+    pub gaps: Vec<(i64, i64)>,
+}
+
+impl From<Vec<types::TimestampRange>> for QueryGaps {
+    fn from(value: Vec<types::TimestampRange>) -> Self {
+        Self {
+            gaps: value
+                .into_iter()
+                .map(|gap| (gap.start.into(), gap.end.into()))
+                .collect(),
+        }
+    }
+}
+
+/// Distinct values of a column of a topic
+#[derive(Serialize, Debug)]
+pub struct QueryDistinct {
+    pub values: Vec<serde_json::Value>,
+    /// True if the column holds more distinct values than the requested limit
+    pub truncated: bool,
+}
+
+impl From<(Vec<query::Value>, bool)> for QueryDistinct {
+    fn from((values, truncated): (Vec<query::Value>, bool)) -> Self {
+        Self {
+            values: values.into_iter().map(query_value_to_json).collect(),
+            truncated,
+        }
+    }
+}
+
+/// Result of the aggregations of the columns of a topic
+#[derive(Serialize, Debug)]
+pub struct QueryAggregate {
+    /// Result of each aggregation in request order, `null` if not defined (e.g. the minimum
+    /// of a column without values)
+    pub values: Vec<Option<serde_json::Value>>,
+    /// Engine that computed the aggregations
+    pub engine: query::QueryEngine,
+}
+
+impl From<(Vec<Option<query::Value>>, query::QueryEngine)> for QueryAggregate {
+    fn from((values, engine): (Vec<Option<query::Value>>, query::QueryEngine)) -> Self {
+        Self {
+            values: values
+                .into_iter()
+                .map(|value| value.map(query_value_to_json))
+                .collect(),
+            engine,
+        }
+    }
+}
+
+fn query_value_to_json(value: query::Value) -> serde_json::Value {
+    match value {
+        query::Value::Integer(v) => v.into(),
+        query::Value::Float(v) => v.into(),
+        query::Value::Text(v) => v.into(),
+        query::Value::Boolean(v) => v.into(),
+    }
+}
+
+/// Outcome of the validation of a query
+#[derive(Serialize, Debug)]
+pub struct QueryValidate {
+    pub valid: bool,
+    /// Problems found, empty if the query is valid
+    pub errors: Vec<QueryValidationError>,
+    /// Schema of each topic selected by the sequence and topic filters, empty if the query is
+    /// invalid
+    pub schemas: Vec<QuerySchemaItem>,
+    /// Number of chunks that would be searched, omitted if the query is invalid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk_count: Option<usize>,
+}
+
+impl QueryValidate {
+    pub fn invalid(errors: Vec<QueryValidationError>) -> Self {
+        Self {
+            valid: false,
+            errors,
+            schemas: Vec::new(),
+            chunk_count: None,
+        }
+    }
+}
+
+/// Problem found validating a query
+#[derive(Serialize, Debug)]
+pub struct QueryValidationError {
+    /// Field of the query the problem refers to, omitted if the problem is not bound to a
+    /// field
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl From<query::Error> for QueryValidationError {
+    fn from(value: query::Error) -> Self {
+        let field = match &value {
+            query::Error::OpError { field, .. } | query::Error::BadField { field } => {
+                Some(field.clone())
+            }
+            _ => None,
+        };
+
+        Self {
+            field,
+            message: value.to_string(),
+        }
+    }
+}
+
+/// Schemas of the topics matching a query
+#[derive(Serialize, Debug)]
+pub struct QuerySchema {
+    pub items: Vec<QuerySchemaItem>,
+}
+
+/// Schema of a topic, encoded as a base64 Arrow IPC stream holding a zero-row batch
+#[derive(Serialize, Debug)]
+pub struct QuerySchemaItem {
+    pub locator: String,
+    /// [`None`] if the topic has no data
+    pub data: Option<String>,
+}
+
+impl QuerySchemaItem {
+    pub fn try_new(locator: String, schema: Option<SchemaRef>) -> Result<Self, ActionError> {
+        let data = schema
+            .map(|schema| encode_batch(&RecordBatch::new_empty(schema)))
+            .transpose()?;
+
+        Ok(Self { locator, data })
+    }
+}
+
+/// Encodes `batch` as a base64 Arrow IPC stream
+fn encode_batch(batch: &RecordBatch) -> Result<String, ActionError> {
+    let mut buffer = Vec::new();
+    let mut writer = StreamWriter::try_new(&mut buffer, &batch.schema())
+        .map_err(|e| ActionError::ResponseSerializationError(e.to_string()))?;
+    writer
+        .write(batch)
+        .and_then(|_| writer.finish())
+        .map_err(|e| ActionError::ResponseSerializationError(e.to_string()))?;
+
+    Ok(BASE64_STANDARD.encode(buffer))
+}
+
 /// Holds topic data: locator and optional timestamp.
 #[derive(Serialize, Debug)]
 pub struct ResponseQueryItemTopic {
@@ -148,6 +638,10 @@ pub struct ResponseQueryItemTopic {
     /// Timestamp range will be omitted from the output if it is None.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timestamp_range: Option<(i64, i64)>,
+    /// Time range searched by the query clamped to the topic data, omitted if the query
+    /// has no time range.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub searched_range: Option<(i64, i64)>,
 }
 
 impl From<types::TopicResourceLocator> for ResponseQueryItemTopic {
@@ -157,6 +651,7 @@ impl From<types::TopicResourceLocator> for ResponseQueryItemTopic {
             timestamp_range: value
                 .timestamp_range
                 .map(|e| (e.start.into(), e.end.into())),
+            searched_range: value.searched_range.map(|e| (e.start.into(), e.end.into())),
         }
     }
 }
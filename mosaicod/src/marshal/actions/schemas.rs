@@ -0,0 +1,402 @@
+//! Registry of the JSON schemas describing the body expected by each action.
+//!
+//! The schemas are static data mirroring the structs defined in [`super::requests`] and are
+//! meant to let generic tooling discover how to build the actions body. Any change to a request
+//! struct should be reflected here.
+
+use serde_json::{Value, json};
+
+/// Returns the JSON schema of the body expected by `action`, [`None`] if the action is unknown.
+pub fn describe(action: &str) -> Option<Value> {
+    let schema = match action {
+        "sequence_create" => object(
+            json!({
+                "name": string(),
+                "time_column": string(),
+                "time_precision": time_precisions(),
+                "user_metadata": { "type": "object" },
+            }),
+            &["name", "user_metadata"],
+        ),
+        "sequence_delete"
+        | "sequence_abort"
+        | "sequence_finalize"
+        | "sequence_system_info"
+        | "sequence_notify_list"
+        | "sequence_notify_purge"
+        | "sequence_manifest"
+        | "topic_delete"
+        | "topic_system_info"
+        | "topic_notify_list"
+        | "topic_notify_purge"
+        | "topic_repair"
+        | "topic_consistency_check"
+        | "topic_tier" => resource_locator(),
+        "sequence_notify_create" | "topic_notify_create" => object(
+            json!({
+                "name": string(),
+                "notify_type": string(),
+                "msg": string(),
+            }),
+            &["name", "notify_type", "msg"],
+        ),
+
+        "topic_create" => object(
+            json!({
+                "name": string(),
+                "sequence_key": string(),
+                "serialization_format": formats(),
+                "ontology_tag": string(),
+                "schema_locked": boolean(),
+                "time_precision": time_precisions(),
+                "time_column": string(),
+                "chunk_naming": { "type": "string", "enum": ["index", "time_range"] },
+                "ordering": { "type": "string", "enum": ["unordered", "time_ascending"] },
+                "page_index": boolean(),
+                "compression_level": { "type": "integer" },
+                "schema": string(),
+                "user_metadata": { "type": "object" },
+            }),
+            &["name", "sequence_key", "ontology_tag", "user_metadata"],
+        ),
+        "topic_create_auto" => object(
+            json!({
+                "sequence_name": string(),
+                "name_strategy": { "type": "string", "enum": ["timestamp", "uuid"] },
+                "sequence_key": string(),
+                "serialization_format": formats(),
+                "ontology_tag": string(),
+                "user_metadata": { "type": "object" },
+            }),
+            &[
+                "sequence_name",
+                "name_strategy",
+                "sequence_key",
+                "ontology_tag",
+                "user_metadata",
+            ],
+        ),
+        "topic_system_info_batch" => object(
+            json!({
+                "names": { "type": "array", "items": string() },
+            }),
+            &["names"],
+        ),
+        "topic_reorder" => object(
+            json!({
+                "name": string(),
+                "parallelism": integer(),
+            }),
+            &["name"],
+        ),
+
+        "layer_create" => object(
+            json!({
+                "name": string(),
+                "description": string(),
+            }),
+            &["name", "description"],
+        ),
+        "layer_delete" => object(json!({ "name": string() }), &["name"]),
+        "layer_update" => object(
+            json!({
+                "prev_name": string(),
+                "curr_name": string(),
+                "curr_description": string(),
+            }),
+            &["prev_name", "curr_name", "curr_description"],
+        ),
+        "layer_list" | "system_info" => object(json!({}), &[]),
+
+        "query" => query(),
+        "query_validate" => object(query_filter(), &[]),
+        "query_tail" => object(
+            json!({
+                "locator": string(),
+                "n": integer(),
+            }),
+            &["locator", "n"],
+        ),
+        "query_at" => object(
+            json!({
+                "locator": string(),
+                "timestamp": { "type": "integer" },
+                "mode": {
+                    "type": "string",
+                    "enum": ["exact", "nearest", "before", "after"],
+                },
+            }),
+            &["locator", "timestamp", "mode"],
+        ),
+        "query_column_stats" => object(
+            json!({
+                "locator": string(),
+                "range": {
+                    "type": "array",
+                    "items": { "type": "integer" },
+                    "minItems": 2,
+                    "maxItems": 2,
+                },
+            }),
+            &["locator"],
+        ),
+        "query_gaps" => object(
+            json!({
+                "locator": string(),
+                "range": {
+                    "type": "array",
+                    "items": { "type": "integer" },
+                    "minItems": 2,
+                    "maxItems": 2,
+                },
+                "expected_interval": integer(),
+            }),
+            &["locator", "range", "expected_interval"],
+        ),
+        "query_distinct" => object(
+            json!({
+                "locator": string(),
+                "column": string(),
+                "range": {
+                    "type": "array",
+                    "items": { "type": "integer" },
+                    "minItems": 2,
+                    "maxItems": 2,
+                },
+                "limit": integer(),
+            }),
+            &["locator", "column", "limit"],
+        ),
+        "query_cancel" => object(json!({ "token": string() }), &["token"]),
+
+        "delete_prefix" => object(
+            json!({
+                "prefix": string(),
+                "dry_run": boolean(),
+                "force": boolean(),
+                "allow_empty_prefix": boolean(),
+            }),
+            &["prefix"],
+        ),
+        "query_aggregate" => object(
+            json!({
+                "locator": string(),
+                "aggregates": {
+                    "type": "array",
+                    "items": object(
+                        json!({
+                            "function": {
+                                "type": "string",
+                                "enum": ["count", "min", "max", "sum", "mean"],
+                            },
+                            "column": string(),
+                        }),
+                        &["function", "column"],
+                    ),
+                },
+                "range": {
+                    "type": "array",
+                    "items": { "type": "integer" },
+                    "minItems": 2,
+                    "maxItems": 2,
+                },
+                "engine": {
+                    "type": "string",
+                    "enum": ["auto", "in_process", "datafusion"],
+                },
+            }),
+            &["locator", "aggregates"],
+        ),
+        "system_migrate" => object(json!({ "target": string() }), &["target"]),
+        "system_vacuum" => object(json!({ "older_than_secs": integer() }), &[]),
+        "describe_action" => object(json!({ "name": string() }), &["name"]),
+        "sequence_verify_manifest" => object(json!({ "manifest": manifest() }), &["manifest"]),
+        "sequence_clone" => object(
+            json!({
+                "source": string(),
+                "dest": string(),
+            }),
+            &["source", "dest"],
+        ),
+
+        _ => return None,
+    };
+
+    Some(schema)
+}
+
+fn object(properties: Value, required: &[&str]) -> Value {
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+fn formats() -> Value {
+    json!({ "type": "string", "enum": ["default", "ragged", "image"] })
+}
+
+fn time_precisions() -> Value {
+    json!({ "type": "string", "enum": ["seconds", "millis", "micros", "nanos"] })
+}
+
+fn manifest() -> Value {
+    let chunk = object(
+        json!({
+            "datafile": string(),
+            "size_bytes": integer(),
+            "checksum": string(),
+        }),
+        &["datafile", "size_bytes", "checksum"],
+    );
+    let topic = object(
+        json!({
+            "name": string(),
+            "serialization_format": formats(),
+            "ontology_tag": string(),
+            "chunks_number": integer(),
+            "total_size_bytes": integer(),
+            "chunks": { "type": "array", "items": chunk },
+        }),
+        &["name", "serialization_format", "ontology_tag", "chunks"],
+    );
+    object(
+        json!({
+            "sequence": string(),
+            "topics": { "type": "array", "items": topic },
+        }),
+        &["sequence", "topics"],
+    )
+}
+
+fn resource_locator() -> Value {
+    object(json!({ "name": string() }), &["name"])
+}
+
+/// Filter on a field, an object with a single operator key (e.g. `{"$eq": 1}`)
+fn op() -> Value {
+    json!({
+        "type": "object",
+        "minProperties": 1,
+        "maxProperties": 1,
+        "propertyNames": {
+            "enum": [
+                "$eq", "$neq", "$leq", "$geq", "$lt", "$gt",
+                "$ex", "$nex", "$between", "$in", "$match",
+            ],
+        },
+    })
+}
+
+fn user_metadata_filter() -> Value {
+    json!({ "type": "object", "additionalProperties": op() })
+}
+
+fn query() -> Value {
+    let mut properties = query_filter();
+    properties["count_only"] = boolean();
+    properties["schema_only"] = boolean();
+    object(properties, &[])
+}
+
+/// Properties of the sequence, topic and ontology sections of a query
+fn query_filter() -> Value {
+    json!({
+        "sequence": object(
+            json!({
+                "name": op(),
+                "created_timestamp": op(),
+                "user_metadata": user_metadata_filter(),
+            }),
+            &[],
+        ),
+        "topic": object(
+            json!({
+                "name": op(),
+                "created_timestamp": op(),
+                "ontology_tag": op(),
+                "serialization_format": op(),
+                "user_metadata": user_metadata_filter(),
+            }),
+            &[],
+        ),
+        // Ontology fields are arbitrary `<tag>.<field>` names, hence additional properties
+        "ontology": {
+            "type": "object",
+            "properties": {
+                "include_timestamp_range": boolean(),
+                "range": {
+                    "oneOf": [
+                        {
+                            "type": "array",
+                            "items": { "type": "integer" },
+                            "minItems": 2,
+                            "maxItems": 2,
+                        },
+                        { "const": "all" },
+                    ],
+                },
+            },
+            "additionalProperties": op(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rw, types};
+
+    #[test]
+    fn describe_query() {
+        let schema = describe("query").unwrap();
+
+        let ontology = &schema["properties"]["ontology"]["properties"];
+        assert!(ontology.get("range").is_some());
+        assert!(schema["properties"].get("count_only").is_some());
+    }
+
+    #[test]
+    fn describe_unknown_action() {
+        assert!(describe("not_an_action").is_none());
+    }
+
+    #[test]
+    fn time_precisions_match_compiled_in() {
+        let schema = time_precisions();
+        for precision in types::TimePrecision::ALL {
+            assert!(
+                schema["enum"]
+                    .as_array()
+                    .unwrap()
+                    .contains(&json!(precision.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn formats_match_compiled_in() {
+        let schema = formats();
+        for format in rw::Format::ALL {
+            assert!(
+                schema["enum"]
+                    .as_array()
+                    .unwrap()
+                    .contains(&json!(format.to_string()))
+            );
+        }
+    }
+}
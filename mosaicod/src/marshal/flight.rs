@@ -1,16 +1,46 @@
-use crate::types;
-use serde::Deserialize;
+use crate::{query, rw, types};
+use serde::{Deserialize, Serialize};
 
 /// Non-exported type for deserialize [`GetFlightInfoCmd`]
 #[derive(Deserialize)]
 struct GetFlightInfoCmd {
     resource_locator: String,
+    filter: Option<serde_json::Value>,
+    #[serde(default)]
+    provenance: Vec<String>,
+    sample: Option<serde_json::Value>,
+    #[serde(default)]
+    projection: Vec<String>,
+    order_by: Option<serde_json::Value>,
+    #[serde(default)]
+    dictionary_reuse: bool,
+    result_batch_rows: Option<usize>,
+    min_batch_rows: Option<usize>,
+    #[serde(default)]
+    partial_on_timeout: bool,
+    request_token: Option<String>,
+    #[serde(default)]
+    scan_stats: bool,
+    #[serde(default)]
+    chunk_attribution: bool,
 }
 
 impl From<GetFlightInfoCmd> for types::flight::GetFlightInfoCmd {
     fn from(value: GetFlightInfoCmd) -> Self {
         types::flight::GetFlightInfoCmd {
             resource_locator: value.resource_locator,
+            filter: value.filter,
+            provenance: value.provenance,
+            sample: value.sample,
+            projection: value.projection,
+            order_by: value.order_by,
+            dictionary_reuse: value.dictionary_reuse,
+            result_batch_rows: value.result_batch_rows,
+            min_batch_rows: value.min_batch_rows,
+            partial_on_timeout: value.partial_on_timeout,
+            request_token: value.request_token,
+            scan_stats: value.scan_stats,
+            chunk_attribution: value.chunk_attribution,
         }
     }
 }
@@ -26,6 +56,23 @@ pub fn get_flight_info_cmd(v: &[u8]) -> Result<types::flight::GetFlightInfoCmd,
 struct DoPutCmd {
     resource_locator: String,
     key: String,
+    #[serde(default)]
+    line_protocol: bool,
+    #[serde(default)]
+    deduplicate: bool,
+    #[serde(default)]
+    write_mode: WriteMode,
+    #[serde(default)]
+    force: bool,
+}
+
+/// Non-exported type used to deserialize [`types::flight::WriteMode`]
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum WriteMode {
+    #[default]
+    Append,
+    Overwrite,
 }
 
 impl From<DoPutCmd> for types::flight::DoPutCmd {
@@ -33,6 +80,13 @@ impl From<DoPutCmd> for types::flight::DoPutCmd {
         types::flight::DoPutCmd {
             resource_locator: value.resource_locator,
             key: value.key,
+            line_protocol: value.line_protocol,
+            deduplicate: value.deduplicate,
+            write_mode: match value.write_mode {
+                WriteMode::Append => types::flight::WriteMode::Append,
+                WriteMode::Overwrite => types::flight::WriteMode::Overwrite,
+            },
+            force: value.force,
         }
     }
 }
@@ -42,3 +96,449 @@ pub fn do_put_cmd(v: &[u8]) -> Result<types::flight::DoPutCmd, super::Error> {
         .map_err(|e| super::Error::DeserializationError(e.to_string()))
         .map(|v| v.into())
 }
+
+/// Non-exported type used to (de)serialize [`types::flight::DoGetTicket`]
+#[derive(Serialize, Deserialize)]
+struct DoGetTicket {
+    resource_locator: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filter: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    provenance: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sample: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    projection: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order_by: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    dictionary_reuse: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result_batch_rows: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_batch_rows: Option<usize>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    partial_on_timeout: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_token: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    scan_stats: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    chunk_attribution: bool,
+}
+
+/// Non-exported type used to deserialize [`query::OrderBy`]
+#[derive(Deserialize)]
+struct OrderBy {
+    column: String,
+    #[serde(default)]
+    order: SortOrder,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl From<OrderBy> for query::OrderBy {
+    fn from(value: OrderBy) -> Self {
+        match value.order {
+            SortOrder::Asc => query::OrderBy::asc(value.column),
+            SortOrder::Desc => query::OrderBy::desc(value.column),
+        }
+    }
+}
+
+/// Non-exported type used to deserialize [`query::Sample`]
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Sample {
+    EveryNth(usize),
+    TargetCount(usize),
+}
+
+impl TryFrom<Sample> for query::Sample {
+    type Error = super::Error;
+
+    fn try_from(value: Sample) -> Result<Self, Self::Error> {
+        let (sample, n) = match value {
+            Sample::EveryNth(n) => (query::Sample::EveryNth(n), n),
+            Sample::TargetCount(n) => (query::Sample::TargetCount(n), n),
+        };
+
+        if n == 0 {
+            return Err(super::Error::DeserializationError(
+                "sample size must be greater than zero".to_owned(),
+            ));
+        }
+
+        Ok(sample)
+    }
+}
+
+impl TryFrom<DoGetTicket> for types::flight::DoGetTicket {
+    type Error = super::Error;
+
+    fn try_from(value: DoGetTicket) -> Result<Self, Self::Error> {
+        if value.result_batch_rows == Some(0) {
+            return Err(super::Error::DeserializationError(
+                "result batch rows must be greater than zero".to_owned(),
+            ));
+        }
+
+        if value.min_batch_rows == Some(0) {
+            return Err(super::Error::DeserializationError(
+                "min batch rows must be greater than zero".to_owned(),
+            ));
+        }
+
+        // Re-batching would merge the rows of different chunks in the same batch
+        if value.chunk_attribution
+            && (value.result_batch_rows.is_some() || value.min_batch_rows.is_some())
+        {
+            return Err(super::Error::DeserializationError(
+                "chunk attribution can't be combined with result or min batch rows".to_owned(),
+            ));
+        }
+
+        // Sorting interleaves the rows of different chunks, while sampling strides across chunk
+        // boundaries
+        if value.chunk_attribution && (value.order_by.is_some() || value.sample.is_some()) {
+            return Err(super::Error::DeserializationError(
+                "chunk attribution can't be combined with order by or sampling".to_owned(),
+            ));
+        }
+
+        Ok(types::flight::DoGetTicket {
+            resource_locator: value.resource_locator,
+            filter: value
+                .filter
+                .map(super::row_filter_from_serde_value)
+                .transpose()?,
+            provenance: value
+                .provenance
+                .iter()
+                .map(|column| column.parse())
+                .collect::<Result<_, query::Error>>()
+                .map_err(|e| super::Error::DeserializationError(e.to_string()))?,
+            sample: value
+                .sample
+                .map(|v| {
+                    serde_json::from_value::<Sample>(v)
+                        .map_err(|e| super::Error::DeserializationError(e.to_string()))?
+                        .try_into()
+                })
+                .transpose()?,
+            projection: value.projection,
+            order_by: value
+                .order_by
+                .map(|v| {
+                    serde_json::from_value::<OrderBy>(v)
+                        .map(Into::into)
+                        .map_err(|e| super::Error::DeserializationError(e.to_string()))
+                })
+                .transpose()?,
+            dictionary_reuse: value.dictionary_reuse,
+            result_batch_rows: value.result_batch_rows,
+            min_batch_rows: value.min_batch_rows,
+            partial_on_timeout: value.partial_on_timeout,
+            request_token: value.request_token,
+            scan_stats: value.scan_stats,
+            chunk_attribution: value.chunk_attribution,
+        })
+    }
+}
+
+/// Convert a raw ticket into a [`types::flight::DoGetTicket`].
+///
+/// Tickets without data options contain only the resource locator as plain text.
+pub fn do_get_ticket(v: &[u8]) -> Result<types::flight::DoGetTicket, super::Error> {
+    let raw =
+        std::str::from_utf8(v).map_err(|e| super::Error::DeserializationError(e.to_string()))?;
+
+    if !raw.trim_start().starts_with('{') {
+        return Ok(types::flight::DoGetTicket {
+            resource_locator: raw.to_owned(),
+            filter: None,
+            provenance: Vec::new(),
+            sample: None,
+            projection: Vec::new(),
+            order_by: None,
+            dictionary_reuse: false,
+            result_batch_rows: None,
+            min_batch_rows: None,
+            partial_on_timeout: false,
+            request_token: None,
+            scan_stats: false,
+            chunk_attribution: false,
+        });
+    }
+
+    serde_json::from_str::<DoGetTicket>(raw)
+        .map_err(|e| super::Error::DeserializationError(e.to_string()))?
+        .try_into()
+}
+
+/// Builds a raw ticket for the provided resource, the data options found in `cmd`
+/// (row filter, provenance columns, projection, ...) are embedded in the ticket and applied when
+/// the data is retrieved.
+pub fn do_get_ticket_to_bytes(
+    resource_locator: String,
+    cmd: &types::flight::GetFlightInfoCmd,
+) -> Result<Vec<u8>, super::Error> {
+    if cmd.filter.is_none()
+        && cmd.provenance.is_empty()
+        && cmd.sample.is_none()
+        && cmd.projection.is_empty()
+        && cmd.order_by.is_none()
+        && !cmd.dictionary_reuse
+        && cmd.result_batch_rows.is_none()
+        && cmd.min_batch_rows.is_none()
+        && !cmd.partial_on_timeout
+        && cmd.request_token.is_none()
+        && !cmd.scan_stats
+        && !cmd.chunk_attribution
+    {
+        return Ok(resource_locator.into_bytes());
+    }
+
+    let ticket = DoGetTicket {
+        resource_locator,
+        filter: cmd.filter.clone(),
+        provenance: cmd.provenance.clone(),
+        sample: cmd.sample.clone(),
+        projection: cmd.projection.clone(),
+        order_by: cmd.order_by.clone(),
+        dictionary_reuse: cmd.dictionary_reuse,
+        result_batch_rows: cmd.result_batch_rows,
+        min_batch_rows: cmd.min_batch_rows,
+        partial_on_timeout: cmd.partial_on_timeout,
+        request_token: cmd.request_token.clone(),
+        scan_stats: cmd.scan_stats,
+        chunk_attribution: cmd.chunk_attribution,
+    };
+
+    let raw =
+        serde_json::to_vec(&ticket).map_err(|e| super::Error::SerializationError(e.to_string()))?;
+
+    // Ensure that the ticket can be decoded before handing it to the clients
+    types::flight::DoGetTicket::try_from(ticket)?;
+
+    Ok(raw)
+}
+
+/// Non-exported type for serialize the acknowledgement of a written chunk
+#[derive(Serialize)]
+struct DoPutAck {
+    ack: &'static str,
+}
+
+/// Builds the `app_metadata` of the [`arrow_flight::PutResult`] acknowledging a chunk
+pub fn do_put_ack_to_bytes(ack: rw::ChunkAck) -> Result<Vec<u8>, super::Error> {
+    let ack = DoPutAck {
+        ack: match ack {
+            rw::ChunkAck::Written => "written",
+            rw::ChunkAck::Deduplicated => "deduplicated",
+            rw::ChunkAck::Coalesced => "coalesced",
+        },
+    };
+
+    serde_json::to_vec(&ack).map_err(|e| super::Error::SerializationError(e.to_string()))
+}
+
+/// Non-exported type for serialize the message closing a truncated data stream
+#[derive(Serialize)]
+struct DoGetPartial {
+    partial: bool,
+    last_timestamp: Option<i64>,
+}
+
+/// Builds the `app_metadata` of the message sent after the data of a stream truncated by the
+/// query timeout. `last_timestamp` is the timestamp of the last row returned, rows following
+/// it were not reached.
+pub fn do_get_partial_to_bytes(last_timestamp: Option<i64>) -> Result<Vec<u8>, super::Error> {
+    let partial = DoGetPartial {
+        partial: true,
+        last_timestamp,
+    };
+
+    serde_json::to_vec(&partial).map_err(|e| super::Error::SerializationError(e.to_string()))
+}
+
+/// Non-exported type for serialize the statistics closing a data stream
+#[derive(Serialize)]
+struct DoGetScanStats {
+    chunks_scanned: usize,
+    bytes_read: usize,
+    rows_scanned: usize,
+    rows_returned: usize,
+    cache_hit: bool,
+}
+
+/// Non-exported type for serialize the message closing a data stream with its statistics
+#[derive(Serialize)]
+struct DoGetStats {
+    scan_stats: DoGetScanStats,
+}
+
+/// Builds the `app_metadata` of the message sent after the data of a stream, reporting the
+/// work done by the query. `rows_returned` is the number of rows streamed to the client.
+pub fn do_get_stats_to_bytes(
+    stats: query::ScanStats,
+    rows_returned: usize,
+) -> Result<Vec<u8>, super::Error> {
+    let stats = DoGetStats {
+        scan_stats: DoGetScanStats {
+            chunks_scanned: stats.chunks_scanned,
+            bytes_read: stats.bytes_read,
+            rows_scanned: stats.rows_scanned,
+            rows_returned,
+            cache_hit: stats.cache_hit,
+        },
+    };
+
+    serde_json::to_vec(&stats).map_err(|e| super::Error::SerializationError(e.to_string()))
+}
+
+/// Non-exported type for serialize the source chunk of the streamed data
+#[derive(Serialize)]
+struct DoGetChunk {
+    chunk_index: Option<usize>,
+}
+
+/// Builds the `app_metadata` of the data messages of a stream with chunk attribution,
+/// reporting the index of the chunk holding the rows of the message. `chunk_index` is
+/// [`None`] for datafiles whose name carries no index.
+pub fn do_get_chunk_to_bytes(chunk_index: Option<usize>) -> Result<Vec<u8>, super::Error> {
+    serde_json::to_vec(&DoGetChunk { chunk_index })
+        .map_err(|e| super::Error::SerializationError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(
+        filter: Option<serde_json::Value>,
+        provenance: Vec<String>,
+    ) -> types::flight::GetFlightInfoCmd {
+        types::flight::GetFlightInfoCmd {
+            resource_locator: "/seq".to_owned(),
+            filter,
+            provenance,
+            sample: None,
+            projection: Vec::new(),
+            order_by: None,
+            dictionary_reuse: false,
+            result_batch_rows: None,
+            min_batch_rows: None,
+            partial_on_timeout: false,
+            request_token: None,
+            scan_stats: false,
+            chunk_attribution: false,
+        }
+    }
+
+    #[test]
+    fn do_get_ticket_roundtrip() {
+        let plain = do_get_ticket_to_bytes("/seq/topic".to_owned(), &cmd(None, vec![])).unwrap();
+        let ticket = do_get_ticket(&plain).unwrap();
+        assert_eq!(ticket.resource_locator, "/seq/topic");
+        assert!(ticket.filter.is_none());
+        assert!(ticket.provenance.is_empty());
+
+        let filter = serde_json::json!({"sensor_id": {"$eq": "A"}});
+        let raw = do_get_ticket_to_bytes(
+            "/seq/topic".to_owned(),
+            &cmd(Some(filter), vec!["_topic".to_owned()]),
+        )
+        .unwrap();
+        let ticket = do_get_ticket(&raw).unwrap();
+        assert_eq!(ticket.resource_locator, "/seq/topic");
+        assert_eq!(ticket.filter.unwrap().group.len(), 1);
+        assert_eq!(ticket.provenance, vec![query::ProvenanceColumn::Topic]);
+    }
+
+    #[test]
+    fn do_get_ticket_sample() {
+        let mut cmd = cmd(None, vec![]);
+        cmd.sample = Some(serde_json::json!({"every_nth": 10}));
+
+        let raw = do_get_ticket_to_bytes("/seq/topic".to_owned(), &cmd).unwrap();
+        let ticket = do_get_ticket(&raw).unwrap();
+        assert_eq!(ticket.sample, Some(query::Sample::EveryNth(10)));
+
+        cmd.sample = Some(serde_json::json!({"target_count": 0}));
+        assert!(do_get_ticket_to_bytes("/seq/topic".to_owned(), &cmd).is_err());
+    }
+
+    #[test]
+    fn do_get_ticket_order_by() {
+        let mut cmd = cmd(None, vec![]);
+        cmd.order_by = Some(serde_json::json!({"column": "value", "order": "desc"}));
+
+        let raw = do_get_ticket_to_bytes("/seq/topic".to_owned(), &cmd).unwrap();
+        let ticket = do_get_ticket(&raw).unwrap();
+        assert_eq!(
+            ticket.order_by,
+            Some(query::OrderBy::desc("value".to_owned()))
+        );
+
+        // The order defaults to ascending
+        cmd.order_by = Some(serde_json::json!({"column": "value"}));
+        let raw = do_get_ticket_to_bytes("/seq/topic".to_owned(), &cmd).unwrap();
+        let ticket = do_get_ticket(&raw).unwrap();
+        assert_eq!(
+            ticket.order_by,
+            Some(query::OrderBy::asc("value".to_owned()))
+        );
+
+        cmd.order_by = Some(serde_json::json!({"column": "value", "order": "random"}));
+        assert!(do_get_ticket_to_bytes("/seq/topic".to_owned(), &cmd).is_err());
+    }
+
+    #[test]
+    fn do_get_ticket_result_batch_rows() {
+        let mut cmd = cmd(None, vec![]);
+        cmd.result_batch_rows = Some(128);
+
+        let raw = do_get_ticket_to_bytes("/seq/topic".to_owned(), &cmd).unwrap();
+        let ticket = do_get_ticket(&raw).unwrap();
+        assert_eq!(ticket.result_batch_rows, Some(128));
+
+        cmd.result_batch_rows = Some(0);
+        assert!(do_get_ticket_to_bytes("/seq/topic".to_owned(), &cmd).is_err());
+    }
+
+    #[test]
+    fn do_get_ticket_chunk_attribution() {
+        let mut cmd = cmd(None, vec![]);
+        cmd.chunk_attribution = true;
+
+        let raw = do_get_ticket_to_bytes("/seq/topic".to_owned(), &cmd).unwrap();
+        assert!(do_get_ticket(&raw).unwrap().chunk_attribution);
+
+        cmd.min_batch_rows = Some(128);
+        assert!(do_get_ticket_to_bytes("/seq/topic".to_owned(), &cmd).is_err());
+
+        cmd.min_batch_rows = None;
+        cmd.order_by = Some(serde_json::json!({"column": "value"}));
+        assert!(do_get_ticket_to_bytes("/seq/topic".to_owned(), &cmd).is_err());
+
+        cmd.order_by = None;
+        cmd.sample = Some(serde_json::json!({"every_nth": 10}));
+        assert!(do_get_ticket_to_bytes("/seq/topic".to_owned(), &cmd).is_err());
+    }
+
+    #[test]
+    fn do_get_ticket_unknown_provenance() {
+        let res = do_get_ticket_to_bytes(
+            "/seq/topic".to_owned(),
+            &cmd(None, vec!["_unknown".to_owned()]),
+        );
+        assert!(res.is_err());
+    }
+}
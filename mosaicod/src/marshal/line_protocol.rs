@@ -0,0 +1,556 @@
+//! Parser for the [InfluxDB line protocol](https://docs.influxdata.com/influxdb/v2/reference/syntax/line-protocol/).
+//!
+//! Each line has the form `measurement[,tag=value...] field=value[,field=value...] [timestamp]`
+//! and is converted in a row of the topic named after its measurement: tags and fields become
+//! columns and the timestamp (in nanoseconds) is stored in the time column.
+//!
+//! Column data types are inferred from the values: tags are always strings, fields follow
+//! the line protocol notation (`1.0` float, `1i` integer, `1u` unsigned integer, `"a"` string,
+//! `t`/`false` boolean). Integer and float values of the same field are promoted to float.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BooleanBuilder, Float64Builder, Int64Array, Int64Builder, RecordBatch, StringBuilder,
+    UInt64Builder,
+};
+use arrow::datatypes::{DataType, Field, Schema};
+
+use crate::params;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("malformed line `{line}` :: {msg}")]
+    MalformedLine { line: String, msg: String },
+    #[error("conflicting types for column `{column}` of measurement `{measurement}`")]
+    TypeConflict { measurement: String, column: String },
+    #[error("arrow error :: {0}")]
+    ArrowError(#[from] arrow::error::ArrowError),
+}
+
+/// Value of a line protocol field
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Float(f64),
+    Integer(i64),
+    UInteger(u64),
+    String(String),
+    Boolean(bool),
+}
+
+impl FieldValue {
+    fn data_type(&self) -> DataType {
+        match self {
+            Self::Float(_) => DataType::Float64,
+            Self::Integer(_) => DataType::Int64,
+            Self::UInteger(_) => DataType::UInt64,
+            Self::String(_) => DataType::Utf8,
+            Self::Boolean(_) => DataType::Boolean,
+        }
+    }
+}
+
+/// A single parsed line protocol point
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub measurement: String,
+    pub tags: Vec<(String, String)>,
+    pub fields: Vec<(String, FieldValue)>,
+    /// Timestamp in nanoseconds, if not provided the ingestion time is used
+    pub timestamp: Option<i64>,
+}
+
+/// Parses a single line, returns [`None`] for empty lines and comments.
+pub fn parse_line(line: &str) -> Result<Option<Line>, Error> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return Ok(None);
+    }
+
+    let malformed = |msg: &str| Error::MalformedLine {
+        line: trimmed.to_owned(),
+        msg: msg.to_owned(),
+    };
+
+    let sections = split_unescaped(trimmed, ' ', true);
+    let sections: Vec<&str> = sections.into_iter().filter(|s| !s.is_empty()).collect();
+    if sections.len() < 2 || sections.len() > 3 {
+        return Err(malformed(
+            "expected measurement, fields and optional timestamp",
+        ));
+    }
+
+    // Measurement and tags
+    let mut series = split_unescaped(sections[0], ',', false).into_iter();
+    let measurement = unescape(series.next().unwrap_or_default());
+    if measurement.is_empty() {
+        return Err(malformed("missing measurement"));
+    }
+
+    let mut tags = Vec::new();
+    for tag in series {
+        let (key, value) = split_key_value(tag).ok_or_else(|| malformed("bad tag"))?;
+        tags.push((unescape(key), unescape(value)));
+    }
+
+    // Fields
+    let mut fields = Vec::new();
+    for field in split_unescaped(sections[1], ',', true) {
+        let (key, value) = split_key_value(field).ok_or_else(|| malformed("bad field"))?;
+        let value = parse_field_value(value).ok_or_else(|| malformed("bad field value"))?;
+        fields.push((unescape(key), value));
+    }
+
+    let timestamp = sections
+        .get(2)
+        .map(|ts| ts.parse::<i64>())
+        .transpose()
+        .map_err(|_| malformed("bad timestamp"))?;
+
+    Ok(Some(Line {
+        measurement,
+        tags,
+        fields,
+        timestamp,
+    }))
+}
+
+/// Splits `s` on each occurrence of `sep` not escaped by a backslash and, if `quotes` is set,
+/// not enclosed in double quotes.
+fn split_unescaped(s: &str, sep: char, quotes: bool) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    let mut quoted = false;
+
+    for (idx, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' && quotes {
+            quoted = !quoted;
+        } else if c == sep && !quoted {
+            parts.push(&s[start..idx]);
+            start = idx + c.len_utf8();
+        }
+    }
+    parts.push(&s[start..]);
+
+    parts
+}
+
+/// Splits a `key=value` pair on the first unescaped `=`
+fn split_key_value(s: &str) -> Option<(&str, &str)> {
+    let mut parts = split_unescaped(s, '=', false);
+    if parts.len() < 2 {
+        return None;
+    }
+
+    // Only the first `=` separates the key, the following ones belong to the value
+    let key = parts.remove(0);
+    let value = &s[key.len() + 1..];
+
+    if key.is_empty() || value.is_empty() {
+        return None;
+    }
+    Some((key, value))
+}
+
+/// Removes the escaping backslashes from `s`
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                out.push(next);
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn parse_field_value(s: &str) -> Option<FieldValue> {
+    if let Some(inner) = s.strip_prefix('"') {
+        let inner = inner.strip_suffix('"')?;
+        return Some(FieldValue::String(unescape(inner)));
+    }
+
+    match s {
+        "t" | "T" | "true" | "True" | "TRUE" => return Some(FieldValue::Boolean(true)),
+        "f" | "F" | "false" | "False" | "FALSE" => return Some(FieldValue::Boolean(false)),
+        _ => {}
+    }
+
+    if let Some(v) = s.strip_suffix('i') {
+        return v.parse().ok().map(FieldValue::Integer);
+    }
+    if let Some(v) = s.strip_suffix('u') {
+        return v.parse().ok().map(FieldValue::UInteger);
+    }
+    s.parse().ok().map(FieldValue::Float)
+}
+
+/// Collects line protocol points grouping them by measurement, and converts them into
+/// record batches.
+///
+/// The points of a stream can be converted as they are received, the data type of each column
+/// is kept across the conversions so that the batches of a measurement have compatible
+/// schemas.
+#[derive(Debug, Default)]
+pub struct LineProtocolBatcher {
+    measurements: BTreeMap<String, Vec<Line>>,
+    /// Data types of the columns of the points already converted, per measurement
+    columns: BTreeMap<String, BTreeMap<String, DataType>>,
+}
+
+impl LineProtocolBatcher {
+    /// Parses and collects all the lines contained in `text`.
+    pub fn push_str(&mut self, text: &str) -> Result<(), Error> {
+        for line in text.lines() {
+            if let Some(line) = parse_line(line)? {
+                self.measurements
+                    .entry(line.measurement.clone())
+                    .or_default()
+                    .push(line);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if no point has been collected
+    pub fn is_empty(&self) -> bool {
+        self.measurements.is_empty()
+    }
+
+    /// Converts the collected points into a record batch for each measurement, the converted
+    /// points are removed from the batcher.
+    ///
+    /// The time column comes first, followed by tags and fields sorted by name. Columns not
+    /// provided by a point are set to null. Points without a timestamp are assigned
+    /// `default_timestamp` (in nanoseconds).
+    ///
+    /// Integer values of a column converted as float by a previous call are promoted to float,
+    /// any other change of the data type of a column is a [`Error::TypeConflict`].
+    pub fn take(&mut self, default_timestamp: i64) -> Result<Vec<(String, RecordBatch)>, Error> {
+        std::mem::take(&mut self.measurements)
+            .into_iter()
+            .map(|(measurement, lines)| {
+                let known = self.columns.entry(measurement.clone()).or_default();
+                let batch = to_record_batch(&measurement, &lines, known, default_timestamp)?;
+                Ok((measurement, batch))
+            })
+            .collect()
+    }
+}
+
+/// Infers the data type of each column of `lines`, tags are returned before fields.
+///
+/// `known` holds the data types of the columns of the points of the measurement already
+/// converted, and is updated with the inferred ones.
+fn infer_columns(
+    measurement: &str,
+    lines: &[Line],
+    known: &mut BTreeMap<String, DataType>,
+) -> Result<Vec<(String, DataType)>, Error> {
+    let conflict = |column: &str| Error::TypeConflict {
+        measurement: measurement.to_owned(),
+        column: column.to_owned(),
+    };
+
+    let mut tags = BTreeMap::new();
+    let mut fields: BTreeMap<String, DataType> = BTreeMap::new();
+
+    for line in lines {
+        for (key, _) in &line.tags {
+            tags.insert(key.clone(), DataType::Utf8);
+        }
+
+        for (key, value) in &line.fields {
+            let data_type = value.data_type();
+            match fields.get(key) {
+                None => {
+                    fields.insert(key.clone(), data_type);
+                }
+                Some(current) if *current == data_type => {}
+                Some(DataType::Int64 | DataType::Float64)
+                    if matches!(data_type, DataType::Int64 | DataType::Float64) =>
+                {
+                    fields.insert(key.clone(), DataType::Float64);
+                }
+                Some(_) => return Err(conflict(key)),
+            }
+        }
+    }
+
+    for key in tags.keys() {
+        if fields.contains_key(key) || key == params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP {
+            return Err(conflict(key));
+        }
+    }
+    if fields.contains_key(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP) {
+        return Err(conflict(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP));
+    }
+
+    // The columns already converted keep their type, integers are promoted to float
+    for key in tags.keys() {
+        if known
+            .get(key)
+            .is_some_and(|previous| *previous != DataType::Utf8)
+        {
+            return Err(conflict(key));
+        }
+    }
+    for (key, data_type) in fields.iter_mut() {
+        match known.get(key) {
+            None => {}
+            Some(previous) if previous == &*data_type => {}
+            Some(DataType::Float64) if *data_type == DataType::Int64 => {
+                *data_type = DataType::Float64;
+            }
+            Some(_) => return Err(conflict(key)),
+        }
+    }
+
+    let mut columns: Vec<(String, DataType)> = tags.into_iter().collect();
+    columns.extend(fields);
+    known.extend(columns.iter().cloned());
+    Ok(columns)
+}
+
+fn to_record_batch(
+    measurement: &str,
+    lines: &[Line],
+    known: &mut BTreeMap<String, DataType>,
+    default_timestamp: i64,
+) -> Result<RecordBatch, Error> {
+    let columns = infer_columns(measurement, lines, known)?;
+
+    let mut fields = vec![Field::new(
+        params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+        DataType::Int64,
+        false,
+    )];
+    let mut arrays: Vec<ArrayRef> = vec![Arc::new(Int64Array::from_iter_values(
+        lines
+            .iter()
+            .map(|line| line.timestamp.unwrap_or(default_timestamp)),
+    ))];
+
+    for (name, data_type) in columns {
+        let values = lines.iter().map(|line| {
+            line.tags
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| FieldValue::String(value.clone()))
+                .or_else(|| {
+                    line.fields
+                        .iter()
+                        .find(|(key, _)| *key == name)
+                        .map(|(_, value)| value.clone())
+                })
+        });
+
+        let array: ArrayRef = match data_type {
+            DataType::Float64 => {
+                let mut builder = Float64Builder::with_capacity(lines.len());
+                for value in values {
+                    builder.append_option(match value {
+                        Some(FieldValue::Float(v)) => Some(v),
+                        Some(FieldValue::Integer(v)) => Some(v as f64),
+                        _ => None,
+                    });
+                }
+                Arc::new(builder.finish())
+            }
+            DataType::Int64 => {
+                let mut builder = Int64Builder::with_capacity(lines.len());
+                for value in values {
+                    builder.append_option(match value {
+                        Some(FieldValue::Integer(v)) => Some(v),
+                        _ => None,
+                    });
+                }
+                Arc::new(builder.finish())
+            }
+            DataType::UInt64 => {
+                let mut builder = UInt64Builder::with_capacity(lines.len());
+                for value in values {
+                    builder.append_option(match value {
+                        Some(FieldValue::UInteger(v)) => Some(v),
+                        _ => None,
+                    });
+                }
+                Arc::new(builder.finish())
+            }
+            DataType::Boolean => {
+                let mut builder = BooleanBuilder::with_capacity(lines.len());
+                for value in values {
+                    builder.append_option(match value {
+                        Some(FieldValue::Boolean(v)) => Some(v),
+                        _ => None,
+                    });
+                }
+                Arc::new(builder.finish())
+            }
+            _ => {
+                let mut builder = StringBuilder::new();
+                for value in values {
+                    builder.append_option(match value {
+                        Some(FieldValue::String(v)) => Some(v),
+                        _ => None,
+                    });
+                }
+                Arc::new(builder.finish())
+            }
+        };
+
+        fields.push(Field::new(name, data_type, true));
+        arrays.push(array);
+    }
+
+    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::{Float64Type, Int64Type};
+
+    #[test]
+    fn parse_lines() {
+        let line = parse_line(r#"weather,location=us\ west,sensor=a temp=82.5,count=3i,ok=t,note="a, \"b\"" 1465839830100400200"#)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(line.measurement, "weather");
+        assert_eq!(
+            line.tags,
+            vec![
+                ("location".to_owned(), "us west".to_owned()),
+                ("sensor".to_owned(), "a".to_owned())
+            ]
+        );
+        assert_eq!(
+            line.fields,
+            vec![
+                ("temp".to_owned(), FieldValue::Float(82.5)),
+                ("count".to_owned(), FieldValue::Integer(3)),
+                ("ok".to_owned(), FieldValue::Boolean(true)),
+                (
+                    "note".to_owned(),
+                    FieldValue::String(r#"a, "b""#.to_owned())
+                ),
+            ]
+        );
+        assert_eq!(line.timestamp, Some(1465839830100400200));
+
+        let line = parse_line("cpu usage=1u").unwrap().unwrap();
+        assert_eq!(
+            line.fields,
+            vec![("usage".to_owned(), FieldValue::UInteger(1))]
+        );
+        assert_eq!(line.timestamp, None);
+
+        assert!(parse_line("# comment").unwrap().is_none());
+        assert!(parse_line("   ").unwrap().is_none());
+        assert!(parse_line("cpu").is_err());
+        assert!(parse_line("cpu usage=").is_err());
+        assert!(parse_line("cpu usage=1 notatime").is_err());
+    }
+
+    #[test]
+    fn multiple_measurements() {
+        let mut batcher = LineProtocolBatcher::default();
+        batcher
+            .push_str(
+                "weather,location=north temp=10i 100\n\
+                 cpu,host=a usage=0.5 100\n\
+                 weather,location=south temp=11.5 200\n\
+                 weather humidity=40i 300\n",
+            )
+            .unwrap();
+
+        let batches = batcher.take(999).unwrap();
+        assert_eq!(batches.len(), 2);
+
+        let (measurement, cpu) = &batches[0];
+        assert_eq!(measurement, "cpu");
+        assert_eq!(cpu.num_rows(), 1);
+
+        let (measurement, weather) = &batches[1];
+        assert_eq!(measurement, "weather");
+        assert_eq!(weather.num_rows(), 3);
+
+        let schema = weather.schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                "location",
+                "humidity",
+                "temp"
+            ]
+        );
+
+        let ts = weather.column(0).as_primitive::<Int64Type>();
+        assert_eq!(ts.values().to_vec(), vec![100, 200, 300]);
+
+        // Integer and float values are promoted to float
+        let temp = weather.column(3).as_primitive::<Float64Type>();
+        assert_eq!(temp.value(0), 10.0);
+        assert_eq!(temp.value(1), 11.5);
+        assert!(temp.is_null(2));
+
+        let humidity = weather.column(2).as_primitive::<Int64Type>();
+        assert!(humidity.is_null(0));
+        assert_eq!(humidity.value(2), 40);
+
+        let location = weather.column(1).as_string::<i32>();
+        assert_eq!(location.value(1), "south");
+        assert!(location.is_null(2));
+    }
+
+    #[test]
+    fn type_conflict() {
+        let mut batcher = LineProtocolBatcher::default();
+        batcher
+            .push_str("cpu usage=1i 100\ncpu usage=\"high\" 200")
+            .unwrap();
+
+        assert!(matches!(batcher.take(0), Err(Error::TypeConflict { .. })));
+    }
+
+    #[test]
+    fn types_kept_across_takes() {
+        let mut batcher = LineProtocolBatcher::default();
+        batcher.push_str("cpu usage=0.5,count=1i 100").unwrap();
+        batcher.take(0).unwrap();
+
+        // Integers are promoted to the float type of the previous points
+        batcher.push_str("cpu,host=a usage=1i 200").unwrap();
+        let batches = batcher.take(0).unwrap();
+        let usage = batches[0].1.column_by_name("usage").unwrap();
+        assert_eq!(usage.as_primitive::<Float64Type>().value(0), 1.0);
+        assert!(batcher.is_empty());
+
+        batcher.push_str("cpu count=1.5 300").unwrap();
+        assert!(matches!(batcher.take(0), Err(Error::TypeConflict { .. })));
+    }
+
+    #[test]
+    fn default_timestamp() {
+        let mut batcher = LineProtocolBatcher::default();
+        batcher.push_str("cpu usage=1i").unwrap();
+
+        let batches = batcher.take(42).unwrap();
+        let ts = batches[0].1.column(0).as_primitive::<Int64Type>();
+        assert_eq!(ts.value(0), 42);
+    }
+}
@@ -1,6 +1,9 @@
 use crate::rw;
 use crate::types::{self, MetadataBlob, MetadataError};
-use serde::{Deserialize, Serialize};
+use arrow::datatypes::SchemaRef;
+use arrow::ipc::{reader::StreamReader, writer::StreamWriter};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
 
 type Error = MetadataError;
@@ -34,12 +37,18 @@ impl From<JsonMetadataBlob> for serde_json::Value {
 #[derive(Serialize, Deserialize)]
 pub struct JsonSequenceMetadata {
     pub user_metadata: JsonMetadataBlob,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_column: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_precision: Option<types::TimePrecision>,
 }
 
 impl From<JsonSequenceMetadata> for types::SequenceMetadata<JsonMetadataBlob> {
     fn from(value: JsonSequenceMetadata) -> Self {
         Self {
             user_metadata: value.user_metadata,
+            time_column: value.time_column,
+            time_precision: value.time_precision,
         }
     }
 }
@@ -48,6 +57,8 @@ impl From<types::SequenceMetadata<JsonMetadataBlob>> for JsonSequenceMetadata {
     fn from(value: types::SequenceMetadata<JsonMetadataBlob>) -> Self {
         Self {
             user_metadata: value.user_metadata,
+            time_column: value.time_column,
+            time_precision: value.time_precision,
         }
     }
 }
@@ -139,10 +150,54 @@ impl TryInto<Vec<u8>> for JsonTopicMetadata {
     }
 }
 
+/// Arrow schema encoded as a base64 Arrow IPC stream, the same encoding of the schemas
+/// returned by the queries. Batches following the schema in the stream are ignored.
+#[derive(Clone, Debug)]
+pub struct JsonArrowSchema(pub SchemaRef);
+
+impl Serialize for JsonArrowSchema {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut buffer = Vec::new();
+        StreamWriter::try_new(&mut buffer, &self.0)
+            .and_then(|mut writer| writer.finish())
+            .map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&BASE64_STANDARD.encode(buffer))
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonArrowSchema {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        let buffer = BASE64_STANDARD
+            .decode(encoded)
+            .map_err(serde::de::Error::custom)?;
+        let reader =
+            StreamReader::try_new(buffer.as_slice(), None).map_err(serde::de::Error::custom)?;
+        Ok(Self(reader.schema()))
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct JsonTopicProperties {
     pub serialization_format: rw::Format,
     pub ontology_tag: String,
+    #[serde(default)]
+    pub schema_locked: bool,
+    /// Topics created by older versions have no precision, their data is in nanoseconds
+    #[serde(default)]
+    pub time_precision: types::TimePrecision,
+    #[serde(default)]
+    pub chunk_naming: types::ChunkNaming,
+    #[serde(default)]
+    pub ordering: types::RowOrdering,
+    #[serde(default)]
+    pub page_index: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression_level: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<JsonArrowSchema>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_column: Option<String>,
 }
 
 impl From<JsonTopicProperties> for types::TopicProperties {
@@ -150,6 +205,14 @@ impl From<JsonTopicProperties> for types::TopicProperties {
         Self {
             serialization_format: value.serialization_format,
             ontology_tag: value.ontology_tag,
+            schema_locked: value.schema_locked,
+            time_precision: value.time_precision,
+            chunk_naming: value.chunk_naming,
+            ordering: value.ordering,
+            page_index: value.page_index,
+            compression_level: value.compression_level,
+            schema: value.schema.map(|schema| schema.0),
+            time_column: value.time_column,
         }
     }
 }
@@ -159,6 +222,14 @@ impl From<types::TopicProperties> for JsonTopicProperties {
         Self {
             serialization_format: value.serialization_format,
             ontology_tag: value.ontology_tag,
+            schema_locked: value.schema_locked,
+            time_precision: value.time_precision,
+            chunk_naming: value.chunk_naming,
+            ordering: value.ordering,
+            page_index: value.page_index,
+            compression_level: value.compression_level,
+            schema: value.schema.map(JsonArrowSchema),
+            time_column: value.time_column,
         }
     }
 }
@@ -13,3 +13,5 @@ mod errors;
 pub use errors::*;
 
 pub mod flight;
+
+pub mod line_protocol;
@@ -1,4 +1,4 @@
-use crate::query;
+use crate::{query, types};
 use serde::Deserialize;
 use std::collections::HashMap;
 
@@ -165,6 +165,47 @@ struct Query {
     ontology: Option<Ontology>,
 }
 
+impl Query {
+    /// Converts the query collecting the errors of all its sections (and of each ontology
+    /// field) instead of stopping at the first one
+    fn try_into_collecting(self) -> Result<query::Filter, Vec<query::Error>> {
+        let mut errors = Vec::new();
+
+        let sequence: Option<query::SequenceFilter> =
+            match self.sequence.map(|v| v.try_into()).transpose() {
+                Ok(sequence) => sequence,
+                Err(e) => {
+                    errors.push(e);
+                    None
+                }
+            };
+        let topic: Option<query::TopicFilter> = match self.topic.map(|v| v.try_into()).transpose() {
+            Ok(topic) => topic,
+            Err(e) => {
+                errors.push(e);
+                None
+            }
+        };
+        let ontology = match self.ontology.map(|v| v.try_into_collecting()).transpose() {
+            Ok(ontology) => ontology,
+            Err(e) => {
+                errors.extend(e);
+                None
+            }
+        };
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(query::Filter {
+            sequence,
+            topic,
+            ontology,
+        })
+    }
+}
+
 impl TryInto<query::Filter> for Query {
     type Error = query::Error;
     fn try_into(self) -> Result<query::Filter, Self::Error> {
@@ -176,37 +217,96 @@ impl TryInto<query::Filter> for Query {
     }
 }
 
+/// Time window of an ontology query, either a `[start, end]` range of data timestamps or
+/// the `all` token to search the whole data
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TimeWindow {
+    Range([i64; 2]),
+    Token(String),
+}
+
+impl TryInto<query::TimeWindow> for TimeWindow {
+    type Error = query::OpError;
+    fn try_into(self) -> Result<query::TimeWindow, Self::Error> {
+        match self {
+            TimeWindow::Range([start, end]) => {
+                if start > end {
+                    return Err(query::OpError::EmptyRange);
+                }
+                Ok(query::TimeWindow::Range(types::TimestampRange::new(
+                    start.into(),
+                    end.into(),
+                )))
+            }
+            TimeWindow::Token(token) if token == "all" => Ok(query::TimeWindow::All),
+            TimeWindow::Token(_) => Err(query::OpError::WrongType),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct Ontology {
     #[serde(flatten)]
     filter: HashMap<String, Op>,
     include_timestamp_range: Option<bool>,
+    range: Option<TimeWindow>,
 }
 
-impl TryInto<query::OntologyFilter> for Ontology {
-    type Error = query::Error;
-    fn try_into(self) -> Result<query::OntologyFilter, Self::Error> {
-        let ontology = self
-            .filter
-            .into_iter()
-            .map(|(col, op)| {
-                let op = op.try_into().map_err(|e| query::Error::OpError {
+impl Ontology {
+    /// Converts the ontology filter collecting the errors of all its fields, sorted by
+    /// message, instead of stopping at the first one
+    fn try_into_collecting(self) -> Result<query::OntologyFilter, Vec<query::Error>> {
+        let mut errors = Vec::new();
+
+        let mut ontology = HashMap::with_capacity(self.filter.len());
+        for (col, op) in self.filter {
+            let op: Result<query::Op<query::Value>, _> =
+                op.try_into().map_err(|e| query::Error::OpError {
                     field: col.clone(),
                     err: e,
-                })?;
+                });
 
-                let col = query::OntologyField::try_new(col)?;
-
-                Ok::<(query::OntologyField, query::Op<query::Value>), Self::Error>((col, op))
-            })
-            .collect::<Result<_, _>>()?;
+            match op.and_then(|op| Ok((query::OntologyField::try_new(col)?, op))) {
+                Ok((col, op)) => {
+                    ontology.insert(col, op);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
 
         let include_timestamp_range = self.include_timestamp_range.unwrap_or_default();
 
-        Ok(query::OntologyFilter::new_with_timestamp_range(
-            ontology,
-            include_timestamp_range,
-        ))
+        let time_window: Result<Option<query::TimeWindow>, _> =
+            self.range.map(|v| v.try_into()).transpose();
+        let time_window = match time_window {
+            Ok(time_window) => time_window.unwrap_or_default(),
+            Err(e) => {
+                errors.push(query::Error::OpError {
+                    field: "ontology.range".to_owned(),
+                    err: e,
+                });
+                query::TimeWindow::default()
+            }
+        };
+
+        if !errors.is_empty() {
+            errors.sort_by_key(|e| e.to_string());
+            return Err(errors);
+        }
+
+        Ok(
+            query::OntologyFilter::new_with_timestamp_range(ontology, include_timestamp_range)
+                .with_time_window(time_window),
+        )
+    }
+}
+
+impl TryInto<query::OntologyFilter> for Ontology {
+    type Error = query::Error;
+    fn try_into(self) -> Result<query::OntologyFilter, Self::Error> {
+        self.try_into_collecting()
+            .map_err(|mut errors| errors.swap_remove(0))
     }
 }
 
@@ -311,6 +411,44 @@ impl TryInto<query::TopicFilter> for Topic {
     }
 }
 
+/// Row-level filter, each data column is bound to a single operation.
+/// All the constraints are combined in AND.
+#[derive(Debug, Deserialize)]
+struct RowFilter(HashMap<String, Op>);
+
+impl TryInto<query::RowFilter> for RowFilter {
+    type Error = query::Error;
+    fn try_into(self) -> Result<query::RowFilter, Self::Error> {
+        let group = self
+            .0
+            .into_iter()
+            .map(|(column, op)| {
+                let op: query::Op<query::Value> =
+                    op.try_into().map_err(|e| query::Error::OpError {
+                        field: column.clone(),
+                        err: e,
+                    })?;
+
+                query::ColumnExpr::try_new(column.clone(), op).map_err(|e| query::Error::OpError {
+                    field: column,
+                    err: e,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(query::RowFilter::new(group))
+    }
+}
+
+pub fn row_filter_from_serde_value(v: serde_json::Value) -> Result<query::RowFilter, super::Error> {
+    let filter: RowFilter =
+        serde_json::from_value(v).map_err(|e| super::Error::DeserializationError(e.to_string()))?;
+    let filter: query::RowFilter = filter
+        .try_into()
+        .map_err(|e: query::Error| super::Error::DeserializationError(e.to_string()))?;
+    Ok(filter)
+}
+
 pub fn query_filter_from_string(s: &str) -> Result<query::Filter, super::Error> {
     let query: Query =
         serde_json::from_str(s).map_err(|e| super::Error::DeserializationError(e.to_string()))?;
@@ -328,3 +466,13 @@ pub fn query_filter_from_serde_value(v: serde_json::Value) -> Result<query::Filt
         .map_err(|e: query::Error| super::Error::DeserializationError(e.to_string()))?;
     Ok(query)
 }
+
+/// Same as [`query_filter_from_serde_value`] but reports all the errors found in the query
+/// instead of the first one.
+pub fn query_filter_from_serde_value_collecting(
+    v: serde_json::Value,
+) -> Result<query::Filter, Vec<query::Error>> {
+    let query: Query = serde_json::from_value(v)
+        .map_err(|e| vec![query::Error::DeserializationError(e.to_string())])?;
+    query.try_into_collecting()
+}
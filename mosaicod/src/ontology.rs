@@ -0,0 +1,149 @@
+//! Registry of the data schemas expected for known ontology tags.
+//!
+//! When enabled, data uploaded on a topic whose `ontology_tag` is registered must contain
+//! all the fields defined for that tag with the expected data types. Additional fields are
+//! allowed. Topics with a tag not found in the registry are not validated.
+//!
+//! The registry is loaded from a JSON file mapping each tag to its fields and data types,
+//! e.g. `{"temperature": {"value": "Float64"}}`, data types are expressed using the Arrow
+//! data type notation.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use arrow::datatypes::{DataType, Schema};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("unable to read ontology registry :: {0}")]
+    IOError(#[from] std::io::Error),
+    #[error("bad ontology registry :: {0}")]
+    BadRegistry(String),
+    #[error("schema mismatch for ontology `{tag}` :: {msg}")]
+    SchemaMismatch { tag: String, msg: String },
+}
+
+/// Maps ontology tags to the fields expected in their data
+#[derive(Debug, Default)]
+pub struct OntologyRegistry {
+    fields: HashMap<String, Vec<(String, DataType)>>,
+}
+
+impl OntologyRegistry {
+    /// Registers the fields expected for `tag`, replacing any previous definition.
+    pub fn register(&mut self, tag: impl Into<String>, fields: Vec<(String, DataType)>) {
+        self.fields.insert(tag.into(), fields);
+    }
+
+    /// Loads a registry from its JSON definition.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let raw: HashMap<String, HashMap<String, String>> =
+            serde_json::from_str(json).map_err(|e| Error::BadRegistry(e.to_string()))?;
+
+        let mut registry = Self::default();
+        for (tag, fields) in raw {
+            let fields = fields
+                .into_iter()
+                .map(|(name, data_type)| {
+                    let data_type = DataType::from_str(&data_type).map_err(|e| {
+                        Error::BadRegistry(format!("field `{}` of `{}` :: {}", name, tag, e))
+                    })?;
+                    Ok((name, data_type))
+                })
+                .collect::<Result<_, Error>>()?;
+
+            registry.register(tag, fields);
+        }
+
+        Ok(registry)
+    }
+
+    /// Loads a registry from a JSON file, see [`OntologyRegistry::from_json`].
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Validates `schema` against the fields registered for `tag`.
+    ///
+    /// Returns an [`Error::SchemaMismatch`] describing the first missing field or the first
+    /// field with an unexpected data type.
+    pub fn validate(&self, tag: &str, schema: &Schema) -> Result<(), Error> {
+        let Some(fields) = self.fields.get(tag) else {
+            return Ok(());
+        };
+
+        for (name, expected) in fields {
+            let field = schema
+                .field_with_name(name)
+                .map_err(|_| Error::SchemaMismatch {
+                    tag: tag.to_owned(),
+                    msg: format!("missing field `{}` of type `{}`", name, expected),
+                })?;
+
+            if field.data_type() != expected {
+                return Err(Error::SchemaMismatch {
+                    tag: tag.to_owned(),
+                    msg: format!(
+                        "field `{}` has type `{}`, expected `{}`",
+                        name,
+                        field.data_type(),
+                        expected
+                    ),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+static REGISTRY: OnceLock<OntologyRegistry> = OnceLock::new();
+
+/// Enables the ontology schema validation using the provided registry.
+pub fn init_registry(registry: OntologyRegistry) {
+    let _ = REGISTRY.set(registry);
+}
+
+/// Returns the ontology registry, [`None`] if the validation is not enabled.
+pub fn registry() -> Option<&'static OntologyRegistry> {
+    REGISTRY.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::Field;
+
+    fn registry() -> OntologyRegistry {
+        OntologyRegistry::from_json(r#"{"temperature": {"value": "Float64"}}"#).unwrap()
+    }
+
+    #[test]
+    fn matching_schema() {
+        let schema = Schema::new(vec![
+            Field::new("timestamp_ns", DataType::Int64, false),
+            Field::new("value", DataType::Float64, false),
+            Field::new("sensor", DataType::Utf8, true),
+        ]);
+
+        assert!(registry().validate("temperature", &schema).is_ok());
+        assert!(registry().validate("unregistered", &schema).is_ok());
+    }
+
+    #[test]
+    fn mismatched_schema() {
+        let schema = Schema::new(vec![
+            Field::new("timestamp_ns", DataType::Int64, false),
+            Field::new("value", DataType::Utf8, false),
+        ]);
+
+        let res = registry().validate("temperature", &schema);
+        assert!(matches!(res, Err(Error::SchemaMismatch { .. })));
+
+        let schema = Schema::new(vec![Field::new("timestamp_ns", DataType::Int64, false)]);
+
+        let res = registry().validate("temperature", &schema);
+        assert!(matches!(res, Err(Error::SchemaMismatch { .. })));
+    }
+}
@@ -37,10 +37,124 @@ pub enum Error {
 pub struct ConfigurablesParams {
     pub max_message_size_in_bytes: usize,
     pub target_message_size_in_bytes: usize,
-    /// Maximum number of concurrent chunk queries during data catalog filtering
+    /// Maximum size in bytes of the body of an action, larger bodies are rejected while being
+    /// received, before being buffered entirely
+    pub max_action_body_bytes: usize,
+    /// Maximum number of chunks read concurrently by a query (data catalog filtering
+    /// included) or by a rewrite of the topic data, such as a reorder or a compaction
     pub max_concurrent_chunk_queries: usize,
     /// Maximum number of database connections in the pool
     pub max_db_connections: u32,
+    /// Time-to-live in seconds of the idempotency keys attached to mutating actions
+    pub idempotency_key_ttl_secs: u64,
+    /// Serialization format used for topics created without an explicit format
+    pub default_format: crate::rw::Format,
+    /// Maximum duration in seconds of a single store operation (read, write, list, stat, ...)
+    pub store_timeout_secs: u64,
+    /// Scheme used to map resource paths to object keys on S3-compatible stores, the
+    /// filesystem store always uses human-readable paths.
+    pub store_key_scheme: crate::store::KeyScheme,
+    /// When the objects written on the filesystem store are synced to durable storage,
+    /// ignored by S3-compatible stores.
+    pub store_fsync_policy: crate::store::FsyncPolicy,
+    /// Whether each write is read back until the store returns the written object, for
+    /// S3-compatible stores that are only eventually consistent on overwrites
+    pub store_read_after_write: bool,
+    /// Whether the store errors report the failed operation and the path of the object
+    pub store_error_context: bool,
+    /// JSON file mapping ontology tags to the expected data fields.
+    /// If set uploaded data is validated against the schema of its ontology tag.
+    pub ontology_registry: Option<std::path::PathBuf>,
+    /// Ontology tag assigned to the topics created with an empty tag, if not set topics
+    /// with an empty tag are rejected.
+    pub default_ontology_tag: Option<String>,
+    /// Store prefix under which the metadata files of the resources are placed.
+    /// If not set metadata files are placed next to the resources data.
+    pub metadata_prefix: Option<std::path::PathBuf>,
+    /// Directory of the write-ahead log used to make uploads durable across crashes.
+    /// If not set the write-ahead log is disabled.
+    pub wal_dir: Option<std::path::PathBuf>,
+    /// Whether the last chunk of every topic is validated at startup, removing the partial
+    /// chunks left by a crash during an upload
+    pub repair_on_startup: bool,
+    /// Time zone used to display datetimes in human-facing outputs (e.g. action responses).
+    /// If not set datetimes are displayed in UTC, storage is always UTC.
+    pub display_timezone: Option<chrono_tz::Tz>,
+    /// Lookback window in seconds applied to queries on ontology data that do not specify
+    /// a time range. If not set such queries search the whole data.
+    pub default_query_lookback_secs: Option<u64>,
+    /// Maximum number of concurrent uploads on the same sequence, if not set uploads are
+    /// unlimited
+    pub max_uploads_per_sequence: Option<usize>,
+    /// Whether uploads exceeding [`Self::max_uploads_per_sequence`] are rejected or queued
+    pub upload_limit_policy: crate::server::limiter::LimitPolicy,
+    /// Maximum number of concurrent queries on the same topic, if not set queries are
+    /// unlimited
+    pub max_queries_per_topic: Option<usize>,
+    /// Whether queries exceeding [`Self::max_queries_per_topic`] are rejected or queued
+    pub query_limit_policy: crate::server::limiter::LimitPolicy,
+    /// Engine computing the aggregations of the requests not asking for a specific one
+    pub query_engine: crate::query::QueryEngine,
+    /// Identifier of this instance, written in the leases of the topics it is uploading.
+    /// If not set a random identifier is generated at startup.
+    pub instance_id: String,
+    /// Time-to-live in seconds of the lease held on a topic during an upload, a lease not
+    /// renewed within this time is considered stale and can be taken over
+    pub upload_lease_ttl_secs: u64,
+    /// Size in bytes of the data received by an upload and not yet flushed to the store
+    /// beyond which the pending data is flushed before reading further from the client
+    pub upload_high_water_mark_bytes: usize,
+    /// Maximum duration in seconds of the flush triggered by the high-water mark, uploads
+    /// whose data can't be flushed in time are rejected
+    pub upload_flush_deadline_secs: u64,
+    /// Duration in seconds without uploads after which the topic of a completed upload is
+    /// locked, if not set the topic is locked as soon as the upload completes
+    pub upload_lock_grace_secs: Option<u64>,
+    /// Size in bytes below which the data of an upload is buffered and written in a single
+    /// chunk along with the data of the other small uploads of the topic, and beyond which the
+    /// buffer is written. If not set, or without a [`Self::wal_dir`], uploads are not coalesced
+    pub upload_coalesce_bytes: Option<usize>,
+    /// Maximum time in seconds the data of small uploads is kept buffered
+    pub upload_coalesce_secs: u64,
+    /// Storage class (e.g. `GLACIER_IR` on S3) the idle chunks are moved to by the
+    /// `topic_tier` action, if not set tiering is disabled
+    pub cold_storage_class: Option<String>,
+    /// Duration in seconds without accesses after which a chunk is moved to the cold
+    /// storage class
+    pub cold_tier_idle_secs: u64,
+    /// Estimated compressed size in bytes beyond which the chunk being uploaded is closed and
+    /// a new one is started, if not set the data of an upload is written in a single chunk
+    /// (unless flushed by the high-water mark)
+    pub target_chunk_bytes: Option<usize>,
+    /// Maximum duration in seconds of the data stream of a query, if not set queries have
+    /// no deadline
+    pub query_timeout_secs: Option<u64>,
+    /// Maximum number of rows of the batches of a query result, the batch sizes requested by
+    /// the clients are clamped to it
+    pub max_result_batch_rows: usize,
+    /// Maximum size in bytes of the serialized user metadata of a resource, larger metadata
+    /// are rejected on creation
+    pub max_user_metadata_bytes: usize,
+    /// Number of chunks of a topic beyond which its most recent chunks are merged in
+    /// background after an upload, if not set topics are never compacted
+    pub max_chunks_per_topic: Option<usize>,
+    /// Characters allowed in resource names besides ASCII alphanumerics and `/`, and how
+    /// names with other characters are handled
+    pub name_charset: crate::types::NameCharset,
+    /// Interval in seconds between the background vacuums of the orphaned staging files of
+    /// the store, if not set the store is vacuumed only on request
+    pub vacuum_interval_secs: Option<u64>,
+    /// Duration in seconds without modifications after which a staging file is considered
+    /// orphaned and removed by the vacuum
+    pub vacuum_age_secs: u64,
+    /// URL prefixes of the stores accepted as targets of a migration (e.g. `s3://` or
+    /// `file:///mnt/backup/`), filesystem targets are rejected unless listed since they would
+    /// let clients write anywhere the server can.
+    pub migration_allowed_targets: Vec<String>,
+    /// Caller identities allowed to run the actions administering the whole system (e.g.
+    /// `system_migrate` and `system_vacuum`), if empty these actions are refused to every
+    /// client.
+    pub admin_identities: Vec<String>,
 }
 
 static ENV: OnceLock<ConfigurablesParams> = OnceLock::new();
@@ -49,6 +163,11 @@ pub fn configurables() -> &'static ConfigurablesParams {
     ENV.get().expect("paramenters not initializes, plase call params::load_variable() before accessing and env variable.")
 }
 
+/// Returns the configurable parameters, [`None`] if not loaded yet.
+pub fn try_configurables() -> Option<&'static ConfigurablesParams> {
+    ENV.get()
+}
+
 pub fn load_configurables_from_env() {
     let ev = ConfigurablesParams {
         max_message_size_in_bytes: cast_env_var(
@@ -59,8 +178,79 @@ pub fn load_configurables_from_env() {
             "MOSAICO_TARGET_MESSAGE_SIZE_IN_BYTES",
             25 * 1024 * 1024,
         ),
+        max_action_body_bytes: cast_env_var("MOSAICO_MAX_ACTION_BODY_BYTES", 1024 * 1024),
         max_concurrent_chunk_queries: cast_env_var("MOSAICO_MAX_CONCURRENT_CHUNK_QUERIES", 4),
         max_db_connections: cast_env_var("MOSAICO_MAX_DB_CONNECTIONS", 10),
+        idempotency_key_ttl_secs: cast_env_var("MOSAICO_IDEMPOTENCY_KEY_TTL_SECS", 24 * 60 * 60),
+        default_format: cast_env_var("MOSAICO_DEFAULT_FORMAT", crate::rw::Format::Default),
+        store_timeout_secs: cast_env_var(
+            "MOSAICO_STORE_TIMEOUT_SECS",
+            crate::store::DEFAULT_OP_TIMEOUT.as_secs(),
+        ),
+        store_key_scheme: cast_env_var("MOSAICO_STORE_KEY_SCHEME", crate::store::KeyScheme::Plain),
+        store_fsync_policy: cast_env_var(
+            "MOSAICO_STORE_FSYNC_POLICY",
+            crate::store::FsyncPolicy::Never,
+        ),
+        store_read_after_write: cast_env_var("MOSAICO_STORE_READ_AFTER_WRITE", false),
+        store_error_context: cast_env_var("MOSAICO_STORE_ERROR_CONTEXT", true),
+        ontology_registry: cast_optional_env_var("MOSAICO_ONTOLOGY_REGISTRY"),
+        default_ontology_tag: cast_optional_env_var("MOSAICO_DEFAULT_ONTOLOGY_TAG"),
+        metadata_prefix: cast_optional_env_var("MOSAICO_METADATA_PREFIX"),
+        wal_dir: cast_optional_env_var("MOSAICO_WAL_DIR"),
+        repair_on_startup: cast_env_var("MOSAICO_REPAIR_ON_STARTUP", false),
+        display_timezone: cast_optional_env_var("MOSAICO_DISPLAY_TIMEZONE"),
+        default_query_lookback_secs: cast_optional_env_var("MOSAICO_DEFAULT_QUERY_LOOKBACK_SECS"),
+        max_uploads_per_sequence: cast_optional_env_var("MOSAICO_MAX_UPLOADS_PER_SEQUENCE"),
+        upload_limit_policy: cast_env_var(
+            "MOSAICO_UPLOAD_LIMIT_POLICY",
+            crate::server::limiter::LimitPolicy::Reject,
+        ),
+        max_queries_per_topic: cast_optional_env_var("MOSAICO_MAX_QUERIES_PER_TOPIC"),
+        query_limit_policy: cast_env_var(
+            "MOSAICO_QUERY_LIMIT_POLICY",
+            crate::server::limiter::LimitPolicy::Reject,
+        ),
+        query_engine: cast_env_var("MOSAICO_QUERY_ENGINE", crate::query::QueryEngine::Auto),
+        instance_id: cast_env_var("MOSAICO_INSTANCE_ID", uuid::Uuid::new_v4().to_string()),
+        upload_lease_ttl_secs: cast_env_var("MOSAICO_UPLOAD_LEASE_TTL_SECS", 5 * 60),
+        upload_high_water_mark_bytes: cast_env_var(
+            "MOSAICO_UPLOAD_HIGH_WATER_MARK_BYTES",
+            256 * 1024 * 1024,
+        ),
+        upload_flush_deadline_secs: cast_env_var("MOSAICO_UPLOAD_FLUSH_DEADLINE_SECS", 60),
+        upload_lock_grace_secs: cast_optional_env_var("MOSAICO_UPLOAD_LOCK_GRACE_SECS"),
+        upload_coalesce_bytes: cast_optional_env_var("MOSAICO_UPLOAD_COALESCE_BYTES"),
+        upload_coalesce_secs: cast_env_var("MOSAICO_UPLOAD_COALESCE_SECS", 30),
+        cold_storage_class: cast_optional_env_var("MOSAICO_COLD_STORAGE_CLASS"),
+        cold_tier_idle_secs: cast_env_var("MOSAICO_COLD_TIER_IDLE_SECS", 30 * 24 * 60 * 60),
+        target_chunk_bytes: cast_optional_env_var("MOSAICO_TARGET_CHUNK_BYTES"),
+        query_timeout_secs: cast_optional_env_var("MOSAICO_QUERY_TIMEOUT_SECS"),
+        max_result_batch_rows: cast_env_var("MOSAICO_MAX_RESULT_BATCH_ROWS", 1024 * 1024),
+        max_user_metadata_bytes: cast_env_var("MOSAICO_MAX_USER_METADATA_BYTES", 1024 * 1024),
+        max_chunks_per_topic: cast_optional_env_var("MOSAICO_MAX_CHUNKS_PER_TOPIC"),
+        name_charset: crate::types::NameCharset::new(
+            cast_env_var(
+                "MOSAICO_NAME_ALLOWED_CHARS",
+                crate::types::DEFAULT_NAME_ALLOWED_CHARS.to_owned(),
+            ),
+            cast_env_var("MOSAICO_NAME_POLICY", crate::types::NamePolicy::Allow),
+        ),
+        vacuum_interval_secs: cast_optional_env_var("MOSAICO_VACUUM_INTERVAL_SECS"),
+        vacuum_age_secs: cast_env_var("MOSAICO_VACUUM_AGE_SECS", 60 * 60),
+        migration_allowed_targets: cast_env_var(
+            "MOSAICO_MIGRATION_ALLOWED_TARGETS",
+            "s3://".to_owned(),
+        )
+        .split(',')
+        .map(|prefix| prefix.trim().to_owned())
+        .filter(|prefix| !prefix.is_empty())
+        .collect(),
+        admin_identities: cast_env_var("MOSAICO_ADMIN_IDENTITIES", String::new())
+            .split(',')
+            .map(|identity| identity.trim().to_owned())
+            .filter(|identity| !identity.is_empty())
+            .collect(),
     };
 
     let _ = ENV.set(ev);
@@ -79,6 +269,19 @@ where
     }
 }
 
+/// Same as [`cast_env_var`] but returns [`None`] if the variable is not set.
+fn cast_optional_env_var<T>(name: &str) -> Option<T>
+where
+    T: std::str::FromStr,
+    <T as FromStr>::Err: std::fmt::Debug,
+{
+    env::var(name).ok().map(|value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("unable to parse variable `{}`", name))
+    })
+}
+
 pub fn require_env_var<T>(name: &str) -> Result<T, Error>
 where
     T: std::str::FromStr,
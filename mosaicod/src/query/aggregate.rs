@@ -0,0 +1,420 @@
+//! Aggregations of the columns of the data of a topic.
+//!
+//! Aggregations are computed either by the query engine (see
+//! [`super::TimeseriesGatewayResult::aggregate`]) or in process by an [`Aggregator`], which
+//! folds the decoded chunks with the arrow compute kernels and avoids planning a query for
+//! simple scans.
+
+use arrow::array::{Array, AsArray, BooleanArray, Int64Array, RecordBatch, Scalar};
+use arrow::compute::{self, kernels::cmp};
+use arrow::datatypes::{DataType, Float64Type, Int64Type};
+use serde::{Deserialize, Serialize};
+
+use super::{Error, OpError, Value};
+use crate::{params, types};
+
+/// Function aggregating the values of a column, null values are ignored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregateFunction {
+    /// Number of non-null values
+    Count,
+    Min,
+    Max,
+    Sum,
+    /// Arithmetic mean, always a float
+    Mean,
+}
+
+/// Aggregation of a column, nested fields are separated by `.`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Aggregate {
+    pub function: AggregateFunction,
+    pub column: String,
+}
+
+/// Engine computing the aggregations of a topic
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryEngine {
+    /// In process if the aggregations are supported by an [`Aggregator`], query engine
+    /// otherwise
+    #[default]
+    Auto,
+    /// Chunks are decoded and aggregated in process
+    InProcess,
+    /// Chunks are scanned by the query engine
+    #[serde(rename = "datafusion")]
+    DataFusion,
+}
+
+impl std::str::FromStr for QueryEngine {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "auto" => Ok(Self::Auto),
+            "in_process" => Ok(Self::InProcess),
+            "datafusion" => Ok(Self::DataFusion),
+            _ => Err(format!("unknown query engine `{value}`")),
+        }
+    }
+}
+
+/// Kind of the values of a column aggregated in process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NumericKind {
+    Integer,
+    Float,
+}
+
+impl NumericKind {
+    /// Returns the kind of a column, [`None`] if its values can't be aggregated in process.
+    ///
+    /// Unsigned 64-bit integers and half floats are left to the query engine, since they may
+    /// not fit the accumulators or have no query representation.
+    fn of(data_type: &DataType) -> Option<Self> {
+        match data_type {
+            DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32 => Some(Self::Integer),
+            DataType::Float32 | DataType::Float64 => Some(Self::Float),
+            _ => None,
+        }
+    }
+}
+
+/// Running state of the aggregations of a column
+#[derive(Debug, Default)]
+struct ColumnState {
+    /// The column is part of at least one of the batches pushed
+    seen: bool,
+    kind: Option<NumericKind>,
+    count: u64,
+    min: Option<Value>,
+    max: Option<Value>,
+    int_sum: i64,
+    float_sum: f64,
+}
+
+impl ColumnState {
+    fn push(&mut self, column: &str, array: &dyn Array) -> Result<(), Error> {
+        let kind = NumericKind::of(array.data_type())
+            .filter(|kind| self.kind.is_none_or(|current| current == *kind))
+            .ok_or_else(|| Error::unsupported_op(column.to_owned()))?;
+        self.seen = true;
+        self.kind = Some(kind);
+        self.count += (array.len() - array.null_count()) as u64;
+
+        let (min, max) = match kind {
+            NumericKind::Integer => {
+                let array = compute::cast(array, &DataType::Int64)?;
+                let array = array.as_primitive::<Int64Type>();
+                let overflow = || Error::OpError {
+                    field: column.to_owned(),
+                    err: OpError::Overflow,
+                };
+                self.int_sum = compute::sum_checked(array)
+                    .map_err(|_| overflow())?
+                    .map_or(Some(self.int_sum), |sum| self.int_sum.checked_add(sum))
+                    .ok_or_else(overflow)?;
+                self.float_sum += array.iter().flatten().map(|v| v as f64).sum::<f64>();
+                (
+                    compute::min(array).map(Value::Integer),
+                    compute::max(array).map(Value::Integer),
+                )
+            }
+            NumericKind::Float => {
+                let array = compute::cast(array, &DataType::Float64)?;
+                let array = array.as_primitive::<Float64Type>();
+                self.float_sum += compute::sum(array).unwrap_or_default();
+                (
+                    compute::min(array).map(Value::Float),
+                    compute::max(array).map(Value::Float),
+                )
+            }
+        };
+
+        self.min = match (self.min.take(), min) {
+            (Some(current), Some(min)) if current <= min => Some(current),
+            (current, min) => min.or(current),
+        };
+        self.max = match (self.max.take(), max) {
+            (Some(current), Some(max)) if current >= max => Some(current),
+            (current, max) => max.or(current),
+        };
+
+        Ok(())
+    }
+
+    fn result(&self, function: AggregateFunction) -> Option<Value> {
+        let non_empty = self.count > 0;
+        match function {
+            AggregateFunction::Count => Some(Value::Integer(self.count as i64)),
+            AggregateFunction::Min => self.min.clone(),
+            AggregateFunction::Max => self.max.clone(),
+            AggregateFunction::Sum => match self.kind? {
+                NumericKind::Integer => non_empty.then_some(Value::Integer(self.int_sum)),
+                NumericKind::Float => non_empty.then_some(Value::Float(self.float_sum)),
+            },
+            AggregateFunction::Mean => {
+                non_empty.then(|| Value::Float(self.float_sum / self.count as f64))
+            }
+        }
+    }
+}
+
+/// Computes aggregations in process, folding the record batches of a topic one at a time.
+///
+/// Only top-level numeric columns are supported. Columns missing from a batch, e.g. added by a
+/// later schema, count as null values.
+pub struct Aggregator {
+    aggregates: Vec<Aggregate>,
+    /// Rows outside the range (in the precision of the data) are ignored
+    range: Option<types::TimestampRange>,
+    /// State of each aggregated column, in order of first appearance in `aggregates`
+    columns: Vec<(String, ColumnState)>,
+    /// At least one batch has been pushed
+    pushed: bool,
+}
+
+impl Aggregator {
+    /// Creates an aggregator computing `aggregates` over the rows within `range`.
+    ///
+    /// Returns an [`Error::OpError`] if an aggregation involves a nested field. The type of the
+    /// columns is checked as batches are pushed.
+    pub fn try_new(
+        aggregates: Vec<Aggregate>,
+        range: Option<types::TimestampRange>,
+    ) -> Result<Self, Error> {
+        if let Some(aggregate) = aggregates.iter().find(|a| a.column.contains('.')) {
+            return Err(Error::unsupported_op(aggregate.column.clone()));
+        }
+
+        let mut columns: Vec<(String, ColumnState)> = Vec::new();
+        for aggregate in &aggregates {
+            if !columns.iter().any(|(name, _)| name == &aggregate.column) {
+                columns.push((aggregate.column.clone(), ColumnState::default()));
+            }
+        }
+
+        Ok(Self {
+            aggregates,
+            range,
+            columns,
+            pushed: false,
+        })
+    }
+
+    /// Adds the rows of `batch` to the aggregations.
+    ///
+    /// Returns an [`Error::OpError`] if an aggregated column is not numeric, if its type
+    /// changes between batches, or if the sum of an integer column overflows.
+    pub fn push(&mut self, batch: &RecordBatch) -> Result<(), Error> {
+        self.pushed = true;
+        let batch = match &self.range {
+            Some(range) => compute::filter_record_batch(batch, &in_range(batch, range)?)?,
+            None => batch.clone(),
+        };
+
+        for (column, state) in &mut self.columns {
+            if let Some(array) = batch.column_by_name(column) {
+                state.push(column, array)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the result of each aggregation, in request order. Aggregations without a
+    /// result (e.g. the minimum of a column holding only nulls) are [`None`].
+    ///
+    /// Returns an [`Error::BadField`] if a column is missing from all the batches pushed.
+    pub fn finish(self) -> Result<Vec<Option<Value>>, Error> {
+        if self.pushed
+            && let Some((column, _)) = self.columns.iter().find(|(_, state)| !state.seen)
+        {
+            return Err(Error::bad_field(column.clone()));
+        }
+
+        Ok(self
+            .aggregates
+            .iter()
+            .map(|aggregate| {
+                self.columns
+                    .iter()
+                    .find(|(name, _)| name == &aggregate.column)
+                    .and_then(|(_, state)| state.result(aggregate.function))
+            })
+            .collect())
+    }
+}
+
+/// Returns the mask of the rows of `batch` whose timestamp is within `range` (bounds included)
+fn in_range(batch: &RecordBatch, range: &types::TimestampRange) -> Result<BooleanArray, Error> {
+    let timestamps = batch
+        .column_by_name(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP)
+        .ok_or_else(|| Error::bad_field(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP.to_owned()))?;
+
+    let bound = |value: types::Timestamp| Scalar::new(Int64Array::from(vec![i64::from(value)]));
+    let after_start = cmp::gt_eq(timestamps, &bound(range.start))?;
+    let before_end = cmp::lt_eq(timestamps, &bound(range.end))?;
+
+    Ok(compute::and(&after_start, &before_end)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Float64Array, Int32Array};
+    use arrow::datatypes::{Field, Schema};
+    use std::sync::Arc;
+
+    fn batch(timestamps: Vec<i64>, values: Vec<Option<i32>>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("value", DataType::Int32, true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(timestamps)),
+                Arc::new(Int32Array::from(values)),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn aggregate(function: AggregateFunction, column: &str) -> Aggregate {
+        Aggregate {
+            function,
+            column: column.to_owned(),
+        }
+    }
+
+    #[test]
+    fn aggregate_in_process() {
+        use AggregateFunction::*;
+        let aggregates = vec![
+            aggregate(Count, "value"),
+            aggregate(Min, "value"),
+            aggregate(Max, "value"),
+            aggregate(Sum, "value"),
+            aggregate(Mean, "value"),
+        ];
+
+        let mut aggregator = Aggregator::try_new(aggregates.clone(), None).unwrap();
+        aggregator
+            .push(&batch(vec![10, 20, 30], vec![Some(4), None, Some(-2)]))
+            .unwrap();
+        aggregator
+            .push(&batch(vec![40, 50], vec![Some(7), Some(3)]))
+            .unwrap();
+        assert_eq!(
+            aggregator.finish().unwrap(),
+            vec![
+                Some(Value::Integer(4)),
+                Some(Value::Integer(-2)),
+                Some(Value::Integer(7)),
+                Some(Value::Integer(12)),
+                Some(Value::Float(3.0)),
+            ]
+        );
+
+        // Rows out of range are ignored
+        let range = types::TimestampRange::new(20.into(), 40.into());
+        let mut aggregator = Aggregator::try_new(aggregates, Some(range)).unwrap();
+        aggregator
+            .push(&batch(vec![10, 20, 30, 40, 50], vec![Some(1); 5]))
+            .unwrap();
+        assert_eq!(aggregator.finish().unwrap()[0], Some(Value::Integer(3)));
+
+        // Columns not found in the data are rejected
+        let mut aggregator = Aggregator::try_new(vec![aggregate(Max, "missing")], None).unwrap();
+        aggregator.push(&batch(vec![10], vec![Some(1)])).unwrap();
+        assert!(matches!(aggregator.finish(), Err(Error::BadField { .. })));
+    }
+
+    #[test]
+    fn integer_sum_overflow() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "value",
+            DataType::Int64,
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int64Array::from(vec![i64::MAX - 1, 1]))],
+        )
+        .unwrap();
+
+        let mut aggregator =
+            Aggregator::try_new(vec![aggregate(AggregateFunction::Sum, "value")], None).unwrap();
+        aggregator.push(&batch).unwrap();
+        assert_eq!(
+            aggregator.finish().unwrap(),
+            vec![Some(Value::Integer(i64::MAX))]
+        );
+
+        // Overflows within a batch and across batches are both reported
+        let overflowing = batch.slice(0, 1);
+        let mut aggregator =
+            Aggregator::try_new(vec![aggregate(AggregateFunction::Sum, "value")], None).unwrap();
+        aggregator.push(&overflowing).unwrap();
+        assert!(matches!(
+            aggregator.push(&overflowing),
+            Err(Error::OpError {
+                err: OpError::Overflow,
+                ..
+            })
+        ));
+
+        let doubled = arrow::compute::concat_batches(&batch.schema(), [&batch, &batch]).unwrap();
+        let mut aggregator =
+            Aggregator::try_new(vec![aggregate(AggregateFunction::Sum, "value")], None).unwrap();
+        assert!(matches!(
+            aggregator.push(&doubled),
+            Err(Error::OpError {
+                err: OpError::Overflow,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn reject_unsupported_columns() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("value", DataType::Float64, true),
+            Field::new("label", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![1.5])),
+                Arc::new(arrow::array::StringArray::from(vec!["a"])),
+            ],
+        )
+        .unwrap();
+
+        let mut aggregator =
+            Aggregator::try_new(vec![aggregate(AggregateFunction::Min, "label")], None).unwrap();
+        assert!(matches!(
+            aggregator.push(&batch),
+            Err(Error::OpError { .. })
+        ));
+
+        // Nested fields are left to the query engine
+        assert!(matches!(
+            Aggregator::try_new(vec![aggregate(AggregateFunction::Sum, "pose.x")], None),
+            Err(Error::OpError { .. })
+        ));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::store;
+use crate::{rw, store};
 
 /// Errors that can occur during the construction or deserialization of a Query.
 #[derive(Debug, thiserror::Error)]
@@ -13,17 +13,26 @@ pub enum Error {
     #[error("bad field `{field}`")]
     BadField { field: String },
 
+    #[error("arrow error :: {0}")]
+    Arrow(#[from] arrow::error::ArrowError),
+
     #[error("datafusion backend error :: {0}")]
     DataFusion(#[from] datafusion::error::DataFusionError),
 
     #[error("not found")]
     NotFound,
 
+    #[error("column `{0}` already exists")]
+    ColumnCollision(String),
+
     #[error("bad path :: {0}")]
     BadPath(#[from] url::ParseError),
 
     #[error("store error :: {0}")]
     StoreError(#[from] store::Error),
+
+    #[error("rw error :: {0}")]
+    RwError(#[from] rw::Error),
 }
 
 impl Error {
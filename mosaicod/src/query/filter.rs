@@ -48,6 +48,10 @@ pub enum OpError {
     /// Occurs when constructing a [`Range`] where `min > max`.
     #[error("empty range")]
     EmptyRange,
+
+    /// Occurs when an integer result does not fit its type.
+    #[error("integer overflow")]
+    Overflow,
 }
 
 /// A wrapper enum to allow heterogeneous values (Numbers and Strings)
@@ -331,6 +335,44 @@ impl<T> IntoIterator for OntologyExprGroup<T> {
     }
 }
 
+/// Time window restricting the ontology data searched by a query.
+#[derive(Debug, Clone, Default)]
+pub enum TimeWindow {
+    /// No window requested, the server default lookback window applies (if configured)
+    #[default]
+    Default,
+    /// The whole data is searched, the server default is ignored
+    All,
+    /// Only data with a timestamp (in nanoseconds) within the range is searched
+    Range(types::TimestampRange),
+}
+
+impl TimeWindow {
+    /// Returns the range of data timestamps to search, [`None`] if the whole data must be
+    /// searched.
+    ///
+    /// The default window covers the `lookback` preceding `now` (a millisecond-precision
+    /// timestamp, e.g. [`types::Timestamp::now`]), if no lookback is provided the whole data
+    /// is searched.
+    pub fn resolve(
+        self,
+        lookback: Option<std::time::Duration>,
+        now: types::Timestamp,
+    ) -> Option<types::TimestampRange> {
+        match self {
+            Self::Default => lookback.map(|lookback| {
+                let lookback = i64::try_from(lookback.as_millis()).unwrap_or(i64::MAX);
+                types::TimestampRange::new(
+                    now.saturating_add_millis(-lookback).millis_to_nanos(),
+                    now.millis_to_nanos(),
+                )
+            }),
+            Self::All => None,
+            Self::Range(range) => Some(range),
+        }
+    }
+}
+
 /// A container for dynamic user-defined expressions mapping to ontology data models.
 #[derive(Debug, Clone)]
 pub struct OntologyFilter {
@@ -339,6 +381,9 @@ pub struct OntologyFilter {
     /// If enabled the response should include timestamp ranges
     /// for each topic in which the query filter matches
     pub include_timestamp_range: bool,
+
+    /// Time window of the data searched
+    pub time_window: TimeWindow,
 }
 
 impl OntologyFilter {
@@ -347,6 +392,7 @@ impl OntologyFilter {
         Self {
             ontology: v,
             include_timestamp_range: false,
+            time_window: TimeWindow::Default,
         }
     }
 
@@ -357,14 +403,22 @@ impl OntologyFilter {
         Self {
             ontology: v,
             include_timestamp_range,
+            time_window: TimeWindow::Default,
         }
     }
 
+    /// Restricts the searched data to the provided time window.
+    pub fn with_time_window(mut self, time_window: TimeWindow) -> Self {
+        self.time_window = time_window;
+        self
+    }
+
     /// Creates an empty Metadata instance.
     pub fn empty() -> Self {
         Self {
             ontology: HashMap::new(),
             include_timestamp_range: false,
+            time_window: TimeWindow::Default,
         }
     }
 
@@ -373,6 +427,11 @@ impl OntologyFilter {
         self.ontology.get(field)
     }
 
+    /// Returns the ontology fields constrained by the filter
+    pub fn fields(&self) -> impl Iterator<Item = &OntologyField> {
+        self.ontology.keys()
+    }
+
     /// Exports filter data as a unique expression group
     pub fn into_expr_group(self) -> OntologyExprGroup<Value> {
         OntologyExprGroup {
@@ -385,6 +444,63 @@ impl OntologyFilter {
     }
 }
 
+/// A row-level constraint on a data column (e.g. *"sensor_id == 'A'"*).
+///
+/// Nested columns can be addressed using dots (e.g. `position.x`).
+#[derive(Debug, Clone)]
+pub struct ColumnExpr {
+    pub column: String,
+    pub op: Op<Value>,
+}
+
+impl ColumnExpr {
+    /// Creates a new column expression, only comparison operations are allowed.
+    ///
+    /// Ordering operations are supported on numeric and text values.
+    pub fn try_new(column: String, op: Op<Value>) -> Result<Self, OpError> {
+        let supported = match &op {
+            Op::Eq(_) | Op::Neq(_) => true,
+            Op::Leq(v) | Op::Geq(v) | Op::Lt(v) | Op::Gt(v) => !matches!(v, Value::Boolean(_)),
+            Op::Between(range) => !matches!(range.min, Value::Boolean(_)),
+            _ => false,
+        };
+
+        if !supported {
+            return Err(OpError::UnsupportedOperation);
+        }
+
+        Ok(Self { column, op })
+    }
+}
+
+/// A collection of row-level constraints, combined in AND, used to filter
+/// the data rows returned to the clients.
+#[derive(Debug, Clone, Default)]
+pub struct RowFilter {
+    pub group: Vec<ColumnExpr>,
+}
+
+impl RowFilter {
+    pub fn new(group: Vec<ColumnExpr>) -> Self {
+        Self { group }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.group.is_empty()
+    }
+
+    /// Creates a filter matching the rows with a timestamp within `range` (bounds included).
+    pub fn timestamp_range(range: &types::TimestampRange) -> Self {
+        Self::new(vec![ColumnExpr {
+            column: crate::params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP.to_owned(),
+            op: Op::Between(Range {
+                min: Value::Integer(range.start.into()),
+                max: Value::Integer(range.end.into()),
+            }),
+        }])
+    }
+}
+
 /// Represents the logical operator to apply to a field for filtering.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Op<T> {
@@ -510,6 +626,36 @@ mod tests {
         assert_eq!(oc.value(), "image.info.height");
     }
 
+    #[test]
+    fn column_expr_supported_ops() {
+        assert!(ColumnExpr::try_new("sensor_id".into(), Op::Eq("A".into())).is_ok());
+        assert!(ColumnExpr::try_new("sensor_id".into(), Op::Lt("B".into())).is_ok());
+        assert!(ColumnExpr::try_new("value".into(), Op::Geq(Value::Integer(3))).is_ok());
+        assert!(ColumnExpr::try_new("valid".into(), Op::Gt(Value::Boolean(true))).is_err());
+        assert!(ColumnExpr::try_new("value".into(), Op::Ex).is_err());
+    }
+
+    #[test]
+    fn time_window_resolve() {
+        let now = types::Timestamp::from(1_000_000);
+        let day = Some(std::time::Duration::from_secs(24 * 3600));
+
+        // The default lookback is applied when no window is provided
+        let range = TimeWindow::Default.resolve(day, now).unwrap();
+        assert_eq!(range.start, types::Timestamp::from(-85_400_000_000_000));
+        assert_eq!(range.end, types::Timestamp::from(1_000_000_000_000));
+        assert!(TimeWindow::Default.resolve(None, now).is_none());
+
+        // An explicit range overrides the default lookback
+        let explicit = types::TimestampRange::new(10.into(), 20.into());
+        let range = TimeWindow::Range(explicit).resolve(day, now).unwrap();
+        assert_eq!(range.start, types::Timestamp::from(10));
+        assert_eq!(range.end, types::Timestamp::from(20));
+
+        // The `all` window disables the default lookback
+        assert!(TimeWindow::All.resolve(day, now).is_none());
+    }
+
     #[test]
     fn expr_grp_split() {
         let grp = OntologyExprGroup {
@@ -6,8 +6,23 @@ pub use filter::*;
 mod builder;
 pub use builder::*;
 
+mod sample;
+pub use sample::*;
+
+mod order;
+pub use order::*;
+
+mod provenance;
+pub use provenance::*;
+
 mod timeseries_gw;
 pub use timeseries_gw::*;
 
+mod running;
+pub use running::*;
+
+mod aggregate;
+pub use aggregate::*;
+
 mod error;
 pub use error::*;
@@ -0,0 +1,36 @@
+//! Ordering of the data rows.
+
+use crate::params;
+
+/// Defines the order of the returned data rows.
+///
+/// Rows sharing the same value of `column` are ordered by ascending timestamp, so that the
+/// returned order is always deterministic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderBy {
+    pub column: String,
+    pub descending: bool,
+}
+
+impl OrderBy {
+    pub fn asc(column: String) -> Self {
+        Self {
+            column,
+            descending: false,
+        }
+    }
+
+    pub fn desc(column: String) -> Self {
+        Self {
+            column,
+            descending: true,
+        }
+    }
+}
+
+/// Rows are ordered by ascending timestamp by default
+impl Default for OrderBy {
+    fn default() -> Self {
+        Self::asc(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP.to_owned())
+    }
+}
@@ -0,0 +1,45 @@
+//! Synthetic columns appended to the data rows to track their provenance.
+
+use super::Error;
+
+/// A synthetic column, sourced from the resource metadata, appended to each data row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProvenanceColumn {
+    /// Locator name of the topic holding the row
+    Topic,
+    /// Index of the chunk holding the row
+    Chunk,
+    /// Write timestamp (in milliseconds) of the chunk holding the row, the creation timestamp
+    /// of the topic for chunks written by older versions
+    IngestedAt,
+}
+
+impl ProvenanceColumn {
+    /// Name of the column appended to the data
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Topic => "_topic",
+            Self::Chunk => "_chunk",
+            Self::IngestedAt => "_ingested_at",
+        }
+    }
+
+    /// Returns true if the value of the column depends on the chunk holding the row, so that
+    /// each chunk must be read by a separate query.
+    pub fn is_per_chunk(&self) -> bool {
+        matches!(self, Self::Chunk | Self::IngestedAt)
+    }
+}
+
+impl std::str::FromStr for ProvenanceColumn {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "_topic" => Ok(Self::Topic),
+            "_chunk" => Ok(Self::Chunk),
+            "_ingested_at" => Ok(Self::IngestedAt),
+            _ => Err(Error::bad_field(value.to_owned())),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Registry of the queries being streamed to the clients.
+//!
+//! A client can tag a query with a request token of its choice, the token can be later used
+//! to cancel the query while its data is still being streamed. Tokens are bound to the
+//! identity of the client registering the query, only the same identity can cancel it.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio_util::sync::CancellationToken;
+
+/// Running queries by token, along with the identity of their owner
+type Queries = Arc<Mutex<HashMap<String, (Option<String>, CancellationToken)>>>;
+
+#[derive(Default)]
+pub struct RunningQueries {
+    queries: Queries,
+}
+
+/// Registration of a running query, removed from the registry when dropped
+pub struct RunningQuery {
+    token: String,
+    cancellation: CancellationToken,
+    queries: Queries,
+}
+
+impl RunningQueries {
+    /// Registers a query tagged by `token` on behalf of `owner`, [`None`] for anonymous
+    /// clients.
+    ///
+    /// Returns [`None`] if a running query is already tagged by the same token.
+    pub fn register(&self, token: &str, owner: Option<&str>) -> Option<RunningQuery> {
+        let mut queries = self.queries.lock().unwrap();
+        if queries.contains_key(token) {
+            return None;
+        }
+
+        let cancellation = CancellationToken::new();
+        queries.insert(
+            token.to_owned(),
+            (owner.map(ToOwned::to_owned), cancellation.clone()),
+        );
+
+        Some(RunningQuery {
+            token: token.to_owned(),
+            cancellation,
+            queries: self.queries.clone(),
+        })
+    }
+
+    /// Signals the cancellation of the query tagged by `token` on behalf of `owner`.
+    ///
+    /// Returns false if no running query is tagged by the token (e.g. the query has already
+    /// completed) or if the query was registered by another owner, so that the tokens of the
+    /// other clients are not disclosed.
+    pub fn cancel(&self, token: &str, owner: Option<&str>) -> bool {
+        let queries = self.queries.lock().unwrap();
+        let Some((registered_by, cancellation)) = queries.get(token) else {
+            return false;
+        };
+        if registered_by.as_deref() != owner {
+            return false;
+        }
+
+        cancellation.cancel();
+        true
+    }
+}
+
+impl RunningQuery {
+    /// Returns the token cancelled when the cancellation of the query is requested
+    pub fn cancellation(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+}
+
+impl Drop for RunningQuery {
+    fn drop(&mut self) {
+        self.queries.lock().unwrap().remove(&self.token);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_running_query() {
+        let running = RunningQueries::default();
+
+        let query = running.register("abc", None).unwrap();
+        assert!(running.register("abc", None).is_none());
+        assert!(!running.cancel("unknown", None));
+
+        assert!(running.cancel("abc", None));
+        assert!(query.cancellation().is_cancelled());
+
+        // Completed queries can't be cancelled and their token can be reused
+        drop(query);
+        assert!(!running.cancel("abc", None));
+        assert!(running.register("abc", None).is_some());
+    }
+
+    #[test]
+    fn cancel_query_of_other_owner() {
+        let running = RunningQueries::default();
+
+        let query = running.register("abc", Some("alice")).unwrap();
+
+        // Neither other identities nor anonymous clients can cancel the query
+        assert!(!running.cancel("abc", Some("bob")));
+        assert!(!running.cancel("abc", None));
+        assert!(!query.cancellation().is_cancelled());
+
+        assert!(running.cancel("abc", Some("alice")));
+        assert!(query.cancellation().is_cancelled());
+    }
+}
@@ -0,0 +1,41 @@
+//! Decimation of the data rows, used to preview large topics.
+
+/// Defines how data rows are sampled.
+///
+/// Differently from a limit, which returns a contiguous prefix of the data, a sample
+/// spreads the returned rows evenly across the whole time range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sample {
+    /// Returns one row every `n` rows
+    EveryNth(usize),
+    /// Returns (approximately) the target number of rows, evenly spread across the data
+    TargetCount(usize),
+}
+
+impl Sample {
+    /// Returns the distance between two consecutive sampled rows.
+    ///
+    /// `total_rows` is the number of rows matching the query, this value is retrieved from the
+    /// chunk statistics when no filter is applied, so that the stride can be computed without
+    /// scanning the data.
+    pub fn stride(&self, total_rows: usize) -> usize {
+        match self {
+            Self::EveryNth(n) => (*n).max(1),
+            Self::TargetCount(target) => total_rows.div_ceil((*target).max(1)).max(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stride() {
+        assert_eq!(Sample::EveryNth(3).stride(100), 3);
+        assert_eq!(Sample::EveryNth(0).stride(100), 1);
+        assert_eq!(Sample::TargetCount(4).stride(14), 4);
+        assert_eq!(Sample::TargetCount(10).stride(5), 1);
+        assert_eq!(Sample::TargetCount(0).stride(5), 5);
+    }
+}
@@ -7,25 +7,56 @@
 use datafusion::scalar::ScalarValue;
 use log::trace;
 
+use crate::traits::AsExtension;
 use crate::types;
 use crate::{params, query, rw, store};
+use arrow::array::{Array, AsArray, Int64Array, UInt32Array};
+use arrow::compute;
 use arrow::datatypes::{Schema, SchemaRef};
 use datafusion::execution::SendableRecordBatchStream;
 use datafusion::execution::runtime_env::{RuntimeEnv, RuntimeEnvBuilder};
 use datafusion::functions::core::expr_ext::FieldAccessor;
-use datafusion::functions_aggregate::expr_fn::{max, min};
+use datafusion::functions_aggregate::expr_fn::{approx_distinct, count, max, min};
+#[cfg(feature = "datafusion-engine")]
+use datafusion::functions_aggregate::expr_fn::{avg, sum};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{self, ExecutionPlan};
 use datafusion::prelude::*;
-use std::collections::HashMap;
-use std::path::Path;
-use std::sync::Arc;
+use futures::StreamExt;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 use super::Error;
 
 pub type TimeseriesGatewayRef = Arc<TimeseriesGateway>;
 
+/// Maximum number of paths whose datafiles inspection is cached, see
+/// [`TimeseriesGateway::inspect_datafiles`]
+const MAX_CACHED_INSPECTIONS: usize = 1024;
+
+/// Unified schema of the datafiles of a path and whether all of them are sorted by timestamp,
+/// along with the datafiles (and their versions) it was computed from.
+struct DatafilesInspection {
+    datafiles: Vec<(String, store::ObjectVersion)>,
+    schema: SchemaRef,
+    time_sorted: bool,
+}
+
+/// Result of [`TimeseriesGateway::inspect_datafiles`]
+struct Inspection {
+    /// Unified schema of the datafiles, [`None`] if not required
+    schema: Option<SchemaRef>,
+    time_sorted: bool,
+    /// The inspection was reused from the cache, no datafile footer was read
+    cached: bool,
+}
+
 pub struct TimeseriesGateway {
     runtime: Arc<RuntimeEnv>,
     store: Arc<store::Store>,
+    running: query::RunningQueries,
+    inspections: Mutex<HashMap<PathBuf, DatafilesInspection>>,
 }
 
 impl TimeseriesGateway {
@@ -39,9 +70,17 @@ impl TimeseriesGateway {
         Ok(TimeseriesGateway {
             runtime,
             store: store.clone(),
+            running: query::RunningQueries::default(),
+            inspections: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Returns the queries tagged by the clients with a request token, which can be
+    /// cancelled while their data is being streamed.
+    pub fn running(&self) -> &query::RunningQueries {
+        &self.running
+    }
+
     /// Read time-series data from a path.
     ///
     /// All files in the provided path will be included in the read.
@@ -49,19 +88,99 @@ impl TimeseriesGateway {
     /// If `batch_size` is provided, the system will use it to configure the batch size
     /// for the query engine. This allows callers to control message sizes based on
     /// pre-computed statistics from the database.
+    ///
+    /// Files with different (compatible) schemas are projected to a unified schema,
+    /// see [`rw::merge_schemas`].
+    ///
+    /// Rows are returned ordered by ascending timestamp, regardless of the order of the
+    /// files, see [`TimeseriesGatewayResult::sort`] to use a different order. If every file is
+    /// internally sorted by timestamp (see [`rw::TIME_SORTED_METADATA_KEY`]) the files are
+    /// streamed through a k-way merge, rather than fully sorted, even if their time ranges
+    /// overlap.
     pub async fn read(
         &self,
         path: impl AsRef<Path>,
         format: rw::Format,
         batch_size: Option<usize>,
+    ) -> Result<TimeseriesGatewayResult, Error> {
+        let path = path.as_ref();
+        let inspection = self.inspect_datafiles(path, format).await?;
+
+        self.read_listing(
+            path,
+            format,
+            &inspection,
+            inspection.time_sorted,
+            batch_size,
+        )
+        .await
+    }
+
+    /// Same as [`TimeseriesGateway::read`], but each datafile located in `path` is read by a
+    /// separate query, so that the source of the returned rows is known.
+    ///
+    /// Returns the datafiles in ascending order along with their result, rows are ordered by
+    /// ascending timestamp within each datafile. All the results have the unified schema of
+    /// the datafiles. Queries are only planned, no data is read until the results are
+    /// streamed.
+    pub async fn read_by_datafile(
+        &self,
+        path: impl AsRef<Path>,
+        format: rw::Format,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<(String, TimeseriesGatewayResult)>, Error> {
+        let path = path.as_ref();
+        let inspection = self.inspect_datafiles(path, format).await?;
+
+        let mut datafiles = self.store.list(path, Some(&format.as_extension())).await?;
+        datafiles.sort_by_key(|datafile| types::datafile_index(datafile));
+
+        let mut results = Vec::with_capacity(datafiles.len());
+        for datafile in datafiles {
+            let result = self
+                .read_listing(Path::new(&datafile), format, &inspection, false, batch_size)
+                .await?;
+            results.push((datafile, result));
+        }
+
+        Ok(results)
+    }
+
+    /// Builds the query reading the datafiles located in `path` (a directory or a single
+    /// datafile) with the schema of `inspection`, or the schema inferred by the query engine
+    /// if not available.
+    async fn read_listing(
+        &self,
+        path: &Path,
+        format: rw::Format,
+        inspection: &Inspection,
+        time_sorted: bool,
+        batch_size: Option<usize>,
     ) -> Result<TimeseriesGatewayResult, Error> {
         // Use Parquet format strategy for listing options
         let parquet_strategy = format
             .as_parquet()
             .expect("TimeseriesGateway::read requires a Parquet-based format");
-        let listing_options = parquet_strategy.listing_options();
+        let mut listing_options = parquet_strategy.listing_options();
 
-        let mut conf = SessionConfig::new();
+        if time_sorted {
+            listing_options = listing_options.with_file_sort_order(vec![vec![
+                col(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP).sort(true, false),
+            ]]);
+        }
+        // Filters are pushed down into the parquet reader and evaluated while decoding,
+        // so non-matching rows are never materialized. Files with a page index (see
+        // `TopicProperties::page_index`) also have the non-matching pages skipped.
+        let mut conf = SessionConfig::new()
+            .set_bool("datafusion.execution.parquet.pushdown_filters", true)
+            .set_bool("datafusion.execution.parquet.enable_page_index", true)
+            .set_bool("datafusion.execution.parquet.reorder_filters", true)
+            // Sorted files with overlapping time ranges are scanned in separate partitions,
+            // which are merged preserving their order
+            .set_bool(
+                "datafusion.execution.split_file_groups_by_statistics",
+                time_sorted,
+            );
         if let Some(batch_size) = batch_size {
             conf = conf.with_batch_size(batch_size);
         }
@@ -73,7 +192,7 @@ impl TimeseriesGateway {
             "data",
             self.datafile_url(path)?,
             listing_options,
-            None,
+            inspection.schema.clone(),
             None,
         )
         .await?;
@@ -85,7 +204,86 @@ impl TimeseriesGateway {
 
         let df = ctx.sql(&select).await?;
 
-        Ok(TimeseriesGatewayResult { data_frame: df })
+        Ok(TimeseriesGatewayResult {
+            data_frame: df,
+            cache_hit: inspection.cached,
+            row_count: None,
+        })
+    }
+
+    /// Computes the unified schema of all the files located in `path`, and whether all of
+    /// them are sorted by ascending timestamp.
+    ///
+    /// Returns [`None`] (and not sorted) if the path holds less than two files, since no
+    /// unification nor merge is required and the schema can be directly inferred by the query
+    /// engine.
+    ///
+    /// The result is cached by path and reused as long as the path holds the same datafiles,
+    /// at the same versions, so that only the listing of the path is required. Any write of a
+    /// datafile, also by another instance sharing the store, changes the listing and causes
+    /// the footers to be read again.
+    async fn inspect_datafiles(
+        &self,
+        path: &Path,
+        format: rw::Format,
+    ) -> Result<Inspection, Error> {
+        let mut datafiles = self
+            .store
+            .list_versioned(path, Some(&format.as_extension()))
+            .await?;
+        if datafiles.len() < 2 {
+            return Ok(Inspection {
+                schema: None,
+                time_sorted: false,
+                cached: false,
+            });
+        }
+        datafiles.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        if let Some(cached) = self.inspections.lock().unwrap().get(path)
+            && cached.datafiles == datafiles
+        {
+            trace!(
+                "reusing the inspection of the datafiles of {}",
+                path.display()
+            );
+            return Ok(Inspection {
+                schema: Some(cached.schema.clone()),
+                time_sorted: cached.time_sorted,
+                cached: true,
+            });
+        }
+
+        let mut schemas = Vec::with_capacity(datafiles.len());
+        let mut time_sorted = true;
+        for (datafile, _) in &datafiles {
+            let schema = self.store.read_parquet_schema(datafile).await?;
+            time_sorted &= rw::is_time_sorted(schema.metadata());
+            schemas.push(schema.as_ref().clone());
+        }
+        let schema = Arc::new(rw::merge_schemas(&schemas)?);
+
+        // Rewrites of datafiles without a version can't be detected
+        if datafiles.iter().all(|(_, version)| version.is_known()) {
+            let mut inspections = self.inspections.lock().unwrap();
+            if inspections.len() >= MAX_CACHED_INSPECTIONS && !inspections.contains_key(path) {
+                inspections.clear();
+            }
+            inspections.insert(
+                path.to_path_buf(),
+                DatafilesInspection {
+                    datafiles,
+                    schema: schema.clone(),
+                    time_sorted,
+                },
+            );
+        }
+
+        Ok(Inspection {
+            schema: Some(schema),
+            time_sorted,
+            cached: false,
+        })
     }
 
     fn datafile_url(&self, path: impl AsRef<Path>) -> Result<url::Url, Error> {
@@ -93,15 +291,30 @@ impl TimeseriesGateway {
             .store
             .as_ref()
             .url_schema
-            .join(&path.as_ref().to_string_lossy())?)
+            .join(self.store.object_key(path).as_ref())?)
     }
 }
 
 pub struct TimeseriesGatewayResult {
     data_frame: DataFrame,
+    /// The query was planned with the cached inspection of the datafiles
+    cache_hit: bool,
+    /// Number of rows of the result if known without scanning the data, see
+    /// [`TimeseriesGatewayResult::with_row_count`]
+    row_count: Option<usize>,
 }
 
 impl TimeseriesGatewayResult {
+    /// Sets the number of rows of the read datafiles, as recorded in the catalog, used by
+    /// [`TimeseriesGatewayResult::count`] to answer without scanning the data as long as no
+    /// filter is applied.
+    pub fn with_row_count(self, row_count: usize) -> Self {
+        Self {
+            row_count: Some(row_count),
+            ..self
+        }
+    }
+
     pub fn schema_with_metadata(&self, metadata: HashMap<String, String>) -> SchemaRef {
         Arc::new(Schema::new_with_metadata(
             self.data_frame.schema().fields().clone(),
@@ -115,21 +328,144 @@ impl TimeseriesGatewayResult {
     {
         let expr = expr_group_to_df_expr(filter);
 
-        let data_frame = if let Some(expr) = expr {
-            trace!("filter expression: {}", expr);
-            self.data_frame.filter(expr)?
-        } else {
-            self.data_frame
+        let Some(expr) = expr else {
+            return Ok(self);
+        };
+
+        trace!("filter expression: {}", expr);
+        let data_frame = self.data_frame.filter(expr)?;
+
+        Ok(TimeseriesGatewayResult {
+            data_frame,
+            row_count: None,
+            ..self
+        })
+    }
+
+    /// Filters the data rows using a set of row-level constraints.
+    pub fn filter_rows(self, filter: query::RowFilter) -> Result<Self, Error> {
+        let expr = row_filter_to_df_expr(filter);
+
+        let Some(expr) = expr else {
+            return Ok(self);
         };
 
-        Ok(TimeseriesGatewayResult { data_frame })
+        trace!("row filter expression: {}", expr);
+        let data_frame = self.data_frame.filter(expr)?;
+
+        Ok(TimeseriesGatewayResult {
+            data_frame,
+            row_count: None,
+            ..self
+        })
+    }
+
+    /// Sorts the data rows following `order`, ties are broken by ascending timestamp.
+    ///
+    /// The sort is executed by the query engine, which merges sorted runs and spills them to
+    /// disk under memory pressure, so large results are never fully buffered. Returns an
+    /// [`Error::BadField`] if the column is not part of the schema.
+    pub fn sort(self, order: &query::OrderBy) -> Result<Self, Error> {
+        if !self
+            .data_frame
+            .schema()
+            .has_column_with_unqualified_name(&order.column)
+        {
+            return Err(Error::bad_field(order.column.clone()));
+        }
+
+        let data_frame = self.data_frame.sort(vec![
+            ident(&order.column).sort(!order.descending, false),
+            ident(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP).sort(true, false),
+        ])?;
+
+        Ok(TimeseriesGatewayResult { data_frame, ..self })
+    }
+
+    /// Restricts the data to the provided columns, preserving the rows order.
+    ///
+    /// The projection is pushed down to the datafiles reader, so unused columns are never
+    /// decoded. Returns an [`Error::BadField`] if a column is not part of the schema.
+    pub fn project(self, columns: &[String]) -> Result<Self, Error> {
+        let schema = self.data_frame.schema();
+        if let Some(column) = columns
+            .iter()
+            .find(|c| !schema.has_column_with_unqualified_name(c))
+        {
+            return Err(Error::bad_field(column.clone()));
+        }
+
+        let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+        let data_frame = self.data_frame.select_columns(&columns)?;
+
+        Ok(TimeseriesGatewayResult { data_frame, ..self })
+    }
+
+    /// Renames the column `from` to `to`, nothing is done if `from` is not part of the
+    /// schema (e.g. not projected).
+    pub fn rename_column(self, from: &str, to: &str) -> Result<Self, Error> {
+        let data_frame = self.data_frame.with_column_renamed(from, to)?;
+
+        Ok(TimeseriesGatewayResult { data_frame, ..self })
+    }
+
+    /// Appends a column holding the same `value` for each row.
+    ///
+    /// Returns an [`Error::ColumnCollision`] if a column with the same name already exists.
+    pub fn with_constant_column(self, name: &str, value: query::Value) -> Result<Self, Error> {
+        if self
+            .data_frame
+            .schema()
+            .has_column_with_unqualified_name(name)
+        {
+            return Err(Error::ColumnCollision(name.to_owned()));
+        }
+
+        let data_frame = self.data_frame.with_column(name, value_to_df_expr(value))?;
+
+        Ok(TimeseriesGatewayResult { data_frame, ..self })
     }
 
     pub async fn stream(self) -> Result<SendableRecordBatchStream, Error> {
         self.data_frame.execute_stream().await.map_err(|e| e.into())
     }
 
+    /// Same as [`TimeseriesGatewayResult::stream`], also returning a [`ScanProbe`] reporting
+    /// the work done by the query engine to produce the streamed data.
+    pub async fn probed_stream(self) -> Result<(SendableRecordBatchStream, ScanProbe), Error> {
+        let task_ctx = Arc::new(self.data_frame.task_ctx());
+        let plan = self.data_frame.create_physical_plan().await?;
+        let stream = physical_plan::execute_stream(plan.clone(), task_ctx)?;
+
+        Ok((
+            stream,
+            ScanProbe {
+                plans: vec![plan],
+                cache_hit: self.cache_hit,
+            },
+        ))
+    }
+
+    /// Returns a stream holding one row every `stride` rows, see [`sample_stream`].
+    pub async fn sampled_stream(self, stride: usize) -> Result<SendableRecordBatchStream, Error> {
+        Ok(sample_stream(self.stream().await?, stride))
+    }
+
+    /// Returns the number of rows matching the current query, without consuming the result.
+    ///
+    /// Only the columns referenced by the filters are read to count the rows.
+    pub async fn matching_rows(&self) -> Result<usize, Error> {
+        Ok(self.data_frame.clone().count().await?)
+    }
+
+    /// Returns the number of rows matching the current query.
+    ///
+    /// If no filter was applied and the row count is known from the catalog (see
+    /// [`TimeseriesGatewayResult::with_row_count`]) no data is scanned.
     pub async fn count(self) -> Result<usize, Error> {
+        if let Some(row_count) = self.row_count {
+            return Ok(row_count);
+        }
         Ok(self.data_frame.count().await?)
     }
 
@@ -178,6 +514,398 @@ impl TimeseriesGatewayResult {
 
         Err(Error::NotFound)
     }
+
+    /// Returns the intervals of `range` where consecutive data timestamps are more than
+    /// `max_interval` apart, i.e. where samples are missing.
+    ///
+    /// Each gap is bounded by the samples surrounding it, the bounds of `range` are used for
+    /// the gaps before the first sample and after the last one. Only the timestamp column is
+    /// read, sorted by the query engine. Data outside `range` is expected to be already
+    /// filtered out.
+    pub async fn gaps(
+        self,
+        range: &types::TimestampRange,
+        max_interval: i64,
+    ) -> Result<Vec<types::TimestampRange>, Error> {
+        let mut stream = self
+            .data_frame
+            .select_columns(&[params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP])?
+            .sort(vec![
+                ident(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP).sort(true, false),
+            ])?
+            .execute_stream()
+            .await?;
+
+        let mut gaps = Vec::new();
+        let mut prev = i64::from(range.start);
+        while let Some(batch) = stream.next().await {
+            let batch = batch?;
+            let timestamps = batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .ok_or_else(|| {
+                    Error::bad_field(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP.to_owned())
+                })?;
+
+            for tstamp in timestamps.iter().flatten() {
+                if tstamp.saturating_sub(prev) > max_interval {
+                    gaps.push(types::TimestampRange::new(prev.into(), tstamp.into()));
+                }
+                prev = tstamp;
+            }
+        }
+
+        let end = i64::from(range.end);
+        if end.saturating_sub(prev) > max_interval {
+            gaps.push(types::TimestampRange::new(prev.into(), end.into()));
+        }
+
+        Ok(gaps)
+    }
+
+    /// Returns up to `limit` distinct non-null values of `column` in the data matching the
+    /// current query, in order of appearance, and whether the column holds more distinct
+    /// values than `limit`.
+    ///
+    /// Dictionary encoded columns are resolved from the dictionaries: only the keys used by
+    /// each batch are collected and each of them is decoded once. Other columns are scanned
+    /// value by value. In both cases the scan stops as soon as the limit is exceeded, so the
+    /// memory used is bounded by `limit`. Values without a query representation (e.g. binary
+    /// data) are not reported.
+    ///
+    /// Returns an [`Error::BadField`] if the column is not part of the schema.
+    pub async fn distinct(
+        self,
+        column: &str,
+        limit: usize,
+    ) -> Result<(Vec<query::Value>, bool), Error> {
+        if !self
+            .data_frame
+            .schema()
+            .has_column_with_unqualified_name(column)
+        {
+            return Err(Error::bad_field(column.to_owned()));
+        }
+
+        let mut stream = self
+            .data_frame
+            .select_columns(&[column])?
+            .execute_stream()
+            .await?;
+
+        let mut seen = HashSet::new();
+        let mut values = Vec::new();
+        let mut truncated = false;
+
+        'batches: while let Some(batch) = stream.next().await {
+            let batch = batch?;
+            let array = batch.column(0);
+
+            // Values are read from the dictionary, once for each key used by the valid rows
+            let (source, indices): (&dyn Array, Vec<usize>) = match array.as_any_dictionary_opt() {
+                Some(dictionary) => {
+                    let keys: BTreeSet<usize> = dictionary
+                        .normalized_keys()
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(row, _)| array.is_valid(*row))
+                        .map(|(_, key)| key)
+                        .collect();
+                    (dictionary.values().as_ref(), keys.into_iter().collect())
+                }
+                None => (
+                    array.as_ref(),
+                    (0..array.len())
+                        .filter(|row| array.is_valid(*row))
+                        .collect(),
+                ),
+            };
+
+            for idx in indices {
+                let value = ScalarValue::try_from_array(source, idx)?;
+                if value.is_null() || !seen.insert(value.clone()) {
+                    continue;
+                }
+                if seen.len() > limit {
+                    truncated = true;
+                    break 'batches;
+                }
+                values.extend(scalar_value_to_value(value));
+            }
+        }
+
+        Ok((values, truncated))
+    }
+
+    /// Returns the result of each aggregation over the data matching the current query, in
+    /// request order, see [`query::Aggregator`] for the in-process counterpart. Aggregations
+    /// without a result (e.g. over no rows) are [`None`].
+    ///
+    /// Returns an [`Error::BadField`] if a column is not part of the schema.
+    #[cfg(feature = "datafusion-engine")]
+    pub async fn aggregate(
+        self,
+        aggregates: &[query::Aggregate],
+    ) -> Result<Vec<Option<query::Value>>, Error> {
+        let schema = self.data_frame.schema();
+        if let Some(aggregate) = aggregates.iter().find(|a| {
+            let top_level = a.column.split('.').next().unwrap_or_default();
+            !schema.has_column_with_unqualified_name(top_level)
+        }) {
+            return Err(Error::bad_field(aggregate.column.clone()));
+        }
+
+        let exprs = aggregates
+            .iter()
+            .enumerate()
+            .map(|(idx, aggregate)| {
+                let column = unfold_column(&aggregate.column);
+                let expr = match aggregate.function {
+                    query::AggregateFunction::Count => count(column),
+                    query::AggregateFunction::Min => min(column),
+                    query::AggregateFunction::Max => max(column),
+                    query::AggregateFunction::Sum => sum(column),
+                    query::AggregateFunction::Mean => avg(column),
+                };
+                expr.alias(format!("aggregate_{idx}"))
+            })
+            .collect();
+
+        let batches = self.data_frame.aggregate(vec![], exprs)?.collect().await?;
+        let Some(batch) = batches.first() else {
+            return Err(Error::NotFound);
+        };
+
+        (0..aggregates.len())
+            .map(|idx| {
+                Ok(scalar_value_to_value(ScalarValue::try_from_array(
+                    batch.column(idx),
+                    0,
+                )?))
+            })
+            .collect()
+    }
+
+    /// Completes `profiler` by scanning the data matching the current query.
+    ///
+    /// Bounds and null counts are computed for [`rw::ColumnProfiler::columns_to_scan`], while
+    /// a distinct estimate is computed for [`rw::ColumnProfiler::distinct_columns`]. All the
+    /// aggregates are computed in a single pass over the data.
+    pub async fn profile(self, profiler: &mut rw::ColumnProfiler) -> Result<(), Error> {
+        let scan = profiler.columns_to_scan();
+        let distinct = profiler.distinct_columns();
+
+        if scan.is_empty() && distinct.is_empty() {
+            return Ok(());
+        }
+
+        let mut aggregates = vec![count(lit(1)).alias("rows")];
+        for (idx, column) in scan.iter().enumerate() {
+            aggregates.push(min(unfold_column(column)).alias(format!("min_{idx}")));
+            aggregates.push(max(unfold_column(column)).alias(format!("max_{idx}")));
+            aggregates.push(count(unfold_column(column)).alias(format!("count_{idx}")));
+        }
+        for (idx, column) in distinct.iter().enumerate() {
+            aggregates
+                .push(approx_distinct(unfold_column(column)).alias(format!("distinct_{idx}")));
+        }
+
+        let batches = self
+            .data_frame
+            .aggregate(vec![], aggregates)?
+            .collect()
+            .await?;
+        let Some(batch) = batches.first() else {
+            return Err(Error::NotFound);
+        };
+
+        let scalar = |idx: usize| ScalarValue::try_from_array(batch.column(idx), 0);
+        let rows = scalar_value_to_u64(scalar(0)?).unwrap_or_default();
+
+        for (idx, column) in scan.iter().enumerate() {
+            let offset = 1 + idx * 3;
+            let count = scalar_value_to_u64(scalar(offset + 2)?);
+            profiler.set_scanned(
+                column,
+                scalar_value_to_value(scalar(offset)?),
+                scalar_value_to_value(scalar(offset + 1)?),
+                count.map(|count| rows.saturating_sub(count)),
+            );
+        }
+
+        let offset = 1 + scan.len() * 3;
+        for (idx, column) in distinct.iter().enumerate() {
+            if let Some(estimate) = scalar_value_to_u64(scalar(offset + idx)?) {
+                profiler.set_distinct_estimate(column, estimate);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns a stream holding one row every `stride` rows of `stream`.
+///
+/// Rows are decimated while streaming, so only the sampled rows are sent to the clients.
+pub fn sample_stream(
+    stream: SendableRecordBatchStream,
+    stride: usize,
+) -> SendableRecordBatchStream {
+    let schema = stream.schema();
+
+    // Index of the first row of the current batch, across the whole stream
+    let mut offset = 0;
+    let stride = stride.max(1);
+
+    let sampled = stream.map(move |batch| {
+        let batch = batch?;
+
+        // First row of the current batch to be sampled
+        let first = (stride - offset % stride) % stride;
+        offset += batch.num_rows();
+
+        let indices = UInt32Array::from_iter_values(
+            (first..batch.num_rows())
+                .step_by(stride)
+                .map(|idx| idx as u32),
+        );
+
+        Ok(compute::take_record_batch(&batch, &indices)?)
+    });
+
+    Box::pin(RecordBatchStreamAdapter::new(schema, sampled))
+}
+
+/// Work done by the query engine to produce a result
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ScanStats {
+    /// Number of datafiles whose data was read, datafiles pruned by their statistics or never
+    /// reached are not counted
+    pub chunks_scanned: usize,
+    /// Number of bytes read from the datafiles
+    pub bytes_read: usize,
+    /// Number of rows decoded from the datafiles, including the ones discarded by the filters
+    pub rows_scanned: usize,
+    /// The unified schema of the datafiles was reused from the cache, so no datafile footer
+    /// was read to plan the query
+    pub cache_hit: bool,
+}
+
+/// Reports the work done by a query, see [`TimeseriesGatewayResult::probed_stream`].
+///
+/// The statistics are collected from the execution plan while the data is streamed, so they
+/// account only for the work done so far. Reading them after a stream ended early (e.g.
+/// truncated or cancelled) reports the data actually read.
+#[derive(Clone)]
+pub struct ScanProbe {
+    plans: Vec<Arc<dyn ExecutionPlan>>,
+    cache_hit: bool,
+}
+
+impl ScanProbe {
+    /// Combines the probes of multiple queries, reporting the work done by all of them. The
+    /// combined queries hit the cache only if all of them did.
+    pub fn merge(probes: impl IntoIterator<Item = ScanProbe>) -> Self {
+        let mut cache_hit = true;
+        let mut plans = Vec::new();
+        for probe in probes {
+            cache_hit &= probe.cache_hit;
+            plans.extend(probe.plans);
+        }
+        Self { plans, cache_hit }
+    }
+
+    pub fn stats(&self) -> ScanStats {
+        let mut stats = ScanStats {
+            cache_hit: self.cache_hit,
+            ..Default::default()
+        };
+        let mut chunks = HashSet::new();
+        for plan in &self.plans {
+            collect_scan_stats(plan, &mut stats, &mut chunks);
+        }
+        stats.chunks_scanned = chunks.len();
+        stats
+    }
+
+    /// Returns the keys of the datafiles whose data was read so far.
+    pub fn scanned_datafiles(&self) -> Vec<String> {
+        let mut chunks = HashSet::new();
+        for plan in &self.plans {
+            collect_scan_stats(plan, &mut ScanStats::default(), &mut chunks);
+        }
+        chunks.into_iter().collect()
+    }
+}
+
+/// Accumulates in `stats` the metrics of the datafile scans found in `plan`, the names of the
+/// datafiles read are collected in `chunks`.
+fn collect_scan_stats(
+    plan: &Arc<dyn ExecutionPlan>,
+    stats: &mut ScanStats,
+    chunks: &mut HashSet<String>,
+) {
+    if let Some(metrics) = plan.metrics() {
+        let sum = |name: &str| {
+            metrics
+                .sum_by_name(name)
+                .map(|v| v.as_usize())
+                .unwrap_or_default()
+        };
+
+        // The parquet reader reports the bytes read per datafile
+        for metric in metrics.iter() {
+            if metric.value().name() != "bytes_scanned" || metric.value().as_usize() == 0 {
+                continue;
+            }
+            if let Some(label) = metric.labels().iter().find(|l| l.name() == "filename") {
+                chunks.insert(label.value().to_owned());
+            }
+        }
+
+        stats.bytes_read += sum("bytes_scanned");
+        // Rows discarded by the filters pushed down into the reader are not part of the
+        // output of the scan
+        stats.rows_scanned += sum("pushdown_rows_pruned");
+        if plan.name() == "DataSourceExec" {
+            stats.rows_scanned += metrics.output_rows().unwrap_or_default();
+        }
+    }
+
+    for child in plan.children() {
+        collect_scan_stats(child, stats, chunks);
+    }
+}
+
+fn scalar_value_to_u64(value: ScalarValue) -> Option<u64> {
+    match value {
+        ScalarValue::UInt64(Some(v)) => Some(v),
+        ScalarValue::Int64(Some(v)) => u64::try_from(v).ok(),
+        _ => None,
+    }
+}
+
+/// Converts an aggregated value into a [`query::Value`], nulls and values without a query
+/// representation are converted to [`None`].
+fn scalar_value_to_value(value: ScalarValue) -> Option<query::Value> {
+    match value {
+        ScalarValue::Boolean(Some(v)) => Some(query::Value::Boolean(v)),
+        ScalarValue::Int8(Some(v)) => Some(query::Value::Integer(v.into())),
+        ScalarValue::Int16(Some(v)) => Some(query::Value::Integer(v.into())),
+        ScalarValue::Int32(Some(v)) => Some(query::Value::Integer(v.into())),
+        ScalarValue::Int64(Some(v)) => Some(query::Value::Integer(v)),
+        ScalarValue::UInt8(Some(v)) => Some(query::Value::Integer(v.into())),
+        ScalarValue::UInt16(Some(v)) => Some(query::Value::Integer(v.into())),
+        ScalarValue::UInt32(Some(v)) => Some(query::Value::Integer(v.into())),
+        ScalarValue::UInt64(Some(v)) => i64::try_from(v).ok().map(query::Value::Integer),
+        ScalarValue::Float32(Some(v)) => Some(query::Value::Float(v.into())),
+        ScalarValue::Float64(Some(v)) => Some(query::Value::Float(v)),
+        ScalarValue::Utf8(Some(v))
+        | ScalarValue::LargeUtf8(Some(v))
+        | ScalarValue::Utf8View(Some(v)) => Some(query::Value::Text(v)),
+        _ => None,
+    }
 }
 
 fn scalar_value_to_timestamp(value: ScalarValue) -> Option<types::Timestamp> {
@@ -188,7 +916,11 @@ fn scalar_value_to_timestamp(value: ScalarValue) -> Option<types::Timestamp> {
 }
 
 fn unfold_field(field: &query::OntologyField) -> Expr {
-    let mut fields = field.field().split(".");
+    unfold_column(field.field())
+}
+
+fn unfold_column(column: &str) -> Expr {
+    let mut fields = column.split(".");
     // By construction fields needs to have at least a value
     let mut col = col(fields.next().unwrap());
     for s in fields {
@@ -197,50 +929,59 @@ fn unfold_field(field: &query::OntologyField) -> Expr {
     col
 }
 
-fn expr_group_to_df_expr<V>(filter: query::OntologyExprGroup<V>) -> Option<Expr>
+fn op_to_df_expr<V>(column: Expr, op: query::Op<V>) -> Option<Expr>
 where
     V: Into<query::Value>,
 {
-    let mut ret: Option<Expr> = None;
-
-    for expr in filter.into_iter() {
-        let (field, op) = expr.into_parts();
-        let expr = match op {
-            query::Op::Eq(v) => Some(unfold_field(&field).eq(value_to_df_expr(v.into()))),
-            query::Op::Neq(v) => Some(unfold_field(&field).not_eq(value_to_df_expr(v.into()))),
-            query::Op::Leq(v) => Some(unfold_field(&field).lt_eq(value_to_df_expr(v.into()))),
-            query::Op::Geq(v) => Some(unfold_field(&field).gt_eq(value_to_df_expr(v.into()))),
-            query::Op::Lt(v) => Some(unfold_field(&field).lt(value_to_df_expr(v.into()))),
-            query::Op::Gt(v) => Some(unfold_field(&field).gt(value_to_df_expr(v.into()))),
-            query::Op::Ex => None,  // no-op
-            query::Op::Nex => None, // no-op
-            query::Op::Between(range) => {
-                let vmin: query::Value = range.min.into();
-                let vmax: query::Value = range.max.into();
-                let emin = unfold_field(&field).lt_eq(value_to_df_expr(vmax));
-                let emax = unfold_field(&field).gt_eq(value_to_df_expr(vmin));
-                Some(emin.and(emax))
-            }
-            query::Op::In(items) => {
-                let list = items
-                    .into_iter()
-                    .map(|v| value_to_df_expr(v.into()))
-                    .collect();
-                Some(unfold_field(&field).in_list(list, false))
-            }
-            query::Op::Match(v) => Some(unfold_field(&field).like(value_to_df_expr(v.into()))),
-        };
-
-        if let Some(expr) = expr {
-            if ret.is_none() {
-                ret = Some(expr);
-            } else {
-                ret = Some(ret.unwrap().and(expr));
-            }
+    match op {
+        query::Op::Eq(v) => Some(column.eq(value_to_df_expr(v.into()))),
+        query::Op::Neq(v) => Some(column.not_eq(value_to_df_expr(v.into()))),
+        query::Op::Leq(v) => Some(column.lt_eq(value_to_df_expr(v.into()))),
+        query::Op::Geq(v) => Some(column.gt_eq(value_to_df_expr(v.into()))),
+        query::Op::Lt(v) => Some(column.lt(value_to_df_expr(v.into()))),
+        query::Op::Gt(v) => Some(column.gt(value_to_df_expr(v.into()))),
+        query::Op::Ex => None,  // no-op
+        query::Op::Nex => None, // no-op
+        query::Op::Between(range) => {
+            let vmin: query::Value = range.min.into();
+            let vmax: query::Value = range.max.into();
+            let emin = column.clone().lt_eq(value_to_df_expr(vmax));
+            let emax = column.gt_eq(value_to_df_expr(vmin));
+            Some(emin.and(emax))
+        }
+        query::Op::In(items) => {
+            let list = items
+                .into_iter()
+                .map(|v| value_to_df_expr(v.into()))
+                .collect();
+            Some(column.in_list(list, false))
         }
+        query::Op::Match(v) => Some(column.like(value_to_df_expr(v.into()))),
     }
+}
+
+/// Combines a list of expressions in AND
+fn and_all(exprs: impl Iterator<Item = Expr>) -> Option<Expr> {
+    exprs.reduce(|acc, expr| acc.and(expr))
+}
+
+fn expr_group_to_df_expr<V>(filter: query::OntologyExprGroup<V>) -> Option<Expr>
+where
+    V: Into<query::Value>,
+{
+    and_all(filter.into_iter().filter_map(|expr| {
+        let (field, op) = expr.into_parts();
+        op_to_df_expr(unfold_field(&field), op)
+    }))
+}
 
-    ret
+fn row_filter_to_df_expr(filter: query::RowFilter) -> Option<Expr> {
+    and_all(
+        filter
+            .group
+            .into_iter()
+            .filter_map(|expr| op_to_df_expr(unfold_column(&expr.column), expr.op)),
+    )
 }
 
 fn value_to_df_expr(v: query::Value) -> Expr {
@@ -303,4 +1044,770 @@ mod tests {
         assert_eq!(ts_range.start, 10010.into());
         assert_eq!(ts_range.end, 10020.into());
     }
+
+    /// Filters rows with an equality constraint on a text column
+    /// The row count of the catalog answers the count of an unfiltered result without
+    /// scanning the data, while a filtered result is counted by scanning it
+    #[tokio::test]
+    async fn count_from_row_count() {
+        let file_path = "dummy_file.parquet";
+
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        write_dummy_file(&store, file_path).await;
+
+        let ts_gw = TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let unfiltered = ts_gw
+            .read(file_path, rw::Format::Default, None)
+            .await
+            .unwrap()
+            .with_row_count(100);
+
+        let expr_grp = query::OntologyExprGroup::new(vec![
+            (
+                OntologyField::try_new("tag.value".to_owned()).unwrap(),
+                query::Op::Between(query::Range::try_new(3, 5).unwrap()),
+            )
+                .into(),
+        ]);
+        let filtered = ts_gw
+            .read(file_path, rw::Format::Default, None)
+            .await
+            .unwrap()
+            .with_row_count(100)
+            .filter(expr_grp)
+            .unwrap();
+
+        // The file holds 7 rows, a count of 100 can only come from the provided row count
+        assert_eq!(unfiltered.count().await.unwrap(), 100);
+        assert_eq!(filtered.count().await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn row_filter_eq() {
+        use ::arrow::array::{Int64Array, RecordBatch, StringArray};
+        use ::arrow::datatypes::{DataType, Field};
+        use parquet::arrow::arrow_writer::ArrowWriter;
+
+        let file_path = "sensors.parquet";
+
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("sensor_id", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![10000, 10005, 10010, 10015])),
+                Arc::new(StringArray::from(vec!["A", "B", "A", "C"])),
+            ],
+        )
+        .unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buffer, schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        store.write_to_path(file_path, buffer).await.unwrap();
+
+        let ts_gw = TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let res = ts_gw
+            .read(file_path, rw::Format::Default, None)
+            .await
+            .unwrap();
+
+        let filter = query::RowFilter::new(vec![
+            query::ColumnExpr::try_new("sensor_id".to_owned(), query::Op::Eq("A".into())).unwrap(),
+        ]);
+
+        let res = res.filter_rows(filter).unwrap();
+
+        assert_eq!(res.count().await.unwrap(), 2);
+    }
+
+    /// Projects a five-column datafile to a two-column subset
+    #[tokio::test]
+    async fn projection() {
+        use ::arrow::array::{Float64Array, Int64Array, RecordBatch};
+        use ::arrow::datatypes::{DataType, Field};
+        use futures::TryStreamExt;
+        use parquet::arrow::arrow_writer::ArrowWriter;
+
+        let file_path = "wide.parquet";
+
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let mut fields = vec![Field::new(
+            params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+            DataType::Int64,
+            false,
+        )];
+        let mut columns: Vec<::arrow::array::ArrayRef> =
+            vec![Arc::new(Int64Array::from(vec![10000, 10005, 10010]))];
+        for name in ["a", "b", "c", "d"] {
+            fields.push(Field::new(name, DataType::Float64, false));
+            columns.push(Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0])));
+        }
+        let schema = Arc::new(Schema::new(fields));
+        let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buffer, schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        store.write_to_path(file_path, buffer).await.unwrap();
+
+        let ts_gw = TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let read = async || {
+            ts_gw
+                .read(file_path, rw::Format::Default, None)
+                .await
+                .unwrap()
+        };
+
+        let res = read()
+            .await
+            .project(&[
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP.to_owned(),
+                "c".to_owned(),
+            ])
+            .unwrap();
+
+        // Names and types of the fields of a schema, the nullability is not checked since it
+        // depends on the schema unification of the datafiles
+        let fields = |schema: &Schema| -> Vec<(String, DataType)> {
+            schema
+                .fields()
+                .iter()
+                .map(|f| (f.name().clone(), f.data_type().clone()))
+                .collect()
+        };
+        let expected = vec![
+            (
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP.to_owned(),
+                DataType::Int64,
+            ),
+            ("c".to_owned(), DataType::Float64),
+        ];
+        assert_eq!(
+            fields(&res.schema_with_metadata(HashMap::new())),
+            expected,
+            "unprojected columns are still in the result schema"
+        );
+
+        let batches: Vec<RecordBatch> = res.stream().await.unwrap().try_collect().await.unwrap();
+        for batch in &batches {
+            assert_eq!(fields(&batch.schema()), expected);
+            for unprojected in ["a", "b", "d"] {
+                assert!(batch.column_by_name(unprojected).is_none());
+            }
+        }
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 3);
+
+        assert!(matches!(
+            read().await.project(&["missing".to_owned()]),
+            Err(Error::BadField { .. })
+        ));
+    }
+
+    /// Writes a datafile holding the timestamp column and `column` named `name`.
+    async fn write_column_file(
+        store: &store::Store,
+        file_path: &str,
+        name: &str,
+        column: ::arrow::array::ArrayRef,
+    ) {
+        use ::arrow::array::RecordBatch;
+        use ::arrow::datatypes::{DataType, Field};
+        use parquet::arrow::arrow_writer::ArrowWriter;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new(name, column.data_type().clone(), false),
+        ]));
+        let timestamps = Int64Array::from_iter_values(0..column.len() as i64);
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(timestamps), column]).unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buffer, schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        store.write_to_path(file_path, buffer).await.unwrap();
+    }
+
+    /// Collects the distinct values of a low-cardinality dictionary encoded column
+    #[tokio::test]
+    async fn distinct_dictionary() {
+        use ::arrow::array::DictionaryArray;
+        use ::arrow::datatypes::Int32Type;
+
+        let file_path = "sensors.parquet";
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sensors: DictionaryArray<Int32Type> =
+            ["A", "B", "A", "C", "B", "A"].into_iter().collect();
+        write_column_file(&store, file_path, "sensor_id", Arc::new(sensors)).await;
+
+        let ts_gw = TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let read = async || {
+            ts_gw
+                .read(file_path, rw::Format::Default, None)
+                .await
+                .unwrap()
+        };
+
+        let (values, truncated) = read().await.distinct("sensor_id", 10).await.unwrap();
+        let mut values: Vec<_> = values
+            .into_iter()
+            .map(|v| match v {
+                query::Value::Text(v) => v,
+                v => panic!("unexpected value {:?}", v),
+            })
+            .collect();
+        values.sort();
+        assert_eq!(values, vec!["A", "B", "C"]);
+        assert!(!truncated);
+
+        // Only the values of the rows in range are reported
+        let (values, _) = read()
+            .await
+            .filter_rows(query::RowFilter::timestamp_range(
+                &types::TimestampRange::new(0.into(), 1.into()),
+            ))
+            .unwrap()
+            .distinct("sensor_id", 10)
+            .await
+            .unwrap();
+        assert_eq!(values.len(), 2);
+
+        assert!(matches!(
+            read().await.distinct("missing", 10).await,
+            Err(Error::BadField { .. })
+        ));
+    }
+
+    /// Stops collecting the distinct values of a high-cardinality column at the limit
+    #[tokio::test]
+    async fn distinct_limit() {
+        let file_path = "counter.parquet";
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let counter = Int64Array::from_iter_values(0..1000);
+        write_column_file(&store, file_path, "counter", Arc::new(counter)).await;
+
+        let ts_gw = TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let (values, truncated) = ts_gw
+            .read(file_path, rw::Format::Default, None)
+            .await
+            .unwrap()
+            .distinct("counter", 10)
+            .await
+            .unwrap();
+
+        assert_eq!(values.len(), 10);
+        assert!(truncated);
+    }
+
+    /// Appends a constant column to the data rows
+    #[tokio::test]
+    async fn constant_column() {
+        use ::arrow::array::{Array, StringArray};
+        use futures::TryStreamExt;
+
+        let file_path = "dummy_file.parquet";
+
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        write_dummy_file(&store, file_path).await;
+
+        let ts_gw = TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let res = ts_gw
+            .read(file_path, rw::Format::Default, None)
+            .await
+            .unwrap();
+
+        let res = res
+            .with_constant_column(
+                query::ProvenanceColumn::Topic.name(),
+                query::Value::Text("test_sequence/test_topic".to_owned()),
+            )
+            .unwrap();
+
+        let batches: Vec<_> = res.stream().await.unwrap().try_collect().await.unwrap();
+
+        let mut rows = 0;
+        for batch in batches {
+            let column = batch
+                .column_by_name("_topic")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            rows += column.len();
+            assert!(column.iter().all(|v| v == Some("test_sequence/test_topic")));
+        }
+        assert_eq!(rows, 7);
+    }
+
+    /// Appending a column with an existing name fails
+    #[tokio::test]
+    async fn constant_column_collision() {
+        let file_path = "dummy_file.parquet";
+
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        write_dummy_file(&store, file_path).await;
+
+        let ts_gw = TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let res = ts_gw
+            .read(file_path, rw::Format::Default, None)
+            .await
+            .unwrap();
+
+        let res = res.with_constant_column("value", query::Value::Integer(0));
+
+        assert!(matches!(res, Err(Error::ColumnCollision(_))));
+    }
+
+    /// Writes two chunks with interleaved timestamps in the `topic` folder
+    async fn write_interleaved_chunks(store: &store::Store) {
+        use ::arrow::array::{Int64Array, RecordBatch};
+        use ::arrow::datatypes::{DataType, Field};
+        use parquet::arrow::arrow_writer::ArrowWriter;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("value", DataType::Int64, false),
+        ]));
+
+        let chunks = [
+            ("topic/data-00000.parquet", vec![30, 10, 50], vec![3, 7, 5]),
+            ("topic/data-00001.parquet", vec![20, 40], vec![9, 1]),
+        ];
+        for (path, timestamps, values) in chunks {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(timestamps)),
+                    Arc::new(Int64Array::from(values)),
+                ],
+            )
+            .unwrap();
+
+            let mut buffer = Vec::new();
+            let mut writer = ArrowWriter::try_new(&mut buffer, schema.clone(), None).unwrap();
+            writer.write(&batch).unwrap();
+            writer.close().unwrap();
+            store.write_to_path(path, buffer).await.unwrap();
+        }
+    }
+
+    /// Collects the values of an Int64 column
+    async fn int_column(res: TimeseriesGatewayResult, column: &str) -> Vec<i64> {
+        use ::arrow::array::{Int64Array, RecordBatch};
+        use futures::TryStreamExt;
+
+        let batches: Vec<RecordBatch> = res.stream().await.unwrap().try_collect().await.unwrap();
+        batches
+            .iter()
+            .flat_map(|b| {
+                b.column_by_name(column)
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    /// Rows from different chunks are returned by ascending timestamp by default
+    #[tokio::test]
+    async fn default_time_order() {
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        write_interleaved_chunks(&store).await;
+
+        let ts_gw = TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let res = ts_gw
+            .read("topic", rw::Format::Default, None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            int_column(res, params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP).await,
+            vec![10, 20, 30, 40, 50]
+        );
+    }
+
+    /// Chunks internally sorted with overlapping time ranges are merged, not sorted
+    #[tokio::test]
+    async fn merge_sorted_chunks() {
+        use ::arrow::array::{Int64Array, RecordBatch};
+        use ::arrow::datatypes::{DataType, Field};
+
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("value", DataType::Int64, false),
+        ]));
+        let chunks = [
+            ("topic/data-00000.parquet", vec![10, 30, 50], vec![1, 3, 5]),
+            ("topic/data-00001.parquet", vec![20, 40, 60], vec![2, 4, 6]),
+        ];
+        for (path, timestamps, values) in chunks {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(timestamps)),
+                    Arc::new(Int64Array::from(values)),
+                ],
+            )
+            .unwrap();
+
+            let mut writer = rw::ChunkWriter::try_new(schema.clone(), rw::Format::Default).unwrap();
+            writer.write(&batch).unwrap();
+            let (buffer, _, _) = writer.finalize().unwrap();
+            store.write_to_path(path, buffer).await.unwrap();
+        }
+
+        let ts_gw = TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let res = ts_gw
+            .read("topic", rw::Format::Default, None)
+            .await
+            .unwrap();
+
+        let plan = res.data_frame.clone().create_physical_plan().await.unwrap();
+        let plan = datafusion::physical_plan::displayable(plan.as_ref())
+            .indent(true)
+            .to_string();
+        assert!(!plan.contains("SortExec:"), "{plan}");
+
+        assert_eq!(int_column(res, "value").await, vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    /// Rows are sorted by descending value
+    #[tokio::test]
+    async fn sort_descending() {
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        write_interleaved_chunks(&store).await;
+
+        let ts_gw = TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let read = async || {
+            ts_gw
+                .read("topic", rw::Format::Default, None)
+                .await
+                .unwrap()
+        };
+
+        let res = read()
+            .await
+            .sort(&query::OrderBy::desc("value".to_owned()))
+            .unwrap();
+        assert_eq!(int_column(res, "value").await, vec![9, 7, 5, 3, 1]);
+
+        assert!(matches!(
+            read()
+                .await
+                .sort(&query::OrderBy::asc("missing".to_owned())),
+            Err(Error::BadField { .. })
+        ));
+    }
+
+    /// Writes two chunks of the dummy batch in the `topic` folder (14 rows)
+    async fn write_dummy_chunks(store: &store::Store) {
+        write_dummy_file(store, "topic/data-00000.parquet").await;
+        write_dummy_file(store, "topic/data-00001.parquet").await;
+    }
+
+    async fn sampled_rows(store: &store::testing::Store, sample: query::Sample) -> usize {
+        use futures::TryStreamExt;
+
+        let ts_gw = TimeseriesGateway::try_new((**store).clone()).unwrap();
+
+        // Use a small batch size to sample across several batches
+        let res = ts_gw
+            .read("topic", rw::Format::Default, Some(3))
+            .await
+            .unwrap();
+
+        let batches: Vec<_> = res
+            .sampled_stream(sample.stride(14))
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+
+        batches.iter().map(|b| b.num_rows()).sum()
+    }
+
+    /// Samples one row every three rows over multiple chunks
+    #[tokio::test]
+    async fn sample_every_nth() {
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        write_dummy_chunks(&store).await;
+
+        // Rows 0, 3, 6, 9, 12
+        assert_eq!(sampled_rows(&store, query::Sample::EveryNth(3)).await, 5);
+    }
+
+    /// Samples a target number of rows over multiple chunks
+    #[tokio::test]
+    async fn sample_target_count() {
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        write_dummy_chunks(&store).await;
+
+        assert_eq!(sampled_rows(&store, query::Sample::TargetCount(4)).await, 4);
+    }
+
+    /// Filters rows with a range constraint on a value column
+    #[tokio::test]
+    async fn row_filter_range() {
+        let file_path = "dummy_file.parquet";
+
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        write_dummy_file(&store, file_path).await;
+
+        let ts_gw = TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let res = ts_gw
+            .read(file_path, rw::Format::Default, None)
+            .await
+            .unwrap();
+
+        let filter = query::RowFilter::new(vec![
+            query::ColumnExpr::try_new("value".to_owned(), query::Op::Gt(query::Value::Integer(2)))
+                .unwrap(),
+            query::ColumnExpr::try_new(
+                "value".to_owned(),
+                query::Op::Leq(query::Value::Integer(5)),
+            )
+            .unwrap(),
+        ]);
+
+        let res = res.filter_rows(filter).unwrap();
+
+        // values in (2, 5] are found in timestamps [10010, 10020]
+        assert_eq!(res.count().await.unwrap(), 3);
+    }
+
+    /// With the page index the reader fetches only the pages of the chunk which can match a
+    /// value filter, for every format
+    #[tokio::test]
+    async fn page_index_skips_pages_on_read() {
+        use ::arrow::array::{ArrayRef, Int64Array, RecordBatch};
+        use ::arrow::datatypes::{DataType, Field};
+
+        let backend =
+            Arc::new(store::testing::HookedStore::<store::testing::CountingReads>::default());
+        let store: store::StoreRef = Arc::new(store::testing::with_driver(backend.clone()));
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("value", DataType::Int64, false),
+        ]));
+
+        // Bytes read counting the rows with `value < threshold`
+        let bytes_read = async |format: rw::Format, page_index: bool, threshold: i64| {
+            let mut writer =
+                rw::ChunkWriter::try_new_with_page_index(schema.clone(), format, page_index)
+                    .unwrap();
+            for start in (0..60_000).step_by(1000) {
+                let values: ArrayRef = Arc::new(Int64Array::from_iter_values(start..start + 1000));
+                let batch =
+                    RecordBatch::try_new(schema.clone(), vec![values.clone(), values]).unwrap();
+                writer.write(&batch).unwrap();
+            }
+            let (buffer, _, _) = writer.finalize().unwrap();
+            let path = format!("chunk.{}", format.as_extension());
+            store.write_bytes(&path, buffer).await.unwrap();
+
+            let ts_gw = TimeseriesGateway::try_new(store.clone()).unwrap();
+            let filter = query::RowFilter::new(vec![
+                query::ColumnExpr::try_new(
+                    "value".to_owned(),
+                    query::Op::Lt(query::Value::Integer(threshold)),
+                )
+                .unwrap(),
+            ]);
+            let res = ts_gw
+                .read(&path, format, None)
+                .await
+                .unwrap()
+                .filter_rows(filter)
+                .unwrap();
+
+            backend.hooks.reset();
+            assert_eq!(res.count().await.unwrap(), threshold as usize);
+            backend.hooks.bytes_read()
+        };
+
+        for format in rw::Format::ALL {
+            let indexed = bytes_read(format, true, 100).await;
+            assert!(
+                indexed < bytes_read(format, true, 60_000).await,
+                "{format:?}"
+            );
+
+            // Ragged and image chunks have no page statistics on the value column otherwise
+            let plain = bytes_read(format, false, 100).await;
+            if format == rw::Format::Default {
+                assert_eq!(indexed, plain);
+            } else {
+                assert!(indexed < plain, "{format:?}");
+            }
+        }
+    }
+
+    /// Chunks written with differing nullability of the same column (also nested) are read
+    /// together, the column is nullable in the result
+    #[tokio::test]
+    async fn mixed_nullability() {
+        use ::arrow::array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StructArray};
+        use ::arrow::datatypes::{DataType, Field, Fields};
+        use parquet::arrow::arrow_writer::ArrowWriter;
+
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let chunks = [
+            ("topic/data-00000.parquet", false, vec![Some(1), Some(2)]),
+            ("topic/data-00001.parquet", true, vec![None, Some(4)]),
+        ];
+        for (idx, (path, nullable, values)) in chunks.into_iter().enumerate() {
+            let pose_fields = Fields::from(vec![Field::new("x", DataType::Float64, nullable)]);
+            let schema = Arc::new(Schema::new(vec![
+                Field::new(
+                    params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                    DataType::Int64,
+                    false,
+                ),
+                Field::new("value", DataType::Int64, nullable),
+                Field::new("pose", DataType::Struct(pose_fields.clone()), false),
+            ]));
+
+            let pose = StructArray::new(
+                pose_fields,
+                vec![Arc::new(Float64Array::from(vec![
+                    Some(0.5),
+                    values[1].map(|v| v as f64),
+                ])) as ArrayRef],
+                None,
+            );
+            let base = idx as i64 * 10;
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(vec![base, base + 1])),
+                    Arc::new(Int64Array::from(values)),
+                    Arc::new(pose),
+                ],
+            )
+            .unwrap();
+
+            let mut buffer = Vec::new();
+            let mut writer = ArrowWriter::try_new(&mut buffer, schema, None).unwrap();
+            writer.write(&batch).unwrap();
+            writer.close().unwrap();
+            store.write_to_path(path, buffer).await.unwrap();
+        }
+
+        let ts_gw = TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let res = ts_gw
+            .read("topic", rw::Format::Default, None)
+            .await
+            .unwrap();
+
+        let schema = res.schema_with_metadata(HashMap::new());
+        assert!(schema.field_with_name("value").unwrap().is_nullable());
+        let DataType::Struct(pose) = schema.field_with_name("pose").unwrap().data_type() else {
+            panic!("`pose` is not a struct");
+        };
+        assert!(pose[0].is_nullable());
+
+        assert_eq!(res.count().await.unwrap(), 4);
+    }
+
+    /// The cached inspection of the datafiles is reused until a datafile is added or rewritten
+    #[tokio::test]
+    async fn cached_inspection() {
+        use ::arrow::array::{Int64Array, RecordBatch};
+        use ::arrow::datatypes::{DataType, Field};
+        use parquet::arrow::arrow_writer::ArrowWriter;
+
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        write_interleaved_chunks(&store).await;
+
+        let ts_gw = TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let inspect = || ts_gw.inspect_datafiles(Path::new("topic"), rw::Format::Default);
+
+        let inspection = inspect().await.unwrap();
+        assert!(!inspection.cached);
+        assert!(inspection.schema.unwrap().field_with_name("extra").is_err());
+        assert_eq!(ts_gw.inspections.lock().unwrap().len(), 1);
+        assert!(inspect().await.unwrap().cached);
+
+        // A datafile rewritten with an additional column changes the unified schema
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("value", DataType::Int64, false),
+            Field::new("extra", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![20, 40])),
+                Arc::new(Int64Array::from(vec![9, 1])),
+                Arc::new(Int64Array::from(vec![0, 0])),
+            ],
+        )
+        .unwrap();
+        let mut buffer = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buffer, schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        store
+            .write_to_path("topic/data-00001.parquet", buffer)
+            .await
+            .unwrap();
+
+        let inspection = inspect().await.unwrap();
+        assert!(!inspection.cached);
+        assert!(inspection.schema.unwrap().field_with_name("extra").is_ok());
+        assert_eq!(ts_gw.inspections.lock().unwrap().len(), 1);
+    }
 }
@@ -7,10 +7,12 @@
 
 use log::debug;
 use sqlx::Pool;
+use std::path::PathBuf;
+use std::sync::Arc;
 use url::Url;
 
 use super::Error;
-use crate::params;
+use crate::{params, types};
 
 /// The concrete database type used throughout this module.
 pub type Database = sqlx::Postgres;
@@ -74,9 +76,34 @@ pub struct Config {
     pub db_url: Url,
 }
 
+/// Resolves the location in the store of the metadata file of a resource.
+pub trait MetadataPathResolver: Send + Sync {
+    fn metadata_path(&self, resource: &dyn types::Resource) -> PathBuf;
+}
+
+/// Places the metadata file next to the resource data, see [`types::Resource::metadata`].
+pub struct DefaultMetadataPath;
+
+impl MetadataPathResolver for DefaultMetadataPath {
+    fn metadata_path(&self, resource: &dyn types::Resource) -> PathBuf {
+        resource.metadata()
+    }
+}
+
+/// Places the metadata files under a dedicated prefix, separated from the resources data
+/// (e.g. `<prefix>/my_sequence/metadata.json`).
+pub struct PrefixedMetadataPath(pub PathBuf);
+
+impl MetadataPathResolver for PrefixedMetadataPath {
+    fn metadata_path(&self, resource: &dyn types::Resource) -> PathBuf {
+        self.0.join(resource.metadata())
+    }
+}
+
 #[derive(Clone)]
 pub struct Repository {
     pub(super) pool: Pool<Database>,
+    metadata_resolver: Arc<dyn MetadataPathResolver>,
 }
 
 impl Repository {
@@ -91,7 +118,27 @@ impl Repository {
         debug!("running migrations");
         sqlx::migrate!().run(&pool).await?;
 
-        Ok(Self { pool })
+        let metadata_resolver: Arc<dyn MetadataPathResolver> =
+            match &params::configurables().metadata_prefix {
+                Some(prefix) => Arc::new(PrefixedMetadataPath(prefix.clone())),
+                None => Arc::new(DefaultMetadataPath),
+            };
+
+        Ok(Self {
+            pool,
+            metadata_resolver,
+        })
+    }
+
+    /// Overrides the resolver used to locate the metadata files of the resources.
+    pub fn with_metadata_resolver(mut self, resolver: Arc<dyn MetadataPathResolver>) -> Self {
+        self.metadata_resolver = resolver;
+        self
+    }
+
+    /// Returns the location in the store of the metadata file of `resource`.
+    pub fn metadata_path(&self, resource: &dyn types::Resource) -> PathBuf {
+        self.metadata_resolver.metadata_path(resource)
     }
 
     /// Builds a transaction.
@@ -104,6 +151,15 @@ impl Repository {
         })
     }
 
+    /// Builds a transaction spanning the repository and the `store`, used to mutate several
+    /// resources atomically (see [`super::FacadeTransaction`]).
+    pub async fn atomic(
+        &self,
+        store: crate::store::StoreRef,
+    ) -> Result<super::FacadeTransaction<'_>, super::FacadeError> {
+        super::FacadeTransaction::begin(self, store).await
+    }
+
     /// Returns a connection to perform operations on the repository.
     ///
     /// This call should be used when performing **read-only** operations on the repository.
@@ -126,10 +182,22 @@ pub mod testing {
         /// Creates a new [`Repository`] instance for testing using the provided database pool.
         pub fn new(pool: sqlx::Pool<super::Database>) -> Self {
             Self {
-                inner: super::Repository { pool },
+                inner: super::Repository {
+                    pool,
+                    metadata_resolver: std::sync::Arc::new(super::DefaultMetadataPath),
+                },
             }
         }
 
+        /// Overrides the resolver used to locate the metadata files of the resources.
+        pub fn with_metadata_resolver(
+            mut self,
+            resolver: std::sync::Arc<dyn super::MetadataPathResolver>,
+        ) -> Self {
+            self.inner = self.inner.with_metadata_resolver(resolver);
+            self
+        }
+
         /// Provides access to the inner database pool.
         pub fn pool(&self) -> &sqlx::Pool<super::Database> {
             &self.inner.pool
@@ -1,5 +1,5 @@
 use super::FacadeError;
-use crate::{repo, types};
+use crate::{repo, rw, types, utils};
 
 pub struct FacadeChunk<'a> {
     tx: repo::Tx<'a>,
@@ -7,79 +7,114 @@ pub struct FacadeChunk<'a> {
 }
 
 impl<'a> FacadeChunk<'a> {
+    /// Registers a new chunk, `content_hash` is the hash of the serialized chunk (see
+    /// [`crate::rw::ChunkMetadata`]) if known.
     pub async fn create(
         topic_id: i32,
         datafile: impl AsRef<std::path::Path>,
         size_bytes: i64,
         row_count: i64,
+        content_hash: Option<utils::hash::Digest>,
         repo: &'a repo::Repository,
     ) -> Result<Self, FacadeError> {
         let mut tx = repo.transaction().await?;
 
-        let chunk = repo::chunk_create(
-            &mut tx,
-            &repo::Chunk::new(topic_id, datafile, size_bytes, row_count),
-        )
-        .await?;
+        let mut chunk = repo::Chunk::new(topic_id, datafile, size_bytes, row_count);
+        if let Some(content_hash) = content_hash {
+            chunk = chunk.with_content_hash(content_hash);
+        }
+
+        let chunk = repo::chunk_create(&mut tx, &chunk).await?;
 
         Ok(Self { tx, chunk })
     }
 
+    /// Registers a new chunk along with its column statistics in the transaction `tx`, used to
+    /// register or replace several chunks atomically.
+    pub async fn create_in(
+        tx: &mut repo::Tx<'_>,
+        topic_id: i32,
+        datafile: impl AsRef<std::path::Path>,
+        metadata: &rw::ChunkMetadata,
+        ontology_tag: &str,
+        cstats: types::ColumnsStats,
+    ) -> Result<(), FacadeError> {
+        let chunk = repo::Chunk::new(
+            topic_id,
+            datafile,
+            metadata.size_bytes as i64,
+            metadata.row_count as i64,
+        )
+        .with_content_hash(metadata.content_hash);
+
+        let chunk = repo::chunk_create(tx, &chunk).await?;
+        push_stats(tx, chunk.chunk_id, ontology_tag, cstats).await
+    }
+
     /// Push all column statistics using batch inserts for better performance.
-    /// This method collects all stats, resolves column IDs, then performs
-    /// two batch INSERT operations (one for numeric, one for literal stats).
     pub async fn push_all_stats(
         &mut self,
         ontology_tag: &str,
         cstats: types::ColumnsStats,
     ) -> Result<(), FacadeError> {
-        let mut numeric_batch: Vec<repo::ColumnChunkNumeric> = Vec::new();
-        let mut literal_batch: Vec<repo::ColumnChunkLiteral> = Vec::new();
+        push_stats(&mut self.tx, self.chunk.chunk_id, ontology_tag, cstats).await
+    }
 
-        // First pass: resolve column IDs and collect stats for batch insert
-        for (field, stats) in cstats.stats {
-            if stats.is_unsupported() {
-                continue;
-            }
+    pub async fn finalize(self) -> Result<(), FacadeError> {
+        self.tx.commit().await?;
+        Ok(())
+    }
+}
 
-            let column = repo::column_get_or_create(&mut self.tx, &field, ontology_tag).await?;
-
-            match stats {
-                types::Stats::Text(stats) => {
-                    let (min, max, has_null) = stats.into_owned();
-                    literal_batch.push(repo::ColumnChunkLiteral::try_new(
-                        column.column_id,
-                        self.chunk.chunk_id,
-                        min,
-                        max,
-                        has_null,
-                    )?);
-                }
-                types::Stats::Numeric(stats) => {
-                    numeric_batch.push(repo::ColumnChunkNumeric::new(
-                        column.column_id,
-                        self.chunk.chunk_id,
-                        stats.min,
-                        stats.max,
-                        stats.has_null,
-                        stats.has_nan,
-                    ));
-                }
-                types::Stats::Unsupported => {}
-            }
-        }
+/// Collects all the column statistics of the chunk `chunk_id`, resolves the column IDs, then
+/// performs two batch INSERT operations (one for numeric, one for literal stats).
+async fn push_stats(
+    tx: &mut repo::Tx<'_>,
+    chunk_id: i32,
+    ontology_tag: &str,
+    cstats: types::ColumnsStats,
+) -> Result<(), FacadeError> {
+    let mut numeric_batch: Vec<repo::ColumnChunkNumeric> = Vec::new();
+    let mut literal_batch: Vec<repo::ColumnChunkLiteral> = Vec::new();
 
-        // Batch insert all numeric stats in one query
-        repo::column_chunk_numeric_create_batch(&mut self.tx, &numeric_batch).await?;
+    // First pass: resolve column IDs and collect stats for batch insert
+    for (field, stats) in cstats.stats {
+        if stats.is_unsupported() {
+            continue;
+        }
 
-        // Batch insert all literal stats in one query
-        repo::column_chunk_literal_create_batch(&mut self.tx, &literal_batch).await?;
+        let column = repo::column_get_or_create(tx, &field, ontology_tag).await?;
 
-        Ok(())
+        match stats {
+            types::Stats::Text(stats) => {
+                let (min, max, has_null) = stats.into_owned();
+                literal_batch.push(repo::ColumnChunkLiteral::try_new(
+                    column.column_id,
+                    chunk_id,
+                    min,
+                    max,
+                    has_null,
+                )?);
+            }
+            types::Stats::Numeric(stats) => {
+                numeric_batch.push(repo::ColumnChunkNumeric::new(
+                    column.column_id,
+                    chunk_id,
+                    stats.min,
+                    stats.max,
+                    stats.has_null,
+                    stats.has_nan,
+                ));
+            }
+            types::Stats::Unsupported => {}
+        }
     }
 
-    pub async fn finalize(self) -> Result<(), FacadeError> {
-        self.tx.commit().await?;
-        Ok(())
-    }
+    // Batch insert all numeric stats in one query
+    repo::column_chunk_numeric_create_batch(tx, &numeric_batch).await?;
+
+    // Batch insert all literal stats in one query
+    repo::column_chunk_literal_create_batch(tx, &literal_batch).await?;
+
+    Ok(())
 }
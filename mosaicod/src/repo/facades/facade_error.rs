@@ -18,6 +18,8 @@ pub enum FacadeError {
     MetadataError(#[from] crate::types::MetadataError),
     #[error("repository error :: {0}")]
     RepositoryError(#[from] crate::repo::Error),
+    #[error("resource `{0}` already exists")]
+    AlreadyExists(String),
     #[error("sequence locked, unable to perform modifications")]
     SequenceLocked,
     #[error("concurrecy error :: {0}")]
@@ -26,8 +28,20 @@ pub enum FacadeError {
     QueryError(#[from] crate::query::Error),
     #[error("topic locked, unable to perform modifications")]
     TopicLocked,
+    #[error("topic is being written by `{0}`, unable to perform modifications")]
+    TopicLeased(String),
     #[error("topic unlocked, unable to perform the requested operation over an unlocked topic")]
     TopicUnlocked,
+    #[error(transparent)]
+    InvalidTransition(#[from] crate::types::InvalidTransition),
+    #[error("idempotency key `{0}` already used by a different request")]
+    IdempotencyKeyMismatch(String),
+    #[error("idempotency key `{0}` used by a request still in progress")]
+    IdempotencyKeyInUse(String),
+    #[error("invalid path `{0}`")]
+    InvalidPath(String),
+    #[error("invalid argument :: {0}")]
+    InvalidArgument(String),
     #[error("unimplemented")]
     Unimplemented,
     #[error("unauthorized")]
@@ -0,0 +1,102 @@
+use crate::{params, repo, types};
+
+use super::FacadeError;
+
+/// Facade used to record the responses of completed mutating actions, allowing to return
+/// the original response when a request is retried with the same idempotency key.
+///
+/// The key is reserved before the action is executed, along with the name and the body hash of
+/// the request, so that a concurrent retry is rejected instead of executing the action twice
+/// and a key reused for a different request is detected.
+///
+/// Keys expire after the configured time-to-live. A reservation whose action never completed
+/// (e.g. the server crashed) expires after the lease time-to-live, so that the request can be
+/// retried.
+pub struct FacadeIdempotencyKey {
+    pub key: String,
+    action: String,
+    body_hash: u64,
+    repo: repo::Repository,
+}
+
+impl FacadeIdempotencyKey {
+    pub fn new(key: String, action: String, body_hash: u64, repo: repo::Repository) -> Self {
+        Self {
+            key,
+            action,
+            body_hash,
+            repo,
+        }
+    }
+
+    /// Reserves the key for the execution of the action at time `now`, expired keys and
+    /// reservations are purged.
+    ///
+    /// Returns the response recorded for the key if the action was already completed,
+    /// [`None`] if the key was reserved and the action must be executed.
+    ///
+    /// Returns [`FacadeError::IdempotencyKeyMismatch`] if the key was used by a different
+    /// request and [`FacadeError::IdempotencyKeyInUse`] if the action is still in progress.
+    pub async fn reserve(&self, now: types::Timestamp) -> Result<Option<Vec<u8>>, FacadeError> {
+        let mut tx = self.repo.transaction().await?;
+
+        let min_tstamp = min_valid_tstamp(now);
+        repo::idempotency_key_purge(&mut tx, min_tstamp).await?;
+        repo::idempotency_key_purge_pending(&mut tx, min_pending_tstamp(now)).await?;
+        let reserved = repo::idempotency_key_reserve(
+            &mut tx,
+            &self.key,
+            &self.action,
+            self.body_hash,
+            now.into(),
+        )
+        .await?;
+        let record = if reserved {
+            None
+        } else {
+            // A missing record was released by a concurrent request in the meantime
+            let record = repo::idempotency_key_find(&mut tx, &self.key, min_tstamp).await?;
+            Some(record.ok_or_else(|| FacadeError::IdempotencyKeyInUse(self.key.clone()))?)
+        };
+
+        tx.commit().await?;
+
+        let Some(record) = record else {
+            return Ok(None);
+        };
+        if !record.matches(&self.action, self.body_hash) {
+            return Err(FacadeError::IdempotencyKeyMismatch(self.key.clone()));
+        }
+        match record.response {
+            Some(response) => Ok(Some(response)),
+            None => Err(FacadeError::IdempotencyKeyInUse(self.key.clone())),
+        }
+    }
+
+    /// Records the response of the action, completing the reservation of the key.
+    pub async fn record(&self, response: &[u8]) -> Result<(), FacadeError> {
+        let mut cx = self.repo.connection();
+        repo::idempotency_key_complete(&mut cx, &self.key, response).await?;
+        Ok(())
+    }
+
+    /// Releases the reservation of the key, called when the action fails so that it can be
+    /// retried.
+    pub async fn release(&self) -> Result<(), FacadeError> {
+        let mut cx = self.repo.connection();
+        repo::idempotency_key_delete(&mut cx, &self.key).await?;
+        Ok(())
+    }
+}
+
+/// Timestamp of the oldest key not yet expired at time `now`
+fn min_valid_tstamp(now: types::Timestamp) -> i64 {
+    let ttl_ms = params::configurables().idempotency_key_ttl_secs as i64 * 1000;
+    i64::from(now) - ttl_ms
+}
+
+/// Timestamp of the oldest reservation of an action in progress not yet expired at time `now`
+fn min_pending_tstamp(now: types::Timestamp) -> i64 {
+    let ttl_ms = params::configurables().upload_lease_ttl_secs as i64 * 1000;
+    i64::from(now) - ttl_ms
+}
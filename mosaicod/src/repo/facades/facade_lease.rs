@@ -0,0 +1,310 @@
+use super::FacadeError;
+use crate::{store, types};
+use log::{trace, warn};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Content of a lease file
+#[derive(Serialize, Deserialize, Debug)]
+struct LeaseRecord {
+    /// Identifier of the instance holding the lease
+    owner: String,
+    /// UNIX timestamp in milliseconds after which the lease is stale
+    expires_at: i64,
+}
+
+impl LeaseRecord {
+    fn new(owner: &str, ttl: Duration) -> Self {
+        let ttl = i64::try_from(ttl.as_millis()).unwrap_or(i64::MAX);
+        Self {
+            owner: owner.to_owned(),
+            expires_at: types::Timestamp::now().saturating_add_millis(ttl).into(),
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        i64::from(types::Timestamp::now()) >= self.expires_at
+    }
+}
+
+/// Lease held on a topic by the instance writing its data.
+///
+/// Instances sharing the same store acquire the lease before uploading a topic, so that a
+/// topic is written by a single instance at a time. The lease is a file placed in the topic
+/// folder, created atomically and holding the owner identifier and an expiry. A lease not
+/// renewed before its expiry (e.g. the owner crashed) is stale and can be taken over.
+///
+/// The lease is removed with [`TopicLease::release`], if dropped without being released the
+/// lease is removed in background.
+pub struct TopicLease {
+    store: store::StoreRef,
+    path: std::path::PathBuf,
+    owner: String,
+    ttl: Duration,
+    renewed_at: Instant,
+    released: bool,
+}
+
+impl TopicLease {
+    /// Acquires the lease of `resource` on behalf of `owner`, the lease expires after `ttl`
+    /// if not renewed.
+    ///
+    /// Fails with [`FacadeError::TopicLeased`] if another valid lease exists, regardless of
+    /// its owner.
+    pub async fn acquire(
+        store: store::StoreRef,
+        resource: &dyn types::Resource,
+        owner: &str,
+        ttl: Duration,
+    ) -> Result<Self, FacadeError> {
+        let path = resource.lease();
+
+        let held = |store| Self {
+            store,
+            path: path.clone(),
+            owner: owner.to_owned(),
+            ttl,
+            renewed_at: Instant::now(),
+            released: false,
+        };
+
+        // A stale lease is replaced only if unchanged since it was read, so that a single
+        // instance takes it over. The second attempt fails only if another instance acquired
+        // the lease in the meantime
+        for _ in 0..2 {
+            let record = serialize(&LeaseRecord::new(owner, ttl))?;
+            if store.put_if_absent(&path, record.clone()).await? {
+                trace!("lease on {} acquired by `{}`", resource, owner);
+                return Ok(held(store));
+            }
+
+            match read_versioned(&store, &path).await? {
+                Some((current, _)) if !current.is_expired() => {
+                    return Err(FacadeError::TopicLeased(current.owner));
+                }
+                Some((current, version)) => {
+                    if store.put_if_version(&path, record, &version).await? {
+                        warn!(
+                            "stale lease on {} held by `{}` taken over by `{}`",
+                            resource, current.owner, owner
+                        );
+                        return Ok(held(store));
+                    }
+                }
+                // Released in the meantime
+                None => {}
+            }
+        }
+
+        let owner = read(&store, &path)
+            .await?
+            .map(|current| current.owner)
+            .unwrap_or_default();
+        Err(FacadeError::TopicLeased(owner))
+    }
+
+    /// Extends the lease expiry, the lease is rewritten only if more than half of its
+    /// time-to-live has elapsed since the last renewal.
+    ///
+    /// Fails with [`FacadeError::TopicLeased`] if the lease was taken over by another
+    /// instance.
+    pub async fn renew(&mut self) -> Result<(), FacadeError> {
+        if self.renewed_at.elapsed() < self.ttl / 2 {
+            return Ok(());
+        }
+
+        // The lease is rewritten only if unchanged since it was read, so that a takeover
+        // happening in between is not overwritten
+        let record = serialize(&LeaseRecord::new(&self.owner, self.ttl))?;
+        let renewed = match read_versioned(&self.store, &self.path).await? {
+            Some((current, _)) if current.owner != self.owner => {
+                return Err(FacadeError::TopicLeased(current.owner));
+            }
+            Some((_, version)) => {
+                self.store
+                    .put_if_version(&self.path, record, &version)
+                    .await?
+            }
+            // Removed in the meantime
+            None => self.store.put_if_absent(&self.path, record).await?,
+        };
+
+        if !renewed {
+            let owner = read(&self.store, &self.path)
+                .await?
+                .map(|current| current.owner)
+                .unwrap_or_default();
+            return Err(FacadeError::TopicLeased(owner));
+        }
+        self.renewed_at = Instant::now();
+
+        trace!("lease {} renewed", self.path.display());
+        Ok(())
+    }
+
+    /// Removes the lease, a lease taken over by another instance is left untouched.
+    pub async fn release(mut self) -> Result<(), FacadeError> {
+        self.released = true;
+        release(&self.store, &self.path, &self.owner).await
+    }
+}
+
+impl Drop for TopicLease {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+
+        let store = self.store.clone();
+        let path = std::mem::take(&mut self.path);
+        let owner = std::mem::take(&mut self.owner);
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    if let Err(e) = release(&store, &path, &owner).await {
+                        warn!("unable to release lease {}: {e}", path.display());
+                    }
+                });
+            }
+            Err(_) => warn!(
+                "lease {} dropped outside of a runtime, it will expire",
+                path.display()
+            ),
+        }
+    }
+}
+
+async fn release(
+    store: &store::Store,
+    path: &std::path::Path,
+    owner: &str,
+) -> Result<(), FacadeError> {
+    match read(store, path).await? {
+        Some(current) if current.owner == owner => {
+            delete(store, path).await?;
+            trace!("lease {} released by `{}`", path.display(), owner);
+        }
+        Some(current) => warn!(
+            "lease {} taken over by `{}`, not released",
+            path.display(),
+            current.owner
+        ),
+        None => {}
+    }
+    Ok(())
+}
+
+/// Reads the lease at `path`, [`None`] if missing
+async fn read(
+    store: &store::Store,
+    path: &std::path::Path,
+) -> Result<Option<LeaseRecord>, FacadeError> {
+    Ok(read_versioned(store, path).await?.map(|(record, _)| record))
+}
+
+/// Reads the lease at `path` along with the version of the lease file, [`None`] if missing
+async fn read_versioned(
+    store: &store::Store,
+    path: &std::path::Path,
+) -> Result<Option<(LeaseRecord, store::ObjectVersion)>, FacadeError> {
+    match store.read_versioned(path).await {
+        Ok((bytes, version)) => {
+            let record = serde_json::from_slice(&bytes).map_err(|e| FacadeError::ReadError {
+                src: path.display().to_string(),
+                msg: e.to_string(),
+            })?;
+            Ok(Some((record, version)))
+        }
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn delete(store: &store::Store, path: &std::path::Path) -> Result<(), FacadeError> {
+    match store.delete(path).await {
+        Err(e) if !e.is_not_found() => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn serialize(record: &LeaseRecord) -> Result<Vec<u8>, FacadeError> {
+    serde_json::to_vec(record).map_err(|e| FacadeError::WriteError {
+        dst: "lease".to_owned(),
+        msg: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    /// Checks that two instances sharing a store can't hold the lease of the same topic at the
+    /// same time, and that stale leases are taken over.
+    async fn lease_contention(store: store::StoreRef) {
+        let resource = types::TopicResourceLocator::from("test_sequence/test_topic");
+
+        let lease = TopicLease::acquire(store.clone(), &resource, "instance_a", TTL)
+            .await
+            .unwrap();
+
+        let res = TopicLease::acquire(store.clone(), &resource, "instance_b", TTL).await;
+        assert!(matches!(res, Err(FacadeError::TopicLeased(owner)) if owner == "instance_a"));
+
+        // Once released the lease can be acquired by the other instance
+        lease.release().await.unwrap();
+        let lease = TopicLease::acquire(store.clone(), &resource, "instance_b", TTL)
+            .await
+            .unwrap();
+        lease.release().await.unwrap();
+
+        // An expired lease is stale and is taken over
+        let mut stale = TopicLease::acquire(store.clone(), &resource, "instance_a", Duration::ZERO)
+            .await
+            .unwrap();
+        let lease = TopicLease::acquire(store.clone(), &resource, "instance_b", TTL)
+            .await
+            .unwrap();
+
+        // The stale owner can't renew the lease of the new owner
+        let res = stale.renew().await;
+        assert!(matches!(res, Err(FacadeError::TopicLeased(owner)) if owner == "instance_b"));
+
+        // Releasing the stale lease does not remove the lease of the new owner
+        stale.release().await.unwrap();
+        let res = TopicLease::acquire(store.clone(), &resource, "instance_a", TTL).await;
+        assert!(matches!(res, Err(FacadeError::TopicLeased(owner)) if owner == "instance_b"));
+
+        lease.release().await.unwrap();
+        assert!(store.read_bytes(resource.lease()).await.is_err());
+
+        // A stale lease is taken over by a single instance, even if concurrently
+        let stale = TopicLease::acquire(store.clone(), &resource, "instance_a", Duration::ZERO)
+            .await
+            .unwrap();
+        let (res_b, res_c) = tokio::join!(
+            TopicLease::acquire(store.clone(), &resource, "instance_b", TTL),
+            TopicLease::acquire(store.clone(), &resource, "instance_c", TTL),
+        );
+        assert!(res_b.is_ok() != res_c.is_ok());
+
+        stale.release().await.unwrap();
+        for lease in [res_b, res_c].into_iter().flatten() {
+            lease.release().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn lease_contention_on_filesystem() {
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        lease_contention((*store).clone()).await;
+    }
+
+    #[tokio::test]
+    async fn lease_contention_in_memory() {
+        let driver = Arc::new(object_store::memory::InMemory::new());
+        lease_contention(Arc::new(store::testing::with_driver(driver))).await;
+    }
+}
@@ -1,6 +1,6 @@
 use super::FacadeError;
 use crate::types::Resource;
-use crate::{params, query, repo, types};
+use crate::{params, query, repo, rw, types};
 use futures::stream::{FuturesUnordered, StreamExt};
 use log::{debug, trace};
 use std::collections::{HashMap, HashSet};
@@ -54,6 +54,7 @@ impl FacadeQuery {
             let start = Instant::now();
 
             let include_timestamp_range = ontology_filter.include_timestamp_range;
+            let time_range = resolve_time_window(&ontology_filter);
 
             let ontology_tag_expr_groups =
                 ontology_filter.into_expr_group().split_by_ontology_tag();
@@ -81,6 +82,7 @@ impl FacadeQuery {
 
                 let repo_clone = repo.clone();
                 let on_topics = on_topics.clone();
+                let time_range = time_range.clone();
 
                 search_jobs.push(async move {
                     let _permit = permit; // sentinel lock
@@ -102,6 +104,25 @@ impl FacadeQuery {
                     };
                     let topics_map = pre_fetch_topics(&mut cx, &chunks, on_topics).await?;
 
+                    let chunks =
+                        split_chunks(&mut cx, chunks, &topics_map, time_range.as_ref()).await?;
+
+                    // The data extents of all the topics are fetched at once, to clamp the
+                    // searched range of each topic before scanning its chunks
+                    let extents: HashMap<i32, types::TimestampRange> = if time_range.is_some() {
+                        let topic_ids: Vec<i32> = topics_map.keys().copied().collect();
+                        repo::topics_timestamp_extent(&mut cx, &topic_ids)
+                            .await?
+                            .into_iter()
+                            .filter_map(|(topic_id, extent)| {
+                                let precision = topics_map.get(&topic_id)?.time_precision();
+                                Some((topic_id, extent.to_nanos(precision)))
+                            })
+                            .collect()
+                    } else {
+                        HashMap::new()
+                    };
+
                     // Store which topic had a positive data file search
                     let mut topics_with_data: HashSet<i32> = HashSet::new();
 
@@ -109,7 +130,9 @@ impl FacadeQuery {
                     let mut topics_timestamp_range: HashMap<String, types::TimestampRange> =
                         HashMap::new();
 
-                    for chunk in chunks {
+                    // Resolve the topic of each chunk before starting the search
+                    let mut targets = Vec::with_capacity(chunks.len());
+                    for (chunk, unit) in chunks {
                         let topic = topics_map.get(&chunk.topic_id);
                         if topic.is_none() {
                             debug!(
@@ -120,11 +143,6 @@ impl FacadeQuery {
                         }
                         let topic = topic.unwrap();
 
-                        trace!(
-                            "searching data file `{}`",
-                            chunk.data_file().to_string_lossy()
-                        );
-
                         let serialization_format =
                             topic.serialization_format().ok_or_else(|| {
                                 FacadeError::MissingSerializationFormat(
@@ -132,39 +150,115 @@ impl FacadeQuery {
                                 )
                             })?;
 
-                        let qr = ts_engine
-                            .read(chunk.data_file(), serialization_format, None)
-                            .await?;
-
-                        let qr = qr.filter(ontology_tag_exprs.to_owned())?;
-
-                        // Set this to true to print a log message that the chunk will be discared
-                        let mut is_discarded = false;
+                        // The work unit of the chunk is clamped to the data extent of its topic,
+                        // chunks of topics without data in the searched range are skipped
+                        let time_range = match (unit, extents.get(&topic.topic_id)) {
+                            (Some(unit), Some(extent)) => match unit.intersect(extent) {
+                                Some(range) => Some(range),
+                                None => {
+                                    trace!(
+                                        "discarding chunk `{}` for no timestamp in range",
+                                        chunk.chunk_uuid
+                                    );
+                                    continue;
+                                }
+                            },
+                            (unit, _) => unit,
+                        };
+
+                        targets.push((
+                            chunk,
+                            topic.topic_id,
+                            topic.locator_name.clone(),
+                            serialization_format,
+                            topic.time_precision(),
+                            time_range,
+                        ));
+                    }
 
-                        if include_timestamp_range {
-                            match qr.timestamp_range().await {
-                                Ok(ts_range) => {
-                                    topics_with_data.insert(topic.topic_id);
-                                    topics_timestamp_range
-                                        .insert(topic.locator_name.clone(), ts_range);
+                    // Chunks are searched concurrently, bounding the number of chunks decoded
+                    // at the same time to avoid exhausting file descriptors and memory
+                    let matches = bounded_concurrent(
+                        targets,
+                        max_concurrent,
+                        |(
+                            chunk,
+                            topic_id,
+                            locator_name,
+                            serialization_format,
+                            time_precision,
+                            time_range,
+                        )| {
+                            let ts_engine = ts_engine.clone();
+                            let exprs = ontology_tag_exprs.to_owned();
+
+                            async move {
+                                trace!(
+                                    "searching data file `{}`",
+                                    chunk.data_file().to_string_lossy()
+                                );
+
+                                let qr = ts_engine
+                                    .read(chunk.data_file(), serialization_format, None)
+                                    .await?;
+
+                                let mut qr = qr.filter(exprs)?;
+                                if let Some(time_range) = &time_range {
+                                    // The searched range is converted to the unit of the data
+                                    let Some(time_range) = time_range.to_precision(time_precision)
+                                    else {
+                                        trace!(
+                                            "discarding chunk `{}` for no timestamp in range",
+                                            chunk.chunk_uuid
+                                        );
+                                        return Ok(None);
+                                    };
+                                    qr = qr.filter_rows(query::RowFilter::timestamp_range(
+                                        &time_range,
+                                    ))?;
                                 }
-                                Err(err) => {
-                                    if let query::Error::NotFound = err {
-                                        is_discarded = true;
-                                    } else {
-                                        return Err(err.into());
+
+                                let ts_range = if include_timestamp_range {
+                                    match qr.timestamp_range().await {
+                                        Ok(ts_range) => Some(ts_range.to_nanos(time_precision)),
+                                        Err(query::Error::NotFound) => {
+                                            trace!(
+                                                "discarding chunk `{}` for no query match",
+                                                chunk.chunk_uuid
+                                            );
+                                            return Ok(None);
+                                        }
+                                        Err(err) => return Err(err.into()),
                                     }
-                                }
+                                } else if qr.has_rows().await? {
+                                    trace!("found matching records in chunk");
+                                    None
+                                } else {
+                                    trace!(
+                                        "discarding chunk `{}` for no query match",
+                                        chunk.chunk_uuid
+                                    );
+                                    return Ok(None);
+                                };
+
+                                Ok::<_, FacadeError>(Some((topic_id, locator_name, ts_range)))
                             }
-                        } else if qr.has_rows().await? {
-                            trace!("found matching records in chunk");
-                            topics_with_data.insert(topic.topic_id);
-                        } else {
-                            is_discarded = true;
-                        }
+                        },
+                    )
+                    .await?;
+
+                    for matched in matches {
+                        let Some((topic_id, locator_name, ts_range)) = matched? else {
+                            continue;
+                        };
 
-                        if is_discarded {
-                            trace!("discarding chunk `{}` for no query match", chunk.chunk_uuid);
+                        topics_with_data.insert(topic_id);
+
+                        if let Some(ts_range) = ts_range {
+                            topics_timestamp_range
+                                .entry(locator_name)
+                                .and_modify(|range| *range = range.merge(&ts_range))
+                                .or_insert(ts_range);
                         }
                     }
 
@@ -183,6 +277,21 @@ impl FacadeQuery {
                             });
                     }
 
+                    // The searched range is reported clamped to the data extent of each topic
+                    if let Some(time_range) = &time_range {
+                        let topic_ids: HashMap<&str, i32> = topics_map
+                            .values()
+                            .map(|t| (t.locator_name.as_str(), t.topic_id))
+                            .collect();
+
+                        for topic in groups.iter_mut().flat_map(|grp| &mut grp.topics) {
+                            topic.searched_range = topic_ids
+                                .get(topic.name().as_str())
+                                .and_then(|topic_id| extents.get(topic_id))
+                                .and_then(|extent| time_range.intersect(extent));
+                        }
+                    }
+
                     Ok::<_, FacadeError>(groups.into())
                 });
 
@@ -213,6 +322,337 @@ impl FacadeQuery {
 
         Ok(result.unwrap_or_default())
     }
+
+    /// Returns the topics selected by the sequence and topic filters of a query, sorted by
+    /// name.
+    ///
+    /// Ontology predicates are ignored, no data is read. If the query has no sequence or
+    /// topic filter no topic is returned.
+    pub async fn topics(
+        filter: query::Filter,
+        repo: repo::Repository,
+    ) -> Result<Vec<repo::TopicRecord>, FacadeError> {
+        let (seq_filt, top_filt, _) = filter.into_parts();
+
+        let mut cx = repo.connection();
+        let mut topics = repo::topic_from_query_filter(&mut cx, seq_filt, top_filt).await?;
+        topics.sort_by(|a, b| a.locator_name.cmp(&b.locator_name));
+
+        Ok(topics)
+    }
+
+    /// Validates a query against the repository without reading any data.
+    ///
+    /// The query is invalid if it names an unsupported serialization format, if it selects
+    /// topics without a serialization format or if its ontology predicates refer to fields
+    /// unknown to the data catalog. Only if the query is valid the selected topics (see
+    /// [`FacadeQuery::topics`]) and the number of candidate chunks are resolved, candidate
+    /// chunks are the chunks whose catalog statistics match the ontology predicates.
+    pub async fn validate(
+        filter: query::Filter,
+        repo: repo::Repository,
+    ) -> Result<QueryValidation, FacadeError> {
+        let (seq_filt, top_filt, on_filt) = filter.into_parts();
+
+        let no_topic_filter = (seq_filt.is_none() || seq_filt.as_ref().unwrap().is_empty())
+            && (top_filt.is_none() || top_filt.as_ref().unwrap().is_empty());
+
+        let mut issues = Vec::new();
+        for format in top_filt.iter().flat_map(unsupported_formats) {
+            issues.push(QueryIssue {
+                field: Some("topic.serialization_format".to_owned()),
+                message: format!("unsupported serialization format `{}`", format),
+            });
+        }
+
+        let mut cx = repo.connection();
+        let mut topics = repo::topic_from_query_filter(&mut cx, seq_filt, top_filt).await?;
+        topics.sort_by(|a, b| a.locator_name.cmp(&b.locator_name));
+
+        for topic in &topics {
+            if topic.serialization_format().is_none() {
+                issues.push(QueryIssue {
+                    field: None,
+                    message: FacadeError::MissingSerializationFormat(topic.locator_name.clone())
+                        .to_string(),
+                });
+            }
+        }
+
+        if let Some(ontology_filter) = &on_filt {
+            let fields: Vec<String> = ontology_filter
+                .fields()
+                .map(|field| field.value().to_owned())
+                .collect();
+            let known: HashSet<String> = repo::columns_known(&mut cx, &fields)
+                .await?
+                .into_iter()
+                .collect();
+
+            let mut unknown: Vec<String> = fields
+                .into_iter()
+                .filter(|field| !known.contains(field))
+                .collect();
+            unknown.sort();
+
+            for field in unknown {
+                issues.push(QueryIssue {
+                    message: format!("unknown ontology field `{}`", field),
+                    field: Some(field),
+                });
+            }
+        }
+
+        if !issues.is_empty() {
+            return Ok(QueryValidation {
+                issues,
+                topics: Vec::new(),
+                chunk_count: 0,
+            });
+        }
+
+        let chunk_count = match on_filt {
+            None => {
+                let topic_ids: Vec<i32> = topics.iter().map(|t| t.topic_id).collect();
+                let ids = if no_topic_filter {
+                    None
+                } else {
+                    Some(topic_ids.as_slice())
+                };
+                repo::chunks_count(&mut cx, ids).await? as usize
+            }
+            Some(ontology_filter) => {
+                let mut count = 0;
+                for exprs in ontology_filter.into_expr_group().split_by_ontology_tag() {
+                    if exprs.group.is_empty() {
+                        continue;
+                    }
+                    count += repo::chunks_from_filters(&mut cx, exprs, Some(&topics))
+                        .await?
+                        .len();
+                }
+                count
+            }
+        };
+
+        trace!(
+            "query valid, {} topics and {} candidate chunks selected",
+            topics.len(),
+            chunk_count
+        );
+
+        let topics = topics
+            .into_iter()
+            .filter_map(|topic| Some((topic.locator_name, topic.serialization_format()?)))
+            .collect();
+
+        Ok(QueryValidation {
+            issues,
+            topics,
+            chunk_count,
+        })
+    }
+
+    /// Counts the data rows matching a query, without retrieving the data.
+    ///
+    /// If the query has no ontology predicates the count is computed from the row counts
+    /// recorded for each chunk (exact and without decoding any data), otherwise the rows of
+    /// each candidate chunk are filtered and counted by streaming the data. Rows matching the
+    /// predicates of different ontology tags are summed, only rows within the time window of
+    /// the ontology filter are counted.
+    pub async fn count(
+        filter: query::Filter,
+        ts_gw: query::TimeseriesGatewayRef,
+        repo: repo::Repository,
+    ) -> Result<usize, FacadeError> {
+        let (seq_filt, top_filt, on_filt) = filter.into_parts();
+
+        let no_topic_filter = (seq_filt.is_none() || seq_filt.as_ref().unwrap().is_empty())
+            && (top_filt.is_none() || top_filt.as_ref().unwrap().is_empty());
+
+        let mut cx = repo.connection();
+        let on_topics = repo::topic_from_query_filter(&mut cx, seq_filt, top_filt).await?;
+
+        let Some(ontology_filter) = on_filt else {
+            trace!("counting rows from chunks metadata");
+
+            let topic_ids: Vec<i32> = on_topics.iter().map(|t| t.topic_id).collect();
+            let ids = if no_topic_filter {
+                None
+            } else {
+                Some(topic_ids.as_slice())
+            };
+
+            let count = repo::chunks_row_count(&mut cx, ids).await?;
+            return Ok(count as usize);
+        };
+
+        trace!("counting rows by streaming the matching chunks");
+
+        let time_range = resolve_time_window(&ontology_filter);
+
+        let mut count = 0;
+        for exprs in ontology_filter.into_expr_group().split_by_ontology_tag() {
+            if exprs.group.is_empty() {
+                continue;
+            }
+
+            let chunks =
+                repo::chunks_from_filters(&mut cx, exprs.clone(), Some(&on_topics)).await?;
+
+            let topics_map = pre_fetch_topics(
+                &mut cx,
+                &chunks,
+                if no_topic_filter {
+                    None
+                } else {
+                    Some(&on_topics)
+                },
+            )
+            .await?;
+
+            let chunks = split_chunks(&mut cx, chunks, &topics_map, time_range.as_ref()).await?;
+
+            let mut targets = Vec::with_capacity(chunks.len());
+            for (chunk, unit) in chunks {
+                let Some(topic) = topics_map.get(&chunk.topic_id) else {
+                    debug!(
+                        "can't find a topic associated with chunk `{}`, skipping",
+                        chunk.chunk_uuid
+                    );
+                    continue;
+                };
+
+                let serialization_format = topic.serialization_format().ok_or_else(|| {
+                    FacadeError::MissingSerializationFormat(topic.locator_name.to_owned())
+                })?;
+
+                targets.push((chunk, serialization_format, topic.time_precision(), unit));
+            }
+
+            let max_concurrent = params::configurables().max_concurrent_chunk_queries;
+            let counts = bounded_concurrent(
+                targets,
+                max_concurrent,
+                |(chunk, serialization_format, time_precision, time_range)| {
+                    let ts_gw = ts_gw.clone();
+                    let exprs = exprs.clone();
+
+                    async move {
+                        let mut qr = ts_gw
+                            .read(chunk.data_file(), serialization_format, None)
+                            .await?
+                            .with_row_count(chunk.row_count as usize)
+                            .filter(exprs)?;
+                        if let Some(time_range) = &time_range {
+                            let Some(time_range) = time_range.to_precision(time_precision) else {
+                                return Ok(0);
+                            };
+                            qr = qr.filter_rows(query::RowFilter::timestamp_range(&time_range))?;
+                        }
+
+                        Ok::<_, FacadeError>(qr.count().await?)
+                    }
+                },
+            )
+            .await?;
+
+            for c in counts {
+                count += c?;
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+/// Problem found by [`FacadeQuery::validate`]
+#[derive(Debug)]
+pub struct QueryIssue {
+    /// Field of the query the problem refers to (e.g. `topic.serialization_format`), [`None`]
+    /// if the problem is not bound to a field
+    pub field: Option<String>,
+    pub message: String,
+}
+
+/// Outcome of [`FacadeQuery::validate`]
+#[derive(Debug)]
+pub struct QueryValidation {
+    /// Problems found, the query is valid if empty
+    pub issues: Vec<QueryIssue>,
+    /// Locator and serialization format of the topics selected by the sequence and topic
+    /// filters, empty if the query is invalid
+    pub topics: Vec<(String, rw::Format)>,
+    /// Number of chunks that would be searched, zero if the query is invalid
+    pub chunk_count: usize,
+}
+
+/// Returns the serialization formats named by the equality and membership predicates of
+/// `filter` that are not supported.
+fn unsupported_formats(filter: &query::TopicFilter) -> Vec<String> {
+    let names: &[String] = match &filter.serialization_format {
+        Some(query::Op::Eq(name)) | Some(query::Op::Neq(name)) => std::slice::from_ref(name),
+        Some(query::Op::In(names)) => names.as_slice(),
+        _ => &[],
+    };
+
+    names
+        .iter()
+        .filter(|name| name.parse::<rw::Format>().is_err())
+        .cloned()
+        .collect()
+}
+
+/// Runs `job` on each item concurrently, keeping at most `limit` jobs in flight.
+///
+/// Results are returned in completion order.
+pub(super) async fn bounded_concurrent<T, R, F, Fut>(
+    items: Vec<T>,
+    limit: usize,
+    mut job: F,
+) -> Result<Vec<R>, FacadeError>
+where
+    F: FnMut(T) -> Fut,
+    Fut: Future<Output = R>,
+{
+    let semaphore = Arc::new(Semaphore::new(limit.max(1)));
+    let mut jobs = FuturesUnordered::new();
+
+    for item in items {
+        let semaphore = semaphore.clone();
+        let fut = job(item);
+
+        jobs.push(async move {
+            let _permit = semaphore.acquire_owned().await.map_err(|e| {
+                FacadeError::ConcurrencyError(format!("semaphore acquire failed: {e}"))
+            })?;
+            Ok::<_, FacadeError>(fut.await)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(result) = jobs.next().await {
+        results.push(result?);
+    }
+
+    Ok(results)
+}
+
+/// Returns the range of data timestamps searched by an ontology query, applying the server
+/// default lookback window if the query does not specify one.
+fn resolve_time_window(filter: &query::OntologyFilter) -> Option<types::TimestampRange> {
+    let lookback = params::configurables()
+        .default_query_lookback_secs
+        .map(std::time::Duration::from_secs);
+
+    let range = filter
+        .time_window
+        .clone()
+        .resolve(lookback, types::Timestamp::now());
+    if let Some(range) = &range {
+        trace!("searching data in time range {}", range);
+    }
+    range
 }
 
 /// A map holding pairs of (topic_id, topic_record) for easy lookup
@@ -237,3 +677,91 @@ async fn pre_fetch_topics(
 
     Ok(topic_map)
 }
+
+/// Splits the searched `range` (in nanoseconds) into per-chunk work units, intersecting it with
+/// the data timestamps of each chunk from the statistics of the timestamp column (see
+/// [`types::TimestampRange::split_by_chunks`]).
+///
+/// Chunks with no data in the range are discarded so that they are never read, chunks without
+/// statistics are kept with the whole range. Chunks whose data lies entirely in the range,
+/// as every chunk without a range, are kept with no range to search.
+async fn split_chunks(
+    cx: &mut repo::Cx<'_>,
+    chunks: Vec<repo::Chunk>,
+    topics_map: &TopicMap,
+    range: Option<&types::TimestampRange>,
+) -> Result<Vec<(repo::Chunk, Option<types::TimestampRange>)>, FacadeError> {
+    let Some(range) = range else {
+        return Ok(chunks.into_iter().map(|chunk| (chunk, None)).collect());
+    };
+
+    let chunk_ids: Vec<i32> = chunks.iter().map(|c| c.chunk_id).collect();
+    let extents = repo::chunks_timestamp_extent(cx, &chunk_ids).await?;
+
+    let count = chunks.len();
+    let mut units = Vec::with_capacity(count);
+    let mut bounded = Vec::with_capacity(count);
+    let mut bounds = Vec::with_capacity(count);
+    for chunk in chunks {
+        let extent = extents
+            .get(&chunk.chunk_id)
+            .zip(topics_map.get(&chunk.topic_id));
+        match extent {
+            Some((extent, topic)) => {
+                let extent = extent.to_nanos(topic.time_precision());
+                bounds.push((extent.start, extent.end));
+                bounded.push(Some(chunk));
+            }
+            None => units.push((chunk, Some(range.clone()))),
+        }
+    }
+
+    for (idx, unit) in range.split_by_chunks(&bounds) {
+        let covered = (unit.start, unit.end) == bounds[idx];
+        if let Some(chunk) = bounded[idx].take() {
+            units.push((chunk, (!covered).then_some(unit)));
+        }
+    }
+    trace!(
+        "pruned {} chunks with no timestamp in range",
+        count - units.len()
+    );
+
+    Ok(units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Checks that the number of jobs in flight never exceeds the provided limit
+    #[tokio::test]
+    async fn bounded_concurrency() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let results = bounded_concurrent((0..32).collect(), 3, |i: usize| {
+            let in_flight = in_flight.clone();
+            let peak = peak.clone();
+
+            async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(current, Ordering::SeqCst);
+
+                // Simulate a slow chunk read
+                for _ in 0..5 {
+                    tokio::task::yield_now().await;
+                }
+
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                i
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(results.len(), 32);
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+}
@@ -5,14 +5,14 @@
 //! sequence and provides transactional methods for interacting with both the
 //! database respository and the object store.
 
-use log::trace;
+use log::{trace, warn};
 
 use crate::{
     marshal, repo, store,
     types::{self, Resource},
 };
 
-use super::{FacadeError, FacadeTopic};
+use super::{FacadeError, FacadeTopic, FacadeTransaction, delete_relocated_metadata};
 
 /// Define sequence metadata type contaning json user metadata
 type SequenceMetadata = types::SequenceMetadata<marshal::JsonMetadataBlob>;
@@ -60,23 +60,27 @@ impl FacadeSequence {
         &self,
         metadata: Option<SequenceMetadata>,
     ) -> Result<types::ResourceId, FacadeError> {
-        let mut tx = self.repo.transaction().await?;
+        // The metadata file is removed if the repository commit fails
+        let mut tx = self.repo.atomic(self.store.clone()).await?;
 
-        let mut record = repo::SequenceRecord::new(self.locator.name());
+        let result = async {
+            let mut record = repo::SequenceRecord::new(self.locator.name());
 
-        if let Some(mdata) = &metadata {
-            record = record.with_user_metadata(mdata.user_metadata.clone());
-        }
+            if let Some(mdata) = &metadata {
+                record = record.with_user_metadata(mdata.user_metadata.clone());
+            }
 
-        let record = repo::sequence_create(&mut tx, &record).await?;
+            let record = repo::sequence_create(tx.tx(), &record).await?;
 
-        if let Some(mdata) = metadata {
-            self.metadata_write_to_store(mdata).await?;
-        }
+            if let Some(mdata) = metadata {
+                self.metadata_write_to_store(&mut tx, mdata).await?;
+            }
 
-        tx.commit().await?;
+            Ok(record.into())
+        }
+        .await;
 
-        Ok(record.into())
+        tx.finish(result).await
     }
 
     /// Read the repository record for this sequence. If no record is found an error is returned.
@@ -96,33 +100,32 @@ impl FacadeSequence {
         Ok(record.is_locked())
     }
 
-    /// Permanently locks the sequence, preventing any new topics from being added.
+    /// Returns the lifecycle state of the sequence, see [`types::ResourceState`].
+    pub async fn state(&self) -> Result<types::ResourceState, FacadeError> {
+        let mut cx = self.repo.connection();
+
+        sequence_state(&mut cx, &self.locator).await
+    }
+
+    /// Permanently locks (finalizes) the sequence, preventing any new topics from being added.
     ///
-    /// Once a sequence is locked, it becomes immutable — no further topics can be
-    /// appended or modified under it. This action is **irreversible**, as there is
-    /// currently no API or mechanism to unlock a sequence.
+    /// Once a sequence is finalized, it becomes immutable — no further topics can be
+    /// appended or modified under it. This action is **irreversible**, since
+    /// [`types::ResourceState::Finalized`] is a terminal state.
     ///
-    /// A sequence can be locked only if all the associated topics are locked, calling this
-    /// function on a sequence with an unlocked topic returns an error.
+    /// A sequence can be finalized only if all the associated topics are locked, calling this
+    /// function on a sequence with an unlocked topic returns a
+    /// [`FacadeError::InvalidTransition`] error.
     ///
-    /// Calling lock on a locked sequence returns a [`HandleError::SequenceLocked`] error.
+    /// Calling lock on a locked sequence returns a [`FacadeError::SequenceLocked`] error.
     pub async fn lock(&self) -> Result<(), FacadeError> {
         let mut tx = self.repo.transaction().await?;
 
-        // check that the sequence is currently unlocked
-        let record = repo::sequence_find_by_locator(&mut tx, &self.locator).await?;
-        if record.is_locked() {
+        let state = sequence_state(&mut tx, &self.locator).await?;
+        if state == types::ResourceState::Finalized {
             return Err(FacadeError::SequenceLocked);
         }
-
-        // check if all associated topics are locked
-        let topics = repo::sequence_find_all_topic_names(&mut tx, &self.locator).await?;
-        for topic_loc in topics {
-            let trecord = repo::topic_find_by_locator(&mut tx, &topic_loc).await?;
-            if !trecord.is_locked() {
-                return Err(FacadeError::TopicUnlocked);
-            }
-        }
+        state.transition(types::ResourceState::Finalized)?;
 
         repo::sequence_lock(&mut tx, &self.locator).await?;
 
@@ -175,7 +178,7 @@ impl FacadeSequence {
 
     /// Read the metadata from the store and returns an `HashMap` containing all the metadata
     pub async fn metadata(&self) -> Result<SequenceMetadata, FacadeError> {
-        let path = self.locator.metadata();
+        let path = self.repo.metadata_path(&self.locator);
         let bytes = self.store.read_bytes(&path).await?;
 
         let data: marshal::JsonSequenceMetadata = bytes.try_into()?;
@@ -183,15 +186,23 @@ impl FacadeSequence {
         Ok(data.into())
     }
 
-    async fn metadata_write_to_store(&self, metadata: SequenceMetadata) -> Result<(), FacadeError> {
-        let path = self.locator.metadata();
+    async fn metadata_write_to_store(
+        &self,
+        tx: &mut FacadeTransaction<'_>,
+        metadata: SequenceMetadata,
+    ) -> Result<(), FacadeError> {
+        let path = self.repo.metadata_path(&self.locator);
 
         trace!("converting metadata to bytes");
         let json_mdata = marshal::JsonSequenceMetadata::from(metadata);
         let bytes: Vec<u8> = json_mdata.try_into()?;
 
+        // The metadata file is created atomically, so that a racing creation never
+        // overwrites it
         trace!("writing metadata to store");
-        self.store.write_bytes(&path, bytes).await?;
+        if !tx.create(path, bytes).await? {
+            return Err(FacadeError::AlreadyExists(self.locator.name().clone()));
+        }
 
         trace!("wiring metadata to database");
         // ...
@@ -208,6 +219,112 @@ impl FacadeSequence {
         Ok(topics)
     }
 
+    /// Returns the manifest of the sequence, listing all its topics, see
+    /// [`FacadeTopic::manifest`] for the meaning of `scan`.
+    pub async fn manifest(&self, scan: bool) -> Result<types::SequenceManifest, FacadeError> {
+        let mut manifest = types::SequenceManifest {
+            sequence: self.locator.name().clone(),
+            topics: Vec::new(),
+        };
+
+        for topic in self.topic_list().await? {
+            let handle = FacadeTopic::new(topic.into(), self.store.clone(), self.repo.clone());
+            manifest.topics.push(handle.manifest(scan).await?);
+        }
+
+        Ok(manifest)
+    }
+
+    /// Validates the sequence content against a manifest, e.g. after a restore from a backup.
+    ///
+    /// Every datafile is read and hashed, returns the differences found, see
+    /// [`types::SequenceManifest::diff`].
+    pub async fn verify_manifest(
+        &self,
+        expected: &types::SequenceManifest,
+    ) -> Result<Vec<String>, FacadeError> {
+        Ok(expected.diff(&self.manifest(true).await?))
+    }
+
+    /// Clones the sequence and all its topics to `dest`, which must not exist.
+    ///
+    /// The metadata of the sequence is copied and each topic is copied as child of the
+    /// destination, see [`FacadeTopic::copy_to`]. The destination sequence is locked if the
+    /// source sequence is locked. If the clone fails the partially cloned destination is
+    /// removed.
+    ///
+    /// Returns the identifier of the destination sequence with the number of topics and chunks
+    /// copied.
+    pub async fn clone_to(
+        &self,
+        dest: &FacadeSequence,
+    ) -> Result<(types::ResourceId, usize, usize), FacadeError> {
+        if dest.resource_id().await.is_ok() {
+            return Err(FacadeError::AlreadyExists(dest.locator.name().clone()));
+        }
+
+        let r_id = dest.create(Some(self.metadata().await?)).await?;
+
+        let (topics, chunks) = match self.clone_topics_to(dest, &r_id).await {
+            Ok(cloned) => cloned,
+            Err(e) => {
+                // The destination has been created by this call, the partial clone is removed
+                let partial = FacadeSequence::new(
+                    dest.locator.name().clone(),
+                    self.store.clone(),
+                    self.repo.clone(),
+                );
+                // unsafe allowed since the destination may have been locked by the clone
+                let cleanup = unsafe { partial.delete_unsafe().await };
+                if let Err(cleanup_err) = cleanup {
+                    warn!(
+                        "unable to remove the partial clone `{}`: {}",
+                        dest.locator, cleanup_err
+                    );
+                }
+                return Err(e);
+            }
+        };
+
+        trace!(
+            "`{}` cloned to `{}`, {} topics",
+            self.locator, dest.locator, topics
+        );
+
+        Ok((r_id, topics, chunks))
+    }
+
+    /// Copies the topics of the sequence under the sequence `dest` identified by `r_id`,
+    /// locking `dest` if the sequence is locked. Returns the number of topics and chunks
+    /// copied.
+    async fn clone_topics_to(
+        &self,
+        dest: &FacadeSequence,
+        r_id: &types::ResourceId,
+    ) -> Result<(usize, usize), FacadeError> {
+        let topics = self.topic_list().await?;
+        let mut chunks = 0;
+        for topic in &topics {
+            let Some(suffix) = topic.name().strip_prefix(self.locator.name().as_str()) else {
+                return Err(FacadeError::InvalidPath(topic.name().clone()));
+            };
+
+            let src = FacadeTopic::new(topic.name().clone(), self.store.clone(), self.repo.clone());
+            let dst = FacadeTopic::new(
+                format!("{}{}", dest.locator.name(), suffix),
+                self.store.clone(),
+                self.repo.clone(),
+            );
+            chunks += src.copy_to(&dst, &r_id.uuid).await?;
+        }
+
+        if self.is_locked().await? {
+            dest.lock().await?;
+        }
+
+        Ok((topics.len(), chunks))
+    }
+
     /// Deletes a sequence and all its associated topics from the system.
     ///
     /// Both the sequence and its topics will be removed from the store and the repository.
@@ -238,6 +355,36 @@ impl FacadeSequence {
         // Delete sequence data
         repo::sequence_delete_unlocked(&mut tx, &self.locator).await?;
         self.store.delete_recursive(self.locator.name()).await?;
+        delete_relocated_metadata(&self.store, &self.repo, &self.locator).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Deletes a sequence and all its associated topics, **bypassing any lock state**.
+    ///
+    /// # Safety
+    ///
+    /// This function permanently deletes a locked sequence and all its data, be caution
+    pub async unsafe fn delete_unsafe(self) -> Result<(), FacadeError> {
+        let mut tx = self.repo.transaction().await?;
+
+        let topics = self.topic_list().await?;
+        for topic_loc in topics {
+            let thandle = FacadeTopic::new(topic_loc.into(), self.store.clone(), self.repo.clone());
+
+            // unsafe allowed since this function is unsafe itself
+            unsafe {
+                thandle.delete_unsafe().await?;
+            }
+        }
+
+        // unsafe allowed since this function is unsafe itself
+        unsafe {
+            repo::sequence_delete(&mut tx, &self.locator).await?;
+        }
+        self.store.delete_recursive(self.locator.name()).await?;
+        delete_relocated_metadata(&self.store, &self.repo, &self.locator).await?;
 
         tx.commit().await?;
         Ok(())
@@ -248,8 +395,21 @@ impl FacadeSequence {
         let mut cx = self.repo.connection();
         let record = repo::sequence_find_by_locator(&mut cx, &self.locator).await?;
 
-        // Compute the sum of the size of all files in the sequence
-        let files = self.store.list(&self.locator.name(), None).await?;
+        // Compute the sum of the size of all files in the sequence, the files of each topic
+        // are listed on their own since the store may not list them along with the sequence
+        // ones (see [`store::KeyScheme`])
+        let root = std::path::Path::new(self.locator.name());
+        let mut files: Vec<String> = self
+            .store
+            .list(self.locator.name(), None)
+            .await?
+            .into_iter()
+            .filter(|file| std::path::Path::new(file).parent() == Some(root))
+            .collect();
+        for topic_loc in self.topic_list().await? {
+            files.extend(self.store.list(topic_loc.name(), None).await?);
+        }
+
         let mut total_size = 0;
         for file in files {
             total_size += self.store.size(file).await?;
@@ -258,7 +418,29 @@ impl FacadeSequence {
         Ok(types::SequenceSystemInfo {
             total_size_bytes: total_size,
             is_locked: record.is_locked(),
-            created_datetime: record.creation_timestamp().into(),
+            created_datetime: record.creation_timestamp().try_into_datetime(),
         })
     }
 }
+
+/// Returns the lifecycle state of the sequence at `loc`, a sequence is locked once all its
+/// topics are locked.
+async fn sequence_state(
+    exe: &mut impl repo::AsExec,
+    loc: &types::SequenceResourceLocator,
+) -> Result<types::ResourceState, FacadeError> {
+    let record = repo::sequence_find_by_locator(exe, loc).await?;
+    if record.is_locked() {
+        return Ok(types::ResourceState::Finalized);
+    }
+
+    let topics = repo::sequence_find_all_topic_names(exe, loc).await?;
+    for topic_loc in topics {
+        let trecord = repo::topic_find_by_locator(exe, &topic_loc).await?;
+        if !trecord.is_locked() {
+            return Ok(types::ResourceState::Open);
+        }
+    }
+
+    Ok(types::ResourceState::Locked)
+}
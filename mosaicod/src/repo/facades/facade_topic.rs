@@ -1,16 +1,27 @@
-use super::FacadeError;
+use super::{FacadeError, delete_relocated_metadata, facade_query::bounded_concurrent};
 use crate::rw;
 use crate::traits::AsExtension;
+use crate::utils;
 use crate::{
-    marshal, repo, store,
+    marshal, query, repo, store,
     types::{self, Resource},
 };
+use arrow::array::RecordBatch;
 use arrow::datatypes::SchemaRef;
-use log::trace;
+use futures::{Stream, StreamExt, TryStreamExt};
+use log::{trace, warn};
+use std::collections::{HashMap, HashSet};
 
 /// Define topic metadata type contaning JSON user metadata
 type TopicMetadata = types::TopicMetadata<marshal::JsonMetadataBlob>;
 
+/// Chunk written to the store and not yet registered in the repository
+pub struct WrittenChunk {
+    pub datafile: std::path::PathBuf,
+    pub stats: types::ColumnsStats,
+    pub metadata: rw::ChunkMetadata,
+}
+
 pub struct FacadeTopic {
     pub locator: types::TopicResourceLocator,
     store: store::StoreRef,
@@ -40,42 +51,67 @@ impl FacadeTopic {
         sequence: &uuid::Uuid,
         metadata: Option<TopicMetadata>,
     ) -> Result<types::ResourceId, FacadeError> {
-        let mut tx = self.repo.transaction().await?;
-
-        // Ensure that a sequence with th provided id is available and is unlocked
-        let srecord = repo::sequence_find_by_uuid(&mut tx, sequence).await?;
-        if srecord.is_locked() {
-            return Err(FacadeError::SequenceLocked);
-        }
-
-        let sloc = types::SequenceResourceLocator::from(&srecord.locator_name);
-
-        // Ensure that this topic is child of the provided sequence, i.e. they are related with the same
-        // name structure
-        if !self.locator.is_sub_resource(&sloc) {
-            return Err(FacadeError::Unauthorized);
-        }
-
-        let mut record = repo::TopicRecord::new(self.locator.name(), srecord.sequence_id);
-
-        if let Some(metadata) = &metadata {
-            record = record
-                .with_user_metadata(metadata.user_metadata.clone())
-                .with_ontology_tag(&metadata.properties.ontology_tag)
-                .with_serialization_format(&metadata.properties.serialization_format.to_string());
-        }
-
-        let record = repo::topic_create(&mut tx, &record).await?;
-
-        // This operation is done at the end to avoid deleting or reverting changes
-        // to metadata file on store if some error causes a rollback on the repository
-        if let Some(metadata) = metadata {
-            self.metadata_write_to_store(metadata).await?;
+        // The metadata file is removed if the repository commit fails
+        let mut tx = self.repo.atomic(self.store.clone()).await?;
+
+        let result = async {
+            // Ensure that a sequence with th provided id is available and is unlocked
+            let srecord = repo::sequence_find_by_uuid(tx.tx(), sequence).await?;
+            if srecord.is_locked() {
+                return Err(FacadeError::SequenceLocked);
+            }
+
+            let sloc = types::SequenceResourceLocator::from(&srecord.locator_name);
+
+            // Ensure that this topic is child of the provided sequence, i.e. they are related with the same
+            // name structure
+            if !self.locator.is_sub_resource(&sloc) {
+                return Err(FacadeError::Unauthorized);
+            }
+
+            let mut record = repo::TopicRecord::new(self.locator.name(), srecord.sequence_id);
+
+            if let Some(metadata) = &metadata {
+                record = record
+                    .with_user_metadata(metadata.user_metadata.clone())
+                    .with_ontology_tag(&metadata.properties.ontology_tag)
+                    .with_serialization_format(
+                        &metadata.properties.serialization_format.to_string(),
+                    );
+            }
+
+            let record = repo::topic_create(tx.tx(), &record).await?;
+
+            if let Some(metadata) = &metadata {
+                repo::topic_update_time_precision(
+                    tx.tx(),
+                    &self.locator,
+                    metadata.properties.time_precision,
+                )
+                .await?;
+            }
+
+            // This operation is done at the end to avoid deleting or reverting changes
+            // to metadata file on store if some error causes a rollback on the repository
+            if let Some(metadata) = metadata {
+                self.metadata_create_in_store(&mut tx, metadata).await?;
+            }
+
+            Ok(record.into())
         }
+        .await;
 
-        tx.commit().await?;
+        tx.finish(result).await
+    }
 
-        Ok(record.into())
+    /// Acquires the lease of the topic on behalf of `owner`, required to write the topic data
+    /// (see [`super::TopicLease`]).
+    pub async fn acquire_lease(
+        &self,
+        owner: &str,
+        ttl: std::time::Duration,
+    ) -> Result<super::TopicLease, FacadeError> {
+        super::TopicLease::acquire(self.store.clone(), &self.locator, owner, ttl).await
     }
 
     pub async fn is_locked(&self) -> Result<bool, FacadeError> {
@@ -118,6 +154,12 @@ impl FacadeTopic {
             &metadata.properties.ontology_tag,
         )
         .await?;
+        repo::topic_update_time_precision(
+            &mut tx, //
+            &self.locator,
+            metadata.properties.time_precision,
+        )
+        .await?;
         // Save the last record for returning it
         let _ = repo::topic_update_serialization_format(
             &mut tx,
@@ -143,14 +185,110 @@ impl FacadeTopic {
         Ok(record.into())
     }
 
+    /// Returns the lifecycle state of the topic, see [`types::ResourceState`].
+    pub async fn state(&self) -> Result<types::ResourceState, FacadeError> {
+        let mut cx = self.repo.connection();
+
+        topic_state(&mut cx, &self.locator).await
+    }
+
+    /// Locks the topic once its upload is closed, removing its deferred lock if any.
+    ///
+    /// Returns a [`FacadeError::InvalidTransition`] error if the topic is finalized.
     pub async fn lock(&self) -> Result<(), FacadeError> {
+        self.lock_if_pending(None).await?;
+        Ok(())
+    }
+
+    /// Records the lock of the topic as deferred until `scheduled_at`, so that the pending
+    /// lock is not lost if the server restarts (see [`FacadeTopic::pending_locks`]).
+    pub async fn defer_lock(&self, scheduled_at: types::Timestamp) -> Result<(), FacadeError> {
+        let mut cx = self.repo.connection();
+        repo::topic_pending_lock_upsert(&mut cx, &self.locator, scheduled_at.into()).await?;
+        Ok(())
+    }
+
+    /// Removes the deferred lock of the topic, returns false if no lock was deferred.
+    pub async fn cancel_deferred_lock(&self) -> Result<bool, FacadeError> {
+        let mut cx = self.repo.connection();
+        Ok(repo::topic_pending_lock_delete(&mut cx, &self.locator, None).await?)
+    }
+
+    /// Performs the lock deferred until `scheduled_at` (see [`FacadeTopic::defer_lock`]).
+    ///
+    /// Returns false without locking the topic if the lock was cancelled or deferred again in
+    /// the meantime, e.g. by a new upload received by another instance.
+    pub async fn lock_deferred(&self, scheduled_at: types::Timestamp) -> Result<bool, FacadeError> {
+        self.lock_if_pending(Some(scheduled_at.into())).await
+    }
+
+    /// Retrieves all topics from the repository.
+    pub async fn all(
+        repo: &repo::Repository,
+    ) -> Result<Vec<types::TopicResourceLocator>, FacadeError> {
+        let mut cx = repo.connection();
+        let records = repo::topic_find_all(&mut cx).await?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| types::TopicResourceLocator::from(record.locator_name))
+            .collect())
+    }
+
+    /// Returns the unlocked topics whose lock is deferred.
+    pub async fn pending_locks(
+        repo: &repo::Repository,
+    ) -> Result<Vec<types::TopicResourceLocator>, FacadeError> {
+        let mut cx = repo.connection();
+        let names = repo::topic_pending_lock_find_all(&mut cx).await?;
+
+        Ok(names
+            .into_iter()
+            .map(types::TopicResourceLocator::from)
+            .collect())
+    }
+
+    /// Locks the topic, if `scheduled_at` is provided only if its lock is still deferred until
+    /// `scheduled_at`. Returns false if the topic was not locked.
+    async fn lock_if_pending(&self, scheduled_at: Option<i64>) -> Result<bool, FacadeError> {
+        // Data is made durable before the topic is marked as locked
+        self.store.sync_on_lock(self.path()).await?;
+
         let mut tx = self.repo.transaction().await?;
 
+        let pending = repo::topic_pending_lock_delete(&mut tx, &self.locator, scheduled_at).await?;
+        if scheduled_at.is_some() && !pending {
+            trace!("deferred lock of `{}` no longer pending", self.locator);
+            return Ok(false);
+        }
+
         trace!("locking `{}`", self.locator);
+        topic_state(&mut tx, &self.locator)
+            .await?
+            .transition(types::ResourceState::Locked)?;
         repo::topic_lock(&mut tx, &self.locator).await?;
 
         tx.commit().await?;
 
+        Ok(true)
+    }
+
+    /// Reopens the topic to overwrite its data, a locked topic stays unlocked until the upload
+    /// is closed and the topic locked again.
+    ///
+    /// Returns a [`FacadeError::InvalidTransition`] error if the topic is finalized.
+    pub async fn reopen(&self) -> Result<(), FacadeError> {
+        let mut tx = self.repo.transaction().await?;
+
+        let state = topic_state(&mut tx, &self.locator).await?;
+        state.transition(types::ResourceState::Open)?;
+        if state == types::ResourceState::Locked {
+            trace!("reopening `{}`", self.locator);
+            repo::topic_unlock(&mut tx, &self.locator).await?;
+        }
+
+        tx.commit().await?;
+
         Ok(())
     }
 
@@ -160,7 +298,7 @@ impl FacadeTopic {
     ///
     /// Returns [`HandleError::ReadError`] if reading or deserializing fails.
     pub async fn metadata(&self) -> Result<TopicMetadata, FacadeError> {
-        let path = self.locator.metadata();
+        let path = self.repo.metadata_path(&self.locator);
         let bytes = self.store.read_bytes(path).await?;
 
         let data: marshal::JsonTopicMetadata = bytes.try_into()?;
@@ -171,14 +309,62 @@ impl FacadeTopic {
     /// Returns the topic arrow schema.
     /// The serialization format is required to extract the schema, can be retrieved using [`TopicHandle::metadata`] function.
     pub async fn arrow_schema(&self, format: rw::Format) -> Result<SchemaRef, FacadeError> {
-        // Get chunk 0 since this chunk needs to exist always
-        let path = self.locator.datafile(0, &format);
+        // Get the first chunk since it needs to exist always
+        let path = self
+            .first_datafile(format)
+            .await?
+            .unwrap_or_else(|| self.locator.datafile(0, &format));
+
+        Ok(self.store.read_parquet_schema(path).await?)
+    }
+
+    /// Returns the topic arrow schema reading only the footer of the first chunk, or [`None`]
+    /// if the topic has no data.
+    pub async fn footer_schema(
+        &self,
+        format: rw::Format,
+    ) -> Result<Option<SchemaRef>, FacadeError> {
+        let Some(path) = self.first_datafile(format).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(self.store.read_parquet_schema(path).await?))
+    }
+
+    /// Returns the datafile with the lowest chunk index, whatever the naming of the topic
+    /// datafiles, or [`None`] if the topic has no data.
+    async fn first_datafile(
+        &self,
+        format: rw::Format,
+    ) -> Result<Option<std::path::PathBuf>, FacadeError> {
+        let datafiles = self
+            .store
+            .list(&self.locator.name(), Some(&format.as_extension()))
+            .await?;
+
+        Ok(datafiles
+            .into_iter()
+            .filter_map(|datafile| Some((types::datafile_index(&datafile)?, datafile)))
+            .min_by_key(|(idx, _)| *idx)
+            .map(|(_, datafile)| std::path::PathBuf::from(datafile)))
+    }
+
+    /// Returns the schema established by the data already written in the topic, or [`None`]
+    /// if the topic has no datafiles yet.
+    pub async fn established_schema(
+        &self,
+        format: rw::Format,
+    ) -> Result<Option<SchemaRef>, FacadeError> {
+        let datafiles = self
+            .store
+            .list(&self.locator.name(), Some(&format.as_extension()))
+            .await?;
+
+        if datafiles.is_empty() {
+            return Ok(None);
+        }
 
-        // Build a chunk reader reading in memory a file
-        // (cabba) TODO: avoid reading the whole file, get from store only the header
-        let buffer = self.store.read_bytes(path).await?;
-        let reader = rw::ChunkReader::new(format, bytes::Bytes::from_owner(buffer))?;
-        Ok(reader.schema())
+        Ok(Some(self.arrow_schema(format).await?))
     }
 
     /// Serializes and writes [`TopicMetadata`] to the object store.
@@ -188,7 +374,7 @@ impl FacadeTopic {
     /// Returns [`HandleError::NotFound`] or [`HandleError::WriteError`] if serialization or writing fails.
     async fn metadata_write_to_store(&self, metadata: TopicMetadata) -> Result<(), FacadeError> {
         trace!("writing metadata to store to `{}`", self.locator);
-        let path = self.locator.metadata();
+        let path = self.repo.metadata_path(&self.locator);
 
         let json_mdata = marshal::JsonTopicMetadata::from(metadata);
         let bytes: Vec<u8> = json_mdata.try_into()?;
@@ -198,12 +384,148 @@ impl FacadeTopic {
         Ok(())
     }
 
-    pub fn writer(&self, format: rw::Format) -> rw::ChunkedWriter<'_, store::Store> {
+    /// Same as [`FacadeTopic::metadata_write_to_store`] but fails with
+    /// [`FacadeError::AlreadyExists`] if the metadata file exists, the check and the write
+    /// are atomic so that a racing creation never overwrites it.
+    async fn metadata_create_in_store(
+        &self,
+        tx: &mut super::FacadeTransaction<'_>,
+        metadata: TopicMetadata,
+    ) -> Result<(), FacadeError> {
+        trace!("creating metadata in store to `{}`", self.locator);
+        let path = self.repo.metadata_path(&self.locator);
+
+        let json_mdata = marshal::JsonTopicMetadata::from(metadata);
+        let bytes: Vec<u8> = json_mdata.try_into()?;
+
+        if !tx.create(path, bytes).await? {
+            return Err(FacadeError::AlreadyExists(self.locator.name().clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the content hash of the last chunk of the topic, if any.
+    pub async fn last_chunk_hash(&self) -> Result<Option<utils::hash::Digest>, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+        Ok(repo::topic_last_chunk_hash(&mut cx, record.topic_id).await?)
+    }
+
+    /// Returns the timestamp of the last row of the topic according to the chunks statistics,
+    /// [`None`] if the topic has no chunks (see [`repo::topic_max_timestamp`]).
+    pub async fn last_timestamp(&self) -> Result<Option<i64>, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+        Ok(repo::topic_max_timestamp(&mut cx, record.topic_id).await?)
+    }
+
+    /// Returns the datafiles of the chunks registered for the topic.
+    pub async fn datafiles(&self) -> Result<Vec<std::path::PathBuf>, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+        Ok(repo::chunks_find_by_topic_id(&mut cx, record.topic_id)
+            .await?
+            .iter()
+            .map(|c| c.data_file().to_owned())
+            .collect())
+    }
+
+    /// Returns the write timestamps of the topic chunks, keyed by chunk index. Chunks written
+    /// by older versions have no write timestamp and are missing from the map.
+    pub async fn chunks_write_timestamps(
+        &self,
+    ) -> Result<std::collections::HashMap<usize, types::Timestamp>, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+        Ok(repo::chunks_find_by_topic_id(&mut cx, record.topic_id)
+            .await?
+            .iter()
+            .filter_map(|c| Some((types::datafile_index(c.data_file())?, c.write_timestamp()?)))
+            .collect())
+    }
+
+    /// Records the current time as the last access of the topic chunks stored in
+    /// `datafiles`, called with the datafiles scanned when the topic data is read.
+    pub async fn touch(
+        &self,
+        datafiles: impl IntoIterator<Item = impl AsRef<std::path::Path>>,
+    ) -> Result<(), FacadeError> {
+        let datafiles: Vec<String> = datafiles
+            .into_iter()
+            .map(|datafile| datafile.as_ref().to_string_lossy().into_owned())
+            .collect();
+        if datafiles.is_empty() {
+            return Ok(());
+        }
+
+        let mut cx = self.repo.connection();
+        repo::chunks_touch(&mut cx, &datafiles, types::Timestamp::now()).await?;
+        Ok(())
+    }
+
+    /// Moves to the storage class `class` the chunks not accessed (read or written) for
+    /// `idle` at the time `now`, see [`store::Store::set_storage_class`]. Moved chunks keep their location,
+    /// so they can still be queried with the latency of the cold storage class.
+    ///
+    /// Chunks written by older versions and never read have no access time and are skipped.
+    ///
+    /// Returns the number of chunks moved.
+    pub async fn tier(
+        &self,
+        idle: std::time::Duration,
+        class: &str,
+        now: types::Timestamp,
+    ) -> Result<usize, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+
+        let threshold = i64::from(now) - idle.as_millis() as i64;
+
+        let mut moved = 0;
+        for chunk in repo::chunks_find_by_topic_id(&mut cx, record.topic_id).await? {
+            let idle_chunk = chunk
+                .last_access()
+                .is_some_and(|access| i64::from(access) <= threshold);
+            if chunk.tier() != types::StorageTier::Warm || !idle_chunk {
+                continue;
+            }
+
+            self.store
+                .set_storage_class(chunk.data_file(), class)
+                .await?;
+            repo::chunk_update_tier(&mut cx, chunk.data_file(), types::StorageTier::Cold).await?;
+            moved += 1;
+        }
+
+        Ok(moved)
+    }
+
+    /// Returns a writer producing the topic datafiles, named according to `naming`.
+    pub fn writer(
+        &self,
+        format: rw::Format,
+        naming: types::ChunkNaming,
+    ) -> rw::ChunkedWriter<'_, store::Store> {
+        self.writer_on(&self.store, format, naming)
+    }
+
+    /// Same as [`FacadeTopic::writer`], writing the datafiles in `store` (the store of the
+    /// topic) so that the writer does not borrow the handle.
+    pub fn writer_on<'a>(
+        &self,
+        store: &'a store::Store,
+        format: rw::Format,
+        naming: types::ChunkNaming,
+    ) -> rw::ChunkedWriter<'a, store::Store> {
         rw::ChunkedWriter::new(
-            self.store.as_ref(),
+            store,
             self.path(),
             format,
-            |path, format, idx| types::TopicResourceLocator::from(path).datafile(idx, format),
+            move |path, format, idx, metadata| {
+                let locator = types::TopicResourceLocator::from(path);
+                chunk_datafile(&locator, idx, format, naming, metadata)
+            },
         )
     }
 
@@ -215,6 +537,7 @@ impl FacadeTopic {
 
         // Delete files
         self.store.delete_recursive(&self.path()).await?;
+        delete_relocated_metadata(&self.store, &self.repo, &self.locator).await?;
 
         tx.commit().await?;
 
@@ -234,6 +557,7 @@ impl FacadeTopic {
 
         // Delete files
         self.store.delete_recursive(&self.path()).await?;
+        delete_relocated_metadata(&self.store, &self.repo, &self.locator).await?;
 
         tx.commit().await?;
 
@@ -281,6 +605,291 @@ impl FacadeTopic {
         Ok(())
     }
 
+    /// Returns the range of the data timestamps of the topic in nanoseconds, computed from the
+    /// chunks statistics. Returns [`None`] if the topic has no data.
+    pub async fn timestamp_extent(&self) -> Result<Option<types::TimestampRange>, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+        let extent = repo::topics_timestamp_extent(&mut cx, &[record.topic_id])
+            .await?
+            .remove(&record.topic_id);
+        Ok(extent.map(|extent| extent.to_nanos(record.time_precision())))
+    }
+
+    /// Clamps `range` to the data extent of the topic, so that it reflects the data actually
+    /// searched.
+    ///
+    /// Returns [`None`] if the range does not overlap the topic data or the topic has no data.
+    pub async fn clamp_range(
+        &self,
+        range: &types::TimestampRange,
+    ) -> Result<Option<types::TimestampRange>, FacadeError> {
+        Ok(self
+            .timestamp_extent()
+            .await?
+            .and_then(|extent| range.intersect(&extent)))
+    }
+
+    /// Returns the statistics of each column of the topic data, without reading the data.
+    ///
+    /// Bounds and null counts are read from the row group statistics stored in the footer of
+    /// the datafiles. The data is scanned only to complete the columns lacking statistics and
+    /// to estimate the number of distinct values of integer and text columns.
+    ///
+    /// If `range` (in nanoseconds) is provided only the rows in range are profiled, the bounds
+    /// read from the footers may exceed the range since row groups are not split.
+    pub async fn column_stats(
+        &self,
+        range: Option<&types::TimestampRange>,
+        ts_gw: query::TimeseriesGatewayRef,
+    ) -> Result<Vec<rw::ColumnProfile>, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+
+        let format = record
+            .serialization_format()
+            .ok_or_else(|| FacadeError::MissingMetadataField("serialization_format".to_owned()))?;
+
+        // A range holding no value of the topic precision is replaced by an empty range
+        let range = range.map(|range| {
+            range
+                .to_precision(record.time_precision())
+                .unwrap_or_else(|| {
+                    types::TimestampRange::new(types::Timestamp::max(), types::Timestamp::min())
+                })
+        });
+
+        let chunks = repo::chunks_find_by_topic_id(&mut cx, record.topic_id).await?;
+
+        let mut profiler = rw::ColumnProfiler::default();
+        for chunk in &chunks {
+            let metadata = self.store.read_parquet_metadata(chunk.data_file()).await?;
+            profiler.push_footer(&metadata, range.as_ref());
+        }
+
+        if profiler.row_count() > 0 {
+            trace!("scanning `{}` to complete column statistics", self.locator);
+
+            let mut result = ts_gw.read(self.path(), format, None).await?;
+            if let Some(range) = &range {
+                result = result.filter_rows(query::RowFilter::timestamp_range(range))?;
+            }
+            result.profile(&mut profiler).await?;
+        }
+
+        Ok(profiler.finish())
+    }
+
+    /// Returns up to `limit` distinct values of `column` and whether the column holds more
+    /// distinct values, see [`query::TimeseriesGatewayResult::distinct`].
+    ///
+    /// If `range` (in nanoseconds) is provided only the rows in range are considered.
+    pub async fn distinct(
+        &self,
+        column: &str,
+        range: Option<&types::TimestampRange>,
+        limit: usize,
+        ts_gw: query::TimeseriesGatewayRef,
+    ) -> Result<(Vec<query::Value>, bool), FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+
+        let format = record
+            .serialization_format()
+            .ok_or_else(|| FacadeError::MissingMetadataField("serialization_format".to_owned()))?;
+
+        let mut result = ts_gw.read(self.path(), format, None).await?;
+        if let Some(range) = range {
+            // A range holding no value of the topic precision matches no row
+            let Some(units) = range.to_precision(record.time_precision()) else {
+                return Ok((Vec::new(), false));
+            };
+            result = result.filter_rows(query::RowFilter::timestamp_range(&units))?;
+        }
+
+        Ok(result.distinct(column, limit).await?)
+    }
+
+    /// Returns the result of each aggregation over the data of the topic, in request order,
+    /// along with the engine that computed them.
+    ///
+    /// If `range` (in nanoseconds) is provided only the rows in range are considered. In
+    /// process (see [`query::Aggregator`]) only the chunks whose timestamp statistics overlap
+    /// the range are decoded, one at a time. With [`query::QueryEngine::Auto`] the aggregations
+    /// not supported in process (e.g. on nested fields) are computed by the query engine, if
+    /// the `datafusion-engine` feature is enabled. Integer sums overflowing are rejected.
+    pub async fn aggregate(
+        &self,
+        aggregates: Vec<query::Aggregate>,
+        range: Option<&types::TimestampRange>,
+        engine: query::QueryEngine,
+        ts_gw: query::TimeseriesGatewayRef,
+    ) -> Result<(Vec<Option<query::Value>>, query::QueryEngine), FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+
+        let format = record
+            .serialization_format()
+            .ok_or_else(|| FacadeError::MissingMetadataField("serialization_format".to_owned()))?;
+
+        // A range holding no value of the topic precision is replaced by an empty range
+        let range = range.map(|range| {
+            range
+                .to_precision(record.time_precision())
+                .unwrap_or_else(|| {
+                    types::TimestampRange::new(types::Timestamp::max(), types::Timestamp::min())
+                })
+        });
+
+        if engine != query::QueryEngine::DataFusion {
+            let chunks = repo::chunks_timestamp_bounds(&mut cx, record.topic_id).await?;
+
+            match self
+                .aggregate_in_process(format, aggregates.clone(), range.clone(), chunks)
+                .await
+            {
+                Ok(values) => return Ok((values, query::QueryEngine::InProcess)),
+                Err(FacadeError::QueryError(
+                    e @ query::Error::OpError {
+                        err: query::OpError::UnsupportedOperation,
+                        ..
+                    },
+                )) if engine == query::QueryEngine::Auto && cfg!(feature = "datafusion-engine") => {
+                    trace!(
+                        "aggregating `{}` with the query engine: {}",
+                        self.locator, e
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok((
+            self.aggregate_with_engine(format, &aggregates, range.as_ref(), ts_gw)
+                .await?,
+            query::QueryEngine::DataFusion,
+        ))
+    }
+
+    /// Computes `aggregates` with the query engine over the rows within `range` (in the topic
+    /// precision).
+    #[cfg(feature = "datafusion-engine")]
+    async fn aggregate_with_engine(
+        &self,
+        format: rw::Format,
+        aggregates: &[query::Aggregate],
+        range: Option<&types::TimestampRange>,
+        ts_gw: query::TimeseriesGatewayRef,
+    ) -> Result<Vec<Option<query::Value>>, FacadeError> {
+        let mut result = ts_gw.read(self.path(), format, None).await?;
+        if let Some(range) = range {
+            result = result.filter_rows(query::RowFilter::timestamp_range(range))?;
+        }
+
+        Ok(result.aggregate(aggregates).await?)
+    }
+
+    /// The aggregations with the query engine are not compiled in, see the
+    /// `datafusion-engine` feature.
+    #[cfg(not(feature = "datafusion-engine"))]
+    async fn aggregate_with_engine(
+        &self,
+        _format: rw::Format,
+        _aggregates: &[query::Aggregate],
+        _range: Option<&types::TimestampRange>,
+        _ts_gw: query::TimeseriesGatewayRef,
+    ) -> Result<Vec<Option<query::Value>>, FacadeError> {
+        Err(FacadeError::InvalidArgument(
+            "the datafusion query engine is not enabled on this server".to_owned(),
+        ))
+    }
+
+    /// Computes `aggregates` in process over the `chunks` overlapping `range` (in the topic
+    /// precision), given as their datafile and range of data timestamps.
+    async fn aggregate_in_process(
+        &self,
+        format: rw::Format,
+        aggregates: Vec<query::Aggregate>,
+        range: Option<types::TimestampRange>,
+        chunks: Vec<(String, Option<(i64, i64)>)>,
+    ) -> Result<Vec<Option<query::Value>>, FacadeError> {
+        let overlaps = |bounds: &Option<(i64, i64)>| match (&range, bounds) {
+            (Some(range), Some((min, max))) => {
+                *max >= i64::from(range.start) && *min <= i64::from(range.end)
+            }
+            _ => true,
+        };
+
+        let mut aggregator = query::Aggregator::try_new(aggregates, range.clone())?;
+        for (datafile, _) in chunks.iter().filter(|(_, bounds)| overlaps(bounds)) {
+            let buffer = self.store.read_bytes(datafile).await?;
+            let reader = rw::ChunkReader::new(format, bytes::Bytes::from_owner(buffer))?;
+            for batch in reader.read_all()? {
+                aggregator.push(&batch)?;
+            }
+        }
+
+        Ok(aggregator.finish()?)
+    }
+
+    /// Returns the intervals of the `range` of data timestamps (in nanoseconds) where no
+    /// sample is found for longer than `expected_interval` nanoseconds.
+    ///
+    /// The interval is rounded up to the time precision of the topic, an interval shorter than
+    /// one unit of precision is rejected with a [`FacadeError::InvalidArgument`]. A range not
+    /// holding any value of the topic precision has no gaps. If the topic has no chunks the
+    /// whole range is returned without reading the datafiles, otherwise the timestamps of the
+    /// data in the range are scanned (chunks outside the range are pruned using the datafiles
+    /// statistics).
+    pub async fn gaps(
+        &self,
+        range: &types::TimestampRange,
+        expected_interval: i64,
+        ts_gw: query::TimeseriesGatewayRef,
+    ) -> Result<Vec<types::TimestampRange>, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+
+        let format = record
+            .serialization_format()
+            .ok_or_else(|| FacadeError::MissingMetadataField("serialization_format".to_owned()))?;
+
+        let precision = record.time_precision();
+        let unit = precision.nanos_per_unit();
+        if expected_interval < unit {
+            return Err(FacadeError::InvalidArgument(format!(
+                "expected interval of {expected_interval}ns is shorter than the topic precision of {unit}ns"
+            )));
+        }
+
+        let Some(units) = range.to_precision(precision) else {
+            return Ok(Vec::new());
+        };
+
+        let chunks = repo::chunks_find_by_topic_id(&mut cx, record.topic_id).await?;
+        if chunks.is_empty() {
+            return Ok(vec![range.clone()]);
+        }
+
+        trace!("scanning `{}` timestamps to find gaps", self.locator);
+
+        // A sample every `expected_interval` nanoseconds is at most this many units apart, the
+        // interval is rounded up so that samples on time are never reported as gaps
+        let max_interval = (expected_interval as u64).div_ceil(unit as u64) as i64;
+
+        let gaps = ts_gw
+            .read(self.path(), format, None)
+            .await?
+            .filter_rows(query::RowFilter::timestamp_range(&units))?
+            .gaps(&units, max_interval)
+            .await?;
+
+        Ok(gaps
+            .into_iter()
+            .map(|gap| gap.to_nanos(precision))
+            .collect())
+    }
+
     /// Returns the statistics about topic's chunks
     pub async fn chunks_stats(&self) -> Result<types::TopicChunksStats, FacadeError> {
         let mut cx = self.repo.connection();
@@ -288,6 +897,591 @@ impl FacadeTopic {
         Ok(stats)
     }
 
+    /// Validates the last chunk of the topic, removing it if corrupted.
+    ///
+    /// A crash during an upload may leave a trailing partial chunk on the store. The last chunk
+    /// is validated by reading its footer and, if unreadable, both the data file and the
+    /// associated repository entries are removed. Chunks preceding the last one are never touched.
+    ///
+    /// The caller must hold the lease of the topic (see [`FacadeTopic::acquire_lease`]), so that
+    /// no upload is writing the last chunk.
+    ///
+    /// Returns the location of the removed chunk, if any.
+    pub async fn repair(&self) -> Result<Option<String>, FacadeError> {
+        let mut tx = self.repo.transaction().await?;
+        let record = repo::topic_find_by_locator(&mut tx, &self.locator).await?;
+
+        let format = record
+            .serialization_format()
+            .ok_or_else(|| FacadeError::MissingMetadataField("serialization_format".to_owned()))?;
+
+        // The last chunk is the one with the highest index, the datafiles names don't follow the
+        // chunk order if they include the chunk time range
+        let datafiles = self
+            .store
+            .list(&self.locator.name(), Some(&format.as_extension()))
+            .await?;
+        let last = datafiles
+            .into_iter()
+            .max_by_key(|datafile| types::datafile_index(datafile));
+
+        let Some(last) = last else {
+            trace!("no chunks found for `{}`, nothing to repair", self.locator);
+            return Ok(None);
+        };
+
+        // Only the footer is fetched, errors other than a malformed footer are propagated
+        match self.store.read_parquet_metadata(&last).await {
+            Ok(_) => {
+                trace!("last chunk `{}` of `{}` is valid", last, self.locator);
+                return Ok(None);
+            }
+            Err(e) if matches!(e.root(), store::Error::ParquetError(_)) => {
+                warn!(
+                    "chunk `{}` of `{}` is corrupted ({}), removing it",
+                    last, self.locator, e
+                );
+            }
+            Err(e) => return Err(e.into()),
+        }
+        repo::chunk_delete_by_data_file(&mut tx, &last).await?;
+        self.store.delete(&last).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(last))
+    }
+
+    /// Compares the chunks registered in the repository against the datafiles found in the
+    /// store, reporting missing, extra and size-mismatched chunks.
+    pub async fn consistency_check(&self) -> Result<types::TopicConsistencyReport, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+
+        let format = record
+            .serialization_format()
+            .ok_or_else(|| FacadeError::MissingMetadataField("serialization_format".to_owned()))?;
+
+        let chunks = repo::chunks_find_by_topic_id(&mut cx, record.topic_id).await?;
+
+        let mut datafiles: HashSet<String> = self
+            .store
+            .list(&self.locator.name(), Some(&format.as_extension()))
+            .await?
+            .into_iter()
+            .collect();
+
+        let mut report = types::TopicConsistencyReport::default();
+
+        for chunk in chunks {
+            let datafile = chunk.data_file().to_string_lossy().to_string();
+
+            if !datafiles.remove(&datafile) {
+                report.missing_chunks.push(datafile);
+                continue;
+            }
+
+            if self.store.size(&datafile).await? as i64 != chunk.size_bytes {
+                report.size_mismatched_chunks.push(datafile);
+            }
+        }
+
+        report.extra_chunks = datafiles.into_iter().collect();
+        report.extra_chunks.sort();
+
+        if !report.is_consistent() {
+            warn!("topic `{}` is inconsistent: {:?}", self.locator, report);
+        }
+
+        Ok(report)
+    }
+
+    /// Returns the manifest of the topic, listing its properties and the size and checksum of
+    /// each datafile.
+    ///
+    /// The sizes and checksums are the ones recorded in the catalog when the chunks were
+    /// written, only the datafiles written by older versions (without a recorded digest) are
+    /// read. If `scan` is true every datafile is read instead, so that the manifest reflects
+    /// the stored content, e.g. to validate a restored backup.
+    pub async fn manifest(&self, scan: bool) -> Result<types::TopicManifest, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+        let chunks = repo::chunks_find_by_topic_id(&mut cx, record.topic_id).await?;
+
+        let mut manifest = types::TopicManifest {
+            name: self.locator.name().clone(),
+            properties: self.metadata().await?.properties,
+            chunks: Vec::with_capacity(chunks.len()),
+        };
+
+        for chunk in chunks {
+            let (size_bytes, digest) = match &chunk.content_hash {
+                Some(digest) if !scan => (chunk.size_bytes as usize, digest.clone()),
+                _ => {
+                    let bytes = self.store.read_bytes(chunk.data_file()).await?;
+                    (bytes.len(), utils::hash::sha256(&bytes).to_vec())
+                }
+            };
+            manifest.chunks.push(types::ChunkManifest {
+                datafile: chunk.data_file().to_string_lossy().to_string(),
+                size_bytes,
+                checksum: utils::hash::to_hex(&digest),
+            });
+        }
+
+        Ok(manifest)
+    }
+
+    /// Rewrites the chunks of the topic so that their time ranges do not overlap.
+    ///
+    /// Out-of-order uploads can produce chunks with interleaved time ranges, defeating the
+    /// range pruning during queries. All the chunks are read and their rows sorted by timestamp,
+    /// then the data is written in new chunks (roughly with the same size of the original ones)
+    /// having disjoint time ranges, non-time columns are preserved.
+    ///
+    /// New chunks are written before the original ones are removed, the chunks are swapped in
+    /// a single transaction (see [`FacadeTopic::swap_chunks`]) so an error never causes data
+    /// loss. Only locked topics can be reordered, the whole topic data is loaded in memory. The
+    /// caller must hold the lease of the topic (see [`FacadeTopic::acquire_lease`]), so that
+    /// concurrent rewrites of the topic are refused.
+    ///
+    /// Up to `parallelism` chunks are read concurrently, the output does not depend on the
+    /// order in which reads complete.
+    ///
+    /// Returns the number of chunks written.
+    pub async fn reorder(&self, parallelism: usize) -> Result<usize, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+
+        if !record.is_locked() {
+            return Err(FacadeError::TopicUnlocked);
+        }
+
+        let format = record
+            .serialization_format()
+            .ok_or_else(|| FacadeError::MissingMetadataField("serialization_format".to_owned()))?;
+        let ontology_tag = record
+            .ontology_tag
+            .clone()
+            .ok_or_else(|| FacadeError::MissingMetadataField("ontology_tag".to_owned()))?;
+        let properties = self.metadata().await?.properties;
+        let options = rw::WriterOptions::from(&properties);
+
+        let chunks = repo::chunks_find_by_topic_id(&mut cx, record.topic_id).await?;
+        if chunks.len() < 2 {
+            trace!(
+                "`{}` has less than two chunks, nothing to reorder",
+                self.locator
+            );
+            return Ok(0);
+        }
+
+        let datafiles: Vec<_> = chunks.iter().map(|c| c.data_file().to_owned()).collect();
+        let batches = read_chunks(&self.store, format, datafiles.clone(), parallelism).await?;
+        let chunk_rows: Vec<usize> = chunks.iter().map(|c| c.row_count as usize).collect();
+
+        let sorted = rw::sort_and_split_by_timestamp(&batches, &chunk_rows)?;
+
+        // New chunks are numbered after the existing datafiles to never overwrite them
+        let first_idx = self.next_chunk_index(format).await?;
+
+        let mut written = Vec::with_capacity(sorted.len());
+        for (idx, batch) in sorted.iter().enumerate() {
+            let chunk = async {
+                let mut writer =
+                    rw::ChunkWriter::try_new_with_options(batch.schema(), format, options)?;
+                writer.write(batch)?;
+                self.store_chunk(writer, first_idx + idx, format, properties.chunk_naming)
+                    .await
+            };
+            match chunk.await {
+                Ok(chunk) => written.push(chunk),
+                Err(e) => {
+                    self.discard_datafiles(written.iter().map(|c| &c.datafile))
+                        .await;
+                    return Err(e);
+                }
+            }
+        }
+
+        self.swap_chunks(record.topic_id, &ontology_tag, written, &datafiles)
+            .await?;
+
+        trace!(
+            "`{}` reordered, {} chunks replaced by {}",
+            self.locator,
+            chunks.len(),
+            sorted.len()
+        );
+
+        Ok(sorted.len())
+    }
+
+    /// Stores the chunk serialized by `writer` with index `index`, its datafile is named
+    /// according to `naming`. The chunk is not registered (see [`FacadeTopic::swap_chunks`]).
+    async fn store_chunk(
+        &self,
+        writer: rw::ChunkWriter,
+        index: usize,
+        format: rw::Format,
+        naming: types::ChunkNaming,
+    ) -> Result<WrittenChunk, FacadeError> {
+        let (buffer, stats, metadata) = writer.finalize()?;
+        let datafile = chunk_datafile(&self.locator, index, &format, naming, &metadata);
+        self.store.write_bytes(&datafile, buffer).await?;
+
+        Ok(WrittenChunk {
+            datafile,
+            stats,
+            metadata,
+        })
+    }
+
+    /// Replaces the chunks stored in `old` with the `new` chunks, already written to the store.
+    ///
+    /// The new chunks are registered and the old ones unregistered in a single transaction,
+    /// so that queries see either set of chunks and never both. Once committed the old
+    /// datafiles are deleted, on failure the new ones are.
+    pub async fn swap_chunks(
+        &self,
+        topic_id: i32,
+        ontology_tag: &str,
+        new: Vec<WrittenChunk>,
+        old: &[std::path::PathBuf],
+    ) -> Result<(), FacadeError> {
+        let new_datafiles: Vec<_> = new.iter().map(|c| c.datafile.clone()).collect();
+
+        let swapped = async {
+            let mut tx = self.repo.transaction().await?;
+            for chunk in new {
+                repo::FacadeChunk::create_in(
+                    &mut tx,
+                    topic_id,
+                    &chunk.datafile,
+                    &chunk.metadata,
+                    ontology_tag,
+                    chunk.stats,
+                )
+                .await?;
+            }
+            for datafile in old {
+                repo::chunk_delete_by_data_file(&mut tx, datafile).await?;
+            }
+            tx.commit().await?;
+            Ok::<_, FacadeError>(())
+        }
+        .await;
+
+        if let Err(e) = swapped {
+            self.discard_datafiles(&new_datafiles).await;
+            return Err(e);
+        }
+
+        for datafile in old {
+            self.store.delete(datafile).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the datafiles of chunks written but never registered, failures are only logged
+    /// since the datafiles hold no data missing from the topic.
+    pub async fn discard_datafiles(
+        &self,
+        datafiles: impl IntoIterator<Item = impl AsRef<std::path::Path>>,
+    ) {
+        for datafile in datafiles {
+            let datafile = datafile.as_ref();
+            if let Err(e) = self.store.delete(datafile).await {
+                warn!(
+                    "unable to delete unregistered datafile `{}`: {}",
+                    datafile.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    /// Merges the most recent chunks of the topic in a single chunk, so that the topic holds at
+    /// most `max_chunks` chunks.
+    ///
+    /// The merged chunk is written before the original ones are removed, the chunks are swapped
+    /// in a single transaction (see [`FacadeTopic::swap_chunks`]) so an error never causes data
+    /// loss. Rows keep their order, the merged chunks are decoded one at a time while up to
+    /// `parallelism` of them are read ahead. The caller must hold the lease of the topic (see
+    /// [`FacadeTopic::acquire_lease`]).
+    ///
+    /// Returns the number of chunks merged, zero if the topic holds at most `max_chunks` chunks.
+    pub async fn compact_tail(
+        &self,
+        max_chunks: usize,
+        parallelism: usize,
+    ) -> Result<usize, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+
+        let format = record
+            .serialization_format()
+            .ok_or_else(|| FacadeError::MissingMetadataField("serialization_format".to_owned()))?;
+        let ontology_tag = record
+            .ontology_tag
+            .clone()
+            .ok_or_else(|| FacadeError::MissingMetadataField("ontology_tag".to_owned()))?;
+        let properties = self.metadata().await?.properties;
+        let options = rw::WriterOptions::from(&properties);
+
+        let chunks = repo::chunks_find_by_topic_id(&mut cx, record.topic_id).await?;
+        let max_chunks = max_chunks.max(1);
+        if chunks.len() <= max_chunks {
+            return Ok(0);
+        }
+
+        let datafiles: Vec<_> = chunks[max_chunks - 1..]
+            .iter()
+            .map(|c| c.data_file().to_owned())
+            .collect();
+
+        // The merged chunk takes the schema of the first chunk merged
+        let mut reads = prefetch_chunks(
+            &self.store,
+            datafiles.clone(),
+            parallelism.saturating_sub(1),
+        );
+        let mut writer: Option<rw::ChunkWriter> = None;
+        while let Some(buffer) = reads.try_next().await? {
+            let reader = rw::ChunkReader::new(format, bytes::Bytes::from_owner(buffer))?;
+            let writer = match &mut writer {
+                Some(writer) => writer,
+                None => writer.insert(rw::ChunkWriter::try_new_with_options(
+                    reader.schema(),
+                    format,
+                    options,
+                )?),
+            };
+            for batch in reader.read_all()? {
+                writer.write(&batch)?;
+            }
+        }
+        let writer = writer.expect("at least one chunk merged");
+
+        let index = self.next_chunk_index(format).await?;
+        let merged = self
+            .store_chunk(writer, index, format, properties.chunk_naming)
+            .await?;
+        self.swap_chunks(record.topic_id, &ontology_tag, vec![merged], &datafiles)
+            .await?;
+
+        trace!(
+            "`{}` compacted, {} chunks merged",
+            self.locator,
+            datafiles.len()
+        );
+
+        Ok(datafiles.len())
+    }
+
+    /// Returns the index following the one of the last datafile of the topic, new chunks
+    /// numbered from it never overwrite existing datafiles.
+    pub async fn next_chunk_index(&self, format: rw::Format) -> Result<usize, FacadeError> {
+        Ok(self
+            .store
+            .list(&self.locator.name(), Some(&format.as_extension()))
+            .await?
+            .iter()
+            .filter_map(types::datafile_index)
+            .max()
+            .map_or(0, |idx| idx + 1))
+    }
+
+    /// Copies the topic data and metadata to `dest`, a new topic created as child of the
+    /// sequence `sequence`.
+    ///
+    /// Each datafile is copied by the store (see [`store::Store::copy`]), keeping its name,
+    /// and registered for the destination topic along with a copy of its column statistics.
+    /// The chunks are registered in a single transaction once all the datafiles are copied.
+    /// The destination topic is locked if the source topic is locked.
+    ///
+    /// Returns the number of chunks copied.
+    pub async fn copy_to(
+        &self,
+        dest: &FacadeTopic,
+        sequence: &uuid::Uuid,
+    ) -> Result<usize, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+
+        let r_id = dest.create(sequence, Some(self.metadata().await?)).await?;
+
+        let chunks = repo::chunks_find_by_topic_id(&mut cx, record.topic_id).await?;
+        let mut copies = Vec::with_capacity(chunks.len());
+        for chunk in &chunks {
+            let src = chunk.data_file();
+            let Some(filename) = src.file_name() else {
+                return Err(FacadeError::InvalidPath(src.display().to_string()));
+            };
+            let dst = std::path::Path::new(dest.locator.name()).join(filename);
+
+            self.store.copy(src, &dst).await?;
+            if self.store.size(&dst).await? as i64 != chunk.size_bytes {
+                return Err(store::Error::CopyMismatch {
+                    src: src.display().to_string(),
+                    dst: dst.display().to_string(),
+                }
+                .into());
+            }
+            copies.push((chunk.chunk_id, dst));
+        }
+
+        let mut tx = self.repo.transaction().await?;
+        for (chunk_id, dst) in copies {
+            repo::chunk_copy(&mut tx, chunk_id, r_id.id, dst).await?;
+        }
+        tx.commit().await?;
+
+        if record.is_locked() {
+            dest.lock().await?;
+        }
+
+        trace!(
+            "`{}` copied to `{}`, {} chunks",
+            self.locator,
+            dest.locator,
+            chunks.len()
+        );
+
+        Ok(chunks.len())
+    }
+
+    /// Returns the last `n` rows of the topic by timestamp, sorted in ascending time order.
+    ///
+    /// Chunks are selected from the timestamp statistics of the data catalog, so that only the
+    /// chunks that can hold the last `n` rows are read instead of the whole topic, whatever the
+    /// order the chunks were written in. The rows read are sorted before taking the tail, in
+    /// case of overlapping time ranges between the selected chunks (see
+    /// [`FacadeTopic::reorder`]).
+    ///
+    /// Returns [`None`] if the topic has no data, an empty batch if `n` is zero.
+    pub async fn tail(
+        &self,
+        n: usize,
+        parallelism: usize,
+    ) -> Result<Option<RecordBatch>, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+
+        let format = record
+            .serialization_format()
+            .ok_or_else(|| FacadeError::MissingMetadataField("serialization_format".to_owned()))?;
+
+        if n == 0 {
+            let schema = self.footer_schema(format).await?;
+            return Ok(schema.map(RecordBatch::new_empty));
+        }
+
+        let row_counts: HashMap<std::path::PathBuf, usize> =
+            repo::chunks_find_by_topic_id(&mut cx, record.topic_id)
+                .await?
+                .into_iter()
+                .map(|chunk| (chunk.data_file().to_owned(), chunk.row_count as usize))
+                .collect();
+
+        // Chunks are visited from the largest timestamp backward, chunks without statistics
+        // come first since they can hold any timestamp
+        let mut chunks = repo::chunks_timestamp_bounds(&mut cx, record.topic_id).await?;
+        chunks.sort_by_key(|(_, bounds)| bounds.map(|(_, max)| std::cmp::Reverse(max)));
+
+        // Once the chunks selected hold `n` rows, the last `n` rows of the topic can't
+        // precede the smallest timestamp of the selected chunks, so the chunks ending before
+        // it are skipped
+        let mut datafiles = Vec::new();
+        let mut rows = 0;
+        let mut lower_bound = i64::MAX;
+        for (datafile, bounds) in &chunks {
+            match bounds {
+                Some((_, max)) if rows >= n && *max < lower_bound => break,
+                Some((min, _)) => lower_bound = lower_bound.min(*min),
+                None => lower_bound = i64::MIN,
+            }
+            let datafile = std::path::PathBuf::from(datafile);
+            rows += row_counts.get(&datafile).copied().unwrap_or_default();
+            datafiles.push(datafile);
+        }
+        datafiles.sort_by_key(types::datafile_index);
+
+        trace!(
+            "reading {} of {} chunks to get the last {} rows of `{}`",
+            datafiles.len(),
+            chunks.len(),
+            n,
+            self.locator
+        );
+
+        let batches = read_chunks(&self.store, format, datafiles, parallelism).await?;
+
+        Ok(rw::tail_by_timestamp(&batches, n)?)
+    }
+
+    /// Returns the row of the topic at `timestamp` (in nanoseconds), selected following `mode`,
+    /// as a single-row batch.
+    ///
+    /// The whole topic is not scanned: the chunks that can hold the row are selected from the
+    /// timestamp statistics of the data catalog, then only the row groups that can hold the row
+    /// are fetched from the store and decoded, selected from the statistics of the chunk
+    /// footer. Rows of time-sorted chunks are binary searched.
+    ///
+    /// Returns [`None`] if no row matches.
+    pub async fn at(
+        &self,
+        timestamp: i64,
+        mode: types::LookupMode,
+    ) -> Result<Option<RecordBatch>, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+
+        let lookup = rw::Lookup::new(timestamp, mode, record.time_precision());
+
+        let chunks = repo::chunks_timestamp_bounds(&mut cx, record.topic_id).await?;
+        let bounds: Vec<_> = chunks.iter().map(|(_, bounds)| *bounds).collect();
+        let candidates = lookup.candidates(&bounds);
+
+        trace!(
+            "looking up {} in {} of {} chunks of `{}`",
+            timestamp,
+            candidates.len(),
+            chunks.len(),
+            self.locator
+        );
+
+        let mut rows = Vec::new();
+        for idx in candidates {
+            let datafile = &chunks[idx].0;
+
+            let metadata = self.store.read_parquet_metadata(datafile).await?;
+            let row_groups = lookup.row_groups(&metadata);
+            if row_groups.is_empty() {
+                continue;
+            }
+
+            let (schema, batches) = self
+                .store
+                .read_parquet_row_groups(datafile, metadata, row_groups)
+                .await?;
+            let time_sorted = rw::is_time_sorted(schema.metadata());
+
+            rows.extend(lookup.find(&batches, time_sorted)?);
+        }
+
+        // Pick the best among the rows found in each chunk
+        Ok(lookup.find(&rows, false)?)
+    }
+
+    /// Returns the creation timestamp of the topic
+    pub async fn creation_timestamp(&self) -> Result<types::Timestamp, FacadeError> {
+        let mut cx = self.repo.connection();
+        let record = repo::topic_find_by_locator(&mut cx, &self.locator).await?;
+        Ok(record.creation_timestamp())
+    }
+
     /// Computes system info for the topic
     pub async fn system_info(&self) -> Result<types::TopicSystemInfo, FacadeError> {
         let mut cx = self.repo.connection();
@@ -307,13 +1501,256 @@ impl FacadeTopic {
             total_size += self.store.size(file).await?;
         }
 
+        // Each chunk flush registers a new chunk, so the most recent chunk gives the last write
+        let last_write = repo::topic_last_write(&mut cx, record.topic_id)
+            .await?
+            .unwrap_or_else(|| record.creation_timestamp());
+
         Ok(types::TopicSystemInfo {
             chunks_number: datafiles.len(),
             is_locked: record.is_locked(),
             total_size_bytes: total_size,
-            created_datetime: record.creation_timestamp().into(),
+            created_datetime: record.creation_timestamp().try_into_datetime(),
+            last_write_datetime: last_write.try_into_datetime(),
         })
     }
 }
 
+/// Reads the provided datafiles keeping at most `parallelism` reads in flight.
+///
+/// Batches are returned following the order of `datafiles`, regardless of the order in which
+/// the reads complete.
+async fn read_chunks(
+    store: &store::Store,
+    format: rw::Format,
+    datafiles: Vec<std::path::PathBuf>,
+    parallelism: usize,
+) -> Result<Vec<RecordBatch>, FacadeError> {
+    let reads = bounded_concurrent(
+        datafiles.into_iter().enumerate().collect(),
+        parallelism,
+        |(idx, datafile)| async move {
+            let buffer = store.read_bytes(&datafile).await?;
+            let reader = rw::ChunkReader::new(format, bytes::Bytes::from_owner(buffer))?;
+            Ok::<_, FacadeError>((idx, reader.read_all()?))
+        },
+    )
+    .await?;
+
+    let mut chunks = reads.into_iter().collect::<Result<Vec<_>, _>>()?;
+    chunks.sort_by_key(|(idx, _)| *idx);
+
+    Ok(chunks
+        .into_iter()
+        .flat_map(|(_, batches)| batches)
+        .collect())
+}
+
+/// Reads the provided datafiles in order, keeping up to `read_ahead` reads in flight beyond
+/// the one being consumed.
+///
+/// Reads are spawned on the runtime, so they progress while the consumer is busy with the
+/// previous chunk.
+fn prefetch_chunks(
+    store: &store::StoreRef,
+    datafiles: Vec<std::path::PathBuf>,
+    read_ahead: usize,
+) -> impl Stream<Item = Result<Vec<u8>, FacadeError>> + use<> {
+    let store = store.clone();
+    futures::stream::iter(datafiles)
+        .map(move |datafile| {
+            let store = store.clone();
+            tokio::spawn(async move { store.read_bytes(&datafile).await })
+        })
+        .buffered(read_ahead + 1)
+        .map(|res| {
+            res.map_err(|e| FacadeError::ConcurrencyError(format!("chunk read failed: {e}")))?
+                .map_err(FacadeError::from)
+        })
+}
+
+/// Returns the location of the datafile of the chunk `index` of the topic at `locator`, named
+/// according to `naming` (see [`types::ChunkNaming`]).
+fn chunk_datafile(
+    locator: &types::TopicResourceLocator,
+    index: usize,
+    format: &rw::Format,
+    naming: types::ChunkNaming,
+    metadata: &rw::ChunkMetadata,
+) -> std::path::PathBuf {
+    match (naming, &metadata.timestamp_range) {
+        (types::ChunkNaming::TimeRange, Some(range)) => {
+            locator.datafile_with_range(index, range, format)
+        }
+        _ => locator.datafile(index, format),
+    }
+}
+
 // Batch Reader needs to implement Stream trait
+
+/// Returns the lifecycle state of the topic at `loc`, the topics are finalized along with their
+/// sequence.
+async fn topic_state(
+    exe: &mut impl repo::AsExec,
+    loc: &types::TopicResourceLocator,
+) -> Result<types::ResourceState, FacadeError> {
+    let record = repo::topic_find_by_locator(exe, loc).await?;
+    let sequence = repo::sequence_find_by_id(exe, record.sequence_id).await?;
+
+    Ok(if sequence.is_locked() {
+        types::ResourceState::Finalized
+    } else if record.is_locked() {
+        types::ResourceState::Locked
+    } else {
+        types::ResourceState::Open
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params;
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    /// Checks that reading chunks concurrently gives the same reordered output of the serial
+    /// path, even when all the rows share the same timestamp and only the read order matters.
+    #[tokio::test]
+    async fn parallel_reorder_matches_serial() {
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let format = rw::Format::Default;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("label", DataType::Utf8, false),
+        ]));
+
+        let mut datafiles = Vec::new();
+        for idx in 0..8 {
+            let labels: Vec<String> = (0..3).map(|row| format!("{}_{}", idx, row)).collect();
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(vec![100; 3])),
+                    Arc::new(StringArray::from(labels)),
+                ],
+            )
+            .unwrap();
+
+            let mut writer = rw::ChunkWriter::try_new(schema.clone(), format).unwrap();
+            writer.write(&batch).unwrap();
+            let (buffer, _, _) = writer.finalize().unwrap();
+
+            let datafile =
+                types::TopicResourceLocator::from("sequence/topic").datafile(idx, &format);
+            store.write_bytes(&datafile, buffer).await.unwrap();
+            datafiles.push(datafile);
+        }
+
+        let serial = read_chunks(&store, format, datafiles.clone(), 1)
+            .await
+            .unwrap();
+        let parallel = read_chunks(&store, format, datafiles, 4).await.unwrap();
+
+        let serial = rw::sort_and_split_by_timestamp(&serial, &[12, 12]).unwrap();
+        let parallel = rw::sort_and_split_by_timestamp(&parallel, &[12, 12]).unwrap();
+
+        assert_eq!(serial, parallel);
+
+        let labels = serial[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(labels.value(0), "0_0");
+        assert_eq!(labels.value(labels.len() - 1), "7_2");
+    }
+
+    /// Checks that the read-ahead keeps the chunks order, and that the reads of the following
+    /// chunks overlap.
+    #[tokio::test]
+    async fn prefetch_read_ahead() {
+        let backend =
+            Arc::new(store::testing::HookedStore::<store::testing::CountingReads>::default());
+        let store: store::StoreRef = Arc::new(store::testing::with_driver(backend.clone()));
+        let format = rw::Format::Default;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("label", DataType::Utf8, false),
+        ]));
+
+        let mut datafiles = Vec::new();
+        for idx in 0..6 {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(vec![idx as i64; 2])),
+                    Arc::new(StringArray::from(vec![
+                        format!("{idx}_0"),
+                        format!("{idx}_1"),
+                    ])),
+                ],
+            )
+            .unwrap();
+
+            let mut writer = rw::ChunkWriter::try_new(schema.clone(), format).unwrap();
+            writer.write(&batch).unwrap();
+            let (buffer, _, _) = writer.finalize().unwrap();
+
+            let datafile =
+                types::TopicResourceLocator::from("sequence/topic").datafile(idx, &format);
+            store.write_bytes(&datafile, buffer).await.unwrap();
+            datafiles.push(datafile);
+        }
+
+        let buffers: Vec<Vec<u8>> = prefetch_chunks(&store, datafiles.clone(), 2)
+            .try_collect()
+            .await
+            .unwrap();
+        assert!(backend.hooks.max_in_flight() > 1);
+
+        let labels: Vec<String> = buffers
+            .into_iter()
+            .flat_map(|buffer| {
+                let reader =
+                    rw::ChunkReader::new(format, bytes::Bytes::from_owner(buffer)).unwrap();
+                reader.read_all().unwrap()
+            })
+            .flat_map(|batch| {
+                let labels = batch
+                    .column(1)
+                    .as_any()
+                    .downcast_ref::<StringArray>()
+                    .unwrap()
+                    .clone();
+                labels
+                    .iter()
+                    .map(|l| l.unwrap().to_owned())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let expected: Vec<String> = (0..6)
+            .flat_map(|idx| [format!("{idx}_0"), format!("{idx}_1")])
+            .collect();
+        assert_eq!(labels, expected);
+
+        // Without read-ahead chunks are read one at a time
+        backend.hooks.reset();
+        let buffers: Vec<Vec<u8>> = prefetch_chunks(&store, datafiles, 0)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(buffers.len(), 6);
+        assert_eq!(backend.hooks.max_in_flight(), 1);
+    }
+}
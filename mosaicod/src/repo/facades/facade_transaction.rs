@@ -0,0 +1,358 @@
+use super::FacadeError;
+use crate::{repo, store};
+use log::{trace, warn};
+use std::path::PathBuf;
+
+/// Prefix of the store location holding the objects saved by the transactions, so that the
+/// objects overwritten or deleted can be restored
+pub const TRANSACTION_PREFIX: &str = ".transactions";
+
+/// Store mutation recorded by a [`FacadeTransaction`], holding what is needed to undo it.
+enum Undo {
+    /// Restores the object at `path` from the copy saved at `backup`, removing the object if
+    /// it did not exist
+    Restore {
+        path: PathBuf,
+        backup: Option<PathBuf>,
+    },
+    /// Moves back an object moved from `src` to `dst`
+    Move { src: PathBuf, dst: PathBuf },
+}
+
+/// Transaction spanning the repository and the store, used to mutate several resources
+/// atomically.
+///
+/// Repository operations are executed on [`FacadeTransaction::tx`], store mutations go
+/// through the transaction methods, which record how to undo them. The objects overwritten or
+/// deleted are first copied by the store under [`TRANSACTION_PREFIX`], so the journal only
+/// holds their locations. On [`FacadeTransaction::commit`] the repository transaction is
+/// committed and the store mutations are kept, on [`FacadeTransaction::rollback`] the
+/// repository transaction is rolled back and the store mutations are undone in reverse order.
+///
+/// The transaction must be concluded explicitly, see [`FacadeTransaction::finish`]. Dropping
+/// it rolls back the repository transaction only, the store mutations are left in place and
+/// reported with a warning. The undo is best effort, since a concurrent writer may have changed
+/// the same objects.
+pub struct FacadeTransaction<'a> {
+    tx: Option<repo::Tx<'a>>,
+    store: store::StoreRef,
+    journal: Vec<Undo>,
+    /// Location of the copies saved by this transaction
+    backups: PathBuf,
+}
+
+impl<'a> FacadeTransaction<'a> {
+    pub async fn begin(
+        repo: &'a repo::Repository,
+        store: store::StoreRef,
+    ) -> Result<Self, FacadeError> {
+        Ok(Self {
+            tx: Some(repo.transaction().await?),
+            store,
+            journal: Vec::new(),
+            backups: PathBuf::from(TRANSACTION_PREFIX).join(uuid::Uuid::new_v4().to_string()),
+        })
+    }
+
+    /// Returns the repository transaction, to be used for the database operations
+    pub fn tx(&mut self) -> &mut repo::Tx<'a> {
+        self.tx
+            .as_mut()
+            .expect("transaction already committed or rolled back")
+    }
+
+    /// Writes `bytes` to `path`, overwriting the existing object if any
+    pub async fn write(
+        &mut self,
+        path: impl Into<PathBuf>,
+        bytes: Vec<u8>,
+    ) -> Result<(), FacadeError> {
+        let path = path.into();
+        let backup = self.save_existing(&path).await?;
+
+        self.journal.push(Undo::Restore {
+            path: path.clone(),
+            backup,
+        });
+        self.store.write_bytes(&path, bytes).await?;
+        Ok(())
+    }
+
+    /// Writes `bytes` to `path` only if no object exists at that location, returns `false`
+    /// (without writing) if the object already exists, see [`store::Store::put_if_absent`]
+    pub async fn create(
+        &mut self,
+        path: impl Into<PathBuf>,
+        bytes: Vec<u8>,
+    ) -> Result<bool, FacadeError> {
+        let path = path.into();
+
+        if !self.store.put_if_absent(&path, bytes).await? {
+            return Ok(false);
+        }
+        self.journal.push(Undo::Restore { path, backup: None });
+        Ok(true)
+    }
+
+    /// Moves the object located at `src` to `dst`, overwriting `dst` if existing
+    pub async fn rename(
+        &mut self,
+        src: impl Into<PathBuf>,
+        dst: impl Into<PathBuf>,
+    ) -> Result<(), FacadeError> {
+        let (src, dst) = (src.into(), dst.into());
+        let backup = self.save_existing(&dst).await?;
+
+        self.store.move_verified(&src, &dst).await?;
+        self.journal.push(Undo::Restore {
+            path: dst.clone(),
+            backup,
+        });
+        self.journal.push(Undo::Move { src, dst });
+        Ok(())
+    }
+
+    /// Deletes the object located at `path`
+    pub async fn delete(&mut self, path: impl Into<PathBuf>) -> Result<(), FacadeError> {
+        let path = path.into();
+        let backup = self.backup_path();
+
+        self.store.copy(&path, &backup).await?;
+        self.store.delete(&path).await?;
+        self.journal.push(Undo::Restore {
+            path,
+            backup: Some(backup),
+        });
+        Ok(())
+    }
+
+    /// Commits the repository transaction and keeps the store mutations.
+    ///
+    /// If the repository commit fails the store mutations are undone.
+    pub async fn commit(mut self) -> Result<(), FacadeError> {
+        let tx = self
+            .tx
+            .take()
+            .expect("transaction already committed or rolled back");
+        let journal = std::mem::take(&mut self.journal);
+
+        if let Err(e) = tx.commit().await {
+            undo(&self.store, journal).await?;
+            return Err(e.into());
+        }
+
+        // The saved copies are no longer needed, leftovers are only wasted space
+        for entry in journal {
+            if let Undo::Restore {
+                backup: Some(backup),
+                ..
+            } = entry
+                && let Err(e) = self.store.delete(&backup).await
+            {
+                warn!(
+                    "unable to remove transaction backup {}: {e}",
+                    backup.display()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Rolls back the repository transaction and undoes the store mutations
+    pub async fn rollback(mut self) -> Result<(), FacadeError> {
+        if let Some(tx) = self.tx.take() {
+            tx.rollback().await?;
+        }
+        undo(&self.store, std::mem::take(&mut self.journal)).await
+    }
+
+    /// Concludes the transaction according to `result`: commits it if the operation
+    /// succeeded, otherwise rolls it back and returns the operation error.
+    ///
+    /// A failed rollback is logged, the operation error is the one reported.
+    pub async fn finish<T>(self, result: Result<T, FacadeError>) -> Result<T, FacadeError> {
+        match result {
+            Ok(value) => {
+                self.commit().await?;
+                Ok(value)
+            }
+            Err(e) => {
+                if let Err(rollback) = self.rollback().await {
+                    warn!("unable to roll back transaction: {rollback}");
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns a new location for a copy saved by this transaction
+    fn backup_path(&self) -> PathBuf {
+        self.backups.join(self.journal.len().to_string())
+    }
+
+    /// Copies the object located at `path`, if any, returning the location of the copy
+    async fn save_existing(&self, path: &std::path::Path) -> Result<Option<PathBuf>, FacadeError> {
+        let backup = self.backup_path();
+        match self.store.copy(path, &backup).await {
+            Ok(()) => Ok(Some(backup)),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for FacadeTransaction<'_> {
+    fn drop(&mut self) {
+        // The repository transaction is rolled back when dropped, the store mutations can't be
+        // undone without blocking
+        if !self.journal.is_empty() {
+            warn!(
+                "transaction dropped without rollback, {} store mutations not undone (saved copies under {})",
+                self.journal.len(),
+                self.backups.display()
+            );
+        }
+    }
+}
+
+/// Undoes the recorded store mutations, latest first
+async fn undo(store: &store::Store, journal: Vec<Undo>) -> Result<(), FacadeError> {
+    trace!("undoing {} store mutations", journal.len());
+
+    for entry in journal.into_iter().rev() {
+        match entry {
+            Undo::Restore {
+                path,
+                backup: Some(backup),
+            } => store.move_verified(&backup, &path).await?,
+            Undo::Restore { path, backup: None } => match store.delete(&path).await {
+                Err(e) if !e.is_not_found() => return Err(e.into()),
+                _ => {}
+            },
+            Undo::Move { src, dst } => store.move_verified(&dst, &src).await?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types;
+
+    const SEQUENCE: &str = "tx_sequence";
+
+    /// Writes the objects used by the transaction tests, returning the initial store content
+    async fn fixture(store: &store::testing::Store) -> Vec<(&'static str, Vec<u8>)> {
+        let initial = vec![
+            ("tx/overwritten", b"original".to_vec()),
+            ("tx/deleted", b"deleted".to_vec()),
+            ("tx/moved", b"moved".to_vec()),
+        ];
+        for (path, bytes) in &initial {
+            store.write_bytes(path, bytes.clone()).await.unwrap();
+        }
+        initial
+    }
+
+    /// Returns true if the store holds exactly the `initial` objects
+    async fn store_unchanged(store: &store::testing::Store, initial: &[(&str, Vec<u8>)]) -> bool {
+        let mut files = store.list("tx", None).await.unwrap();
+        files.sort();
+        let mut expected: Vec<String> = initial.iter().map(|(p, _)| p.to_string()).collect();
+        expected.sort();
+        if files != expected {
+            return false;
+        }
+
+        for (path, bytes) in initial {
+            if store.read_bytes(path).await.unwrap() != *bytes {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns true if no copy saved by the transactions is left in the store
+    async fn no_backups(store: &store::testing::Store) -> bool {
+        store
+            .list(TRANSACTION_PREFIX, None)
+            .await
+            .unwrap()
+            .is_empty()
+    }
+
+    /// Returns true if the sequence created by [`mutations`] is in the repository
+    async fn sequence_exists(repo: &repo::Repository) -> bool {
+        let locator = types::SequenceResourceLocator::from(SEQUENCE);
+        repo::sequence_find_by_locator(&mut repo.connection(), &locator)
+            .await
+            .is_ok()
+    }
+
+    async fn mutations(tx: &mut FacadeTransaction<'_>) {
+        repo::sequence_create(tx.tx(), &repo::SequenceRecord::new(SEQUENCE))
+            .await
+            .unwrap();
+
+        assert!(tx.create("tx/created", b"created".to_vec()).await.unwrap());
+        assert!(!tx.create("tx/moved", b"created".to_vec()).await.unwrap());
+        tx.write("tx/overwritten", b"updated".to_vec())
+            .await
+            .unwrap();
+        tx.delete("tx/deleted").await.unwrap();
+        tx.rename("tx/moved", "tx/renamed").await.unwrap();
+    }
+
+    #[sqlx::test]
+    /// Test checking that the repository and store mutations of a committed transaction are
+    /// kept.
+    async fn transaction_commit(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        fixture(&store).await;
+
+        let mut tx = repo.atomic((*store).clone()).await.unwrap();
+        mutations(&mut tx).await;
+        tx.commit().await.unwrap();
+
+        assert!(sequence_exists(&repo).await);
+        assert_eq!(store.read_bytes("tx/created").await.unwrap(), b"created");
+        assert_eq!(
+            store.read_bytes("tx/overwritten").await.unwrap(),
+            b"updated"
+        );
+        assert_eq!(store.read_bytes("tx/renamed").await.unwrap(), b"moved");
+        assert!(store.read_bytes("tx/deleted").await.is_err());
+        assert!(store.read_bytes("tx/moved").await.is_err());
+        assert!(no_backups(&store).await);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that a rolled back transaction leaves the repository and the store
+    /// unchanged, while a transaction dropped without rollback only reverts the repository.
+    async fn transaction_rollback(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let initial = fixture(&store).await;
+
+        let mut tx = repo.atomic((*store).clone()).await.unwrap();
+        mutations(&mut tx).await;
+        tx.rollback().await.unwrap();
+        assert!(!sequence_exists(&repo).await);
+        assert!(store_unchanged(&store, &initial).await);
+        assert!(no_backups(&store).await);
+
+        // Dropped transactions only roll back the repository
+        let mut tx = repo.atomic((*store).clone()).await.unwrap();
+        mutations(&mut tx).await;
+        drop(tx);
+        assert!(!sequence_exists(&repo).await);
+        assert_eq!(store.read_bytes("tx/created").await.unwrap(), b"created");
+
+        Ok(())
+    }
+}
@@ -32,3 +32,137 @@ pub use facade_chunk::*;
 
 mod facade_query;
 pub use facade_query::*;
+
+mod facade_idempotency_key;
+pub use facade_idempotency_key::*;
+
+mod facade_transaction;
+pub use facade_transaction::*;
+
+mod facade_lease;
+pub use facade_lease::*;
+
+use crate::{repo, store, types};
+use futures::{Stream, StreamExt, TryStreamExt, stream};
+use log::trace;
+
+/// Deletes the metadata file of `resource` if the repository places it outside the resource
+/// path (see [`repo::MetadataPathResolver`]), since it would not be removed along with the
+/// resource data. A missing metadata file is not an error.
+async fn delete_relocated_metadata(
+    store: &store::Store,
+    repo: &repo::Repository,
+    resource: &dyn types::Resource,
+) -> Result<(), FacadeError> {
+    let path = repo.metadata_path(resource);
+    if path.starts_with(resource.name()) {
+        return Ok(());
+    }
+
+    match store.delete(&path).await {
+        Err(e) if !e.is_not_found() => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Streams all the resources of the repository, each sequence followed by its topics.
+///
+/// The topics of a sequence are retrieved only when the stream reaches the sequence.
+pub fn iter_resources(
+    store: store::StoreRef,
+    repo: repo::Repository,
+) -> impl Stream<Item = Result<Box<dyn types::Resource>, FacadeError>> {
+    stream::once(FacadeSequence::all(repo.clone()))
+        .map_ok(|sequences| stream::iter(sequences).map(Ok))
+        .try_flatten()
+        .and_then(move |loc| {
+            let handle = FacadeSequence::new(loc.into(), store.clone(), repo.clone());
+            async move {
+                let topics = handle.topic_list().await?;
+                let resources =
+                    std::iter::once(Box::new(handle.locator) as Box<dyn types::Resource>).chain(
+                        topics
+                            .into_iter()
+                            .map(|t| Box::new(t) as Box<dyn types::Resource>),
+                    );
+                Ok(stream::iter(resources).map(Ok))
+            }
+        })
+        .try_flatten()
+}
+
+/// Repairs the last chunk of every topic (see [`FacadeTopic::repair`]), called at startup to
+/// remove the partial chunks left by a crash.
+///
+/// Each topic is repaired holding its lease on behalf of `owner`, the topics leased by another
+/// instance are being written and are skipped. Returns the locations of the removed chunks.
+pub async fn repair_topics(
+    store: store::StoreRef,
+    repo: repo::Repository,
+    owner: &str,
+    ttl: std::time::Duration,
+) -> Result<Vec<String>, FacadeError> {
+    let mut removed = Vec::new();
+    for locator in FacadeTopic::all(&repo).await? {
+        let handle = FacadeTopic::new(locator.name().clone(), store.clone(), repo.clone());
+
+        let lease = match handle.acquire_lease(owner, ttl).await {
+            Err(FacadeError::TopicLeased(other)) => {
+                trace!("{} leased by `{}`, repair skipped", handle.locator, other);
+                continue;
+            }
+            lease => lease?,
+        };
+        let repaired = handle.repair().await;
+        lease.release().await?;
+        removed.extend(repaired?);
+    }
+
+    Ok(removed)
+}
+
+/// Copies the objects of `resource` to the same locations in the `target` store, verifying
+/// each copy (see [`store::Store::copy_verified_to`]).
+///
+/// The objects of a resource are its metadata file and the objects placed directly under its
+/// path, the objects of its sub-resources (e.g. the topics of a sequence) are left to their
+/// own migration. Objects already present in the target are skipped, so an interrupted
+/// migration can be resumed. Returns the number of objects copied and
+/// skipped.
+pub async fn migrate_resource(
+    store: &store::Store,
+    repo: &repo::Repository,
+    resource: &dyn types::Resource,
+    target: &store::Store,
+) -> Result<(usize, usize), FacadeError> {
+    let root = std::path::Path::new(resource.name());
+    let mut paths: Vec<std::path::PathBuf> = store
+        .list(resource.name(), None)
+        .await?
+        .into_iter()
+        .map(std::path::PathBuf::from)
+        .filter(|path| path.parent() == Some(root))
+        .collect();
+
+    // Metadata files placed outside the resource paths are not listed along with the data
+    let metadata = repo.metadata_path(resource);
+    if !metadata.starts_with(root) {
+        paths.push(metadata);
+    }
+
+    let (mut copied, mut skipped) = (0, 0);
+    for path in &paths {
+        if store.copy_verified_to(path, target).await? {
+            copied += 1;
+        } else {
+            skipped += 1;
+        }
+    }
+
+    trace!(
+        "`{}` migrated, {} objects copied, {} skipped",
+        resource, copied, skipped
+    );
+
+    Ok((copied, skipped))
+}
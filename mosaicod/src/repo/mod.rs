@@ -1,5 +1,8 @@
 pub mod core;
-pub use core::{AsExec, Config, Cx, Database, Repository, Tx, UNREGISTERED};
+pub use core::{
+    AsExec, Config, Cx, Database, DefaultMetadataPath, MetadataPathResolver, PrefixedMetadataPath,
+    Repository, Tx, UNREGISTERED,
+};
 
 mod facades;
 pub use facades::*;
@@ -16,6 +19,10 @@ pub use error::Error;
 #[cfg(test)]
 pub use core::testing;
 
+// Queries used by tests to inspect the repository or reproduce partial operations
+#[cfg(test)]
+pub use sql_models::{chunks_find_by_topic_id, sequence_find_all_topic_names, sequence_lock};
+
 // Private to module exports
 
 mod sql_models;
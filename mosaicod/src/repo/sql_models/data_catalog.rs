@@ -1,4 +1,4 @@
-use crate::repo;
+use crate::{repo, types, utils};
 
 #[derive(Debug)]
 pub struct Column {
@@ -29,6 +29,15 @@ pub struct Chunk {
     pub(super) data_file: String,
     pub size_bytes: i64,
     pub row_count: i64,
+    /// UNIX timestamp in milliseconds of the chunk write, missing for chunks written by
+    /// older versions
+    pub creation_unix_tstamp: Option<i64>,
+    /// SHA-256 digest of the serialized chunk, missing for chunks written by older versions
+    pub content_hash: Option<Vec<u8>>,
+    /// UNIX timestamp in milliseconds of the last read of the chunk data, missing if never
+    /// read
+    pub last_access_unix_tstamp: Option<i64>,
+    pub(super) tier: String,
 }
 
 impl Chunk {
@@ -45,12 +54,40 @@ impl Chunk {
             data_file: data_file.as_ref().to_string_lossy().to_string(),
             size_bytes,
             row_count,
+            creation_unix_tstamp: Some(types::Timestamp::now().into()),
+            content_hash: None,
+            last_access_unix_tstamp: None,
+            tier: types::StorageTier::default().to_string(),
         }
     }
 
+    pub fn with_content_hash(mut self, content_hash: utils::hash::Digest) -> Self {
+        self.content_hash = Some(content_hash.to_vec());
+        self
+    }
+
     pub fn data_file(&self) -> &std::path::Path {
         std::path::Path::new(&self.data_file)
     }
+
+    pub fn tier(&self) -> types::StorageTier {
+        self.tier
+            .parse()
+            .expect("BUG: invalid storage tier in database")
+    }
+
+    /// Returns the timestamp of the chunk write, [`None`] for chunks written by older versions.
+    pub fn write_timestamp(&self) -> Option<types::Timestamp> {
+        self.creation_unix_tstamp.map(types::Timestamp::from)
+    }
+
+    /// Returns the timestamp of the last read of the chunk, or of its write if never read.
+    /// Returns [`None`] for chunks written by older versions and never read.
+    pub fn last_access(&self) -> Option<types::Timestamp> {
+        self.last_access_unix_tstamp
+            .or(self.creation_unix_tstamp)
+            .map(types::Timestamp::from)
+    }
 }
 
 /// Chunk of literal data associated with a column.
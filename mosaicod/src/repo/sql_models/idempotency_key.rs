@@ -0,0 +1,20 @@
+/// Idempotency key reserved by a mutating action, holding the action response once completed.
+#[derive(Debug)]
+pub struct IdempotencyKeyRecord {
+    pub idempotency_key: String,
+    /// Name of the action which reserved the key
+    pub action: String,
+    /// Hash of the action body, stored as the bit pattern of the unsigned hash
+    pub body_hash: i64,
+    /// Serialized response of the action, missing while the action is in progress
+    pub response: Option<Vec<u8>>,
+    /// UNIX timestamp in milliseconds of the key reservation
+    pub creation_unix_tstamp: i64,
+}
+
+impl IdempotencyKeyRecord {
+    /// Returns true if the key was reserved by the request `action` with body hash `body_hash`.
+    pub fn matches(&self, action: &str, body_hash: u64) -> bool {
+        self.action == action && self.body_hash == body_hash as i64
+    }
+}
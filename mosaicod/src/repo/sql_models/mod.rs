@@ -3,6 +3,9 @@
 mod data_catalog;
 pub use data_catalog::*;
 
+mod idempotency_key;
+pub use idempotency_key::*;
+
 mod layers;
 pub use layers::*;
 
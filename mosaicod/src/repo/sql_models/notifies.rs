@@ -36,7 +36,7 @@ impl SequenceNotify {
             target: Box::new(loc),
             notify_type: self.notify_type(),
             msg: self.msg,
-            created_at: types::Timestamp::from(self.creation_unix_tstamp).into(),
+            created_at: types::Timestamp::from(self.creation_unix_tstamp).try_into_datetime(),
         }
     }
 
@@ -96,7 +96,7 @@ impl TopicNotify {
             target: Box::new(loc),
             notify_type: self.notify_type(),
             msg: self.msg,
-            created_at: types::Timestamp::from(self.creation_unix_tstamp).into(),
+            created_at: types::Timestamp::from(self.creation_unix_tstamp).try_into_datetime(),
         }
     }
 
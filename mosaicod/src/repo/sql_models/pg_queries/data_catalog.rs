@@ -1,10 +1,12 @@
 use crate::{
-    query,
+    params, query,
     repo::{self, sql_models},
     types::{self, Resource},
+    utils,
 };
 use log::trace;
 use sqlx::{Row, postgres::PgRow};
+use std::collections::HashMap;
 
 pub async fn column_get_or_create(
     exec: &mut impl repo::AsExec,
@@ -35,20 +37,170 @@ pub async fn chunk_create(
 ) -> Result<sql_models::Chunk, repo::Error> {
     let res = sqlx::query_as!(
         sql_models::Chunk,
-        r#"INSERT INTO chunk_t(chunk_uuid, topic_id, data_file, size_bytes, row_count)
-        VALUES ($1, $2, $3, $4, $5)
+        r#"INSERT INTO chunk_t(chunk_uuid, topic_id, data_file, size_bytes, row_count, creation_unix_tstamp, content_hash)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
         RETURNING *"#,
         chunk.chunk_uuid,
         chunk.topic_id,
         chunk.data_file,
         chunk.size_bytes,
         chunk.row_count,
+        chunk.creation_unix_tstamp,
+        chunk.content_hash,
     )
     .fetch_one(exec.as_exec())
     .await?;
     Ok(res)
 }
 
+/// Registers for the topic `topic_id` a copy of the chunk `chunk_id` whose data is stored in
+/// `data_file`, the column statistics of the chunk are copied along with it.
+pub async fn chunk_copy(
+    exec: &mut impl repo::AsExec,
+    chunk_id: i32,
+    topic_id: i32,
+    data_file: impl AsRef<std::path::Path>,
+) -> Result<sql_models::Chunk, repo::Error> {
+    let data_file = data_file.as_ref().to_string_lossy().to_string();
+    let chunk = sqlx::query_as!(
+        sql_models::Chunk,
+        r#"INSERT INTO chunk_t(chunk_uuid, topic_id, data_file, size_bytes, row_count, creation_unix_tstamp, content_hash)
+        SELECT $1, $2, $3, size_bytes, row_count, creation_unix_tstamp, content_hash
+        FROM chunk_t
+        WHERE chunk_id = $4
+        RETURNING *"#,
+        uuid::Uuid::new_v4(),
+        topic_id,
+        data_file,
+        chunk_id,
+    )
+    .fetch_one(exec.as_exec())
+    .await?;
+
+    sqlx::query!(
+        r#"INSERT INTO column_chunk_numeric_t(column_id, chunk_id, min_value, max_value, has_null, has_nan)
+        SELECT column_id, $1, min_value, max_value, has_null, has_nan
+        FROM column_chunk_numeric_t
+        WHERE chunk_id = $2"#,
+        chunk.chunk_id,
+        chunk_id,
+    )
+    .execute(exec.as_exec())
+    .await?;
+
+    sqlx::query!(
+        r#"INSERT INTO column_chunk_literal_t(column_id, chunk_id, min_value, max_value, has_null)
+        SELECT column_id, $1, min_value, max_value, has_null
+        FROM column_chunk_literal_t
+        WHERE chunk_id = $2"#,
+        chunk.chunk_id,
+        chunk_id,
+    )
+    .execute(exec.as_exec())
+    .await?;
+
+    Ok(chunk)
+}
+
+/// Deletes the chunk associated with the provided data file.
+///
+/// Column statistics associated with the chunk are removed in cascade.
+pub async fn chunk_delete_by_data_file(
+    exec: &mut impl repo::AsExec,
+    data_file: impl AsRef<std::path::Path>,
+) -> Result<(), repo::Error> {
+    let data_file = data_file.as_ref().to_string_lossy().to_string();
+    trace!("deleting chunk with data file `{}`", data_file);
+    sqlx::query!("DELETE FROM chunk_t WHERE data_file = $1", data_file)
+        .execute(exec.as_exec())
+        .await?;
+    Ok(())
+}
+
+/// Returns all the chunks registered for the provided topic.
+pub async fn chunks_find_by_topic_id(
+    exec: &mut impl repo::AsExec,
+    topic_id: i32,
+) -> Result<Vec<sql_models::Chunk>, repo::Error> {
+    let res = sqlx::query_as!(
+        sql_models::Chunk,
+        "SELECT * FROM chunk_t WHERE topic_id = $1 ORDER BY data_file",
+        topic_id,
+    )
+    .fetch_all(exec.as_exec())
+    .await?;
+    Ok(res)
+}
+
+/// Returns the total number of rows of the chunks registered for the provided topics.
+///
+/// If `topic_ids` is [`None`] the rows of all the chunks are counted.
+pub async fn chunks_row_count(
+    exec: &mut impl repo::AsExec,
+    topic_ids: Option<&[i32]>,
+) -> Result<i64, repo::Error> {
+    let res = match topic_ids {
+        Some(ids) => {
+            sqlx::query_scalar!(
+                r#"SELECT COALESCE(SUM(row_count), 0)::BIGINT as "row_count!"
+                FROM chunk_t WHERE topic_id = ANY($1)"#,
+                ids,
+            )
+            .fetch_one(exec.as_exec())
+            .await?
+        }
+        None => {
+            sqlx::query_scalar!(
+                r#"SELECT COALESCE(SUM(row_count), 0)::BIGINT as "row_count!" FROM chunk_t"#
+            )
+            .fetch_one(exec.as_exec())
+            .await?
+        }
+    };
+    Ok(res)
+}
+
+/// Returns the number of chunks registered for the provided topics.
+///
+/// If `topic_ids` is [`None`] all the chunks are counted.
+pub async fn chunks_count(
+    exec: &mut impl repo::AsExec,
+    topic_ids: Option<&[i32]>,
+) -> Result<i64, repo::Error> {
+    let res = match topic_ids {
+        Some(ids) => {
+            sqlx::query_scalar!(
+                r#"SELECT COUNT(*) as "count!" FROM chunk_t WHERE topic_id = ANY($1)"#,
+                ids,
+            )
+            .fetch_one(exec.as_exec())
+            .await?
+        }
+        None => {
+            sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM chunk_t"#)
+                .fetch_one(exec.as_exec())
+                .await?
+        }
+    };
+    Ok(res)
+}
+
+/// Returns the ontology fields (`<ontology_tag>.<column_name>`) among `fields` that are
+/// registered in the data catalog.
+pub async fn columns_known(
+    exec: &mut impl repo::AsExec,
+    fields: &[String],
+) -> Result<Vec<String>, repo::Error> {
+    let res = sqlx::query_scalar!(
+        r#"SELECT ontology_tag || '.' || column_name as "field!" FROM column_t
+        WHERE ontology_tag || '.' || column_name = ANY($1)"#,
+        fields,
+    )
+    .fetch_all(exec.as_exec())
+    .await?;
+    Ok(res)
+}
+
 pub async fn column_chunk_literal_create(
     exec: &mut impl repo::AsExec,
     val: &sql_models::ColumnChunkLiteral,
@@ -194,9 +346,83 @@ fn cast_chunk_data(row: PgRow) -> Result<sql_models::Chunk, repo::Error> {
         data_file: row.try_get("data_file")?,
         size_bytes: row.try_get("size_bytes")?,
         row_count: row.try_get("row_count")?,
+        creation_unix_tstamp: row.try_get("creation_unix_tstamp")?,
+        content_hash: row.try_get("content_hash")?,
+        last_access_unix_tstamp: row.try_get("last_access_unix_tstamp")?,
+        tier: row.try_get("tier")?,
     })
 }
 
+/// Records `now` as the last access time of the chunks associated with the provided data
+/// files.
+pub async fn chunks_touch(
+    exec: &mut impl repo::AsExec,
+    data_files: &[String],
+    now: types::Timestamp,
+) -> Result<(), repo::Error> {
+    sqlx::query!(
+        "UPDATE chunk_t SET last_access_unix_tstamp = $1 WHERE data_file = ANY($2)",
+        i64::from(now),
+        data_files,
+    )
+    .execute(exec.as_exec())
+    .await?;
+    Ok(())
+}
+
+/// Sets the storage tier of the chunk associated with the provided data file.
+pub async fn chunk_update_tier(
+    exec: &mut impl repo::AsExec,
+    data_file: impl AsRef<std::path::Path>,
+    tier: types::StorageTier,
+) -> Result<(), repo::Error> {
+    let data_file = data_file.as_ref().to_string_lossy().to_string();
+    trace!(
+        "moving chunk with data file `{}` to {} tier",
+        data_file, tier
+    );
+    sqlx::query!(
+        "UPDATE chunk_t SET tier = $1 WHERE data_file = $2",
+        tier.to_string(),
+        data_file,
+    )
+    .execute(exec.as_exec())
+    .await?;
+    Ok(())
+}
+
+/// Returns the content hash of the last chunk of a topic, [`None`] if the topic has no chunks
+/// or the last chunk has no hash.
+pub async fn topic_last_chunk_hash(
+    exec: &mut impl repo::AsExec,
+    topic_id: i32,
+) -> Result<Option<utils::hash::Digest>, repo::Error> {
+    let res = sqlx::query_scalar!(
+        "SELECT content_hash FROM chunk_t WHERE topic_id = $1 ORDER BY data_file DESC LIMIT 1",
+        topic_id,
+    )
+    .fetch_optional(exec.as_exec())
+    .await?;
+
+    Ok(res.flatten().and_then(|hash| hash.try_into().ok()))
+}
+
+/// Returns the write timestamp of the most recent chunk of a topic, [`None`] if the topic
+/// has no (timestamped) chunks.
+pub async fn topic_last_write(
+    exec: &mut impl repo::AsExec,
+    topic_id: i32,
+) -> Result<Option<types::Timestamp>, repo::Error> {
+    let res = sqlx::query_scalar!(
+        "SELECT MAX(creation_unix_tstamp) FROM chunk_t WHERE topic_id = $1",
+        topic_id,
+    )
+    .fetch_one(exec.as_exec())
+    .await?;
+
+    Ok(res.map(types::Timestamp::from))
+}
+
 /// Returns aggregated size and row count statistics for all chunks belonging to a topic.
 pub async fn topic_get_stats(
     exec: &mut impl repo::AsExec,
@@ -218,3 +444,135 @@ pub async fn topic_get_stats(
         total_row_count: res.total_row_count,
     })
 }
+
+/// Returns the range of data timestamps of each of the given topics, aggregated from the
+/// statistics of the timestamp column of their chunks. Topics without chunks are missing from
+/// the returned map.
+///
+/// Statistics are stored as doubles, which can't represent exactly timestamps above 2^53, so
+/// the returned ranges are widened by the rounding error to always contain the data.
+pub async fn topics_timestamp_extent(
+    exec: &mut impl repo::AsExec,
+    topic_ids: &[i32],
+) -> Result<HashMap<i32, types::TimestampRange>, repo::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT c.topic_id, MIN(ccn.min_value) as "min_value!", MAX(ccn.max_value) as "max_value!"
+        FROM column_chunk_numeric_t ccn
+        JOIN column_t col ON col.column_id = ccn.column_id
+        JOIN chunk_t c ON c.chunk_id = ccn.chunk_id
+        WHERE c.topic_id = ANY($1) AND col.column_name = $2
+        GROUP BY c.topic_id"#,
+        topic_ids,
+        params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+    )
+    .fetch_all(exec.as_exec())
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let start = (row.min_value - row.min_value.abs() * f64::EPSILON).floor() as i64;
+            let end = (row.max_value + row.max_value.abs() * f64::EPSILON).ceil() as i64;
+            (
+                row.topic_id,
+                types::TimestampRange::new(start.into(), end.into()),
+            )
+        })
+        .collect())
+}
+
+/// Returns the range of data timestamps of each of the given chunks, from the statistics of
+/// the timestamp column. Chunks without statistics are missing from the returned map.
+///
+/// As in [`topics_timestamp_extent`], ranges are widened by the rounding error of the
+/// statistics to always contain the data of the chunk.
+pub async fn chunks_timestamp_extent(
+    exec: &mut impl repo::AsExec,
+    chunk_ids: &[i32],
+) -> Result<HashMap<i32, types::TimestampRange>, repo::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT ccn.chunk_id, ccn.min_value, ccn.max_value
+        FROM column_chunk_numeric_t ccn
+        JOIN column_t col ON col.column_id = ccn.column_id
+        WHERE ccn.chunk_id = ANY($1) AND col.column_name = $2"#,
+        chunk_ids,
+        params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+    )
+    .fetch_all(exec.as_exec())
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let start = (row.min_value - row.min_value.abs() * f64::EPSILON).floor() as i64;
+            let end = (row.max_value + row.max_value.abs() * f64::EPSILON).ceil() as i64;
+            (
+                row.chunk_id,
+                types::TimestampRange::new(start.into(), end.into()),
+            )
+        })
+        .collect())
+}
+
+/// Returns the largest data timestamp of a topic, from the statistics of the timestamp column
+/// of its chunks. Returns [`None`] if the topic has no chunks.
+///
+/// Unlike [`topics_timestamp_extent`] the timestamp is narrowed by the rounding error of the
+/// statistics, so that it never exceeds the timestamps of the data.
+pub async fn topic_max_timestamp(
+    exec: &mut impl repo::AsExec,
+    topic_id: i32,
+) -> Result<Option<i64>, repo::Error> {
+    let res = sqlx::query_scalar!(
+        r#"SELECT MAX(ccn.max_value)
+        FROM column_chunk_numeric_t ccn
+        JOIN column_t col ON col.column_id = ccn.column_id
+        JOIN chunk_t c ON c.chunk_id = ccn.chunk_id
+        WHERE c.topic_id = $1 AND col.column_name = $2"#,
+        topic_id,
+        params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+    )
+    .fetch_one(exec.as_exec())
+    .await?;
+
+    Ok(res.map(|max| (max - max.abs() * f64::EPSILON).floor() as i64))
+}
+
+/// Returns the data file of each chunk of a topic along with the range of its data timestamps,
+/// from the statistics of the timestamp column. The range is [`None`] for chunks without
+/// statistics.
+///
+/// As in [`topics_timestamp_extent`], ranges are widened by the rounding error of the
+/// statistics to always contain the data of the chunk.
+pub async fn chunks_timestamp_bounds(
+    exec: &mut impl repo::AsExec,
+    topic_id: i32,
+) -> Result<Vec<(String, Option<(i64, i64)>)>, repo::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT c.data_file, ccn.min_value as "min_value?", ccn.max_value as "max_value?"
+        FROM chunk_t c
+        LEFT JOIN (
+            column_chunk_numeric_t ccn
+            JOIN column_t col ON col.column_id = ccn.column_id AND col.column_name = $2
+        ) ON ccn.chunk_id = c.chunk_id
+        WHERE c.topic_id = $1
+        ORDER BY c.data_file"#,
+        topic_id,
+        params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+    )
+    .fetch_all(exec.as_exec())
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let bounds = row.min_value.zip(row.max_value).map(|(min, max)| {
+                (
+                    (min - min.abs() * f64::EPSILON).floor() as i64,
+                    (max + max.abs() * f64::EPSILON).ceil() as i64,
+                )
+            });
+            (row.data_file, bounds)
+        })
+        .collect())
+}
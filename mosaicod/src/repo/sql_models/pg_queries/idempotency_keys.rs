@@ -0,0 +1,106 @@
+use crate::repo::{self, Error, sql_models};
+use log::trace;
+
+/// Returns the record of `key`, keys created before `min_tstamp` are ignored.
+pub async fn idempotency_key_find(
+    exec: &mut impl repo::AsExec,
+    key: &str,
+    min_tstamp: i64,
+) -> Result<Option<sql_models::IdempotencyKeyRecord>, Error> {
+    let res = sqlx::query_as!(
+        sql_models::IdempotencyKeyRecord,
+        "SELECT * FROM idempotency_key_t
+        WHERE idempotency_key = $1 AND creation_unix_tstamp >= $2",
+        key,
+        min_tstamp,
+    )
+    .fetch_optional(exec.as_exec())
+    .await?;
+    Ok(res)
+}
+
+/// Reserves `key` for the execution of `action`, returns false if the key is already
+/// reserved.
+///
+/// The reservation relies on the primary key constraint, so that only one of the concurrent
+/// reservations of the same key succeeds.
+pub async fn idempotency_key_reserve(
+    exec: &mut impl repo::AsExec,
+    key: &str,
+    action: &str,
+    body_hash: u64,
+    tstamp: i64,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        "INSERT INTO idempotency_key_t(idempotency_key, action, body_hash, creation_unix_tstamp)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (idempotency_key) DO NOTHING",
+        key,
+        action,
+        body_hash as i64,
+        tstamp,
+    )
+    .execute(exec.as_exec())
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Records the `response` of the action which reserved `key`.
+pub async fn idempotency_key_complete(
+    exec: &mut impl repo::AsExec,
+    key: &str,
+    response: &[u8],
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE idempotency_key_t SET response = $1 WHERE idempotency_key = $2",
+        response,
+        key,
+    )
+    .execute(exec.as_exec())
+    .await?;
+    Ok(())
+}
+
+/// Deletes `key`, releasing its reservation.
+pub async fn idempotency_key_delete(exec: &mut impl repo::AsExec, key: &str) -> Result<(), Error> {
+    sqlx::query!(
+        "DELETE FROM idempotency_key_t WHERE idempotency_key = $1",
+        key
+    )
+    .execute(exec.as_exec())
+    .await?;
+    Ok(())
+}
+
+/// Deletes the keys reserved before `min_tstamp` whose action never completed.
+pub async fn idempotency_key_purge_pending(
+    exec: &mut impl repo::AsExec,
+    min_tstamp: i64,
+) -> Result<(), Error> {
+    trace!(
+        "purging pending idempotency keys reserved before {}",
+        min_tstamp
+    );
+    sqlx::query!(
+        "DELETE FROM idempotency_key_t WHERE response IS NULL AND creation_unix_tstamp < $1",
+        min_tstamp,
+    )
+    .execute(exec.as_exec())
+    .await?;
+    Ok(())
+}
+
+/// Deletes all the keys created before `min_tstamp`.
+pub async fn idempotency_key_purge(
+    exec: &mut impl repo::AsExec,
+    min_tstamp: i64,
+) -> Result<(), Error> {
+    trace!("purging idempotency keys created before {}", min_tstamp);
+    sqlx::query!(
+        "DELETE FROM idempotency_key_t WHERE creation_unix_tstamp < $1",
+        min_tstamp,
+    )
+    .execute(exec.as_exec())
+    .await?;
+    Ok(())
+}
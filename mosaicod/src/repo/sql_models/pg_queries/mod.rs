@@ -19,6 +19,12 @@ pub use layers::*;
 mod group;
 pub use group::*;
 
+mod idempotency_keys;
+pub use idempotency_keys::*;
+
+mod topic_pending_locks;
+pub use topic_pending_locks::*;
+
 mod compilers;
 use compilers::*;
 
@@ -0,0 +1,58 @@
+use crate::{
+    repo::{self, Error},
+    types::{self, Resource},
+};
+use log::trace;
+
+/// Records the lock of `loc` as pending, scheduled at `tstamp`. A lock already pending is
+/// rescheduled.
+pub async fn topic_pending_lock_upsert(
+    exec: &mut impl repo::AsExec,
+    loc: &types::TopicResourceLocator,
+    tstamp: i64,
+) -> Result<(), Error> {
+    trace!("recording pending lock of `{}`", loc);
+    sqlx::query!(
+        "INSERT INTO topic_pending_lock_t(topic_id, scheduled_unix_tstamp)
+        SELECT topic_id, $2 FROM topic_t WHERE locator_name = $1
+        ON CONFLICT (topic_id) DO UPDATE SET scheduled_unix_tstamp = EXCLUDED.scheduled_unix_tstamp",
+        loc.name(),
+        tstamp,
+    )
+    .execute(exec.as_exec())
+    .await?;
+    Ok(())
+}
+
+/// Deletes the pending lock of `loc`, if `tstamp` is provided only if the lock is still
+/// scheduled at `tstamp`. Returns false if no matching lock was pending.
+pub async fn topic_pending_lock_delete(
+    exec: &mut impl repo::AsExec,
+    loc: &types::TopicResourceLocator,
+    tstamp: Option<i64>,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        "DELETE FROM topic_pending_lock_t
+        WHERE topic_id = (SELECT topic_id FROM topic_t WHERE locator_name = $1)
+            AND ($2::BIGINT IS NULL OR scheduled_unix_tstamp = $2)",
+        loc.name(),
+        tstamp,
+    )
+    .execute(exec.as_exec())
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Returns the names of the unlocked topics whose lock is pending.
+pub async fn topic_pending_lock_find_all(
+    exec: &mut impl repo::AsExec,
+) -> Result<Vec<String>, Error> {
+    let names = sqlx::query_scalar!(
+        "SELECT topic.locator_name FROM topic_pending_lock_t pending
+        JOIN topic_t topic ON topic.topic_id = pending.topic_id
+        WHERE NOT topic.locked",
+    )
+    .fetch_all(exec.as_exec())
+    .await?;
+    Ok(names)
+}
@@ -15,6 +15,7 @@ fn cast_topic_data(row: PgRow) -> Result<sql_models::TopicRecord, repo::Error> {
         sequence_id: row.try_get("sequence_id")?,
         ontology_tag: row.try_get("ontology_tag")?,
         serialization_format: row.try_get("serialization_format")?,
+        time_precision: row.try_get("time_precision")?,
         user_metadata: row.try_get("user_metadata")?,
         creation_unix_tstamp: row.try_get("creation_unix_tstamp")?,
         locked: row.try_get("locked")?,
@@ -149,6 +150,24 @@ pub async fn topic_lock(
     Ok(())
 }
 
+pub async fn topic_unlock(
+    exe: &mut impl repo::AsExec,
+    loc: &types::TopicResourceLocator,
+) -> Result<(), repo::Error> {
+    trace!("unlocking `{}`", loc);
+    sqlx::query!(
+        r#"
+            UPDATE topic_t
+            SET locked = FALSE
+            WHERE locator_name = $1
+    "#,
+        loc.name(),
+    )
+    .execute(exe.as_exec())
+    .await?;
+    Ok(())
+}
+
 pub async fn topic_update_serialization_format(
     exe: &mut impl repo::AsExec,
     loc: &types::TopicResourceLocator,
@@ -174,6 +193,29 @@ pub async fn topic_update_serialization_format(
     Ok(res)
 }
 
+pub async fn topic_update_time_precision(
+    exe: &mut impl repo::AsExec,
+    loc: &types::TopicResourceLocator,
+    time_precision: types::TimePrecision,
+) -> Result<(), repo::Error> {
+    trace!(
+        "updating time_precision to `{}` for `{}`",
+        time_precision, loc
+    );
+    sqlx::query!(
+        r#"
+            UPDATE topic_t
+            SET time_precision = $1
+            WHERE locator_name = $2
+    "#,
+        time_precision.to_string(),
+        loc.name(),
+    )
+    .execute(exe.as_exec())
+    .await?;
+    Ok(())
+}
+
 pub async fn topic_update_ontology_tag(
     exe: &mut impl repo::AsExec,
     loc: &types::TopicResourceLocator,
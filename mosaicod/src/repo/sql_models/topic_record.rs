@@ -12,6 +12,8 @@ pub struct TopicRecord {
 
     pub(super) locked: bool,
     pub(super) serialization_format: Option<String>,
+    /// Missing for topics created by older versions, their data is in nanoseconds
+    pub(super) time_precision: Option<String>,
 
     /// This metadata field is only for database query access and
     /// should not be exposed
@@ -40,6 +42,7 @@ impl TopicRecord {
             locked: false,
             ontology_tag: None,
             serialization_format: None,
+            time_precision: None,
             user_metadata: None,
             creation_unix_tstamp: types::Timestamp::now().into(),
         }
@@ -70,6 +73,16 @@ impl TopicRecord {
         })
     }
 
+    pub fn time_precision(&self) -> types::TimePrecision {
+        self.time_precision
+            .as_ref()
+            .map(|value| {
+                types::TimePrecision::from_str(value)
+                    .expect("BUG: invalid time precision in database")
+            })
+            .unwrap_or_default()
+    }
+
     pub fn creation_timestamp(&self) -> types::Timestamp {
         types::Timestamp::from(self.creation_unix_tstamp)
     }
@@ -1,3 +1,4 @@
+use arrow::array::RecordBatch;
 use arrow::datatypes::SchemaRef;
 use parquet::arrow::arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder};
 
@@ -35,4 +36,11 @@ impl ChunkReader {
             Reader::Parquet { schema, .. } => schema.clone(),
         }
     }
+
+    /// Decodes all the batches of the chunk
+    pub fn read_all(self) -> Result<Vec<RecordBatch>, Error> {
+        match self.reader {
+            Reader::Parquet { reader, .. } => Ok(reader.collect::<Result<_, _>>()?),
+        }
+    }
 }
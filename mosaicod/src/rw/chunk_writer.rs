@@ -1,13 +1,30 @@
-use super::{Error, Format, writer::Writer};
-use crate::types;
-use arrow::{array::RecordBatch, datatypes::Schema, datatypes::SchemaRef};
+use super::{
+    Error, Format,
+    writer::{Writer, WriterOptions},
+};
+use crate::{params, types, utils};
+use arrow::{
+    array::{Int64Array, RecordBatch},
+    datatypes::Schema,
+    datatypes::SchemaRef,
+};
+use parquet::file::metadata::KeyValue;
 use std::sync::Arc;
 
-/// Metadata about a finalized chunk, including size and row count.
+/// Key of the Parquet footer metadata set to `true` on the chunks whose rows are sorted by
+/// ascending timestamp, see [`is_time_sorted`]
+pub const TIME_SORTED_METADATA_KEY: &str = "mosaico:time_sorted";
+
+/// Metadata about a finalized chunk, including size, row count and content hash.
 #[derive(Debug, Clone)]
 pub struct ChunkMetadata {
     pub size_bytes: usize,
     pub row_count: usize,
+    /// SHA-256 digest of the serialized chunk
+    pub content_hash: utils::hash::Digest,
+    /// Exact range of the chunk timestamps, [`None`] if the chunk has no timestamp column
+    /// or no rows
+    pub timestamp_range: Option<types::TimestampRange>,
 }
 
 /// The [`ChunkWriter`] is used to serialize [`RecordBatch`] instances into a single memory chunk,
@@ -22,6 +39,11 @@ pub struct ChunkWriter {
     stats: types::ColumnsStats,
     schema: SchemaRef,
     row_count: usize,
+    timestamp_range: Option<types::TimestampRange>,
+    /// True while the rows written are sorted by ascending timestamp
+    time_sorted: bool,
+    /// Timestamp of the last row written
+    last_timestamp: Option<i64>,
 }
 
 impl ChunkWriter {
@@ -30,12 +52,25 @@ impl ChunkWriter {
     /// This fallible constructor initializes an appropriate underlying writer
     /// based on the provided `format`.
     pub fn try_new(schema: Arc<Schema>, format: Format) -> Result<Self, Error> {
+        Self::try_new_with_options(schema, format, WriterOptions::default())
+    }
+
+    /// Creates a new [`ChunkWriter`] whose datafiles are written with `options`, e.g. the
+    /// options of the topic, see [`WriterOptions`].
+    pub fn try_new_with_options(
+        schema: Arc<Schema>,
+        format: Format,
+        options: WriterOptions,
+    ) -> Result<Self, Error> {
         Ok(ChunkWriter {
-            writer: Writer::new(&schema, format)?,
+            writer: Writer::new(&schema, format, options)?,
             format,
             stats: crate::arrow::column_stats_from_schema(&schema),
             schema,
             row_count: 0,
+            timestamp_range: None,
+            time_sorted: true,
+            last_timestamp: None,
         })
     }
 
@@ -51,6 +86,20 @@ impl ChunkWriter {
             }
         }
         self.row_count += batch.num_rows();
+
+        if self.time_sorted {
+            match super::check_time_ascending(batch, self.last_timestamp) {
+                Ok(last) => self.last_timestamp = last,
+                Err(_) => self.time_sorted = false,
+            }
+        }
+
+        if let Some(range) = timestamp_range(batch) {
+            self.timestamp_range = Some(match &self.timestamp_range {
+                Some(current) => current.merge(&range),
+                None => range,
+            });
+        }
         Ok(())
     }
 
@@ -86,27 +135,70 @@ impl ChunkWriter {
         }
     }
 
+    /// Returns an estimate of the size in bytes of the serialized chunk, i.e. the compressed
+    /// data already flushed plus the estimated encoded size of the data still buffered.
+    pub fn estimated_size(&self) -> usize {
+        match &self.writer {
+            Writer::Parquet(writer) => writer.bytes_written() + writer.in_progress_size(),
+        }
+    }
+
     /// Finalizes the writer, ensuring all buffered data and metadata are written to the file.
     ///
     /// This method must be called to complete the writing process. It consumes the writer object,
     /// preventing any further writes.
     ///
-    /// Returns the serialized buffer, column statistics, and chunk metadata (size and row count).
+    /// Returns the serialized buffer, column statistics, and chunk metadata (size, row count
+    /// and content hash).
     pub fn finalize(self) -> Result<(Vec<u8>, types::ColumnsStats, ChunkMetadata), Error> {
         // We are calling `finish`` since the implementation is the same as
         // close but takes no ownership of the writer. And we return the internal data buffer.
         let row_count = self.row_count;
         let buffer = match self.writer {
-            Writer::Parquet(w) => w.into_inner()?,
+            Writer::Parquet(mut w) => {
+                // Lets the readers merge sorted chunks instead of sorting their rows
+                if self.time_sorted {
+                    w.append_key_value_metadata(KeyValue::new(
+                        TIME_SORTED_METADATA_KEY.to_owned(),
+                        "true".to_owned(),
+                    ));
+                }
+                w.into_inner()?
+            }
         };
         let metadata = ChunkMetadata {
             size_bytes: buffer.len(),
             row_count,
+            content_hash: utils::hash::sha256(&buffer),
+            timestamp_range: self.timestamp_range,
         };
         Ok((buffer, self.stats, metadata))
     }
 }
 
+/// Returns true if the footer metadata of a chunk, as exposed in its Arrow schema metadata,
+/// marks its rows as sorted by ascending timestamp.
+pub fn is_time_sorted(metadata: &std::collections::HashMap<String, String>) -> bool {
+    metadata
+        .get(TIME_SORTED_METADATA_KEY)
+        .is_some_and(|v| v == "true")
+}
+
+/// Returns the range of the timestamp column of `batch`.
+///
+/// Column statistics are kept as `f64` and lose precision on nanosecond timestamps, so the
+/// range is computed on the column values.
+fn timestamp_range(batch: &RecordBatch) -> Option<types::TimestampRange> {
+    let timestamps = batch
+        .column_by_name(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP)?
+        .as_any()
+        .downcast_ref::<Int64Array>()?;
+
+    let start = arrow::compute::min(timestamps)?;
+    let end = arrow::compute::max(timestamps)?;
+    Some(types::TimestampRange::new(start.into(), end.into()))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{params, types};
@@ -251,4 +343,73 @@ mod tests {
         assert_eq!(metadata.row_count, 3);
         assert_eq!(metadata.size_bytes, buffer.len());
     }
+
+    /// Writes `rows` rows with ascending `timestamp` and `value` columns in small batches,
+    /// so that the chunk holds several data pages per column.
+    fn write_pages(format: Format, page_index: bool, rows: i64) -> Vec<u8> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("timestamp", DataType::Int64, false),
+            Field::new("value", DataType::Int64, false),
+        ]));
+        let options = WriterOptions {
+            page_index,
+            ..Default::default()
+        };
+        let mut writer = ChunkWriter::try_new_with_options(schema.clone(), format, options)
+            .expect("Failed to create ChunkWriter");
+
+        for start in (0..rows).step_by(1000) {
+            let values: ArrayRef = Arc::new(Int64Array::from_iter_values(start..start + 1000));
+            let batch = RecordBatch::try_new(schema.clone(), vec![values.clone(), values])
+                .expect("Fail during record batch creation");
+            writer.write(&batch).expect("Failed to write batch");
+        }
+
+        writer.finalize().expect("Failed to finalize writer").0
+    }
+
+    /// Returns the number of pages of the `value` column and the number of them a reader
+    /// has to decode to evaluate `value < threshold`, pages can be skipped only if the page
+    /// index holds their statistics.
+    fn pages_read(buffer: Vec<u8>, threshold: i64) -> (usize, usize) {
+        use parquet::arrow::arrow_reader::{ArrowReaderMetadata, ArrowReaderOptions};
+        use parquet::file::page_index::index::Index;
+
+        let metadata = ArrowReaderMetadata::load(
+            &bytes::Bytes::from(buffer),
+            ArrowReaderOptions::new().with_page_index(true),
+        )
+        .expect("Failed to read metadata");
+        let metadata = metadata.metadata();
+
+        let pages = metadata.offset_index().expect("Missing offset index")[0][1]
+            .page_locations()
+            .len();
+        let read = match &metadata.column_index().expect("Missing column index")[0][1] {
+            Index::INT64(index) => index
+                .indexes
+                .iter()
+                .filter(|page| page.min.is_none_or(|min| min < threshold))
+                .count(),
+            _ => pages,
+        };
+
+        (pages, read)
+    }
+
+    #[test]
+    fn page_index_skips_pages() {
+        for format in Format::ALL {
+            // Default chunks always collect page statistics, ragged and image chunks only on
+            // the timestamp column unless the page index is requested
+            let (pages, read) = pages_read(write_pages(format, false, 60_000), 100);
+            assert!(pages > 1);
+            let expected = if format == Format::Default { 1 } else { pages };
+            assert_eq!(read, expected, "{format:?}");
+
+            let (pages, read) = pages_read(write_pages(format, true, 60_000), 100);
+            assert!(pages > 1);
+            assert_eq!(read, 1, "{format:?}");
+        }
+    }
 }
@@ -4,10 +4,11 @@ use std::pin::Pin;
 use arrow::array::RecordBatch;
 use log::{debug, trace};
 
-use crate::{traits, types};
+use crate::{traits, types, utils};
 
 use super::Error;
 use super::Format;
+use super::WriterOptions;
 use super::chunk_writer::{ChunkMetadata, ChunkWriter};
 
 /// Callback called just before file serialization
@@ -22,7 +23,19 @@ type OnChunkCallback = Box<
 >;
 
 /// Callback used to define a format function for files
-type OnFileFormat = Box<dyn Fn(&std::path::Path, &Format, usize) -> std::path::PathBuf + Send>;
+type OnFileFormat =
+    Box<dyn Fn(&std::path::Path, &Format, usize, &ChunkMetadata) -> std::path::PathBuf + Send>;
+
+/// Outcome of the finalization of a chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkAck {
+    /// The chunk was written
+    Written,
+    /// The chunk was identical to the previous one and was not written
+    Deduplicated,
+    /// The data was buffered, to be written in a chunk along with the data of other uploads
+    Coalesced,
+}
 
 /// Writes [`RecordBatch`] into multiple chunks to a location. A location is a path like structure.
 /// Internally the [`ChunkedWriter`] can subdivide the batches in multiple files
@@ -49,6 +62,18 @@ where
     on_chunk_created_clbk: Option<OnChunkCallback>,
     /// Callback used to format data when written
     on_file_format: OnFileFormat,
+    /// If true chunks identical to the previous one are not written
+    deduplicate: bool,
+    /// Content hash of the previous chunk, if known
+    last_content_hash: Option<utils::hash::Digest>,
+    /// Estimated serialized size in bytes beyond which the current chunk is closed
+    target_chunk_bytes: Option<usize>,
+    /// Ordering enforced on the written rows
+    ordering: types::RowOrdering,
+    /// Timestamp of the last row written, tracked only if an ordering is enforced
+    last_timestamp: Option<i64>,
+    /// Options of the written chunks
+    options: WriterOptions,
 }
 
 impl<'a, W> ChunkedWriter<'a, W>
@@ -63,7 +88,9 @@ where
         format_callback: F,
     ) -> Self
     where
-        F: Fn(&std::path::Path, &Format, usize) -> std::path::PathBuf + Send + 'static,
+        F: Fn(&std::path::Path, &Format, usize, &ChunkMetadata) -> std::path::PathBuf
+            + Send
+            + 'static,
     {
         Self {
             writer: None,
@@ -73,9 +100,61 @@ where
             chunk_serialized_number: 0,
             on_chunk_created_clbk: None,
             on_file_format: Box::new(format_callback),
+            deduplicate: false,
+            last_content_hash: None,
+            target_chunk_bytes: None,
+            ordering: types::RowOrdering::default(),
+            last_timestamp: None,
+            options: WriterOptions::default(),
         }
     }
 
+    /// Enables the deduplication of chunks: a chunk whose content hash matches the one of the
+    /// immediately preceding chunk is discarded. `last_content_hash` is the hash of the chunk
+    /// already stored before this writer, if any.
+    pub fn with_deduplication(mut self, last_content_hash: Option<utils::hash::Digest>) -> Self {
+        self.deduplicate = true;
+        self.last_content_hash = last_content_hash;
+        self
+    }
+
+    /// Closes the current chunk once its estimated serialized (compressed) size reaches
+    /// `target` bytes, see [`ChunkWriter::estimated_size`]. Chunks are closed between batches,
+    /// so they may exceed the target by the size of a batch.
+    pub fn with_target_chunk_bytes(mut self, target: Option<usize>) -> Self {
+        self.target_chunk_bytes = target;
+        self
+    }
+
+    /// Starts the numbering of the produced chunks from `index`, e.g. the number of chunks
+    /// already written in the location by a previous writer.
+    pub fn with_first_chunk_index(mut self, index: usize) -> Self {
+        self.chunk_serialized_number = index;
+        self
+    }
+
+    /// Enforces `ordering` on the written rows. With [`types::RowOrdering::TimeAscending`]
+    /// batches not sorted by timestamp, or preceding the rows already written (also in
+    /// previous chunks), are rejected with an [`Error::OutOfOrder`].
+    pub fn with_ordering(mut self, ordering: types::RowOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// Sets `last_timestamp` as the timestamp of the last row already stored before this
+    /// writer, rows preceding it are rejected if [`types::RowOrdering::TimeAscending`] is
+    /// enforced (see [`ChunkedWriter::with_ordering`]).
+    pub fn with_last_timestamp(mut self, last_timestamp: Option<i64>) -> Self {
+        self.last_timestamp = last_timestamp;
+        self
+    }
+
+    /// Writes the chunks with `options`, see [`WriterOptions`].
+    pub fn with_options(mut self, options: WriterOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     /// Sets a callback function that will be called every time a chunk is produced just before
     /// serialization.
     pub fn on_chunk_created<F1, Fut>(mut self, clbk: F1) -> Self
@@ -102,7 +181,14 @@ where
     /// based on the serialization format and the maximum chunk size (if any).
     /// To perform custom actions when a chunk is produced, use the
     /// [`on_chunk_produced`] method to set a callback function.
-    pub async fn write(&mut self, batch: &RecordBatch) -> Result<(), Error> {
+    ///
+    /// Returns the outcome of the chunk closed by this write, if the chunk reached the target
+    /// size (see [`ChunkedWriter::with_target_chunk_bytes`]).
+    pub async fn write(&mut self, batch: &RecordBatch) -> Result<Option<ChunkAck>, Error> {
+        if self.ordering == types::RowOrdering::TimeAscending {
+            self.last_timestamp = super::check_time_ascending(batch, self.last_timestamp)?;
+        }
+
         // Take the writer and if not inizialized creates a new one.
         // At the end the writer will be put back.
         //
@@ -111,7 +197,7 @@ where
         // chunk produced callback will be triggered
         let mut writer = match self.writer.take() {
             Some(w) => w,
-            None => ChunkWriter::try_new(batch.schema(), self.format)?,
+            None => ChunkWriter::try_new_with_options(batch.schema(), self.format, self.options)?,
         };
 
         // Clone batch for spawn_blocking (requires 'static)
@@ -125,29 +211,56 @@ where
         .await
         .map_err(|e| Error::SpawnBlockingError(e.to_string()))??;
 
+        let estimated_size = writer.estimated_size();
         self.writer = Some(writer);
 
-        Ok(())
+        if self
+            .target_chunk_bytes
+            .is_some_and(|target| estimated_size >= target)
+        {
+            debug!(
+                "chunk estimated size of {} bytes reached the target, closing chunk",
+                estimated_size
+            );
+            return self.finalize().await;
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the size in bytes of the data written but not yet flushed to the write target.
+    pub fn pending_bytes(&self) -> usize {
+        self.writer
+            .as_ref()
+            .map_or(0, |writer| writer.memory_size() + writer.buffer().len())
     }
 
     /// Finalizes any pending reading, writing operation.
     ///
     /// It is important to call this method to ensure that an open chunk is properly finalized
-    /// and written.
-    pub async fn finalize(&mut self) -> Result<(), Error> {
+    /// and written. Returns [`None`] if there was no pending data.
+    pub async fn finalize(&mut self) -> Result<Option<ChunkAck>, Error> {
         // Calling this function will "consume" the current writer.
         // If another write_batch willl be called after this function call
         // will cause the instantiation of another writer.
         if let Some(writer) = self.writer.take() {
-            let path =
-                (self.on_file_format)(&self.path, &writer.format, self.chunk_serialized_number);
-            self.chunk_serialized_number += 1;
+            let format = writer.format;
 
             // Offload CPU-intensive parquet finalization to blocking thread pool
             let (buffer, stats, metadata) = tokio::task::spawn_blocking(move || writer.finalize())
                 .await
                 .map_err(|e| Error::SpawnBlockingError(e.to_string()))??;
 
+            if self.deduplicate && self.last_content_hash == Some(metadata.content_hash) {
+                debug!("chunk identical to the previous one, skipping write");
+                return Ok(Some(ChunkAck::Deduplicated));
+            }
+            self.last_content_hash = Some(metadata.content_hash);
+
+            let path =
+                (self.on_file_format)(&self.path, &format, self.chunk_serialized_number, &metadata);
+            self.chunk_serialized_number += 1;
+
             self.write_target.write_to_path(&path, buffer).await?;
 
             trace!(
@@ -155,17 +268,152 @@ where
                 self.on_chunk_created_clbk.is_some()
             );
 
-            return self
-                .on_chunk_created_clbk
-                .as_ref()
-                .map(async move |clbk| {
-                    debug!("calling chunk serialization callback");
-                    return clbk(path, stats, metadata).await;
-                })
-                .unwrap()
-                .await
-                .map_err(|e| Error::ChunkCreationCallbackError(e.to_string()));
+            if let Some(clbk) = &self.on_chunk_created_clbk {
+                debug!("calling chunk serialization callback");
+                clbk(path, stats, metadata)
+                    .await
+                    .map_err(|e| Error::ChunkCreationCallbackError(e.to_string()))?;
+            }
+
+            return Ok(Some(ChunkAck::Written));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{store, traits::AsExtension, types::Resource};
+
+    #[tokio::test]
+    async fn deduplicate_identical_chunks() {
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let locator = types::TopicResourceLocator::from("sequence/topic");
+        let batch = crate::arrow::testing::dummy_batch();
+
+        let mut writer = ChunkedWriter::new(&*store, locator.name(), Format::Default, {
+            let locator = locator.clone();
+            move |_, format, idx, _| locator.datafile(idx, format)
+        })
+        .with_deduplication(None);
+
+        writer.write(&batch).await.unwrap();
+        assert_eq!(writer.finalize().await.unwrap(), Some(ChunkAck::Written));
+
+        // Same chunk sent again, e.g. by a retried upload
+        writer.write(&batch).await.unwrap();
+        assert_eq!(
+            writer.finalize().await.unwrap(),
+            Some(ChunkAck::Deduplicated)
+        );
+
+        let datafiles = store
+            .list(locator.name(), Some(&Format::Default.as_extension()))
+            .await
+            .unwrap();
+        assert_eq!(datafiles.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn roll_chunks_at_target_size() {
+        use arrow::array::Int64Array;
+        use arrow::datatypes::{DataType, Field, Schema};
+        use std::sync::Arc;
+
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let locator = types::TopicResourceLocator::from("sequence/topic");
+        let target = 64 * 1024;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                crate::params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("value", DataType::Int64, false),
+        ]));
+
+        // Pseudo-random values, so that the data does not compress away
+        let mut seed: i64 = 42;
+        let mut next = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            seed
+        };
+
+        let mut writer = ChunkedWriter::new(&*store, locator.name(), Format::Default, {
+            let locator = locator.clone();
+            move |_, format, idx, _| locator.datafile(idx, format)
+        })
+        .with_target_chunk_bytes(Some(target));
+
+        let mut acks = Vec::new();
+        for idx in 0..40 {
+            let timestamps = Int64Array::from_iter_values(idx * 1000..(idx + 1) * 1000);
+            let values = Int64Array::from_iter_values((0..1000).map(|_| next()));
+            let batch =
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(timestamps), Arc::new(values)])
+                    .unwrap();
+            acks.extend(writer.write(&batch).await.unwrap());
+        }
+        acks.extend(writer.finalize().await.unwrap());
+
+        let mut sizes = Vec::new();
+        for idx in 0..acks.len() {
+            let datafile = locator.datafile(idx, &Format::Default);
+            sizes.push(store.read_bytes(&datafile).await.unwrap().len());
         }
-        Ok(())
+
+        assert!(sizes.len() > 2);
+        // All the chunks but the last one cluster around the target size
+        for size in &sizes[..sizes.len() - 1] {
+            assert!(
+                (target / 2..target * 2).contains(size),
+                "chunk of {size} bytes too far from the target"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn enforce_time_ascending_ordering() {
+        use arrow::array::Int64Array;
+        use std::sync::Arc;
+
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let locator = types::TopicResourceLocator::from("sequence/topic");
+        let dummy = crate::arrow::testing::dummy_batch();
+        let batch = |timestamps: Vec<i64>| {
+            let values = Int64Array::from_iter_values(0..timestamps.len() as i64);
+            RecordBatch::try_new(
+                dummy.schema(),
+                vec![Arc::new(Int64Array::from(timestamps)), Arc::new(values)],
+            )
+            .unwrap()
+        };
+
+        let mut writer = ChunkedWriter::new(&*store, locator.name(), Format::Default, {
+            let locator = locator.clone();
+            move |_, format, idx, _| locator.datafile(idx, format)
+        })
+        .with_ordering(types::RowOrdering::TimeAscending);
+
+        // Sorted rows are accepted, also across chunks and on equal timestamps
+        writer.write(&batch(vec![10, 20, 20])).await.unwrap();
+        writer.finalize().await.unwrap();
+        writer.write(&batch(vec![20, 30])).await.unwrap();
+
+        // Rows preceding the ones already written are rejected
+        let result = writer.write(&batch(vec![25, 40])).await;
+        assert!(matches!(result, Err(Error::OutOfOrder(_))));
+
+        // Unsorted rows are rejected
+        let result = writer.write(&batch(vec![50, 45])).await;
+        assert!(matches!(result, Err(Error::OutOfOrder(_))));
+
+        // Rejected batches are not taken into account
+        writer.write(&batch(vec![30, 60])).await.unwrap();
+        writer.finalize().await.unwrap();
     }
 }
@@ -17,4 +17,10 @@ pub enum Error {
     Unsupported,
     #[error("spawn_blocking task failed: {0}")]
     SpawnBlockingError(String),
+    #[error("incompatible schema :: {0}")]
+    IncompatibleSchema(String),
+    #[error("out of order data :: {0}")]
+    OutOfOrder(String),
+    #[error(transparent)]
+    InvalidCompressionLevel(#[from] super::InvalidCompressionLevel),
 }
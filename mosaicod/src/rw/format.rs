@@ -9,7 +9,7 @@ use std::sync::Arc;
 use datafusion::datasource::file_format::parquet::ParquetFormat;
 use datafusion::datasource::listing::ListingOptions;
 use parquet::{
-    basic::{Compression, ZstdLevel},
+    basic::{BrotliLevel, Compression, GzipLevel, ZstdLevel},
     file::properties::{EnabledStatistics, WriterProperties, WriterVersion},
     schema::types::ColumnPath,
 };
@@ -43,6 +43,9 @@ pub trait FormatStrategy: Send + Sync {
 /// statistics, and DataFusion integration. Formats that store data as Parquet
 /// files should implement this trait.
 pub trait ParquetFormatStrategy: FormatStrategy {
+    /// Returns the compression codec of the data columns.
+    fn compression(&self) -> Compression;
+
     /// Returns the Parquet writer properties configured for this format.
     fn writer_properties(&self) -> WriterProperties;
 
@@ -69,9 +72,14 @@ impl FormatStrategy for DefaultFormatStrategy {
 }
 
 impl ParquetFormatStrategy for DefaultFormatStrategy {
+    fn compression(&self) -> Compression {
+        Compression::UNCOMPRESSED
+    }
+
     fn writer_properties(&self) -> WriterProperties {
         WriterProperties::builder()
             .set_writer_version(WriterVersion::PARQUET_2_0)
+            .set_compression(self.compression())
             .build()
     }
 
@@ -106,14 +114,18 @@ impl FormatStrategy for RaggedFormatStrategy {
 }
 
 impl ParquetFormatStrategy for RaggedFormatStrategy {
+    fn compression(&self) -> Compression {
+        Compression::ZSTD(
+            ZstdLevel::try_new(Self::COMPRESSION_LEVEL).expect("valid ZSTD compression level"),
+        )
+    }
+
     fn writer_properties(&self) -> WriterProperties {
         let ts_path = ColumnPath::from(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP);
 
         WriterProperties::builder()
             .set_writer_version(WriterVersion::PARQUET_2_0)
-            .set_compression(Compression::ZSTD(
-                ZstdLevel::try_new(Self::COMPRESSION_LEVEL).expect("valid ZSTD compression level"),
-            ))
+            .set_compression(self.compression())
             .set_dictionary_enabled(false)
             .set_statistics_enabled(EnabledStatistics::None)
             // Timestamp column: uncompressed for fast seeking
@@ -154,14 +166,18 @@ impl FormatStrategy for ImageFormatStrategy {
 }
 
 impl ParquetFormatStrategy for ImageFormatStrategy {
+    fn compression(&self) -> Compression {
+        Compression::ZSTD(
+            ZstdLevel::try_new(Self::COMPRESSION_LEVEL).expect("valid ZSTD compression level"),
+        )
+    }
+
     fn writer_properties(&self) -> WriterProperties {
         let ts_path = ColumnPath::from(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP);
 
         WriterProperties::builder()
             .set_writer_version(WriterVersion::PARQUET_2_0)
-            .set_compression(Compression::ZSTD(
-                ZstdLevel::try_new(Self::COMPRESSION_LEVEL).expect("valid ZSTD compression level"),
-            ))
+            .set_compression(self.compression())
             .set_dictionary_enabled(false)
             .set_statistics_enabled(EnabledStatistics::None)
             // Timestamp column: uncompressed for fast seeking
@@ -200,6 +216,9 @@ pub enum Format {
 }
 
 impl Format {
+    /// List of all the formats supported by the system.
+    pub const ALL: [Format; 3] = [Self::Default, Self::Ragged, Self::Image];
+
     /// Returns the base strategy implementation for this format variant.
     ///
     /// Use this method when you only need format-agnostic behavior like
@@ -240,6 +259,52 @@ impl Format {
     }
 }
 
+/// Error raised when a compression level is not valid for the codec of a format.
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("invalid compression level {level} for format `{format}` :: {reason}")]
+pub struct InvalidCompressionLevel {
+    pub format: Format,
+    pub level: i32,
+    pub reason: String,
+}
+
+impl Format {
+    /// Returns the compression codec of the format with its quality level set to `level`.
+    ///
+    /// Only ZSTD, GZIP and BROTLI support levels, each with its own range of valid values.
+    pub fn compression_with_level(
+        &self,
+        level: i32,
+    ) -> Result<Compression, InvalidCompressionLevel> {
+        let invalid = |reason: String| InvalidCompressionLevel {
+            format: *self,
+            level,
+            reason,
+        };
+
+        let compression = self
+            .as_parquet()
+            .ok_or_else(|| invalid("format is not compressed".to_owned()))?
+            .compression();
+        let unsigned = || u32::try_from(level).map_err(|e| invalid(e.to_string()));
+
+        match compression {
+            Compression::ZSTD(_) => Ok(Compression::ZSTD(
+                ZstdLevel::try_new(level).map_err(|e| invalid(e.to_string()))?,
+            )),
+            Compression::GZIP(_) => Ok(Compression::GZIP(
+                GzipLevel::try_new(unsigned()?).map_err(|e| invalid(e.to_string()))?,
+            )),
+            Compression::BROTLI(_) => Ok(Compression::BROTLI(
+                BrotliLevel::try_new(unsigned()?).map_err(|e| invalid(e.to_string()))?,
+            )),
+            codec => Err(invalid(format!(
+                "codec {codec:?} has no compression levels"
+            ))),
+        }
+    }
+}
+
 impl traits::AsExtension for Format {
     fn as_extension(&self) -> String {
         self.strategy().file_extension().to_owned()
@@ -329,6 +394,21 @@ mod tests {
         let _ = Format::Image.as_parquet().unwrap().listing_options();
     }
 
+    #[test]
+    fn compression_with_level() {
+        assert_eq!(
+            Format::Image.compression_with_level(3).unwrap(),
+            Compression::ZSTD(ZstdLevel::try_new(3).unwrap())
+        );
+
+        let err = Format::Ragged.compression_with_level(23).unwrap_err();
+        assert_eq!(err.format, Format::Ragged);
+        assert_eq!(err.level, 23);
+
+        // Default datafiles are not compressed
+        assert!(Format::Default.compression_with_level(3).is_err());
+    }
+
     #[test]
     fn as_parquet_returns_some_for_parquet_formats() {
         assert!(Format::Default.as_parquet().is_some());
@@ -0,0 +1,264 @@
+//! Point lookups of the row of a topic at a timestamp.
+
+use arrow::array::{Array, RecordBatch};
+use parquet::file::metadata::ParquetMetaData;
+use parquet::file::statistics::Statistics;
+
+use super::{Error, reorder::timestamp_column};
+use crate::{params, types};
+
+/// Looks up the row at a timestamp, following a [`types::LookupMode`].
+///
+/// The looked up timestamp is in nanoseconds, while the data is in the precision of its topic:
+/// data timestamps are converted to nanoseconds before comparing them, so that no precision is
+/// lost in the conversion.
+pub struct Lookup {
+    timestamp: i128,
+    mode: types::LookupMode,
+    nanos_per_unit: i128,
+}
+
+impl Lookup {
+    pub fn new(timestamp: i64, mode: types::LookupMode, precision: types::TimePrecision) -> Self {
+        Self {
+            timestamp: timestamp.into(),
+            mode,
+            nanos_per_unit: precision.nanos_per_unit().into(),
+        }
+    }
+
+    fn nanos(&self, units: i64) -> i128 {
+        i128::from(units) * self.nanos_per_unit
+    }
+
+    /// Returns true if a row at `ts` (in nanoseconds) is a valid result of the lookup
+    fn accepts(&self, ts: i128) -> bool {
+        use types::LookupMode::*;
+        match self.mode {
+            Exact => ts == self.timestamp,
+            Nearest => true,
+            Before => ts <= self.timestamp,
+            After => ts >= self.timestamp,
+        }
+    }
+
+    /// Returns true if a row at `ts` is a better result than a row at `best` (both accepted)
+    fn prefers(&self, ts: i128, best: i128) -> bool {
+        use types::LookupMode::*;
+        match self.mode {
+            Exact => false,
+            Nearest => {
+                let (distance, best_distance) =
+                    ((ts - self.timestamp).abs(), (best - self.timestamp).abs());
+                distance < best_distance || (distance == best_distance && ts < best)
+            }
+            Before => ts > best,
+            After => ts < best,
+        }
+    }
+
+    /// Returns the indices of the items (chunks or row groups) that can hold the result of the
+    /// lookup, given the range of their data timestamps (in the data precision).
+    ///
+    /// Besides the items whose range contains the timestamp, the closest item entirely before
+    /// and the closest item entirely after the timestamp are selected if the mode accepts them,
+    /// since ranges can overlap. Items without a range are always selected.
+    pub fn candidates(&self, bounds: &[Option<(i64, i64)>]) -> Vec<usize> {
+        use types::LookupMode::*;
+
+        let mut selected = Vec::new();
+        // Item with the greatest end before the timestamp
+        let mut before: Option<(usize, i128)> = None;
+        // Item with the smallest start after the timestamp
+        let mut after: Option<(usize, i128)> = None;
+
+        for (idx, bounds) in bounds.iter().enumerate() {
+            let Some((min, max)) = bounds else {
+                selected.push(idx);
+                continue;
+            };
+            let (min, max) = (self.nanos(*min), self.nanos(*max));
+
+            if max < self.timestamp {
+                if before.is_none_or(|(_, end)| max > end) {
+                    before = Some((idx, max));
+                }
+            } else if min > self.timestamp {
+                if after.is_none_or(|(_, start)| min < start) {
+                    after = Some((idx, min));
+                }
+            } else {
+                selected.push(idx);
+            }
+        }
+
+        if matches!(self.mode, Nearest | Before) {
+            selected.extend(before.map(|(idx, _)| idx));
+        }
+        if matches!(self.mode, Nearest | After) {
+            selected.extend(after.map(|(idx, _)| idx));
+        }
+        selected.sort_unstable();
+
+        selected
+    }
+
+    /// Returns the indices of the row groups of a parquet file that can hold the result of the
+    /// lookup, from the statistics of their timestamp column.
+    pub fn row_groups(&self, metadata: &ParquetMetaData) -> Vec<usize> {
+        let bounds: Vec<Option<(i64, i64)>> = metadata
+            .row_groups()
+            .iter()
+            .map(|row_group| {
+                let statistics = row_group
+                    .columns()
+                    .iter()
+                    .find(|c| {
+                        c.column_path().string() == params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP
+                    })
+                    .and_then(|c| c.statistics());
+
+                match statistics {
+                    Some(Statistics::Int64(s)) => Some((*s.min_opt()?, *s.max_opt()?)),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        self.candidates(&bounds)
+    }
+
+    /// Returns the row of `batches` matching the lookup as a single-row batch, [`None`] if no
+    /// row matches.
+    ///
+    /// If `time_sorted` is true the rows of each batch are known to be in ascending time order,
+    /// and the row is found with a binary search instead of a scan of the batches.
+    pub fn find(
+        &self,
+        batches: &[RecordBatch],
+        time_sorted: bool,
+    ) -> Result<Option<RecordBatch>, Error> {
+        // (batch, row, timestamp) of the best row found
+        let mut best: Option<(usize, usize, i128)> = None;
+
+        for (batch_idx, batch) in batches.iter().enumerate() {
+            let timestamps = timestamp_column(batch)?;
+
+            let rows: Vec<usize> = if time_sorted && timestamps.null_count() == 0 {
+                // The best row is either the last one before or the first one after the
+                // timestamp, at each side of the run of rows at the timestamp
+                let values = timestamps.values();
+                let start = values.partition_point(|&ts| self.nanos(ts) < self.timestamp);
+                let end = values.partition_point(|&ts| self.nanos(ts) <= self.timestamp);
+                [
+                    start.checked_sub(1),
+                    Some(start),
+                    end.checked_sub(1),
+                    Some(end),
+                ]
+                .into_iter()
+                .flatten()
+                .filter(|&row| row < values.len())
+                .collect()
+            } else {
+                (0..timestamps.len())
+                    .filter(|&row| timestamps.is_valid(row))
+                    .collect()
+            };
+
+            for row in rows {
+                let ts = self.nanos(timestamps.value(row));
+                if !self.accepts(ts) {
+                    continue;
+                }
+                if best.is_none_or(|(_, _, best_ts)| self.prefers(ts, best_ts)) {
+                    best = Some((batch_idx, row, ts));
+                }
+            }
+        }
+
+        Ok(best.map(|(batch_idx, row, _)| batches[batch_idx].slice(row, 1)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    fn batch(timestamps: Vec<i64>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+            DataType::Int64,
+            false,
+        )]));
+        RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(timestamps))]).unwrap()
+    }
+
+    fn found(lookup: &Lookup, batches: &[RecordBatch], time_sorted: bool) -> Option<i64> {
+        lookup
+            .find(batches, time_sorted)
+            .unwrap()
+            .map(|row| timestamp_column(&row).unwrap().value(0))
+    }
+
+    #[test]
+    fn find_rows() {
+        use types::LookupMode::*;
+        let precision = types::TimePrecision::Nanos;
+        let batches = [batch(vec![10, 20, 20, 40]), batch(vec![50, 70])];
+
+        for time_sorted in [true, false] {
+            let lookup = |ts, mode| found(&Lookup::new(ts, mode, precision), &batches, time_sorted);
+
+            assert_eq!(lookup(20, Exact), Some(20));
+            assert_eq!(lookup(30, Exact), None);
+            // Equally close rows, the earlier one is returned
+            assert_eq!(lookup(30, Nearest), Some(20));
+            assert_eq!(lookup(46, Nearest), Some(50));
+            assert_eq!(lookup(45, Before), Some(40));
+            assert_eq!(lookup(45, After), Some(50));
+            assert_eq!(lookup(5, Before), None);
+            assert_eq!(lookup(5, Nearest), Some(10));
+            assert_eq!(lookup(80, After), None);
+        }
+
+        // Data timestamps are converted to the precision of the lookup
+        let lookup = Lookup::new(20_000, Exact, types::TimePrecision::Micros);
+        assert_eq!(found(&lookup, &batches, true), Some(20));
+    }
+
+    #[test]
+    fn select_candidates() {
+        use types::LookupMode::*;
+        let precision = types::TimePrecision::Nanos;
+        let bounds = [
+            Some((0, 100)),
+            Some((50, 60)),
+            None,
+            Some((110, 120)),
+            Some((80, 90)),
+        ];
+
+        // The range of the first item contains the timestamp, but not necessarily the closest
+        // rows: the closest items at each side are selected too
+        assert_eq!(
+            Lookup::new(70, Nearest, precision).candidates(&bounds),
+            vec![0, 1, 2, 4]
+        );
+        assert_eq!(
+            Lookup::new(70, Exact, precision).candidates(&bounds),
+            vec![0, 2]
+        );
+        assert_eq!(
+            Lookup::new(105, Before, precision).candidates(&bounds),
+            vec![0, 2]
+        );
+        assert_eq!(
+            Lookup::new(105, After, precision).candidates(&bounds),
+            vec![2, 3]
+        );
+    }
+}
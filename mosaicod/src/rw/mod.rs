@@ -7,12 +7,28 @@ pub mod format;
 pub use format::*;
 
 pub mod chunk_writer;
-pub use chunk_writer::{ChunkMetadata, ChunkWriter};
+pub use chunk_writer::{ChunkMetadata, ChunkWriter, TIME_SORTED_METADATA_KEY, is_time_sorted};
 
 mod writer;
+pub use writer::WriterOptions;
 
 pub mod chunked_writer;
-pub use chunked_writer::ChunkedWriter;
+pub use chunked_writer::{ChunkAck, ChunkedWriter};
 
 pub mod chunk_reader;
 pub use chunk_reader::ChunkReader;
+
+mod schema;
+pub use schema::{check_pinned_schema, merge_schemas};
+
+mod reorder;
+pub use reorder::{check_time_ascending, sort_and_split_by_timestamp, tail_by_timestamp};
+
+mod lookup;
+pub use lookup::Lookup;
+
+mod profile;
+pub use profile::{ColumnProfile, ColumnProfiler};
+
+pub mod wal;
+pub use wal::{WalEntry, WalWriter};
@@ -0,0 +1,309 @@
+//! Profiling of the columns of parquet files from the row group statistics of their footer.
+
+use crate::{params, query, types};
+use parquet::basic::{ConvertedType, LogicalType, Type as PhysicalType};
+use parquet::file::metadata::{ParquetMetaData, RowGroupMetaData};
+use parquet::file::statistics::Statistics;
+use parquet::schema::types::ColumnDescriptor;
+
+/// Statistics of a column of the data of a topic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnProfile {
+    /// Path of the column, nested fields are separated by `.`
+    pub column: String,
+    pub min: Option<query::Value>,
+    pub max: Option<query::Value>,
+    pub null_count: Option<u64>,
+    /// Approximated number of distinct values, available for integer and text columns
+    pub distinct_estimate: Option<u64>,
+}
+
+/// Kind of the values of a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnKind {
+    Boolean,
+    Integer,
+    Float,
+    Text,
+    /// Values without a meaningful order (e.g. binary data)
+    Other,
+}
+
+impl ColumnKind {
+    fn of(column: &ColumnDescriptor) -> Self {
+        match column.physical_type() {
+            PhysicalType::BOOLEAN => Self::Boolean,
+            PhysicalType::INT32 | PhysicalType::INT64 => Self::Integer,
+            PhysicalType::FLOAT | PhysicalType::DOUBLE => Self::Float,
+            PhysicalType::BYTE_ARRAY
+                if matches!(column.logical_type(), Some(LogicalType::String))
+                    || column.converted_type() == ConvertedType::UTF8 =>
+            {
+                Self::Text
+            }
+            _ => Self::Other,
+        }
+    }
+}
+
+struct ColumnAccumulator {
+    profile: ColumnProfile,
+    kind: ColumnKind,
+    /// The column is not nested in a list, so it can be aggregated by the query engine
+    scalar: bool,
+    /// Every row group read has exact statistics for the column
+    complete: bool,
+}
+
+impl ColumnAccumulator {
+    fn new(column: &ColumnDescriptor) -> Self {
+        Self {
+            profile: ColumnProfile {
+                column: column.path().string(),
+                min: None,
+                max: None,
+                null_count: Some(0),
+                distinct_estimate: None,
+            },
+            kind: ColumnKind::of(column),
+            scalar: column.max_rep_level() == 0,
+            complete: true,
+        }
+    }
+
+    fn push(&mut self, statistics: Option<&Statistics>) {
+        let Some(statistics) = statistics else {
+            self.complete = false;
+            self.profile.null_count = None;
+            return;
+        };
+
+        self.profile.null_count = self
+            .profile
+            .null_count
+            .zip(statistics.null_count_opt())
+            .map(|(total, count)| total + count);
+
+        if self.kind == ColumnKind::Other {
+            return;
+        }
+
+        // Truncated bounds (e.g. long strings) are not reported
+        if !statistics.min_is_exact() || !statistics.max_is_exact() {
+            self.complete = false;
+            return;
+        }
+
+        if let Some((min, max)) = bounds(statistics, self.kind) {
+            self.profile.min = match self.profile.min.take() {
+                Some(current) if current <= min => Some(current),
+                _ => Some(min),
+            };
+            self.profile.max = match self.profile.max.take() {
+                Some(current) if current >= max => Some(current),
+                _ => Some(max),
+            };
+        }
+    }
+}
+
+/// Aggregates the row group statistics found in the footer of multiple parquet files into
+/// a [`ColumnProfile`] for each column.
+///
+/// Statistics are not available for every column (e.g. formats that compute statistics only
+/// on the timestamp column), see [`ColumnProfiler::columns_to_scan`] for the columns whose
+/// profile must be completed by scanning the data.
+#[derive(Default)]
+pub struct ColumnProfiler {
+    columns: Vec<ColumnAccumulator>,
+    row_count: u64,
+}
+
+impl ColumnProfiler {
+    /// Adds the statistics of the row groups of a file.
+    ///
+    /// If `range` is provided only the row groups whose timestamps overlap the range are
+    /// considered. The statistics of a row group partially overlapping the range cover all
+    /// its rows, so the reported bounds may exceed the values in range.
+    pub fn push_footer(
+        &mut self,
+        metadata: &ParquetMetaData,
+        range: Option<&types::TimestampRange>,
+    ) {
+        for column in metadata.file_metadata().schema_descr().columns() {
+            if self.position(&column.path().string()).is_none() {
+                self.columns.push(ColumnAccumulator::new(column));
+            }
+        }
+
+        for row_group in metadata.row_groups() {
+            if range.is_some_and(|range| !overlaps(row_group, range)) {
+                continue;
+            }
+
+            self.row_count += row_group.num_rows() as u64;
+
+            for chunk in row_group.columns() {
+                if let Some(idx) = self.position(&chunk.column_path().string()) {
+                    self.columns[idx].push(chunk.statistics());
+                }
+            }
+        }
+    }
+
+    /// Number of rows of the row groups considered.
+    pub fn row_count(&self) -> u64 {
+        self.row_count
+    }
+
+    /// Returns the columns lacking exact statistics that can be computed by scanning the data.
+    pub fn columns_to_scan(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .filter(|c| c.scalar && !c.complete && c.kind != ColumnKind::Other)
+            .map(|c| c.profile.column.clone())
+            .collect()
+    }
+
+    /// Returns the columns whose number of distinct values can be estimated by scanning the
+    /// data.
+    pub fn distinct_columns(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .filter(|c| c.scalar && matches!(c.kind, ColumnKind::Integer | ColumnKind::Text))
+            .map(|c| c.profile.column.clone())
+            .collect()
+    }
+
+    /// Replaces the bounds and the null count of `column` with the ones computed by a scan.
+    pub fn set_scanned(
+        &mut self,
+        column: &str,
+        min: Option<query::Value>,
+        max: Option<query::Value>,
+        null_count: Option<u64>,
+    ) {
+        if let Some(idx) = self.position(column) {
+            let profile = &mut self.columns[idx].profile;
+            profile.min = min;
+            profile.max = max;
+            profile.null_count = null_count;
+        }
+    }
+
+    pub fn set_distinct_estimate(&mut self, column: &str, estimate: u64) {
+        if let Some(idx) = self.position(column) {
+            self.columns[idx].profile.distinct_estimate = Some(estimate);
+        }
+    }
+
+    /// Returns the profile of each column, in schema order.
+    pub fn finish(self) -> Vec<ColumnProfile> {
+        self.columns.into_iter().map(|c| c.profile).collect()
+    }
+
+    fn position(&self, column: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.profile.column == column)
+    }
+}
+
+/// Checks if the timestamps of `row_group` overlap `range`, row groups without timestamp
+/// statistics are assumed to overlap.
+fn overlaps(row_group: &RowGroupMetaData, range: &types::TimestampRange) -> bool {
+    let statistics = row_group
+        .columns()
+        .iter()
+        .find(|c| c.column_path().string() == params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP)
+        .and_then(|c| c.statistics());
+
+    let Some(Statistics::Int64(statistics)) = statistics else {
+        return true;
+    };
+
+    match (statistics.min_opt(), statistics.max_opt()) {
+        (Some(min), Some(max)) => types::TimestampRange::new((*min).into(), (*max).into())
+            .intersect(range)
+            .is_some(),
+        _ => true,
+    }
+}
+
+/// Returns the bounds of a row group column, [`None`] if not available (e.g. only nulls).
+fn bounds(statistics: &Statistics, kind: ColumnKind) -> Option<(query::Value, query::Value)> {
+    use query::Value;
+
+    match (statistics, kind) {
+        (Statistics::Boolean(s), _) => {
+            Some((Value::Boolean(*s.min_opt()?), Value::Boolean(*s.max_opt()?)))
+        }
+        (Statistics::Int32(s), _) => Some((
+            Value::Integer((*s.min_opt()?).into()),
+            Value::Integer((*s.max_opt()?).into()),
+        )),
+        (Statistics::Int64(s), _) => {
+            Some((Value::Integer(*s.min_opt()?), Value::Integer(*s.max_opt()?)))
+        }
+        (Statistics::Float(s), _) => Some((
+            Value::Float((*s.min_opt()?).into()),
+            Value::Float((*s.max_opt()?).into()),
+        )),
+        (Statistics::Double(s), _) => {
+            Some((Value::Float(*s.min_opt()?), Value::Float(*s.max_opt()?)))
+        }
+        (Statistics::ByteArray(s), ColumnKind::Text) => Some((
+            Value::Text(s.min_opt()?.as_utf8().ok()?.to_owned()),
+            Value::Text(s.max_opt()?.as_utf8().ok()?.to_owned()),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rw::{ChunkWriter, Format};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    fn footer(batch: &arrow::array::RecordBatch, format: Format) -> ParquetMetaData {
+        let mut writer = ChunkWriter::try_new(batch.schema(), format).unwrap();
+        writer.write(batch).unwrap();
+        let (buffer, _, _) = writer.finalize().unwrap();
+
+        let reader = SerializedFileReader::new(bytes::Bytes::from(buffer)).unwrap();
+        reader.metadata().clone()
+    }
+
+    #[test]
+    fn profile_from_footer() {
+        let batch = crate::arrow::testing::dummy_batch();
+
+        let mut profiler = ColumnProfiler::default();
+        profiler.push_footer(&footer(&batch, Format::Default), None);
+
+        assert_eq!(profiler.row_count(), 7);
+        assert!(profiler.columns_to_scan().is_empty());
+
+        let profiles = profiler.finish();
+        let value = profiles.iter().find(|p| p.column == "value").unwrap();
+        assert_eq!(value.min, Some(query::Value::Integer(1)));
+        assert_eq!(value.max, Some(query::Value::Integer(7)));
+        assert_eq!(value.null_count, Some(0));
+
+        // Row groups outside the range are skipped
+        let mut profiler = ColumnProfiler::default();
+        let range = types::TimestampRange::new(0.into(), 100.into());
+        profiler.push_footer(&footer(&batch, Format::Default), Some(&range));
+        assert_eq!(profiler.row_count(), 0);
+    }
+
+    #[test]
+    fn profile_without_statistics() {
+        let batch = crate::arrow::testing::dummy_batch();
+
+        // The ragged format computes statistics only for the timestamp column
+        let mut profiler = ColumnProfiler::default();
+        profiler.push_footer(&footer(&batch, Format::Ragged), None);
+
+        assert_eq!(profiler.columns_to_scan(), vec!["value".to_owned()]);
+    }
+}
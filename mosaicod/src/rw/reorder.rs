@@ -0,0 +1,204 @@
+//! Utilities to rewrite the data of a topic in time order.
+
+use arrow::array::{Array, Int64Array, RecordBatch, UInt32Array};
+use arrow::compute;
+
+use super::Error;
+use crate::params;
+
+/// Sorts the rows of `batches` by timestamp and splits them in chunks having time ranges that
+/// do not overlap.
+///
+/// `chunk_rows` holds the target number of rows of each output chunk (usually the row count of
+/// the original chunks). Rows sharing the same timestamp are always placed in the same chunk,
+/// so a chunk could contain more rows than requested and trailing chunks could be dropped.
+/// The sort is stable, rows with the same timestamp keep their relative order.
+pub fn sort_and_split_by_timestamp(
+    batches: &[RecordBatch],
+    chunk_rows: &[usize],
+) -> Result<Vec<RecordBatch>, Error> {
+    let Some(first) = batches.first() else {
+        return Ok(Vec::new());
+    };
+
+    let data = compute::concat_batches(&first.schema(), batches)?;
+
+    let timestamps = timestamp_column(&data)?;
+
+    // Stable sort, rows with the same timestamp keep their relative order
+    let mut indices: Vec<u32> = (0..data.num_rows() as u32).collect();
+    indices.sort_by_key(|&i| timestamps.value(i as usize));
+
+    let data = compute::take_record_batch(&data, &UInt32Array::from(indices))?;
+    let timestamps = timestamp_column(&data)?;
+
+    let total = data.num_rows();
+    let mut chunks = Vec::with_capacity(chunk_rows.len());
+    let mut start = 0;
+
+    for (idx, rows) in chunk_rows.iter().enumerate() {
+        if start >= total {
+            break;
+        }
+
+        // The last chunk takes all the remaining rows
+        let mut end = if idx + 1 == chunk_rows.len() {
+            total
+        } else {
+            (start + rows).min(total)
+        };
+
+        // Move the boundary forward until the timestamp changes
+        while end > start && end < total && timestamps.value(end) == timestamps.value(end - 1) {
+            end += 1;
+        }
+
+        if end > start {
+            chunks.push(data.slice(start, end - start));
+        }
+        start = end;
+    }
+
+    Ok(chunks)
+}
+
+/// Returns the last `n` rows of `batches` by timestamp, sorted in ascending time order.
+///
+/// If the batches hold less than `n` rows all of them are returned, [`None`] is returned if
+/// there are no rows at all. The sort is stable, so between rows sharing the same timestamp
+/// the ones appearing last in `batches` are preferred.
+pub fn tail_by_timestamp(batches: &[RecordBatch], n: usize) -> Result<Option<RecordBatch>, Error> {
+    let Some(first) = batches.first() else {
+        return Ok(None);
+    };
+
+    let data = compute::concat_batches(&first.schema(), batches)?;
+    if data.num_rows() == 0 {
+        return Ok(None);
+    }
+
+    let timestamps = timestamp_column(&data)?;
+
+    let mut indices: Vec<u32> = (0..data.num_rows() as u32).collect();
+    indices.sort_by_key(|&i| timestamps.value(i as usize));
+
+    let start = indices.len().saturating_sub(n);
+    let data = compute::take_record_batch(&data, &UInt32Array::from(indices.split_off(start)))?;
+
+    Ok(Some(data))
+}
+
+/// Checks that the rows of `batch` are sorted by ascending timestamp and follow `last`, the
+/// timestamp of the row preceding the batch (if any).
+///
+/// Returns the timestamp of the last row of the batch, or `last` if the batch is empty. An
+/// [`Error::OutOfOrder`] reporting the first offending row is returned otherwise.
+pub fn check_time_ascending(batch: &RecordBatch, last: Option<i64>) -> Result<Option<i64>, Error> {
+    let timestamps = timestamp_column(batch)?;
+
+    let mut prev = last;
+    for (row, tstamp) in timestamps.values().iter().enumerate() {
+        if let Some(prev) = prev.filter(|prev| tstamp < prev) {
+            return Err(Error::OutOfOrder(format!(
+                "row {} has timestamp {} preceding timestamp {} of the previous row",
+                row, tstamp, prev
+            )));
+        }
+        prev = Some(*tstamp);
+    }
+
+    Ok(prev)
+}
+
+pub(super) fn timestamp_column(data: &RecordBatch) -> Result<&Int64Array, Error> {
+    data.column_by_name(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP)
+        .and_then(|c| c.as_any().downcast_ref::<Int64Array>())
+        .ok_or_else(|| {
+            Error::IncompatibleSchema(format!(
+                "missing Int64 `{}` column",
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::StringArray;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    fn batch(timestamps: Vec<i64>, labels: Vec<&str>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("label", DataType::Utf8, false),
+        ]));
+
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(timestamps)),
+                Arc::new(StringArray::from(labels)),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn range(batch: &RecordBatch) -> (i64, i64) {
+        let ts = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        (ts.value(0), ts.value(ts.len() - 1))
+    }
+
+    #[test]
+    fn overlapping_chunks() {
+        let c1 = batch(vec![10, 30, 50, 70], vec!["a", "c", "e", "g"]);
+        let c2 = batch(vec![20, 30, 40, 60], vec!["b", "d", "f", "h"]);
+
+        let chunks = sort_and_split_by_timestamp(&[c1, c2], &[3, 5]).unwrap();
+
+        assert_eq!(chunks.len(), 2);
+
+        // The boundary is moved to keep the two rows with timestamp 30 in the first chunk
+        assert_eq!(range(&chunks[0]), (10, 30));
+        assert_eq!(range(&chunks[1]), (40, 70));
+        assert!(range(&chunks[0]).1 < range(&chunks[1]).0);
+
+        // No rows are lost and the non-time columns follow their timestamps
+        assert_eq!(chunks.iter().map(|c| c.num_rows()).sum::<usize>(), 8);
+        let labels = chunks[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(
+            labels.iter().flatten().collect::<Vec<_>>(),
+            vec!["a", "b", "c", "d"]
+        );
+    }
+
+    #[test]
+    fn tail_across_chunks() {
+        let c1 = batch(vec![10, 20, 30, 40], vec!["a", "b", "c", "d"]);
+        let c2 = batch(vec![50, 60], vec!["e", "f"]);
+
+        let tail = tail_by_timestamp(&[c1.clone(), c2.clone()], 3)
+            .unwrap()
+            .unwrap();
+        assert_eq!(range(&tail), (40, 60));
+        assert_eq!(tail.num_rows(), 3);
+
+        // Less rows than requested
+        let tail = tail_by_timestamp(&[c1, c2], 10).unwrap().unwrap();
+        assert_eq!(tail.num_rows(), 6);
+
+        assert!(tail_by_timestamp(&[], 3).unwrap().is_none());
+    }
+}
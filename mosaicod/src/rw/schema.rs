@@ -0,0 +1,225 @@
+//! Utilities to handle schema evolution across the chunks of a topic.
+
+use std::sync::Arc;
+
+use arrow::datatypes::{DataType, Field, Fields, Schema};
+
+use super::Error;
+
+/// Merges a list of schemas into a single superset schema.
+///
+/// The resulting schema contains the union of all the fields, in order of first appearance.
+/// A field that is missing in some of the schemas is marked as nullable, so that chunks
+/// written without that field can be projected to the merged schema filling the missing
+/// column with nulls.
+///
+/// A field that is nullable in any of the schemas is nullable in the merged schema, this
+/// holds also for the children of nested types (e.g. the fields of a struct or the items of
+/// a list), so that chunks written with differing nullability can be read together.
+///
+/// Only additive changes are allowed, if the same field appears with different data types
+/// (including nested types) an [`Error::IncompatibleSchema`] is returned.
+pub fn merge_schemas(schemas: &[Schema]) -> Result<Schema, Error> {
+    let mut fields: Vec<Field> = Vec::new();
+
+    for (idx, schema) in schemas.iter().enumerate() {
+        for field in schema.fields() {
+            match fields.iter_mut().find(|f| f.name() == field.name()) {
+                Some(merged) => {
+                    let Some(data_type) = merge_data_types(merged.data_type(), field.data_type())
+                    else {
+                        return Err(Error::IncompatibleSchema(format!(
+                            "field `{}` has type `{}` but was previously defined as `{}`",
+                            field.name(),
+                            field.data_type(),
+                            merged.data_type()
+                        )));
+                    };
+                    merged.set_data_type(data_type);
+                    if field.is_nullable() {
+                        merged.set_nullable(true);
+                    }
+                }
+                None => {
+                    // A field not found in the previous schemas is missing in some chunks
+                    let nullable = field.is_nullable() || idx > 0;
+                    fields.push(field.as_ref().clone().with_nullable(nullable));
+                }
+            }
+        }
+
+        // Fields missing in the current schema are missing in some chunks
+        for merged in &mut fields {
+            if schema.field_with_name(merged.name()).is_err() {
+                merged.set_nullable(true);
+            }
+        }
+    }
+
+    Ok(Schema::new(fields))
+}
+
+/// Merges two data types differing at most in the nullability of their nested fields, which
+/// are relaxed to nullable if nullable in any of the two. Returns [`None`] if the data types
+/// differ otherwise.
+fn merge_data_types(a: &DataType, b: &DataType) -> Option<DataType> {
+    let merge_field = |a: &Field, b: &Field| -> Option<Field> {
+        if a.name() != b.name() {
+            return None;
+        }
+        let data_type = merge_data_types(a.data_type(), b.data_type())?;
+        Some(
+            a.clone()
+                .with_data_type(data_type)
+                .with_nullable(a.is_nullable() || b.is_nullable()),
+        )
+    };
+
+    match (a, b) {
+        (DataType::Struct(fa), DataType::Struct(fb)) if fa.len() == fb.len() => {
+            let fields = fa
+                .iter()
+                .zip(fb.iter())
+                .map(|(a, b)| merge_field(a, b))
+                .collect::<Option<Vec<_>>>()?;
+            Some(DataType::Struct(Fields::from(fields)))
+        }
+        (DataType::List(a), DataType::List(b)) => {
+            Some(DataType::List(Arc::new(merge_field(a, b)?)))
+        }
+        (DataType::LargeList(a), DataType::LargeList(b)) => {
+            Some(DataType::LargeList(Arc::new(merge_field(a, b)?)))
+        }
+        (DataType::FixedSizeList(a, na), DataType::FixedSizeList(b, nb)) if na == nb => {
+            Some(DataType::FixedSizeList(Arc::new(merge_field(a, b)?), *na))
+        }
+        (a, b) if a == b => Some(a.clone()),
+        _ => None,
+    }
+}
+
+/// Checks that `schema` is identical to the `established` schema of a topic whose schema
+/// is pinned.
+///
+/// Unlike [`merge_schemas`] no change is allowed, including additive ones, an
+/// [`Error::IncompatibleSchema`] is returned if fields differ in name, order, data type or
+/// nullability. Schema metadata is ignored.
+pub fn check_pinned_schema(established: &Schema, schema: &Schema) -> Result<(), Error> {
+    if established.fields() != schema.fields() {
+        return Err(Error::IncompatibleSchema(format!(
+            "schema is pinned to `{}`, received `{}`",
+            established, schema
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::DataType;
+
+    #[test]
+    fn merge_additive_schemas() {
+        let s1 = Schema::new(vec![
+            Field::new("timestamp_ns", DataType::Int64, false),
+            Field::new("x", DataType::Float64, false),
+        ]);
+        let s2 = Schema::new(vec![
+            Field::new("timestamp_ns", DataType::Int64, false),
+            Field::new("x", DataType::Float64, false),
+            Field::new("y", DataType::Float64, false),
+        ]);
+        let s3 = Schema::new(vec![
+            Field::new("timestamp_ns", DataType::Int64, false),
+            Field::new("y", DataType::Float64, true),
+        ]);
+
+        let merged = merge_schemas(&[s1, s2, s3]).unwrap();
+
+        assert_eq!(
+            merged,
+            Schema::new(vec![
+                Field::new("timestamp_ns", DataType::Int64, false),
+                Field::new("x", DataType::Float64, true),
+                Field::new("y", DataType::Float64, true),
+            ])
+        );
+    }
+
+    #[test]
+    fn merge_conflicting_schemas() {
+        let s1 = Schema::new(vec![
+            Field::new("timestamp_ns", DataType::Int64, false),
+            Field::new("x", DataType::Float64, false),
+        ]);
+        let s2 = Schema::new(vec![
+            Field::new("timestamp_ns", DataType::Int64, false),
+            Field::new("x", DataType::Utf8, false),
+        ]);
+
+        let result = merge_schemas(&[s1, s2]);
+
+        assert!(matches!(result, Err(Error::IncompatibleSchema(_))));
+    }
+
+    #[test]
+    fn merge_nested_nullability() {
+        let pose = |nullable: bool| {
+            DataType::Struct(Fields::from(vec![
+                Field::new("x", DataType::Float64, false),
+                Field::new("y", DataType::Float64, nullable),
+            ]))
+        };
+        let samples = |nullable: bool| {
+            DataType::List(Arc::new(Field::new("item", DataType::Float64, nullable)))
+        };
+
+        let s1 = Schema::new(vec![
+            Field::new("pose", pose(false), false),
+            Field::new("samples", samples(true), false),
+        ]);
+        let s2 = Schema::new(vec![
+            Field::new("pose", pose(true), false),
+            Field::new("samples", samples(false), false),
+        ]);
+
+        let merged = merge_schemas(&[s1, s2]).unwrap();
+
+        assert_eq!(
+            merged,
+            Schema::new(vec![
+                Field::new("pose", pose(true), false),
+                Field::new("samples", samples(true), false),
+            ])
+        );
+    }
+
+    #[test]
+    fn pinned_schema_identical() {
+        let established = Schema::new(vec![
+            Field::new("timestamp_ns", DataType::Int64, false),
+            Field::new("x", DataType::Float64, false),
+        ]);
+        let schema = established.clone();
+
+        assert!(check_pinned_schema(&established, &schema).is_ok());
+    }
+
+    #[test]
+    fn pinned_schema_additive_change() {
+        let established = Schema::new(vec![
+            Field::new("timestamp_ns", DataType::Int64, false),
+            Field::new("x", DataType::Float64, false),
+        ]);
+        let schema = Schema::new(vec![
+            Field::new("timestamp_ns", DataType::Int64, false),
+            Field::new("x", DataType::Float64, false),
+            Field::new("y", DataType::Float64, false),
+        ]);
+
+        let result = check_pinned_schema(&established, &schema);
+
+        assert!(matches!(result, Err(Error::IncompatibleSchema(_))));
+    }
+}
@@ -0,0 +1,305 @@
+//! Write-ahead log (WAL) used to make uploads durable across crashes.
+//!
+//! Incoming batches are appended to an append-only log on the local filesystem before being
+//! handed to the chunk writers. Each log is an Arrow IPC stream, the resource locator of the
+//! uploaded topic is stored in the schema metadata so that, on restart, unflushed logs can be
+//! replayed into chunks. The log is truncated every time its batches are flushed as chunks and
+//! discarded once the upload is completed.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow::array::RecordBatch;
+use arrow::datatypes::Schema;
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use log::{trace, warn};
+
+use super::Error;
+
+/// Schema metadata key holding the resource locator of the logged topic
+const RESOURCE_LOCATOR_KEY: &str = "mosaico:wal:resource_locator";
+
+/// Extension of the log files
+const EXTENSION: &str = "wal";
+
+/// Extension of the empty logs being written by a checkpoint, ignored by the replay
+const TMP_EXTENSION: &str = "wal.tmp";
+
+/// Appends batches to a write-ahead log.
+pub struct WalWriter {
+    writer: StreamWriter<File>,
+    /// Schema of the log, holding the resource locator
+    schema: Schema,
+    path: PathBuf,
+}
+
+impl WalWriter {
+    /// Creates a new log named `name` inside `dir` for the resource `resource_locator`.
+    ///
+    /// If a log with the same name already exists it will be overwritten.
+    pub fn try_new(
+        dir: impl AsRef<Path>,
+        name: &str,
+        resource_locator: &str,
+        schema: &Schema,
+    ) -> Result<Self, Error> {
+        std::fs::create_dir_all(&dir)?;
+
+        let path = dir.as_ref().join(name).with_extension(EXTENSION);
+
+        let mut metadata = schema.metadata().clone();
+        metadata.insert(RESOURCE_LOCATOR_KEY.to_owned(), resource_locator.to_owned());
+        let schema = schema.clone().with_metadata(metadata);
+
+        let file = File::create(&path)?;
+        let mut writer = StreamWriter::try_new(file, &schema)?;
+        sync(&mut writer)?;
+        sync_dir(&path)?;
+
+        trace!("created wal `{}`", path.to_string_lossy());
+
+        Ok(Self {
+            writer,
+            schema,
+            path,
+        })
+    }
+
+    /// Appends a batch to the log.
+    ///
+    /// The batch is synced to disk before returning, so that once this function returns the
+    /// batch can be safely acknowledged.
+    pub fn append(&mut self, batch: &RecordBatch) -> Result<(), Error> {
+        self.writer.write(batch)?;
+        sync(&mut self.writer)
+    }
+
+    /// Truncates the log keeping only the `retained` batches, to be called once the other
+    /// logged batches have been persisted, so that they are not replayed after a crash.
+    /// `retained` are the logged batches not yet persisted, in the order they were appended.
+    ///
+    /// The truncated log is written aside and then moved in place of the current one, a crash
+    /// leaves either of the two.
+    pub fn checkpoint(&mut self, retained: &[RecordBatch]) -> Result<(), Error> {
+        let tmp_path = self.path.with_extension(TMP_EXTENSION);
+
+        let file = File::create(&tmp_path)?;
+        let mut writer = StreamWriter::try_new(file, &self.schema)?;
+        for batch in retained {
+            writer.write(batch)?;
+        }
+        sync(&mut writer)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        sync_dir(&self.path)?;
+        self.writer = writer;
+
+        trace!("checkpointed wal `{}`", self.path.to_string_lossy());
+
+        Ok(())
+    }
+
+    /// Removes the log, to be called once all the logged batches have been persisted.
+    pub fn discard(self) -> Result<(), Error> {
+        let path = self.path;
+        drop(self.writer);
+        std::fs::remove_file(&path)?;
+
+        trace!("discarded wal `{}`", path.to_string_lossy());
+
+        Ok(())
+    }
+}
+
+fn sync(writer: &mut StreamWriter<File>) -> Result<(), Error> {
+    writer.flush()?;
+    writer.get_ref().sync_data()?;
+    Ok(())
+}
+
+/// Syncs the directory holding the log at `path`, otherwise the entry of a newly created or
+/// renamed log may be lost
+fn sync_dir(path: &Path) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Content of a write-ahead log left behind by an interrupted upload.
+pub struct WalEntry {
+    /// Resource locator of the logged topic
+    pub resource_locator: String,
+    /// Schema of the logged batches
+    pub schema: Arc<Schema>,
+    /// Batches appended to the log
+    pub batches: Vec<RecordBatch>,
+    path: PathBuf,
+}
+
+impl WalEntry {
+    /// Name of the log, as given to [`WalWriter::try_new`]
+    pub fn name(&self) -> &str {
+        self.path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default()
+    }
+
+    /// Removes the log, to be called once the batches have been replayed.
+    pub fn discard(self) -> Result<(), Error> {
+        std::fs::remove_file(&self.path)?;
+        Ok(())
+    }
+}
+
+/// Reads all the logs found in `dir`.
+///
+/// A crash could leave a partially written batch at the end of a log, since the batch was never
+/// acknowledged the tail is ignored. Logs without a readable header do not contain any batch
+/// and are removed.
+pub fn replay(dir: impl AsRef<Path>) -> Result<Vec<WalEntry>, Error> {
+    if !dir.as_ref().exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+
+    for dir_entry in std::fs::read_dir(dir)? {
+        let path = dir_entry?.path();
+
+        if path.extension().is_none_or(|ext| ext != EXTENSION) {
+            continue;
+        }
+
+        let reader = match StreamReader::try_new(File::open(&path)?, None) {
+            Ok(reader) => reader,
+            Err(e) => {
+                warn!(
+                    "removing unreadable wal `{}`: {}",
+                    path.to_string_lossy(),
+                    e
+                );
+                std::fs::remove_file(&path)?;
+                continue;
+            }
+        };
+
+        let schema = reader.schema();
+        let Some(resource_locator) = schema.metadata().get(RESOURCE_LOCATOR_KEY).cloned() else {
+            warn!(
+                "skipping wal `{}`, missing resource locator",
+                path.to_string_lossy()
+            );
+            continue;
+        };
+
+        let mut batches = Vec::new();
+        for batch in reader {
+            match batch {
+                Ok(batch) => batches.push(batch),
+                Err(e) => {
+                    warn!(
+                        "truncated wal `{}`, ignoring tail: {}",
+                        path.to_string_lossy(),
+                        e
+                    );
+                    break;
+                }
+            }
+        }
+
+        // Remove the internal metadata, batches are replayed with the original schema
+        let mut metadata = schema.metadata().clone();
+        metadata.remove(RESOURCE_LOCATOR_KEY);
+        let schema = Arc::new(schema.as_ref().clone().with_metadata(metadata));
+
+        let batches = batches
+            .into_iter()
+            .map(|batch| batch.with_schema(schema.clone()))
+            .collect::<Result<_, _>>()?;
+
+        entries.push(WalEntry {
+            resource_locator,
+            schema,
+            batches,
+            path,
+        });
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+    use arrow::datatypes::{DataType, Field};
+
+    fn batch(schema: &Arc<Schema>, values: Vec<i64>) -> RecordBatch {
+        RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(values))]).unwrap()
+    }
+
+    #[test]
+    fn replay_after_crash() {
+        let dir = std::env::temp_dir().join(crate::utils::random::random_string(10));
+
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "timestamp_ns",
+            DataType::Int64,
+            false,
+        )]));
+
+        let mut wal = WalWriter::try_new(&dir, "upload", "sequence/topic", &schema).unwrap();
+        wal.append(&batch(&schema, vec![1, 2, 3])).unwrap();
+        wal.append(&batch(&schema, vec![4, 5])).unwrap();
+
+        // Simulate a crash, the writer is dropped without discarding the log
+        drop(wal);
+
+        let mut entries = replay(&dir).unwrap();
+        assert_eq!(entries.len(), 1);
+
+        let entry = entries.pop().unwrap();
+        assert_eq!(entry.name(), "upload");
+        assert_eq!(entry.resource_locator, "sequence/topic");
+        assert_eq!(entry.schema, schema);
+        assert_eq!(entry.batches.len(), 2);
+        assert_eq!(entry.batches.iter().map(|b| b.num_rows()).sum::<usize>(), 5);
+
+        entry.discard().unwrap();
+        assert!(replay(&dir).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replay_after_checkpoint() {
+        let dir = std::env::temp_dir().join(crate::utils::random::random_string(10));
+
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "timestamp_ns",
+            DataType::Int64,
+            false,
+        )]));
+
+        // The batches logged before the checkpoint are not replayed, unless retained
+        let mut wal = WalWriter::try_new(&dir, "upload", "sequence/topic", &schema).unwrap();
+        wal.append(&batch(&schema, vec![1, 2, 3])).unwrap();
+        wal.append(&batch(&schema, vec![4])).unwrap();
+        wal.checkpoint(&[batch(&schema, vec![4])]).unwrap();
+        wal.append(&batch(&schema, vec![5, 6])).unwrap();
+        drop(wal);
+
+        let entries = replay(&dir).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].resource_locator, "sequence/topic");
+        assert_eq!(entries[0].batches.len(), 2);
+        assert_eq!(entries[0].batches[0].num_rows(), 1);
+        assert_eq!(entries[0].batches[1].num_rows(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,8 +2,10 @@ use std::sync::Arc;
 
 use arrow::datatypes::Schema;
 use parquet::arrow::ArrowWriter;
+use parquet::file::properties::EnabledStatistics;
 
 use super::{Error, Format};
+use crate::types;
 
 pub enum Writer {
     /// Parquet file format <https://parquet.apache.org/docs/file-format/>
@@ -11,13 +13,52 @@ pub enum Writer {
     Parquet(ArrowWriter<Vec<u8>>),
 }
 
+/// Options of the datafiles written for a topic, see [`types::TopicProperties`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct WriterOptions {
+    /// If true page-level statistics are collected for every column, so that the column
+    /// index written in the file footer allows readers to skip the pages that can't match a
+    /// predicate.
+    pub page_index: bool,
+    /// Quality level of the format compression codec, if not provided the format default
+    /// level is used.
+    pub compression_level: Option<i32>,
+}
+
+impl From<&types::TopicProperties> for WriterOptions {
+    fn from(value: &types::TopicProperties) -> Self {
+        Self {
+            page_index: value.page_index,
+            compression_level: value.compression_level,
+        }
+    }
+}
+
 impl Writer {
-    pub fn new(schema: &Arc<Schema>, format: Format) -> Result<Self, Error> {
+    /// Creates a writer for `format` configured with `options`.
+    pub fn new(
+        schema: &Arc<Schema>,
+        format: Format,
+        options: WriterOptions,
+    ) -> Result<Self, Error> {
         // Delegate to Parquet strategy for format-specific writer properties
         let parquet_strategy = format
             .as_parquet()
             .expect("Writer::new requires a Parquet-based format");
-        let props = parquet_strategy.writer_properties();
+        let mut props = parquet_strategy.writer_properties();
+        if options.page_index {
+            props = props
+                .into_builder()
+                .set_statistics_enabled(EnabledStatistics::Page)
+                .build();
+        }
+        if let Some(level) = options.compression_level {
+            // Columns with a specific codec (e.g. the uncompressed timestamp) are unaffected
+            props = props
+                .into_builder()
+                .set_compression(format.compression_with_level(level)?)
+                .build();
+        }
 
         Ok(Self::Parquet(ArrowWriter::try_new(
             Vec::new(),
@@ -0,0 +1,126 @@
+//! Authorization of the operations requested by the clients.
+//!
+//! Before executing an action, an upload or a query the server consults an [`Authorizer`],
+//! which decides whether the caller is allowed to access each resource targeted by the
+//! operation. By default every operation is allowed ([`AllowAll`]), deployments serving
+//! multiple tenants can provide their own implementation.
+
+pub use crate::marshal::Access;
+use crate::marshal::ActionRequest;
+use crate::params;
+use crate::server::endpoints::ActionError;
+
+/// Flight metadata key carrying the identity of the caller.
+///
+/// The identity is asserted by the client, deployments relying on it should place the server
+/// behind a proxy that authenticates the clients and sets this key.
+pub const IDENTITY_METADATA_KEY: &str = "x-mosaico-identity";
+
+/// Identity of the client requesting an action
+#[derive(Debug, Clone, Default)]
+pub struct Caller {
+    /// Identity found in the request metadata, [`None`] for anonymous callers
+    pub identity: Option<String>,
+}
+
+impl Caller {
+    pub fn new(identity: impl Into<String>) -> Self {
+        Self {
+            identity: Some(identity.into()),
+        }
+    }
+
+    pub fn anonymous() -> Self {
+        Self::default()
+    }
+
+    /// Extracts the caller from the metadata of a Flight request
+    pub fn from_metadata(metadata: &tonic::metadata::MetadataMap) -> Self {
+        Self {
+            identity: metadata
+                .get(IDENTITY_METADATA_KEY)
+                .and_then(|v| v.to_str().ok())
+                .map(ToOwned::to_owned),
+        }
+    }
+}
+
+/// Operation requested by a client
+#[derive(Debug, Clone, Copy)]
+pub enum Operation<'a> {
+    /// Flight action
+    Action(&'a ActionRequest),
+    /// Upload of data on a topic, or on a sequence for line protocol uploads (`do_put`)
+    Upload,
+    /// Query streaming the data of a topic (`do_get`)
+    Query,
+}
+
+/// Outcome of an authorization request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Allow,
+    Deny,
+}
+
+/// Decides whether a caller can perform an operation.
+pub trait Authorizer: Send + Sync {
+    /// Returns the decision for `caller` requesting `access` to `resource` through
+    /// `operation`, `resource` is the name of a resource targeted by the operation if any
+    /// (see [`ActionRequest::targets`]). Actions targeting several resources are allowed only
+    /// if every target is allowed.
+    fn authorize(
+        &self,
+        caller: &Caller,
+        operation: Operation<'_>,
+        resource: Option<&str>,
+        access: Access,
+    ) -> Decision;
+}
+
+/// Default [`Authorizer`], allows every operation.
+pub struct AllowAll;
+
+impl Authorizer for AllowAll {
+    fn authorize(&self, _: &Caller, _: Operation<'_>, _: Option<&str>, _: Access) -> Decision {
+        Decision::Allow
+    }
+}
+
+/// Returns true if `caller` is one of the `admins` identities.
+pub fn is_admin(caller: &Caller, admins: &[String]) -> bool {
+    caller
+        .identity
+        .as_ref()
+        .is_some_and(|identity| admins.contains(identity))
+}
+
+/// Checks that `authorizer` allows `caller` to `access` `resource` through `operation`,
+/// returning [`ActionError::PermissionDenied`] otherwise.
+///
+/// [`Access::Admin`] is granted only to the configured administrators (see
+/// [`params::ConfigurablesParams::admin_identities`]), whatever the authorizer decides.
+pub fn check(
+    authorizer: &dyn Authorizer,
+    caller: &Caller,
+    operation: Operation<'_>,
+    resource: Option<&str>,
+    access: Access,
+) -> Result<(), ActionError> {
+    if access == Access::Admin && !is_admin(caller, &params::configurables().admin_identities) {
+        return Err(ActionError::PermissionDenied(format!(
+            "caller `{}` is not an administrator",
+            caller.identity.as_deref().unwrap_or("anonymous"),
+        )));
+    }
+
+    match authorizer.authorize(caller, operation, resource, access) {
+        Decision::Allow => Ok(()),
+        Decision::Deny => Err(ActionError::PermissionDenied(format!(
+            "caller `{}` is not allowed to {} `{}`",
+            caller.identity.as_deref().unwrap_or("anonymous"),
+            access,
+            resource.unwrap_or_default(),
+        ))),
+    }
+}
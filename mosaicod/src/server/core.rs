@@ -1,11 +1,12 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use log::{error, info, trace};
+use log::{error, info, trace, warn};
 use tokio::sync::Notify;
 
-use crate::{repo, store};
+use crate::{params, repo, store};
 
-use super::flight;
+use super::{auth, endpoints, flight};
 
 /// Mosaico server.
 /// Handles incoming requests and manages the repository and store.
@@ -20,6 +21,8 @@ pub struct Server {
     store: store::StoreRef,
     /// Repository configuration params
     pub repo_config: repo::Config,
+    /// Authorizer consulted before executing actions, uploads and queries
+    authorizer: Arc<dyn auth::Authorizer>,
 }
 
 impl Server {
@@ -30,9 +33,17 @@ impl Server {
             store,
             repo_config,
             shutdown: Arc::new(Notify::new()),
+            authorizer: Arc::new(auth::AllowAll),
         }
     }
 
+    /// Sets the authorizer consulted before executing actions, uploads and queries, by default
+    /// every operation is allowed.
+    pub fn with_authorizer(mut self, authorizer: Arc<dyn auth::Authorizer>) -> Self {
+        self.authorizer = authorizer;
+        self
+    }
+
     /// Start the server and wait for it to finish.
     ///
     /// The `on_start` callback is called once the server has started.
@@ -78,12 +89,48 @@ impl Server {
             Ok::<repo::Repository, Box<dyn std::error::Error>>(repo)
         })?;
 
+        if let Some(wal_dir) = &params::configurables().wal_dir {
+            info!("replaying write-ahead logs");
+            rt.block_on(endpoints::wal_replay(
+                self.store.clone(),
+                repo.clone(),
+                wal_dir,
+            ))?;
+        }
+
+        if params::configurables().repair_on_startup {
+            info!("repairing the last chunk of the topics");
+            let removed = rt.block_on(repo::repair_topics(
+                self.store.clone(),
+                repo.clone(),
+                &params::configurables().instance_id,
+                Duration::from_secs(params::configurables().upload_lease_ttl_secs),
+            ))?;
+            for chunk in &removed {
+                warn!("removed corrupted chunk `{}`", chunk);
+            }
+        }
+
         let store = self.store.clone();
+        let authorizer = self.authorizer.clone();
         rt.block_on(async {
+            if let Some(interval) = params::configurables()
+                .vacuum_interval_secs
+                .filter(|&secs| secs > 0)
+            {
+                spawn_vacuum(
+                    store.clone(),
+                    Duration::from_secs(interval),
+                    Duration::from_secs(params::configurables().vacuum_age_secs),
+                );
+            }
+
             // Create a thread in tokio runtime to handle flight requests
             let handle_flight = rt.spawn(async move {
                 trace!("flight service starting");
-                if let Err(err) = flight::start(config, store, repo, Some(shutdown)).await {
+                if let Err(err) =
+                    flight::start(config, store, repo, authorizer, Some(shutdown)).await
+                {
                     error!("flight server error: {}", err);
                 }
             });
@@ -98,3 +145,21 @@ impl Server {
         Ok(())
     }
 }
+
+/// Spawns a task removing the orphaned staging files of the store every `interval`, the first
+/// vacuum runs at startup (see [`store::Store::vacuum`]).
+fn spawn_vacuum(store: store::StoreRef, interval: Duration, older_than: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match store.vacuum(older_than).await {
+                Ok(report) => info!(
+                    "vacuum completed, {} files removed, {} bytes reclaimed",
+                    report.files_removed, report.bytes_reclaimed
+                ),
+                Err(e) => error!("vacuum failed :: {}", e),
+            }
+        }
+    });
+}
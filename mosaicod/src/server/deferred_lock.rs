@@ -0,0 +1,358 @@
+//! Defers the lock of the topics at the end of their uploads.
+//!
+//! By default a topic is locked as soon as an upload is gracefully closed. Clients appending data
+//! incrementally with several short uploads can configure a grace period: the topic is locked
+//! only once no upload is received for the whole period, or when its sequence is finalized.
+//!
+//! Deferred locks are recorded in the repository, so that the locks pending when the server
+//! stops are scheduled again at startup (see [`DeferredLocks::recover`]) and an upload received
+//! by another instance sharing the repository cancels them.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, info, trace, warn};
+
+use crate::{
+    repo, store,
+    types::{self, Resource},
+};
+
+pub struct DeferredLocks {
+    /// Time without uploads after which a topic is locked, [`None`] to lock immediately
+    grace: Option<Duration>,
+    /// Topics waiting to be locked, with the generation of their scheduled lock
+    pending: Mutex<HashMap<String, u64>>,
+    generation: AtomicU64,
+    /// Clock measuring the grace period
+    clock: Arc<dyn types::Clock>,
+}
+
+impl DeferredLocks {
+    pub fn new(grace: Option<Duration>) -> Self {
+        Self {
+            grace,
+            pending: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            clock: Arc::new(types::SystemClock),
+        }
+    }
+
+    /// Replaces the system clock with `clock`
+    pub fn with_clock(mut self, clock: Arc<dyn types::Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Cancels the pending lock of the topic of `handle`, called when a new upload on the topic
+    /// starts. The lock may have been deferred by another instance or before a restart.
+    ///
+    /// Returns true if a lock was pending.
+    pub async fn cancel(&self, handle: &repo::FacadeTopic) -> Result<bool, repo::FacadeError> {
+        let scheduled = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(handle.locator.name())
+            .is_some();
+        let recorded = handle.cancel_deferred_lock().await?;
+        Ok(scheduled || recorded)
+    }
+
+    /// Locks the topic of a completed upload.
+    ///
+    /// Without a grace period the topic is locked immediately, otherwise the lock is scheduled
+    /// and performed once the grace period elapses on the clock of the locks, unless a new
+    /// upload on the topic starts in the meantime (see [`DeferredLocks::cancel`]).
+    pub async fn lock(
+        self: &Arc<Self>,
+        handle: repo::FacadeTopic,
+    ) -> Result<(), repo::FacadeError> {
+        let Some(grace) = self.grace else {
+            handle.lock().await?;
+            trace!("resource {} locked", handle.locator);
+            return Ok(());
+        };
+
+        let scheduled_at = self.clock.now();
+        handle.defer_lock(scheduled_at).await?;
+
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed);
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(handle.locator.name().to_owned(), generation);
+        debug!("lock of {} deferred by {:?}", handle.locator, grace);
+
+        let locks = self.clone();
+        tokio::spawn(async move {
+            let due = i64::from(scheduled_at).saturating_add(grace.as_millis() as i64);
+            let mut wait = grace;
+            loop {
+                tokio::time::sleep(wait).await;
+                let remaining = due - i64::from(locks.clock.now());
+                if remaining <= 0 {
+                    break;
+                }
+                // The clock is behind the timer, wait for the rest of the grace period
+                wait = Duration::from_millis(remaining as u64).min(grace);
+            }
+
+            if !locks.take(handle.locator.name(), generation) {
+                return;
+            }
+
+            match handle.lock_deferred(scheduled_at).await {
+                Ok(true) => trace!("resource {} locked", handle.locator),
+                Ok(false) => trace!("lock of {} cancelled elsewhere", handle.locator),
+                Err(e) => warn!("unable to lock {}: {}", handle.locator, e),
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Schedules again the locks deferred and not performed before the server stopped, called
+    /// at startup. Returns the number of topics whose lock was pending.
+    pub async fn recover(
+        self: &Arc<Self>,
+        store: store::StoreRef,
+        repo: repo::Repository,
+    ) -> Result<usize, repo::FacadeError> {
+        let topics = repo::FacadeTopic::pending_locks(&repo).await?;
+
+        for topic in &topics {
+            let handle = repo::FacadeTopic::new(topic.name().clone(), store.clone(), repo.clone());
+            self.lock(handle).await?;
+        }
+
+        if !topics.is_empty() {
+            info!("{} deferred topic locks recovered", topics.len());
+        }
+
+        Ok(topics.len())
+    }
+
+    /// Immediately locks the topics of `sequence` whose lock is pending, including the locks
+    /// deferred by other instances, called when the sequence is finalized.
+    pub async fn flush(
+        &self,
+        sequence: &str,
+        store: store::StoreRef,
+        repo: repo::Repository,
+    ) -> Result<(), repo::FacadeError> {
+        let mut topics: Vec<String> = {
+            let mut pending = self.pending.lock().unwrap();
+            let topics: Vec<String> = pending
+                .keys()
+                .filter(|name| {
+                    types::TopicResourceLocator::from(name.as_str()).sequence_name() == sequence
+                })
+                .cloned()
+                .collect();
+            for topic in &topics {
+                pending.remove(topic);
+            }
+            topics
+        };
+
+        for topic in repo::FacadeTopic::pending_locks(&repo).await? {
+            if topic.sequence_name() == sequence && !topics.contains(topic.name()) {
+                topics.push(topic.name().clone());
+            }
+        }
+
+        for topic in topics {
+            let handle = repo::FacadeTopic::new(topic, store.clone(), repo.clone());
+            handle.lock().await?;
+            trace!("resource {} locked", handle.locator);
+        }
+
+        Ok(())
+    }
+
+    /// Removes the pending lock of `topic` if still scheduled with `generation`, returns
+    /// `false` if the lock was cancelled or rescheduled.
+    fn take(&self, topic: &str, generation: u64) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.get(topic) != Some(&generation) {
+            return false;
+        }
+        pending.remove(topic);
+        true
+    }
+}
+
+impl Default for DeferredLocks {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::FacadeTopic;
+    use crate::server::testing;
+
+    /// Creates the topic `sequence/topic` without data, returning its handle.
+    async fn create_topic(repo: &repo::Repository, store: &store::StoreRef) -> FacadeTopic {
+        let sequence = testing::create_empty_sequence(repo, store, "sequence")
+            .await
+            .unwrap();
+        testing::create_empty_topic(repo, store, &sequence, "sequence/topic")
+            .await
+            .unwrap();
+        FacadeTopic::new("sequence/topic".to_owned(), store.clone(), repo.clone())
+    }
+
+    #[sqlx::test]
+    async fn immediate_lock(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let handle = create_topic(&repo, &store).await;
+        let topic = FacadeTopic::new(
+            handle.locator.name().clone(),
+            (*store).clone(),
+            (*repo).clone(),
+        );
+
+        let locks = Arc::new(DeferredLocks::default());
+        locks.lock(handle).await.unwrap();
+
+        assert!(topic.is_locked().await.unwrap());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn deferred_lock(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let handle = create_topic(&repo, &store).await;
+        let topic = || {
+            FacadeTopic::new(
+                handle.locator.name().clone(),
+                (*store).clone(),
+                (*repo).clone(),
+            )
+        };
+
+        let grace = Duration::from_millis(200);
+        let locks = Arc::new(DeferredLocks::new(Some(grace)));
+        locks.lock(topic()).await.unwrap();
+        assert!(!topic().is_locked().await.unwrap());
+
+        // A new upload within the grace period postpones the lock
+        tokio::time::sleep(grace / 2).await;
+        assert!(locks.cancel(&topic()).await.unwrap());
+        locks.lock(topic()).await.unwrap();
+
+        tokio::time::sleep(grace / 2 + grace / 4).await;
+        assert!(!topic().is_locked().await.unwrap());
+
+        tokio::time::sleep(grace).await;
+        assert!(topic().is_locked().await.unwrap());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the grace period is measured on the clock of the locks.
+    async fn deferred_lock_clock(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let handle = create_topic(&repo, &store).await;
+        let topic = FacadeTopic::new(
+            handle.locator.name().clone(),
+            (*store).clone(),
+            (*repo).clone(),
+        );
+
+        let clock = Arc::new(types::MockClock::new(types::Timestamp::from(1_000_000)));
+        let grace = Duration::from_millis(100);
+        let locks = Arc::new(DeferredLocks::new(Some(grace)).with_clock(clock.clone()));
+        locks.lock(handle).await.unwrap();
+
+        // The timer expired but the grace period has not elapsed on the clock
+        tokio::time::sleep(grace * 3).await;
+        assert!(!topic.is_locked().await.unwrap());
+
+        clock.advance(grace);
+        tokio::time::sleep(grace * 3).await;
+        assert!(topic.is_locked().await.unwrap());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn flush_pending_locks(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let handle = create_topic(&repo, &store).await;
+        let topic = FacadeTopic::new(
+            handle.locator.name().clone(),
+            (*store).clone(),
+            (*repo).clone(),
+        );
+
+        let locks = Arc::new(DeferredLocks::new(Some(Duration::from_secs(3600))));
+        locks.lock(handle).await.unwrap();
+        assert!(!topic.is_locked().await.unwrap());
+
+        locks
+            .flush("sequence", (*store).clone(), (*repo).clone())
+            .await
+            .unwrap();
+        assert!(topic.is_locked().await.unwrap());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the locks pending when the server stops are scheduled again at
+    /// startup, and that an upload received by another instance cancels them.
+    async fn recover_pending_locks(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let handle = create_topic(&repo, &store).await;
+        let topic = || {
+            FacadeTopic::new(
+                handle.locator.name().clone(),
+                (*store).clone(),
+                (*repo).clone(),
+            )
+        };
+
+        // The lock is deferred, then the server stops
+        let locks = Arc::new(DeferredLocks::new(Some(Duration::from_secs(3600))));
+        locks.lock(topic()).await.unwrap();
+        drop(locks);
+
+        let grace = Duration::from_millis(200);
+        let locks = Arc::new(DeferredLocks::new(Some(grace)));
+        let recovered = locks
+            .recover((*store).clone(), (*repo).clone())
+            .await
+            .unwrap();
+        assert_eq!(recovered, 1);
+        assert!(!topic().is_locked().await.unwrap());
+
+        tokio::time::sleep(grace * 2).await;
+        assert!(topic().is_locked().await.unwrap());
+        assert!(FacadeTopic::pending_locks(&repo).await.unwrap().is_empty());
+
+        // A lock deferred by an instance is cancelled by an upload received by another one
+        topic().reopen().await.unwrap();
+        locks.lock(topic()).await.unwrap();
+        let other = DeferredLocks::new(Some(grace));
+        assert!(other.cancel(&topic()).await.unwrap());
+
+        tokio::time::sleep(grace * 2).await;
+        assert!(!topic().is_locked().await.unwrap());
+
+        Ok(())
+    }
+}
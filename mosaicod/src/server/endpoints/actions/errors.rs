@@ -0,0 +1,209 @@
+//! Errors returned by the action handlers.
+
+use thiserror::Error;
+
+use crate::{marshal, query, repo, rw, types};
+
+/// Errors returned by the action handlers.
+///
+/// Each variant is mapped to a specific Flight (gRPC) status code, so that clients
+/// can react to the cause of the failure (e.g. retrying only on internal errors).
+#[derive(Error, Debug)]
+pub enum ActionError {
+    /// The requested resource does not exist
+    #[error("not found :: {0}")]
+    NotFound(String),
+
+    /// The resource to create already exists
+    #[error("already exists :: {0}")]
+    AlreadyExists(String),
+
+    /// The resource is locked and can't be modified
+    #[error("resource locked :: {0}")]
+    ResourceLocked(String),
+
+    /// The request contains invalid data
+    #[error("invalid argument :: {0}")]
+    InvalidArgument(String),
+
+    /// The caller is not allowed to perform the action
+    #[error("permission denied :: {0}")]
+    PermissionDenied(String),
+
+    /// The request exceeds a limit configured on the server
+    #[error("quota exceeded :: {0}")]
+    QuotaExceeded(String),
+
+    /// Unexpected failure of the server
+    #[error("internal error :: {0}")]
+    Internal(String),
+}
+
+impl From<ActionError> for tonic::Status {
+    fn from(value: ActionError) -> Self {
+        use tonic::Status;
+        let msg = value.to_string();
+        match value {
+            ActionError::NotFound(_) => Status::not_found(msg),
+            ActionError::AlreadyExists(_) => Status::already_exists(msg),
+            ActionError::ResourceLocked(_) => Status::failed_precondition(msg),
+            ActionError::InvalidArgument(_) => Status::invalid_argument(msg),
+            ActionError::PermissionDenied(_) => Status::permission_denied(msg),
+            ActionError::QuotaExceeded(_) => Status::resource_exhausted(msg),
+            ActionError::Internal(_) => Status::internal(msg),
+        }
+    }
+}
+
+impl From<repo::FacadeError> for ActionError {
+    fn from(value: repo::FacadeError) -> Self {
+        use repo::FacadeError;
+        let msg = value.to_string();
+        match value {
+            FacadeError::NotFound(_)
+            | FacadeError::RepositoryError(repo::Error::NotFound)
+            | FacadeError::RepositoryError(repo::Error::BackendError(sqlx::Error::RowNotFound)) => {
+                Self::NotFound(msg)
+            }
+            FacadeError::SequenceLocked
+            | FacadeError::TopicLocked
+            | FacadeError::TopicLeased(_)
+            | FacadeError::InvalidTransition(_)
+            | FacadeError::IdempotencyKeyInUse(_) => Self::ResourceLocked(msg),
+            FacadeError::TopicUnlocked
+            | FacadeError::IdempotencyKeyMismatch(_)
+            | FacadeError::Unauthorized
+            | FacadeError::MetadataError(_)
+            | FacadeError::InvalidArgument(_)
+            | FacadeError::QueryError(query::Error::BadField { .. })
+            | FacadeError::QueryError(query::Error::OpError { .. }) => Self::InvalidArgument(msg),
+            FacadeError::AlreadyExists(_) => Self::AlreadyExists(msg),
+            // A racing creation of the same resource is rejected by the database constraints
+            FacadeError::RepositoryError(repo::Error::BackendError(sqlx::Error::Database(e)))
+                if e.is_unique_violation() =>
+            {
+                Self::AlreadyExists(msg)
+            }
+            _ => Self::Internal(msg),
+        }
+    }
+}
+
+impl From<marshal::ActionError> for ActionError {
+    fn from(value: marshal::ActionError) -> Self {
+        match value {
+            marshal::ActionError::ResponseSerializationError(_) => {
+                Self::Internal(value.to_string())
+            }
+            _ => Self::InvalidArgument(value.to_string()),
+        }
+    }
+}
+
+impl From<marshal::Error> for ActionError {
+    fn from(value: marshal::Error) -> Self {
+        Self::InvalidArgument(value.to_string())
+    }
+}
+
+impl From<types::EmptyOntologyTag> for ActionError {
+    fn from(value: types::EmptyOntologyTag) -> Self {
+        Self::InvalidArgument(value.to_string())
+    }
+}
+
+impl From<rw::InvalidCompressionLevel> for ActionError {
+    fn from(value: rw::InvalidCompressionLevel) -> Self {
+        Self::InvalidArgument(value.to_string())
+    }
+}
+
+impl From<types::DisallowedNameCharacter> for ActionError {
+    fn from(value: types::DisallowedNameCharacter) -> Self {
+        Self::InvalidArgument(value.to_string())
+    }
+}
+
+impl From<crate::arrow::SchemaError> for ActionError {
+    fn from(value: crate::arrow::SchemaError) -> Self {
+        Self::InvalidArgument(value.to_string())
+    }
+}
+
+impl From<crate::ontology::Error> for ActionError {
+    fn from(value: crate::ontology::Error) -> Self {
+        Self::InvalidArgument(value.to_string())
+    }
+}
+
+impl From<uuid::Error> for ActionError {
+    fn from(value: uuid::Error) -> Self {
+        Self::InvalidArgument(format!("malformed key :: {value}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    fn code(err: ActionError) -> Code {
+        tonic::Status::from(err).code()
+    }
+
+    #[test]
+    fn status_codes() {
+        assert_eq!(code(ActionError::NotFound("".into())), Code::NotFound);
+        assert_eq!(
+            code(ActionError::AlreadyExists("".into())),
+            Code::AlreadyExists
+        );
+        assert_eq!(
+            code(ActionError::ResourceLocked("".into())),
+            Code::FailedPrecondition
+        );
+        assert_eq!(
+            code(ActionError::InvalidArgument("".into())),
+            Code::InvalidArgument
+        );
+        assert_eq!(
+            code(ActionError::QuotaExceeded("".into())),
+            Code::ResourceExhausted
+        );
+        assert_eq!(code(ActionError::Internal("".into())), Code::Internal);
+    }
+
+    #[test]
+    fn facade_errors() {
+        assert!(matches!(
+            ActionError::from(repo::FacadeError::TopicLocked),
+            ActionError::ResourceLocked(_)
+        ));
+        assert!(matches!(
+            ActionError::from(repo::FacadeError::NotFound("topic".into())),
+            ActionError::NotFound(_)
+        ));
+        assert!(matches!(
+            ActionError::from(repo::FacadeError::Unimplemented),
+            ActionError::Internal(_)
+        ));
+        assert!(matches!(
+            ActionError::from(repo::FacadeError::from(query::Error::bad_field(
+                "missing".into()
+            ))),
+            ActionError::InvalidArgument(_)
+        ));
+    }
+
+    #[test]
+    fn marshal_errors() {
+        assert!(matches!(
+            ActionError::from(marshal::ActionError::BodyTooLarge { size: 2, limit: 1 }),
+            ActionError::InvalidArgument(_)
+        ));
+        assert!(matches!(
+            ActionError::from(marshal::ActionError::ResponseSerializationError("".into())),
+            ActionError::Internal(_)
+        ));
+    }
+}
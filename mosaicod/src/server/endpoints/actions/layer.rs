@@ -2,15 +2,15 @@
 
 use log::{info, warn};
 
-use super::ActionContext;
-use crate::{marshal::ActionResponse, repo::FacadeLayer, server::errors::ServerError, types};
+use super::{ActionContext, ActionError};
+use crate::{marshal::ActionResponse, repo::FacadeLayer, types};
 
 /// Creates a new layer with the given name and description.
 pub async fn create(
     ctx: &ActionContext,
     name: String,
     description: String,
-) -> Result<ActionResponse, ServerError> {
+) -> Result<ActionResponse, ActionError> {
     info!("creating layer `{}`", name);
 
     let handle = FacadeLayer::new(
@@ -24,7 +24,7 @@ pub async fn create(
 }
 
 /// Deletes a layer.
-pub async fn delete(ctx: &ActionContext, name: String) -> Result<ActionResponse, ServerError> {
+pub async fn delete(ctx: &ActionContext, name: String) -> Result<ActionResponse, ActionError> {
     warn!("deleting layer `{}`", name);
 
     let handle = FacadeLayer::new(
@@ -43,7 +43,7 @@ pub async fn update(
     prev_name: String,
     curr_name: String,
     curr_description: String,
-) -> Result<ActionResponse, ServerError> {
+) -> Result<ActionResponse, ActionError> {
     info!(
         "updating layer `{}` with new name `{}` and new description `{}`",
         prev_name, curr_name, curr_description
@@ -65,7 +65,7 @@ pub async fn update(
 }
 
 /// Lists all layers.
-pub async fn list(ctx: &ActionContext) -> Result<ActionResponse, ServerError> {
+pub async fn list(ctx: &ActionContext) -> Result<ActionResponse, ActionError> {
     info!("request layer list");
 
     let layers = FacadeLayer::all(ctx.repo.clone()).await?;
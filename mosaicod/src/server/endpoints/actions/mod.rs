@@ -1,23 +1,39 @@
 //! Action handlers for Flight DoAction requests.
 //!
 //! This module contains free functions for handling Flight actions,
-//! organized by resource type (sequence, topic, layer, query).
+//! organized by resource type (sequence, topic, layer, query, system).
+
+mod errors;
+pub use errors::ActionError;
 
 pub mod layer;
 pub mod query;
 pub mod sequence;
+pub mod system;
 pub mod topic;
 
-use crate::{query as ts_query, repo, store};
+use std::sync::Arc;
+
+use crate::{
+    marshal, params, query as ts_query, repo,
+    server::{deferred_lock::DeferredLocks, upload_coalescer::UploadCoalescer},
+    store, types,
+    types::MetadataBlob,
+};
 
 /// Shared context for all action handlers.
 ///
 /// Contains references to the store, repository, and timeseries engine
-/// that handlers need to perform their operations.
+/// that handlers need to perform their operations, the clock handlers
+/// read the current time from, the topic locks deferred by the uploads and the data buffered
+/// by the small uploads.
 pub struct ActionContext {
     pub store: store::StoreRef,
     pub repo: repo::Repository,
     pub ts_gw: ts_query::TimeseriesGatewayRef,
+    pub clock: Arc<dyn types::Clock>,
+    pub deferred_locks: Arc<DeferredLocks>,
+    pub upload_coalescer: Arc<UploadCoalescer>,
 }
 
 impl ActionContext {
@@ -26,6 +42,62 @@ impl ActionContext {
         repo: repo::Repository,
         ts_gw: ts_query::TimeseriesGatewayRef,
     ) -> Self {
-        Self { store, repo, ts_gw }
+        Self {
+            store,
+            repo,
+            ts_gw,
+            clock: Arc::new(types::SystemClock),
+            deferred_locks: Arc::new(DeferredLocks::default()),
+            upload_coalescer: Arc::new(UploadCoalescer::default()),
+        }
+    }
+
+    /// Replaces the system clock with `clock`
+    pub fn with_clock(mut self, clock: Arc<dyn types::Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Shares the topic locks deferred by the uploads, see [`DeferredLocks`]
+    pub fn with_deferred_locks(mut self, deferred_locks: Arc<DeferredLocks>) -> Self {
+        self.deferred_locks = deferred_locks;
+        self
+    }
+
+    /// Shares the data buffered by the small uploads, see [`UploadCoalescer`]
+    pub fn with_upload_coalescer(mut self, upload_coalescer: Arc<UploadCoalescer>) -> Self {
+        self.upload_coalescer = upload_coalescer;
+        self
+    }
+}
+
+/// Parses the serialized user metadata of a resource being created.
+///
+/// Returns an [`ActionError::InvalidArgument`] if the metadata exceeds the configured
+/// `max_user_metadata_bytes`, since the metadata is read back on every resource access.
+fn user_metadata_blob(user_metadata: &str) -> Result<marshal::JsonMetadataBlob, ActionError> {
+    let limit = params::configurables().max_user_metadata_bytes;
+    if user_metadata.len() > limit {
+        return Err(ActionError::InvalidArgument(format!(
+            "user metadata size ({} bytes) exceeds the limit of {} bytes",
+            user_metadata.len(),
+            limit
+        )));
+    }
+
+    marshal::JsonMetadataBlob::try_from_str(user_metadata)
+        .map_err(|e| repo::FacadeError::from(e).into())
+}
+
+/// Checks the time column declared by a resource being created.
+///
+/// Returns an [`ActionError::InvalidArgument`] if the name is empty (or blank), since no data
+/// column can match it.
+fn check_time_column(time_column: Option<String>) -> Result<Option<String>, ActionError> {
+    match time_column {
+        Some(time_column) if time_column.trim().is_empty() => Err(ActionError::InvalidArgument(
+            "empty time column name".to_owned(),
+        )),
+        time_column => Ok(time_column),
     }
 }
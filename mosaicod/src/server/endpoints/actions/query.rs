@@ -2,27 +2,375 @@
 
 use log::{info, trace};
 
-use super::ActionContext;
+use super::{ActionContext, ActionError};
 use crate::{
     marshal::{self, ActionResponse},
-    repo::FacadeQuery,
-    server::errors::ServerError,
+    params, query,
+    repo::{FacadeError, FacadeQuery, FacadeTopic},
+    rw,
+    server::auth::Caller,
+    types,
 };
 
 /// Executes a query and returns matching groups.
+///
+/// If `count_only` is set only the number of data rows matching the query is returned. If
+/// `schema_only` is set only the schema of the topics selected by the sequence and topic
+/// filters is returned, as zero-row batches read from the chunks footer, without scanning
+/// any data.
 pub async fn execute(
     ctx: &ActionContext,
     query: serde_json::Value,
-) -> Result<ActionResponse, ServerError> {
+    count_only: bool,
+    schema_only: bool,
+) -> Result<ActionResponse, ActionError> {
     info!("performing a query");
 
+    if count_only && schema_only {
+        return Err(ActionError::InvalidArgument(
+            "`count_only` and `schema_only` are mutually exclusive".to_owned(),
+        ));
+    }
+
     let filter = marshal::query_filter_from_serde_value(query)?;
 
     trace!("query filter: {:?}", filter);
 
+    if schema_only {
+        return schemas(ctx, filter).await;
+    }
+
+    if count_only {
+        let row_count = FacadeQuery::count(filter, ctx.ts_gw.clone(), ctx.repo.clone()).await?;
+
+        trace!("rows found: {}", row_count);
+
+        return Ok(ActionResponse::QueryCount(marshal::QueryCount {
+            row_count,
+        }));
+    }
+
     let groups = FacadeQuery::query(filter, ctx.ts_gw.clone(), ctx.repo.clone()).await?;
 
     trace!("groups found: {:?}", groups);
 
     Ok(ActionResponse::Query(groups.into()))
 }
+
+/// Returns the schema of each topic selected by `filter`.
+async fn schemas(
+    ctx: &ActionContext,
+    filter: query::Filter,
+) -> Result<ActionResponse, ActionError> {
+    let topics = FacadeQuery::topics(filter, ctx.repo.clone())
+        .await?
+        .into_iter()
+        .map(|topic| {
+            let format = topic.serialization_format().ok_or_else(|| {
+                FacadeError::MissingSerializationFormat(topic.locator_name.clone())
+            })?;
+            Ok((topic.locator_name, format))
+        })
+        .collect::<Result<Vec<_>, FacadeError>>()?;
+    let items = topic_schemas(ctx, topics).await?;
+
+    Ok(ActionResponse::QuerySchema(
+        marshal::responses::QuerySchema { items },
+    ))
+}
+
+/// Reads the schema of each topic, given as its locator and serialization format, from the
+/// footer of its chunks. The schema of a topic without data is the one declared at its
+/// creation, if any.
+async fn topic_schemas(
+    ctx: &ActionContext,
+    topics: Vec<(String, rw::Format)>,
+) -> Result<Vec<marshal::responses::QuerySchemaItem>, ActionError> {
+    trace!("reading the schema of {} topics", topics.len());
+
+    let mut items = Vec::with_capacity(topics.len());
+    for (locator, format) in topics {
+        let handle = FacadeTopic::new(locator.clone(), ctx.store.clone(), ctx.repo.clone());
+        let schema = match handle.footer_schema(format).await? {
+            Some(schema) => Some(schema),
+            None => handle.metadata().await?.properties.schema,
+        };
+
+        items.push(marshal::responses::QuerySchemaItem::try_new(
+            locator, schema,
+        )?);
+    }
+
+    Ok(items)
+}
+
+/// Validates a query without executing it.
+///
+/// The validation stops at the first failing stage, reporting all the problems found in
+/// it: first the query syntax (operations, values and ranges), then the query against the
+/// repository (serialization formats and ontology fields, see [`FacadeQuery::validate`]). If
+/// the query is valid the schema of the selected topics and the number of chunks that would
+/// be searched are returned, no data is scanned.
+pub async fn validate(
+    ctx: &ActionContext,
+    query: serde_json::Value,
+) -> Result<ActionResponse, ActionError> {
+    info!("validating a query");
+
+    let filter = match marshal::query_filter_from_serde_value_collecting(query) {
+        Ok(filter) => filter,
+        Err(errors) => {
+            trace!("query rejected with {} syntax errors", errors.len());
+            return Ok(ActionResponse::QueryValidate(
+                marshal::responses::QueryValidate::invalid(
+                    errors.into_iter().map(Into::into).collect(),
+                ),
+            ));
+        }
+    };
+
+    let validation = FacadeQuery::validate(filter, ctx.repo.clone()).await?;
+    if !validation.issues.is_empty() {
+        trace!("query rejected with {} errors", validation.issues.len());
+        let errors = validation
+            .issues
+            .into_iter()
+            .map(|issue| marshal::responses::QueryValidationError {
+                field: issue.field,
+                message: issue.message,
+            })
+            .collect();
+        return Ok(ActionResponse::QueryValidate(
+            marshal::responses::QueryValidate::invalid(errors),
+        ));
+    }
+
+    let schemas = topic_schemas(ctx, validation.topics).await?;
+
+    Ok(ActionResponse::QueryValidate(
+        marshal::responses::QueryValidate {
+            valid: true,
+            errors: Vec::new(),
+            schemas,
+            chunk_count: Some(validation.chunk_count),
+        },
+    ))
+}
+
+/// Returns the last `n` rows of the topic `locator` by timestamp.
+///
+/// Only the chunks holding the most recent timestamps are read (see [`FacadeTopic::tail`]),
+/// if the topic has less than `n` rows all of them are returned.
+pub async fn tail(
+    ctx: &ActionContext,
+    locator: String,
+    n: usize,
+) -> Result<ActionResponse, ActionError> {
+    info!("requested last {} rows of {}", n, locator);
+
+    let handle = FacadeTopic::new(locator, ctx.store.clone(), ctx.repo.clone());
+    let batch = handle
+        .tail(n, params::configurables().max_concurrent_chunk_queries)
+        .await?;
+
+    trace!("rows found: {}", batch.as_ref().map_or(0, |b| b.num_rows()));
+
+    Ok(ActionResponse::QueryTail(
+        marshal::responses::QueryTail::try_from_batch(batch)?,
+    ))
+}
+
+/// Returns the row of the topic `locator` at `timestamp` (in nanoseconds), selected following
+/// `mode`.
+///
+/// Only the chunks and row groups whose timestamp statistics allow them to hold the row are
+/// read (see [`FacadeTopic::at`]).
+pub async fn at(
+    ctx: &ActionContext,
+    locator: String,
+    timestamp: i64,
+    mode: types::LookupMode,
+) -> Result<ActionResponse, ActionError> {
+    info!("requested row of {} at {} ({:?})", locator, timestamp, mode);
+
+    let handle = FacadeTopic::new(locator, ctx.store.clone(), ctx.repo.clone());
+    let batch = handle.at(timestamp, mode).await?;
+
+    trace!("row found: {}", batch.is_some());
+
+    Ok(ActionResponse::QueryAt(
+        marshal::responses::QueryAt::try_from_batch(batch)?,
+    ))
+}
+
+/// Returns the statistics of each column of the topic `locator`, optionally restricted to the
+/// `[start, end]` range of data timestamps.
+///
+/// Statistics are read from the datafiles footer whenever available, the data is scanned
+/// only to complete them (see [`FacadeTopic::column_stats`]).
+pub async fn column_stats(
+    ctx: &ActionContext,
+    locator: String,
+    range: Option<[i64; 2]>,
+) -> Result<ActionResponse, ActionError> {
+    info!("requested column statistics of {}", locator);
+
+    let range = match range {
+        Some([start, end]) if start > end => {
+            return Err(ActionError::InvalidArgument(format!(
+                "empty range [{}, {}]",
+                start, end
+            )));
+        }
+        Some([start, end]) => Some(types::TimestampRange::new(start.into(), end.into())),
+        None => None,
+    };
+
+    let handle = FacadeTopic::new(locator, ctx.store.clone(), ctx.repo.clone());
+    let columns = handle
+        .column_stats(range.as_ref(), ctx.ts_gw.clone())
+        .await?;
+
+    trace!("columns profiled: {}", columns.len());
+
+    Ok(ActionResponse::QueryColumnStats(columns.into()))
+}
+
+/// Returns the intervals of the `[start, end]` range of data timestamps of the topic `locator`
+/// where consecutive samples are more than `expected_interval` apart (e.g. sensor outages).
+///
+/// The interval is rounded up to the time precision of the topic. Returns an
+/// [`ActionError::InvalidArgument`] if the range is inverted or the interval is shorter than
+/// one unit of the topic precision.
+pub async fn gaps(
+    ctx: &ActionContext,
+    locator: String,
+    range: [i64; 2],
+    expected_interval: i64,
+) -> Result<ActionResponse, ActionError> {
+    info!("requested gaps of {}", locator);
+
+    let [start, end] = range;
+    if start > end {
+        return Err(ActionError::InvalidArgument(format!(
+            "empty range [{}, {}]",
+            start, end
+        )));
+    }
+    if expected_interval <= 0 {
+        return Err(ActionError::InvalidArgument(
+            "expected interval must be positive".to_owned(),
+        ));
+    }
+
+    let range = types::TimestampRange::new(start.into(), end.into());
+
+    let handle = FacadeTopic::new(locator, ctx.store.clone(), ctx.repo.clone());
+    let gaps = handle
+        .gaps(&range, expected_interval, ctx.ts_gw.clone())
+        .await?;
+
+    trace!("gaps found: {}", gaps.len());
+
+    Ok(ActionResponse::QueryGaps(gaps.into()))
+}
+
+/// Returns up to `limit` distinct values of `column` of the topic `locator`, optionally
+/// restricted to the `[start, end]` range of data timestamps.
+///
+/// Dictionary encoded columns are resolved from their dictionaries, other columns are scanned
+/// until the limit is exceeded (see [`FacadeTopic::distinct`]). Returns an
+/// [`ActionError::InvalidArgument`] if the range is empty or the column does not exist.
+pub async fn distinct(
+    ctx: &ActionContext,
+    locator: String,
+    column: String,
+    range: Option<[i64; 2]>,
+    limit: usize,
+) -> Result<ActionResponse, ActionError> {
+    info!("requested distinct values of `{}` in {}", column, locator);
+
+    let range = match range {
+        Some([start, end]) if start > end => {
+            return Err(ActionError::InvalidArgument(format!(
+                "empty range [{}, {}]",
+                start, end
+            )));
+        }
+        Some([start, end]) => Some(types::TimestampRange::new(start.into(), end.into())),
+        None => None,
+    };
+
+    let handle = FacadeTopic::new(locator, ctx.store.clone(), ctx.repo.clone());
+    let distinct = handle
+        .distinct(&column, range.as_ref(), limit, ctx.ts_gw.clone())
+        .await?;
+
+    trace!("distinct values found: {}", distinct.0.len());
+
+    Ok(ActionResponse::QueryDistinct(distinct.into()))
+}
+
+/// Computes `aggregates` over the columns of the topic `locator`, optionally restricted to the
+/// `[start, end]` range of data timestamps.
+///
+/// Simple aggregations are computed in process, the others by the query engine, unless an
+/// `engine` is forced (see [`FacadeTopic::aggregate`]). Returns an
+/// [`ActionError::InvalidArgument`] if the range is empty, if a column does not exist or if
+/// the aggregations are not supported by the requested engine.
+pub async fn aggregate(
+    ctx: &ActionContext,
+    locator: String,
+    aggregates: Vec<query::Aggregate>,
+    range: Option<[i64; 2]>,
+    engine: query::QueryEngine,
+) -> Result<ActionResponse, ActionError> {
+    info!("requested {} aggregations of {}", aggregates.len(), locator);
+
+    let range = match range {
+        Some([start, end]) if start > end => {
+            return Err(ActionError::InvalidArgument(format!(
+                "empty range [{}, {}]",
+                start, end
+            )));
+        }
+        Some([start, end]) => Some(types::TimestampRange::new(start.into(), end.into())),
+        None => None,
+    };
+
+    let handle = FacadeTopic::new(locator, ctx.store.clone(), ctx.repo.clone());
+    let aggregated = handle
+        .aggregate(aggregates, range.as_ref(), engine, ctx.ts_gw.clone())
+        .await?;
+
+    trace!("aggregations computed by the {:?} engine", aggregated.1);
+
+    Ok(ActionResponse::QueryAggregate(aggregated.into()))
+}
+
+/// Cancels the data stream of the query tagged by the request `token`, only the `caller`
+/// that requested the query can cancel it.
+///
+/// The stream fails as soon as the cancellation is received, releasing the resources held by
+/// the query. Returns an [`ActionError::NotFound`] if no running query of the caller is tagged
+/// by the token, e.g. because it has already completed.
+pub async fn cancel(
+    ctx: &ActionContext,
+    caller: &Caller,
+    token: String,
+) -> Result<ActionResponse, ActionError> {
+    info!("requested cancellation of query `{}`", token);
+
+    if !ctx
+        .ts_gw
+        .running()
+        .cancel(&token, caller.identity.as_deref())
+    {
+        return Err(ActionError::NotFound(format!(
+            "no running query with request token `{}`",
+            token
+        )));
+    }
+
+    Ok(ActionResponse::Empty)
+}
@@ -2,36 +2,54 @@
 
 use log::{info, trace, warn};
 
-use super::ActionContext;
+use super::{ActionContext, ActionError};
 use crate::{
     marshal::{self, ActionResponse},
-    repo::{FacadeError, FacadeSequence},
-    server::errors::ServerError,
-    types::{self, MetadataBlob, Resource},
+    repo::{FacadeError, FacadeSequence, FacadeTopic},
+    types::{self, Resource},
 };
 
 /// Creates a new sequence with the given name and metadata.
+///
+/// The `time_column` and `time_precision`, if provided, are inherited by the topics of the
+/// sequence not declaring their own.
 pub async fn create(
     ctx: &ActionContext,
     name: String,
+    time_column: Option<String>,
+    time_precision: Option<types::TimePrecision>,
     user_metadata_str: &str,
-) -> Result<ActionResponse, ServerError> {
+) -> Result<ActionResponse, ActionError> {
     info!("requested resource {} creation", name);
 
     let handle = FacadeSequence::new(name, ctx.store.clone(), ctx.repo.clone());
+    types::check_name(handle.locator.name())?;
 
     // Check if sequence exists, if so return with an error
     if handle.resource_id().await.is_ok() {
-        return Err(ServerError::SequenceAlreadyExists(
-            handle.locator.name().into(),
-        ));
+        return Err(ActionError::AlreadyExists(handle.locator.name().into()));
     }
 
-    let user_mdata =
-        marshal::JsonMetadataBlob::try_from_str(user_metadata_str).map_err(FacadeError::from)?;
+    // Sequences and topics share the same name space
+    let topic = FacadeTopic::new(
+        handle.locator.name().clone(),
+        ctx.store.clone(),
+        ctx.repo.clone(),
+    );
+    if topic.resource_id().await.is_ok() {
+        return Err(ActionError::AlreadyExists(format!(
+            "{} (used by a topic)",
+            handle.locator.name()
+        )));
+    }
+
+    let user_mdata = super::user_metadata_blob(user_metadata_str)?;
+    let time_column = super::check_time_column(time_column)?;
 
     // No sequence record was found, let's write it
-    let metadata = types::SequenceMetadata::new(user_mdata);
+    let metadata = types::SequenceMetadata::new(user_mdata)
+        .with_time_column(time_column)
+        .with_time_precision(time_precision);
     let r_id = handle.create(Some(metadata)).await?;
 
     trace!(
@@ -42,13 +60,13 @@ pub async fn create(
 }
 
 /// Deletes an unlocked sequence.
-pub async fn delete(ctx: &ActionContext, name: String) -> Result<ActionResponse, ServerError> {
+pub async fn delete(ctx: &ActionContext, name: String) -> Result<ActionResponse, ActionError> {
     warn!("requested deletion of resource {}", name);
 
     let handle = FacadeSequence::new(name, ctx.store.clone(), ctx.repo.clone());
 
     if handle.is_locked().await? {
-        return Err(ServerError::SequenceLocked);
+        return Err(ActionError::ResourceLocked(handle.locator.name().into()));
     }
 
     let loc = handle.locator.clone();
@@ -63,21 +81,21 @@ pub async fn abort(
     ctx: &ActionContext,
     name: String,
     key: String,
-) -> Result<ActionResponse, ServerError> {
+) -> Result<ActionResponse, ActionError> {
     warn!("abort for {}", name);
 
     let handle = FacadeSequence::new(name, ctx.store.clone(), ctx.repo.clone());
 
     // Avoid aborting on locked sequences
     if handle.is_locked().await? {
-        return Err(ServerError::SequenceLocked);
+        return Err(ActionError::ResourceLocked(handle.locator.name().into()));
     }
 
     // Check that sequence id and provided key matches
     let r_id = handle.resource_id().await?;
     let received_uuid: uuid::Uuid = key.parse()?;
     if r_id.uuid != received_uuid {
-        return Err(ServerError::BadKey);
+        return Err(ActionError::InvalidArgument("bad key".to_owned()));
     }
 
     // Save handle name (for logging) since the delete will consume the handle
@@ -89,11 +107,16 @@ pub async fn abort(
 }
 
 /// Finalizes and locks a sequence.
+///
+/// The finalization is idempotent: the check of the topics and the lock of the sequence are
+/// committed atomically, so an interrupted finalization leaves the sequence untouched and can
+/// be re-run, while finalizing an already locked sequence (e.g. a retry after a lost response)
+/// succeeds without changes.
 pub async fn finalize(
     ctx: &ActionContext,
     name: String,
     key: String,
-) -> Result<ActionResponse, ServerError> {
+) -> Result<ActionResponse, ActionError> {
     info!("resource {} finalized", name);
 
     let handle = FacadeSequence::new(name, ctx.store.clone(), ctx.repo.clone());
@@ -103,10 +126,35 @@ pub async fn finalize(
     let received_uuid: uuid::Uuid = key.parse()?;
 
     if r_id.uuid != received_uuid {
-        return Err(ServerError::BadKey);
+        return Err(ActionError::InvalidArgument("bad key".to_owned()));
     }
 
-    handle.lock().await?;
+    if handle.is_locked().await? {
+        trace!("resource {} already finalized", handle.locator);
+        return Ok(ActionResponse::Empty);
+    }
+
+    // Data buffered by small uploads is written before locking the topics
+    ctx.upload_coalescer
+        .flush(
+            handle.locator.name(),
+            ctx.store.clone(),
+            ctx.repo.clone(),
+            &ctx.deferred_locks,
+        )
+        .await
+        .map_err(|e| ActionError::Internal(e.to_string()))?;
+
+    // Topics waiting for the grace period after their upload are locked right away
+    ctx.deferred_locks
+        .flush(handle.locator.name(), ctx.store.clone(), ctx.repo.clone())
+        .await?;
+
+    match handle.lock().await {
+        // A concurrent finalization locked the sequence in the meantime
+        Ok(()) | Err(FacadeError::SequenceLocked) => {}
+        Err(e) => return Err(e.into()),
+    }
     trace!("resource {} locked", handle.locator);
 
     Ok(ActionResponse::Empty)
@@ -118,7 +166,7 @@ pub async fn notify_create(
     name: String,
     notify_type: String,
     msg: String,
-) -> Result<ActionResponse, ServerError> {
+) -> Result<ActionResponse, ActionError> {
     info!("new notify for {}", name);
 
     let handle = FacadeSequence::new(name, ctx.store.clone(), ctx.repo.clone());
@@ -129,7 +177,7 @@ pub async fn notify_create(
 }
 
 /// Lists all notifications for a sequence.
-pub async fn notify_list(ctx: &ActionContext, name: String) -> Result<ActionResponse, ServerError> {
+pub async fn notify_list(ctx: &ActionContext, name: String) -> Result<ActionResponse, ActionError> {
     info!("notify list for {}", name);
 
     let handle = FacadeSequence::new(name, ctx.store.clone(), ctx.repo.clone());
@@ -142,7 +190,7 @@ pub async fn notify_list(ctx: &ActionContext, name: String) -> Result<ActionResp
 pub async fn notify_purge(
     ctx: &ActionContext,
     name: String,
-) -> Result<ActionResponse, ServerError> {
+) -> Result<ActionResponse, ActionError> {
     warn!("notify purge for {}", name);
 
     let handle = FacadeSequence::new(name, ctx.store.clone(), ctx.repo.clone());
@@ -152,7 +200,7 @@ pub async fn notify_purge(
 }
 
 /// Gets system information for a sequence.
-pub async fn system_info(ctx: &ActionContext, name: String) -> Result<ActionResponse, ServerError> {
+pub async fn system_info(ctx: &ActionContext, name: String) -> Result<ActionResponse, ActionError> {
     info!("[{}] sequence system informations", name);
 
     let handle = FacadeSequence::new(name, ctx.store.clone(), ctx.repo.clone());
@@ -160,3 +208,87 @@ pub async fn system_info(ctx: &ActionContext, name: String) -> Result<ActionResp
 
     Ok(ActionResponse::SequenceSystemInfo(sysinfo.into()))
 }
+
+/// Returns the manifest of a sequence, listing its topics and datafiles.
+pub async fn manifest(ctx: &ActionContext, name: String) -> Result<ActionResponse, ActionError> {
+    info!("manifest for {}", name);
+
+    let handle = FacadeSequence::new(name, ctx.store.clone(), ctx.repo.clone());
+    let manifest = handle.manifest(false).await?;
+
+    Ok(ActionResponse::SequenceManifest(manifest.into()))
+}
+
+/// Validates the sequence named in `manifest` against the manifest, e.g. after a restore.
+pub async fn verify_manifest(
+    ctx: &ActionContext,
+    manifest: marshal::responses::SequenceManifest,
+) -> Result<ActionResponse, ActionError> {
+    let manifest = types::SequenceManifest::from(manifest);
+    info!("validating {} against its manifest", manifest.sequence);
+
+    let handle = FacadeSequence::new(
+        manifest.sequence.clone(),
+        ctx.store.clone(),
+        ctx.repo.clone(),
+    );
+    let mismatches = handle.verify_manifest(&manifest).await?;
+
+    if !mismatches.is_empty() {
+        warn!(
+            "sequence {} does not match its manifest: {:?}",
+            manifest.sequence, mismatches
+        );
+    }
+
+    Ok(ActionResponse::SequenceVerifyManifest(mismatches.into()))
+}
+
+/// Clones the sequence `source` with all its topics data and metadata to the new sequence
+/// `dest`, see [`FacadeSequence::clone_to`].
+///
+/// Locked sources can be cloned, the clone is locked as well. Returns an
+/// [`ActionError::AlreadyExists`] if `dest` is already used by a sequence or a topic.
+pub async fn clone(
+    ctx: &ActionContext,
+    source: String,
+    dest: String,
+) -> Result<ActionResponse, ActionError> {
+    info!("requested clone of resource {} to {}", source, dest);
+
+    let source = FacadeSequence::new(source, ctx.store.clone(), ctx.repo.clone());
+    let dest = FacadeSequence::new(dest, ctx.store.clone(), ctx.repo.clone());
+    types::check_name(dest.locator.name())?;
+
+    if source.resource_id().await.is_err() {
+        return Err(ActionError::NotFound(source.locator.name().into()));
+    }
+
+    // Sequences and topics share the same name space
+    let topic = FacadeTopic::new(
+        dest.locator.name().clone(),
+        ctx.store.clone(),
+        ctx.repo.clone(),
+    );
+    if topic.resource_id().await.is_ok() {
+        return Err(ActionError::AlreadyExists(format!(
+            "{} (used by a topic)",
+            dest.locator.name()
+        )));
+    }
+
+    let (r_id, topics_cloned, chunks_cloned) = source.clone_to(&dest).await?;
+
+    trace!(
+        "cloned resource {} to {} ({} topics, {} chunks)",
+        source.locator, dest.locator, topics_cloned, chunks_cloned
+    );
+
+    Ok(ActionResponse::SequenceClone(
+        marshal::responses::SequenceClone {
+            key: r_id.uuid.to_string(),
+            topics_cloned,
+            chunks_cloned,
+        },
+    ))
+}
@@ -0,0 +1,308 @@
+//! System-related action handlers.
+
+use std::{sync::Arc, time::Duration};
+
+use futures::TryStreamExt;
+use log::{info, warn};
+
+use super::{ActionContext, ActionError};
+use crate::{
+    marshal::{self, ActionResponse, responses},
+    params,
+    repo::{self, FacadeSequence, FacadeTopic},
+    rw, store,
+    types::{self, Resource},
+};
+
+/// Optional features compiled in the server, always advertised to the clients.
+const FEATURES: &[&str] = &[
+    "topic_repair",
+    "schema_evolution",
+    "upload_deduplication",
+    "upload_overwrite",
+    "line_protocol",
+    "topic_leases",
+    "query_cancel",
+    "query_aggregate",
+];
+
+/// Returns the optional features enabled on the server, the compiled-in features along with
+/// the ones enabled by the configuration `params`.
+pub fn features(params: &params::ConfigurablesParams) -> Vec<String> {
+    let configured = [
+        ("datafusion_engine", cfg!(feature = "datafusion-engine")),
+        ("write_ahead_log", params.wal_dir.is_some()),
+        ("upload_coalescing", params.upload_coalesce_bytes.is_some()),
+        ("deferred_lock", params.upload_lock_grace_secs.is_some()),
+        ("chunk_compaction", params.max_chunks_per_topic.is_some()),
+        ("cold_tiering", params.cold_storage_class.is_some()),
+        ("ontology_validation", params.ontology_registry.is_some()),
+        (
+            "periodic_vacuum",
+            params.vacuum_interval_secs.is_some_and(|secs| secs > 0),
+        ),
+    ];
+
+    FEATURES
+        .iter()
+        .copied()
+        .chain(
+            configured
+                .into_iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(feature, _)| feature),
+        )
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Returns the server version and capabilities.
+///
+/// The response depends on the configuration only, it does not access neither the store nor
+/// the repository.
+pub async fn info(_ctx: &ActionContext) -> Result<ActionResponse, ActionError> {
+    info!("request system info");
+
+    Ok(ActionResponse::SystemInfo(responses::SystemInfo {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        formats: rw::Format::ALL.to_vec(),
+        max_message_size_in_bytes: params::configurables().max_message_size_in_bytes,
+        features: features(params::configurables()),
+    }))
+}
+
+/// Returns the JSON schema of the body expected by the action `name`.
+pub async fn describe_action(
+    _ctx: &ActionContext,
+    name: String,
+) -> Result<ActionResponse, ActionError> {
+    info!("request description of action `{}`", name);
+
+    let schema = marshal::schemas::describe(&name)
+        .ok_or_else(|| ActionError::NotFound(format!("action `{}`", name)))?;
+
+    Ok(ActionResponse::DescribeAction(responses::DescribeAction {
+        name,
+        schema,
+    }))
+}
+
+/// Deletes all the sequences and topics whose name starts with `prefix`.
+///
+/// A matching sequence is deleted along with all its topics. If any of the matching resources
+/// is locked nothing is deleted and an error is returned, unless `force` is set. Since an empty
+/// prefix matches every resource in the system it is accepted only if `allow_empty_prefix` is set.
+///
+/// When `dry_run` is set nothing is deleted and the list of the matching resources is returned.
+pub async fn delete_prefix(
+    ctx: &ActionContext,
+    prefix: String,
+    dry_run: bool,
+    force: bool,
+    allow_empty_prefix: bool,
+) -> Result<ActionResponse, ActionError> {
+    warn!(
+        "requested deletion of resources matching prefix `{}`",
+        prefix
+    );
+
+    if prefix.is_empty() && !allow_empty_prefix {
+        return Err(ActionError::InvalidArgument(
+            "empty prefix matches every resource, explicit opt-in is required".to_owned(),
+        ));
+    }
+
+    let mut deleted = Vec::new();
+    let mut sequences = Vec::new();
+    let mut topics = Vec::new();
+
+    // Collect matching resources and check their lock state before deleting anything
+    for loc in FacadeSequence::all(ctx.repo.clone()).await? {
+        let handle = FacadeSequence::new(loc.into(), ctx.store.clone(), ctx.repo.clone());
+        let topic_locs = handle.topic_list().await?;
+
+        if handle.locator.name().starts_with(&prefix) {
+            let locked = handle.is_locked().await?;
+            if locked && !force {
+                return Err(ActionError::ResourceLocked(handle.locator.name().into()));
+            }
+
+            deleted.push(handle.locator.name().to_owned());
+            deleted.extend(topic_locs.iter().map(|t| t.name().to_owned()));
+            sequences.push((handle, locked));
+            continue;
+        }
+
+        for topic_loc in topic_locs {
+            if !topic_loc.name().starts_with(&prefix) {
+                continue;
+            }
+
+            let thandle = FacadeTopic::new(topic_loc.into(), ctx.store.clone(), ctx.repo.clone());
+            let locked = thandle.is_locked().await?;
+            if locked && !force {
+                return Err(ActionError::ResourceLocked(thandle.path().into()));
+            }
+
+            deleted.push(thandle.path().to_owned());
+            topics.push((thandle, locked));
+        }
+    }
+
+    if !dry_run {
+        for (handle, locked) in sequences {
+            if locked {
+                // unsafe allowed since the deletion of locked resources was explicitly forced
+                unsafe {
+                    handle.delete_unsafe().await?;
+                }
+            } else {
+                handle.delete().await?;
+            }
+        }
+
+        for (thandle, locked) in topics {
+            if locked {
+                // unsafe allowed since the deletion of locked resources was explicitly forced
+                unsafe {
+                    thandle.delete_unsafe().await?;
+                }
+            } else {
+                thandle.delete().await?;
+            }
+        }
+
+        warn!(
+            "deleted {} resources matching prefix `{}`",
+            deleted.len(),
+            prefix
+        );
+    }
+
+    Ok(ActionResponse::DeletePrefix(responses::DeletePrefix {
+        deleted,
+        dry_run,
+    }))
+}
+
+/// Builds the store located at `url`, the target of a [`migrate`].
+///
+/// `file://<path>` targets a filesystem store, `s3://<bucket>` an S3-compatible store whose
+/// endpoint and credentials are read from the `MOSAICO_STORE_*` environment variables.
+/// Targets not matching the configured prefixes (see
+/// [`params::ConfigurablesParams::migration_allowed_targets`]) are rejected.
+pub fn migration_target(url: &str) -> Result<store::StoreRef, ActionError> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        ActionError::InvalidArgument(format!("invalid migration target `{}` :: {}", url, e))
+    };
+    let parsed = url::Url::parse(url).map_err(|e| invalid(&e))?;
+
+    // The parsed url is normalized, so that `..` segments can't escape an allowed prefix
+    let allowed = &params::configurables().migration_allowed_targets;
+    if !allowed
+        .iter()
+        .any(|prefix| parsed.as_str().starts_with(prefix.as_str()))
+    {
+        return Err(ActionError::PermissionDenied(format!(
+            "migration target `{}` not allowed",
+            parsed
+        )));
+    }
+
+    let target = match parsed.scheme() {
+        "file" => store::Store::try_from_filesystem(parsed.path()),
+        "s3" => {
+            let var = |name: &str| params::require_env_var::<String>(name).map_err(|e| invalid(&e));
+            store::Store::try_from_s3_store(store::S3Config {
+                bucket: parsed.host_str().unwrap_or_default().to_owned(),
+                endpoint: var("MOSAICO_STORE_ENDPOINT")?,
+                access_key: var("MOSAICO_STORE_ACCESS_KEY")?,
+                secret_key: params::Hidden::from(var("MOSAICO_STORE_SECRET_KEY")?),
+                key_scheme: params::configurables().store_key_scheme,
+            })
+        }
+        scheme => return Err(invalid(&format!("unsupported scheme `{}`", scheme))),
+    }
+    .map_err(|e| invalid(&e))?;
+
+    let timeout = Duration::from_secs(params::configurables().store_timeout_secs);
+
+    Ok(Arc::new(target.with_timeout(timeout)))
+}
+
+/// Copies the data and the metadata of all the sequences and topics to `target`.
+///
+/// Each object is verified after the copy, while objects already copied to `target` are
+/// skipped, so an interrupted migration can be resumed by running it again. The resources
+/// are only copied, the current store is never modified.
+pub async fn migrate(
+    ctx: &ActionContext,
+    target: store::StoreRef,
+) -> Result<ActionResponse, ActionError> {
+    warn!(
+        "requested migration of all resources to {:?}",
+        target.target()
+    );
+
+    let mut response = responses::SystemMigrate {
+        sequences_migrated: 0,
+        objects_copied: 0,
+        objects_skipped: 0,
+    };
+
+    let mut resources = std::pin::pin!(repo::iter_resources(ctx.store.clone(), ctx.repo.clone()));
+    while let Some(resource) = resources.try_next().await? {
+        let (copied, skipped) =
+            repo::migrate_resource(&ctx.store, &ctx.repo, resource.as_ref(), &target).await?;
+
+        info!(
+            "migrated `{}`, {} objects copied, {} skipped",
+            resource.name(),
+            copied,
+            skipped
+        );
+
+        if matches!(resource.resource_type(), types::ResourceType::Sequence) {
+            response.sequences_migrated += 1;
+        }
+        response.objects_copied += copied;
+        response.objects_skipped += skipped;
+    }
+
+    warn!(
+        "migration completed, {} objects copied, {} skipped",
+        response.objects_copied, response.objects_skipped
+    );
+
+    Ok(ActionResponse::SystemMigrate(response))
+}
+
+/// Removes the orphaned staging files of the store not modified for `older_than`.
+///
+/// Live data and recent staging files, which may belong to writes in progress, are never
+/// removed (see [`store::Store::vacuum`]).
+pub async fn vacuum(
+    ctx: &ActionContext,
+    older_than: Duration,
+) -> Result<ActionResponse, ActionError> {
+    info!(
+        "request vacuum of staging files older than {:?}",
+        older_than
+    );
+
+    let report = ctx
+        .store
+        .vacuum(older_than)
+        .await
+        .map_err(|e| ActionError::Internal(e.to_string()))?;
+
+    info!(
+        "vacuum completed, {} files removed, {} bytes reclaimed",
+        report.files_removed, report.bytes_reclaimed
+    );
+
+    Ok(ActionResponse::SystemVacuum(responses::SystemVacuum {
+        files_removed: report.files_removed,
+        bytes_reclaimed: report.bytes_reclaimed,
+    }))
+}
@@ -1,43 +1,230 @@
 //! Topic-related action handlers.
 
+use futures::{StreamExt, TryStreamExt};
 use log::{info, trace, warn};
 
-use super::ActionContext;
+use super::{ActionContext, ActionError};
 use crate::{
     marshal::{self, ActionResponse},
-    repo::{FacadeError, FacadeTopic},
+    params,
+    repo::{FacadeError, FacadeSequence, FacadeTopic},
     rw,
-    server::errors::ServerError,
-    types::{self, MetadataBlob, Resource},
+    types::{self, Resource},
 };
 
+/// Maximum number of names generated while looking for a free topic name
+const MAX_AUTO_NAME_ATTEMPTS: usize = 16;
+
+/// Maximum number of topics read concurrently by [`system_info_batch`]
+const SYSTEM_INFO_BATCH_CONCURRENCY: usize = 8;
+
+/// Maximum number of topics requested at once to [`system_info_batch`]
+pub const SYSTEM_INFO_BATCH_MAX_NAMES: usize = 1000;
+
 /// Creates a new topic with the given name and metadata.
+///
+/// If no `serialization_format` is provided the server default format is used. If
+/// `schema_locked` is set the schema established by the first upload can't change. If no
+/// `time_precision` is provided the precision declared by the parent sequence is used, falling
+/// back to nanoseconds. With `time_column` set the data timestamps are read from that column
+/// instead of `timestamp_ns`, the parent sequence column is used if not provided. With
+/// `chunk_naming` set to `time_range` the datafile names include the chunk time range. With
+/// `ordering` set to `time_ascending` uploads of rows not sorted by timestamp are rejected.
+/// With `page_index` set the datafiles include the Parquet page index of every column. A
+/// `compression_level` not valid for the codec of the serialization format is rejected. If a
+/// `schema` is declared, uploads must match it and it is returned by schema-only queries
+/// before any data is written.
 pub async fn create(
     ctx: &ActionContext,
-    name: String,
+    request: marshal::requests::TopicCreate,
+) -> Result<ActionResponse, ActionError> {
+    info!("requested resource {} creation", request.name);
+
+    let user_metadata = request.user_metadata()?;
+    let serialization_format = resolve_format(&request.name, request.serialization_format);
+    let (default_column, default_precision) = sequence_time_defaults(ctx, &request.name).await?;
+    let time_column = super::check_time_column(request.time_column.or(default_column))?;
+    // A declared schema is stored as the uploads, with the timestamp field in place of the
+    // time column
+    let schema = request
+        .schema
+        .map(|schema| match &time_column {
+            Some(time_column) => crate::arrow::rename_time_column(&schema.0, time_column),
+            None => Ok(schema.0),
+        })
+        .transpose()?;
+    let properties = types::TopicProperties::try_new(
+        serialization_format,
+        request.ontology_tag,
+        params::configurables().default_ontology_tag.as_deref(),
+    )?
+    .with_schema_locked(request.schema_locked)
+    .with_time_precision(
+        request
+            .time_precision
+            .or(default_precision)
+            .unwrap_or_default(),
+    )
+    .with_time_column(time_column)
+    .with_chunk_naming(request.chunk_naming)
+    .with_ordering(request.ordering)
+    .with_page_index(request.page_index)
+    .with_compression_level(request.compression_level)?
+    .with_schema(schema);
+
+    // A declared schema must be accepted by the uploads
+    if let Some(schema) = &properties.schema {
+        crate::arrow::check_schema(schema)?;
+        if let Some(registry) = crate::ontology::registry() {
+            registry.validate(&properties.ontology_tag, schema)?;
+        }
+    }
+
+    let r_id = create_topic(
+        ctx,
+        request.name,
+        request.sequence_key,
+        properties,
+        user_metadata.as_str(),
+    )
+    .await?;
+
+    Ok(ActionResponse::TopicCreate(r_id.into()))
+}
+
+/// Creates a new topic as child of `sequence_name`, the topic name is generated by `strategy`.
+///
+/// Generated names colliding with existing topics are discarded and a new name is requested
+/// to the strategy, as are the names taken by a racing creation between the check and the
+/// insertion of the topic (rejected by the database constraints).
+pub async fn create_auto(
+    ctx: &ActionContext,
+    sequence_name: String,
+    strategy: &dyn types::NameStrategy,
     sequence_key: String,
-    serialization_format: rw::Format,
+    serialization_format: Option<rw::Format>,
     ontology_tag: String,
     user_metadata_str: &str,
-) -> Result<ActionResponse, ServerError> {
-    info!("requested resource {} creation", name);
+) -> Result<ActionResponse, ActionError> {
+    info!(
+        "requested auto-named resource creation in {}",
+        sequence_name
+    );
 
-    let handle = FacadeTopic::new(name.clone(), ctx.store.clone(), ctx.repo.clone());
+    // The generated part of the names is checked on each attempt, a disallowed character in
+    // the sequence name is reported before generating any name
+    let sequence_name = sequence_name.trim_end_matches('/');
+    types::check_name(sequence_name)?;
+    let (default_column, default_precision) = sequence_time_defaults(ctx, sequence_name).await?;
+
+    for attempt in 0..MAX_AUTO_NAME_ATTEMPTS {
+        let name = format!("{}/{}", sequence_name, strategy.generate(attempt));
+
+        let properties = types::TopicProperties::try_new(
+            resolve_format(&name, serialization_format),
+            ontology_tag.clone(),
+            params::configurables().default_ontology_tag.as_deref(),
+        )?
+        .with_time_precision(default_precision.unwrap_or_default())
+        .with_time_column(default_column.clone());
+
+        let res = create_topic(
+            ctx,
+            name.clone(),
+            sequence_key.clone(),
+            properties,
+            user_metadata_str,
+        )
+        .await;
+
+        match res {
+            Ok(r_id) => {
+                return Ok(ActionResponse::TopicCreateAuto(marshal::TopicCreateAuto {
+                    name,
+                    key: r_id.uuid.to_string(),
+                }));
+            }
+            // Both a name found in use and a unique violation of a racing creation are reported
+            // as already existing
+            Err(ActionError::AlreadyExists(name)) => {
+                trace!("generated name {} already in use, retrying", name);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(ActionError::AlreadyExists(format!(
+        "unable to find a free topic name in {} after {} attempts",
+        sequence_name, MAX_AUTO_NAME_ATTEMPTS
+    )))
+}
+
+/// Returns the requested serialization format, falling back to the server default.
+fn resolve_format(name: &str, serialization_format: Option<rw::Format>) -> rw::Format {
+    serialization_format.unwrap_or_else(|| {
+        let default_format = params::configurables().default_format;
+        trace!(
+            "no serialization format for {}, using default `{}`",
+            name, default_format
+        );
+        default_format
+    })
+}
+
+/// Returns the time column and precision declared by the parent sequence of the resource
+/// `name`, inherited by the topics not declaring their own.
+async fn sequence_time_defaults(
+    ctx: &ActionContext,
+    name: &str,
+) -> Result<(Option<String>, Option<types::TimePrecision>), ActionError> {
+    let locator = types::TopicResourceLocator::from(name);
+    let sequence = FacadeSequence::new(
+        locator.sequence_name().to_owned(),
+        ctx.store.clone(),
+        ctx.repo.clone(),
+    );
+
+    match sequence.metadata().await {
+        Ok(mdata) => Ok((mdata.time_column, mdata.time_precision)),
+        // A missing parent sequence is reported by the topic creation
+        Err(FacadeError::StoreError(e)) if e.is_not_found() => Ok((None, None)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn create_topic(
+    ctx: &ActionContext,
+    name: String,
+    sequence_key: String,
+    properties: types::TopicProperties,
+    user_metadata_str: &str,
+) -> Result<types::ResourceId, ActionError> {
+    let handle = FacadeTopic::new(name, ctx.store.clone(), ctx.repo.clone());
+    types::check_name(handle.locator.name())?;
 
     // Check if the topic has already been created
     if handle.resource_id().await.is_ok() {
-        return Err(ServerError::TopicAlreadyExists(
-            handle.locator.name().into(),
-        ));
+        return Err(ActionError::AlreadyExists(handle.locator.name().into()));
     }
 
-    let user_mdata =
-        marshal::JsonMetadataBlob::try_from_str(user_metadata_str).map_err(FacadeError::from)?;
-
-    let mdata = types::TopicMetadata::new(
-        types::TopicProperties::new(serialization_format, ontology_tag),
-        user_mdata,
+    // Sequences and topics share the same name space
+    let sequence = FacadeSequence::new(
+        handle.locator.name().clone(),
+        ctx.store.clone(),
+        ctx.repo.clone(),
     );
+    if sequence.resource_id().await.is_ok() {
+        return Err(ActionError::AlreadyExists(format!(
+            "{} (used by a sequence)",
+            handle.locator.name()
+        )));
+    }
+
+    let user_mdata = super::user_metadata_blob(user_metadata_str)?;
+
+    trace!("creating resource {} with {}", handle.locator, properties);
+
+    let mdata = types::TopicMetadata::new(properties, user_mdata);
 
     let received_uuid: uuid::Uuid = sequence_key.parse()?;
     let r_id = handle.create(&received_uuid, Some(mdata)).await?;
@@ -47,17 +234,17 @@ pub async fn create(
         handle.locator, r_id.uuid,
     );
 
-    Ok(ActionResponse::TopicCreate(r_id.into()))
+    Ok(r_id)
 }
 
 /// Deletes an unlocked topic.
-pub async fn delete(ctx: &ActionContext, name: String) -> Result<ActionResponse, ServerError> {
+pub async fn delete(ctx: &ActionContext, name: String) -> Result<ActionResponse, ActionError> {
     warn!("requested deletion of resource {}", name);
 
     let handle = FacadeTopic::new(name.clone(), ctx.store.clone(), ctx.repo.clone());
 
     if handle.is_locked().await? {
-        return Err(ServerError::SequenceLocked);
+        return Err(ActionError::ResourceLocked(handle.locator.name().into()));
     }
 
     handle.delete().await?;
@@ -72,7 +259,7 @@ pub async fn notify_create(
     name: String,
     notify_type: String,
     msg: String,
-) -> Result<ActionResponse, ServerError> {
+) -> Result<ActionResponse, ActionError> {
     info!("nofity for {}", name);
 
     let handle = FacadeTopic::new(name, ctx.store.clone(), ctx.repo.clone());
@@ -82,7 +269,7 @@ pub async fn notify_create(
 }
 
 /// Lists all notifications for a topic.
-pub async fn notify_list(ctx: &ActionContext, name: String) -> Result<ActionResponse, ServerError> {
+pub async fn notify_list(ctx: &ActionContext, name: String) -> Result<ActionResponse, ActionError> {
     info!("notify list for {}", name);
 
     let handle = FacadeTopic::new(name, ctx.store.clone(), ctx.repo.clone());
@@ -95,7 +282,7 @@ pub async fn notify_list(ctx: &ActionContext, name: String) -> Result<ActionResp
 pub async fn notify_purge(
     ctx: &ActionContext,
     name: String,
-) -> Result<ActionResponse, ServerError> {
+) -> Result<ActionResponse, ActionError> {
     warn!("nofity purge for {}", name);
 
     let handle = FacadeTopic::new(name, ctx.store.clone(), ctx.repo.clone());
@@ -105,7 +292,7 @@ pub async fn notify_purge(
 }
 
 /// Gets system information for a topic.
-pub async fn system_info(ctx: &ActionContext, name: String) -> Result<ActionResponse, ServerError> {
+pub async fn system_info(ctx: &ActionContext, name: String) -> Result<ActionResponse, ActionError> {
     info!("[{}] topic system informations", name);
 
     let handle = FacadeTopic::new(name, ctx.store.clone(), ctx.repo.clone());
@@ -113,3 +300,172 @@ pub async fn system_info(ctx: &ActionContext, name: String) -> Result<ActionResp
 
     Ok(ActionResponse::TopicSystemInfo(sysinfo.into()))
 }
+
+/// Gets system information for multiple topics.
+///
+/// Topics not found are reported in the response, any other error fails the whole request.
+/// Requests for more than [`SYSTEM_INFO_BATCH_MAX_NAMES`] topics are rejected.
+pub async fn system_info_batch(
+    ctx: &ActionContext,
+    names: Vec<String>,
+) -> Result<ActionResponse, ActionError> {
+    info!("system informations of {} topics", names.len());
+
+    if names.len() > SYSTEM_INFO_BATCH_MAX_NAMES {
+        return Err(ActionError::InvalidArgument(format!(
+            "{} topics requested, at most {} topics can be requested at once",
+            names.len(),
+            SYSTEM_INFO_BATCH_MAX_NAMES
+        )));
+    }
+
+    let topics = futures::stream::iter(names)
+        .map(|name| async move {
+            let handle = FacadeTopic::new(name.clone(), ctx.store.clone(), ctx.repo.clone());
+            let info = match handle.system_info().await.map_err(ActionError::from) {
+                Ok(info) => Some(info.into()),
+                Err(ActionError::NotFound(_)) => None,
+                Err(e) => return Err(e),
+            };
+
+            Ok(marshal::TopicSystemInfoEntry {
+                name,
+                not_found: info.is_none(),
+                info,
+            })
+        })
+        .buffered(SYSTEM_INFO_BATCH_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    Ok(ActionResponse::TopicSystemInfoBatch(
+        marshal::TopicSystemInfoBatch { topics },
+    ))
+}
+
+/// Validates the last chunk of a topic, removing it if corrupted.
+///
+/// The lease of the topic is held during the repair, so that topics being written by an
+/// instance sharing the store are refused.
+pub async fn repair(ctx: &ActionContext, name: String) -> Result<ActionResponse, ActionError> {
+    warn!("requested repair of resource {}", name);
+
+    let handle = FacadeTopic::new(name, ctx.store.clone(), ctx.repo.clone());
+
+    let params = params::configurables();
+    let lease = handle
+        .acquire_lease(
+            &params.instance_id,
+            std::time::Duration::from_secs(params.upload_lease_ttl_secs),
+        )
+        .await?;
+    let removed_chunk = handle.repair().await;
+    lease.release().await?;
+    let removed_chunk = removed_chunk?;
+
+    if let Some(chunk) = &removed_chunk {
+        warn!(
+            "removed corrupted chunk `{}` from {}",
+            chunk, handle.locator
+        );
+    }
+
+    Ok(ActionResponse::TopicRepair(marshal::TopicRepair {
+        removed_chunk,
+    }))
+}
+
+/// Rewrites the chunks of a topic so that their time ranges do not overlap.
+///
+/// Up to `parallelism` chunks are read concurrently, if not provided the server
+/// `max_concurrent_chunk_queries` setting is used. The lease of the topic is held during the
+/// rewrite, so that concurrent rewrites of the topic are refused.
+pub async fn reorder(
+    ctx: &ActionContext,
+    name: String,
+    parallelism: Option<usize>,
+) -> Result<ActionResponse, ActionError> {
+    warn!("requested reorder of resource {}", name);
+
+    let parallelism = parallelism.unwrap_or(params::configurables().max_concurrent_chunk_queries);
+    if parallelism == 0 {
+        return Err(ActionError::InvalidArgument(
+            "parallelism must be greater than zero".to_owned(),
+        ));
+    }
+
+    let handle = FacadeTopic::new(name, ctx.store.clone(), ctx.repo.clone());
+
+    let params = params::configurables();
+    let lease = handle
+        .acquire_lease(
+            &params.instance_id,
+            std::time::Duration::from_secs(params.upload_lease_ttl_secs),
+        )
+        .await?;
+    let chunks_written = handle.reorder(parallelism).await;
+    lease.release().await?;
+    let chunks_written = chunks_written?;
+
+    info!(
+        "resource {} reordered in {} chunks",
+        handle.locator, chunks_written
+    );
+
+    Ok(ActionResponse::TopicReorder(marshal::TopicReorder {
+        chunks_written,
+    }))
+}
+
+/// Moves the chunks of a topic not accessed for `cold_tier_idle_secs` to the configured
+/// `cold_storage_class`, the moved chunks remain queryable.
+pub async fn tier(ctx: &ActionContext, name: String) -> Result<ActionResponse, ActionError> {
+    warn!("requested tiering of resource {}", name);
+
+    let params = params::configurables();
+    let Some(class) = &params.cold_storage_class else {
+        return Err(ActionError::InvalidArgument(
+            "no cold storage class configured".to_owned(),
+        ));
+    };
+
+    // The lease keeps uploads and rewrites of the topic from racing with the chunks moved
+    let handle = FacadeTopic::new(name, ctx.store.clone(), ctx.repo.clone());
+    let lease = handle
+        .acquire_lease(
+            &params.instance_id,
+            std::time::Duration::from_secs(params.upload_lease_ttl_secs),
+        )
+        .await?;
+    let chunks_moved = handle
+        .tier(
+            std::time::Duration::from_secs(params.cold_tier_idle_secs),
+            class,
+            ctx.clock.now(),
+        )
+        .await;
+    lease.release().await?;
+    let chunks_moved = chunks_moved?;
+
+    info!(
+        "{} chunks of resource {} moved to {}",
+        chunks_moved, handle.locator, class
+    );
+
+    Ok(ActionResponse::TopicTier(marshal::TopicTier {
+        chunks_moved,
+    }))
+}
+
+/// Compares the chunks registered for a topic against the datafiles in the store.
+pub async fn consistency_check(
+    ctx: &ActionContext,
+    name: String,
+) -> Result<ActionResponse, ActionError> {
+    info!("requested consistency check of resource {}", name);
+
+    let handle = FacadeTopic::new(name, ctx.store.clone(), ctx.repo.clone());
+    let report = handle.consistency_check().await?;
+
+    Ok(ActionResponse::TopicConsistencyCheck(report.into()))
+}
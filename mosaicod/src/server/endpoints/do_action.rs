@@ -3,14 +3,87 @@
 //! This module implements the main dispatcher for Flight DoAction requests,
 //! delegating to specialized handler functions for each action category.
 
+use std::time::Duration;
+
+use log::{info, warn};
+
 use crate::{
-    marshal::{ActionRequest, ActionResponse},
-    query, repo,
+    marshal::{ActionRequest, ActionResponse, IdempotentRequest},
+    params, query, repo,
+    server::auth::{self, Authorizer, Caller, Operation},
     server::errors::ServerError,
-    store,
+    store, types,
+};
+
+use super::actions::{
+    ActionContext, ActionError, layer, query as query_action, sequence, system, topic,
 };
 
-use super::actions::{ActionContext, layer, query as query_action, sequence, topic};
+/// Dispatches a Flight action request guarded by an optional idempotency key, returning the
+/// serialized response.
+///
+/// Actions are executed only if `authorizer` allows the `caller` to access every resource
+/// targeted by the action.
+///
+/// If a mutating action carries a key already used by a completed action, the action is not
+/// executed again and the response recorded for the first execution is returned instead. The
+/// key is reserved until the action completes, a retry received in the meantime or a key
+/// reused by a different request are rejected. A failed action releases the key.
+pub async fn do_action_idempotent(
+    ctx: ActionContext,
+    authorizer: &dyn Authorizer,
+    caller: &Caller,
+    action: ActionRequest,
+    idempotency_key: Option<IdempotentRequest>,
+) -> Result<Vec<u8>, ServerError> {
+    for (resource, access) in action.targets() {
+        auth::check(
+            authorizer,
+            caller,
+            Operation::Action(&action),
+            resource,
+            access,
+        )?;
+    }
+
+    let handle = idempotency_key.filter(|_| action.is_mutating()).map(|req| {
+        repo::FacadeIdempotencyKey::new(req.key, req.action, req.body_hash, ctx.repo.clone())
+    });
+
+    if let Some(handle) = &handle {
+        if let Some(response) = handle.reserve(ctx.clock.now()).await? {
+            info!(
+                "action already completed for idempotency key `{}`",
+                handle.key
+            );
+            return Ok(response);
+        }
+    }
+
+    let response = match dispatch(&ctx, caller, action)
+        .await
+        .and_then(|r| Ok(r.bytes()?))
+    {
+        Ok(response) => response,
+        Err(e) => {
+            if let Some(handle) = &handle
+                && let Err(release_err) = handle.release().await
+            {
+                warn!(
+                    "unable to release idempotency key `{}`: {}",
+                    handle.key, release_err
+                );
+            }
+            return Err(e.into());
+        }
+    };
+
+    if let Some(handle) = handle {
+        handle.record(&response).await?;
+    }
+
+    Ok(response)
+}
 
 /// Dispatches a Flight action request to the appropriate handler.
 ///
@@ -21,33 +94,53 @@ pub async fn do_action(
     repo: repo::Repository,
     ts_gw: query::TimeseriesGatewayRef,
     action: ActionRequest,
-) -> Result<ActionResponse, ServerError> {
-    let ctx = ActionContext::new(store, repo, ts_gw);
+) -> Result<ActionResponse, ActionError> {
+    dispatch(
+        &ActionContext::new(store, repo, ts_gw),
+        &Caller::anonymous(),
+        action,
+    )
+    .await
+}
 
+/// Routes an action request of `caller` to its handler, sharing `ctx` with the handler.
+async fn dispatch(
+    ctx: &ActionContext,
+    caller: &Caller,
+    action: ActionRequest,
+) -> Result<ActionResponse, ActionError> {
     match action {
         // Sequence actions
         ActionRequest::SequenceCreate(data) => {
             let user_metadata = data.user_metadata()?;
-            sequence::create(&ctx, data.name, user_metadata.as_str()).await
-        }
-        ActionRequest::SequenceDelete(data) => sequence::delete(&ctx, data.name).await,
-        ActionRequest::SequenceAbort(data) => sequence::abort(&ctx, data.name, data.key).await,
-        ActionRequest::SequenceFinalize(data) => {
-            sequence::finalize(&ctx, data.name, data.key).await
+            sequence::create(
+                ctx,
+                data.name,
+                data.time_column,
+                data.time_precision,
+                user_metadata.as_str(),
+            )
+            .await
         }
+        ActionRequest::SequenceDelete(data) => sequence::delete(ctx, data.name).await,
+        ActionRequest::SequenceAbort(data) => sequence::abort(ctx, data.name, data.key).await,
+        ActionRequest::SequenceFinalize(data) => sequence::finalize(ctx, data.name, data.key).await,
         ActionRequest::SequenceNotifyCreate(data) => {
-            sequence::notify_create(&ctx, data.name, data.notify_type, data.msg).await
+            sequence::notify_create(ctx, data.name, data.notify_type, data.msg).await
         }
-        ActionRequest::SequenceNotifyList(data) => sequence::notify_list(&ctx, data.name).await,
-        ActionRequest::SequenceNotifyPurge(data) => sequence::notify_purge(&ctx, data.name).await,
-        ActionRequest::SequenceSystemInfo(data) => sequence::system_info(&ctx, data.name).await,
+        ActionRequest::SequenceNotifyList(data) => sequence::notify_list(ctx, data.name).await,
+        ActionRequest::SequenceNotifyPurge(data) => sequence::notify_purge(ctx, data.name).await,
+        ActionRequest::SequenceSystemInfo(data) => sequence::system_info(ctx, data.name).await,
 
         // Topic actions
-        ActionRequest::TopicCreate(data) => {
+        ActionRequest::TopicCreate(data) => topic::create(ctx, data).await,
+        ActionRequest::TopicCreateAuto(data) => {
             let user_metadata = data.user_metadata()?;
-            topic::create(
-                &ctx,
-                data.name,
+            let strategy: Box<dyn types::NameStrategy> = data.name_strategy.into();
+            topic::create_auto(
+                ctx,
+                data.sequence_name,
+                strategy.as_ref(),
                 data.sequence_key,
                 data.serialization_format,
                 data.ontology_tag,
@@ -55,24 +148,83 @@ pub async fn do_action(
             )
             .await
         }
-        ActionRequest::TopicDelete(data) => topic::delete(&ctx, data.name).await,
+        ActionRequest::TopicDelete(data) => topic::delete(ctx, data.name).await,
         ActionRequest::TopicNotifyCreate(data) => {
-            topic::notify_create(&ctx, data.name, data.notify_type, data.msg).await
+            topic::notify_create(ctx, data.name, data.notify_type, data.msg).await
+        }
+        ActionRequest::TopicNotifyList(data) => topic::notify_list(ctx, data.name).await,
+        ActionRequest::TopicNotifyPurge(data) => topic::notify_purge(ctx, data.name).await,
+        ActionRequest::TopicSystemInfo(data) => topic::system_info(ctx, data.name).await,
+        ActionRequest::TopicSystemInfoBatch(data) => {
+            topic::system_info_batch(ctx, data.names).await
+        }
+        ActionRequest::TopicRepair(data) => topic::repair(ctx, data.name).await,
+        ActionRequest::TopicReorder(data) => topic::reorder(ctx, data.name, data.parallelism).await,
+        ActionRequest::TopicTier(data) => topic::tier(ctx, data.name).await,
+        ActionRequest::SequenceManifest(data) => sequence::manifest(ctx, data.name).await,
+        ActionRequest::SequenceVerifyManifest(data) => {
+            sequence::verify_manifest(ctx, data.manifest).await
+        }
+        ActionRequest::SequenceClone(data) => sequence::clone(ctx, data.source, data.dest).await,
+        ActionRequest::TopicConsistencyCheck(data) => {
+            topic::consistency_check(ctx, data.name).await
         }
-        ActionRequest::TopicNotifyList(data) => topic::notify_list(&ctx, data.name).await,
-        ActionRequest::TopicNotifyPurge(data) => topic::notify_purge(&ctx, data.name).await,
-        ActionRequest::TopicSystemInfo(data) => topic::system_info(&ctx, data.name).await,
 
         // Layer actions
-        ActionRequest::LayerCreate(data) => layer::create(&ctx, data.name, data.description).await,
-        ActionRequest::LayerDelete(data) => layer::delete(&ctx, data.name).await,
+        ActionRequest::LayerCreate(data) => layer::create(ctx, data.name, data.description).await,
+        ActionRequest::LayerDelete(data) => layer::delete(ctx, data.name).await,
         ActionRequest::LayerUpdate(data) => {
-            layer::update(&ctx, data.prev_name, data.curr_name, data.curr_description).await
+            layer::update(ctx, data.prev_name, data.curr_name, data.curr_description).await
         }
-        ActionRequest::LayerList(_) => layer::list(&ctx).await,
+        ActionRequest::LayerList(_) => layer::list(ctx).await,
 
         // Query actions
-        ActionRequest::Query(data) => query_action::execute(&ctx, data.query).await,
+        ActionRequest::Query(data) => {
+            query_action::execute(ctx, data.query, data.count_only, data.schema_only).await
+        }
+        ActionRequest::QueryValidate(data) => query_action::validate(ctx, data.query).await,
+        ActionRequest::QueryTail(data) => query_action::tail(ctx, data.locator, data.n).await,
+        ActionRequest::QueryAt(data) => {
+            query_action::at(ctx, data.locator, data.timestamp, data.mode).await
+        }
+        ActionRequest::QueryColumnStats(data) => {
+            query_action::column_stats(ctx, data.locator, data.range).await
+        }
+        ActionRequest::QueryGaps(data) => {
+            query_action::gaps(ctx, data.locator, data.range, data.expected_interval).await
+        }
+        ActionRequest::QueryDistinct(data) => {
+            query_action::distinct(ctx, data.locator, data.column, data.range, data.limit).await
+        }
+        ActionRequest::QueryAggregate(data) => {
+            let engine = data.engine.unwrap_or(params::configurables().query_engine);
+            query_action::aggregate(ctx, data.locator, data.aggregates, data.range, engine).await
+        }
+        ActionRequest::QueryCancel(data) => query_action::cancel(ctx, caller, data.token).await,
+
+        // System actions
+        ActionRequest::SystemInfo(_) => system::info(ctx).await,
+        ActionRequest::DeletePrefix(data) => {
+            system::delete_prefix(
+                ctx,
+                data.prefix,
+                data.dry_run,
+                data.force,
+                data.allow_empty_prefix,
+            )
+            .await
+        }
+        ActionRequest::SystemMigrate(data) => {
+            let target = system::migration_target(&data.target)?;
+            system::migrate(ctx, target).await
+        }
+        ActionRequest::SystemVacuum(data) => {
+            let older_than = data
+                .older_than_secs
+                .unwrap_or(params::configurables().vacuum_age_secs);
+            system::vacuum(ctx, Duration::from_secs(older_than)).await
+        }
+        ActionRequest::DescribeAction(data) => system::describe_action(ctx, data.name).await,
     }
 }
 
@@ -83,58 +235,20 @@ mod tests {
     use super::*;
 
     use crate::{
-        marshal, repo, repo::FacadeSequence, repo::FacadeTopic, rw, types, types::MetadataBlob,
+        marshal, repo,
+        repo::FacadeSequence,
+        repo::FacadeTopic,
+        rw,
+        server::auth,
+        server::testing::{
+            create_empty_sequence, create_empty_topic, create_topic_with, write_chunk,
+            write_dummy_chunk,
+        },
+        types,
+        types::{MetadataBlob, Resource},
+        utils,
     };
 
-    /// Creates an empty sequence (no data) for testing purposes.
-    async fn create_empty_sequence(
-        repo: &repo::testing::Repository,
-        store: &store::testing::Store,
-        name: &str,
-    ) -> Result<types::ResourceId, repo::FacadeError> {
-        let handle = FacadeSequence::new(name.to_owned(), (*store).clone(), (*repo).clone());
-
-        let metadata = types::SequenceMetadata::new(
-            marshal::JsonMetadataBlob::try_from_str(
-                r#"{
-                    "test_field_1" : "value1",
-                    "test_field_2" : "value2"
-                }"#,
-            )
-            .expect("Error parsing user metadata"),
-        );
-
-        let record = handle.create(Some(metadata)).await?;
-
-        Ok(record)
-    }
-
-    /// Creates an empty topic (no data) for testing purposes.
-    async fn create_empty_topic(
-        repo: &repo::testing::Repository,
-        store: &store::testing::Store,
-        sequence: &types::ResourceId,
-        name: &str,
-    ) -> Result<types::ResourceId, repo::FacadeError> {
-        let handle = FacadeTopic::new(name.to_owned(), (*store).clone(), (*repo).clone());
-        let props = types::TopicProperties::new(rw::Format::Default, "test_tag".to_owned());
-
-        let metadata = types::TopicMetadata::new(
-            props,
-            marshal::JsonMetadataBlob::try_from_str(
-                r#"{
-                    "test_field_1" : "test_value_1",
-                    "test_field_2" : "test_value_2"
-                }"#,
-            )
-            .expect("Error parsing user metadata json string"),
-        );
-
-        let record = handle.create(&sequence.uuid, Some(metadata)).await?;
-
-        Ok(record)
-    }
-
     #[sqlx::test]
     /// This test checks the creation against the repository and compares values to check if
     /// the creation was successful.
@@ -186,6 +300,176 @@ mod tests {
         Ok(())
     }
 
+    #[sqlx::test]
+    /// Test checking that a create action retried with the same idempotency key
+    /// is executed only once and returns the original response.
+    async fn sequence_create_idempotent(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+
+        let body = r#"{
+            "name": "test_sequence",
+            "user_metadata": {},
+            "idempotency_key": "retry-key"
+        }"#;
+
+        let mut responses = Vec::new();
+        for _ in 0..2 {
+            let action = ActionRequest::try_new("sequence_create", body.as_bytes()).unwrap();
+            let key = marshal::idempotency_key("sequence_create", body.as_bytes()).unwrap();
+
+            // Without the key the second creation would fail since the sequence already exists
+            let response = do_action_idempotent(
+                ActionContext::new((*store).clone(), repo.clone(), ts_engine.clone()),
+                &auth::AllowAll,
+                &auth::Caller::anonymous(),
+                action,
+                key,
+            )
+            .await
+            .unwrap();
+            responses.push(response);
+        }
+
+        assert_eq!(responses[0], responses[1]);
+
+        let sequences = repo::sequence_find_all(&mut repo.connection())
+            .await
+            .unwrap();
+        assert_eq!(sequences.len(), 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that an idempotency key is rejected while its action is in progress or
+    /// when reused by a different request, and released when its action fails.
+    async fn idempotency_key_conflicts(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+
+        let caller = auth::Caller::anonymous();
+        let run = |name: &str, key: &str| {
+            let body = format!(
+                r#"{{"name": "{name}", "user_metadata": {{}}, "idempotency_key": "{key}"}}"#
+            );
+            let action = ActionRequest::try_new("sequence_create", body.as_bytes()).unwrap();
+            let key = marshal::idempotency_key("sequence_create", body.as_bytes()).unwrap();
+            let ctx = ActionContext::new((*store).clone(), repo.clone(), ts_engine.clone());
+
+            do_action_idempotent(ctx, &auth::AllowAll, &caller, action, key)
+        };
+
+        // The key of a completed action can't be used by a different request
+        run("first_sequence", "reused-key").await.unwrap();
+        assert!(matches!(
+            run("second_sequence", "reused-key").await,
+            Err(ServerError::ActionFailed(ActionError::InvalidArgument(_)))
+        ));
+
+        // Reserves a key as an action still in progress
+        let reserve = |name: &str, key: &str, now: types::Timestamp| {
+            let body = format!(
+                r#"{{"name": "{name}", "user_metadata": {{}}, "idempotency_key": "{key}"}}"#
+            );
+            let request = marshal::idempotency_key("sequence_create", body.as_bytes())
+                .unwrap()
+                .unwrap();
+            let pending = repo::FacadeIdempotencyKey::new(
+                request.key,
+                request.action,
+                request.body_hash,
+                (*repo).clone(),
+            );
+            async move { pending.reserve(now).await.unwrap() }
+        };
+
+        // A retry is rejected while the action holding the key is in progress
+        assert!(
+            reserve("pending_sequence", "pending-key", types::Timestamp::now())
+                .await
+                .is_none()
+        );
+        assert!(matches!(
+            run("pending_sequence", "pending-key").await,
+            Err(ServerError::ActionFailed(ActionError::ResourceLocked(_)))
+        ));
+
+        // The reservation of an action which never completed (e.g. crashed) expires after the
+        // lease time-to-live
+        let lease_ttl_ms = crate::params::configurables().upload_lease_ttl_secs as i64 * 1000;
+        let stale = i64::from(types::Timestamp::now()) - lease_ttl_ms - 1000;
+        assert!(
+            reserve("stale_sequence", "stale-key", stale.into())
+                .await
+                .is_none()
+        );
+        run("stale_sequence", "stale-key").await.unwrap();
+
+        // A failed action releases its key, the retry is executed again
+        assert!(matches!(
+            run("first_sequence", "failing-key").await,
+            Err(ServerError::ActionFailed(ActionError::AlreadyExists(_)))
+        ));
+        assert!(matches!(
+            run("first_sequence", "failing-key").await,
+            Err(ServerError::ActionFailed(ActionError::AlreadyExists(_)))
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that an idempotency key is no longer honored once its time-to-live
+    /// has elapsed on the context clock.
+    async fn idempotency_key_expiry(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+        let clock = Arc::new(types::MockClock::new(types::Timestamp::from(1_000_000)));
+
+        let body = r#"{
+            "name": "test_sequence",
+            "user_metadata": {},
+            "idempotency_key": "expiring-key"
+        }"#;
+
+        let caller = auth::Caller::anonymous();
+        let retry = || {
+            let action = ActionRequest::try_new("sequence_create", body.as_bytes()).unwrap();
+            let key = marshal::idempotency_key("sequence_create", body.as_bytes()).unwrap();
+            let ctx = ActionContext::new((*store).clone(), repo.clone(), ts_engine.clone())
+                .with_clock(clock.clone());
+
+            do_action_idempotent(ctx, &auth::AllowAll, &caller, action, key)
+        };
+
+        retry().await.unwrap();
+
+        // Within the time-to-live the recorded response is returned
+        clock.advance(std::time::Duration::from_secs(
+            crate::params::configurables().idempotency_key_ttl_secs,
+        ));
+        retry().await.unwrap();
+
+        // Once expired the action is executed again, failing on the existing sequence
+        clock.advance(std::time::Duration::from_secs(1));
+        assert!(matches!(
+            retry().await,
+            Err(ServerError::ActionFailed(ActionError::AlreadyExists(_)))
+        ));
+
+        Ok(())
+    }
+
     #[sqlx::test]
     /// Test checking if the creation of an already existing sequence fails.
     async fn sequence_create_existing(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
@@ -205,6 +489,251 @@ mod tests {
         Ok(())
     }
 
+    #[sqlx::test]
+    /// Test checking that only one of two racing creations of the same sequence succeeds.
+    async fn sequence_create_race(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let (first, second) = tokio::join!(
+            create_empty_sequence(&repo, &store, "test_sequence"),
+            create_empty_sequence(&repo, &store, "test_sequence"),
+        );
+
+        let (created, rejected): (Vec<_>, Vec<_>) =
+            [first, second].into_iter().partition(Result::is_ok);
+        assert_eq!(created.len(), 1);
+        assert_eq!(rejected.len(), 1);
+
+        let err = rejected.into_iter().next().unwrap().unwrap_err();
+        assert!(matches!(
+            ActionError::from(err),
+            ActionError::AlreadyExists(_)
+        ));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that a creation never overwrites a metadata file already in the store,
+    /// e.g. left behind by a resource not tracked by the repository.
+    async fn sequence_create_existing_metadata(
+        pool: sqlx::Pool<repo::Database>,
+    ) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let path = repo.metadata_path(&types::SequenceResourceLocator::from("test_sequence"));
+        store.write_bytes(&path, b"{}".to_vec()).await.unwrap();
+
+        let err = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, repo::FacadeError::AlreadyExists(_)));
+
+        // The creation is rolled back and the existing file is untouched
+        assert!(
+            repo::sequence_find_all(&mut repo.connection())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(store.read_bytes(&path).await.unwrap(), b"{}".to_vec());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that sequences with user metadata larger than the configured limit are
+    /// rejected, while metadata within the limit are accepted.
+    async fn sequence_create_metadata_limit(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+        let limit = crate::params::configurables().max_user_metadata_bytes;
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let create = |name: &str, blob_size: usize| {
+            let body = serde_json::json!({
+                "name": name,
+                "user_metadata": { "blob": "x".repeat(blob_size) },
+            });
+            let action =
+                ActionRequest::try_new("sequence_create", body.to_string().as_bytes()).unwrap();
+            let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+            do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+        };
+
+        // Under the limit
+        create("small_sequence", 1024).await.unwrap();
+
+        // Over the limit
+        let err = create("large_sequence", limit).await.unwrap_err();
+        assert!(matches!(err, ActionError::InvalidArgument(_)));
+
+        let handle =
+            repo::FacadeSequence::new("large_sequence".to_owned(), (*store).clone(), repo.clone());
+        assert!(handle.resource_id().await.is_err());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that a sequence can't be created with the name of an existing topic.
+    async fn sequence_create_topic_name(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        create_empty_topic(&repo, &store, &sequence, "test_sequence/test_topic")
+            .await
+            .unwrap();
+
+        let action = ActionRequest::try_new(
+            "sequence_create",
+            br#"{"name": "test_sequence/test_topic", "user_metadata": {}}"#,
+        )
+        .unwrap();
+
+        let err = do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+            .await
+            .unwrap_err();
+        assert!(matches!(&err, ActionError::AlreadyExists(msg) if msg.contains("topic")));
+
+        let handle = repo::FacadeSequence::new(
+            "test_sequence/test_topic".to_owned(),
+            (*store).clone(),
+            repo.clone(),
+        );
+        assert!(handle.resource_id().await.is_err());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test walking a sequence and its topic through the legal state transitions, and checking
+    /// that the finalized topic can't be reopened.
+    async fn resource_state_transitions(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use types::ResourceState::*;
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        create_empty_topic(&repo, &store, &sequence, "test_sequence/test_topic")
+            .await
+            .unwrap();
+
+        let sequence =
+            FacadeSequence::new("test_sequence".to_owned(), (*store).clone(), repo.clone());
+        let topic = FacadeTopic::new(
+            "test_sequence/test_topic".to_owned(),
+            (*store).clone(),
+            repo.clone(),
+        );
+        assert_eq!(topic.state().await.unwrap(), Open);
+        assert_eq!(sequence.state().await.unwrap(), Open);
+
+        // A sequence with an open topic can't be finalized
+        let err = sequence.lock().await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                repo::FacadeError::InvalidTransition(types::InvalidTransition {
+                    from: Open,
+                    to: Finalized
+                })
+            ),
+            "{err}"
+        );
+
+        // Open -> Locked
+        topic.lock().await.unwrap();
+        assert_eq!(topic.state().await.unwrap(), Locked);
+        assert_eq!(sequence.state().await.unwrap(), Locked);
+
+        // Locked -> Open -> Locked
+        topic.reopen().await.unwrap();
+        assert_eq!(topic.state().await.unwrap(), Open);
+        assert_eq!(sequence.state().await.unwrap(), Open);
+        topic.lock().await.unwrap();
+
+        // Locked -> Finalized
+        sequence.lock().await.unwrap();
+        assert_eq!(sequence.state().await.unwrap(), Finalized);
+        assert_eq!(topic.state().await.unwrap(), Finalized);
+
+        // Finalized is a terminal state
+        assert!(matches!(
+            topic.reopen().await,
+            Err(repo::FacadeError::InvalidTransition(_))
+        ));
+        assert!(matches!(
+            sequence.lock().await,
+            Err(repo::FacadeError::SequenceLocked)
+        ));
+        assert_eq!(topic.state().await.unwrap(), Finalized);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that a finalization interrupted after the topics check can be re-run, and
+    /// that re-running a completed finalization succeeds.
+    async fn sequence_finalize_resume(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        create_empty_topic(&repo, &store, &sequence, "test_sequence/test_topic")
+            .await
+            .unwrap();
+        FacadeTopic::new(
+            "test_sequence/test_topic".to_owned(),
+            (*store).clone(),
+            repo.clone(),
+        )
+        .lock()
+        .await
+        .unwrap();
+
+        // Crash after the topics check, before the lock is committed
+        let locator = types::SequenceResourceLocator::from("test_sequence");
+        {
+            let mut tx = repo.transaction().await.unwrap();
+            repo::sequence_find_all_topic_names(&mut tx, &locator)
+                .await
+                .unwrap();
+            repo::sequence_lock(&mut tx, &locator).await.unwrap();
+        }
+
+        let finalize = || {
+            let body = format!(r#"{{"name": "test_sequence", "key": "{}"}}"#, sequence.uuid);
+            let action = ActionRequest::try_new("sequence_finalize", body.as_bytes()).unwrap();
+            let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+            do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+        };
+
+        finalize().await.unwrap();
+        // Retried finalization, e.g. after a lost response
+        finalize().await.unwrap();
+
+        let handle =
+            repo::FacadeSequence::new("test_sequence".to_owned(), (*store).clone(), repo.clone());
+        assert!(handle.is_locked().await.unwrap());
+
+        Ok(())
+    }
+
     #[sqlx::test]
     /// Test checking if the creation of a topic succeeds.
     async fn topic_create(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
@@ -227,26 +756,2571 @@ mod tests {
     }
 
     #[sqlx::test]
-    /// Test checking if the creation of a topic with unauthorized name fails.
-    async fn topic_create_unauthorized(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+    /// Test checking that a topic created without a format uses the server default format.
+    async fn topic_create_default_format(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+
         let sequence_name = "test_sequence".to_owned();
-        let topic_name = "test_topic".to_owned();
+        let topic_name = "test_sequence/test_topic".to_owned();
 
         let repo = repo::testing::Repository::new(pool);
         let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
 
-        // Create a first sequence to then try to create it again
         let sequence = create_empty_sequence(&repo, &store, &sequence_name)
             .await
             .unwrap();
 
-        // This should fail since the topic name is not a child of the sequence
-        let topic = create_empty_topic(&repo, &store, &sequence, &topic_name).await;
+        let request = serde_json::json!({
+            "name": topic_name,
+            "sequence_key": sequence.uuid.to_string(),
+            "ontology_tag": "test_tag",
+            "user_metadata": {},
+        });
 
-        if topic.is_ok() {
-            panic!("the topic creation should fail")
+        let action = ActionRequest::try_new("topic_create", request.to_string().as_bytes())
+            .expect("Unable to create action from string");
+
+        do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+            .await
+            .unwrap();
+
+        let handle = FacadeTopic::new(topic_name, (*store).clone(), repo.clone());
+        let metadata = handle.metadata().await.unwrap();
+
+        assert_eq!(
+            metadata.properties.serialization_format,
+            crate::params::configurables().default_format
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that auto-named topics get distinct names inside the parent sequence.
+    async fn topic_create_auto(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+
+        let sequence_name = "test_sequence".to_owned();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+
+        let sequence = create_empty_sequence(&repo, &store, &sequence_name)
+            .await
+            .unwrap();
+
+        let mut names = Vec::new();
+        for strategy in ["timestamp", "timestamp", "uuid", "uuid"] {
+            let request = serde_json::json!({
+                "sequence_name": sequence_name,
+                "name_strategy": strategy,
+                "sequence_key": sequence.uuid.to_string(),
+                "ontology_tag": "test_tag",
+                "user_metadata": {},
+            });
+
+            let action =
+                ActionRequest::try_new("topic_create_auto", request.to_string().as_bytes())
+                    .expect("Unable to create action from string");
+
+            let response = do_action((*store).clone(), repo.clone(), ts_engine.clone(), action)
+                .await
+                .unwrap();
+
+            if let ActionResponse::TopicCreateAuto(created) = response {
+                assert!(created.name.starts_with("test_sequence/"));
+                names.push(created.name);
+            } else {
+                panic!("wrong response returned")
+            }
+        }
+
+        let unique: std::collections::HashSet<_> = names.iter().collect();
+        assert_eq!(unique.len(), names.len());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that auto-named topics created concurrently with the same generated names
+    /// get distinct names, the creations losing the race on a name are retried with a new one.
+    async fn topic_create_auto_racing(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        /// Generates the same names on every creation, so that concurrent creations collide
+        struct Sequential;
+
+        impl types::NameStrategy for Sequential {
+            fn generate(&self, attempt: usize) -> String {
+                format!("topic_{attempt}")
+            }
         }
 
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+
+        let ctx = ActionContext::new((*store).clone(), repo.clone(), ts_engine);
+        let creations = (0..4).map(|_| {
+            topic::create_auto(
+                &ctx,
+                "test_sequence".to_owned(),
+                &Sequential,
+                sequence.uuid.to_string(),
+                None,
+                "test_tag".to_owned(),
+                "{}",
+            )
+        });
+
+        let mut names = std::collections::HashSet::new();
+        for response in futures::future::join_all(creations).await {
+            let ActionResponse::TopicCreateAuto(created) = response.unwrap() else {
+                panic!("wrong response returned")
+            };
+            names.insert(created.name);
+        }
+        assert_eq!(names.len(), 4);
+
+        Ok(())
+    }
+
+    /// Runs a `count_only` query and returns the row count.
+    async fn query_count(
+        repo: &repo::testing::Repository,
+        store: &store::testing::Store,
+        query: serde_json::Value,
+    ) -> usize {
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let mut body = query;
+        body["count_only"] = serde_json::Value::Bool(true);
+
+        let action = ActionRequest::try_new("query", body.to_string().as_bytes()).unwrap();
+
+        let response = do_action(
+            (*store).clone(),
+            (*repo).clone(),
+            Arc::new(ts_engine),
+            action,
+        )
+        .await
+        .unwrap();
+
+        if let ActionResponse::QueryCount(response) = response {
+            response.row_count
+        } else {
+            panic!("wrong response returned")
+        }
+    }
+
+    #[sqlx::test]
+    /// Test checking that a range wider than the topic data is clamped to the data extent.
+    async fn topic_clamp_range(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use ::arrow::array::{Int64Array, RecordBatch};
+        use ::arrow::datatypes::{DataType, Field, Schema};
+
+        let topic_name = "test_sequence/test_topic";
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            crate::params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+            DataType::Int64,
+            false,
+        )]));
+        let batch = |ts: Vec<i64>| {
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(ts))]).unwrap()
+        };
+
+        write_chunk(&repo, &store, &topic, topic_name, 0, &batch(vec![10, 20])).await;
+        write_chunk(&repo, &store, &topic, topic_name, 1, &batch(vec![30, 40])).await;
+
+        let handle = FacadeTopic::new(topic_name.to_owned(), (*store).clone(), repo.clone());
+
+        let clamped = handle
+            .clamp_range(&types::TimestampRange::new(0.into(), 100.into()))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(i64::from(clamped.start), 10);
+        assert_eq!(i64::from(clamped.end), 40);
+
+        // Ranges outside the data are empty
+        let clamped = handle
+            .clamp_range(&types::TimestampRange::new(50.into(), 100.into()))
+            .await
+            .unwrap();
+        assert!(clamped.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the last rows of a topic are collected across a chunk boundary.
+    async fn query_tail(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use ::arrow::array::{Int64Array, RecordBatch};
+        use ::arrow::datatypes::{DataType, Field, Schema};
+        use ::arrow::ipc::reader::StreamReader;
+        use base64::{Engine, prelude::BASE64_STANDARD};
+
+        crate::params::load_configurables_from_env();
+
+        let topic_name = "test_sequence/test_topic";
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                crate::params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("value", DataType::Int64, false),
+        ]));
+        let batch = |ts: Vec<i64>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(ts.clone())),
+                    Arc::new(Int64Array::from(ts)),
+                ],
+            )
+            .unwrap()
+        };
+
+        // The last chunk holds less rows than requested
+        write_chunk(
+            &repo,
+            &store,
+            &topic,
+            topic_name,
+            0,
+            &batch(vec![10, 20, 30, 40]),
+        )
+        .await;
+        write_chunk(&repo, &store, &topic, topic_name, 1, &batch(vec![50, 60])).await;
+
+        let action = ActionRequest::try_new(
+            "query_tail",
+            format!(r#"{{"locator": "{}", "n": 3}}"#, topic_name).as_bytes(),
+        )
+        .unwrap();
+
+        let response = do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+            .await
+            .unwrap();
+
+        let ActionResponse::QueryTail(response) = response else {
+            panic!("wrong response returned")
+        };
+        assert_eq!(response.row_count, 3);
+
+        let buffer = BASE64_STANDARD.decode(response.data.unwrap()).unwrap();
+        let batches = StreamReader::try_new(buffer.as_slice(), None)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let timestamps: Vec<i64> = batches
+            .iter()
+            .flat_map(|b| {
+                b.column(0)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+
+        assert_eq!(timestamps, vec![40, 50, 60]);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the tail is selected by the chunk timestamps rather than by the chunk
+    /// order, and that an empty tail keeps the topic schema.
+    async fn topic_tail_out_of_order(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use ::arrow::array::{AsArray, Int64Array, RecordBatch};
+        use ::arrow::datatypes::{DataType, Field, Int64Type, Schema};
+
+        let topic_name = "test_sequence/test_topic";
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            crate::params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+            DataType::Int64,
+            false,
+        )]));
+        let batch = |ts: Vec<i64>| {
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(ts))]).unwrap()
+        };
+
+        // The most recent rows are in the first chunk
+        write_chunk(&repo, &store, &topic, topic_name, 0, &batch(vec![50, 60])).await;
+        write_chunk(
+            &repo,
+            &store,
+            &topic,
+            topic_name,
+            1,
+            &batch(vec![10, 20, 30, 40]),
+        )
+        .await;
+        write_chunk(&repo, &store, &topic, topic_name, 2, &batch(vec![5])).await;
+
+        let handle = FacadeTopic::new(topic_name.to_owned(), (*store).clone(), repo.clone());
+
+        let tail = handle.tail(3, 2).await.unwrap().unwrap();
+        let timestamps = tail.column(0).as_primitive::<Int64Type>().values().to_vec();
+        assert_eq!(timestamps, vec![40, 50, 60]);
+
+        let tail = handle.tail(0, 2).await.unwrap().unwrap();
+        assert_eq!(tail.num_rows(), 0);
+        assert_eq!(tail.schema().fields().len(), 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that point lookups return the row at or nearest a timestamp, across the
+    /// chunks of a topic.
+    async fn query_at(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use ::arrow::array::{Int64Array, RecordBatch};
+        use ::arrow::datatypes::{DataType, Field, Schema};
+        use ::arrow::ipc::reader::StreamReader;
+        use base64::{Engine, prelude::BASE64_STANDARD};
+
+        crate::params::load_configurables_from_env();
+
+        let topic_name = "test_sequence/test_topic";
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                crate::params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("value", DataType::Int64, false),
+        ]));
+        let batch = |ts: Vec<i64>| {
+            let values: Vec<i64> = ts.iter().map(|ts| ts * 10).collect();
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(ts)),
+                    Arc::new(Int64Array::from(values)),
+                ],
+            )
+            .unwrap()
+        };
+
+        write_chunk(
+            &repo,
+            &store,
+            &topic,
+            topic_name,
+            0,
+            &batch(vec![10, 20, 30, 40]),
+        )
+        .await;
+        write_chunk(&repo, &store, &topic, topic_name, 1, &batch(vec![70, 80])).await;
+
+        // Returns the value of the row found, if any
+        let lookup = async |timestamp: i64, mode: &str| {
+            let action = ActionRequest::try_new(
+                "query_at",
+                format!(
+                    r#"{{"locator": "{}", "timestamp": {}, "mode": "{}"}}"#,
+                    topic_name, timestamp, mode
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+
+            let response = do_action((*store).clone(), repo.clone(), ts_engine.clone(), action)
+                .await
+                .unwrap();
+
+            let ActionResponse::QueryAt(response) = response else {
+                panic!("wrong response returned")
+            };
+
+            let data = response.data?;
+            assert_eq!(response.row_count, 1);
+
+            let buffer = BASE64_STANDARD.decode(data).unwrap();
+            let batches = StreamReader::try_new(buffer.as_slice(), None)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            Some(
+                batches[0]
+                    .column(1)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap()
+                    .value(0),
+            )
+        };
+
+        // Exact hit
+        assert_eq!(lookup(30, "exact").await, Some(300));
+        assert_eq!(lookup(35, "exact").await, None);
+
+        // Nearest between two points, across the chunk boundary
+        assert_eq!(lookup(60, "nearest").await, Some(700));
+        assert_eq!(lookup(50, "nearest").await, Some(400));
+        assert_eq!(lookup(60, "before").await, Some(400));
+        assert_eq!(lookup(60, "after").await, Some(700));
+
+        // Timestamp before all data
+        assert_eq!(lookup(5, "before").await, None);
+        assert_eq!(lookup(5, "nearest").await, Some(100));
+        assert_eq!(lookup(5, "after").await, Some(100));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that aggregations computed in process match the ones computed by the
+    /// query engine, and that `auto` falls back to the query engine for unsupported columns.
+    async fn query_aggregate(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use ::arrow::array::{Float64Array, Int64Array, RecordBatch, StringArray};
+        use ::arrow::datatypes::{DataType, Field, Schema};
+
+        crate::params::load_configurables_from_env();
+
+        let topic_name = "test_sequence/test_topic";
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                crate::params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("value", DataType::Int64, true),
+            Field::new("reading", DataType::Float64, false),
+            Field::new("label", DataType::Utf8, false),
+        ]));
+        let batch = |ts: Vec<i64>| {
+            // Every third value is missing
+            let values: Vec<Option<i64>> = ts
+                .iter()
+                .map(|ts| (ts % 30 != 0).then_some(ts - 45))
+                .collect();
+            let readings: Vec<f64> = ts.iter().map(|ts| *ts as f64 * 0.5).collect();
+            let labels: Vec<String> = ts.iter().map(|ts| format!("l{}", ts % 20)).collect();
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(ts)),
+                    Arc::new(Int64Array::from(values)),
+                    Arc::new(Float64Array::from(readings)),
+                    Arc::new(StringArray::from(labels)),
+                ],
+            )
+            .unwrap()
+        };
+
+        write_chunk(
+            &repo,
+            &store,
+            &topic,
+            topic_name,
+            0,
+            &batch(vec![10, 20, 30, 40]),
+        )
+        .await;
+        write_chunk(
+            &repo,
+            &store,
+            &topic,
+            topic_name,
+            1,
+            &batch(vec![60, 70, 80]),
+        )
+        .await;
+
+        let aggregate = async |aggregates: &str, range: Option<[i64; 2]>, engine: &str| {
+            let action = ActionRequest::try_new(
+                "query_aggregate",
+                serde_json::json!({
+                    "locator": topic_name,
+                    "aggregates": serde_json::from_str::<serde_json::Value>(aggregates).unwrap(),
+                    "range": range,
+                    "engine": engine,
+                })
+                .to_string()
+                .as_bytes(),
+            )
+            .unwrap();
+
+            let response = do_action((*store).clone(), repo.clone(), ts_engine.clone(), action)
+                .await
+                .unwrap();
+            let ActionResponse::QueryAggregate(response) = response else {
+                panic!("wrong response returned")
+            };
+            (
+                serde_json::to_value(&response.values).unwrap(),
+                serde_json::to_value(response.engine).unwrap(),
+            )
+        };
+
+        let numeric = r#"[
+            {"function": "count", "column": "value"},
+            {"function": "min", "column": "value"},
+            {"function": "max", "column": "value"},
+            {"function": "sum", "column": "value"},
+            {"function": "mean", "column": "value"},
+            {"function": "sum", "column": "reading"},
+            {"function": "mean", "column": "reading"}
+        ]"#;
+
+        for range in [None, Some([20, 70]), Some([100, 200])] {
+            let (in_process, engine) = aggregate(numeric, range, "in_process").await;
+            assert_eq!(engine, "in_process");
+            if cfg!(feature = "datafusion-engine") {
+                let (datafusion, engine) = aggregate(numeric, range, "datafusion").await;
+                assert_eq!(engine, "datafusion");
+                assert_eq!(in_process, datafusion, "range {:?}", range);
+            }
+        }
+
+        let (values, engine) = aggregate(numeric, None, "auto").await;
+        assert_eq!(engine, "in_process");
+        // Values: -35, -25, -5, 25, 35 (30 and 60 missing), readings: 5 to 40
+        assert_eq!(
+            values,
+            serde_json::json!([5, -35, 35, -5, -1.0, 155.0, 155.0 / 7.0])
+        );
+
+        // Text columns can't be aggregated in process
+        let text = r#"[{"function": "max", "column": "label"}]"#;
+        if cfg!(feature = "datafusion-engine") {
+            let (values, engine) = aggregate(text, None, "auto").await;
+            assert_eq!(engine, "datafusion");
+            assert_eq!(values, serde_json::json!(["l10"]));
+        }
+
+        let action = ActionRequest::try_new(
+            "query_aggregate",
+            format!(
+                r#"{{"locator": "{}", "aggregates": {}, "engine": "in_process"}}"#,
+                topic_name, text
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let err = do_action((*store).clone(), repo.clone(), ts_engine.clone(), action)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ActionError::InvalidArgument(_)), "{err}");
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that column statistics match the data written to a topic.
+    async fn query_column_stats(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+
+        let topic_name = "test_sequence/test_topic";
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+
+        let batch = crate::arrow::testing::dummy_batch();
+        write_chunk(&repo, &store, &topic, topic_name, 0, &batch).await;
+
+        let action = ActionRequest::try_new(
+            "query_column_stats",
+            format!(r#"{{"locator": "{}"}}"#, topic_name).as_bytes(),
+        )
+        .unwrap();
+
+        let response = do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+            .await
+            .unwrap();
+
+        let ActionResponse::QueryColumnStats(response) = response else {
+            panic!("wrong response returned")
+        };
+
+        let value = response
+            .columns
+            .iter()
+            .find(|c| c.column == "value")
+            .unwrap();
+        assert_eq!(value.min, Some(serde_json::json!(1)));
+        assert_eq!(value.max, Some(serde_json::json!(7)));
+        assert_eq!(value.null_count, Some(0));
+        assert!(value.distinct_estimate.is_some());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the gaps between the chunks of a topic, and after its last sample,
+    /// are reported.
+    async fn query_gaps(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use ::arrow::array::{Int64Array, RecordBatch};
+
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let topic_name = "test_sequence/topic";
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+
+        // Samples every 5ns in [10000, 10030] and [10100, 10130]
+        let batch = crate::arrow::testing::dummy_batch();
+        let shifted = RecordBatch::try_new(
+            batch.schema(),
+            vec![
+                Arc::new(Int64Array::from_iter_values((10100..=10130).step_by(5))),
+                Arc::new(Int64Array::from_iter_values(1..=7)),
+            ],
+        )
+        .unwrap();
+        write_chunk(&repo, &store, &topic, topic_name, 0, &batch).await;
+        write_chunk(&repo, &store, &topic, topic_name, 1, &shifted).await;
+
+        let body = serde_json::json!({
+            "locator": topic_name,
+            "range": [10000, 10200],
+            "expected_interval": 5,
+        });
+        let action = ActionRequest::try_new("query_gaps", body.to_string().as_bytes()).unwrap();
+
+        let response = do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+            .await
+            .unwrap();
+
+        let ActionResponse::QueryGaps(response) = response else {
+            panic!("wrong response returned")
+        };
+        assert_eq!(response.gaps, vec![(10030, 10100), (10130, 10200)]);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the expected interval of `query_gaps` is rounded up to the topic
+    /// precision, that intervals shorter than one unit are rejected and that a range holding no
+    /// value of the topic precision has no gaps.
+    async fn query_gaps_precision(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use ::arrow::array::{Int64Array, RecordBatch};
+
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+        let topic_name = "test_sequence/micros";
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let properties = types::TopicProperties::new(rw::Format::Default, "test_tag".to_owned())
+            .with_time_precision(types::TimePrecision::Micros);
+        let topic = create_topic_with(&repo, &store, &sequence, topic_name, properties)
+            .await
+            .unwrap();
+
+        // Samples every 2us in [10us, 20us] and [30us, 40us]
+        let timestamps: Vec<i64> = (10..=20).step_by(2).chain((30..=40).step_by(2)).collect();
+        let batch = RecordBatch::try_new(
+            crate::arrow::testing::dummy_batch().schema(),
+            vec![
+                Arc::new(Int64Array::from(timestamps.clone())),
+                Arc::new(Int64Array::from_iter_values(0..timestamps.len() as i64)),
+            ],
+        )
+        .unwrap();
+        write_chunk(&repo, &store, &topic, topic_name, 0, &batch).await;
+
+        let gaps = |range: [i64; 2], expected_interval: i64| {
+            let body = serde_json::json!({
+                "locator": topic_name,
+                "range": range,
+                "expected_interval": expected_interval,
+            });
+            let action = ActionRequest::try_new("query_gaps", body.to_string().as_bytes()).unwrap();
+            do_action((*store).clone(), repo.clone(), ts_engine.clone(), action)
+        };
+
+        // 1.5us is rounded up to 2us, samples 2us apart are not gaps
+        let ActionResponse::QueryGaps(response) = gaps([10_000, 50_000], 1_500).await.unwrap()
+        else {
+            panic!("wrong response returned")
+        };
+        assert_eq!(response.gaps, vec![(20_000, 30_000), (40_000, 50_000)]);
+
+        let err = gaps([10_000, 50_000], 999).await.unwrap_err();
+        assert!(matches!(err, ActionError::InvalidArgument(_)), "{err}");
+
+        // No microsecond falls in the range
+        let ActionResponse::QueryGaps(response) = gaps([10_001, 10_999], 1_000).await.unwrap()
+        else {
+            panic!("wrong response returned")
+        };
+        assert!(response.gaps.is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that a `schema_only` query returns the schema of the matching topics as
+    /// zero-row batches.
+    async fn query_schema_only(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use ::arrow::ipc::reader::StreamReader;
+        use base64::{Engine, prelude::BASE64_STANDARD};
+
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, "test_sequence/with_data")
+            .await
+            .unwrap();
+        create_empty_topic(&repo, &store, &sequence, "test_sequence/without_data")
+            .await
+            .unwrap();
+
+        write_dummy_chunk(&repo, &store, &topic, "test_sequence/with_data", 0).await;
+
+        let body = serde_json::json!({
+            "schema_only": true,
+            "sequence": { "name": { "$eq": "test_sequence" } },
+        });
+        let action = ActionRequest::try_new("query", body.to_string().as_bytes()).unwrap();
+
+        let response = do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+            .await
+            .unwrap();
+
+        let ActionResponse::QuerySchema(response) = response else {
+            panic!("wrong response returned")
+        };
+        assert_eq!(response.items.len(), 2);
+
+        let with_data = &response.items[0];
+        assert_eq!(with_data.locator, "test_sequence/with_data");
+
+        let buffer = BASE64_STANDARD
+            .decode(with_data.data.as_ref().unwrap())
+            .unwrap();
+        let reader = StreamReader::try_new(buffer.as_slice(), None).unwrap();
+
+        let expected = crate::arrow::testing::dummy_batch().schema();
+        let fields = |schema: &::arrow::datatypes::Schema| {
+            schema
+                .fields()
+                .iter()
+                .map(|f| (f.name().clone(), f.data_type().clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fields(reader.schema().as_ref()), fields(expected.as_ref()));
+
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(rows, 0);
+
+        let without_data = &response.items[1];
+        assert_eq!(without_data.locator, "test_sequence/without_data");
+        assert!(without_data.data.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that a `schema_only` query returns the schema declared at the creation of
+    /// a topic without data.
+    async fn query_schema_only_declared(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use ::arrow::ipc::reader::StreamReader;
+        use base64::{Engine, prelude::BASE64_STANDARD};
+
+        crate::params::load_configurables_from_env();
+
+        let topic_name = "test_sequence/test_topic";
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+
+        let declared = crate::arrow::testing::dummy_batch().schema();
+        let request = serde_json::json!({
+            "name": topic_name,
+            "sequence_key": sequence.uuid.to_string(),
+            "ontology_tag": "test_tag",
+            "schema": marshal::JsonArrowSchema(declared.clone()),
+            "user_metadata": {},
+        });
+        let action =
+            ActionRequest::try_new("topic_create", request.to_string().as_bytes()).unwrap();
+        do_action((*store).clone(), repo.clone(), ts_engine.clone(), action)
+            .await
+            .unwrap();
+
+        let body = serde_json::json!({
+            "schema_only": true,
+            "topic": { "name": { "$eq": topic_name } },
+        });
+        let action = ActionRequest::try_new("query", body.to_string().as_bytes()).unwrap();
+
+        let response = do_action((*store).clone(), repo.clone(), ts_engine, action)
+            .await
+            .unwrap();
+
+        let ActionResponse::QuerySchema(response) = response else {
+            panic!("wrong response returned")
+        };
+        assert_eq!(response.items.len(), 1);
+
+        // No data was written, the schema is the declared one
+        let buffer = BASE64_STANDARD
+            .decode(response.items[0].data.as_ref().unwrap())
+            .unwrap();
+        let reader = StreamReader::try_new(buffer.as_slice(), None).unwrap();
+        assert_eq!(reader.schema().fields(), declared.fields());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that without value predicates rows are counted from the chunks metadata.
+    async fn query_count_from_metadata(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, "test_sequence/test_topic")
+            .await
+            .unwrap();
+
+        write_dummy_chunk(&repo, &store, &topic, "test_sequence/test_topic", 0).await;
+        write_dummy_chunk(&repo, &store, &topic, "test_sequence/test_topic", 1).await;
+
+        // Remove the data, the count must not need to read it
+        store.delete_recursive("test_sequence").await.unwrap();
+
+        let query = serde_json::json!({
+            "topic": { "name": { "$eq": "test_sequence/test_topic" } }
+        });
+
+        assert_eq!(query_count(&repo, &store, query).await, 14);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that with a value predicate the matching rows are counted by streaming.
+    async fn query_count_with_predicate(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, "test_sequence/test_topic")
+            .await
+            .unwrap();
+
+        write_dummy_chunk(&repo, &store, &topic, "test_sequence/test_topic", 0).await;
+        write_dummy_chunk(&repo, &store, &topic, "test_sequence/test_topic", 1).await;
+
+        let query = serde_json::json!({
+            "ontology": { "test_tag.value": { "$between": [3, 5] } }
+        });
+
+        assert_eq!(query_count(&repo, &store, query).await, 6);
+
+        Ok(())
+    }
+
+    /// Runs a `query_validate` action on `query`.
+    async fn query_validate(
+        repo: &repo::testing::Repository,
+        store: &store::testing::Store,
+        query: serde_json::Value,
+    ) -> marshal::responses::QueryValidate {
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let action =
+            ActionRequest::try_new("query_validate", query.to_string().as_bytes()).unwrap();
+
+        let response = do_action(
+            (*store).clone(),
+            (*repo).clone(),
+            Arc::new(ts_engine),
+            action,
+        )
+        .await
+        .unwrap();
+
+        if let ActionResponse::QueryValidate(response) = response {
+            response
+        } else {
+            panic!("wrong response returned")
+        }
+    }
+
+    #[sqlx::test]
+    /// Test checking that a valid query is validated with the schema of the selected topics
+    /// and the number of chunks that would be searched.
+    async fn query_validate_valid(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, "test_sequence/test_topic")
+            .await
+            .unwrap();
+
+        write_dummy_chunk(&repo, &store, &topic, "test_sequence/test_topic", 0).await;
+        write_dummy_chunk(&repo, &store, &topic, "test_sequence/test_topic", 1).await;
+
+        let response = query_validate(
+            &repo,
+            &store,
+            serde_json::json!({
+                "sequence": { "name": { "$eq": "test_sequence" } },
+                "ontology": { "test_tag.value": { "$between": [3, 5] } }
+            }),
+        )
+        .await;
+
+        assert!(response.valid);
+        assert!(response.errors.is_empty());
+        assert_eq!(response.schemas.len(), 1);
+        assert_eq!(response.schemas[0].locator, "test_sequence/test_topic");
+        assert!(response.schemas[0].data.is_some());
+        assert_eq!(response.chunk_count, Some(2));
+
+        let response = query_validate(
+            &repo,
+            &store,
+            serde_json::json!({ "topic": { "name": { "$eq": "test_sequence/test_topic" } } }),
+        )
+        .await;
+
+        assert!(response.valid);
+        assert_eq!(response.chunk_count, Some(2));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the validation of an invalid query reports all the problems found,
+    /// without executing the query.
+    async fn query_validate_invalid(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, "test_sequence/test_topic")
+            .await
+            .unwrap();
+
+        write_dummy_chunk(&repo, &store, &topic, "test_sequence/test_topic", 0).await;
+
+        // Syntax errors: an unsupported operation and two empty ranges
+        let response = query_validate(
+            &repo,
+            &store,
+            serde_json::json!({
+                "topic": { "name": { "$gt": "test_sequence" } },
+                "ontology": {
+                    "test_tag.value": { "$between": [5, 3] },
+                    "test_tag.other": { "$between": [10, 1] }
+                }
+            }),
+        )
+        .await;
+
+        assert!(!response.valid);
+        assert_eq!(response.errors.len(), 3);
+        assert!(response.schemas.is_empty());
+        assert!(response.chunk_count.is_none());
+
+        // Errors against the repository: an unknown field and an unsupported format
+        let response = query_validate(
+            &repo,
+            &store,
+            serde_json::json!({
+                "topic": { "serialization_format": { "$eq": "not_a_format" } },
+                "ontology": { "test_tag.not_a_column": { "$gt": 0 } }
+            }),
+        )
+        .await;
+
+        assert!(!response.valid);
+        let fields: Vec<_> = response
+            .errors
+            .iter()
+            .filter_map(|e| e.field.as_deref())
+            .collect();
+        assert_eq!(
+            fields,
+            ["topic.serialization_format", "test_tag.not_a_column"]
+        );
+        assert!(response.chunk_count.is_none());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that query time ranges (in nanoseconds) are converted to the time
+    /// precision of each topic before filtering its data.
+    async fn query_time_precision(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use ::arrow::array::{Int64Array, RecordBatch};
+        use ::arrow::datatypes::{DataType, Field, Schema};
+
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                crate::params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("value", DataType::Int64, false),
+        ]));
+        let batch = |ts: Vec<i64>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(ts)),
+                    Arc::new(Int64Array::from(vec![1, 2, 3, 4])),
+                ],
+            )
+            .unwrap()
+        };
+
+        // Both topics hold data at 1us, 2us, 3us and 4us
+        let topics = [
+            (
+                "test_sequence/micros",
+                types::TimePrecision::Micros,
+                vec![1, 2, 3, 4],
+            ),
+            (
+                "test_sequence/nanos",
+                types::TimePrecision::Nanos,
+                vec![1_000, 2_000, 3_000, 4_000],
+            ),
+        ];
+
+        for (name, precision, ts) in &topics {
+            let properties =
+                types::TopicProperties::new(rw::Format::Default, "test_tag".to_owned())
+                    .with_time_precision(*precision);
+            let topic = create_topic_with(&repo, &store, &sequence, name, properties)
+                .await
+                .unwrap();
+            write_chunk(&repo, &store, &topic, name, 0, &batch(ts.clone())).await;
+        }
+
+        let count = |name: &str, range: [i64; 2]| {
+            serde_json::json!({
+                "topic": { "name": { "$eq": name } },
+                "ontology": {
+                    "test_tag.value": { "$between": [0, 10] },
+                    "range": range,
+                }
+            })
+        };
+
+        for (name, _, _) in &topics {
+            assert_eq!(
+                query_count(&repo, &store, count(*name, [1_500, 3_000])).await,
+                2
+            );
+            assert_eq!(
+                query_count(&repo, &store, count(*name, [0, 4_000])).await,
+                4
+            );
+            // No data timestamp falls in the range, even if raw values of the micros topic do
+            assert_eq!(query_count(&repo, &store, count(*name, [1, 4])).await, 0);
+        }
+
+        // The timestamp range of the matching data is reported in nanoseconds
+        let body = serde_json::json!({
+            "topic": { "name": { "$eq": "test_sequence/micros" } },
+            "ontology": {
+                "test_tag.value": { "$between": [0, 10] },
+                "include_timestamp_range": true,
+                "range": [1_500, 3_000],
+            }
+        });
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let action = ActionRequest::try_new("query", body.to_string().as_bytes()).unwrap();
+        let response = do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+            .await
+            .unwrap();
+
+        let ActionResponse::Query(response) = response else {
+            panic!("wrong response returned")
+        };
+        let topic = &response.items[0].topics[0];
+        assert_eq!(topic.timestamp_range, Some((2_000, 3_000)));
+        assert_eq!(topic.searched_range, Some((1_500, 3_000)));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the chunks with no data in the searched time range are pruned by their
+    /// statistics and never read.
+    async fn query_prunes_chunks_by_time_range(
+        pool: sqlx::Pool<repo::Database>,
+    ) -> sqlx::Result<()> {
+        use ::arrow::array::{Int64Array, RecordBatch};
+        use ::arrow::datatypes::{DataType, Field, Schema};
+
+        crate::params::load_configurables_from_env();
+
+        let topic_name = "test_sequence/test_topic";
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(
+                crate::params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("value", DataType::Int64, false),
+        ]));
+        for (idx, ts) in [vec![1, 2, 3, 4], vec![1_001, 1_002, 1_003, 1_004]]
+            .into_iter()
+            .enumerate()
+        {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(ts)),
+                    Arc::new(Int64Array::from(vec![1, 2, 3, 4])),
+                ],
+            )
+            .unwrap();
+            write_chunk(&repo, &store, &topic, topic_name, idx, &batch).await;
+        }
+
+        // The data of the first chunk is removed, so the query fails if the chunk is read
+        let datafile =
+            types::TopicResourceLocator::from(topic_name).datafile(0, &crate::rw::Format::Default);
+        store.delete(&datafile).await.unwrap();
+
+        let body = serde_json::json!({
+            "ontology": {
+                "test_tag.value": { "$between": [0, 10] },
+                "range": [1_000, 2_000],
+            }
+        });
+        assert_eq!(query_count(&repo, &store, body.clone()).await, 4);
+
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let action = ActionRequest::try_new("query", body.to_string().as_bytes()).unwrap();
+        let response = do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+            .await
+            .unwrap();
+
+        let ActionResponse::Query(response) = response else {
+            panic!("wrong response returned")
+        };
+        assert_eq!(response.items[0].topics.len(), 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the chunks matched by a query are searched concurrently, counting the
+    /// reads in flight on an instrumented store.
+    async fn query_chunks_in_parallel(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+
+        let topic_name = "test_sequence/test_topic";
+
+        let repo = repo::testing::Repository::new(pool);
+        let backend =
+            Arc::new(store::testing::HookedStore::<store::testing::CountingReads>::default());
+        let store: store::StoreRef = Arc::new(store::testing::with_driver(backend.clone()));
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+        for idx in 0..8 {
+            write_dummy_chunk(&repo, &store, &topic, topic_name, idx).await;
+        }
+
+        let body = serde_json::json!({
+            "ontology": { "test_tag.value": { "$between": [0, 10] } }
+        });
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let action = ActionRequest::try_new("query", body.to_string().as_bytes()).unwrap();
+
+        backend.hooks.reset();
+        let response = do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+            .await
+            .unwrap();
+
+        let ActionResponse::Query(response) = response else {
+            panic!("wrong response returned")
+        };
+        assert_eq!(response.items[0].topics.len(), 1);
+        assert!(
+            backend.hooks.max_in_flight() > 1,
+            "chunks searched one at a time"
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that two time-overlapping chunks are rewritten with disjoint time ranges.
+    async fn topic_reorder(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use ::arrow::array::{Array, Int64Array};
+
+        crate::params::load_configurables_from_env();
+
+        let topic_name = "test_sequence/test_topic";
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+
+        // Both chunks cover the same time range
+        write_dummy_chunk(&repo, &store, &topic, topic_name, 0).await;
+        write_dummy_chunk(&repo, &store, &topic, topic_name, 1).await;
+
+        let handle = FacadeTopic::new(topic_name.to_owned(), (*store).clone(), repo.clone());
+        handle.lock().await.unwrap();
+        let original = handle.datafiles().await.unwrap();
+
+        let action = || {
+            ActionRequest::try_new(
+                "topic_reorder",
+                format!(r#"{{"name": "{}"}}"#, topic_name).as_bytes(),
+            )
+            .unwrap()
+        };
+        let ts_engine = Arc::new(ts_engine);
+
+        // Topics leased by another instance are refused
+        let lease = handle
+            .acquire_lease("other_instance", std::time::Duration::from_secs(60))
+            .await
+            .unwrap();
+        let err = do_action((*store).clone(), repo.clone(), ts_engine.clone(), action())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ActionError::ResourceLocked(_)));
+        lease.release().await.unwrap();
+
+        do_action((*store).clone(), repo.clone(), ts_engine, action())
+            .await
+            .unwrap();
+
+        let chunks = repo::chunks_find_by_topic_id(&mut repo.connection(), topic.id)
+            .await
+            .unwrap();
+
+        // New chunks are numbered after the original ones, which are deleted
+        for datafile in &original {
+            assert!(store.size(datafile).await.is_err());
+        }
+        assert!(
+            chunks
+                .iter()
+                .all(|c| !original.contains(&c.data_file().to_owned()))
+        );
+
+        let mut ranges = Vec::new();
+        let mut rows = 0;
+        for chunk in chunks {
+            let buffer = store.read_bytes(chunk.data_file()).await.unwrap();
+            let batches = rw::ChunkReader::new(rw::Format::Default, buffer.into())
+                .unwrap()
+                .read_all()
+                .unwrap();
+
+            for batch in batches {
+                let ts = batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap();
+                rows += ts.len();
+                ranges.push((ts.value(0), ts.value(ts.len() - 1)));
+            }
+        }
+
+        assert_eq!(rows, 14);
+        assert!(ranges.len() > 1);
+
+        ranges.sort();
+        for pair in ranges.windows(2) {
+            assert!(pair[0].1 < pair[1].0, "overlapping ranges {:?}", ranges);
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that reordered chunks are named after their time range when the topic
+    /// requests it.
+    async fn topic_reorder_time_range_naming(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use ::arrow::array::AsArray;
+        use ::arrow::datatypes::Int64Type;
+
+        crate::params::load_configurables_from_env();
+
+        let topic_name = "test_sequence/test_topic";
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let properties = types::TopicProperties::new(rw::Format::Default, "test_tag".to_owned())
+            .with_chunk_naming(types::ChunkNaming::TimeRange);
+        let topic = create_topic_with(&repo, &store, &sequence, topic_name, properties)
+            .await
+            .unwrap();
+
+        write_dummy_chunk(&repo, &store, &topic, topic_name, 0).await;
+        write_dummy_chunk(&repo, &store, &topic, topic_name, 1).await;
+
+        let handle = FacadeTopic::new(topic_name.to_owned(), (*store).clone(), repo.clone());
+        handle.lock().await.unwrap();
+
+        let action = ActionRequest::try_new(
+            "topic_reorder",
+            format!(r#"{{"name": "{}"}}"#, topic_name).as_bytes(),
+        )
+        .unwrap();
+        do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+            .await
+            .unwrap();
+
+        let datafiles = handle.datafiles().await.unwrap();
+        assert!(datafiles.len() > 1);
+        for datafile in datafiles {
+            let buffer = store.read_bytes(&datafile).await.unwrap();
+            let batches = rw::ChunkReader::new(rw::Format::Default, buffer.into())
+                .unwrap()
+                .read_all()
+                .unwrap();
+            let timestamps: Vec<i64> = batches
+                .iter()
+                .flat_map(|b| b.column(0).as_primitive::<Int64Type>().values().to_vec())
+                .collect();
+
+            let suffix = format!(
+                "_{}_{}",
+                timestamps.first().unwrap(),
+                timestamps.last().unwrap()
+            );
+            let stem = datafile.file_stem().unwrap().to_string_lossy().into_owned();
+            assert!(stem.ends_with(&suffix), "{stem} not named after {suffix}");
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that only the idle chunks are moved to the cold storage class, and that
+    /// they remain queryable.
+    async fn topic_tier(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use crate::types::Clock;
+        use object_store::{Attribute, AttributeValue, ObjectStore};
+        use std::time::Duration;
+
+        let topic_name = "tier_sequence/tier_topic";
+
+        let driver = Arc::new(object_store::memory::InMemory::new());
+        let store: store::StoreRef = Arc::new(store::testing::with_driver(driver.clone()));
+        let repo = repo::testing::Repository::new(pool);
+        let ts_engine = query::TimeseriesGateway::try_new(store.clone()).unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "tier_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+        write_dummy_chunk(&repo, &store, &topic, topic_name, 0).await;
+
+        let handle = FacadeTopic::new(topic_name.to_owned(), store.clone(), repo.clone());
+        let datafiles = handle.datafiles().await.unwrap();
+        handle.touch(&datafiles).await.unwrap();
+
+        let idle = Duration::from_secs(3600);
+        let clock = types::MockClock::new(types::Timestamp::now());
+        let tier = async || handle.tier(idle, "GLACIER_IR", clock.now()).await.unwrap();
+
+        // The chunk was just accessed
+        assert_eq!(tier().await, 0);
+
+        clock.advance(idle + Duration::from_secs(1));
+        assert_eq!(tier().await, 1);
+
+        let chunks = repo::chunks_find_by_topic_id(&mut repo.connection(), topic.id)
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].tier(), types::StorageTier::Cold);
+        assert!(chunks[0].last_access_unix_tstamp.is_some());
+
+        let object = driver
+            .get(&object_store::path::Path::from(
+                chunks[0].data_file().to_string_lossy().as_ref(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            object.attributes.get(&Attribute::StorageClass),
+            Some(&AttributeValue::from("GLACIER_IR"))
+        );
+
+        // Cold chunks are not moved again
+        assert_eq!(tier().await, 0);
+
+        let rows = ts_engine
+            .read(topic_name, rw::Format::Default, None)
+            .await
+            .unwrap()
+            .count()
+            .await
+            .unwrap();
+        assert_eq!(rows, crate::arrow::testing::dummy_batch().num_rows());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that a corrupted trailing chunk is removed by the repair action and by
+    /// the startup scan while the preceding chunks are preserved.
+    async fn topic_repair(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let sequence_name = "test_sequence".to_owned();
+        let topic_name = "test_sequence/test_topic".to_owned();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, &sequence_name)
+            .await
+            .unwrap();
+        create_empty_topic(&repo, &store, &sequence, &topic_name)
+            .await
+            .unwrap();
+
+        let locator = types::TopicResourceLocator::from(&topic_name);
+
+        // Write a valid first chunk
+        let batch = crate::arrow::testing::dummy_batch();
+        let mut writer = rw::ChunkWriter::try_new(batch.schema(), rw::Format::Default).unwrap();
+        writer.write(&batch).unwrap();
+        let (buffer, _, _) = writer.finalize().unwrap();
+        let valid_chunk = locator.datafile(0, &rw::Format::Default);
+        store.write_bytes(&valid_chunk, buffer).await.unwrap();
+
+        // Write a trailing chunk without a valid footer
+        let corrupted_chunk = locator.datafile(1, &rw::Format::Default);
+        store
+            .write_bytes(&corrupted_chunk, b"PAR1 truncated data".to_vec())
+            .await
+            .unwrap();
+
+        let action = || {
+            ActionRequest::try_new(
+                "topic_repair",
+                format!(r#"{{"name": "{}"}}"#, topic_name).as_bytes(),
+            )
+            .unwrap()
+        };
+        let ts_engine = Arc::new(ts_engine);
+        let handle = FacadeTopic::new(topic_name.clone(), (*store).clone(), repo.clone());
+
+        // Topics leased by another instance are refused
+        let lease = handle
+            .acquire_lease("other_instance", std::time::Duration::from_secs(60))
+            .await
+            .unwrap();
+        let err = do_action((*store).clone(), repo.clone(), ts_engine.clone(), action())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ActionError::ResourceLocked(_)));
+        lease.release().await.unwrap();
+
+        let response = do_action((*store).clone(), repo.clone(), ts_engine, action())
+            .await
+            .unwrap();
+
+        if let ActionResponse::TopicRepair(response) = response {
+            assert_eq!(
+                response.removed_chunk,
+                Some(corrupted_chunk.to_string_lossy().to_string())
+            );
+        } else {
+            panic!("wrong response returned")
+        }
+
+        let datafiles = store
+            .list(&topic_name, Some(crate::params::ext::PARQUET))
+            .await
+            .unwrap();
+        assert_eq!(datafiles, vec![valid_chunk.to_string_lossy().to_string()]);
+
+        // The startup scan skips the topics leased by another instance and repairs the others
+        store
+            .write_bytes(&corrupted_chunk, b"PAR1 truncated data".to_vec())
+            .await
+            .unwrap();
+        let ttl = std::time::Duration::from_secs(60);
+
+        let lease = handle.acquire_lease("other_instance", ttl).await.unwrap();
+        let removed = repo::repair_topics((*store).clone(), repo.clone(), "instance", ttl)
+            .await
+            .unwrap();
+        assert!(removed.is_empty());
+        lease.release().await.unwrap();
+
+        let removed = repo::repair_topics((*store).clone(), repo.clone(), "instance", ttl)
+            .await
+            .unwrap();
+        assert_eq!(removed, vec![corrupted_chunk.to_string_lossy().to_string()]);
+
+        let datafiles = store
+            .list(&topic_name, Some(crate::params::ext::PARQUET))
+            .await
+            .unwrap();
+        assert_eq!(datafiles, vec![valid_chunk.to_string_lossy().to_string()]);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that a datafile not registered in the repository is reported.
+    async fn topic_consistency_check(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let sequence_name = "test_sequence".to_owned();
+        let topic_name = "test_sequence/test_topic".to_owned();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, &sequence_name)
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, &topic_name)
+            .await
+            .unwrap();
+
+        let locator = types::TopicResourceLocator::from(&topic_name);
+
+        // Write and register a first chunk
+        let batch = crate::arrow::testing::dummy_batch();
+        let mut writer = rw::ChunkWriter::try_new(batch.schema(), rw::Format::Default).unwrap();
+        writer.write(&batch).unwrap();
+        let (buffer, _, _) = writer.finalize().unwrap();
+        let registered_chunk = locator.datafile(0, &rw::Format::Default);
+        let size = buffer.len() as i64;
+        store.write_bytes(&registered_chunk, buffer).await.unwrap();
+        repo::FacadeChunk::create(topic.id, &registered_chunk, size, 7, None, &repo)
+            .await
+            .unwrap()
+            .finalize()
+            .await
+            .unwrap();
+
+        // Write a stray chunk, never registered in the repository
+        let stray_chunk = locator.datafile(1, &rw::Format::Default);
+        store
+            .write_bytes(&stray_chunk, b"stray data".to_vec())
+            .await
+            .unwrap();
+
+        let action = ActionRequest::try_new(
+            "topic_consistency_check",
+            format!(r#"{{"name": "{}"}}"#, topic_name).as_bytes(),
+        )
+        .unwrap();
+
+        let response = do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+            .await
+            .unwrap();
+
+        if let ActionResponse::TopicConsistencyCheck(report) = response {
+            assert!(!report.is_consistent);
+            assert!(report.missing_chunks.is_empty());
+            assert!(report.size_mismatched_chunks.is_empty());
+            assert_eq!(
+                report.extra_chunks,
+                vec![stray_chunk.to_string_lossy().to_string()]
+            );
+        } else {
+            panic!("wrong response returned")
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking if the creation of a topic with unauthorized name fails.
+    async fn topic_create_unauthorized(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let sequence_name = "test_sequence".to_owned();
+        let topic_name = "test_topic".to_owned();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        // Create a first sequence to then try to create it again
+        let sequence = create_empty_sequence(&repo, &store, &sequence_name)
+            .await
+            .unwrap();
+
+        // This should fail since the topic name is not a child of the sequence
+        let topic = create_empty_topic(&repo, &store, &sequence, &topic_name).await;
+
+        if topic.is_ok() {
+            panic!("the topic creation should fail")
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the system info lists all the compiled-in formats.
+    async fn system_info(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = query::TimeseriesGateway::try_new(store.clone()).unwrap();
+
+        let action = ActionRequest::try_new("system_info", b"{}").unwrap();
+
+        let response = do_action((*store).clone(), repo.clone(), Arc::new(ts_engine), action)
+            .await
+            .unwrap();
+
+        if let ActionResponse::SystemInfo(response) = response {
+            assert_eq!(response.version, env!("CARGO_PKG_VERSION"));
+            assert!(response.formats.contains(&rw::Format::Default));
+            assert!(response.formats.contains(&rw::Format::Ragged));
+            assert!(response.formats.contains(&rw::Format::Image));
+
+            // Configured features are advertised only when enabled
+            let params = crate::params::configurables();
+            assert!(response.features.contains(&"topic_repair".to_owned()));
+            assert_eq!(
+                response.features.contains(&"write_ahead_log".to_owned()),
+                params.wal_dir.is_some()
+            );
+            assert_eq!(response.features, system::features(params));
+        } else {
+            panic!("wrong response returned")
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the last write datetime of a topic advances on each chunk written.
+    async fn topic_last_write_datetime(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let topic_name = "test_sequence/topic";
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+
+        let handle = FacadeTopic::new(topic_name.to_owned(), (*store).clone(), repo.clone());
+
+        write_dummy_chunk(&repo, &store, &topic, topic_name, 0).await;
+        let first = handle
+            .system_info()
+            .await
+            .unwrap()
+            .last_write_datetime
+            .unwrap();
+
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+        write_dummy_chunk(&repo, &store, &topic, topic_name, 1).await;
+        let second = handle
+            .system_info()
+            .await
+            .unwrap()
+            .last_write_datetime
+            .unwrap();
+
+        assert!(second > first);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the batch system info reports missing topics without failing.
+    async fn topic_system_info_batch(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new(store.clone()).unwrap());
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, "test_sequence/topic_1")
+            .await
+            .unwrap();
+        create_empty_topic(&repo, &store, &sequence, "test_sequence/topic_2")
+            .await
+            .unwrap();
+        write_dummy_chunk(&repo, &store, &topic, "test_sequence/topic_1", 0).await;
+
+        let request = serde_json::json!({
+            "names": [
+                "test_sequence/topic_1",
+                "test_sequence/missing",
+                "test_sequence/topic_2",
+            ],
+        });
+        let action =
+            ActionRequest::try_new("topic_system_info_batch", request.to_string().as_bytes())
+                .unwrap();
+
+        let response = do_action((*store).clone(), repo.clone(), ts_engine.clone(), action)
+            .await
+            .unwrap();
+
+        let ActionResponse::TopicSystemInfoBatch(response) = response else {
+            panic!("wrong response returned")
+        };
+
+        let names: Vec<_> = response.topics.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "test_sequence/topic_1",
+                "test_sequence/missing",
+                "test_sequence/topic_2",
+            ]
+        );
+        assert_eq!(
+            response.topics.iter().filter(|t| t.info.is_some()).count(),
+            2
+        );
+        assert!(response.topics[1].not_found);
+        assert!(response.topics[1].info.is_none());
+        assert_eq!(response.topics[0].info.as_ref().unwrap().chunks_number, 1);
+        assert_eq!(response.topics[2].info.as_ref().unwrap().chunks_number, 0);
+
+        // Each topic of the batch is authorized
+        let request = serde_json::json!({ "names": ["test_sequence/topic_1", "private/topic"] });
+        let action =
+            ActionRequest::try_new("topic_system_info_batch", request.to_string().as_bytes())
+                .unwrap();
+        let res = do_action_idempotent(
+            ActionContext::new((*store).clone(), repo.clone(), ts_engine.clone()),
+            &DenyGuestPrivate,
+            &Caller::new("guest"),
+            action,
+            None,
+        )
+        .await;
+        assert!(matches!(
+            res,
+            Err(ServerError::ActionFailed(ActionError::PermissionDenied(_)))
+        ));
+
+        // Too many topics are requested
+        let names = vec!["test_sequence/topic_1"; topic::SYSTEM_INFO_BATCH_MAX_NAMES + 1];
+        let request = serde_json::json!({ "names": names });
+        let action =
+            ActionRequest::try_new("topic_system_info_batch", request.to_string().as_bytes())
+                .unwrap();
+        let res = do_action((*store).clone(), repo.clone(), ts_engine, action).await;
+        assert!(matches!(res, Err(ActionError::InvalidArgument(_))));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that a sequence validates against its own manifest and that a corrupted
+    /// datafile is reported.
+    async fn sequence_manifest(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new(store.clone()).unwrap());
+        let topic_name = "test_sequence/topic";
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        let topic = create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+        write_dummy_chunk(&repo, &store, &topic, topic_name, 0).await;
+        write_dummy_chunk(&repo, &store, &topic, topic_name, 1).await;
+
+        let action =
+            ActionRequest::try_new("sequence_manifest", br#"{"name": "test_sequence"}"#).unwrap();
+        let response = do_action((*store).clone(), repo.clone(), ts_engine.clone(), action)
+            .await
+            .unwrap();
+        let ActionResponse::SequenceManifest(manifest) = response else {
+            panic!("wrong response returned");
+        };
+        assert_eq!(manifest.topics.len(), 1);
+        assert_eq!(manifest.topics[0].chunks_number, 2);
+
+        let body = serde_json::to_vec(&serde_json::json!({ "manifest": manifest })).unwrap();
+        let verify = async || {
+            let action = ActionRequest::try_new("sequence_verify_manifest", &body).unwrap();
+            match do_action((*store).clone(), repo.clone(), ts_engine.clone(), action).await {
+                Ok(ActionResponse::SequenceVerifyManifest(validation)) => validation,
+                _ => panic!("wrong response returned"),
+            }
+        };
+
+        let validation = verify().await;
+        assert!(validation.is_valid, "{:?}", validation.mismatches);
+
+        // The checksums recorded at write time are the digests of the stored datafiles
+        let datafile = &manifest.topics[0].chunks[1].datafile;
+        let mut bytes = store.read_bytes(datafile).await.unwrap();
+        assert_eq!(
+            manifest.topics[0].chunks[1].checksum,
+            utils::hash::to_hex(&utils::hash::sha256(&bytes))
+        );
+
+        // Corrupt a datafile
+        bytes[0] ^= 0xff;
+        store.write_bytes(datafile, bytes).await.unwrap();
+
+        let validation = verify().await;
+        assert!(!validation.is_valid);
+        assert_eq!(validation.mismatches.len(), 1);
+        assert!(validation.mismatches[0].contains("checksum"));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that all the resources are migrated between two in-memory stores with the
+    /// same content, and that a resumed migration copies only the missing objects.
+    async fn system_migrate(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let in_memory = || -> store::StoreRef {
+            Arc::new(store::testing::with_driver(Arc::new(
+                object_store::memory::InMemory::new(),
+            )))
+        };
+        let repo = repo::testing::Repository::new(pool);
+        let (store, target) = (in_memory(), in_memory());
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new(store.clone()).unwrap());
+
+        let sequence = create_empty_sequence(&repo, &store, "migrate")
+            .await
+            .unwrap();
+        for name in ["migrate/topic_a", "migrate/topic_b"] {
+            let topic = create_empty_topic(&repo, &store, &sequence, name)
+                .await
+                .unwrap();
+            write_dummy_chunk(&repo, &store, &topic, name, 0).await;
+        }
+
+        let ctx = ActionContext::new(store.clone(), (*repo).clone(), ts_engine);
+        let migrate = || system::migrate(&ctx, target.clone());
+
+        let ActionResponse::SystemMigrate(response) = migrate().await.unwrap() else {
+            panic!("wrong response returned");
+        };
+        let objects = store.list("migrate", None).await.unwrap();
+        assert_eq!(response.sequences_migrated, 1);
+        assert_eq!(response.objects_copied, objects.len());
+        assert_eq!(response.objects_skipped, 0);
+        for path in &objects {
+            assert_eq!(
+                store.read_bytes(path).await.unwrap(),
+                target.read_bytes(path).await.unwrap()
+            );
+        }
+
+        // Only the object missing from the target is copied when the migration is resumed
+        target.delete(&objects[0]).await.unwrap();
+        let ActionResponse::SystemMigrate(response) = migrate().await.unwrap() else {
+            panic!("wrong response returned");
+        };
+        assert_eq!(response.objects_copied, 1);
+        assert_eq!(response.objects_skipped, objects.len() - 1);
+        assert_eq!(
+            store.read_bytes(&objects[0]).await.unwrap(),
+            target.read_bytes(&objects[0]).await.unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    /// Test checking that filesystem migration targets are rejected unless allowed.
+    fn system_migrate_target() {
+        crate::params::load_configurables_from_env();
+
+        for url in ["file:///tmp/target", "s3://bucket/../../../tmp/target"] {
+            let res = system::migration_target(url);
+            assert_eq!(
+                matches!(res, Err(ActionError::PermissionDenied(_))),
+                url.starts_with("file"),
+                "{url}"
+            );
+        }
+    }
+
+    #[sqlx::test]
+    /// Test checking that a locked two-topic sequence is cloned with identical aggregated
+    /// topic stats, and that an existing destination is rejected.
+    async fn sequence_clone(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new(store.clone()).unwrap());
+
+        let sequence = create_empty_sequence(&repo, &store, "source")
+            .await
+            .unwrap();
+        for (name, chunks) in [("source/topic_a", 1), ("source/topic_b", 2)] {
+            let topic = create_empty_topic(&repo, &store, &sequence, name)
+                .await
+                .unwrap();
+            for idx in 0..chunks {
+                write_dummy_chunk(&repo, &store, &topic, name, idx).await;
+            }
+            FacadeTopic::new(name.to_owned(), (*store).clone(), repo.clone())
+                .lock()
+                .await
+                .unwrap();
+        }
+        FacadeSequence::new("source".to_owned(), (*store).clone(), repo.clone())
+            .lock()
+            .await
+            .unwrap();
+
+        let body = br#"{"source": "source", "dest": "dest"}"#;
+        let action = ActionRequest::try_new("sequence_clone", body).unwrap();
+        let response = do_action((*store).clone(), repo.clone(), ts_engine.clone(), action)
+            .await
+            .unwrap();
+        let ActionResponse::SequenceClone(response) = response else {
+            panic!("wrong response returned");
+        };
+        assert_eq!(response.topics_cloned, 2);
+        assert_eq!(response.chunks_cloned, 3);
+
+        for name in ["topic_a", "topic_b"] {
+            let stats = |sequence: &str| {
+                let handle =
+                    FacadeTopic::new(format!("{sequence}/{name}"), (*store).clone(), repo.clone());
+                async move { handle.chunks_stats().await.unwrap() }
+            };
+            let (source, dest) = (stats("source").await, stats("dest").await);
+            assert_eq!(source.total_size_bytes, dest.total_size_bytes);
+            assert_eq!(source.total_row_count, dest.total_row_count);
+        }
+        assert!(
+            FacadeSequence::new("dest".to_owned(), (*store).clone(), repo.clone())
+                .is_locked()
+                .await
+                .unwrap()
+        );
+
+        let action = ActionRequest::try_new("sequence_clone", body).unwrap();
+        let res = do_action((*store).clone(), repo.clone(), ts_engine.clone(), action).await;
+        assert!(matches!(res, Err(ActionError::AlreadyExists(_))));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that a failed clone leaves no partial destination behind.
+    async fn sequence_clone_failure(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new(store.clone()).unwrap());
+
+        let sequence = create_empty_sequence(&repo, &store, "source")
+            .await
+            .unwrap();
+        for name in ["source/topic_a", "source/topic_b"] {
+            let topic = create_empty_topic(&repo, &store, &sequence, name)
+                .await
+                .unwrap();
+            write_dummy_chunk(&repo, &store, &topic, name, 0).await;
+        }
+
+        // The datafile of one topic is lost, its copy fails
+        let datafile =
+            types::TopicResourceLocator::from("source/topic_b").datafile(0, &rw::Format::Default);
+        store.delete(&datafile).await.unwrap();
+
+        let body = br#"{"source": "source", "dest": "dest"}"#;
+        let action = ActionRequest::try_new("sequence_clone", body).unwrap();
+        do_action((*store).clone(), repo.clone(), ts_engine.clone(), action)
+            .await
+            .unwrap_err();
+
+        for name in ["dest/topic_a", "dest/topic_b"] {
+            let handle = FacadeTopic::new(name.to_owned(), (*store).clone(), repo.clone());
+            assert!(handle.resource_id().await.is_err());
+        }
+        let dest = FacadeSequence::new("dest".to_owned(), (*store).clone(), repo.clone());
+        assert!(dest.resource_id().await.is_err());
+        assert!(store.list("dest", None).await.unwrap().is_empty());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the description of the query action lists exactly the fields of its
+    /// body, including the time range filter and not the projection (an option of the flight
+    /// tickets).
+    async fn describe_action(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new(store.clone()).unwrap());
+
+        let action = ActionRequest::try_new("describe_action", br#"{"name": "query"}"#).unwrap();
+        let response = do_action((*store).clone(), repo.clone(), ts_engine.clone(), action)
+            .await
+            .unwrap();
+
+        if let ActionResponse::DescribeAction(response) = response {
+            assert_eq!(response.name, "query");
+            assert_eq!(response.schema["type"], "object");
+
+            let properties = response.schema["properties"].as_object().unwrap();
+            let mut fields: Vec<&str> = properties.keys().map(String::as_str).collect();
+            fields.sort();
+            assert_eq!(
+                fields,
+                ["count_only", "ontology", "schema_only", "sequence", "topic"]
+            );
+
+            let ontology = &properties["ontology"]["properties"];
+            assert!(ontology.get("range").is_some());
+        } else {
+            panic!("wrong response returned")
+        }
+
+        let action =
+            ActionRequest::try_new("describe_action", br#"{"name": "not_an_action"}"#).unwrap();
+        let err = do_action((*store).clone(), repo.clone(), ts_engine, action)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ActionError::NotFound(_)));
+
+        Ok(())
+    }
+
+    /// Runs a `delete_prefix` action with the provided body.
+    async fn delete_prefix(
+        repo: &repo::testing::Repository,
+        store: &store::testing::Store,
+        body: serde_json::Value,
+    ) -> Result<marshal::responses::DeletePrefix, ActionError> {
+        let ts_engine = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+
+        let action = ActionRequest::try_new("delete_prefix", body.to_string().as_bytes()).unwrap();
+
+        let response = do_action(
+            (*store).clone(),
+            (*repo).clone(),
+            Arc::new(ts_engine),
+            action,
+        )
+        .await?;
+
+        if let ActionResponse::DeletePrefix(response) = response {
+            Ok(response)
+        } else {
+            panic!("wrong response returned")
+        }
+    }
+
+    #[sqlx::test]
+    /// Test checking that a dry run lists the matching resources without deleting them.
+    async fn delete_prefix_dry_run(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_a")
+            .await
+            .unwrap();
+        create_empty_topic(&repo, &store, &sequence, "test_a/topic")
+            .await
+            .unwrap();
+        create_empty_sequence(&repo, &store, "other").await.unwrap();
+
+        let response = delete_prefix(
+            &repo,
+            &store,
+            serde_json::json!({"prefix": "test_", "dry_run": true}),
+        )
+        .await
+        .unwrap();
+
+        assert!(response.dry_run);
+        assert_eq!(response.deleted.len(), 2);
+        assert!(response.deleted.contains(&"test_a".to_owned()));
+        assert!(response.deleted.contains(&"test_a/topic".to_owned()));
+
+        // Nothing has been deleted
+        let handle = FacadeSequence::new("test_a".to_owned(), (*store).clone(), repo.clone());
+        assert!(handle.resource_id().await.is_ok());
+        let handle = FacadeTopic::new("test_a/topic".to_owned(), (*store).clone(), repo.clone());
+        assert!(handle.resource_id().await.is_ok());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that only the resources matching the prefix are deleted and that locked
+    /// resources are deleted only if forced.
+    async fn delete_prefix_match(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        create_empty_sequence(&repo, &store, "test_a")
+            .await
+            .unwrap();
+        let sequence = create_empty_sequence(&repo, &store, "other").await.unwrap();
+        create_empty_topic(&repo, &store, &sequence, "other/test_camera")
+            .await
+            .unwrap();
+        create_empty_topic(&repo, &store, &sequence, "other/lidar")
+            .await
+            .unwrap();
+
+        let response = delete_prefix(&repo, &store, serde_json::json!({"prefix": "test_"}))
+            .await
+            .unwrap();
+
+        assert!(!response.dry_run);
+        assert_eq!(response.deleted, vec!["test_a".to_owned()]);
+
+        let handle = FacadeSequence::new("test_a".to_owned(), (*store).clone(), repo.clone());
+        assert!(handle.resource_id().await.is_err());
+
+        // Match a single topic, which is locked
+        let handle = FacadeTopic::new(
+            "other/test_camera".to_owned(),
+            (*store).clone(),
+            repo.clone(),
+        );
+        handle.lock().await.unwrap();
+
+        let res = delete_prefix(&repo, &store, serde_json::json!({"prefix": "other/test"})).await;
+        assert!(matches!(res, Err(ActionError::ResourceLocked(_))));
+        assert!(handle.resource_id().await.is_ok());
+
+        let response = delete_prefix(
+            &repo,
+            &store,
+            serde_json::json!({"prefix": "other/test", "force": true}),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.deleted, vec!["other/test_camera".to_owned()]);
+        assert!(handle.resource_id().await.is_err());
+
+        let handle = FacadeTopic::new("other/lidar".to_owned(), (*store).clone(), repo.clone());
+        assert!(handle.resource_id().await.is_ok());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that an empty prefix is refused unless explicitly allowed.
+    async fn delete_prefix_empty_guard(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        create_empty_sequence(&repo, &store, "test_a")
+            .await
+            .unwrap();
+
+        let res = delete_prefix(&repo, &store, serde_json::json!({"prefix": ""})).await;
+        assert!(matches!(res, Err(ActionError::InvalidArgument(_))));
+
+        let handle = FacadeSequence::new("test_a".to_owned(), (*store).clone(), repo.clone());
+        assert!(handle.resource_id().await.is_ok());
+
+        let response = delete_prefix(
+            &repo,
+            &store,
+            serde_json::json!({"prefix": "", "allow_empty_prefix": true}),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.deleted, vec!["test_a".to_owned()]);
+        assert!(handle.resource_id().await.is_err());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that metadata files are read and written at the location provided by a
+    /// custom resolver, instead of next to the resource data.
+    async fn metadata_path_override(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        /// Places all metadata files in a flat `meta` folder
+        struct FlatMetadataPath;
+
+        impl repo::MetadataPathResolver for FlatMetadataPath {
+            fn metadata_path(&self, resource: &dyn types::Resource) -> std::path::PathBuf {
+                format!("meta/{}.json", resource.name().replace('/', "_")).into()
+            }
+        }
+
+        let topic_name = "test_sequence/test_topic";
+
+        let repo =
+            repo::testing::Repository::new(pool).with_metadata_resolver(Arc::new(FlatMetadataPath));
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+
+        // Metadata is written at the overridden path only
+        let locator = types::TopicResourceLocator::from(topic_name);
+        assert!(
+            store
+                .read_bytes("meta/test_sequence_test_topic.json")
+                .await
+                .is_ok()
+        );
+        assert!(store.read_bytes(locator.metadata()).await.is_err());
+        assert!(store.read_bytes("meta/test_sequence.json").await.is_ok());
+
+        // and read back from it
+        let handle = FacadeTopic::new(topic_name.to_owned(), (*store).clone(), repo.clone());
+        let metadata = handle.metadata().await.unwrap();
+        assert_eq!(metadata.properties.ontology_tag, "test_tag");
+
+        // Deleting the topic removes the relocated metadata file as well
+        handle.delete().await.unwrap();
+        assert!(
+            store
+                .read_bytes("meta/test_sequence_test_topic.json")
+                .await
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    /// Authorizer denying the deletion of topics to the `guest` caller
+    struct DenyGuestDelete;
+
+    impl Authorizer for DenyGuestDelete {
+        fn authorize(
+            &self,
+            caller: &Caller,
+            operation: Operation<'_>,
+            _resource: Option<&str>,
+            _access: auth::Access,
+        ) -> auth::Decision {
+            match (caller.identity.as_deref(), operation) {
+                (Some("guest"), Operation::Action(ActionRequest::TopicDelete(_))) => {
+                    auth::Decision::Deny
+                }
+                _ => auth::Decision::Allow,
+            }
+        }
+    }
+
+    /// Authorizer denying to the `guest` caller any access to the resources under `private`
+    struct DenyGuestPrivate;
+
+    impl Authorizer for DenyGuestPrivate {
+        fn authorize(
+            &self,
+            caller: &Caller,
+            _operation: Operation<'_>,
+            resource: Option<&str>,
+            _access: auth::Access,
+        ) -> auth::Decision {
+            match (caller.identity.as_deref(), resource) {
+                (Some("guest"), Some(name)) if name.starts_with("private") => auth::Decision::Deny,
+                _ => auth::Decision::Allow,
+            }
+        }
+    }
+
+    #[sqlx::test]
+    /// Test checking that the authorizer gates the deletion of a topic.
+    async fn topic_delete_authorization(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new(store.clone()).unwrap());
+        let topic_name = "test_sequence/topic";
+
+        let sequence = create_empty_sequence(&repo, &store, "test_sequence")
+            .await
+            .unwrap();
+        create_empty_topic(&repo, &store, &sequence, topic_name)
+            .await
+            .unwrap();
+
+        let body = format!(r#"{{"name": "{topic_name}"}}"#);
+        let handle = FacadeTopic::new(topic_name.to_owned(), (*store).clone(), repo.clone());
+
+        // Denied, the topic is left untouched
+        let action = ActionRequest::try_new("topic_delete", body.as_bytes()).unwrap();
+        let res = do_action_idempotent(
+            ActionContext::new((*store).clone(), repo.clone(), ts_engine.clone()),
+            &DenyGuestDelete,
+            &Caller::new("guest"),
+            action,
+            None,
+        )
+        .await;
+        assert!(matches!(
+            res,
+            Err(ServerError::ActionFailed(ActionError::PermissionDenied(_)))
+        ));
+        assert!(handle.resource_id().await.is_ok());
+
+        // Allowed, the topic is deleted
+        let action = ActionRequest::try_new("topic_delete", body.as_bytes()).unwrap();
+        do_action_idempotent(
+            ActionContext::new((*store).clone(), repo.clone(), ts_engine.clone()),
+            &DenyGuestDelete,
+            &Caller::new("admin"),
+            action,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(handle.resource_id().await.is_err());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the source of a clone is authorized along with its destination.
+    async fn sequence_clone_authorization(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new(store.clone()).unwrap());
+
+        create_empty_sequence(&repo, &store, "private_sequence")
+            .await
+            .unwrap();
+
+        let body = r#"{"source": "private_sequence", "dest": "public_copy"}"#;
+        let dest = FacadeSequence::new("public_copy".to_owned(), (*store).clone(), repo.clone());
+
+        // The source can't be read by the guest, nothing is cloned
+        let action = ActionRequest::try_new("sequence_clone", body.as_bytes()).unwrap();
+        let res = do_action_idempotent(
+            ActionContext::new((*store).clone(), repo.clone(), ts_engine.clone()),
+            &DenyGuestPrivate,
+            &Caller::new("guest"),
+            action,
+            None,
+        )
+        .await;
+        assert!(matches!(
+            res,
+            Err(ServerError::ActionFailed(ActionError::PermissionDenied(_)))
+        ));
+        assert!(dest.resource_id().await.is_err());
+
+        let action = ActionRequest::try_new("sequence_clone", body.as_bytes()).unwrap();
+        do_action_idempotent(
+            ActionContext::new((*store).clone(), repo.clone(), ts_engine.clone()),
+            &DenyGuestPrivate,
+            &Caller::new("owner"),
+            action,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(dest.resource_id().await.is_ok());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the actions administering the system are refused to the callers
+    /// not configured as administrators, even if the authorizer allows everything.
+    async fn system_actions_require_admin(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        crate::params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new(store.clone()).unwrap());
+
+        let requests: [(&str, &[u8]); 2] = [
+            ("system_vacuum", br#"{"older_than_secs": 0}"#),
+            ("system_migrate", br#"{"target": "s3://bucket/backup"}"#),
+        ];
+        for (name, body) in requests {
+            let action = ActionRequest::try_new(name, body).unwrap();
+            assert_eq!(action.targets(), vec![(None, auth::Access::Admin)]);
+
+            let res = do_action_idempotent(
+                ActionContext::new((*store).clone(), repo.clone(), ts_engine.clone()),
+                &auth::AllowAll,
+                &Caller::new("guest"),
+                action,
+                None,
+            )
+            .await;
+            assert!(
+                matches!(
+                    res,
+                    Err(ServerError::ActionFailed(ActionError::PermissionDenied(_)))
+                ),
+                "{name}"
+            );
+        }
+
+        let admins = vec!["root".to_owned()];
+        assert!(auth::is_admin(&Caller::new("root"), &admins));
+        assert!(!auth::is_admin(&Caller::new("guest"), &admins));
+        assert!(!auth::is_admin(&Caller::anonymous(), &admins));
+
         Ok(())
     }
 }
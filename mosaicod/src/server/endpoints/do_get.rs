@@ -1,63 +1,714 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use arrow::array::{AsArray, RecordBatch};
+use arrow::datatypes::{Int64Type, SchemaRef};
+use arrow::ipc::{self, writer::IpcWriteOptions};
 use arrow_flight::{
-    Ticket,
-    encode::{FlightDataEncoder, FlightDataEncoderBuilder},
+    FlightData, Ticket,
+    encode::{DictionaryHandling, FlightDataEncoder, FlightDataEncoderBuilder},
     error::FlightError,
 };
 
-use futures::TryStreamExt;
-use log::{info, trace};
+use futures::{Stream, StreamExt, TryStreamExt, stream::BoxStream};
+use log::{info, trace, warn};
 
-use crate::{marshal, params, query, repo, server::errors::ServerError, store, types::Resource};
+use crate::{
+    marshal, params, query, repo,
+    server::{
+        auth::{self, Access, Authorizer, Caller, Operation},
+        errors::ServerError,
+        limiter::Limiter,
+    },
+    store,
+    types::{self, Resource},
+};
 
+/// Streams the data requested by `ticket`, if `authorizer` allows `caller` to read the topic.
 pub async fn do_get(
     store: store::StoreRef,
     repo: repo::Repository,
     ts_engine: query::TimeseriesGatewayRef,
+    limiter: &Limiter,
+    authorizer: &dyn Authorizer,
+    caller: &Caller,
     ticket: Ticket,
-) -> Result<FlightDataEncoder, ServerError> {
-    let ticket = String::from_utf8(ticket.ticket.to_vec())
+) -> Result<BoxStream<'static, Result<FlightData, FlightError>>, ServerError> {
+    let mut ticket = marshal::flight::do_get_ticket(&ticket.ticket)
         .map_err(|e| ServerError::BadTicket(e.to_string()))?;
 
-    info!("requesting data for ticket `{}`", ticket);
+    info!("requesting data for ticket `{}`", ticket.resource_locator);
+
+    auth::check(
+        authorizer,
+        caller,
+        Operation::Query,
+        Some(&ticket.resource_locator),
+        Access::Read,
+    )?;
+
+    // The slot of the topic is held until the data stream is dropped
+    let permit = limiter.acquire(&ticket.resource_locator).await?;
+
+    // Register the query before doing any work, so that it can be cancelled at any time by
+    // the same caller
+    let running = ticket
+        .request_token
+        .as_deref()
+        .map(|token| {
+            let owner = caller.identity.as_deref();
+            ts_engine.running().register(token, owner).ok_or_else(|| {
+                ServerError::BadTicket(format!("request token `{token}` already in use"))
+            })
+        })
+        .transpose()?;
+
+    let timeout = params::configurables()
+        .query_timeout_secs
+        .map(std::time::Duration::from_secs);
+    let started = tokio::time::Instant::now();
 
     // Create topic handle
-    let topic = ticket;
-    let tfacade = repo::FacadeTopic::new(topic, store, repo.clone());
+    let topic = ticket.resource_locator;
+    let tfacade = repo::FacadeTopic::new(topic, store.clone(), repo.clone());
 
     // Read metadata from topic
     let metadata = tfacade.metadata().await?;
 
     trace!("{:?}", metadata);
 
+    // The time column is stored as the timestamp field, the client refers to it and receives
+    // it by its own name
+    let time_column = metadata.properties.time_column.clone();
+    if let Some(time_column) = &time_column {
+        resolve_time_column(
+            time_column,
+            &mut ticket.filter,
+            &mut ticket.order_by,
+            &mut ticket.projection,
+        );
+    }
+
     // Compute optimal batch size from database statistics
     let batch_size = compute_optimal_batch_size(&tfacade).await?;
 
-    let query_result = ts_engine
-        .read(
-            &tfacade.locator.name(),
-            metadata.properties.serialization_format,
-            batch_size,
-        )
-        .await?;
+    // With chunk attribution or per chunk provenance columns each chunk is read by a separate
+    // query, topics without datafiles are read as a whole
+    let per_chunk = ticket.chunk_attribution
+        || ticket
+            .provenance
+            .iter()
+            .any(query::ProvenanceColumn::is_per_chunk);
+    let format = metadata.properties.serialization_format;
+    let mut sources = Vec::new();
+    if per_chunk {
+        sources = ts_engine
+            .read_by_datafile(&tfacade.locator.name(), format, batch_size)
+            .await?
+            .into_iter()
+            .map(|(datafile, result)| (types::datafile_index(datafile), result))
+            .collect();
+    }
+    if sources.is_empty() {
+        sources.push((
+            None,
+            ts_engine
+                .read(&tfacade.locator.name(), format, batch_size)
+                .await?,
+        ));
+    }
+
+    // Chunks written by older versions have no write timestamp, the topic creation is used
+    let write_timestamps = if ticket
+        .provenance
+        .contains(&query::ProvenanceColumn::IngestedAt)
+    {
+        Some((
+            tfacade.chunks_write_timestamps().await?,
+            tfacade.creation_timestamp().await?,
+        ))
+    } else {
+        None
+    };
 
     // Append JSON metadata to original data schema
     let metadata = marshal::JsonTopicMetadata::from(metadata);
     let flatten_mdata = metadata
         .to_flat_hashmap()
         .map_err(repo::FacadeError::from)?;
-    let schema = query_result.schema_with_metadata(flatten_mdata);
+
+    let mut schema = None;
+    let mut queries = Vec::with_capacity(sources.len());
+    for (chunk_index, query_result) in sources {
+        // Provenance columns requested by the client
+        let provenance: Vec<_> = ticket
+            .provenance
+            .iter()
+            .map(|&column| {
+                let value = match column {
+                    query::ProvenanceColumn::Topic => {
+                        query::Value::Text(tfacade.locator.name().clone())
+                    }
+                    query::ProvenanceColumn::Chunk => {
+                        query::Value::Integer(chunk_index.unwrap_or_default() as i64)
+                    }
+                    query::ProvenanceColumn::IngestedAt => {
+                        let (timestamps, topic_creation) =
+                            write_timestamps.as_ref().expect("write timestamps loaded");
+                        let timestamp = chunk_index
+                            .and_then(|index| timestamps.get(&index))
+                            .unwrap_or(topic_creation);
+                        query::Value::Integer((*timestamp).into())
+                    }
+                };
+                (column, value)
+            })
+            .collect();
+
+        let query_result = prepare_query(
+            query_result,
+            ticket.filter.clone(),
+            ticket.order_by.as_ref(),
+            &ticket.projection,
+            time_column.as_deref(),
+            &provenance,
+        )?;
+
+        // Every source has the unified schema of the topic datafiles
+        if schema.is_none() {
+            schema = Some(query_result.schema_with_metadata(flatten_mdata.clone()));
+        }
+
+        queries.push((chunk_index, query_result));
+    }
+    let schema = schema.expect("at least one data source");
+
+    // The stride is computed from the chunk statistics to avoid scanning the data, unless
+    // the filter discards some of the rows, then only the matching rows are counted
+    let stride = match ticket.sample {
+        Some(sample) => {
+            let total_rows = if ticket.filter.is_none() {
+                tfacade.chunks_stats().await?.total_row_count as usize
+            } else {
+                let mut total_rows = 0;
+                for (_, query_result) in &queries {
+                    total_rows += query_result.matching_rows().await?;
+                }
+                total_rows
+            };
+            Some(sample.stride(total_rows))
+        }
+        None => None,
+    };
+
+    let mut streams = Vec::with_capacity(queries.len());
+    let mut probes = Vec::with_capacity(queries.len());
+    for (chunk_index, query_result) in queries {
+        // Get data stream from query result
+        let (stream, probe) = query_result.probed_stream().await?;
+        let stream = if let Some(stride) = stride {
+            trace!("sampling one row every {} rows", stride);
+            query::sample_stream(stream, stride)
+        } else {
+            stream
+        };
+
+        let stream = if ticket.chunk_attribution {
+            stream
+                .map_ok(move |batch| tag_chunk_index(batch, chunk_index))
+                .boxed()
+        } else {
+            stream
+        };
+
+        streams.push(stream.boxed());
+        probes.push(probe);
+    }
+    let probe = query::ScanProbe::merge(probes);
 
     trace!("{:?}", schema);
 
-    // Get data stream from query result
-    let stream = query_result.stream().await?;
+    let stream = futures::stream::iter(streams).flatten();
 
     // Convert the data stream to a flight stream casting the returned error
     let stream = stream.map_err(|e| FlightError::ExternalError(Box::new(e)));
 
-    Ok(FlightDataEncoderBuilder::new()
+    let stream = if let Some(running) = running {
+        with_cancellation(stream, running).boxed()
+    } else {
+        stream.boxed()
+    };
+
+    let truncation = Arc::new(Mutex::new(None));
+    let stream = if let Some(timeout) = timeout {
+        let deadline = Deadline {
+            at: started + timeout,
+            timeout,
+            partial: ticket.partial_on_timeout,
+        };
+        with_deadline(stream, deadline, truncation.clone()).boxed()
+    } else {
+        stream.boxed()
+    };
+
+    let batching = resolve_batching(
+        ticket.result_batch_rows,
+        ticket.min_batch_rows,
+        params::configurables().max_result_batch_rows,
+    );
+    let stream = if let Some((min_rows, max_rows)) = batching {
+        trace!(
+            "re-chunking the data in batches of {} to {:?} rows",
+            min_rows, max_rows
+        );
+        rebatch(stream, min_rows, max_rows).boxed()
+    } else {
+        stream
+    };
+
+    let rows_returned = Arc::new(AtomicUsize::new(0));
+    let stream = if ticket.scan_stats {
+        count_rows(stream, rows_returned.clone()).boxed()
+    } else {
+        stream
+    };
+
+    let encoder = if ticket.chunk_attribution {
+        attributed_encoder(schema, stream, ticket.dictionary_reuse).boxed()
+    } else {
+        flight_encoder(schema, stream, ticket.dictionary_reuse).boxed()
+    };
+    let encoder = encoder.chain(partial_trailer(truncation));
+    let access = AccessRecorder {
+        topic: tfacade.locator.name().clone(),
+        store,
+        repo,
+        probe: probe.clone(),
+    };
+    let encoder = if ticket.scan_stats {
+        encoder.chain(stats_trailer(probe, rows_returned)).boxed()
+    } else {
+        encoder.boxed()
+    };
+
+    Ok(encoder
+        .inspect(move |_| {
+            let _permit = &permit;
+            let _access = &access;
+        })
+        .boxed())
+}
+
+/// Records the access to the datafiles scanned by a query when its data stream is dropped,
+/// see [`repo::FacadeTopic::touch`].
+///
+/// The access time drives the tiering of the chunks. It is recorded by a background task with
+/// a single update, so that it doesn't delay the data stream, and a failure doesn't affect the
+/// query.
+struct AccessRecorder {
+    topic: String,
+    store: store::StoreRef,
+    repo: repo::Repository,
+    probe: query::ScanProbe,
+}
+
+impl Drop for AccessRecorder {
+    fn drop(&mut self) {
+        let datafiles: Vec<_> = self
+            .probe
+            .scanned_datafiles()
+            .iter()
+            .map(|key| self.store.resource_path(key))
+            .collect();
+        if datafiles.is_empty() {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            warn!("unable to record the access to {}: no runtime", self.topic);
+            return;
+        };
+
+        let handle = repo::FacadeTopic::new(
+            std::mem::take(&mut self.topic),
+            self.store.clone(),
+            self.repo.clone(),
+        );
+        runtime.spawn(async move {
+            if let Err(e) = handle.touch(&datafiles).await {
+                warn!("unable to record the access to {}: {}", handle.locator, e);
+            }
+        });
+    }
+}
+
+/// Applies to `query_result` the data options of a ticket: the row filter, the order, the
+/// projection and the provenance columns.
+fn prepare_query(
+    query_result: query::TimeseriesGatewayResult,
+    filter: Option<query::RowFilter>,
+    order_by: Option<&query::OrderBy>,
+    projection: &[String],
+    time_column: Option<&str>,
+    provenance: &[(query::ProvenanceColumn, query::Value)],
+) -> Result<query::TimeseriesGatewayResult, ServerError> {
+    let mut query_result = if let Some(filter) = filter {
+        trace!("applying row filter: {:?}", filter);
+        query_result.filter_rows(filter)?
+    } else {
+        query_result
+    };
+
+    // The sort is applied before the projection since the sort column may be unprojected,
+    // without an explicit order rows are returned by ascending timestamp
+    if let Some(order_by) = order_by {
+        trace!("sorting by {:?}", order_by);
+        query_result = query_result.sort(order_by)?;
+    }
+
+    // Restrict the data to the requested columns, the projection is validated against the
+    // topic schema and applied after the filter since it may constrain unprojected columns
+    if !projection.is_empty() {
+        trace!("applying projection: {:?}", projection);
+        query_result = query_result.project(projection)?;
+    }
+
+    // The timestamp field is returned under the time column name of the topic
+    if let Some(time_column) = time_column {
+        query_result =
+            query_result.rename_column(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP, time_column)?;
+    }
+
+    // Append provenance columns requested by the client
+    for (column, value) in provenance {
+        query_result = query_result.with_constant_column(column.name(), value.clone())?;
+    }
+
+    Ok(query_result)
+}
+
+/// Replaces the references to `time_column` in the filter, sort and projection of a query with
+/// the timestamp field the time column is stored as (see [`crate::arrow::rename_time_column`]).
+fn resolve_time_column(
+    time_column: &str,
+    filter: &mut Option<query::RowFilter>,
+    order_by: &mut Option<query::OrderBy>,
+    projection: &mut [String],
+) {
+    let resolve = |column: &mut String| {
+        if column == time_column {
+            *column = params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP.to_owned();
+        }
+    };
+
+    filter
+        .iter_mut()
+        .flat_map(|filter| filter.group.iter_mut())
+        .for_each(|expr| resolve(&mut expr.column));
+    order_by
+        .iter_mut()
+        .for_each(|order_by| resolve(&mut order_by.column));
+    projection.iter_mut().for_each(resolve);
+}
+
+/// Key of the batch schema metadata holding the index of the chunk the rows were read from
+const CHUNK_INDEX_METADATA_KEY: &str = "mosaico:chunk_index";
+
+/// Records in the schema metadata of `batch` the index of the chunk holding its rows, read
+/// back by [`attributed_encoder`]. Datafiles whose name carries no index are left untagged.
+fn tag_chunk_index(batch: RecordBatch, chunk_index: Option<usize>) -> RecordBatch {
+    let Some(chunk_index) = chunk_index else {
+        return batch;
+    };
+
+    let mut metadata = batch.schema_ref().metadata().clone();
+    metadata.insert(CHUNK_INDEX_METADATA_KEY.to_owned(), chunk_index.to_string());
+    let schema = Arc::new(batch.schema_ref().as_ref().clone().with_metadata(metadata));
+    batch
         .with_schema(schema)
-        .build(stream))
+        .expect("only the schema metadata is changed")
+}
+
+/// Builds the encoder of a data stream with chunk attribution, setting the `app_metadata` of
+/// the data messages to the index of the chunk holding their rows, as tagged on each batch by
+/// [`tag_chunk_index`].
+///
+/// Each batch is encoded on its own, so every message of a batch (e.g. the messages a large
+/// batch is split into or its dictionaries) carries its chunk. As a consequence with
+/// `dictionary_reuse` the dictionaries are sent again with every batch.
+fn attributed_encoder(
+    schema: SchemaRef,
+    stream: impl Stream<Item = Result<RecordBatch, FlightError>> + Send + 'static,
+    dictionary_reuse: bool,
+) -> impl Stream<Item = Result<FlightData, FlightError>> + Send + 'static {
+    // An encoder without batches only sends the schema
+    let schema_message = flight_encoder(schema.clone(), futures::stream::empty(), dictionary_reuse);
+
+    let data = stream
+        .map(move |batch| {
+            let batch = batch?;
+            let chunk_index = batch
+                .schema_ref()
+                .metadata()
+                .get(CHUNK_INDEX_METADATA_KEY)
+                .and_then(|index| index.parse().ok());
+            let metadata = marshal::flight::do_get_chunk_to_bytes(chunk_index)
+                .map_err(|e| FlightError::ExternalError(Box::new(e)))?;
+
+            // The first message of each encoder is the schema, already sent
+            let messages = flight_encoder(
+                schema.clone(),
+                futures::stream::iter([Ok(batch)]),
+                dictionary_reuse,
+            )
+            .skip(1)
+            .map_ok(move |data| data.with_app_metadata(metadata.clone()));
+            Ok::<_, FlightError>(messages)
+        })
+        .try_flatten();
+
+    schema_message.chain(data)
+}
+
+/// Deadline of the data stream of a query
+#[derive(Clone, Copy)]
+struct Deadline {
+    at: tokio::time::Instant,
+    timeout: std::time::Duration,
+    /// If true the stream is truncated when the deadline is hit, otherwise it fails
+    partial: bool,
+}
+
+/// Point where a stream was truncated by its deadline
+struct Truncation {
+    /// Timestamp of the last row returned, [`None`] if no row was returned or the data has no
+    /// timestamp column (e.g. projected out)
+    last_timestamp: Option<i64>,
+}
+
+/// Bounds the data stream with `deadline`.
+///
+/// When the deadline is hit the stream fails, or if [`Deadline::partial`] is set it ends
+/// recording in `truncation` the last timestamp returned.
+fn with_deadline(
+    stream: impl Stream<Item = Result<RecordBatch, FlightError>> + Send + 'static,
+    deadline: Deadline,
+    truncation: Arc<Mutex<Option<Truncation>>>,
+) -> impl Stream<Item = Result<RecordBatch, FlightError>> + Send + 'static {
+    let state = (stream.boxed(), None::<i64>, false);
+
+    futures::stream::unfold(state, move |(mut input, last_timestamp, done)| {
+        let truncation = truncation.clone();
+        async move {
+            if done {
+                return None;
+            }
+
+            match tokio::time::timeout_at(deadline.at, input.next()).await {
+                Ok(Some(Ok(batch))) => {
+                    let last_timestamp = last_timestamp_of(&batch).or(last_timestamp);
+                    Some((Ok(batch), (input, last_timestamp, false)))
+                }
+                Ok(Some(Err(e))) => Some((Err(e), (input, last_timestamp, true))),
+                Ok(None) => None,
+                Err(_) if deadline.partial => {
+                    warn!("query timed out, returning a partial result");
+                    *truncation.lock().unwrap() = Some(Truncation { last_timestamp });
+                    None
+                }
+                Err(_) => {
+                    let e = ServerError::QueryTimeout(deadline.timeout.as_secs());
+                    Some((
+                        Err(FlightError::ExternalError(Box::new(e))),
+                        (input, last_timestamp, true),
+                    ))
+                }
+            }
+        }
+    })
+}
+
+/// Stops the data stream when the cancellation of the `running` query is requested, failing
+/// it with a [`ServerError::QueryCancelled`].
+///
+/// The query is registered until the stream is dropped, dropping the input stream releases the
+/// resources held by the query engine.
+fn with_cancellation(
+    stream: impl Stream<Item = Result<RecordBatch, FlightError>> + Send + 'static,
+    running: query::RunningQuery,
+) -> impl Stream<Item = Result<RecordBatch, FlightError>> + Send + 'static {
+    let cancellation = running.cancellation();
+    let state = (Some(stream.boxed()), running);
+
+    futures::stream::unfold(state, move |(input, running)| {
+        let cancellation = cancellation.clone();
+        async move {
+            let mut input = input?;
+
+            tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => {
+                    warn!("query cancelled by the client");
+                    let e = ServerError::QueryCancelled;
+                    Some((Err(FlightError::ExternalError(Box::new(e))), (None, running)))
+                }
+                item = input.next() => item.map(|item| (item, (Some(input), running))),
+            }
+        }
+    })
+}
+
+/// Returns the timestamp of the last row of `batch`, if the batch has a timestamp column.
+fn last_timestamp_of(batch: &RecordBatch) -> Option<i64> {
+    let timestamps = batch
+        .column_by_name(params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP)?
+        .as_primitive_opt::<Int64Type>()?;
+
+    (!timestamps.is_empty()).then(|| timestamps.value(timestamps.len() - 1))
+}
+
+/// Returns the message closing a stream truncated by its deadline, empty if the stream was
+/// not truncated. The message has no data and carries the truncation in its `app_metadata`.
+fn partial_trailer(
+    truncation: Arc<Mutex<Option<Truncation>>>,
+) -> impl Stream<Item = Result<FlightData, FlightError>> + Send + 'static {
+    futures::stream::once(async move { truncation.lock().unwrap().take() }).filter_map(
+        |truncation| async move {
+            let truncation = truncation?;
+            Some(
+                marshal::flight::do_get_partial_to_bytes(truncation.last_timestamp)
+                    .map(|metadata| FlightData::new().with_app_metadata(metadata))
+                    .map_err(|e| FlightError::ExternalError(Box::new(e))),
+            )
+        },
+    )
+}
+
+/// Adds to `rows` the number of rows of each batch of the data stream.
+fn count_rows(
+    stream: impl Stream<Item = Result<RecordBatch, FlightError>> + Send + 'static,
+    rows: Arc<AtomicUsize>,
+) -> impl Stream<Item = Result<RecordBatch, FlightError>> + Send + 'static {
+    stream.inspect_ok(move |batch| {
+        rows.fetch_add(batch.num_rows(), Ordering::Relaxed);
+    })
+}
+
+/// Returns the message closing a stream with the statistics of the query, computed once all
+/// the data was streamed. The message has no data and carries the statistics in its
+/// `app_metadata`.
+fn stats_trailer(
+    probe: query::ScanProbe,
+    rows_returned: Arc<AtomicUsize>,
+) -> impl Stream<Item = Result<FlightData, FlightError>> + Send + 'static {
+    futures::stream::once(async move {
+        let stats = probe.stats();
+        trace!("{:?}", stats);
+
+        marshal::flight::do_get_stats_to_bytes(stats, rows_returned.load(Ordering::Relaxed))
+            .map(|metadata| FlightData::new().with_app_metadata(metadata))
+            .map_err(|e| FlightError::ExternalError(Box::new(e)))
+    })
+}
+
+/// Returns the minimum and maximum number of rows of the result batches requested by the
+/// client, if any. Exact batch sizes take precedence over the minimum ones, both are clamped
+/// to `max_rows` so that a request can't force arbitrarily large batches.
+fn resolve_batching(
+    result_batch_rows: Option<usize>,
+    min_batch_rows: Option<usize>,
+    max_rows: usize,
+) -> Option<(usize, Option<usize>)> {
+    let max_rows = max_rows.max(1);
+    match (result_batch_rows, min_batch_rows) {
+        (Some(batch_rows), _) => {
+            let batch_rows = batch_rows.min(max_rows);
+            Some((batch_rows, Some(batch_rows)))
+        }
+        (None, Some(min_rows)) => Some((min_rows.min(max_rows), Some(max_rows))),
+        (None, None) => None,
+    }
+}
+
+/// Re-chunks the data stream in batches of at least `min_rows` rows and at most `max_rows`
+/// rows, if set. Consecutive batches are merged until they hold `min_rows` rows and batches
+/// larger than `max_rows` are split. Only the last batch may hold fewer rows, rows are never
+/// dropped or reordered.
+fn rebatch(
+    stream: impl Stream<Item = Result<RecordBatch, FlightError>> + Send + 'static,
+    min_rows: usize,
+    max_rows: Option<usize>,
+) -> impl Stream<Item = Result<RecordBatch, FlightError>> + Send + 'static {
+    let state = (stream.boxed(), Vec::<RecordBatch>::new(), 0usize, false);
+
+    futures::stream::unfold(
+        state,
+        move |(mut input, mut pending, mut pending_rows, mut exhausted)| async move {
+            loop {
+                if pending_rows >= min_rows || (exhausted && pending_rows > 0) {
+                    let merged = match pending.as_slice() {
+                        [batch] => batch.clone(),
+                        batches => {
+                            match arrow::compute::concat_batches(&batches[0].schema(), batches) {
+                                Ok(batch) => batch,
+                                Err(e) => return Some((Err(e.into()), (input, vec![], 0, true))),
+                            }
+                        }
+                    };
+
+                    let len = max_rows.map_or(merged.num_rows(), |max| max.min(merged.num_rows()));
+                    let rest = merged.slice(len, merged.num_rows() - len);
+                    pending_rows = rest.num_rows();
+                    pending = if pending_rows > 0 { vec![rest] } else { vec![] };
+
+                    return Some((
+                        Ok(merged.slice(0, len)),
+                        (input, pending, pending_rows, exhausted),
+                    ));
+                }
+
+                if exhausted {
+                    return None;
+                }
+
+                match input.next().await {
+                    Some(Ok(batch)) => {
+                        pending_rows += batch.num_rows();
+                        pending.push(batch);
+                    }
+                    Some(Err(e)) => return Some((Err(e), (input, vec![], 0, true))),
+                    None => exhausted = true,
+                }
+            }
+        },
+    )
+}
+
+/// Builds the encoder converting the data stream into flight data.
+///
+/// By default dictionary encoded columns are hydrated, i.e. expanded to plain values in every
+/// batch. With `dictionary_reuse` they are sent as dictionaries, the encoder tracks the last
+/// dictionary sent for each column: an unchanged dictionary is not sent again, a dictionary
+/// extending the previous one is sent as a delta holding only the new values, and any other
+/// dictionary replaces the previous one.
+fn flight_encoder(
+    schema: SchemaRef,
+    stream: impl Stream<Item = Result<RecordBatch, FlightError>> + Send + 'static,
+    dictionary_reuse: bool,
+) -> FlightDataEncoder {
+    let builder = FlightDataEncoderBuilder::new().with_schema(schema);
+
+    let builder = if dictionary_reuse {
+        builder
+            .with_dictionary_handling(DictionaryHandling::Resend)
+            .with_options(
+                IpcWriteOptions::default()
+                    .with_dictionary_handling(ipc::writer::DictionaryHandling::Delta),
+            )
+    } else {
+        builder.with_dictionary_handling(DictionaryHandling::Hydrate)
+    };
+
+    builder.build(stream)
 }
 
 /// Computes the optimal batch size based on topic statistics from the database.
@@ -78,3 +729,858 @@ async fn compute_optimal_batch_size(
 
     Ok(Some(batch_size as usize))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::testing;
+    use arrow::array::{AsArray, DictionaryArray, Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Int32Type, Int64Type, Schema};
+    use arrow_flight::decode::FlightRecordBatchStream;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn dictionary_reuse_stream() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("timestamp", DataType::Int64, false),
+            Field::new(
+                "label",
+                DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+                false,
+            ),
+        ]));
+
+        let values = Arc::new(StringArray::from(vec!["idle", "moving"]));
+        let batch = |ts: Vec<i64>, keys: Vec<i32>, values: Arc<StringArray>| {
+            let labels = DictionaryArray::<Int32Type>::try_new(keys.into(), values).unwrap();
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int64Array::from(ts)), Arc::new(labels)],
+            )
+            .unwrap()
+        };
+
+        let batches = vec![
+            batch(vec![1, 2, 3], vec![0, 1, 0], values.clone()),
+            // Same dictionary, not sent again
+            batch(vec![4, 5], vec![1, 1], values),
+            // The last batch introduces a new value
+            batch(
+                vec![6, 7],
+                vec![2, 0],
+                Arc::new(StringArray::from(vec!["idle", "moving", "stopped"])),
+            ),
+        ];
+
+        let stream = futures::stream::iter(batches.clone().into_iter().map(Ok));
+        let data: Vec<FlightData> = flight_encoder(schema, stream, true)
+            .try_collect()
+            .await
+            .unwrap();
+
+        // The dictionary is sent with the first batch and extended with a delta by the last
+        let dictionaries: Vec<bool> = data
+            .iter()
+            .filter_map(|data| {
+                let message = ipc::root_as_message(&data.data_header).ok()?;
+                Some(message.header_as_dictionary_batch()?.isDelta())
+            })
+            .collect();
+        assert_eq!(dictionaries, [false, true]);
+
+        let decoded: Vec<RecordBatch> = FlightRecordBatchStream::new_from_flight_data(
+            futures::stream::iter(data.into_iter().map(Ok)),
+        )
+        .try_collect()
+        .await
+        .unwrap();
+
+        assert_eq!(decoded.len(), batches.len());
+        for (decoded, expected) in decoded.iter().zip(&batches) {
+            // Dictionaries are not expanded
+            assert!(matches!(
+                decoded.column(1).data_type(),
+                DataType::Dictionary(_, _)
+            ));
+
+            let labels = |batch: &RecordBatch| -> Vec<String> {
+                let dict = batch.column(1).as_dictionary::<Int32Type>();
+                let values = dict.values().as_string::<i32>();
+                dict.keys()
+                    .iter()
+                    .map(|k| values.value(k.unwrap() as usize).to_owned())
+                    .collect()
+            };
+
+            assert_eq!(decoded.column(0), expected.column(0));
+            assert_eq!(labels(decoded), labels(expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn rebatch_stream() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "timestamp",
+            DataType::Int64,
+            false,
+        )]));
+        let batch = |range: std::ops::Range<i64>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int64Array::from_iter_values(range))],
+            )
+            .unwrap()
+        };
+        let batches = vec![batch(0..5), batch(5..7), batch(7..14)];
+
+        for (batch_rows, expected) in [(3, vec![3, 3, 3, 3, 2]), (10, vec![10, 4])] {
+            let stream = futures::stream::iter(batches.clone().into_iter().map(Ok));
+            let rebatched: Vec<RecordBatch> = rebatch(stream, batch_rows, Some(batch_rows))
+                .try_collect()
+                .await
+                .unwrap();
+
+            let sizes: Vec<usize> = rebatched.iter().map(|b| b.num_rows()).collect();
+            assert_eq!(sizes, expected);
+
+            // Rows keep their order across the batch boundaries
+            let timestamps: Vec<i64> = rebatched
+                .iter()
+                .flat_map(|b| b.column(0).as_primitive::<Int64Type>().values().to_vec())
+                .collect();
+            assert_eq!(timestamps, (0..14).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn batching_clamped() {
+        assert_eq!(resolve_batching(None, None, 100), None);
+        assert_eq!(
+            resolve_batching(Some(10), Some(50), 100),
+            Some((10, Some(10)))
+        );
+        assert_eq!(
+            resolve_batching(Some(1000), None, 100),
+            Some((100, Some(100)))
+        );
+        assert_eq!(resolve_batching(None, Some(10), 100), Some((10, Some(100))));
+        assert_eq!(
+            resolve_batching(None, Some(1000), 100),
+            Some((100, Some(100)))
+        );
+    }
+
+    #[tokio::test]
+    async fn coalesce_stream() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "timestamp",
+            DataType::Int64,
+            false,
+        )]));
+        // Single-row batches, like the ones read from single-row chunks, followed by a batch
+        // already large enough
+        let mut batches: Vec<RecordBatch> = (0..10)
+            .map(|ts| {
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(vec![ts]))])
+                    .unwrap()
+            })
+            .collect();
+        batches.push(
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int64Array::from_iter_values(10..20))],
+            )
+            .unwrap(),
+        );
+
+        let stream = futures::stream::iter(batches.into_iter().map(Ok));
+        let coalesced: Vec<RecordBatch> = rebatch(stream, 4, None).try_collect().await.unwrap();
+
+        // Without a maximum size large batches are not split
+        let sizes: Vec<usize> = coalesced.iter().map(|b| b.num_rows()).collect();
+        assert_eq!(sizes, vec![4, 4, 12]);
+
+        let timestamps: Vec<i64> = coalesced
+            .iter()
+            .flat_map(|b| b.column(0).as_primitive::<Int64Type>().values().to_vec())
+            .collect();
+        assert_eq!(timestamps, (0..20).collect::<Vec<_>>());
+    }
+
+    /// Stream yielding a batch of three timestamps every `delay`, like a slow reader.
+    fn slow_stream(
+        schema: SchemaRef,
+        batches: usize,
+        delay: std::time::Duration,
+    ) -> impl Stream<Item = Result<RecordBatch, FlightError>> + Send + 'static {
+        futures::stream::iter(0..batches as i64).then(move |idx| {
+            let schema = schema.clone();
+            async move {
+                tokio::time::sleep(delay).await;
+                let timestamps = Int64Array::from_iter_values(idx * 3..idx * 3 + 3);
+                Ok(RecordBatch::try_new(schema, vec![Arc::new(timestamps)]).unwrap())
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn cancel_running_query() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+            DataType::Int64,
+            false,
+        )]));
+        let delay = std::time::Duration::from_millis(100);
+
+        let running = query::RunningQueries::default();
+        let registration = running.register("abc", None).unwrap();
+
+        // Streaming all the batches would take 10s
+        let stream = with_cancellation(slow_stream(schema, 100, delay), registration);
+
+        let started = tokio::time::Instant::now();
+        let cancel = async {
+            tokio::time::sleep(delay * 3 / 2).await;
+            assert!(running.cancel("abc", None));
+        };
+        let (items, _) = tokio::join!(stream.collect::<Vec<_>>(), cancel);
+
+        // The stream stops as soon as the cancellation is received
+        assert!(started.elapsed() < delay * 3);
+        let (last, data) = items.split_last().unwrap();
+        assert_eq!(data.len(), 1);
+        assert!(matches!(
+            last,
+            Err(FlightError::ExternalError(e))
+                if matches!(e.downcast_ref::<ServerError>(), Some(ServerError::QueryCancelled))
+        ));
+
+        // The query is unregistered once its stream is dropped
+        assert!(!running.cancel("abc", None));
+    }
+
+    #[tokio::test]
+    async fn partial_result_on_timeout() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+            DataType::Int64,
+            false,
+        )]));
+        let delay = std::time::Duration::from_millis(100);
+
+        // The deadline is hit while waiting the third batch
+        let deadline = |partial| Deadline {
+            at: tokio::time::Instant::now() + delay * 5 / 2,
+            timeout: delay * 5 / 2,
+            partial,
+        };
+
+        let truncation = Arc::new(Mutex::new(None));
+        let stream = with_deadline(
+            slow_stream(schema.clone(), 5, delay),
+            deadline(true),
+            truncation.clone(),
+        );
+        let data: Vec<FlightData> = flight_encoder(schema.clone(), stream.boxed(), false)
+            .chain(partial_trailer(truncation))
+            .try_collect()
+            .await
+            .unwrap();
+
+        let (trailer, data) = data.split_last().unwrap();
+        assert!(trailer.data_header.is_empty());
+        let trailer: serde_json::Value = serde_json::from_slice(&trailer.app_metadata).unwrap();
+        assert_eq!(
+            trailer,
+            serde_json::json!({ "partial": true, "last_timestamp": 5 })
+        );
+
+        let batches: Vec<RecordBatch> = FlightRecordBatchStream::new_from_flight_data(
+            futures::stream::iter(data.to_vec().into_iter().map(Ok)),
+        )
+        .try_collect()
+        .await
+        .unwrap();
+        let timestamps: Vec<i64> = batches
+            .iter()
+            .flat_map(|b| b.column(0).as_primitive::<Int64Type>().values().to_vec())
+            .collect();
+        assert_eq!(timestamps, (0..6).collect::<Vec<_>>());
+
+        // Without partial results the stream fails
+        let truncation = Arc::new(Mutex::new(None));
+        let stream = with_deadline(
+            slow_stream(schema, 5, delay),
+            deadline(false),
+            truncation.clone(),
+        );
+        let result: Result<Vec<RecordBatch>, _> = stream.try_collect().await;
+        assert!(result.is_err());
+        assert!(truncation.lock().unwrap().is_none());
+    }
+
+    /// Writes two chunks of `rows` sorted timestamps under `seq/topic`.
+    async fn write_sorted_chunks(store: &store::StoreRef, rows: i64) {
+        use crate::{rw, types};
+
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+            DataType::Int64,
+            false,
+        )]));
+        for idx in 0..2 {
+            let start = idx as i64 * rows;
+            let timestamps = Int64Array::from_iter_values(start..start + rows);
+            let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(timestamps)]).unwrap();
+
+            let mut writer = rw::ChunkWriter::try_new(schema.clone(), rw::Format::Default).unwrap();
+            writer.write(&batch).unwrap();
+            let (buffer, _, _) = writer.finalize().unwrap();
+
+            let datafile =
+                types::TopicResourceLocator::from("seq/topic").datafile(idx, &rw::Format::Default);
+            store.write_bytes(&datafile, buffer).await.unwrap();
+        }
+    }
+
+    /// Reads `seq/topic` consuming at most `batches` batches of the stream, and returns the
+    /// scan statistics of its trailer.
+    async fn read_scan_stats(
+        ts_gw: &query::TimeseriesGateway,
+        batch_size: Option<usize>,
+        batches: usize,
+    ) -> serde_json::Value {
+        let res = ts_gw
+            .read("seq/topic", crate::rw::Format::Default, batch_size)
+            .await
+            .unwrap();
+        let schema = res.schema_with_metadata(Default::default());
+        let (stream, probe) = res.probed_stream().await.unwrap();
+
+        let rows_returned = Arc::new(AtomicUsize::new(0));
+        let stream = stream
+            .take(batches)
+            .map_err(|e| FlightError::ExternalError(Box::new(e)));
+        let stream = count_rows(stream, rows_returned.clone());
+        let data: Vec<FlightData> = flight_encoder(schema, stream, false)
+            .chain(stats_trailer(probe, rows_returned))
+            .try_collect()
+            .await
+            .unwrap();
+
+        let trailer = data.last().unwrap();
+        assert!(trailer.data_header.is_empty());
+        let mut trailer: serde_json::Value = serde_json::from_slice(&trailer.app_metadata).unwrap();
+        trailer["scan_stats"].take()
+    }
+
+    /// The trailer reports the chunks read by the query
+    #[tokio::test]
+    async fn scan_stats_trailer() {
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        write_sorted_chunks(&store, 7).await;
+
+        let ts_gw = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let stats = read_scan_stats(&ts_gw, None, usize::MAX).await;
+        assert_eq!(stats["chunks_scanned"], 2);
+        assert_eq!(stats["rows_scanned"], 14);
+        assert_eq!(stats["rows_returned"], 14);
+        assert!(stats["bytes_read"].as_u64().unwrap() > 0);
+        assert_eq!(stats["cache_hit"], false);
+
+        // The second query reuses the inspection of the datafiles
+        let stats = read_scan_stats(&ts_gw, None, usize::MAX).await;
+        assert_eq!(stats["rows_returned"], 14);
+        assert_eq!(stats["cache_hit"], true);
+    }
+
+    /// A stream dropped before its end reports only the work done until then
+    #[tokio::test]
+    async fn scan_stats_trailer_early_termination() {
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        write_sorted_chunks(&store, 1000).await;
+
+        let ts_gw = query::TimeseriesGateway::try_new((*store).clone()).unwrap();
+        let stats = read_scan_stats(&ts_gw, Some(10), 1).await;
+        assert_eq!(stats["rows_returned"], 10);
+
+        // The datafiles are decoded lazily, the query stops long before reading them all
+        let rows_scanned = stats["rows_scanned"].as_u64().unwrap();
+        assert!(rows_scanned >= 10);
+        assert!(rows_scanned < 2000);
+        assert!(stats["chunks_scanned"].as_u64().unwrap() <= 2);
+    }
+
+    /// Authorizer denying to the `guest` caller the queries on the topics under `private`
+    struct DenyGuestPrivate;
+
+    impl Authorizer for DenyGuestPrivate {
+        fn authorize(
+            &self,
+            caller: &Caller,
+            operation: Operation<'_>,
+            resource: Option<&str>,
+            _access: Access,
+        ) -> auth::Decision {
+            match (caller.identity.as_deref(), operation, resource) {
+                (Some("guest"), Operation::Query, Some(name)) if name.starts_with("private") => {
+                    auth::Decision::Deny
+                }
+                _ => auth::Decision::Allow,
+            }
+        }
+    }
+
+    #[sqlx::test]
+    async fn query_authorization(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use crate::server::limiter::LimitPolicy;
+
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = testing::create_empty_sequence(&repo, &store, "private")
+            .await
+            .unwrap();
+        let topic = testing::create_empty_topic(&repo, &store, &sequence, "private/topic")
+            .await
+            .unwrap();
+        testing::write_dummy_chunk(&repo, &store, &topic, "private/topic", 0).await;
+
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+        let limiter = Limiter::queries(None, LimitPolicy::Reject);
+        let query_as = async |caller: Caller| {
+            let cmd =
+                marshal::flight::get_flight_info_cmd(br#"{"resource_locator": "private/topic"}"#)
+                    .unwrap();
+            let ticket =
+                marshal::flight::do_get_ticket_to_bytes("private/topic".to_owned(), &cmd).unwrap();
+            do_get(
+                (*store).clone(),
+                (*repo).clone(),
+                ts_engine.clone(),
+                &limiter,
+                &DenyGuestPrivate,
+                &caller,
+                Ticket::new(ticket),
+            )
+            .await
+        };
+
+        let res = query_as(Caller::new("guest")).await;
+        assert!(matches!(
+            res,
+            Err(ServerError::ActionFailed(
+                crate::server::endpoints::ActionError::PermissionDenied(_)
+            ))
+        ));
+
+        let data = query_as(Caller::new("admin")).await.unwrap();
+        let batches: Vec<RecordBatch> = FlightRecordBatchStream::new_from_flight_data(data)
+            .try_collect()
+            .await
+            .unwrap();
+        let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, crate::arrow::testing::dummy_batch().num_rows());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn chunk_attribution(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use crate::server::limiter::LimitPolicy;
+
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = testing::create_empty_sequence(&repo, &store, "seq")
+            .await
+            .unwrap();
+        let topic = testing::create_empty_topic(&repo, &store, &sequence, "seq/topic")
+            .await
+            .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("timestamp", DataType::Int64, false),
+            Field::new("chunk", DataType::Int64, false),
+            Field::new(
+                "label",
+                DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+                false,
+            ),
+        ]));
+
+        // Overlapping time ranges, rows are attributed by chunk and not by timestamp
+        let chunks: [&[i64]; 3] = [&[10, 20, 30], &[15, 25], &[5]];
+        for (idx, timestamps) in chunks.iter().enumerate() {
+            let labels = DictionaryArray::<Int32Type>::try_new(
+                vec![0; timestamps.len()].into(),
+                Arc::new(StringArray::from(vec![format!("chunk-{idx}")])),
+            )
+            .unwrap();
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(timestamps.to_vec())),
+                    Arc::new(Int64Array::from(vec![idx as i64; timestamps.len()])),
+                    Arc::new(labels),
+                ],
+            )
+            .unwrap();
+            testing::write_chunk(&repo, &store, &topic, "seq/topic", idx, &batch).await;
+        }
+
+        let cmd = marshal::flight::get_flight_info_cmd(
+            br#"{"resource_locator": "seq/topic", "chunk_attribution": true, "dictionary_reuse": true}"#,
+        )
+        .unwrap();
+        let ticket = marshal::flight::do_get_ticket_to_bytes("seq/topic".to_owned(), &cmd).unwrap();
+
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+        let limiter = Limiter::queries(None, LimitPolicy::Reject);
+        let data: Vec<FlightData> = do_get(
+            (*store).clone(),
+            (*repo).clone(),
+            ts_engine,
+            &limiter,
+            &auth::AllowAll,
+            &Caller::anonymous(),
+            Ticket::new(ticket),
+        )
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+        let decoded: Vec<RecordBatch> = FlightRecordBatchStream::new_from_flight_data(
+            futures::stream::iter(data.clone().into_iter().map(Ok)),
+        )
+        .try_collect()
+        .await
+        .unwrap();
+
+        // The schema message is not attributed, every other message reports its chunk
+        assert!(data[0].app_metadata.is_empty());
+        let tags: Vec<(bool, i64)> = data[1..]
+            .iter()
+            .map(|message| {
+                let header = ipc::root_as_message(&message.data_header).unwrap();
+                let tag: serde_json::Value = serde_json::from_slice(&message.app_metadata).unwrap();
+                (
+                    header.header_as_record_batch().is_some(),
+                    tag["chunk_index"].as_i64().unwrap(),
+                )
+            })
+            .collect();
+
+        // A dictionary is attributed to the chunk of the batch following it
+        for pair in tags.windows(2) {
+            if !pair[0].0 {
+                assert_eq!(pair[0].1, pair[1].1);
+            }
+        }
+
+        let batch_tags: Vec<i64> = tags
+            .iter()
+            .filter(|(is_batch, _)| *is_batch)
+            .map(|(_, chunk)| *chunk)
+            .collect();
+        assert_eq!(batch_tags.len(), decoded.len());
+
+        let mut rows_per_chunk = vec![0; chunks.len()];
+        for (chunk_index, batch) in batch_tags.into_iter().zip(&decoded) {
+            let chunk = batch
+                .column_by_name("chunk")
+                .unwrap()
+                .as_primitive::<Int64Type>();
+            assert!(chunk.values().iter().all(|c| *c == chunk_index));
+
+            let labels = batch
+                .column_by_name("label")
+                .unwrap()
+                .as_dictionary::<Int32Type>();
+            let values = labels.values().as_string::<i32>();
+            for key in labels.keys().iter() {
+                assert_eq!(
+                    values.value(key.unwrap() as usize),
+                    format!("chunk-{chunk_index}")
+                );
+            }
+            rows_per_chunk[chunk_index as usize] += batch.num_rows();
+        }
+
+        let expected: Vec<_> = chunks.iter().map(|c| c.len()).collect();
+        assert_eq!(rows_per_chunk, expected);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn provenance_columns(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use crate::server::limiter::LimitPolicy;
+
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = testing::create_empty_sequence(&repo, &store, "seq")
+            .await
+            .unwrap();
+        let topic = testing::create_empty_topic(&repo, &store, &sequence, "seq/topic")
+            .await
+            .unwrap();
+
+        let batch = crate::arrow::testing::dummy_batch();
+        for idx in 0..2 {
+            testing::write_chunk(&repo, &store, &topic, "seq/topic", idx, &batch).await;
+        }
+
+        let cmd = marshal::flight::get_flight_info_cmd(
+            br#"{"resource_locator": "seq/topic", "provenance": ["_topic", "_chunk", "_ingested_at"]}"#,
+        )
+        .unwrap();
+        let ticket = marshal::flight::do_get_ticket_to_bytes("seq/topic".to_owned(), &cmd).unwrap();
+
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+        let limiter = Limiter::queries(None, LimitPolicy::Reject);
+        let data = do_get(
+            (*store).clone(),
+            (*repo).clone(),
+            ts_engine,
+            &limiter,
+            &auth::AllowAll,
+            &Caller::anonymous(),
+            Ticket::new(ticket),
+        )
+        .await
+        .unwrap();
+        let batches: Vec<RecordBatch> = FlightRecordBatchStream::new_from_flight_data(data)
+            .try_collect()
+            .await
+            .unwrap();
+
+        // Each chunk reports its own index and write timestamp
+        let tfacade =
+            repo::FacadeTopic::new("seq/topic".to_owned(), (*store).clone(), (*repo).clone());
+        let write_timestamps = tfacade.chunks_write_timestamps().await.unwrap();
+        let mut rows_per_chunk = [0; 2];
+        for batch in &batches {
+            let topics = batch
+                .column_by_name("_topic")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            assert!(topics.iter().all(|v| v == Some("seq/topic")));
+
+            let chunks = batch
+                .column_by_name("_chunk")
+                .unwrap()
+                .as_primitive::<Int64Type>();
+            let ingested_at = batch
+                .column_by_name("_ingested_at")
+                .unwrap()
+                .as_primitive::<Int64Type>();
+            for (chunk, ingested_at) in chunks.values().iter().zip(ingested_at.values()) {
+                let expected = write_timestamps[&(*chunk as usize)];
+                assert_eq!(*ingested_at, i64::from(expected));
+                rows_per_chunk[*chunk as usize] += 1;
+            }
+        }
+        assert_eq!(rows_per_chunk, [batch.num_rows(); 2]);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    /// Test checking that the time column of a topic is resolved in the query and returned
+    /// under its own name instead of the timestamp field it is stored as.
+    async fn time_column_read(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use crate::rw;
+        use crate::server::limiter::LimitPolicy;
+
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = testing::create_empty_sequence(&repo, &store, "seq")
+            .await
+            .unwrap();
+        let properties = types::TopicProperties::new(rw::Format::Default, "test_tag".to_owned())
+            .with_time_column(Some("t".to_owned()));
+        let topic = testing::create_topic_with(&repo, &store, &sequence, "seq/topic", properties)
+            .await
+            .unwrap();
+        testing::write_dummy_chunk(&repo, &store, &topic, "seq/topic", 0).await;
+
+        let cmd = marshal::flight::get_flight_info_cmd(
+            br#"{
+                "resource_locator": "seq/topic",
+                "filter": {"t": {"$geq": 10020}},
+                "order_by": {"column": "t", "order": "desc"},
+                "projection": ["t", "value"]
+            }"#,
+        )
+        .unwrap();
+        let ticket = marshal::flight::do_get_ticket_to_bytes("seq/topic".to_owned(), &cmd).unwrap();
+
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+        let limiter = Limiter::queries(None, LimitPolicy::Reject);
+        let data = do_get(
+            (*store).clone(),
+            (*repo).clone(),
+            ts_engine,
+            &limiter,
+            &auth::AllowAll,
+            &Caller::anonymous(),
+            Ticket::new(ticket),
+        )
+        .await
+        .unwrap();
+        let batches: Vec<RecordBatch> = FlightRecordBatchStream::new_from_flight_data(data)
+            .try_collect()
+            .await
+            .unwrap();
+
+        let schema = batches[0].schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, ["t", "value"]);
+
+        let timestamps: Vec<i64> = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column_by_name("t")
+                    .unwrap()
+                    .as_primitive::<Int64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        assert_eq!(timestamps, [10030, 10025, 10020]);
+
+        Ok(())
+    }
+
+    /// The stride of a target count is computed on the rows matching the filter, not on
+    /// the rows of the topic
+    #[sqlx::test]
+    async fn sample_filtered_rows(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use crate::server::limiter::LimitPolicy;
+
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = testing::create_empty_sequence(&repo, &store, "seq")
+            .await
+            .unwrap();
+        let topic = testing::create_empty_topic(&repo, &store, &sequence, "seq/topic")
+            .await
+            .unwrap();
+        for idx in 0..4 {
+            testing::write_dummy_chunk(&repo, &store, &topic, "seq/topic", idx).await;
+        }
+
+        // 8 of the 28 rows match the filter
+        let cmd = marshal::flight::get_flight_info_cmd(
+            br#"{
+                "resource_locator": "seq/topic",
+                "filter": {"value": {"$geq": 6}},
+                "sample": {"target_count": 4}
+            }"#,
+        )
+        .unwrap();
+        let ticket = marshal::flight::do_get_ticket_to_bytes("seq/topic".to_owned(), &cmd).unwrap();
+
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+        let limiter = Limiter::queries(None, LimitPolicy::Reject);
+        let data = do_get(
+            (*store).clone(),
+            (*repo).clone(),
+            ts_engine,
+            &limiter,
+            &auth::AllowAll,
+            &Caller::anonymous(),
+            Ticket::new(ticket),
+        )
+        .await
+        .unwrap();
+        let batches: Vec<RecordBatch> = FlightRecordBatchStream::new_from_flight_data(data)
+            .try_collect()
+            .await
+            .unwrap();
+
+        let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 4);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn read_records_access(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use crate::server::limiter::LimitPolicy;
+
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let sequence = testing::create_empty_sequence(&repo, &store, "seq")
+            .await
+            .unwrap();
+        let topic = testing::create_empty_topic(&repo, &store, &sequence, "seq/topic")
+            .await
+            .unwrap();
+        for idx in 0..2 {
+            testing::write_dummy_chunk(&repo, &store, &topic, "seq/topic", idx).await;
+        }
+
+        let last_accesses = async || {
+            repo::chunks_find_by_topic_id(&mut repo.connection(), topic.id)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|chunk| chunk.last_access_unix_tstamp)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(last_accesses().await, [None, None]);
+
+        let cmd =
+            marshal::flight::get_flight_info_cmd(br#"{"resource_locator": "seq/topic"}"#).unwrap();
+        let ticket = marshal::flight::do_get_ticket_to_bytes("seq/topic".to_owned(), &cmd).unwrap();
+
+        let ts_engine = Arc::new(query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+        let limiter = Limiter::queries(None, LimitPolicy::Reject);
+        let data = do_get(
+            (*store).clone(),
+            (*repo).clone(),
+            ts_engine,
+            &limiter,
+            &auth::AllowAll,
+            &Caller::anonymous(),
+            Ticket::new(ticket),
+        )
+        .await
+        .unwrap();
+
+        // Nothing is recorded before the data is read
+        assert_eq!(last_accesses().await, [None, None]);
+
+        let _: Vec<FlightData> = data.try_collect().await.unwrap();
+
+        // The access to the scanned chunks is recorded in the background
+        let mut recorded = false;
+        for _ in 0..50 {
+            if last_accesses().await.iter().all(Option::is_some) {
+                recorded = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(recorded);
+
+        Ok(())
+    }
+}
@@ -1,18 +1,67 @@
 use crate::marshal;
-use crate::{repo, rw, server::errors::ServerError, store, types};
-use arrow::datatypes::SchemaRef;
+use crate::server::{
+    auth::{self, Access, Authorizer, Caller, Operation},
+    deferred_lock::DeferredLocks,
+    errors::ServerError,
+    limiter::Limiter,
+    upload_coalescer::UploadCoalescer,
+};
+use crate::types::{MetadataBlob, Resource};
+use crate::{ontology, params, repo, rw, store, types};
+use arrow::array::{AsArray, RecordBatch};
+use arrow::datatypes::{DataType, Schema, SchemaRef};
 use arrow_flight::decode::{DecodedFlightData, DecodedPayload, FlightDataDecoder};
 use arrow_flight::flight_descriptor::DescriptorType;
 use futures::TryStreamExt;
-use log::{debug, info, trace};
+use log::{debug, info, trace, warn};
+use std::collections::{BTreeMap, btree_map::Entry};
+use std::sync::Arc;
 
+/// Handles an upload, returning the acknowledgement of each chunk produced.
+///
+/// The upload holds a slot of its sequence in `limiter` until completed. Once completed the
+/// uploaded topic is locked through `locks`, which may defer the lock. The data of small uploads
+/// may be handed to `coalescer` instead of being written, see [`UploadCoalescer`]. Topics holding
+/// more than `max_chunks_per_topic` chunks are compacted in background once the upload
+/// completes.
+///
+/// The upload is rejected before reading any data if `authorizer` doesn't allow `caller` to
+/// write the uploaded resource.
+#[expect(
+    clippy::too_many_arguments,
+    reason = "the upload is configured by the state shared across the uploads of the server"
+)]
 pub async fn do_put(
     store: store::StoreRef,
     repo: repo::Repository,
+    limiter: &Limiter,
+    locks: &Arc<DeferredLocks>,
+    coalescer: &Arc<UploadCoalescer>,
+    max_chunks_per_topic: Option<usize>,
+    authorizer: &dyn Authorizer,
+    caller: &Caller,
     decoder: &mut FlightDataDecoder,
-) -> Result<(), ServerError> {
+) -> Result<Vec<rw::ChunkAck>, ServerError> {
     let (cmd, schema) = extract_command_and_schema_from_header_message(decoder).await?;
-    do_put_topic_data(store, repo, decoder, schema, cmd).await
+
+    auth::check(
+        authorizer,
+        caller,
+        Operation::Upload,
+        Some(&cmd.resource_locator),
+        Access::Write,
+    )?;
+
+    // Topic locators are children of the sequence, line protocol uploads target the sequence
+    let sequence = types::TopicResourceLocator::from(&cmd.resource_locator)
+        .sequence_name()
+        .to_owned();
+    let _permit = limiter.acquire(&sequence).await?;
+
+    if cmd.line_protocol {
+        return do_put_line_protocol(store, repo, decoder, schema, cmd).await;
+    }
+    do_put_topic_data(store, repo, locks, coalescer, decoder, schema, cmd).await
 }
 
 async fn extract_command_and_schema_from_header_message(
@@ -60,10 +109,12 @@ fn extract_command_from_flight_data(
 async fn do_put_topic_data(
     store: store::StoreRef,
     repo: repo::Repository,
+    locks: &Arc<DeferredLocks>,
+    coalescer: &Arc<UploadCoalescer>,
     decoder: &mut FlightDataDecoder,
     schema: SchemaRef,
     cmd: types::flight::DoPutCmd,
-) -> Result<(), ServerError> {
+) -> Result<Vec<rw::ChunkAck>, ServerError> {
     let locator = cmd.resource_locator;
     let key = &cmd.key;
 
@@ -72,8 +123,6 @@ async fn do_put_topic_data(
         locator, key
     );
 
-    crate::arrow::check_schema(&schema)?;
-
     let handle = repo::FacadeTopic::new(locator, store.clone(), repo.clone());
 
     // perform the match between received key and topic id
@@ -84,14 +133,782 @@ async fn do_put_topic_data(
     }
 
     let mdata = handle.metadata().await?;
+    debug!(
+        "uploading data on {} ({})",
+        handle.locator, mdata.properties
+    );
+
+    // Data declaring its own time column is stored with the timestamp field in its place
+    let time_column = mdata.properties.time_column.clone();
+    let schema = match &time_column {
+        Some(time_column) => crate::arrow::rename_time_column(&schema, time_column)?,
+        None => schema,
+    };
+    crate::arrow::check_schema(&schema)?;
+
+    if let Some(registry) = ontology::registry() {
+        registry.validate(&mdata.properties.ontology_tag, &schema)?;
+    }
+
+    check_pinned_schema(&handle, &mdata, &schema).await?;
+
+    // Locked topics are overwritten only if forced, the chunks being overwritten are replaced
+    // by the uploaded ones once the upload completes
+    let overwrite = cmd.write_mode == types::flight::WriteMode::Overwrite;
+    if overwrite && handle.is_locked().await? && !cmd.force {
+        return Err(repo::FacadeError::TopicLocked.into());
+    }
+
+    // Other instances sharing the store are prevented from writing the topic concurrently
+    let mut lease = acquire_lease(&handle).await?;
+
+    // Finalized topics can't be written, a locked topic is reopened only to be overwritten and
+    // stays open until the upload completes
+    let state = handle.state().await?;
+    state
+        .transition(types::ResourceState::Open)
+        .map_err(repo::FacadeError::from)?;
+
+    // The topic may be waiting to be locked after a previous upload
+    let lock_pending = locks.cancel(&handle).await?;
+
+    let reopened = overwrite && state == types::ResourceState::Locked;
+    if reopened {
+        handle.reopen().await?;
+    }
+
+    // A failed upload restores the lock of the topic, or schedules again its pending lock
+    let restore =
+        repo::FacadeTopic::new(handle.locator.name().clone(), store.clone(), repo.clone());
+
+    // The chunks written by an overwrite are registered only once the upload completes
+    let staged = overwrite.then(|| Arc::new(std::sync::Mutex::new(Vec::new())));
+
+    let mut wal = None;
+    let result: Result<_, ServerError> = async {
+        let overwritten = if overwrite {
+            handle.datafiles().await?
+        } else {
+            Vec::new()
+        };
+
+        // Batches are logged before being written, so that the upload can be replayed after a
+        // crash
+        wal = params::configurables()
+            .wal_dir
+            .as_ref()
+            .map(|dir| {
+                rw::WalWriter::try_new(dir, &r_id.uuid.to_string(), handle.locator.name(), &schema)
+            })
+            .transpose()?;
+
+        // Topics with too many chunks are compacted once the upload completes
+        let compaction = max_chunks_per_topic.map(|max_chunks| {
+            let handle =
+                repo::FacadeTopic::new(handle.locator.name().clone(), store.clone(), repo.clone());
+            (handle, max_chunks)
+        });
+
+        // Small uploads are buffered and written along with the other small uploads of the topic.
+        // Deduplication and ordering checks work on the chunks of a single upload, uploads relying
+        // on them are always written right away. Buffered data is never written in a locked
+        // topic, uploads appending to a locked topic are written right away as well.
+        let ordering = mdata.properties.ordering;
+        let coalesce_threshold = coalescer.threshold().filter(|_| {
+            state == types::ResourceState::Open
+                && !overwrite
+                && !cmd.deduplicate
+                && ordering == types::RowOrdering::Unordered
+        });
+        let mut coalesced = coalesce_threshold.map(|_| Vec::new());
+
+        // Chunks are numbered after the existing datafiles, compacted topics may have gaps
+        let first_chunk_index = handle
+            .next_chunk_index(mdata.properties.serialization_format)
+            .await?;
+        let ontology_tag = mdata.properties.ontology_tag.clone();
+        let writer = match &staged {
+            Some(staged) => staging_writer(&store, &handle, &mdata.properties, staged.clone()),
+            None => topic_writer(&store, &handle, repo.clone(), r_id.id, &mdata.properties),
+        };
+        let mut writer = writer
+            .with_target_chunk_bytes(params::configurables().target_chunk_bytes)
+            .with_first_chunk_index(first_chunk_index);
+        if cmd.deduplicate && !overwrite {
+            writer = writer.with_deduplication(handle.last_chunk_hash().await?);
+        }
+        // Appended rows can't precede the rows already stored
+        if ordering == types::RowOrdering::TimeAscending && !overwrite {
+            writer = writer.with_last_timestamp(handle.last_timestamp().await?);
+        }
+
+        let params = params::configurables();
+        let high_water_mark = params.upload_high_water_mark_bytes;
+        let flush_deadline = std::time::Duration::from_secs(params.upload_flush_deadline_secs);
+
+        let mut acks = Vec::new();
+
+        // Batches logged and not yet flushed as chunks, in the order they are handed to the
+        // writer. The first `in_writer` ones were already handed to the writer, the others are
+        // held back by the coalescing.
+        let mut unflushed = Vec::new();
+        let mut in_writer = 0;
+
+        // Consume all batches
+        while let Some(data) = decoder
+            .try_next()
+            .await
+            .map_err(|e| ServerError::StreamError(e.to_string()))?
+        {
+            match data.payload {
+                DecodedPayload::RecordBatch(batch) => {
+                    debug!(
+                        "processing batch (cols: {}, memory_size: {}",
+                        batch.columns().len(),
+                        batch.get_array_memory_size()
+                    );
+                    let batch = if time_column.is_some() {
+                        RecordBatch::try_new(schema.clone(), batch.columns().to_vec())?
+                    } else {
+                        batch
+                    };
+                    if let Some(wal) = &mut wal {
+                        wal.append(&batch)?;
+                        unflushed.push(batch.clone());
+                    }
+
+                    // Batches are held back until the upload is known to be small
+                    let batches = match coalesced.as_mut() {
+                        Some(buffered) => {
+                            buffered.push(batch);
+                            let bytes: usize = buffered
+                                .iter()
+                                .map(RecordBatch::get_array_memory_size)
+                                .sum();
+                            if coalesce_threshold.is_some_and(|threshold| bytes < threshold) {
+                                lease.renew().await?;
+                                continue;
+                            }
+
+                            debug!("upload too large to be coalesced, writing it right away");
+                            let batches = std::mem::take(buffered);
+                            coalesced = None;
+                            batches
+                        }
+                        None => vec![batch],
+                    };
+
+                    // A chunk closed while writing a batch holds all the rows written so far
+                    let mut flushed = 0;
+                    for batch in &batches {
+                        let written = acks.len();
+                        acks.extend(writer.write(batch).await?);
+                        acks.extend(
+                            apply_backpressure(&mut writer, high_water_mark, flush_deadline)
+                                .await?,
+                        );
+                        in_writer += 1;
+                        if acks.len() > written {
+                            flushed = in_writer;
+                        }
+                    }
+
+                    // The batches flushed as chunks are dropped from the log, so that they are
+                    // not replayed after a crash, the ones still pending are kept
+                    if let Some(wal) = &mut wal
+                        && flushed > 0
+                    {
+                        unflushed.drain(..flushed);
+                        in_writer -= flushed;
+                        wal.checkpoint(&unflushed)?;
+                    }
+                    lease.renew().await?;
+                }
+                DecodedPayload::Schema(_) => {
+                    return Err(ServerError::DuplicateSchemaInPayload);
+                }
+                DecodedPayload::None => {
+                    return Err(ServerError::NoData);
+                }
+            }
+        }
+
+        // If the finalize fails (e.g. problems during stats computation) the topic is locked only
+        // if it was before the upload, this allows the reindexing (currently not implemented) of
+        // the topic
+        trace!("finializing data write");
+        acks.extend(writer.finalize().await?);
+        let deduplicated = acks
+            .iter()
+            .filter(|ack| **ack == rw::ChunkAck::Deduplicated)
+            .count();
+        if deduplicated > 0 {
+            info!(
+                "discarded {} duplicated chunks uploaded on {}",
+                deduplicated, handle.locator
+            );
+        }
+
+        if let Some(staged) = &staged {
+            let written = std::mem::take(&mut *staged.lock().unwrap());
+            handle
+                .swap_chunks(r_id.id, &ontology_tag, written, &overwritten)
+                .await?;
+            info!(
+                "{} chunks overwritten on {}",
+                overwritten.len(),
+                handle.locator
+            );
+        }
+
+        // The coalesced data is handed over holding the upload lease, so that the topic is not
+        // locked by the write of a previous buffer. The coalescer locks the topic once the data
+        // is written, buffers to be written right away are written once the lease is released.
+        let pushed = coalesced
+            .map(|batches| {
+                coalescer.push(
+                    handle.locator.name().clone(),
+                    batches,
+                    store.clone(),
+                    repo.clone(),
+                    locks,
+                )
+            })
+            .transpose()?;
 
-    // Setup the callback that will be used to create the repository record for the data catalog
-    // and prepare variables that will be moved in the closure
-    let ontology_tag = mdata.properties.ontology_tag;
-    let serialization_format = mdata.properties.serialization_format;
-    let topic_id = r_id.id;
+        lease.release().await?;
 
-    let mut writer = handle.writer(serialization_format).on_chunk_created(
+        match pushed {
+            Some(pushed) => {
+                acks.extend(
+                    coalescer
+                        .write_pushed(pushed, store.clone(), repo, locks)
+                        .await?,
+                );
+            }
+            None => locks.lock(handle).await?,
+        }
+
+        if let Some(wal) = wal.take() {
+            wal.discard()?;
+        }
+
+        if let Some((handle, max_chunks)) = compaction {
+            schedule_compaction(handle, max_chunks);
+        }
+
+        Ok(acks)
+    }
+    .await;
+
+    if let Err(e) = &result {
+        // The data of a failed overwrite is discarded, the overwritten chunks are kept
+        if let Some(staged) = staged {
+            let written = std::mem::take(&mut *staged.lock().unwrap());
+            restore
+                .discard_datafiles(written.iter().map(|c| &c.datafile))
+                .await;
+        }
+
+        // Failed uploads are not replayed
+        if let Some(wal) = wal
+            && let Err(e) = wal.discard()
+        {
+            warn!("unable to discard the wal of {}: {}", restore.locator, e);
+        }
+
+        let locator = restore.locator.clone();
+        let restored = if reopened {
+            restore.lock().await
+        } else if lock_pending {
+            locks.lock(restore).await
+        } else {
+            Ok(())
+        };
+        if let Err(restore_err) = restored {
+            warn!(
+                "unable to restore the lock of {} after a failed upload ({}): {}",
+                locator, e, restore_err
+            );
+        }
+    }
+
+    result
+}
+
+/// Writes `batches` as new chunks of the topic, the caller holds the lease of the topic.
+///
+/// Used to write the data of the small uploads buffered by the [`UploadCoalescer`].
+pub async fn write_batches(
+    store: &store::Store,
+    handle: &repo::FacadeTopic,
+    repo: repo::Repository,
+    batches: &[RecordBatch],
+) -> Result<Vec<rw::ChunkAck>, ServerError> {
+    let r_id = handle.resource_id().await?;
+    let mdata = handle.metadata().await?;
+    let first_chunk_index = handle
+        .next_chunk_index(mdata.properties.serialization_format)
+        .await?;
+    let mut writer = topic_writer(store, handle, repo, r_id.id, &mdata.properties)
+        .with_target_chunk_bytes(params::configurables().target_chunk_bytes)
+        .with_first_chunk_index(first_chunk_index);
+
+    let mut acks = Vec::new();
+    for batch in batches {
+        acks.extend(writer.write(batch).await?);
+    }
+    acks.extend(writer.finalize().await?);
+
+    Ok(acks)
+}
+
+/// Merges in background the most recent chunks of the topic if it holds more than
+/// `max_chunks` chunks, see [`repo::FacadeTopic::compact_tail`].
+///
+/// The compaction holds the lease of the topic, it is skipped if the topic is being written.
+fn schedule_compaction(
+    handle: repo::FacadeTopic,
+    max_chunks: usize,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let lease = match acquire_lease(&handle).await {
+            Ok(lease) => lease,
+            Err(e) => {
+                debug!("compaction of {} skipped: {}", handle.locator, e);
+                return;
+            }
+        };
+
+        let parallelism = params::configurables().max_concurrent_chunk_queries;
+        match handle.compact_tail(max_chunks, parallelism).await {
+            Ok(0) => {}
+            Ok(merged) => info!("{} chunks of {} compacted", merged, handle.locator),
+            Err(e) => warn!("unable to compact {}: {}", handle.locator, e),
+        }
+
+        if let Err(e) = lease.release().await {
+            warn!("unable to release the lease of {}: {}", handle.locator, e);
+        }
+    })
+}
+
+/// Flushes the data pending in `writer` as a chunk once it exceeds `high_water_mark` bytes.
+///
+/// The client stream is not read while the flush is in progress, so a slow store slows down
+/// the upload instead of buffering its data in memory. If the flush does not complete within
+/// `deadline` the upload is rejected.
+async fn apply_backpressure<W>(
+    writer: &mut rw::ChunkedWriter<'_, W>,
+    high_water_mark: usize,
+    deadline: std::time::Duration,
+) -> Result<Option<rw::ChunkAck>, ServerError>
+where
+    W: crate::traits::AsyncWriteToPath,
+{
+    let pending = writer.pending_bytes();
+    if pending < high_water_mark {
+        return Ok(None);
+    }
+
+    debug!(
+        "{} pending bytes exceed the high-water mark, flushing before reading further",
+        pending
+    );
+
+    tokio::time::timeout(deadline, writer.finalize())
+        .await
+        .map_err(|_| ServerError::UploadBackpressure(deadline.as_secs()))?
+        .map_err(ServerError::from)
+}
+
+/// Ingests InfluxDB line protocol text in the sequence identified by the command.
+///
+/// Each received batch must have a single utf8 column, whose values contain one or more lines.
+/// The points of each batch are grouped by measurement and written as they are received in the
+/// topic `<sequence>/<measurement>`, see [`LineProtocolTopic`]. Missing topics are created
+/// using the measurement as ontology tag. Once the stream is completed the written topics are
+/// locked.
+///
+/// The points are always appended, uploads requesting to overwrite the topics are rejected.
+async fn do_put_line_protocol(
+    store: store::StoreRef,
+    repo: repo::Repository,
+    decoder: &mut FlightDataDecoder,
+    schema: SchemaRef,
+    cmd: types::flight::DoPutCmd,
+) -> Result<Vec<rw::ChunkAck>, ServerError> {
+    info!(
+        "client trying to ingest line protocol in '{}' using key `{}`",
+        cmd.resource_locator, cmd.key
+    );
+
+    if cmd.write_mode != types::flight::WriteMode::Append {
+        return Err(ServerError::UnsupportedLineProtocolOption("write_mode"));
+    }
+    if cmd.force {
+        return Err(ServerError::UnsupportedLineProtocolOption("force"));
+    }
+
+    if schema.fields().len() != 1 || *schema.field(0).data_type() != DataType::Utf8 {
+        return Err(ServerError::BadLineProtocolSchema);
+    }
+
+    let shandle = repo::FacadeSequence::new(cmd.resource_locator, store.clone(), repo.clone());
+
+    // perform the match between received key and sequence id
+    let s_id = shandle.resource_id().await?;
+    let received_uuid: uuid::Uuid = cmd.key.parse()?;
+    if received_uuid != s_id.uuid {
+        return Err(ServerError::BadKey);
+    }
+
+    let mut topics: BTreeMap<String, LineProtocolTopic> = BTreeMap::new();
+    let result: Result<_, ServerError> = async {
+        let mut batcher = marshal::line_protocol::LineProtocolBatcher::default();
+        let mut acks = Vec::new();
+
+        while let Some(data) = decoder
+            .try_next()
+            .await
+            .map_err(|e| ServerError::StreamError(e.to_string()))?
+        {
+            match data.payload {
+                DecodedPayload::RecordBatch(batch) => {
+                    for text in batch.column(0).as_string::<i32>().iter().flatten() {
+                        batcher.push_str(text)?;
+                    }
+                }
+                DecodedPayload::Schema(_) => {
+                    return Err(ServerError::DuplicateSchemaInPayload);
+                }
+                DecodedPayload::None => {
+                    return Err(ServerError::NoData);
+                }
+            }
+
+            // Points without a timestamp are assigned the ingestion time (in nanoseconds)
+            let now = i64::from(types::Timestamp::now()) * 1_000_000;
+
+            for (measurement, batch) in batcher.take(now)? {
+                let topic = match topics.entry(measurement) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let topic = LineProtocolTopic::open(
+                            &store,
+                            &repo,
+                            &shandle,
+                            &s_id,
+                            entry.key(),
+                            cmd.deduplicate,
+                        )
+                        .await?;
+                        entry.insert(topic)
+                    }
+                };
+                acks.extend(topic.write(&batch).await?);
+            }
+        }
+
+        if topics.is_empty() {
+            return Err(ServerError::NoData);
+        }
+
+        for topic in topics.values_mut() {
+            acks.extend(topic.writer.finalize().await?);
+            topic.handle.lock().await?;
+            trace!("resource {} locked", topic.handle.locator);
+        }
+
+        Ok(acks)
+    }
+    .await;
+
+    // The leases are released also when the upload fails, so that the topics can be written
+    // again without waiting for the leases to expire
+    let mut released = Ok(());
+    for topic in topics.into_values() {
+        let locator = topic.handle.locator.clone();
+        if let Err(e) = topic.lease.release().await {
+            warn!("unable to release the lease of {}: {}", locator, e);
+            released = Err(e);
+        }
+    }
+
+    let acks = result?;
+    released?;
+    Ok(acks)
+}
+
+/// Topic written by a line protocol upload, holding its lease and the writer of its points
+/// until the upload is completed.
+struct LineProtocolTopic<'a> {
+    handle: repo::FacadeTopic,
+    mdata: types::TopicMetadata<marshal::JsonMetadataBlob>,
+    lease: repo::TopicLease,
+    writer: rw::ChunkedWriter<'a, store::Store>,
+    /// Schema of the chunk being written, the points of a measurement received in different
+    /// batches may carry different columns
+    schema: Option<SchemaRef>,
+}
+
+impl<'a> LineProtocolTopic<'a> {
+    /// Opens the topic of the points of `measurement`, creating it if missing, and acquires its
+    /// lease.
+    async fn open(
+        store: &'a store::StoreRef,
+        repo: &repo::Repository,
+        shandle: &repo::FacadeSequence,
+        s_id: &types::ResourceId,
+        measurement: &str,
+        deduplicate: bool,
+    ) -> Result<Self, ServerError> {
+        let handle = repo::FacadeTopic::new(
+            format!("{}/{}", shandle.locator.name(), measurement),
+            store.clone(),
+            repo.clone(),
+        );
+
+        let r_id = if let Ok(r_id) = handle.resource_id().await {
+            if handle.is_locked().await? {
+                return Err(repo::FacadeError::TopicLocked.into());
+            }
+            r_id
+        } else {
+            types::check_name(handle.locator.name()).map_err(super::ActionError::from)?;
+
+            let properties = types::TopicProperties::new(
+                params::configurables().default_format,
+                measurement.to_owned(),
+            );
+            let user_mdata =
+                marshal::JsonMetadataBlob::try_from_str("{}").map_err(repo::FacadeError::from)?;
+
+            trace!("creating resource {} with {}", handle.locator, properties);
+            handle
+                .create(
+                    &s_id.uuid,
+                    Some(types::TopicMetadata::new(properties, user_mdata)),
+                )
+                .await?
+        };
+
+        let mdata = handle.metadata().await?;
+        let lease = acquire_lease(&handle).await?;
+
+        let writer = async {
+            let first_chunk_index = handle
+                .next_chunk_index(mdata.properties.serialization_format)
+                .await?;
+            let mut writer = topic_writer(store, &handle, repo.clone(), r_id.id, &mdata.properties)
+                .with_target_chunk_bytes(params::configurables().target_chunk_bytes)
+                .with_first_chunk_index(first_chunk_index);
+            if deduplicate {
+                writer = writer.with_deduplication(handle.last_chunk_hash().await?);
+            }
+            Ok::<_, ServerError>(writer)
+        }
+        .await;
+        let writer = match writer {
+            Ok(writer) => writer,
+            Err(e) => {
+                if let Err(release_err) = lease.release().await {
+                    warn!(
+                        "unable to release the lease of {}: {}",
+                        handle.locator, release_err
+                    );
+                }
+                return Err(e);
+            }
+        };
+
+        Ok(Self {
+            handle,
+            mdata,
+            lease,
+            writer,
+            schema: None,
+        })
+    }
+
+    /// Writes the points of a measurement, the chunk being written is closed first if the
+    /// points carry different columns.
+    async fn write(&mut self, batch: &RecordBatch) -> Result<Vec<rw::ChunkAck>, ServerError> {
+        if let Some(registry) = ontology::registry() {
+            registry.validate(&self.mdata.properties.ontology_tag, &batch.schema())?;
+        }
+
+        check_pinned_schema(&self.handle, &self.mdata, &batch.schema()).await?;
+
+        debug!(
+            "writing {} line protocol points on {}",
+            batch.num_rows(),
+            self.handle.locator
+        );
+
+        let mut acks = Vec::new();
+        if self
+            .schema
+            .as_ref()
+            .is_some_and(|schema| *schema != batch.schema())
+        {
+            acks.extend(self.writer.finalize().await?);
+        }
+        self.schema = Some(batch.schema());
+        acks.extend(self.writer.write(batch).await?);
+
+        Ok(acks)
+    }
+}
+
+/// Replays the write-ahead logs left behind by interrupted uploads.
+///
+/// The batches of each log are written as chunks of the logged topic and the topic is locked.
+/// Logs of topics already locked (i.e. the crash happened after the upload completion) are
+/// simply discarded, except for the logs of the data buffered by the [`UploadCoalescer`] which
+/// belongs to completed uploads.
+pub async fn wal_replay(
+    store: store::StoreRef,
+    repo: repo::Repository,
+    dir: impl AsRef<std::path::Path>,
+) -> Result<(), ServerError> {
+    for entry in rw::wal::replay(dir)? {
+        let handle =
+            repo::FacadeTopic::new(entry.resource_locator.clone(), store.clone(), repo.clone());
+
+        // Coalesced data is written before its topic is locked, the log of a locked topic
+        // holds data already written
+        if handle.is_locked().await? {
+            trace!("resource {} already locked, discarding wal", handle.locator);
+            entry.discard()?;
+            continue;
+        }
+
+        warn!(
+            "replaying {} batches from wal of resource {}",
+            entry.batches.len(),
+            handle.locator
+        );
+
+        crate::arrow::check_schema(&entry.schema)?;
+
+        let r_id = handle.resource_id().await?;
+        let mdata = handle.metadata().await?;
+        let first_chunk_index = handle
+            .next_chunk_index(mdata.properties.serialization_format)
+            .await?;
+        let mut writer = topic_writer(&store, &handle, repo.clone(), r_id.id, &mdata.properties)
+            .with_first_chunk_index(first_chunk_index);
+
+        for batch in &entry.batches {
+            writer.write(batch).await?;
+        }
+        writer.finalize().await?;
+
+        handle.lock().await?;
+        entry.discard()?;
+
+        info!("resource {} recovered from wal", handle.locator);
+    }
+
+    Ok(())
+}
+
+/// Acquires the lease of the topic on behalf of this instance.
+pub async fn acquire_lease(handle: &repo::FacadeTopic) -> Result<repo::TopicLease, ServerError> {
+    let params = params::configurables();
+    Ok(handle
+        .acquire_lease(
+            &params.instance_id,
+            std::time::Duration::from_secs(params.upload_lease_ttl_secs),
+        )
+        .await?)
+}
+
+/// Checks that `schema` matches the schema declared at the topic creation, if any.
+///
+/// Otherwise, if the topic schema is pinned, checks that `schema` matches the schema
+/// established by the data already written in the topic. Topics without data accept any
+/// schema.
+async fn check_pinned_schema(
+    handle: &repo::FacadeTopic,
+    mdata: &types::TopicMetadata<marshal::JsonMetadataBlob>,
+    schema: &Schema,
+) -> Result<(), ServerError> {
+    if let Some(declared) = &mdata.properties.schema {
+        rw::check_pinned_schema(declared, schema)?;
+        return Ok(());
+    }
+
+    if !mdata.properties.schema_locked {
+        return Ok(());
+    }
+
+    if let Some(established) = handle
+        .established_schema(mdata.properties.serialization_format)
+        .await?
+    {
+        rw::check_pinned_schema(&established, schema)?;
+    }
+
+    Ok(())
+}
+
+/// Creates the chunk writer of a topic writing in `store`, configured after the topic
+/// `properties`.
+fn configured_writer<'a>(
+    store: &'a store::Store,
+    handle: &repo::FacadeTopic,
+    properties: &types::TopicProperties,
+) -> rw::ChunkedWriter<'a, store::Store> {
+    handle
+        .writer_on(
+            store,
+            properties.serialization_format,
+            properties.chunk_naming,
+        )
+        .with_ordering(properties.ordering)
+        .with_options(rw::WriterOptions::from(properties))
+}
+
+/// Creates the chunk writer of a topic being overwritten.
+///
+/// The chunks produced are not registered but collected in `staged`, so that they replace the
+/// overwritten chunks at once when the upload completes (see
+/// [`repo::FacadeTopic::swap_chunks`]).
+fn staging_writer<'a>(
+    store: &'a store::Store,
+    handle: &repo::FacadeTopic,
+    properties: &types::TopicProperties,
+    staged: Arc<std::sync::Mutex<Vec<repo::WrittenChunk>>>,
+) -> rw::ChunkedWriter<'a, store::Store> {
+    configured_writer(store, handle, properties).on_chunk_created(
+        move |datafile, stats, metadata| {
+            staged.lock().unwrap().push(repo::WrittenChunk {
+                datafile,
+                stats,
+                metadata,
+            });
+            async { Ok(()) }
+        },
+    )
+}
+
+/// Creates the chunk writer of a topic.
+///
+/// The writer is configured with the callback that will be used to create the repository
+/// record for the data catalog every time a chunk is produced.
+fn topic_writer<'a>(
+    store: &'a store::Store,
+    handle: &repo::FacadeTopic,
+    repo: repo::Repository,
+    topic_id: i32,
+    properties: &types::TopicProperties,
+) -> rw::ChunkedWriter<'a, store::Store> {
+    // Prepare variables that will be moved in the closure
+    let ontology_tag = properties.ontology_tag.clone();
+
+    configured_writer(store, handle, properties).on_chunk_created(
         move |target_path, cols_stats, chunk_metadata| {
             let topic_id = topic_id;
             let repo_clone = repo.clone();
@@ -115,42 +932,7 @@ async fn do_put_topic_data(
                 .await?)
             }
         },
-    );
-
-    // Consume all batches
-    while let Some(data) = decoder
-        .try_next()
-        .await
-        .map_err(|e| ServerError::StreamError(e.to_string()))?
-    {
-        match data.payload {
-            DecodedPayload::RecordBatch(batch) => {
-                debug!(
-                    "processing batch (cols: {}, memory_size: {}",
-                    batch.columns().len(),
-                    batch.get_array_memory_size()
-                );
-                writer.write(&batch).await?;
-            }
-            DecodedPayload::Schema(_) => {
-                return Err(ServerError::DuplicateSchemaInPayload);
-            }
-            DecodedPayload::None => {
-                return Err(ServerError::NoData);
-            }
-        }
-    }
-
-    // If the finalize fails (e.g. problems during stats computation) the topic will not be locked,
-    // this allows the reindexing (currently not implemented) of
-    // the topic
-    trace!("finializing data write");
-    writer.finalize().await?;
-
-    trace!("resource {} locked", handle.locator);
-    handle.lock().await?;
-
-    Ok(())
+    )
 }
 
 async fn on_chunk_created(
@@ -166,6 +948,7 @@ async fn on_chunk_created(
         &target_path,
         chunk_metadata.size_bytes as i64,
         chunk_metadata.row_count as i64,
+        Some(chunk_metadata.content_hash),
         &repo,
     )
     .await?;
@@ -177,3 +960,973 @@ async fn on_chunk_created(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::testing;
+    use std::time::Duration;
+
+    /// Write target taking `delay` to complete every write, like a saturated store.
+    struct SlowTarget {
+        delay: Duration,
+    }
+
+    impl crate::traits::AsyncWriteToPath for SlowTarget {
+        async fn write_to_path(
+            &self,
+            _path: impl AsRef<std::path::Path>,
+            _buf: impl Into<bytes::Bytes>,
+        ) -> std::io::Result<()> {
+            tokio::time::sleep(self.delay).await;
+            Ok(())
+        }
+    }
+
+    fn writer(target: &SlowTarget) -> rw::ChunkedWriter<'_, SlowTarget> {
+        let locator = types::TopicResourceLocator::from("sequence/topic");
+        rw::ChunkedWriter::new(target, locator.name(), rw::Format::Default, {
+            let locator = locator.clone();
+            move |_, format, idx, _| locator.datafile(idx, format)
+        })
+        .on_chunk_created(|_, _, _| async { Ok(()) })
+    }
+
+    #[tokio::test]
+    async fn backpressure_below_high_water_mark() {
+        let target = SlowTarget {
+            delay: Duration::from_secs(10),
+        };
+        let mut writer = writer(&target);
+        writer
+            .write(&crate::arrow::testing::dummy_batch())
+            .await
+            .unwrap();
+
+        let pending = writer.pending_bytes();
+        assert!(pending > 0);
+
+        // Nothing is flushed, the slow target is never reached
+        let ack = apply_backpressure(&mut writer, usize::MAX, Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert_eq!(ack, None);
+        assert_eq!(writer.pending_bytes(), pending);
+    }
+
+    #[tokio::test]
+    async fn backpressure_flushes_pending_data() {
+        let target = SlowTarget {
+            delay: Duration::from_millis(50),
+        };
+        let mut writer = writer(&target);
+        writer
+            .write(&crate::arrow::testing::dummy_batch())
+            .await
+            .unwrap();
+
+        let ack = apply_backpressure(&mut writer, 1, Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert_eq!(ack, Some(rw::ChunkAck::Written));
+        assert_eq!(writer.pending_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn backpressure_rejects_slow_store() {
+        let target = SlowTarget {
+            delay: Duration::from_secs(10),
+        };
+        let mut writer = writer(&target);
+        writer
+            .write(&crate::arrow::testing::dummy_batch())
+            .await
+            .unwrap();
+
+        let err = apply_backpressure(&mut writer, 1, Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ServerError::UploadBackpressure(_)));
+        assert_eq!(
+            tonic::Status::from(err).code(),
+            tonic::Code::ResourceExhausted
+        );
+    }
+
+    /// Creates the empty topic `sequence/topic`, returning its key.
+    async fn create_topic(repo: &repo::Repository, store: &store::StoreRef) -> String {
+        let properties = types::TopicProperties::new(rw::Format::Default, "test_tag".to_owned());
+        create_topic_with(repo, store, properties).await
+    }
+
+    /// Creates the empty topic `sequence/topic` with the provided properties, returning its
+    /// key.
+    async fn create_topic_with(
+        repo: &repo::Repository,
+        store: &store::StoreRef,
+        properties: types::TopicProperties,
+    ) -> String {
+        let sequence = testing::create_empty_sequence(repo, store, "sequence")
+            .await
+            .unwrap();
+        let r_id = testing::create_topic_with(repo, store, &sequence, "sequence/topic", properties)
+            .await
+            .unwrap();
+
+        r_id.uuid.to_string()
+    }
+
+    /// Uploads the dummy batch on `sequence/topic` with the provided upload command options.
+    async fn upload(
+        repo: &repo::Repository,
+        store: &store::StoreRef,
+        locks: &Arc<DeferredLocks>,
+        key: &str,
+        options: serde_json::Value,
+    ) -> Result<Vec<rw::ChunkAck>, ServerError> {
+        let coalescer = Arc::new(UploadCoalescer::default());
+        upload_coalesced(repo, store, locks, &coalescer, key, options).await
+    }
+
+    /// Uploads the dummy batch on `sequence/topic`, small uploads may be coalesced by
+    /// `coalescer`.
+    async fn upload_coalesced(
+        repo: &repo::Repository,
+        store: &store::StoreRef,
+        locks: &Arc<DeferredLocks>,
+        coalescer: &Arc<UploadCoalescer>,
+        key: &str,
+        options: serde_json::Value,
+    ) -> Result<Vec<rw::ChunkAck>, ServerError> {
+        let batch = crate::arrow::testing::dummy_batch();
+        upload_batch(repo, store, locks, coalescer, key, options, batch).await
+    }
+
+    /// Uploads `batch` on `sequence/topic`, small uploads may be coalesced by `coalescer`.
+    async fn upload_batch(
+        repo: &repo::Repository,
+        store: &store::StoreRef,
+        locks: &Arc<DeferredLocks>,
+        coalescer: &Arc<UploadCoalescer>,
+        key: &str,
+        options: serde_json::Value,
+        batch: RecordBatch,
+    ) -> Result<Vec<rw::ChunkAck>, ServerError> {
+        upload_stream(repo, store, locks, coalescer, key, options, vec![Ok(batch)]).await
+    }
+
+    /// Uploads on `sequence/topic` the stream of `batches`, which may be interrupted by an
+    /// error.
+    async fn upload_stream(
+        repo: &repo::Repository,
+        store: &store::StoreRef,
+        locks: &Arc<DeferredLocks>,
+        coalescer: &Arc<UploadCoalescer>,
+        key: &str,
+        options: serde_json::Value,
+        batches: Vec<Result<RecordBatch, arrow_flight::error::FlightError>>,
+    ) -> Result<Vec<rw::ChunkAck>, ServerError> {
+        let mut decoder = upload_decoder(key, options, batches);
+
+        let limiter = Limiter::uploads(None, Default::default());
+        do_put(
+            store.clone(),
+            repo.clone(),
+            &limiter,
+            locks,
+            coalescer,
+            None,
+            &auth::AllowAll,
+            &Caller::anonymous(),
+            &mut decoder,
+        )
+        .await
+    }
+
+    /// Builds the decoder of an upload on `sequence/topic` of the stream of `batches`, with the
+    /// provided upload command options.
+    fn upload_decoder(
+        key: &str,
+        options: serde_json::Value,
+        batches: Vec<Result<RecordBatch, arrow_flight::error::FlightError>>,
+    ) -> FlightDataDecoder {
+        let mut cmd = serde_json::json!({"resource_locator": "sequence/topic", "key": key});
+        cmd.as_object_mut()
+            .unwrap()
+            .extend(options.as_object().unwrap().clone());
+
+        let encoder = arrow_flight::encode::FlightDataEncoderBuilder::new()
+            .with_flight_descriptor(Some(arrow_flight::FlightDescriptor::new_cmd(
+                cmd.to_string(),
+            )))
+            .build(futures::stream::iter(batches));
+        FlightDataDecoder::new(encoder)
+    }
+
+    /// Returns the number of chunks and rows of `sequence/topic`.
+    async fn topic_chunks(repo: &repo::Repository, store: &store::StoreRef) -> (usize, i64) {
+        testing::topic_chunks(repo, store, "sequence/topic").await
+    }
+
+    #[sqlx::test]
+    async fn upload_appends_by_default(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let key = create_topic(&repo, &store).await;
+        let rows = crate::arrow::testing::dummy_batch().num_rows() as i64;
+        let locks = Arc::new(DeferredLocks::default());
+
+        upload(&repo, &store, &locks, &key, serde_json::json!({}))
+            .await
+            .unwrap();
+        upload(
+            &repo,
+            &store,
+            &locks,
+            &key,
+            serde_json::json!({"write_mode": "append"}),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(topic_chunks(&repo, &store).await, (2, 2 * rows));
+
+        Ok(())
+    }
+
+    /// Authorizer denying the uploads to the `guest` caller
+    struct DenyGuestUpload;
+
+    impl Authorizer for DenyGuestUpload {
+        fn authorize(
+            &self,
+            caller: &Caller,
+            operation: Operation<'_>,
+            _resource: Option<&str>,
+            _access: Access,
+        ) -> auth::Decision {
+            match (caller.identity.as_deref(), operation) {
+                (Some("guest"), Operation::Upload) => auth::Decision::Deny,
+                _ => auth::Decision::Allow,
+            }
+        }
+    }
+
+    #[sqlx::test]
+    async fn upload_authorization(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let key = create_topic(&repo, &store).await;
+        let rows = crate::arrow::testing::dummy_batch().num_rows() as i64;
+        let locks = Arc::new(DeferredLocks::default());
+        let coalescer = Arc::new(UploadCoalescer::default());
+        let limiter = Limiter::uploads(None, Default::default());
+
+        let upload_as = async |caller: Caller| {
+            let batches = vec![Ok(crate::arrow::testing::dummy_batch())];
+            let mut decoder = upload_decoder(&key, serde_json::json!({}), batches);
+            do_put(
+                (*store).clone(),
+                (*repo).clone(),
+                &limiter,
+                &locks,
+                &coalescer,
+                None,
+                &DenyGuestUpload,
+                &caller,
+                &mut decoder,
+            )
+            .await
+        };
+
+        // Denied, no data is written
+        let err = upload_as(Caller::new("guest")).await.unwrap_err();
+        assert!(matches!(
+            err,
+            ServerError::ActionFailed(crate::server::endpoints::ActionError::PermissionDenied(_))
+        ));
+        assert_eq!(topic_chunks(&repo, &store).await, (0, 0));
+
+        upload_as(Caller::new("admin")).await.unwrap();
+        assert_eq!(topic_chunks(&repo, &store).await, (1, rows));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn upload_rejected_on_finalized(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let key = create_topic(&repo, &store).await;
+        let rows = crate::arrow::testing::dummy_batch().num_rows() as i64;
+        let locks = Arc::new(DeferredLocks::default());
+
+        upload(&repo, &store, &locks, &key, serde_json::json!({}))
+            .await
+            .unwrap();
+        repo::FacadeSequence::new("sequence".to_owned(), (*store).clone(), (*repo).clone())
+            .lock()
+            .await
+            .unwrap();
+
+        let err = upload(&repo, &store, &locks, &key, serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ServerError::FacadeError(repo::FacadeError::InvalidTransition(_))
+            ),
+            "{err}"
+        );
+        assert_eq!(
+            tonic::Status::from(err).code(),
+            tonic::Code::FailedPrecondition
+        );
+        assert_eq!(topic_chunks(&repo, &store).await, (1, rows));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn upload_declared_schema(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use arrow::datatypes::Field;
+
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let locks = Arc::new(DeferredLocks::default());
+
+        // The declared schema has a `value` column of a different type than the uploaded one
+        let declared = Arc::new(Schema::new(vec![
+            Field::new(
+                params::ARROW_SCHEMA_COLUMN_NAME_TIMESTAMP,
+                DataType::Int64,
+                false,
+            ),
+            Field::new("value", DataType::Float64, false),
+        ]));
+        let properties = types::TopicProperties::new(rw::Format::Default, "test_tag".to_owned())
+            .with_schema(Some(declared));
+        let key = create_topic_with(&repo, &store, properties).await;
+
+        // Even the first upload of the topic is rejected
+        let err = upload(&repo, &store, &locks, &key, serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ServerError::RwError(rw::Error::IncompatibleSchema(_))
+        ));
+        assert_eq!(topic_chunks(&repo, &store).await, (0, 0));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn upload_inherited_time_column(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use arrow::array::{Float64Array, Int64Array};
+        use arrow::datatypes::Field;
+
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let ts_engine =
+            Arc::new(crate::query::TimeseriesGateway::try_new((*store).clone()).unwrap());
+        let locks = Arc::new(DeferredLocks::default());
+
+        let action = async |name: &str, body: serde_json::Value| {
+            let action =
+                marshal::ActionRequest::try_new(name, body.to_string().as_bytes()).unwrap();
+            crate::server::endpoints::do_action(
+                (*store).clone(),
+                (*repo).clone(),
+                ts_engine.clone(),
+                action,
+            )
+            .await
+            .unwrap()
+        };
+
+        // The sequence declares the time column and precision of its topics
+        let marshal::ActionResponse::SequenceCreate(sequence) = action(
+            "sequence_create",
+            serde_json::json!({
+                "name": "sequence",
+                "time_column": "t",
+                "time_precision": "micros",
+                "user_metadata": {},
+            }),
+        )
+        .await
+        else {
+            panic!("wrong response returned")
+        };
+        let marshal::ActionResponse::TopicCreate(topic) = action(
+            "topic_create",
+            serde_json::json!({
+                "name": "sequence/topic",
+                "sequence_key": sequence.key,
+                "ontology_tag": "test_tag",
+                "user_metadata": {},
+            }),
+        )
+        .await
+        else {
+            panic!("wrong response returned")
+        };
+
+        let properties = repo::FacadeTopic::new(
+            "sequence/topic".to_owned(),
+            (*store).clone(),
+            (*repo).clone(),
+        )
+        .metadata()
+        .await
+        .unwrap()
+        .properties;
+        assert_eq!(properties.time_column.as_deref(), Some("t"));
+        assert_eq!(properties.time_precision, types::TimePrecision::Micros);
+
+        // The uploaded data holds its timestamps, in microseconds, in the `t` column
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("value", DataType::Float64, false),
+            Field::new("t", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0, 4.0])),
+                Arc::new(Int64Array::from(vec![10, 20, 30, 40])),
+            ],
+        )
+        .unwrap();
+        let coalescer = Arc::new(UploadCoalescer::default());
+        upload_batch(
+            &repo,
+            &store,
+            &locks,
+            &coalescer,
+            &topic.key,
+            serde_json::json!({}),
+            batch,
+        )
+        .await
+        .unwrap();
+
+        // The queried range is in nanoseconds, holding the rows at 20us and 30us
+        let marshal::ActionResponse::QueryAggregate(count) = action(
+            "query_aggregate",
+            serde_json::json!({
+                "locator": "sequence/topic",
+                "aggregates": [{"function": "count", "column": "value"}],
+                "range": [15_000, 35_000],
+            }),
+        )
+        .await
+        else {
+            panic!("wrong response returned")
+        };
+        assert_eq!(
+            serde_json::to_value(&count.values).unwrap(),
+            serde_json::json!([2])
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn upload_overwrites(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use crate::traits::AsExtension;
+        use arrow_flight::error::FlightError;
+
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let key = create_topic(&repo, &store).await;
+        let rows = crate::arrow::testing::dummy_batch().num_rows() as i64;
+
+        // The topic is kept unlocked between the uploads
+        let locks = Arc::new(DeferredLocks::new(Some(Duration::from_secs(3600))));
+
+        upload(&repo, &store, &locks, &key, serde_json::json!({}))
+            .await
+            .unwrap();
+        upload(&repo, &store, &locks, &key, serde_json::json!({}))
+            .await
+            .unwrap();
+        let previous = repo::FacadeTopic::new(
+            "sequence/topic".to_owned(),
+            (*store).clone(),
+            (*repo).clone(),
+        )
+        .datafiles()
+        .await
+        .unwrap();
+
+        // A failed overwrite keeps the previous chunks
+        let batches = vec![
+            Ok(crate::arrow::testing::dummy_batch()),
+            Err(FlightError::ProtocolError("client disconnected".to_owned())),
+        ];
+        let coalescer = Arc::new(UploadCoalescer::default());
+        let options = serde_json::json!({"write_mode": "overwrite"});
+        upload_stream(&repo, &store, &locks, &coalescer, &key, options, batches)
+            .await
+            .unwrap_err();
+        assert_eq!(topic_chunks(&repo, &store).await, (2, 2 * rows));
+
+        upload(
+            &repo,
+            &store,
+            &locks,
+            &key,
+            serde_json::json!({"write_mode": "overwrite"}),
+        )
+        .await
+        .unwrap();
+
+        // Only the chunk of the last upload is left, the previous datafiles are deleted
+        assert_eq!(topic_chunks(&repo, &store).await, (1, rows));
+        for datafile in &previous {
+            assert!(store.read_bytes(datafile).await.is_err());
+        }
+        let stored = store
+            .list("sequence/topic", Some(&rw::Format::Default.as_extension()))
+            .await
+            .unwrap();
+        assert_eq!(stored.len(), 1);
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn upload_overwrite_locked(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let key = create_topic(&repo, &store).await;
+        let rows = crate::arrow::testing::dummy_batch().num_rows() as i64;
+
+        // The topic is locked once the upload completes
+        let locks = Arc::new(DeferredLocks::default());
+        upload(&repo, &store, &locks, &key, serde_json::json!({}))
+            .await
+            .unwrap();
+
+        let err = upload(
+            &repo,
+            &store,
+            &locks,
+            &key,
+            serde_json::json!({"write_mode": "overwrite"}),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            tonic::Status::from(err).code(),
+            tonic::Code::FailedPrecondition
+        );
+        assert_eq!(topic_chunks(&repo, &store).await, (1, rows));
+
+        upload(
+            &repo,
+            &store,
+            &locks,
+            &key,
+            serde_json::json!({"write_mode": "overwrite", "force": true}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(topic_chunks(&repo, &store).await, (1, rows));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn line_protocol_rejects_overwrite(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use arrow::array::StringArray;
+        use arrow::datatypes::Field;
+
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        create_topic(&repo, &store).await;
+        let key =
+            repo::FacadeSequence::new("sequence".to_owned(), (*store).clone(), (*repo).clone())
+                .resource_id()
+                .await
+                .unwrap()
+                .uuid
+                .to_string();
+        let locks = Arc::new(DeferredLocks::default());
+        let coalescer = Arc::new(UploadCoalescer::default());
+
+        let schema = Arc::new(Schema::new(vec![Field::new("line", DataType::Utf8, false)]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(StringArray::from(vec!["cpu value=1 10"]))],
+        )
+        .unwrap();
+
+        for options in [
+            serde_json::json!({"write_mode": "overwrite"}),
+            serde_json::json!({"force": true}),
+        ] {
+            let mut options = options;
+            options["resource_locator"] = "sequence".into();
+            options["line_protocol"] = true.into();
+            let err = upload_batch(
+                &repo,
+                &store,
+                &locks,
+                &coalescer,
+                &key,
+                options,
+                batch.clone(),
+            )
+            .await
+            .unwrap_err();
+            assert!(matches!(err, ServerError::UnsupportedLineProtocolOption(_)));
+        }
+
+        // No measurement topic was written
+        let cpu =
+            repo::FacadeTopic::new("sequence/cpu".to_owned(), (*store).clone(), (*repo).clone());
+        assert!(cpu.resource_id().await.is_err());
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn line_protocol_streams_topics(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use arrow::array::StringArray;
+        use arrow::datatypes::Field;
+        use arrow_flight::error::FlightError;
+
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        create_topic(&repo, &store).await;
+        let key =
+            repo::FacadeSequence::new("sequence".to_owned(), (*store).clone(), (*repo).clone())
+                .resource_id()
+                .await
+                .unwrap()
+                .uuid
+                .to_string();
+        let locks = Arc::new(DeferredLocks::default());
+        let coalescer = Arc::new(UploadCoalescer::default());
+        let options = serde_json::json!({"resource_locator": "sequence", "line_protocol": true});
+
+        let schema = Arc::new(Schema::new(vec![Field::new("line", DataType::Utf8, false)]));
+        let lines = |text: &str| {
+            Ok(RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(StringArray::from(vec![text.to_owned()]))],
+            )
+            .unwrap())
+        };
+
+        // Each batch is written as it is received, new columns close the chunk being written
+        let batches = vec![lines("cpu value=1 10"), lines("cpu value=2,load=3 20")];
+        let acks = upload_stream(
+            &repo,
+            &store,
+            &locks,
+            &coalescer,
+            &key,
+            options.clone(),
+            batches,
+        )
+        .await
+        .unwrap();
+        assert_eq!(acks, [rw::ChunkAck::Written; 2]);
+        assert_eq!(
+            testing::topic_chunks(&repo, &store, "sequence/cpu").await,
+            (2, 2)
+        );
+
+        let cpu =
+            repo::FacadeTopic::new("sequence/cpu".to_owned(), (*store).clone(), (*repo).clone());
+        assert!(cpu.is_locked().await.unwrap());
+        acquire_lease(&cpu).await.unwrap().release().await.unwrap();
+
+        // A failed upload releases the leases of the topics written
+        let batches = vec![
+            lines("mem used=5i 10"),
+            Err(FlightError::ProtocolError("client disconnected".to_owned())),
+        ];
+        upload_stream(&repo, &store, &locks, &coalescer, &key, options, batches)
+            .await
+            .unwrap_err();
+
+        let mem =
+            repo::FacadeTopic::new("sequence/mem".to_owned(), (*store).clone(), (*repo).clone());
+        assert!(!mem.is_locked().await.unwrap());
+        acquire_lease(&mem).await.unwrap().release().await.unwrap();
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn failed_upload_keeps_lock(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        use arrow_flight::error::FlightError;
+
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let key = create_topic(&repo, &store).await;
+        let rows = crate::arrow::testing::dummy_batch().num_rows() as i64;
+        let locks = Arc::new(DeferredLocks::default());
+        let coalescer = Arc::new(UploadCoalescer::default());
+        let handle = repo::FacadeTopic::new(
+            "sequence/topic".to_owned(),
+            (*store).clone(),
+            (*repo).clone(),
+        );
+
+        upload(&repo, &store, &locks, &key, serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(handle.state().await.unwrap(), types::ResourceState::Locked);
+
+        // Appends don't reopen the topic, a forced overwrite reopens it until it completes
+        for options in [
+            serde_json::json!({}),
+            serde_json::json!({"write_mode": "overwrite", "force": true}),
+        ] {
+            let batches = vec![
+                Ok(crate::arrow::testing::dummy_batch()),
+                Err(FlightError::ProtocolError("client disconnected".to_owned())),
+            ];
+            let err = upload_stream(&repo, &store, &locks, &coalescer, &key, options, batches)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, ServerError::StreamError(_)), "{err}");
+            assert_eq!(handle.state().await.unwrap(), types::ResourceState::Locked);
+            assert_eq!(topic_chunks(&repo, &store).await, (1, rows));
+        }
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn upload_compacts_tail(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let properties = types::TopicProperties::new(rw::Format::Default, "test_tag".to_owned())
+            .with_chunk_naming(types::ChunkNaming::TimeRange);
+        let key = create_topic_with(&repo, &store, properties).await;
+        let rows = crate::arrow::testing::dummy_batch().num_rows() as i64;
+        let locks = Arc::new(DeferredLocks::new(Some(Duration::from_secs(3600))));
+        let handle = repo::FacadeTopic::new(
+            "sequence/topic".to_owned(),
+            (*store).clone(),
+            (*repo).clone(),
+        );
+
+        for _ in 0..2 {
+            upload(&repo, &store, &locks, &key, serde_json::json!({}))
+                .await
+                .unwrap();
+        }
+        assert_eq!(topic_chunks(&repo, &store).await, (2, 2 * rows));
+
+        // The upload going over the maximum number of chunks triggers the compaction
+        let batches = vec![Ok(crate::arrow::testing::dummy_batch())];
+        let mut decoder = upload_decoder(&key, serde_json::json!({}), batches);
+        do_put(
+            (*store).clone(),
+            (*repo).clone(),
+            &Limiter::uploads(None, Default::default()),
+            &locks,
+            &Arc::new(UploadCoalescer::default()),
+            Some(2),
+            &auth::AllowAll,
+            &Caller::anonymous(),
+            &mut decoder,
+        )
+        .await
+        .unwrap();
+
+        // The last two chunks are merged in background, no row is lost
+        let mut compacted = false;
+        for _ in 0..100 {
+            if topic_chunks(&repo, &store).await.0 == 2 {
+                compacted = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(compacted);
+        assert_eq!(topic_chunks(&repo, &store).await, (2, 3 * rows));
+
+        // The merged chunk is numbered after the original ones and named after its time range
+        let merged = handle.datafiles().await.unwrap().pop().unwrap();
+        assert_eq!(types::datafile_index(&merged), Some(3));
+        assert!(
+            merged
+                .file_stem()
+                .unwrap()
+                .to_string_lossy()
+                .ends_with("_10000_10030")
+        );
+
+        // Wait for the compaction to release the lease
+        let lease = loop {
+            match handle
+                .acquire_lease("test_instance", Duration::from_secs(60))
+                .await
+            {
+                Ok(lease) => break lease,
+                Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+            }
+        };
+        lease.release().await.unwrap();
+
+        // The next upload is numbered after the merged chunk
+        upload(&repo, &store, &locks, &key, serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(topic_chunks(&repo, &store).await, (3, 4 * rows));
+        let last = handle.datafiles().await.unwrap().pop().unwrap();
+        assert_eq!(types::datafile_index(&last), Some(4));
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn upload_coalesces_small_uploads(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let key = create_topic(&repo, &store).await;
+        let rows = crate::arrow::testing::dummy_batch().num_rows() as i64;
+        let locks = Arc::new(DeferredLocks::default());
+
+        let wal_dir = std::env::temp_dir().join(crate::utils::random::random_string(10));
+        let max_delay = Duration::from_millis(200);
+        let coalescer = Arc::new(UploadCoalescer::new(
+            Some(1024 * 1024),
+            max_delay,
+            Some(wal_dir.clone()),
+        ));
+
+        for _ in 0..3 {
+            let acks = upload_coalesced(
+                &repo,
+                &store,
+                &locks,
+                &coalescer,
+                &key,
+                serde_json::json!({}),
+            )
+            .await
+            .unwrap();
+            assert_eq!(acks, [rw::ChunkAck::Coalesced]);
+        }
+
+        // Nothing is written yet, the buffered data is kept in the write-ahead log and the topic
+        // is not locked until the data is written
+        let handle =
+            repo::FacadeTopic::new("sequence/topic".to_owned(), store.clone(), repo.clone());
+        assert_eq!(topic_chunks(&repo, &store).await, (0, 0));
+        assert!(!handle.is_locked().await.unwrap());
+        let logs = rw::wal::replay(&wal_dir).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].batches.len(), 3);
+
+        // Once the maximum delay elapses the three uploads are written as a single chunk and the
+        // topic is locked
+        tokio::time::sleep(max_delay * 5).await;
+        assert_eq!(topic_chunks(&repo, &store).await, (1, 3 * rows));
+        assert!(handle.is_locked().await.unwrap());
+        assert!(rw::wal::replay(&wal_dir).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&wal_dir).unwrap();
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn upload_time_ascending_after_stored_rows(
+        pool: sqlx::Pool<repo::Database>,
+    ) -> sqlx::Result<()> {
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+        let mut properties =
+            types::TopicProperties::new(rw::Format::Default, "test_tag".to_owned());
+        properties.ordering = types::RowOrdering::TimeAscending;
+        let key = create_topic_with(&repo, &store, properties).await;
+        let locks = Arc::new(DeferredLocks::new(Some(Duration::from_secs(60))));
+        let coalescer = Arc::new(UploadCoalescer::default());
+
+        let dummy = crate::arrow::testing::dummy_batch();
+        let batch = |timestamps: Vec<i64>| {
+            let values = arrow::array::Int64Array::from_iter_values(0..timestamps.len() as i64);
+            RecordBatch::try_new(
+                dummy.schema(),
+                vec![
+                    Arc::new(arrow::array::Int64Array::from(timestamps)),
+                    Arc::new(values),
+                ],
+            )
+            .unwrap()
+        };
+        let options = || serde_json::json!({});
+
+        upload_batch(
+            &repo,
+            &store,
+            &locks,
+            &coalescer,
+            &key,
+            options(),
+            batch(vec![10, 20]),
+        )
+        .await
+        .unwrap();
+
+        // The rows of a new upload can't precede the rows stored by the previous ones
+        let err = upload_batch(
+            &repo,
+            &store,
+            &locks,
+            &coalescer,
+            &key,
+            options(),
+            batch(vec![15, 30]),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(err, ServerError::RwError(rw::Error::OutOfOrder(_))),
+            "{err}"
+        );
+        assert_eq!(
+            tonic::Status::from(err).code(),
+            tonic::Code::InvalidArgument
+        );
+
+        upload_batch(
+            &repo,
+            &store,
+            &locks,
+            &coalescer,
+            &key,
+            options(),
+            batch(vec![20, 30]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(topic_chunks(&repo, &store).await, (2, 4));
+
+        Ok(())
+    }
+}
@@ -40,12 +40,16 @@ pub async fn get_flight_info(
 
                     trace!("{} generating endpoints", handle.locator);
                     let topics = handle.topic_list().await?;
-                    let endpoints = topics.into_iter().map(|topic| {
-                        let ticket: String = topic.into();
-                        FlightEndpoint::new().with_ticket(Ticket {
-                            ticket: ticket.into(),
+                    let endpoints = topics
+                        .into_iter()
+                        .map(|topic| {
+                            let ticket =
+                                marshal::flight::do_get_ticket_to_bytes(topic.into(), &cmd)?;
+                            Ok::<_, ServerError>(FlightEndpoint::new().with_ticket(Ticket {
+                                ticket: ticket.into(),
+                            }))
                         })
-                    });
+                        .collect::<Result<Vec<_>, _>>()?;
 
                     trace!("{} generating response", handle.locator);
                     let mut flight_info = FlightInfo::new()
@@ -73,7 +77,10 @@ pub async fn get_flight_info(
                     let schema =
                         Schema::new_with_metadata(schema.fields().clone(), flatten_metadata);
 
-                    let ticket: String = handle.locator.clone().into();
+                    let ticket = marshal::flight::do_get_ticket_to_bytes(
+                        handle.locator.clone().into(),
+                        &cmd,
+                    )?;
                     // building a single endpoint for topic data
                     let endpoint = FlightEndpoint::new().with_ticket(Ticket {
                         ticket: ticket.into(),
@@ -5,8 +5,9 @@ mod do_put;
 mod get_flight_info;
 mod list_flights;
 
-pub use do_action::do_action;
+pub use actions::{ActionContext, ActionError};
+pub use do_action::{do_action, do_action_idempotent};
 pub use do_get::do_get;
-pub use do_put::do_put;
+pub use do_put::{acquire_lease, do_put, wal_replay, write_batches};
 pub use get_flight_info::get_flight_info;
 pub use list_flights::list_flights;
@@ -91,6 +91,30 @@ pub enum ServerError {
 
     #[error("query error :: {0}")]
     QueryError(#[from] query::Error),
+
+    #[error("ontology error :: {0}")]
+    OntologyError(#[from] crate::ontology::Error),
+
+    #[error("line protocol error :: {0}")]
+    LineProtocolError(#[from] crate::marshal::line_protocol::Error),
+
+    #[error("expected a single utf8 column containing line protocol text")]
+    BadLineProtocolSchema,
+
+    #[error("`{0}` is not supported by line protocol uploads")]
+    UnsupportedLineProtocolOption(&'static str),
+
+    #[error("store too slow, pending upload data not flushed within {0}s")]
+    UploadBackpressure(u64),
+
+    #[error("query exceeded the timeout of {0}s")]
+    QueryTimeout(u64),
+
+    #[error("query cancelled by the client")]
+    QueryCancelled,
+
+    #[error("action failed :: {0}")]
+    ActionFailed(#[from] super::endpoints::ActionError),
 }
 
 impl From<ServerError> for tonic::Status {
@@ -100,6 +124,26 @@ impl From<ServerError> for tonic::Status {
             ServerError::MultiplePathUnsupported => Status::invalid_argument(value.to_string()),
             ServerError::MissingDescriptior => Status::invalid_argument(value.to_string()),
             ServerError::BadTicket(_) => Status::invalid_argument(value.to_string()),
+            ServerError::OntologyError(crate::ontology::Error::SchemaMismatch { .. }) => {
+                Status::invalid_argument(value.to_string())
+            }
+            ServerError::RwError(rw::Error::IncompatibleSchema(_))
+            | ServerError::RwError(rw::Error::OutOfOrder(_)) => {
+                Status::invalid_argument(value.to_string())
+            }
+            ServerError::LineProtocolError(_) => Status::invalid_argument(value.to_string()),
+            ServerError::BadLineProtocolSchema | ServerError::UnsupportedLineProtocolOption(_) => {
+                Status::invalid_argument(value.to_string())
+            }
+            ServerError::ActionFailed(err) => err.into(),
+            ServerError::QueryTimeout(_) => Status::deadline_exceeded(value.to_string()),
+            ServerError::QueryCancelled => Status::cancelled(value.to_string()),
+            ServerError::UploadBackpressure(_) => Status::resource_exhausted(value.to_string()),
+            ServerError::FacadeError(crate::repo::FacadeError::TopicLeased(_))
+            | ServerError::FacadeError(crate::repo::FacadeError::TopicLocked)
+            | ServerError::FacadeError(crate::repo::FacadeError::InvalidTransition(_)) => {
+                Status::failed_precondition(value.to_string())
+            }
 
             _ => Status::internal(value.to_string()),
         }
@@ -1,6 +1,10 @@
+use crate::server::auth::{AllowAll, Authorizer, Caller};
+use crate::server::deferred_lock::DeferredLocks;
 use crate::server::endpoints;
 use crate::server::errors::ServerError;
-use crate::{marshal, params, query, repo, store};
+use crate::server::limiter::Limiter;
+use crate::server::upload_coalescer::UploadCoalescer;
+use crate::{marshal, params, query, repo, store, types};
 use arrow_flight::decode::FlightDataDecoder;
 use arrow_flight::{
     Action as FlightAction, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightInfo,
@@ -9,9 +13,14 @@ use arrow_flight::{
 };
 use futures::TryStreamExt;
 use futures::stream::BoxStream;
+use http_body_util::{BodyExt, LengthLimitError, Limited};
 use log::{error, trace};
 use std::sync::Arc;
+use std::task::{Context, Poll};
 use tokio::sync::Notify;
+use tonic::body::Body;
+use tonic::codegen::{Service, StdError, http};
+use tonic::server::NamedService;
 use tonic::transport::Server;
 use tonic::{Request, Response, Status, Streaming};
 
@@ -30,18 +39,25 @@ pub async fn start(
     config: Config,
     store: store::StoreRef,
     repo: repo::Repository,
+    authorizer: Arc<dyn Authorizer>,
     shutdown: Option<ShutdownNotifier>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let addr = format!("{}:{}", config.host, config.port).parse()?;
 
-    let service = MosaicoFlightService::try_new(store, repo)?;
+    let service =
+        MosaicoFlightService::try_new(store.clone(), repo.clone())?.with_authorizer(authorizer);
 
-    let svc = FlightServiceServer::new(service);
+    // The topic locks deferred before the server stopped are scheduled again
+    service.deferred_locks.recover(store, repo).await?;
 
-    let server = Server::builder().add_service(
-        svc.max_decoding_message_size(params::configurables().max_message_size_in_bytes)
-            .max_encoding_message_size(params::configurables().max_message_size_in_bytes),
-    );
+    let svc = FlightServiceServer::new(service)
+        .max_decoding_message_size(params::configurables().max_message_size_in_bytes)
+        .max_encoding_message_size(params::configurables().max_message_size_in_bytes);
+
+    let server = Server::builder().add_service(ActionBodyLimit::new(
+        svc,
+        params::configurables().max_action_body_bytes,
+    ));
 
     if let Some(shutdown_notifier) = shutdown {
         server
@@ -61,19 +77,55 @@ struct MosaicoFlightService {
     store: store::StoreRef,
     repo: repo::Repository,
     ts_engine: query::TimeseriesGatewayRef,
+    authorizer: Arc<dyn Authorizer>,
+    upload_limiter: Limiter,
+    query_limiter: Limiter,
+    deferred_locks: Arc<DeferredLocks>,
+    upload_coalescer: Arc<UploadCoalescer>,
+    /// Clock read by the time-dependent logic of the actions and of the deferred locks
+    clock: Arc<dyn types::Clock>,
 }
 
 impl MosaicoFlightService {
     pub fn try_new(store: store::StoreRef, repo: repo::Repository) -> Result<Self, String> {
         let ts_engine =
             Arc::new(query::TimeseriesGateway::try_new(store.clone()).map_err(|e| e.to_string())?);
+        let clock: Arc<dyn types::Clock> = Arc::new(types::SystemClock);
 
         Ok(MosaicoFlightService {
             store,
             repo,
             ts_engine,
+            authorizer: Arc::new(AllowAll),
+            upload_limiter: Limiter::uploads(
+                params::configurables().max_uploads_per_sequence,
+                params::configurables().upload_limit_policy,
+            ),
+            query_limiter: Limiter::queries(
+                params::configurables().max_queries_per_topic,
+                params::configurables().query_limit_policy,
+            ),
+            deferred_locks: Arc::new(
+                DeferredLocks::new(
+                    params::configurables()
+                        .upload_lock_grace_secs
+                        .map(std::time::Duration::from_secs),
+                )
+                .with_clock(clock.clone()),
+            ),
+            upload_coalescer: Arc::new(UploadCoalescer::new(
+                params::configurables().upload_coalesce_bytes,
+                std::time::Duration::from_secs(params::configurables().upload_coalesce_secs),
+                params::configurables().wal_dir.clone(),
+            )),
+            clock,
         })
     }
+
+    pub fn with_authorizer(mut self, authorizer: Arc<dyn Authorizer>) -> Self {
+        self.authorizer = authorizer;
+        self
+    }
 }
 #[tonic::async_trait]
 impl FlightService for MosaicoFlightService {
@@ -142,12 +194,16 @@ impl FlightService for MosaicoFlightService {
         &self,
         request: Request<Ticket>,
     ) -> Result<Response<Self::DoGetStream>, Status> {
+        let caller = Caller::from_metadata(request.metadata());
         let ticket = request.into_inner();
 
         let data_stream = endpoints::do_get(
             self.store.clone(),
             self.repo.clone(),
             self.ts_engine.clone(),
+            &self.query_limiter,
+            self.authorizer.as_ref(),
+            &caller,
             ticket,
         )
         .await
@@ -165,39 +221,90 @@ impl FlightService for MosaicoFlightService {
         &self,
         request: Request<Streaming<FlightData>>,
     ) -> Result<Response<Self::DoPutStream>, Status> {
+        let caller = Caller::from_metadata(request.metadata());
         let stream = request.into_inner();
         let mut decoder = FlightDataDecoder::new(stream.map_err(Into::into));
 
-        endpoints::do_put(self.store.clone(), self.repo.clone(), &mut decoder)
-            .await
-            .inspect_err(log_server_error)?;
+        let acks = endpoints::do_put(
+            self.store.clone(),
+            self.repo.clone(),
+            &self.upload_limiter,
+            &self.deferred_locks,
+            &self.upload_coalescer,
+            params::configurables().max_chunks_per_topic,
+            self.authorizer.as_ref(),
+            &caller,
+            &mut decoder,
+        )
+        .await
+        .inspect_err(log_server_error)?;
 
-        Ok(Response::new(Box::pin(futures::stream::empty())))
+        let results = acks
+            .into_iter()
+            .map(|ack| {
+                let app_metadata = marshal::flight::do_put_ack_to_bytes(ack)
+                    .map_err(ServerError::from)
+                    .inspect_err(log_server_error)?;
+                Ok(PutResult {
+                    app_metadata: app_metadata.into(),
+                })
+            })
+            .collect::<Vec<Result<PutResult, Status>>>();
+
+        Ok(Response::new(Box::pin(futures::stream::iter(results))))
     }
 
     async fn do_action(
         &self,
         request: Request<FlightAction>,
     ) -> Result<Response<Self::DoActionStream>, Status> {
-        let action = request.into_inner();
-        let action = marshal::ActionRequest::try_new(action.r#type.as_str(), &action.body)
-            .map_err(ServerError::from)
-            .inspect_err(log_server_error)?;
+        let caller = Caller::from_metadata(request.metadata());
+        let encoding = marshal::ResponseEncoding::from_header(
+            request
+                .metadata()
+                .get(marshal::RESPONSE_ENCODING_METADATA_KEY)
+                .and_then(|v| v.to_str().ok()),
+        )
+        .map_err(|e| ServerError::from(endpoints::ActionError::from(e)))
+        .inspect_err(log_server_error)?;
+
+        let raw_action = request.into_inner();
+        let action = marshal::ActionRequest::try_new_bounded(
+            raw_action.r#type.as_str(),
+            &raw_action.body,
+            params::configurables().max_action_body_bytes,
+        )
+        .map_err(|e| ServerError::from(endpoints::ActionError::from(e)))
+        .inspect_err(log_server_error)?;
+
+        let idempotency_key = if action.is_mutating() {
+            marshal::idempotency_key(&raw_action.r#type, &raw_action.body)
+                .map_err(ServerError::from)
+                .inspect_err(log_server_error)?
+        } else {
+            None
+        };
 
-        let response = endpoints::do_action(
+        let ctx = endpoints::ActionContext::new(
             self.store.clone(),
             self.repo.clone(),
             self.ts_engine.clone(),
+        )
+        .with_clock(self.clock.clone())
+        .with_deferred_locks(self.deferred_locks.clone())
+        .with_upload_coalescer(self.upload_coalescer.clone());
+
+        let bytes = endpoints::do_action_idempotent(
+            ctx,
+            self.authorizer.as_ref(),
+            &caller,
             action,
+            idempotency_key,
         )
         .await
+        .and_then(|bytes| encoding.encode(bytes).map_err(ServerError::from))
         .inspect_err(log_server_error)?;
 
-        let bytes = response
-            .bytes()
-            .map_err(ServerError::from)
-            .inspect_err(log_server_error)?;
-
         // Create the stream from the flight result
         let stream = futures::stream::iter(vec![Ok(arrow_flight::Result::new(bytes))]);
         Ok(Response::new(Box::pin(stream)))
@@ -222,6 +329,73 @@ impl FlightService for MosaicoFlightService {
     }
 }
 
+/// gRPC path of the `DoAction` method of the Flight service
+const DO_ACTION_PATH: &str = "/arrow.flight.protocol.FlightService/DoAction";
+
+/// Limits the size of the `DoAction` requests to the size of the action bodies (see
+/// [`params::ConfigurablesParams::max_action_body_bytes`]) while they are received.
+///
+/// The maximum decoding message size of the service applies to every method, and must fit
+/// the uploaded data. Actions larger than their limit are rejected with `InvalidArgument` as
+/// soon as the limit is exceeded, without buffering the whole request.
+#[derive(Clone)]
+struct ActionBodyLimit<S> {
+    inner: S,
+    max_body_bytes: usize,
+}
+
+impl<S> ActionBodyLimit<S> {
+    /// Room left to the gRPC framing and to the action type besides the action body
+    const FRAMING_BYTES: usize = 1024;
+
+    fn new(inner: S, max_body_bytes: usize) -> Self {
+        Self {
+            inner,
+            max_body_bytes,
+        }
+    }
+}
+
+impl<S: NamedService> NamedService for ActionBodyLimit<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S> Service<http::Request<Body>> for ActionBodyLimit<S>
+where
+    S: Service<http::Request<Body>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        if request.uri().path() != DO_ACTION_PATH {
+            return self.inner.call(request);
+        }
+
+        let max_body_bytes = self.max_body_bytes;
+        let request = request.map(|body| {
+            let limited = Limited::new(body, max_body_bytes + Self::FRAMING_BYTES).map_err(
+                move |e| -> StdError {
+                    if e.is::<LengthLimitError>() {
+                        Box::new(Status::invalid_argument(format!(
+                            "action body exceeds the limit of {max_body_bytes} bytes"
+                        )))
+                    } else {
+                        e
+                    }
+                },
+            );
+            Body::new(limited)
+        });
+        self.inner.call(request)
+    }
+}
+
 /// Log `ServerError` to terminal
 ///
 /// Use this function with `.inspect_err`
@@ -239,6 +413,79 @@ mod tests {
 
     use super::*;
 
+    /// Returns a `DoAction` request of the action `name` with `body`, encoded as a gRPC message
+    fn do_action_request(name: &str, body: &[u8]) -> http::Request<Body> {
+        // Protobuf encoding of the `Action` message, with its `type` and `body` fields
+        let mut message = Vec::new();
+        for (tag, field) in [(0x0a, name.as_bytes()), (0x12, body)] {
+            message.push(tag);
+            let mut len = field.len();
+            while len >= 0x80 {
+                message.push((len as u8 & 0x7f) | 0x80);
+                len >>= 7;
+            }
+            message.push(len as u8);
+            message.extend_from_slice(field);
+        }
+
+        // Uncompressed gRPC frame
+        let mut frame = vec![0];
+        frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&message);
+
+        http::Request::builder()
+            .method("POST")
+            .uri(DO_ACTION_PATH)
+            .header("content-type", "application/grpc")
+            .header("te", "trailers")
+            .body(Body::new(http_body_util::Full::new(bytes::Bytes::from(
+                frame,
+            ))))
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    /// Test checking that the actions whose body exceeds the limit are rejected while the
+    /// request is received, and that the actions within the limit are served.
+    async fn action_body_limit(pool: sqlx::Pool<repo::Database>) -> sqlx::Result<()> {
+        params::load_configurables_from_env();
+        let repo = repo::testing::Repository::new(pool);
+        let store = store::testing::Store::new_random_on_tmp().unwrap();
+
+        let service = MosaicoFlightService::try_new((*store).clone(), (*repo).clone()).unwrap();
+        let mut svc = ActionBodyLimit::new(FlightServiceServer::new(service), 1024);
+
+        let grpc_status = |response: &http::Response<Body>| {
+            response
+                .headers()
+                .get("grpc-status")
+                .map(|status| status.to_str().unwrap().to_owned())
+        };
+
+        let body = format!(r#"{{"name": "query", "padding": "{}"}}"#, "x".repeat(4096));
+        let response = svc
+            .call(do_action_request("describe_action", body.as_bytes()))
+            .await
+            .unwrap();
+        assert_eq!(
+            grpc_status(&response),
+            Some((tonic::Code::InvalidArgument as i32).to_string())
+        );
+        let message = response.headers().get("grpc-message").unwrap();
+        assert!(message.to_str().unwrap().contains("limit"));
+
+        let response = svc
+            .call(do_action_request(
+                "describe_action",
+                br#"{"name": "query"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(grpc_status(&response), None);
+
+        Ok(())
+    }
+
     #[test]
     fn error_logging() {
         fn my_function() -> Result<(), ServerError> {
@@ -0,0 +1,199 @@
+//! Limits the number of concurrent operations on each resource.
+//!
+//! Without a limit a single resource hit by many parallel operations can monopolize the server
+//! resources and starve the operations on the other resources. Two limiters are used: one on
+//! the uploads on each sequence and one on the queries on each topic. Operations on different
+//! resources never limit each other, the limit on queries is independent of the global limits
+//! on chunk queries.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::endpoints::ActionError;
+
+/// What happens to an operation exceeding the limit of its resource
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LimitPolicy {
+    /// The operation is rejected, the client should retry later
+    #[default]
+    Reject,
+    /// The operation waits until one of the running operations on the resource completes
+    Queue,
+}
+
+impl std::str::FromStr for LimitPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "reject" => Ok(Self::Reject),
+            "queue" => Ok(Self::Queue),
+            _ => Err(format!("unknown limit policy `{value}`")),
+        }
+    }
+}
+
+/// Slot of a running operation, released when dropped
+pub struct LimitPermit {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+pub struct Limiter {
+    /// Maximum number of concurrent operations on each resource, [`None`] if unlimited
+    max_per_resource: Option<usize>,
+    policy: LimitPolicy,
+    /// Kind of the limited resources and operations, reported in the rejection errors
+    resource_kind: &'static str,
+    operation_kind: &'static str,
+    resources: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl Limiter {
+    /// Creates a limiter of the uploads on each sequence
+    pub fn uploads(max_per_sequence: Option<usize>, policy: LimitPolicy) -> Self {
+        Self::new(max_per_sequence, policy, "sequence", "active uploads")
+    }
+
+    /// Creates a limiter of the queries on each topic
+    pub fn queries(max_per_topic: Option<usize>, policy: LimitPolicy) -> Self {
+        Self::new(max_per_topic, policy, "topic", "running queries")
+    }
+
+    fn new(
+        max_per_resource: Option<usize>,
+        policy: LimitPolicy,
+        resource_kind: &'static str,
+        operation_kind: &'static str,
+    ) -> Self {
+        Self {
+            max_per_resource,
+            policy,
+            resource_kind,
+            operation_kind,
+            resources: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Acquires a slot for an operation on `resource`, the slot is held until the returned
+    /// permit is dropped.
+    ///
+    /// If all slots are taken the operation is rejected with an
+    /// [`ActionError::QuotaExceeded`] or waits for a free slot, depending on the
+    /// [`LimitPolicy`].
+    pub async fn acquire(&self, resource: &str) -> Result<LimitPermit, ActionError> {
+        let Some(max) = self.max_per_resource else {
+            return Ok(LimitPermit { _permit: None });
+        };
+
+        let semaphore = {
+            let mut resources = self.resources.lock().unwrap();
+            // Forget the resources without running operations
+            resources.retain(|_, s| Arc::strong_count(s) > 1 || s.available_permits() < max);
+            resources
+                .entry(resource.to_owned())
+                .or_insert_with(|| Arc::new(Semaphore::new(max)))
+                .clone()
+        };
+
+        let permit = match self.policy {
+            LimitPolicy::Reject => semaphore.try_acquire_owned().map_err(|_| {
+                ActionError::QuotaExceeded(format!(
+                    "{} `{resource}` has already {max} {}, retry later",
+                    self.resource_kind, self.operation_kind
+                ))
+            })?,
+            LimitPolicy::Queue => semaphore
+                .acquire_owned()
+                .await
+                .map_err(|e| ActionError::Internal(e.to_string()))?,
+        };
+
+        Ok(LimitPermit {
+            _permit: Some(permit),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Spawns `n` operations on `resource`, each holding its slot for a while, and returns how
+    /// many were accepted along with the maximum number of operations running at the same time
+    async fn spawn_operations(limiter: &Arc<Limiter>, resource: &str, n: usize) -> (usize, usize) {
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        let operations: Vec<_> = (0..n)
+            .map(|_| {
+                let limiter = limiter.clone();
+                let resource = resource.to_owned();
+                let running = running.clone();
+                let max_running = max_running.clone();
+                tokio::spawn(async move {
+                    let Ok(_permit) = limiter.acquire(&resource).await else {
+                        return false;
+                    };
+                    let current = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(current, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    true
+                })
+            })
+            .collect();
+
+        let mut accepted = 0;
+        for operation in operations {
+            if operation.await.unwrap() {
+                accepted += 1;
+            }
+        }
+        (accepted, max_running.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn reject_excess_operations() {
+        let limiter = Arc::new(Limiter::queries(Some(2), LimitPolicy::Reject));
+
+        let hot = spawn_operations(&limiter, "seq/hot", 6);
+        let other = spawn_operations(&limiter, "seq/other", 2);
+        let (hot, other) = tokio::join!(hot, other);
+
+        assert_eq!(hot, (2, 2));
+        // Operations on other resources are not starved by the hot one
+        assert_eq!(other.0, 2);
+
+        // Slots are released once the operations complete
+        assert!(limiter.acquire("seq/hot").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn queue_excess_operations() {
+        let limiter = Arc::new(Limiter::uploads(Some(2), LimitPolicy::Queue));
+
+        let started = tokio::time::Instant::now();
+        assert_eq!(spawn_operations(&limiter, "busy", 6).await, (6, 2));
+
+        // Six operations with two slots run in three waves, never more than two at a time
+        assert!(started.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[tokio::test]
+    async fn rejection_names_the_resource() {
+        let limiter = Limiter::uploads(Some(1), LimitPolicy::Reject);
+
+        let _permit = limiter.acquire("busy").await.unwrap();
+        let Err(ActionError::QuotaExceeded(msg)) = limiter.acquire("busy").await else {
+            panic!("second upload accepted");
+        };
+        assert_eq!(
+            msg,
+            "sequence `busy` has already 1 active uploads, retry later"
+        );
+    }
+}
@@ -1,6 +1,12 @@
+pub mod auth;
 mod core;
+pub mod deferred_lock;
 mod errors;
 mod flight;
+pub mod limiter;
+#[cfg(test)]
+pub mod testing;
+pub mod upload_coalescer;
 
 mod endpoints;
 
@@ -0,0 +1,121 @@
+//! Fixtures shared by the tests of the server.
+
+use arrow::array::RecordBatch;
+
+use crate::{marshal, repo, rw, store, types};
+
+/// Creates an empty sequence (no data) for testing purposes.
+pub async fn create_empty_sequence(
+    repo: &repo::Repository,
+    store: &store::StoreRef,
+    name: &str,
+) -> Result<types::ResourceId, repo::FacadeError> {
+    let handle = repo::FacadeSequence::new(name.to_owned(), store.clone(), repo.clone());
+
+    let metadata = types::SequenceMetadata::new(
+        marshal::JsonMetadataBlob::try_from_str(
+            r#"{
+                "test_field_1" : "value1",
+                "test_field_2" : "value2"
+            }"#,
+        )
+        .expect("Error parsing user metadata"),
+    );
+
+    handle.create(Some(metadata)).await
+}
+
+/// Creates an empty topic (no data) for testing purposes.
+pub async fn create_empty_topic(
+    repo: &repo::Repository,
+    store: &store::StoreRef,
+    sequence: &types::ResourceId,
+    name: &str,
+) -> Result<types::ResourceId, repo::FacadeError> {
+    let properties = types::TopicProperties::new(rw::Format::Default, "test_tag".to_owned());
+    create_topic_with(repo, store, sequence, name, properties).await
+}
+
+/// Creates an empty topic with the provided properties.
+pub async fn create_topic_with(
+    repo: &repo::Repository,
+    store: &store::StoreRef,
+    sequence: &types::ResourceId,
+    name: &str,
+    properties: types::TopicProperties,
+) -> Result<types::ResourceId, repo::FacadeError> {
+    let handle = repo::FacadeTopic::new(name.to_owned(), store.clone(), repo.clone());
+
+    let metadata = types::TopicMetadata::new(
+        properties,
+        marshal::JsonMetadataBlob::try_from_str(
+            r#"{
+                "test_field_1" : "test_value_1",
+                "test_field_2" : "test_value_2"
+            }"#,
+        )
+        .expect("Error parsing user metadata json string"),
+    );
+
+    handle.create(&sequence.uuid, Some(metadata)).await
+}
+
+/// Writes [`crate::arrow::testing::dummy_batch`] as a chunk of the topic, registering the chunk
+/// and its column statistics in the repository.
+pub async fn write_dummy_chunk(
+    repo: &repo::Repository,
+    store: &store::StoreRef,
+    topic: &types::ResourceId,
+    topic_name: &str,
+    idx: usize,
+) {
+    let batch = crate::arrow::testing::dummy_batch();
+    write_chunk(repo, store, topic, topic_name, idx, &batch).await;
+}
+
+/// Writes and registers a chunk containing `batch`.
+pub async fn write_chunk(
+    repo: &repo::Repository,
+    store: &store::StoreRef,
+    topic: &types::ResourceId,
+    topic_name: &str,
+    idx: usize,
+    batch: &RecordBatch,
+) {
+    let mut writer = rw::ChunkWriter::try_new(batch.schema(), rw::Format::Default).unwrap();
+    writer.write(batch).unwrap();
+    let (buffer, stats, metadata) = writer.finalize().unwrap();
+
+    let datafile =
+        types::TopicResourceLocator::from(topic_name).datafile(idx, &rw::Format::Default);
+    store.write_bytes(&datafile, buffer).await.unwrap();
+
+    let mut handle = repo::FacadeChunk::create(
+        topic.id,
+        &datafile,
+        metadata.size_bytes as i64,
+        metadata.row_count as i64,
+        Some(metadata.content_hash),
+        repo,
+    )
+    .await
+    .unwrap();
+    handle.push_all_stats("test_tag", stats).await.unwrap();
+    handle.finalize().await.unwrap();
+}
+
+/// Returns the number of chunks and rows of the topic `name`, checking that every registered
+/// datafile is stored.
+pub async fn topic_chunks(
+    repo: &repo::Repository,
+    store: &store::StoreRef,
+    name: &str,
+) -> (usize, i64) {
+    let handle = repo::FacadeTopic::new(name.to_owned(), store.clone(), repo.clone());
+    let datafiles = handle.datafiles().await.unwrap();
+    for datafile in &datafiles {
+        assert!(store.read_bytes(datafile).await.is_ok());
+    }
+    let rows = handle.chunks_stats().await.unwrap().total_row_count;
+    (datafiles.len(), rows)
+}
@@ -0,0 +1,320 @@
+//! Coalesces small uploads into larger chunks.
+//!
+//! Clients uploading a few rows at a time create a chunk per upload, multiplying the datafiles
+//! and catalog records of the topic. If enabled, the data of uploads smaller than a threshold
+//! is not written right away: it is buffered along with the data of the other small uploads of
+//! the same topic, also received on other connections, and written as a single chunk once the
+//! buffer reaches the threshold, once the buffer is older than a maximum delay or when the
+//! sequence is finalized.
+//!
+//! Buffered data is appended to a write-ahead log before the upload is acknowledged, so that
+//! data not yet written is recovered by the replay of the logs at the next startup. The topic
+//! of the buffered data is locked only once the data is written.
+
+use std::collections::{HashMap, hash_map::Entry};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use arrow::array::RecordBatch;
+use log::{debug, trace, warn};
+
+use super::{deferred_lock::DeferredLocks, endpoints, errors::ServerError};
+use crate::{repo, rw, store, types};
+
+/// Prefix of the names of the write-ahead logs of the buffers
+pub const WAL_PREFIX: &str = "coalesced-";
+
+/// Attempts made to write a buffer while its topic is leased (e.g. being uploaded)
+const WRITE_ATTEMPTS: usize = 5;
+
+/// Delay between the attempts to write a buffer of a leased topic
+const WRITE_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Outcome of [`UploadCoalescer::push`], the buffers of a topic to be written right away
+pub struct Pushed {
+    topic: String,
+    full: Vec<Buffer>,
+    /// Whether the pushed data is still buffered
+    buffered: bool,
+}
+
+/// Data of the small uploads of a topic waiting to be written
+struct Buffer {
+    batches: Vec<RecordBatch>,
+    bytes: usize,
+    wal: rw::WalWriter,
+    generation: u64,
+}
+
+pub struct UploadCoalescer {
+    /// Size in bytes below which uploads are coalesced and beyond which a buffer is written,
+    /// [`None`] if coalescing is disabled
+    threshold: Option<usize>,
+    /// Time after which a buffer is written regardless of its size
+    max_delay: Duration,
+    /// Directory of the write-ahead logs of the buffers
+    wal_dir: PathBuf,
+    /// Buffers of the topics, by topic name
+    buffers: Mutex<HashMap<String, Buffer>>,
+    generation: AtomicU64,
+}
+
+impl UploadCoalescer {
+    /// Creates a coalescer buffering uploads smaller than `threshold` bytes for at most
+    /// `max_delay`.
+    ///
+    /// Coalescing requires the write-ahead log, it is disabled if `wal_dir` is not set.
+    pub fn new(threshold: Option<usize>, max_delay: Duration, wal_dir: Option<PathBuf>) -> Self {
+        let threshold = match (&threshold, &wal_dir) {
+            (Some(_), None) => {
+                warn!("upload coalescing disabled, it requires the write-ahead log");
+                None
+            }
+            _ => threshold,
+        };
+
+        Self {
+            threshold,
+            max_delay,
+            wal_dir: wal_dir.unwrap_or_default(),
+            buffers: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Size in bytes below which uploads are coalesced, [`None`] if coalescing is disabled
+    pub fn threshold(&self) -> Option<usize> {
+        self.threshold
+    }
+
+    /// Buffers the data of a small upload on `topic`, called holding the upload lease of the
+    /// topic.
+    ///
+    /// The data is logged before returning. If the buffer of the topic reaches the threshold it
+    /// is returned to be written by [`UploadCoalescer::write_pushed`] once the upload lease is
+    /// released, otherwise it is written within the maximum delay. Data with a schema different
+    /// from the buffered one can't share its chunk, the buffer is returned to be written as well.
+    ///
+    /// The topic is locked through `locks` only once its buffered data is written, so that the
+    /// data is never written in a topic locked in the meantime.
+    pub fn push(
+        self: &Arc<Self>,
+        topic: String,
+        batches: Vec<RecordBatch>,
+        store: store::StoreRef,
+        repo: repo::Repository,
+        locks: &Arc<DeferredLocks>,
+    ) -> Result<Pushed, ServerError> {
+        let mut pushed = Pushed {
+            topic,
+            full: Vec::new(),
+            buffered: false,
+        };
+        let (Some(threshold), Some(first)) = (self.threshold, batches.first()) else {
+            return Ok(pushed);
+        };
+        let topic = &pushed.topic;
+        let schema = first.schema();
+        let bytes: usize = batches.iter().map(RecordBatch::get_array_memory_size).sum();
+
+        let mut created = None;
+        {
+            let mut buffers = self.buffers.lock().unwrap();
+
+            if buffers
+                .get(topic)
+                .is_some_and(|buffer| *buffer.batches[0].schema() != *schema)
+            {
+                pushed.full.extend(buffers.remove(topic));
+            }
+
+            let buffer = match buffers.entry(topic.clone()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let name = format!("{}{}", WAL_PREFIX, uuid::Uuid::new_v4());
+                    let wal = rw::WalWriter::try_new(&self.wal_dir, &name, topic, &schema)?;
+                    let generation = self.generation.fetch_add(1, Ordering::Relaxed);
+                    created = Some(generation);
+                    entry.insert(Buffer {
+                        batches: Vec::new(),
+                        bytes: 0,
+                        wal,
+                        generation,
+                    })
+                }
+            };
+
+            for batch in &batches {
+                buffer.wal.append(batch)?;
+            }
+            buffer.batches.extend(batches);
+            buffer.bytes += bytes;
+
+            trace!(
+                "{} bytes buffered on {}, {} bytes pending",
+                bytes, topic, buffer.bytes
+            );
+
+            if buffer.bytes >= threshold {
+                pushed.full.extend(buffers.remove(topic));
+            } else {
+                pushed.buffered = true;
+            }
+        }
+
+        if let Some(generation) = created.filter(|_| pushed.buffered) {
+            self.schedule(topic.clone(), generation, store, repo, locks.clone());
+        }
+
+        Ok(pushed)
+    }
+
+    /// Writes the buffers returned by [`UploadCoalescer::push`], called once the upload lease
+    /// of the topic is released.
+    ///
+    /// Returns the acknowledgements of the written chunks, followed by
+    /// [`rw::ChunkAck::Coalesced`] if the data is still buffered.
+    pub async fn write_pushed(
+        &self,
+        pushed: Pushed,
+        store: store::StoreRef,
+        repo: repo::Repository,
+        locks: &Arc<DeferredLocks>,
+    ) -> Result<Vec<rw::ChunkAck>, ServerError> {
+        let mut acks = Vec::new();
+        for buffer in pushed.full {
+            acks.extend(
+                self.write(&pushed.topic, buffer, store.clone(), repo.clone(), locks)
+                    .await?,
+            );
+        }
+        if pushed.buffered {
+            acks.push(rw::ChunkAck::Coalesced);
+        }
+
+        Ok(acks)
+    }
+
+    /// Immediately writes the buffers of the topics of `sequence`, called when the sequence is
+    /// finalized.
+    pub async fn flush(
+        &self,
+        sequence: &str,
+        store: store::StoreRef,
+        repo: repo::Repository,
+        locks: &Arc<DeferredLocks>,
+    ) -> Result<(), ServerError> {
+        let buffers: Vec<(String, Buffer)> = {
+            let mut buffers = self.buffers.lock().unwrap();
+            let topics: Vec<String> = buffers
+                .keys()
+                .filter(|name| {
+                    types::TopicResourceLocator::from(name.as_str()).sequence_name() == sequence
+                })
+                .cloned()
+                .collect();
+            topics
+                .into_iter()
+                .filter_map(|topic| buffers.remove_entry(&topic))
+                .collect()
+        };
+
+        for (topic, buffer) in buffers {
+            self.write(&topic, buffer, store.clone(), repo.clone(), locks)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the buffer of `topic` created with `generation` once the maximum delay elapses,
+    /// unless it was written in the meantime.
+    fn schedule(
+        self: &Arc<Self>,
+        topic: String,
+        generation: u64,
+        store: store::StoreRef,
+        repo: repo::Repository,
+        locks: Arc<DeferredLocks>,
+    ) {
+        let coalescer = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(coalescer.max_delay).await;
+
+            let Some(buffer) = coalescer.take(&topic, generation) else {
+                return;
+            };
+
+            if let Err(e) = coalescer.write(&topic, buffer, store, repo, &locks).await {
+                warn!("unable to write the data coalesced on {}: {}", topic, e);
+            }
+        });
+    }
+
+    /// Removes the buffer of `topic` if it is still the one created with `generation`
+    fn take(&self, topic: &str, generation: u64) -> Option<Buffer> {
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.get(topic)?.generation != generation {
+            return None;
+        }
+        buffers.remove(topic)
+    }
+
+    /// Writes a buffer as a chunk of `topic`, discards its log and locks the topic through
+    /// `locks` unless new data was buffered on it in the meantime.
+    ///
+    /// The chunk is written holding the lease of the topic, if the topic is leased the write is
+    /// retried a few times. Uploads push their data holding the lease as well, so a buffer
+    /// created by an upload not yet completed is always seen before locking. If the write fails
+    /// the log is kept and the topic is not locked, so that the data is recovered by the replay
+    /// at the next startup.
+    async fn write(
+        &self,
+        topic: &str,
+        buffer: Buffer,
+        store: store::StoreRef,
+        repo: repo::Repository,
+        locks: &Arc<DeferredLocks>,
+    ) -> Result<Vec<rw::ChunkAck>, ServerError> {
+        let handle = repo::FacadeTopic::new(topic.to_owned(), store.clone(), repo.clone());
+        debug!(
+            "writing {} coalesced bytes on {}",
+            buffer.bytes, handle.locator
+        );
+
+        let mut attempt = 1;
+        let lease = loop {
+            match endpoints::acquire_lease(&handle).await {
+                Err(ServerError::FacadeError(repo::FacadeError::TopicLeased(owner)))
+                    if attempt < WRITE_ATTEMPTS =>
+                {
+                    trace!(
+                        "{} leased by `{}`, retrying the write of the coalesced data",
+                        handle.locator, owner
+                    );
+                    attempt += 1;
+                    tokio::time::sleep(WRITE_RETRY_DELAY).await;
+                }
+                result => break result?,
+            }
+        };
+
+        let acks = endpoints::write_batches(&store, &handle, repo.clone(), &buffer.batches).await?;
+        buffer.wal.discard()?;
+
+        if !self.buffers.lock().unwrap().contains_key(topic) {
+            locks.lock(handle).await?;
+        }
+        lease.release().await?;
+
+        Ok(acks)
+    }
+}
+
+impl Default for UploadCoalescer {
+    fn default() -> Self {
+        Self::new(None, Duration::ZERO, None)
+    }
+}
@@ -2,16 +2,33 @@
 //! with S3-compatible object storage services providing
 //! essential CRUD (Create, Read, Update, Delete) methods for byte-level data access.
 
-use futures::stream::TryStreamExt;
-use std::sync::Arc;
+use futures::future::BoxFuture;
+use futures::stream::{BoxStream, TryStreamExt};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+use arrow::array::RecordBatch;
+use arrow::datatypes::SchemaRef;
 use datafusion::execution::object_store::{DefaultObjectStoreRegistry, ObjectStoreRegistry};
 use log::trace;
-use object_store::{ObjectStore, PutPayload, aws::AmazonS3Builder, local::LocalFileSystem};
+use object_store::{
+    Attribute, AttributeValue, Attributes, GetOptions, GetResult, ListResult, MultipartUpload,
+    ObjectMeta, ObjectStore, PutMode, PutMultipartOpts, PutOptions, PutPayload, PutResult,
+    UpdateVersion, UploadPart,
+    aws::{AmazonS3, AmazonS3Builder, S3CopyIfNotExists},
+    local::LocalFileSystem,
+    path::Path as ObjectPath,
+};
+use parquet::arrow::arrow_reader::ArrowReaderMetadata;
+use parquet::arrow::async_reader::{ParquetObjectReader, ParquetRecordBatchStreamBuilder};
+use parquet::errors::ParquetError;
+use parquet::file::metadata::{ParquetMetaData, ParquetMetaDataReader};
 use thiserror::Error;
 use url::Url;
 
-use crate::{params, traits};
+use crate::{params, traits, utils};
 
 /// Converts a filesystem path to an object_store Path.
 #[inline]
@@ -19,6 +36,528 @@ fn to_object_path(path: impl AsRef<std::path::Path>) -> object_store::path::Path
     object_store::path::Path::from(path.as_ref().to_string_lossy().into_owned())
 }
 
+/// Scheme used to map resource paths to the keys of the objects in the store.
+///
+/// Under S3 many objects sharing a long common prefix (e.g. the topics of the same
+/// sequence) can end up in the same hot partition. The [`KeyScheme::HashPrefix`] scheme
+/// prepends to each key a short hash of the full locator of the resource holding the object
+/// (its parent path), e.g. `my_sequence/my_topic/data-0.parquet` is stored as
+/// `3fa1/my_sequence/my_topic/data-0.parquet`, spreading resources, and the topics of a same
+/// sequence, across partitions.
+///
+/// The mapping is reversible and all the objects placed directly under a resource share the
+/// same hashed prefix, so listing the content of a resource still works. The trade-off is
+/// that a listing no longer returns the objects of the sub-resources (e.g. listing a sequence
+/// does not return the data of its topics), and listing by an arbitrary name prefix (e.g. all
+/// the sequences starting with `run_`) is no longer possible without scanning the whole
+/// bucket.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyScheme {
+    /// Keys match the resource paths (human-readable)
+    #[default]
+    Plain,
+    /// Keys are prefixed with a hash of the resource name
+    HashPrefix,
+}
+
+impl KeyScheme {
+    /// Number of hex digits of the hashed prefix
+    const HASH_PREFIX_LEN: usize = 4;
+
+    /// Maps a resource path to the key of the object in the store
+    pub fn to_key(&self, path: object_store::path::Path) -> object_store::path::Path {
+        match self {
+            Self::Plain => path,
+            Self::HashPrefix => {
+                let parts: Vec<_> = path.parts().collect();
+                // The locator is the parent path, objects placed at the top level are hashed
+                // by their own name
+                let locator = match parts.split_last() {
+                    Some((_, parent)) if !parent.is_empty() => parent,
+                    _ => &parts[..],
+                };
+                Self::hashed(locator, &parts)
+            }
+        }
+    }
+
+    /// Maps a resource path to the key prefix of the objects placed directly under it, used to
+    /// list them.
+    pub fn to_prefix(&self, path: object_store::path::Path) -> object_store::path::Path {
+        match self {
+            Self::Plain => path,
+            Self::HashPrefix => {
+                let parts: Vec<_> = path.parts().collect();
+                Self::hashed(&parts, &parts)
+            }
+        }
+    }
+
+    /// Returns whether the object identified by `key` is part of the listing of `path`, keys
+    /// of sub-resources can share the prefix of `path` on a collision of their hashes.
+    pub fn is_listed(
+        &self,
+        path: &object_store::path::Path,
+        key: &object_store::path::Path,
+    ) -> bool {
+        match self {
+            Self::Plain => true,
+            Self::HashPrefix => {
+                let depth = path.parts().count();
+                depth == 0 || key.parts().count() <= depth + 2
+            }
+        }
+    }
+
+    /// Returns `parts` prefixed by the hash of `locator`, unchanged if empty
+    fn hashed(
+        locator: &[object_store::path::PathPart<'_>],
+        parts: &[object_store::path::PathPart<'_>],
+    ) -> object_store::path::Path {
+        if parts.is_empty() {
+            return object_store::path::Path::default();
+        }
+        let locator = locator
+            .iter()
+            .map(|part| part.as_ref())
+            .collect::<Vec<_>>()
+            .join(object_store::path::DELIMITER);
+        let prefix = format!(
+            "{:0width$x}",
+            utils::hash::fnv1a(locator.as_bytes()) >> (64 - 4 * Self::HASH_PREFIX_LEN),
+            width = Self::HASH_PREFIX_LEN
+        );
+        std::iter::once(object_store::path::PathPart::from(prefix))
+            .chain(parts.iter().cloned())
+            .collect()
+    }
+
+    /// Maps the key of an object in the store back to the resource path
+    pub fn to_path(&self, key: &object_store::path::Path) -> object_store::path::Path {
+        match self {
+            Self::Plain => key.clone(),
+            Self::HashPrefix => key.parts().skip(1).collect(),
+        }
+    }
+}
+
+impl std::str::FromStr for KeyScheme {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "plain" => Ok(Self::Plain),
+            "hash_prefix" => Ok(Self::HashPrefix),
+            _ => Err(Error::UnknownKeyScheme(value.to_owned())),
+        }
+    }
+}
+
+/// Policy used to flush to durable storage the objects written on a filesystem store.
+///
+/// Writes on the filesystem store go through the OS page cache, so data not yet synced can be
+/// lost on a power failure or a kernel crash (a crash of the server process alone is harmless).
+/// S3-compatible stores acknowledge a write only once it is durable, the policy does not apply
+/// to them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// Each object is synced before its write returns. Acknowledged data survives a power
+    /// failure, at the cost of the lowest write throughput.
+    Always,
+    /// The objects of a topic are synced when the topic is gracefully locked. A power failure
+    /// during an upload can lose the uploaded data, while the data of locked topics is durable.
+    OnLock,
+    /// Objects are never explicitly synced and the OS decides when to flush them. Highest
+    /// throughput, but recently written data (even of locked topics) can be lost on a power
+    /// failure.
+    #[default]
+    Never,
+}
+
+impl std::str::FromStr for FsyncPolicy {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "always" => Ok(Self::Always),
+            "on_lock" => Ok(Self::OnLock),
+            "never" => Ok(Self::Never),
+            _ => Err(Error::UnknownFsyncPolicy(value.to_owned())),
+        }
+    }
+}
+
+/// Flushes to durable storage the objects written in a store.
+pub trait ObjectSync: std::fmt::Debug + Send + Sync {
+    /// Blocks until the object identified by `key` is durable.
+    fn sync(&self, key: &object_store::path::Path) -> std::io::Result<()>;
+}
+
+/// Syncs the files of a filesystem store
+#[derive(Debug)]
+struct FileSync {
+    root: std::path::PathBuf,
+}
+
+impl ObjectSync for FileSync {
+    fn sync(&self, key: &object_store::path::Path) -> std::io::Result<()> {
+        let path = self.root.join(key.as_ref());
+        std::fs::File::open(&path)?.sync_all()?;
+
+        // The directories up to the root are synced as well, otherwise the entry of a newly
+        // created (or renamed in place) file, or of a directory created along with it, may be
+        // lost
+        for dir in path.ancestors().skip(1) {
+            std::fs::File::open(dir)?.sync_all()?;
+            if dir == self.root {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Changes the storage class of the objects of a store with a server-side copy, without
+/// transferring their content.
+pub trait ObjectReclass: std::fmt::Debug + Send + Sync {
+    /// Copies the object identified by `key` onto itself in the storage class `class`.
+    fn set_storage_class<'a>(
+        &'a self,
+        key: &'a object_store::path::Path,
+        class: &'a str,
+    ) -> BoxFuture<'a, object_store::Result<()>>;
+}
+
+/// Changes the storage class of the objects of a S3-compatible store
+#[derive(Debug)]
+struct S3Reclass {
+    builder: AmazonS3Builder,
+    /// Clients copying the objects in each storage class
+    clients: Mutex<HashMap<String, Arc<AmazonS3>>>,
+}
+
+impl S3Reclass {
+    /// Returns the client whose copies write the objects in the storage class `class`.
+    ///
+    /// The header of the copy-if-not-exists condition is the only header that can be added to
+    /// (and signed with) the copy requests, here it carries the storage class in place of the
+    /// condition so the copy always succeeds.
+    fn client(&self, class: &str) -> object_store::Result<Arc<AmazonS3>> {
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(client) = clients.get(class) {
+            return Ok(client.clone());
+        }
+
+        let client = Arc::new(
+            self.builder
+                .clone()
+                .with_copy_if_not_exists(S3CopyIfNotExists::Header(
+                    "x-amz-storage-class".to_owned(),
+                    class.to_owned(),
+                ))
+                .build()?,
+        );
+        clients.insert(class.to_owned(), client.clone());
+        Ok(client)
+    }
+}
+
+impl ObjectReclass for S3Reclass {
+    fn set_storage_class<'a>(
+        &'a self,
+        key: &'a object_store::path::Path,
+        class: &'a str,
+    ) -> BoxFuture<'a, object_store::Result<()>> {
+        Box::pin(async move { self.client(class)?.copy_if_not_exists(key, key).await })
+    }
+}
+
+/// Driver bounding each operation of the wrapped driver by a timeout and syncing the written
+/// objects as the store policy requires, used for the accesses that don't go through the
+/// [`Store`] methods (e.g. the reads and writes of the query engine).
+#[derive(Debug)]
+struct DeadlineDriver {
+    inner: Arc<dyn ObjectStore>,
+    timeout: Duration,
+    /// Syncs each written object before the write returns, set under [`FsyncPolicy::Always`]
+    syncer: Option<Arc<dyn ObjectSync>>,
+}
+
+impl DeadlineDriver {
+    /// Runs a driver operation returning an [`Error::Timeout`] as source of the driver error
+    /// if the operation takes longer than the timeout.
+    async fn deadline<T>(
+        &self,
+        op: &'static str,
+        location: &ObjectPath,
+        fut: impl Future<Output = object_store::Result<T>>,
+    ) -> object_store::Result<T> {
+        tokio::time::timeout(self.timeout, fut)
+            .await
+            .map_err(|_| object_store::Error::Generic {
+                store: "deadline",
+                source: Box::new(Error::Timeout {
+                    op,
+                    path: location.to_string(),
+                    timeout: self.timeout,
+                }),
+            })?
+    }
+
+    /// Blocks until the object at `location` is durable, no-op without a syncer.
+    async fn sync(&self, location: &ObjectPath) -> object_store::Result<()> {
+        let Some(syncer) = self.syncer.clone() else {
+            return Ok(());
+        };
+        sync_object(syncer, location.clone())
+            .await
+            .map_err(|e| object_store::Error::Generic {
+                store: "sync",
+                source: Box::new(e),
+            })
+    }
+}
+
+/// Syncs the object identified by `key` on a blocking thread.
+async fn sync_object(syncer: Arc<dyn ObjectSync>, key: ObjectPath) -> std::io::Result<()> {
+    tokio::task::spawn_blocking(move || syncer.sync(&key))
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+/// Multipart upload syncing the uploaded object once completed
+#[derive(Debug)]
+struct SyncedUpload {
+    inner: Box<dyn MultipartUpload>,
+    location: ObjectPath,
+    syncer: Arc<dyn ObjectSync>,
+}
+
+impl MultipartUpload for SyncedUpload {
+    fn put_part(&mut self, data: PutPayload) -> UploadPart {
+        self.inner.put_part(data)
+    }
+
+    fn complete<'a, 'r>(&'a mut self) -> BoxFuture<'r, object_store::Result<PutResult>>
+    where
+        'a: 'r,
+        Self: 'r,
+    {
+        Box::pin(async move {
+            let res = self.inner.complete().await?;
+            sync_object(self.syncer.clone(), self.location.clone())
+                .await
+                .map_err(|e| object_store::Error::Generic {
+                    store: "sync",
+                    source: Box::new(e),
+                })?;
+            Ok(res)
+        })
+    }
+
+    fn abort<'a, 'r>(&'a mut self) -> BoxFuture<'r, object_store::Result<()>>
+    where
+        'a: 'r,
+        Self: 'r,
+    {
+        self.inner.abort()
+    }
+}
+
+impl std::fmt::Display for DeadlineDriver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Deadline({})", self.inner)
+    }
+}
+
+impl ObjectStore for DeadlineDriver {
+    fn put_opts<'a, 'b, 'r>(
+        &'a self,
+        location: &'b ObjectPath,
+        payload: PutPayload,
+        opts: PutOptions,
+    ) -> BoxFuture<'r, object_store::Result<PutResult>>
+    where
+        'a: 'r,
+        'b: 'r,
+        Self: 'r,
+    {
+        Box::pin(async move {
+            let res = self
+                .deadline(
+                    "write",
+                    location,
+                    self.inner.put_opts(location, payload, opts),
+                )
+                .await?;
+            self.sync(location).await?;
+            Ok(res)
+        })
+    }
+
+    fn put_multipart_opts<'a, 'b, 'r>(
+        &'a self,
+        location: &'b ObjectPath,
+        opts: PutMultipartOpts,
+    ) -> BoxFuture<'r, object_store::Result<Box<dyn MultipartUpload>>>
+    where
+        'a: 'r,
+        'b: 'r,
+        Self: 'r,
+    {
+        Box::pin(async move {
+            let upload = self
+                .deadline(
+                    "write",
+                    location,
+                    self.inner.put_multipart_opts(location, opts),
+                )
+                .await?;
+            let upload: Box<dyn MultipartUpload> = match self.syncer.clone() {
+                Some(syncer) => Box::new(SyncedUpload {
+                    inner: upload,
+                    location: location.clone(),
+                    syncer,
+                }),
+                None => upload,
+            };
+            Ok(upload)
+        })
+    }
+
+    fn get_opts<'a, 'b, 'r>(
+        &'a self,
+        location: &'b ObjectPath,
+        options: GetOptions,
+    ) -> BoxFuture<'r, object_store::Result<GetResult>>
+    where
+        'a: 'r,
+        'b: 'r,
+        Self: 'r,
+    {
+        Box::pin(self.deadline("read", location, self.inner.get_opts(location, options)))
+    }
+
+    fn get_range<'a, 'b, 'r>(
+        &'a self,
+        location: &'b ObjectPath,
+        range: Range<u64>,
+    ) -> BoxFuture<'r, object_store::Result<bytes::Bytes>>
+    where
+        'a: 'r,
+        'b: 'r,
+        Self: 'r,
+    {
+        Box::pin(self.deadline(
+            "read_range",
+            location,
+            self.inner.get_range(location, range),
+        ))
+    }
+
+    fn get_ranges<'a, 'b, 'c, 'r>(
+        &'a self,
+        location: &'b ObjectPath,
+        ranges: &'c [Range<u64>],
+    ) -> BoxFuture<'r, object_store::Result<Vec<bytes::Bytes>>>
+    where
+        'a: 'r,
+        'b: 'r,
+        'c: 'r,
+        Self: 'r,
+    {
+        Box::pin(self.deadline(
+            "read_range",
+            location,
+            self.inner.get_ranges(location, ranges),
+        ))
+    }
+
+    fn head<'a, 'b, 'r>(
+        &'a self,
+        location: &'b ObjectPath,
+    ) -> BoxFuture<'r, object_store::Result<ObjectMeta>>
+    where
+        'a: 'r,
+        'b: 'r,
+        Self: 'r,
+    {
+        Box::pin(self.deadline("stat", location, self.inner.head(location)))
+    }
+
+    fn delete<'a, 'b, 'r>(
+        &'a self,
+        location: &'b ObjectPath,
+    ) -> BoxFuture<'r, object_store::Result<()>>
+    where
+        'a: 'r,
+        'b: 'r,
+        Self: 'r,
+    {
+        Box::pin(self.deadline("delete", location, self.inner.delete(location)))
+    }
+
+    fn list(
+        &self,
+        prefix: Option<&ObjectPath>,
+    ) -> BoxStream<'static, object_store::Result<ObjectMeta>> {
+        self.inner.list(prefix)
+    }
+
+    fn list_with_delimiter<'a, 'b, 'r>(
+        &'a self,
+        prefix: Option<&'b ObjectPath>,
+    ) -> BoxFuture<'r, object_store::Result<ListResult>>
+    where
+        'a: 'r,
+        'b: 'r,
+        Self: 'r,
+    {
+        let location = prefix.cloned().unwrap_or_default();
+        Box::pin(async move {
+            self.deadline("list", &location, self.inner.list_with_delimiter(prefix))
+                .await
+        })
+    }
+
+    fn copy<'a, 'b, 'c, 'r>(
+        &'a self,
+        from: &'b ObjectPath,
+        to: &'c ObjectPath,
+    ) -> BoxFuture<'r, object_store::Result<()>>
+    where
+        'a: 'r,
+        'b: 'r,
+        'c: 'r,
+        Self: 'r,
+    {
+        Box::pin(async move {
+            self.deadline("copy", from, self.inner.copy(from, to))
+                .await?;
+            self.sync(to).await
+        })
+    }
+
+    fn copy_if_not_exists<'a, 'b, 'c, 'r>(
+        &'a self,
+        from: &'b ObjectPath,
+        to: &'c ObjectPath,
+    ) -> BoxFuture<'r, object_store::Result<()>>
+    where
+        'a: 'r,
+        'b: 'r,
+        'c: 'r,
+        Self: 'r,
+    {
+        Box::pin(async move {
+            self.deadline("copy", from, self.inner.copy_if_not_exists(from, to))
+                .await?;
+            self.sync(to).await
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct S3Config {
     /// Bucket name.
@@ -27,6 +566,8 @@ pub struct S3Config {
     pub endpoint: String,
     pub access_key: String,
     pub secret_key: params::Hidden,
+    /// Scheme used to map resource paths to object keys
+    pub key_scheme: KeyScheme,
 }
 
 #[derive(Error, Debug)]
@@ -37,6 +578,86 @@ pub enum Error {
     BadUrl(#[from] url::ParseError),
     #[error("io error :: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("parquet error :: {0}")]
+    ParquetError(#[from] parquet::errors::ParquetError),
+    #[error("store operation `{op}` on `{path}` timed out after {timeout:?}")]
+    Timeout {
+        op: &'static str,
+        path: String,
+        timeout: Duration,
+    },
+    #[error("copy of `{src}` to `{dst}` does not match the source data")]
+    CopyMismatch { src: String, dst: String },
+    #[error("unknown key scheme `{0}`")]
+    UnknownKeyScheme(String),
+    #[error("unknown fsync policy `{0}`")]
+    UnknownFsyncPolicy(String),
+    #[error("store still returns stale data for `{path}` after {attempts} reads")]
+    StaleRead { path: String, attempts: usize },
+    #[error("store operation `{op}` on `{path}` failed :: {source}")]
+    Context {
+        op: &'static str,
+        path: String,
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Returns the underlying error, without the context added by the store operations (see
+    /// [`Store::with_error_context`]).
+    pub fn root(&self) -> &Error {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+
+    /// Returns true if the error was caused by a missing object
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self.root(),
+            Self::BackendError(object_store::Error::NotFound { .. })
+        )
+    }
+}
+
+/// Number of trailing bytes of a parquet file fetched to read its footer, larger footers
+/// require a second read
+pub const PARQUET_FOOTER_PREFETCH_BYTES: u64 = 64 * 1024;
+
+/// Default maximum duration of a single store operation
+pub const DEFAULT_OP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Maximum number of reads issued by the read-after-write guard before giving up
+pub const READ_AFTER_WRITE_ATTEMPTS: usize = 5;
+
+/// Delay before the first retry of the read-after-write guard, doubled on each retry
+pub const READ_AFTER_WRITE_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Version of an object, returned by [`Store::read_versioned`] and [`Store::list_versioned`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectVersion(UpdateVersion);
+
+impl ObjectVersion {
+    /// Returns false if the backend provided no version, so that a rewrite of the object
+    /// can't be detected comparing the versions.
+    pub fn is_known(&self) -> bool {
+        self.0.e_tag.is_some() || self.0.version.is_some()
+    }
+}
+
+/// Serializes the conditional updates emulated on the backends not supporting them, see
+/// [`Store::put_if_version`]
+static CONDITIONAL_UPDATE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Outcome of a [`Store::vacuum`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VacuumReport {
+    /// Number of staging files removed
+    pub files_removed: usize,
+    /// Total size in bytes of the removed files
+    pub bytes_reclaimed: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -54,7 +675,20 @@ pub struct Store {
     pub url_schema: Url,
     target: StoreTarget,
     driver: Arc<dyn ObjectStore>,
-    registry: Arc<dyn ObjectStoreRegistry>,
+    /// Maximum duration of a single operation (read, write, list, stat, ...)
+    timeout: Duration,
+    /// Scheme used to map resource paths to object keys
+    key_scheme: KeyScheme,
+    fsync_policy: FsyncPolicy,
+    /// Used to sync the written objects, [`None`] if the backend does not need it
+    syncer: Option<Arc<dyn ObjectSync>>,
+    /// Used to change the storage class of the objects, [`None`] if the backend has no
+    /// server-side copy supporting it
+    reclass: Option<Arc<dyn ObjectReclass>>,
+    /// Whether each write is verified by re-reading the written object
+    read_after_write: bool,
+    /// Whether the errors of the store operations carry the operation and the path
+    error_context: bool,
 }
 
 pub type StoreRef = Arc<Store>;
@@ -65,20 +699,25 @@ impl Store {
         std::fs::create_dir_all(&root)?;
 
         let target = root.as_ref().to_string_lossy().to_string();
+        let syncer = Arc::new(FileSync {
+            root: root.as_ref().to_path_buf(),
+        });
 
         let storage = Arc::new(LocalFileSystem::new_with_prefix(root)?);
 
         let bucket_url = Url::parse("file://")?;
 
-        // Create object store registry (for datafusion support)
-        let registry = Arc::new(DefaultObjectStoreRegistry::default());
-        registry.register_store(&bucket_url, storage.clone());
-
         Ok(Self {
             url_schema: bucket_url,
             target: StoreTarget::Filesystem(target),
-            driver: storage.clone(),
-            registry,
+            driver: storage,
+            timeout: DEFAULT_OP_TIMEOUT,
+            key_scheme: KeyScheme::Plain,
+            fsync_policy: FsyncPolicy::default(),
+            syncer: Some(syncer),
+            reclass: None,
+            read_after_write: false,
+            error_context: true,
         })
     }
 
@@ -92,45 +731,179 @@ impl Store {
 
         // Setup connection with object storage service
         // (cabba) TODO: add region support
-        let storage = Arc::new(
-            AmazonS3Builder::new()
-                .with_endpoint(&config.endpoint)
-                .with_bucket_name(&config.bucket)
-                .with_access_key_id(config.access_key)
-                .with_secret_access_key(config.secret_key.take())
-                .with_allow_http(true)
-                .build()?,
-        );
-
-        // Create object store registry (for datafusion support)
-        let registry = Arc::new(DefaultObjectStoreRegistry::default());
-        registry.register_store(&bucket_url, storage.clone());
+        let builder = AmazonS3Builder::new()
+            .with_endpoint(&config.endpoint)
+            .with_bucket_name(&config.bucket)
+            .with_access_key_id(config.access_key)
+            .with_secret_access_key(config.secret_key.take())
+            .with_allow_http(true);
+        let storage = Arc::new(builder.clone().build()?);
+        let reclass = Arc::new(S3Reclass {
+            builder,
+            clients: Mutex::default(),
+        });
 
         Ok(Self {
             url_schema: bucket_url,
             target: StoreTarget::S3Compatible(config.bucket),
-            driver: storage.clone(),
-            registry: registry.clone(),
+            driver: storage,
+            timeout: DEFAULT_OP_TIMEOUT,
+            key_scheme: config.key_scheme,
+            fsync_policy: FsyncPolicy::default(),
+            syncer: None,
+            reclass: Some(reclass),
+            read_after_write: false,
+            error_context: true,
         })
     }
 
+    /// Sets the maximum duration of each store operation, if an operation exceeds the
+    /// timeout an [`Error::Timeout`] is returned.
+    ///
+    /// Since cloning a [`Store`] is cheap this can be used to override the timeout of a
+    /// single call, e.g. `store.as_ref().clone().with_timeout(timeout).read_bytes(path)`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Sets the [`FsyncPolicy`] of the store, ignored by stores whose writes are durable once
+    /// acknowledged (S3-compatible stores).
+    pub fn with_fsync_policy(mut self, fsync_policy: FsyncPolicy) -> Self {
+        self.fsync_policy = fsync_policy;
+        self
+    }
+
+    pub fn fsync_policy(&self) -> FsyncPolicy {
+        self.fsync_policy
+    }
+
+    /// Enables the read-after-write guard: after each write the object is read back until
+    /// the store returns the written version (same ETag, or same content if the backend
+    /// returns no ETag), retrying with a short backoff. If the written version is not
+    /// returned within [`READ_AFTER_WRITE_ATTEMPTS`] reads an [`Error::StaleRead`] is
+    /// returned.
+    ///
+    /// Meant for S3-compatible stores that are only eventually consistent on overwrites,
+    /// AWS S3 is strongly consistent and does not need it.
+    pub fn with_read_after_write(mut self, enabled: bool) -> Self {
+        self.read_after_write = enabled;
+        self
+    }
+
+    /// Enables the error context: the errors of the store operations are wrapped in an
+    /// [`Error::Context`] carrying the failed operation and the path of the object, the
+    /// original error is kept as its source (see [`Error::root`]). Enabled by default.
+    pub fn with_error_context(mut self, enabled: bool) -> Self {
+        self.error_context = enabled;
+        self
+    }
+
+    /// Runs a store operation returning an [`Error::Timeout`] if the operation takes longer
+    /// than the store timeout.
+    ///
+    /// If the error context is enabled, the errors of the operation are wrapped with the
+    /// operation and the path.
+    async fn deadline<T>(
+        &self,
+        op: &'static str,
+        path: &std::path::Path,
+        fut: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        tokio::time::timeout(self.timeout, fut)
+            .await
+            .map_err(|_| Error::Timeout {
+                op,
+                path: path.display().to_string(),
+                timeout: self.timeout,
+            })?
+            .map_err(|e| self.context(op, path, e))
+    }
+
+    /// Wraps `err` with the failed operation and path, if the error context is enabled
+    fn context(&self, op: &'static str, path: &std::path::Path, err: Error) -> Error {
+        if !self.error_context || matches!(err, Error::Context { .. }) {
+            return err;
+        }
+        Error::Context {
+            op,
+            path: path.display().to_string(),
+            source: Box::new(err),
+        }
+    }
+
+    /// Returns the object store registry used by the query engine to read the store.
+    ///
+    /// The operations of the registered store are bound by the store timeout, as the ones of
+    /// the [`Store`] itself.
     pub fn registry(&self) -> Arc<dyn ObjectStoreRegistry> {
-        self.registry.clone()
+        let registry = DefaultObjectStoreRegistry::default();
+        registry.register_store(
+            &self.url_schema,
+            Arc::new(DeadlineDriver {
+                inner: self.driver.clone(),
+                timeout: self.timeout,
+                syncer: self
+                    .syncer
+                    .clone()
+                    .filter(|_| self.fsync_policy == FsyncPolicy::Always),
+            }),
+        );
+        Arc::new(registry)
     }
 
     pub fn target(&self) -> &StoreTarget {
         &self.target
     }
 
+    /// Returns the key of the object located at `path`, according to the store
+    /// [`KeyScheme`].
+    pub fn object_key(&self, path: impl AsRef<std::path::Path>) -> object_store::path::Path {
+        self.key_scheme.to_key(to_object_path(path))
+    }
+
+    /// Maps the key of an object back to its resource path, see [`Store::object_key`].
+    pub fn resource_path(&self, key: &str) -> String {
+        self.key_scheme
+            .to_path(&object_store::path::Path::from(key))
+            .to_string()
+    }
+
     pub async fn read_bytes(&self, path: impl AsRef<std::path::Path>) -> Result<Vec<u8>, Error> {
         trace!("reading bytes from {}", path.as_ref().display());
-        Ok(self
-            .driver
-            .get(&to_object_path(&path))
-            .await?
-            .bytes()
-            .await?
-            .into())
+        self.deadline("read", path.as_ref(), async {
+            Ok(self
+                .driver
+                .get(&self.object_key(&path))
+                .await?
+                .bytes()
+                .await?
+                .into())
+        })
+        .await
+    }
+
+    /// Returns the bytes of the object located at `path` in the `range` of offsets.
+    ///
+    /// Only the requested bytes are fetched from the store (a ranged request on S3, a seek
+    /// on the local filesystem).
+    pub async fn get_range(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        range: Range<u64>,
+    ) -> Result<bytes::Bytes, Error> {
+        trace!("reading bytes {:?} from {}", range, path.as_ref().display());
+        self.deadline("read_range", path.as_ref(), async {
+            Ok(self
+                .driver
+                .get_range(&self.object_key(&path), range)
+                .await?)
+        })
+        .await
     }
 
     pub async fn write_bytes(
@@ -140,67 +913,645 @@ impl Store {
     ) -> Result<(), Error> {
         trace!("writing bytes to {}", path.as_ref().display());
 
-        self.driver
-            .put(&to_object_path(&path), PutPayload::from_bytes(bytes.into()))
-            .await?;
+        let bytes = bytes.into();
+        let payload = PutPayload::from_bytes(bytes.clone());
+
+        let written = self
+            .deadline("write", path.as_ref(), async {
+                Ok(self.driver.put(&self.object_key(&path), payload).await?)
+            })
+            .await?;
+
+        if self.read_after_write {
+            self.await_consistent(&path, written.e_tag, &bytes).await?;
+        }
+
+        if self.fsync_policy == FsyncPolicy::Always {
+            self.sync(&path).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the object located at `path` to the storage class `class` (e.g. `GLACIER_IR`
+    /// on S3), the object content and location are unchanged.
+    ///
+    /// S3-compatible stores copy the object onto itself server-side, other stores rewrite
+    /// the object. Storage classes are not supported by the filesystem store, which returns
+    /// an error.
+    pub async fn set_storage_class(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        class: &str,
+    ) -> Result<(), Error> {
+        trace!(
+            "moving {} to storage class {}",
+            path.as_ref().display(),
+            class
+        );
+
+        let key = self.object_key(&path);
+        if let Some(reclass) = &self.reclass {
+            return self
+                .deadline("copy", path.as_ref(), async {
+                    reclass.set_storage_class(&key, class).await?;
+                    Ok(())
+                })
+                .await;
+        }
+
+        let payload = PutPayload::from(self.read_bytes(&path).await?);
+        let opts = PutOptions {
+            attributes: Attributes::from_iter([(
+                Attribute::StorageClass,
+                AttributeValue::from(class.to_owned()),
+            )]),
+            ..Default::default()
+        };
+
+        self.deadline("write", path.as_ref(), async {
+            self.driver.put_opts(&key, payload, opts).await?;
+            Ok(())
+        })
+        .await?;
+
+        if self.fsync_policy == FsyncPolicy::Always {
+            self.sync(&path).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `bytes` at `path` only if no object exists at that location.
+    ///
+    /// The check and the write are a single atomic operation of the backend (conditional
+    /// write on S3-compatible stores, exclusive creation on the filesystem), returns `false`
+    /// (without writing) if the object already exists.
+    pub async fn put_if_absent(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        bytes: impl Into<bytes::Bytes>,
+    ) -> Result<bool, Error> {
+        trace!("writing bytes to {} if absent", path.as_ref().display());
+
+        let bytes = bytes.into();
+        let payload = PutPayload::from_bytes(bytes.clone());
+
+        let written = self
+            .deadline("write", path.as_ref(), async {
+                match self
+                    .driver
+                    .put_opts(&self.object_key(&path), payload, PutMode::Create.into())
+                    .await
+                {
+                    Ok(res) => Ok(Some(res)),
+                    Err(object_store::Error::AlreadyExists { .. }) => Ok(None),
+                    Err(e) => Err(e.into()),
+                }
+            })
+            .await?;
+
+        let Some(written) = written else {
+            return Ok(false);
+        };
+
+        if self.read_after_write {
+            self.await_consistent(&path, written.e_tag, &bytes).await?;
+        }
+
+        if self.fsync_policy == FsyncPolicy::Always {
+            self.sync(&path).await?;
+        }
+
+        Ok(true)
+    }
+
+    /// Returns the bytes of the object located at `path` along with its version, used to make
+    /// a subsequent write conditional on the object being unchanged, see
+    /// [`Store::put_if_version`].
+    pub async fn read_versioned(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(Vec<u8>, ObjectVersion), Error> {
+        trace!("reading versioned bytes from {}", path.as_ref().display());
+        self.deadline("read", path.as_ref(), async {
+            let result = self.driver.get(&self.object_key(&path)).await?;
+            let version = ObjectVersion(UpdateVersion {
+                e_tag: result.meta.e_tag.clone(),
+                version: result.meta.version.clone(),
+            });
+            Ok((result.bytes().await?.into(), version))
+        })
+        .await
+    }
+
+    /// Writes `bytes` at `path` only if the object is unchanged since it was read at
+    /// `version`, returns `false` (without writing) if the object was modified or removed.
+    ///
+    /// The check and the write are a single atomic operation of S3-compatible stores. Backends
+    /// without conditional updates (the filesystem) check the version and write the object
+    /// holding a lock, so the operation is atomic only among the conditional writes of this
+    /// process.
+    pub async fn put_if_version(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        bytes: impl Into<bytes::Bytes>,
+        version: &ObjectVersion,
+    ) -> Result<bool, Error> {
+        trace!("writing bytes to {} if unchanged", path.as_ref().display());
+
+        let bytes = bytes.into();
+        let key = self.object_key(&path);
+
+        let written = self
+            .deadline("write", path.as_ref(), async {
+                let payload = PutPayload::from_bytes(bytes.clone());
+                let mode = PutMode::Update(version.0.clone());
+                match self.driver.put_opts(&key, payload, mode.into()).await {
+                    Ok(res) => Ok(Some(res)),
+                    Err(object_store::Error::Precondition { .. })
+                    | Err(object_store::Error::NotFound { .. }) => Ok(None),
+                    Err(object_store::Error::NotImplemented) => {
+                        let _guard = CONDITIONAL_UPDATE_LOCK.lock().await;
+                        let current = match self.driver.head(&key).await {
+                            Ok(meta) => meta,
+                            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+                            Err(e) => return Err(e.into()),
+                        };
+                        if current.e_tag.is_none() || current.e_tag != version.0.e_tag {
+                            return Ok(None);
+                        }
+                        let payload = PutPayload::from_bytes(bytes.clone());
+                        Ok(Some(self.driver.put(&key, payload).await?))
+                    }
+                    Err(e) => Err(e.into()),
+                }
+            })
+            .await?;
+
+        let Some(written) = written else {
+            return Ok(false);
+        };
+
+        if self.read_after_write {
+            self.await_consistent(&path, written.e_tag, &bytes).await?;
+        }
+
+        if self.fsync_policy == FsyncPolicy::Always {
+            self.sync(&path).await?;
+        }
+
+        Ok(true)
+    }
+
+    /// Reads back the object located at `path` until the store returns the version just
+    /// written, identified by its `e_tag` or, if the backend returned none, by its `content`.
+    async fn await_consistent(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        e_tag: Option<String>,
+        content: &[u8],
+    ) -> Result<(), Error> {
+        let mut delay = READ_AFTER_WRITE_RETRY_DELAY;
+
+        for attempt in 1..=READ_AFTER_WRITE_ATTEMPTS {
+            let fresh = match &e_tag {
+                Some(e_tag) => {
+                    let head = self
+                        .deadline("stat", path.as_ref(), async {
+                            Ok(self.driver.head(&self.object_key(&path)).await?)
+                        })
+                        .await?;
+                    head.e_tag.as_ref() == Some(e_tag)
+                }
+                None => self.read_bytes(&path).await? == content,
+            };
+
+            if fresh {
+                return Ok(());
+            }
+
+            if attempt < READ_AFTER_WRITE_ATTEMPTS {
+                trace!(
+                    "stale read of {} after write, retrying in {:?}",
+                    path.as_ref().display(),
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+
+        Err(Error::StaleRead {
+            path: path.as_ref().display().to_string(),
+            attempts: READ_AFTER_WRITE_ATTEMPTS,
+        })
+    }
+
+    /// Syncs all the objects located under `path` if the store policy is
+    /// [`FsyncPolicy::OnLock`], called when a resource is gracefully locked.
+    pub async fn sync_on_lock(&self, path: impl AsRef<std::path::Path>) -> Result<(), Error> {
+        if self.fsync_policy != FsyncPolicy::OnLock {
+            return Ok(());
+        }
+
+        for location in self.list(&path, None).await? {
+            self.sync(&location).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Blocks until the object located at `path` is durable, no-op if the backend does not
+    /// need it.
+    async fn sync(&self, path: impl AsRef<std::path::Path>) -> Result<(), Error> {
+        let Some(syncer) = self.syncer.clone() else {
+            return Ok(());
+        };
+
+        trace!("syncing {}", path.as_ref().display());
+        let key = self.object_key(&path);
+        self.deadline("sync", path.as_ref(), async {
+            Ok(sync_object(syncer, key).await?)
+        })
+        .await
+    }
+
+    /// Returns a list of elements located at the given `path`.
+    ///
+    /// Under [`KeyScheme::HashPrefix`] only the objects placed directly under `path` are
+    /// listed, see [`KeyScheme`].
+    ///
+    /// If an extension is provided, the results will be filtered to include only
+    /// the elements whose extension matches exactly.es extacly
+    pub async fn list(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        extension: Option<&str>,
+    ) -> Result<Vec<String>, Error> {
+        let locations = self
+            .deadline("list", path.as_ref(), self.list_inner(&path, extension))
+            .await?;
+        Ok(locations
+            .into_iter()
+            .map(|(location, _)| location)
+            .collect())
+    }
+
+    /// Same as [`Store::list`], but each location is returned along with the version of the
+    /// object, which changes whenever the object is rewritten.
+    pub async fn list_versioned(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        extension: Option<&str>,
+    ) -> Result<Vec<(String, ObjectVersion)>, Error> {
+        self.deadline("list", path.as_ref(), self.list_inner(&path, extension))
+            .await
+    }
+
+    async fn list_inner(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        extension: Option<&str>,
+    ) -> Result<Vec<(String, ObjectVersion)>, Error> {
+        let prefix = to_object_path(&path);
+        let mut list_stream = self
+            .driver
+            .list(Some(&self.key_scheme.to_prefix(prefix.clone())));
+
+        let mut locations = Vec::new();
+        while let Some(elem) = list_stream.try_next().await? {
+            let location = &elem.location;
+            if !self.key_scheme.is_listed(&prefix, location) {
+                continue;
+            }
+            // If some extension is provided:
+            // - check if current element has an extension, if has no extension
+            //   should the excluded
+            // - if has an extension but is different from the one provided shoukd
+            //   be excluded
+            if let Some(ext) = extension {
+                if let Some(path_ext) = location.extension() {
+                    if path_ext != ext {
+                        continue;
+                    }
+                } else {
+                    continue;
+                }
+            }
+            let version = ObjectVersion(UpdateVersion {
+                e_tag: elem.e_tag.clone(),
+                version: elem.version.clone(),
+            });
+            locations.push((self.key_scheme.to_path(location).to_string(), version));
+        }
+
+        Ok(locations)
+    }
+
+    /// Returns the arrow schema of a parquet file located at `path`.
+    ///
+    /// Only the file footer is fetched from the store.
+    pub async fn read_parquet_schema(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<SchemaRef, Error> {
+        trace!("reading parquet schema from {}", path.as_ref().display());
+        self.deadline("read_parquet_schema", path.as_ref(), async {
+            let reader = ParquetObjectReader::new(self.driver.clone(), self.object_key(&path));
+            let builder = ParquetRecordBatchStreamBuilder::new(reader).await?;
+            Ok(builder.schema().clone())
+        })
+        .await
+    }
+
+    /// Returns the metadata (schema and row group statistics) of a parquet file located at
+    /// `path`.
+    ///
+    /// Only the file footer is fetched from the store: the trailing
+    /// [`PARQUET_FOOTER_PREFETCH_BYTES`] of the file are read first, the remaining part of the
+    /// footer is read only if it does not fit them. Returns an [`Error::ParquetError`] (see
+    /// [`Error::root`]) if the footer is malformed.
+    pub async fn read_parquet_metadata(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Arc<ParquetMetaData>, Error> {
+        trace!("reading parquet metadata from {}", path.as_ref().display());
+        let size = self.size(&path).await? as u64;
+
+        let mut reader = ParquetMetaDataReader::new();
+        let prefetch = PARQUET_FOOTER_PREFETCH_BYTES.min(size);
+        let tail = self.get_range(&path, size - prefetch..size).await?;
+
+        let malformed =
+            |e: ParquetError| self.context("read_parquet_metadata", path.as_ref(), e.into());
+
+        match reader.try_parse_sized(&tail, size) {
+            Ok(()) => {}
+            Err(ParquetError::NeedMoreData(needed)) if needed as u64 <= size => {
+                let tail = self.get_range(&path, size - needed as u64..size).await?;
+                reader.try_parse_sized(&tail, size).map_err(malformed)?;
+            }
+            Err(e) => return Err(malformed(e)),
+        }
+
+        Ok(Arc::new(reader.finish().map_err(malformed)?))
+    }
+
+    /// Decodes the row groups at the `row_groups` indices of the parquet file located at
+    /// `path`, whose `metadata` was already read (see [`Store::read_parquet_metadata`]).
+    ///
+    /// Only the byte ranges of the selected row groups are fetched from the store. Returns the
+    /// arrow schema of the file along with the decoded batches.
+    pub async fn read_parquet_row_groups(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        metadata: Arc<ParquetMetaData>,
+        row_groups: Vec<usize>,
+    ) -> Result<(SchemaRef, Vec<RecordBatch>), Error> {
+        trace!(
+            "reading row groups {:?} from {}",
+            row_groups,
+            path.as_ref().display()
+        );
+        self.deadline("read_row_groups", path.as_ref(), async {
+            let reader = ParquetObjectReader::new(self.driver.clone(), self.object_key(&path));
+            let metadata = ArrowReaderMetadata::try_new(metadata, Default::default())?;
+            let schema = metadata.schema().clone();
+            let batches = ParquetRecordBatchStreamBuilder::new_with_metadata(reader, metadata)
+                .with_row_groups(row_groups)
+                .build()?
+                .try_collect()
+                .await?;
+            Ok((schema, batches))
+        })
+        .await
+    }
+
+    pub async fn size(&self, path: impl AsRef<std::path::Path>) -> Result<usize, Error> {
+        self.deadline("stat", path.as_ref(), async {
+            let head = self.driver.head(&self.object_key(&path)).await?;
+            Ok(head.size as usize)
+        })
+        .await
+    }
+
+    pub async fn delete(&self, path: impl AsRef<std::path::Path>) -> Result<(), Error> {
+        self.deadline("delete", path.as_ref(), async {
+            Ok(self.driver.delete(&self.object_key(&path)).await?)
+        })
+        .await
+    }
+
+    /// Copies the object located at `src` to `dst`, overwriting `dst` if existing.
+    ///
+    /// The copy is performed by the backend (server-side copy on S3-compatible stores), the
+    /// data is not transferred through this process. See [`Store::copy_verified`] to also
+    /// verify the copied data.
+    pub async fn copy(
+        &self,
+        src: impl AsRef<std::path::Path>,
+        dst: impl AsRef<std::path::Path>,
+    ) -> Result<(), Error> {
+        trace!(
+            "copying {} to {}",
+            src.as_ref().display(),
+            dst.as_ref().display()
+        );
+
+        self.deadline("copy", src.as_ref(), async {
+            Ok(self
+                .driver
+                .copy(&self.object_key(&src), &self.object_key(&dst))
+                .await?)
+        })
+        .await?;
+
+        if self.fsync_policy == FsyncPolicy::Always {
+            self.sync(&dst).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Copies the object located at `src` to `dst`, verifying that the copied data matches
+    /// the source.
+    ///
+    /// If the copy does not match the source it is removed and an [`Error::CopyMismatch`] is
+    /// returned, the source is never modified.
+    pub async fn copy_verified(
+        &self,
+        src: impl AsRef<std::path::Path>,
+        dst: impl AsRef<std::path::Path>,
+    ) -> Result<(), Error> {
+        self.copy(&src, &dst).await?;
+
+        let source = self.read_bytes(&src).await?;
+        let copy = self.read_bytes(&dst).await?;
+        if source != copy {
+            self.delete(&dst).await?;
+            return Err(Error::CopyMismatch {
+                src: src.as_ref().display().to_string(),
+                dst: dst.as_ref().display().to_string(),
+            });
+        }
 
         Ok(())
     }
 
-    /// Returns a list of elements located at the given `path`.
+    /// Copies the object located at `path` to the same location in the `target` store,
+    /// verifying that the copied data matches the source.
     ///
-    /// If an extension is provided, the results will be filtered to include only
-    /// the elements whose extension matches exactly.es extacly
-    pub async fn list(
+    /// Returns `false` without reading the source if `target` already holds an object of the
+    /// same size, so an interrupted copy of many objects can be resumed cheaply. Since writes
+    /// are atomic on every backend, an existing object is never a partial copy. A copy not
+    /// matching the source is removed and an [`Error::CopyMismatch`] is returned.
+    pub async fn copy_verified_to(
         &self,
         path: impl AsRef<std::path::Path>,
-        extension: Option<&str>,
-    ) -> Result<Vec<String>, Error> {
-        let mut list_stream = self.driver.list(Some(&to_object_path(&path)));
-
-        let mut locations = Vec::new();
-        while let Some(elem) = list_stream.try_next().await? {
-            let location = &elem.location;
-            // If some extension is provided:
-            // - check if current element has an extension, if has no extension
-            //   should the excluded
-            // - if has an extension but is different from the one provided shoukd
-            //   be excluded
-            if let Some(ext) = extension {
-                if let Some(path_ext) = location.extension() {
-                    if path_ext != ext {
-                        continue;
-                    }
-                } else {
-                    continue;
-                }
-            }
-            locations.push(location.to_string());
+        target: &Store,
+    ) -> Result<bool, Error> {
+        match target.size(&path).await {
+            Ok(size) if size == self.size(&path).await? => return Ok(false),
+            Err(e) if !e.is_not_found() => return Err(e),
+            _ => {}
         }
 
-        Ok(locations)
-    }
+        trace!("copying {} to another store", path.as_ref().display());
+        let source = self.read_bytes(&path).await?;
+        target.write_bytes(&path, source.clone()).await?;
 
-    pub async fn size(&self, path: impl AsRef<std::path::Path>) -> Result<usize, Error> {
-        let head = self.driver.head(&to_object_path(&path)).await?;
+        if target.read_bytes(&path).await? != source {
+            target.delete(&path).await?;
+            return Err(Error::CopyMismatch {
+                src: path.as_ref().display().to_string(),
+                dst: format!("{:?}", target.target()),
+            });
+        }
 
-        Ok(head.size as usize)
+        Ok(true)
     }
 
-    pub async fn delete(&self, path: impl AsRef<std::path::Path>) -> Result<(), Error> {
-        Ok(self.driver.delete(&to_object_path(&path)).await?)
+    /// Moves the object located at `src` to `dst`.
+    ///
+    /// The source is deleted only after the copy has been verified, see
+    /// [`Store::copy_verified`], so a failed move never causes data loss.
+    pub async fn move_verified(
+        &self,
+        src: impl AsRef<std::path::Path>,
+        dst: impl AsRef<std::path::Path>,
+    ) -> Result<(), Error> {
+        self.copy_verified(&src, &dst).await?;
+        self.delete(&src).await
     }
 
     /// Deletes recursively all objects under a given path
+    ///
+    /// Under [`KeyScheme::HashPrefix`] only the objects placed directly under `path` are
+    /// deleted, the ones of its sub-resources are not listed along with them.
+    ///
+    /// The timeout is applied to each listed object, not to the whole operation.
     pub async fn delete_recursive(&self, path: impl AsRef<std::path::Path>) -> Result<(), Error> {
-        let mut list_stream = self.driver.list(Some(&to_object_path(&path)));
+        let prefix = to_object_path(&path);
+        let mut list_stream = self
+            .driver
+            .list(Some(&self.key_scheme.to_prefix(prefix.clone())));
 
-        while let Some(e) = list_stream.try_next().await? {
-            self.driver.delete(&e.location).await?;
+        while let Some(e) = self
+            .deadline("list", path.as_ref(), async {
+                Ok(list_stream.try_next().await?)
+            })
+            .await?
+        {
+            if !self.key_scheme.is_listed(&prefix, &e.location) {
+                continue;
+            }
+            self.delete(self.key_scheme.to_path(&e.location).as_ref())
+                .await?;
         }
 
         Ok(())
     }
+
+    /// Removes the orphaned staging files not modified for `older_than`.
+    ///
+    /// The filesystem backend writes each object to a `<name>#<n>` staging file, renamed to
+    /// `<name>` once complete: interrupted writes and aborted multipart uploads leave the
+    /// staging file behind, unlisted. Live objects are never touched, nor are recent staging
+    /// files, which may belong to a write in progress.
+    ///
+    /// No-op on S3-compatible stores, where the parts of aborted uploads are expected to be
+    /// expired by the lifecycle rules of the bucket.
+    pub async fn vacuum(&self, older_than: Duration) -> Result<VacuumReport, Error> {
+        let StoreTarget::Filesystem(root) = &self.target else {
+            return Ok(VacuumReport::default());
+        };
+
+        trace!("vacuuming staging files older than {:?}", older_than);
+        let root = std::path::PathBuf::from(root);
+        let report = tokio::task::spawn_blocking(move || {
+            let mut report = VacuumReport::default();
+            vacuum_dir(&root, older_than, &mut report).map(|()| report)
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+
+        Ok(report)
+    }
+}
+
+/// Returns true if `file_name` is the name of a staging file of the filesystem backend
+fn is_staging_file(file_name: &str) -> bool {
+    file_name.split_once('#').is_some_and(|(name, suffix)| {
+        !name.is_empty() && !suffix.is_empty() && suffix.bytes().all(|b| b.is_ascii_digit())
+    })
+}
+
+/// Recursively removes the staging files under `dir` not modified for `older_than`
+fn vacuum_dir(
+    dir: &std::path::Path,
+    older_than: Duration,
+    report: &mut VacuumReport,
+) -> std::io::Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            vacuum_dir(&entry.path(), older_than, report)?;
+            continue;
+        }
+
+        if !file_type.is_file() || !entry.file_name().to_str().is_some_and(is_staging_file) {
+            continue;
+        }
+
+        let metadata = entry.metadata()?;
+        // Files modified in the future (e.g. clock skew) are considered recent
+        let age = metadata.modified()?.elapsed().unwrap_or_default();
+        if age < older_than {
+            continue;
+        }
+
+        trace!("removing staging file {}", entry.path().display());
+        match std::fs::remove_file(entry.path()) {
+            Ok(()) => {
+                report.files_removed += 1;
+                report.bytes_reclaimed += metadata.len();
+            }
+            // Renamed or removed in the meantime
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
 }
 
 impl traits::AsyncWriteToPath for Store {
@@ -234,7 +1585,14 @@ impl traits::AsyncWriteToPath for Store {
 #[cfg(test)]
 pub mod testing {
     use super::*;
+    use futures::future::BoxFuture;
+    use futures::stream::BoxStream;
+    use object_store::{
+        GetOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, PutMultipartOpts,
+        PutOptions, PutResult, memory::InMemory, path::Path,
+    };
     use std::ops::Deref;
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
     pub struct Store {
         inner: super::StoreRef,
@@ -283,6 +1641,241 @@ pub mod testing {
             &self.inner
         }
     }
+
+    /// Creates a [`super::Store`] on top of the provided backend, e.g. an instrumented one.
+    ///
+    /// The backend is registered under the `memory://` URL, so the store can be queried.
+    pub fn with_driver(driver: Arc<dyn ObjectStore>) -> super::Store {
+        super::Store {
+            url_schema: Url::parse("memory://").unwrap(),
+            target: StoreTarget::Filesystem("memory".to_owned()),
+            driver,
+            timeout: DEFAULT_OP_TIMEOUT,
+            key_scheme: KeyScheme::Plain,
+            fsync_policy: FsyncPolicy::default(),
+            syncer: None,
+            reclass: None,
+            read_after_write: false,
+            error_context: true,
+        }
+    }
+
+    /// Hooks of a [`HookedStore`] intercepting the operations on the in-memory backend, the
+    /// default implementations forward the operations as they are.
+    pub trait StoreHooks: std::fmt::Debug + Send + Sync + 'static {
+        fn put<'r>(
+            &'r self,
+            inner: &'r InMemory,
+            location: &'r Path,
+            payload: PutPayload,
+            opts: PutOptions,
+        ) -> BoxFuture<'r, object_store::Result<PutResult>> {
+            inner.put_opts(location, payload, opts)
+        }
+
+        fn get<'r>(
+            &'r self,
+            inner: &'r InMemory,
+            location: &'r Path,
+            options: GetOptions,
+        ) -> BoxFuture<'r, object_store::Result<GetResult>> {
+            inner.get_opts(location, options)
+        }
+
+        fn copy<'r>(
+            &'r self,
+            inner: &'r InMemory,
+            from: &'r Path,
+            to: &'r Path,
+        ) -> BoxFuture<'r, object_store::Result<()>> {
+            inner.copy(from, to)
+        }
+
+        fn copy_if_not_exists<'r>(
+            &'r self,
+            inner: &'r InMemory,
+            from: &'r Path,
+            to: &'r Path,
+        ) -> BoxFuture<'r, object_store::Result<()>> {
+            inner.copy_if_not_exists(from, to)
+        }
+    }
+
+    /// In-memory backend whose operations are intercepted by `hooks`, used to instrument the
+    /// store or to simulate misbehaving backends.
+    #[derive(Debug, Default)]
+    pub struct HookedStore<H> {
+        inner: InMemory,
+        pub hooks: H,
+    }
+
+    impl<H: StoreHooks> HookedStore<H> {
+        pub fn new(hooks: H) -> Self {
+            Self {
+                inner: InMemory::new(),
+                hooks,
+            }
+        }
+    }
+
+    impl<H> std::fmt::Display for HookedStore<H> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "HookedStore")
+        }
+    }
+
+    impl<H: StoreHooks> ObjectStore for HookedStore<H> {
+        fn put_opts<'a, 'b, 'r>(
+            &'a self,
+            location: &'b Path,
+            payload: PutPayload,
+            opts: PutOptions,
+        ) -> BoxFuture<'r, object_store::Result<PutResult>>
+        where
+            'a: 'r,
+            'b: 'r,
+            Self: 'r,
+        {
+            self.hooks.put(&self.inner, location, payload, opts)
+        }
+
+        fn put_multipart_opts<'a, 'b, 'r>(
+            &'a self,
+            location: &'b Path,
+            opts: PutMultipartOpts,
+        ) -> BoxFuture<'r, object_store::Result<Box<dyn MultipartUpload>>>
+        where
+            'a: 'r,
+            'b: 'r,
+            Self: 'r,
+        {
+            self.inner.put_multipart_opts(location, opts)
+        }
+
+        fn get_opts<'a, 'b, 'r>(
+            &'a self,
+            location: &'b Path,
+            options: GetOptions,
+        ) -> BoxFuture<'r, object_store::Result<GetResult>>
+        where
+            'a: 'r,
+            'b: 'r,
+            Self: 'r,
+        {
+            self.hooks.get(&self.inner, location, options)
+        }
+
+        fn delete<'a, 'b, 'r>(
+            &'a self,
+            location: &'b Path,
+        ) -> BoxFuture<'r, object_store::Result<()>>
+        where
+            'a: 'r,
+            'b: 'r,
+            Self: 'r,
+        {
+            self.inner.delete(location)
+        }
+
+        fn list(
+            &self,
+            prefix: Option<&Path>,
+        ) -> BoxStream<'static, object_store::Result<ObjectMeta>> {
+            self.inner.list(prefix)
+        }
+
+        fn list_with_delimiter<'a, 'b, 'r>(
+            &'a self,
+            prefix: Option<&'b Path>,
+        ) -> BoxFuture<'r, object_store::Result<ListResult>>
+        where
+            'a: 'r,
+            'b: 'r,
+            Self: 'r,
+        {
+            self.inner.list_with_delimiter(prefix)
+        }
+
+        fn copy<'a, 'b, 'c, 'r>(
+            &'a self,
+            from: &'b Path,
+            to: &'c Path,
+        ) -> BoxFuture<'r, object_store::Result<()>>
+        where
+            'a: 'r,
+            'b: 'r,
+            'c: 'r,
+            Self: 'r,
+        {
+            self.hooks.copy(&self.inner, from, to)
+        }
+
+        fn copy_if_not_exists<'a, 'b, 'c, 'r>(
+            &'a self,
+            from: &'b Path,
+            to: &'c Path,
+        ) -> BoxFuture<'r, object_store::Result<()>>
+        where
+            'a: 'r,
+            'b: 'r,
+            'c: 'r,
+            Self: 'r,
+        {
+            self.hooks.copy_if_not_exists(&self.inner, from, to)
+        }
+    }
+
+    /// Hooks tracking the maximum number of reads in flight at the same time and the number
+    /// of bytes read.
+    ///
+    /// Each read is delayed, so that reads issued concurrently are observed as overlapping.
+    #[derive(Debug, Default)]
+    pub struct CountingReads {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+        bytes_read: AtomicU64,
+    }
+
+    impl CountingReads {
+        const READ_DELAY: Duration = Duration::from_millis(20);
+
+        pub fn max_in_flight(&self) -> usize {
+            self.max_in_flight.load(Ordering::SeqCst)
+        }
+
+        pub fn bytes_read(&self) -> u64 {
+            self.bytes_read.load(Ordering::SeqCst)
+        }
+
+        pub fn reset(&self) {
+            self.max_in_flight.store(0, Ordering::SeqCst);
+            self.bytes_read.store(0, Ordering::SeqCst);
+        }
+    }
+
+    impl StoreHooks for CountingReads {
+        fn get<'r>(
+            &'r self,
+            inner: &'r InMemory,
+            location: &'r Path,
+            options: GetOptions,
+        ) -> BoxFuture<'r, object_store::Result<GetResult>> {
+            Box::pin(async move {
+                let current = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                self.max_in_flight.fetch_max(current, Ordering::SeqCst);
+
+                tokio::time::sleep(Self::READ_DELAY).await;
+                let res = inner.get_opts(location, options).await;
+                if let Ok(res) = &res {
+                    self.bytes_read
+                        .fetch_add(res.range.end - res.range.start, Ordering::SeqCst);
+                }
+
+                self.in_flight.fetch_sub(1, Ordering::SeqCst);
+                res
+            })
+        }
+    }
 }
 
 #[cfg(test)]
@@ -313,4 +1906,575 @@ mod test {
 
         assert_eq!(buffer, read_buffer);
     }
+
+    /// Checks that changing the storage class preserves the object content
+    #[tokio::test]
+    async fn storage_class() {
+        let driver = Arc::new(object_store::memory::InMemory::new());
+        let store = testing::with_driver(driver.clone());
+        let buffer: Vec<u8> = (0..=255).collect();
+
+        store.write_bytes("chunk", buffer.clone()).await.unwrap();
+        store
+            .set_storage_class("chunk", "GLACIER_IR")
+            .await
+            .unwrap();
+
+        let object = driver
+            .get(&object_store::path::Path::from("chunk"))
+            .await
+            .unwrap();
+        assert_eq!(
+            object.attributes.get(&Attribute::StorageClass),
+            Some(&AttributeValue::from("GLACIER_IR"))
+        );
+        assert_eq!(store.read_bytes("chunk").await.unwrap(), buffer);
+    }
+
+    /// Checks that the stores with a server-side copy change the storage class without
+    /// rewriting the object
+    #[tokio::test]
+    async fn storage_class_server_side() {
+        /// Server-side copy recording the copied objects
+        #[derive(Debug, Default)]
+        struct RecordingReclass(Mutex<Vec<(String, String)>>);
+
+        impl ObjectReclass for RecordingReclass {
+            fn set_storage_class<'a>(
+                &'a self,
+                key: &'a object_store::path::Path,
+                class: &'a str,
+            ) -> BoxFuture<'a, object_store::Result<()>> {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((key.to_string(), class.to_owned()));
+                Box::pin(async { Ok(()) })
+            }
+        }
+
+        let driver = Arc::new(object_store::memory::InMemory::new());
+        let reclass = Arc::new(RecordingReclass::default());
+        let store = Store {
+            reclass: Some(reclass.clone()),
+            ..testing::with_driver(driver.clone())
+        };
+
+        store.write_bytes("chunk", b"data".to_vec()).await.unwrap();
+        store
+            .set_storage_class("chunk", "GLACIER_IR")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *reclass.0.lock().unwrap(),
+            [("chunk".to_owned(), "GLACIER_IR".to_owned())]
+        );
+
+        // The object was not rewritten
+        let object = driver
+            .get(&object_store::path::Path::from("chunk"))
+            .await
+            .unwrap();
+        assert_eq!(object.attributes.get(&Attribute::StorageClass), None);
+    }
+
+    /// Checks that a range read returns exactly the requested bytes
+    #[tokio::test]
+    async fn get_range() {
+        let store = testing::Store::new_random_on_tmp().unwrap();
+        let buffer: Vec<u8> = (0..=255).collect();
+
+        store.write_bytes("range", buffer.clone()).await.unwrap();
+
+        let bytes = store.get_range("range", 10..20).await.unwrap();
+        assert_eq!(bytes.as_ref(), &buffer[10..20]);
+
+        let bytes = store.get_range("range", 250..256).await.unwrap();
+        assert_eq!(bytes.as_ref(), &buffer[250..]);
+    }
+
+    /// Checks that failed operations report the operation and the path, keeping the original
+    /// error as source
+    #[tokio::test]
+    async fn error_context() {
+        use std::error::Error as _;
+
+        let driver = Arc::new(object_store::memory::InMemory::new());
+        let store = testing::with_driver(driver);
+
+        let err = store.read_bytes("seq/topic/missing").await.unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("`read`"), "{msg}");
+        assert!(msg.contains("seq/topic/missing"), "{msg}");
+        assert!(err.is_not_found());
+        assert!(matches!(
+            err.root(),
+            Error::BackendError(object_store::Error::NotFound { .. })
+        ));
+        assert_eq!(err.source().unwrap().to_string(), err.root().to_string());
+
+        // Errors are left untouched if the context is disabled
+        let store = store.with_error_context(false);
+        let err = store.read_bytes("seq/topic/missing").await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::BackendError(object_store::Error::NotFound { .. })
+        ));
+    }
+
+    /// Checks that the vacuum removes the stale staging files only, keeping the live objects
+    /// and the recent staging files
+    #[tokio::test]
+    async fn vacuum() {
+        let store = testing::Store::new_random_on_tmp().unwrap();
+        store
+            .write_bytes("seq/topic/data-0.parquet", vec![1; 16])
+            .await
+            .unwrap();
+
+        let StoreTarget::Filesystem(root) = store.target() else {
+            unreachable!()
+        };
+        let dir = std::path::Path::new(root).join("seq/topic");
+        let live = dir.join("data-0.parquet");
+        let stale = dir.join("data-1.parquet#1");
+        let recent = dir.join("data-1.parquet#2");
+        std::fs::write(&stale, vec![0; 64]).unwrap();
+        std::fs::write(&recent, vec![0; 32]).unwrap();
+
+        let two_hours_ago = std::time::SystemTime::now() - Duration::from_secs(2 * 60 * 60);
+        for path in [&live, &stale] {
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(two_hours_ago)
+                .unwrap();
+        }
+
+        let report = store.vacuum(Duration::from_secs(60 * 60)).await.unwrap();
+        assert_eq!(
+            report,
+            VacuumReport {
+                files_removed: 1,
+                bytes_reclaimed: 64
+            }
+        );
+        assert!(!stale.exists());
+        assert!(recent.exists());
+        assert_eq!(
+            store.read_bytes("seq/topic/data-0.parquet").await.unwrap(),
+            vec![1; 16]
+        );
+    }
+
+    /// Checks that an operation on a stalled store fails with a timeout instead of hanging
+    #[tokio::test]
+    async fn operation_timeout() {
+        use object_store::{
+            memory::InMemory,
+            throttle::{ThrottleConfig, ThrottledStore},
+        };
+
+        let driver = Arc::new(ThrottledStore::new(
+            InMemory::new(),
+            ThrottleConfig {
+                wait_get_per_call: Duration::from_millis(200),
+                ..Default::default()
+            },
+        ));
+
+        let store = testing::with_driver(driver).with_timeout(Duration::from_millis(50));
+
+        store.write_bytes("slow", b"data".to_vec()).await.unwrap();
+
+        let res = store.read_bytes("slow").await;
+        assert!(matches!(res, Err(Error::Timeout { op: "read", .. })));
+
+        // The timeout can be overridden for a single call
+        let res = store
+            .clone()
+            .with_timeout(Duration::from_secs(5))
+            .read_bytes("slow")
+            .await;
+        assert_eq!(res.unwrap(), b"data");
+    }
+
+    /// Checks that the reads of the query engine through the registry are bound by the store
+    /// timeout as well
+    #[tokio::test]
+    async fn registry_operation_timeout() {
+        use object_store::{
+            memory::InMemory,
+            throttle::{ThrottleConfig, ThrottledStore},
+        };
+
+        let driver = Arc::new(ThrottledStore::new(
+            InMemory::new(),
+            ThrottleConfig {
+                wait_get_per_call: Duration::from_millis(200),
+                ..Default::default()
+            },
+        ));
+
+        let store = testing::with_driver(driver).with_timeout(Duration::from_millis(50));
+        store.write_bytes("slow", b"data".to_vec()).await.unwrap();
+
+        let registered = store.registry().get_store(&store.url_schema).unwrap();
+        let res = registered.get_range(&store.object_key("slow"), 0..4).await;
+
+        let Err(object_store::Error::Generic { source, .. }) = res else {
+            panic!("read not timed out: {res:?}");
+        };
+        assert!(matches!(
+            source.downcast_ref::<Error>(),
+            Some(Error::Timeout {
+                op: "read_range",
+                ..
+            })
+        ));
+    }
+
+    /// Checks that a move whose copy is corrupted by the backend is aborted, leaving the
+    /// source intact
+    #[tokio::test]
+    async fn move_corrupted_copy() {
+        use futures::future::BoxFuture;
+        use object_store::{memory::InMemory, path::Path};
+
+        /// Hooks corrupting the copies
+        #[derive(Debug)]
+        struct CorruptingCopy;
+
+        impl testing::StoreHooks for CorruptingCopy {
+            fn copy<'r>(
+                &'r self,
+                inner: &'r InMemory,
+                _from: &'r Path,
+                to: &'r Path,
+            ) -> BoxFuture<'r, object_store::Result<()>> {
+                Box::pin(async move {
+                    inner
+                        .put(to, PutPayload::from_static(b"corrupted"))
+                        .await
+                        .map(|_| ())
+                })
+            }
+
+            fn copy_if_not_exists<'r>(
+                &'r self,
+                inner: &'r InMemory,
+                from: &'r Path,
+                to: &'r Path,
+            ) -> BoxFuture<'r, object_store::Result<()>> {
+                self.copy(inner, from, to)
+            }
+        }
+
+        let store = testing::with_driver(Arc::new(testing::HookedStore::new(CorruptingCopy)));
+
+        store.write_bytes("src", b"data".to_vec()).await.unwrap();
+
+        let res = store.move_verified("src", "dst").await;
+        assert!(matches!(res, Err(Error::CopyMismatch { .. })));
+
+        // The source is left intact and the corrupted copy is removed
+        assert_eq!(store.read_bytes("src").await.unwrap(), b"data");
+        assert!(store.read_bytes("dst").await.is_err());
+    }
+
+    /// Checks that hash-prefixed keys map back to the original resource paths and that
+    /// objects are listed by their resource path
+    #[tokio::test]
+    async fn hash_prefix_key_round_trip() {
+        use object_store::memory::InMemory;
+
+        let scheme = KeyScheme::HashPrefix;
+        for path in ["seq", "seq/topic/data-0.parquet", "other_seq/metadata.json"] {
+            let key = scheme.to_key(to_object_path(path));
+            assert_ne!(key.as_ref(), path);
+            assert_eq!(scheme.to_path(&key).as_ref(), path);
+        }
+
+        // All the objects of a resource share the same prefix, which is the listing prefix of
+        // the resource
+        let k1 = scheme.to_key(to_object_path("seq/topic/data-0.parquet"));
+        let k2 = scheme.to_key(to_object_path("seq/topic/metadata.json"));
+        let prefix = scheme.to_prefix(to_object_path("seq/topic"));
+        assert_eq!(k1.parts().next(), k2.parts().next());
+        assert!(k1.prefix_matches(&prefix));
+
+        // The topics of a sequence are hashed by their full locator, not by the sequence name
+        let prefixes: std::collections::HashSet<_> = (0..16)
+            .map(|idx| {
+                let key = scheme.to_key(to_object_path(format!("seq/topic_{idx}/metadata.json")));
+                key.parts().next().unwrap().as_ref().to_owned()
+            })
+            .collect();
+        assert!(prefixes.len() > 1);
+
+        let store = Store {
+            target: StoreTarget::S3Compatible("memory".to_owned()),
+            key_scheme: scheme,
+            ..testing::with_driver(Arc::new(InMemory::new()))
+        };
+
+        store
+            .write_bytes("seq/topic/data-0.parquet", b"data".to_vec())
+            .await
+            .unwrap();
+        store
+            .write_bytes("seq/metadata.json", b"data".to_vec())
+            .await
+            .unwrap();
+
+        // Listings return the objects placed directly under a resource
+        assert_eq!(
+            store.list("seq/topic", None).await.unwrap(),
+            vec!["seq/topic/data-0.parquet".to_owned()]
+        );
+        assert_eq!(
+            store.list("seq", None).await.unwrap(),
+            vec!["seq/metadata.json".to_owned()]
+        );
+        assert_eq!(
+            store.read_bytes("seq/topic/data-0.parquet").await.unwrap(),
+            b"data"
+        );
+
+        store.delete_recursive("seq/topic").await.unwrap();
+        store.delete_recursive("seq").await.unwrap();
+        assert!(store.list("", None).await.unwrap().is_empty());
+    }
+
+    /// Checks that the fsync policy decides when the written objects are synced
+    #[tokio::test]
+    async fn fsync_policy() {
+        use object_store::memory::InMemory;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Backend sync counting the synced objects
+        #[derive(Debug, Default)]
+        struct CountingSync(AtomicUsize);
+
+        impl ObjectSync for CountingSync {
+            fn sync(&self, _key: &object_store::path::Path) -> std::io::Result<()> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        let syncer = Arc::new(CountingSync::default());
+        let store = Store {
+            syncer: Some(syncer.clone()),
+            ..testing::with_driver(Arc::new(InMemory::new()))
+        };
+
+        let write_topic = async |store: &Store| {
+            for idx in 0..3 {
+                let path = format!("seq/topic/data-{idx}.parquet");
+                store.write_bytes(path, b"data".to_vec()).await.unwrap();
+            }
+            store.sync_on_lock("seq/topic").await.unwrap();
+        };
+
+        write_topic(&store.clone().with_fsync_policy(FsyncPolicy::Never)).await;
+        assert_eq!(syncer.0.swap(0, Ordering::SeqCst), 0);
+
+        // One sync per write
+        write_topic(&store.clone().with_fsync_policy(FsyncPolicy::Always)).await;
+        assert_eq!(syncer.0.swap(0, Ordering::SeqCst), 3);
+
+        // Writes are not synced, all the objects are synced on lock
+        let on_lock = store.clone().with_fsync_policy(FsyncPolicy::OnLock);
+        on_lock
+            .write_bytes("seq/topic/data-0.parquet", b"data".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(syncer.0.load(Ordering::SeqCst), 0);
+        on_lock.sync_on_lock("seq/topic").await.unwrap();
+        assert_eq!(syncer.0.swap(0, Ordering::SeqCst), 3);
+
+        assert_eq!(
+            "on_lock".parse::<FsyncPolicy>().unwrap(),
+            FsyncPolicy::OnLock
+        );
+        assert!("sometimes".parse::<FsyncPolicy>().is_err());
+    }
+
+    /// Checks that under [`FsyncPolicy::Always`] every write path syncs the written object,
+    /// including copies and the writes of the query engine through the registered store
+    #[tokio::test]
+    async fn fsync_always_all_writes() {
+        use object_store::memory::InMemory;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Backend sync counting the synced objects
+        #[derive(Debug, Default)]
+        struct CountingSync(AtomicUsize);
+
+        impl ObjectSync for CountingSync {
+            fn sync(&self, _key: &object_store::path::Path) -> std::io::Result<()> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        let syncer = Arc::new(CountingSync::default());
+        let store = Store {
+            syncer: Some(syncer.clone()),
+            ..testing::with_driver(Arc::new(InMemory::new()))
+        }
+        .with_fsync_policy(FsyncPolicy::Always);
+
+        assert!(store.put_if_absent("a", b"data".to_vec()).await.unwrap());
+        assert_eq!(syncer.0.swap(0, Ordering::SeqCst), 1);
+
+        let (_, version) = store.read_versioned("a").await.unwrap();
+        assert!(
+            store
+                .put_if_version("a", b"new".to_vec(), &version)
+                .await
+                .unwrap()
+        );
+        assert_eq!(syncer.0.swap(0, Ordering::SeqCst), 1);
+
+        store.move_verified("a", "b").await.unwrap();
+        assert_eq!(syncer.0.swap(0, Ordering::SeqCst), 1);
+
+        let driver = store.registry().get_store(&store.url_schema).unwrap();
+        driver
+            .put(&ObjectPath::from("c"), PutPayload::from_static(b"data"))
+            .await
+            .unwrap();
+        assert_eq!(syncer.0.swap(0, Ordering::SeqCst), 1);
+
+        let mut upload = driver.put_multipart(&ObjectPath::from("d")).await.unwrap();
+        upload
+            .put_part(PutPayload::from_static(b"data"))
+            .await
+            .unwrap();
+        assert_eq!(syncer.0.load(Ordering::SeqCst), 0);
+        upload.complete().await.unwrap();
+        assert_eq!(syncer.0.swap(0, Ordering::SeqCst), 1);
+
+        // The registered store does not sync under the other policies
+        let driver = store
+            .clone()
+            .with_fsync_policy(FsyncPolicy::OnLock)
+            .registry()
+            .get_store(&store.url_schema)
+            .unwrap();
+        driver
+            .put(&ObjectPath::from("e"), PutPayload::from_static(b"data"))
+            .await
+            .unwrap();
+        assert_eq!(syncer.0.load(Ordering::SeqCst), 0);
+    }
+
+    /// Checks that the filesystem sync reaches the directories created along with an object
+    #[tokio::test]
+    async fn fsync_filesystem_nested() {
+        let store = testing::Store::new_random_on_tmp().unwrap();
+        let store = (**store).clone().with_fsync_policy(FsyncPolicy::Always);
+
+        store
+            .write_bytes("seq/topic/data-0.parquet", b"data".to_vec())
+            .await
+            .unwrap();
+        store
+            .copy("seq/topic/data-0.parquet", "seq/backup/data-0.parquet")
+            .await
+            .unwrap();
+        assert_eq!(
+            store.read_bytes("seq/backup/data-0.parquet").await.unwrap(),
+            b"data"
+        );
+    }
+
+    /// Checks that the read-after-write guard waits until an eventually consistent store
+    /// returns the written object
+    #[tokio::test]
+    async fn read_after_write() {
+        use futures::future::BoxFuture;
+        use object_store::{
+            GetOptions, GetResult, PutOptions, PutResult, memory::InMemory, path::Path,
+        };
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Hooks returning the previous version of an overwritten object for the first
+        /// `stale_reads` reads after the overwrite
+        #[derive(Debug, Default)]
+        struct StaleReads {
+            stale: InMemory,
+            stale_reads: AtomicUsize,
+            pending: AtomicUsize,
+        }
+
+        impl testing::StoreHooks for StaleReads {
+            fn put<'r>(
+                &'r self,
+                inner: &'r InMemory,
+                location: &'r Path,
+                payload: PutPayload,
+                opts: PutOptions,
+            ) -> BoxFuture<'r, object_store::Result<PutResult>> {
+                Box::pin(async move {
+                    if let Ok(previous) = inner.get(location).await {
+                        let previous = previous.bytes().await?;
+                        self.stale.put(location, previous.into()).await?;
+                        self.pending
+                            .store(self.stale_reads.load(Ordering::SeqCst), Ordering::SeqCst);
+                    }
+                    inner.put_opts(location, payload, opts).await
+                })
+            }
+
+            fn get<'r>(
+                &'r self,
+                inner: &'r InMemory,
+                location: &'r Path,
+                options: GetOptions,
+            ) -> BoxFuture<'r, object_store::Result<GetResult>> {
+                let stale = self
+                    .pending
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_ok();
+                if stale {
+                    self.stale.get_opts(location, options)
+                } else {
+                    inner.get_opts(location, options)
+                }
+            }
+        }
+
+        let driver = Arc::new(testing::HookedStore::new(StaleReads::default()));
+        let hooks = &driver.hooks;
+        hooks.stale_reads.store(2, Ordering::SeqCst);
+        let store = testing::with_driver(driver.clone());
+
+        // Without the guard the overwrite is acknowledged while reads are still stale
+        store.write_bytes("metadata", b"v1".to_vec()).await.unwrap();
+        store.write_bytes("metadata", b"v2".to_vec()).await.unwrap();
+        assert_eq!(hooks.pending.load(Ordering::SeqCst), 2);
+        assert_eq!(store.read_bytes("metadata").await.unwrap(), b"v1");
+        hooks.pending.store(0, Ordering::SeqCst);
+
+        // The guard consumes the stale reads, the next read returns the written data
+        let guarded = store.clone().with_read_after_write(true);
+        guarded
+            .write_bytes("metadata", b"v3".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(hooks.pending.load(Ordering::SeqCst), 0);
+        assert_eq!(guarded.read_bytes("metadata").await.unwrap(), b"v3");
+
+        // A store stale for longer than the guard attempts is reported
+        hooks
+            .stale_reads
+            .store(READ_AFTER_WRITE_ATTEMPTS + 1, Ordering::SeqCst);
+        let res = guarded.write_bytes("metadata", b"v4".to_vec()).await;
+        assert!(matches!(res, Err(Error::StaleRead { .. })));
+    }
 }
@@ -167,6 +167,38 @@ impl TextStats {
     }
 }
 
+/// Storage tier holding the datafile of a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageTier {
+    /// Datafile stored in the default storage class
+    #[default]
+    Warm,
+    /// Datafile moved to the cold storage class, still readable with a higher latency
+    Cold,
+}
+
+impl std::fmt::Display for StorageTier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Warm => "warm",
+            Self::Cold => "cold",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for StorageTier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warm" => Ok(Self::Warm),
+            "cold" => Ok(Self::Cold),
+            _ => Err(format!("unknown storage tier `{}`", s)),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2,9 +2,99 @@
 pub struct DoPutCmd {
     pub resource_locator: String,
     pub key: String,
+    /// If true the stream carries InfluxDB line protocol text to be ingested in the
+    /// sequence identified by `resource_locator`, instead of the data of a topic
+    pub line_protocol: bool,
+    /// If true a chunk identical to the last chunk of the topic is discarded instead of
+    /// being written, so that naive retries of an upload do not duplicate data. Ignored when
+    /// overwriting the topic.
+    pub deduplicate: bool,
+    /// How the uploaded data is combined with the data already in the topic, line protocol
+    /// uploads only support [`WriteMode::Append`]
+    pub write_mode: WriteMode,
+    /// If true a locked topic can be overwritten, not supported by line protocol uploads
+    pub force: bool,
+}
+
+/// How the data of an upload is combined with the data already in the topic
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteMode {
+    /// The uploaded data is added to the topic data
+    #[default]
+    Append,
+    /// The uploaded data replaces the topic data. The previous chunks are removed only once
+    /// the upload completes, a failed upload leaves them untouched.
+    Overwrite,
 }
 
 /// Request info on a mosaico resource (topic or sequence)
 pub struct GetFlightInfoCmd {
     pub resource_locator: String,
+    /// Optional row filter embedded in the returned tickets
+    pub filter: Option<serde_json::Value>,
+    /// Provenance columns to append to the data, embedded in the returned tickets
+    pub provenance: Vec<String>,
+    /// Optional sampling of the data, embedded in the returned tickets
+    pub sample: Option<serde_json::Value>,
+    /// Columns to retrieve, embedded in the returned tickets. If empty all columns are returned
+    pub projection: Vec<String>,
+    /// Optional order of the data, embedded in the returned tickets
+    pub order_by: Option<serde_json::Value>,
+    /// If true dictionaries are reused across the streamed batches, embedded in the returned
+    /// tickets
+    pub dictionary_reuse: bool,
+    /// Optional number of rows of the streamed batches, embedded in the returned tickets
+    pub result_batch_rows: Option<usize>,
+    /// Optional minimum number of rows of the streamed batches, embedded in the returned
+    /// tickets
+    pub min_batch_rows: Option<usize>,
+    /// If true data streams exceeding the query timeout are truncated instead of failing,
+    /// embedded in the returned tickets
+    pub partial_on_timeout: bool,
+    /// Optional token chosen by the client to cancel the data streams, embedded in the
+    /// returned tickets
+    pub request_token: Option<String>,
+    /// If true data streams are closed by a message reporting the work done by the query,
+    /// embedded in the returned tickets
+    pub scan_stats: bool,
+    /// If true the streamed data messages report their source chunk, embedded in the
+    /// returned tickets
+    pub chunk_attribution: bool,
+}
+
+/// Ticket used to retrieve the data of a topic
+pub struct DoGetTicket {
+    pub resource_locator: String,
+    /// Optional row filter applied to the returned data
+    pub filter: Option<crate::query::RowFilter>,
+    /// Provenance columns appended to the returned data
+    pub provenance: Vec<crate::query::ProvenanceColumn>,
+    /// Optional sampling of the returned data
+    pub sample: Option<crate::query::Sample>,
+    /// Columns to retrieve, if empty all columns are returned
+    pub projection: Vec<String>,
+    /// Order of the returned data, if not set rows are ordered by ascending timestamp
+    pub order_by: Option<crate::query::OrderBy>,
+    /// If true dictionary encoded columns are streamed as dictionaries, sending each
+    /// dictionary only when it changes, instead of being expanded in every batch
+    pub dictionary_reuse: bool,
+    /// If set the returned data is streamed in batches of this number of rows (the last
+    /// batch may be smaller), otherwise the batches are sized by the server
+    pub result_batch_rows: Option<usize>,
+    /// If set consecutive small batches are merged, so that every streamed batch but the last
+    /// one holds at least this number of rows
+    pub min_batch_rows: Option<usize>,
+    /// If true, when the query timeout is hit, the data streamed so far is returned followed
+    /// by a message flagging the result as partial, instead of failing the stream
+    pub partial_on_timeout: bool,
+    /// If set the data stream can be cancelled by the `query_cancel` action using this token,
+    /// requested with the same identity of the stream
+    pub request_token: Option<String>,
+    /// If true the returned data is followed by a message reporting the work done by the
+    /// query (chunks scanned, bytes read, rows scanned and returned)
+    pub scan_stats: bool,
+    /// If true the data is streamed chunk by chunk, and the `app_metadata` of each data
+    /// message reports the index of the chunk holding its rows. Rows are sorted within each
+    /// chunk only.
+    pub chunk_attribution: bool,
 }
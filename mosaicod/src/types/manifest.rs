@@ -0,0 +1,149 @@
+//! Manifests describing the content of a sequence, used to verify backups.
+
+use std::collections::HashMap;
+
+use super::TopicProperties;
+
+/// Content of a sequence: its topics with their properties and datafiles.
+#[derive(Debug, Clone)]
+pub struct SequenceManifest {
+    pub sequence: String,
+    pub topics: Vec<TopicManifest>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TopicManifest {
+    pub name: String,
+    pub properties: TopicProperties,
+    pub chunks: Vec<ChunkManifest>,
+}
+
+impl TopicManifest {
+    pub fn total_size_bytes(&self) -> usize {
+        self.chunks.iter().map(|c| c.size_bytes).sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkManifest {
+    pub datafile: String,
+    pub size_bytes: usize,
+    /// Hex encoded SHA-256 digest of the datafile content, see [`crate::utils::hash::sha256`]
+    pub checksum: String,
+}
+
+impl SequenceManifest {
+    /// Compares the `actual` content of a sequence against this manifest, returning a
+    /// description of each difference found. An empty list means that `actual` matches the
+    /// manifest.
+    pub fn diff(&self, actual: &SequenceManifest) -> Vec<String> {
+        let mut mismatches = Vec::new();
+
+        if self.sequence != actual.sequence {
+            mismatches.push(format!(
+                "sequence `{}` does not match `{}`",
+                actual.sequence, self.sequence
+            ));
+        }
+
+        let mut actual_topics: HashMap<&str, &TopicManifest> =
+            actual.topics.iter().map(|t| (t.name.as_str(), t)).collect();
+
+        for expected in &self.topics {
+            let Some(topic) = actual_topics.remove(expected.name.as_str()) else {
+                mismatches.push(format!("missing topic `{}`", expected.name));
+                continue;
+            };
+            expected.diff(topic, &mut mismatches);
+        }
+
+        let mut extra: Vec<_> = actual_topics.into_keys().collect();
+        extra.sort();
+        for name in extra {
+            mismatches.push(format!("unexpected topic `{name}`"));
+        }
+
+        mismatches
+    }
+}
+
+impl TopicManifest {
+    fn diff(&self, actual: &TopicManifest, mismatches: &mut Vec<String>) {
+        if self.properties.serialization_format != actual.properties.serialization_format
+            || self.properties.ontology_tag != actual.properties.ontology_tag
+        {
+            mismatches.push(format!(
+                "topic `{}` has properties `{}`, expected `{}`",
+                self.name, actual.properties, self.properties
+            ));
+        }
+
+        let mut actual_chunks: HashMap<&str, &ChunkManifest> = actual
+            .chunks
+            .iter()
+            .map(|c| (c.datafile.as_str(), c))
+            .collect();
+
+        for expected in &self.chunks {
+            match actual_chunks.remove(expected.datafile.as_str()) {
+                None => mismatches.push(format!("missing datafile `{}`", expected.datafile)),
+                Some(chunk) if chunk.size_bytes != expected.size_bytes => mismatches.push(format!(
+                    "datafile `{}` has size {}, expected {}",
+                    expected.datafile, chunk.size_bytes, expected.size_bytes
+                )),
+                Some(chunk) if chunk.checksum != expected.checksum => mismatches.push(format!(
+                    "datafile `{}` checksum does not match",
+                    expected.datafile
+                )),
+                Some(_) => {}
+            }
+        }
+
+        let mut extra: Vec<_> = actual_chunks.into_keys().collect();
+        extra.sort();
+        for datafile in extra {
+            mismatches.push(format!("unexpected datafile `{datafile}`"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rw;
+
+    fn manifest(chunks: &[(&str, &str)]) -> SequenceManifest {
+        SequenceManifest {
+            sequence: "seq".to_owned(),
+            topics: vec![TopicManifest {
+                name: "seq/topic".to_owned(),
+                properties: TopicProperties::new(rw::Format::Default, "imu".to_owned()),
+                chunks: chunks
+                    .iter()
+                    .map(|(datafile, checksum)| ChunkManifest {
+                        datafile: datafile.to_string(),
+                        size_bytes: 10,
+                        checksum: checksum.to_string(),
+                    })
+                    .collect(),
+            }],
+        }
+    }
+
+    #[test]
+    fn manifest_diff() {
+        let expected = manifest(&[("seq/topic/data-0", "01"), ("seq/topic/data-1", "02")]);
+
+        assert!(expected.diff(&expected.clone()).is_empty());
+
+        let actual = manifest(&[("seq/topic/data-0", "01"), ("seq/topic/data-1", "03")]);
+        assert_eq!(
+            expected.diff(&actual),
+            vec!["datafile `seq/topic/data-1` checksum does not match"]
+        );
+
+        let mut actual = expected.clone();
+        actual.topics.clear();
+        assert_eq!(expected.diff(&actual), vec!["missing topic `seq/topic`"]);
+    }
+}
@@ -12,10 +12,22 @@ pub use notify::*;
 mod resources;
 pub use resources::*;
 
+mod state;
+pub use state::*;
+
 mod layer;
 pub use layer::*;
 
 mod chunk;
 pub use chunk::*;
 
+mod naming;
+pub use naming::*;
+
+mod name_charset;
+pub use name_charset::*;
+
+mod manifest;
+pub use manifest::*;
+
 pub mod flight;
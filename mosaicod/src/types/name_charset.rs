@@ -0,0 +1,193 @@
+//! Characters allowed in the names of the resources.
+//!
+//! Names are mapped to paths on the store, characters such as colons or control characters
+//! behave differently across filesystems and object stores, so names are restricted to an
+//! allowlist of portable characters.
+
+use std::sync::LazyLock;
+
+/// Characters allowed in resource names besides the ASCII alphanumerics, used if no allowlist
+/// is configured.
+pub const DEFAULT_NAME_ALLOWED_CHARS: &str = "-_/.";
+
+/// How the characters of a resource name missing from the allowlist are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NamePolicy {
+    /// Names are accepted as they are, the allowlist is not enforced
+    #[default]
+    Allow,
+    /// Resources with a disallowed character in their name can't be created
+    Reject,
+    /// Disallowed characters are percent-encoded (e.g. `:` becomes `%3A`)
+    Encode,
+}
+
+impl std::str::FromStr for NamePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "allow" => Ok(Self::Allow),
+            "reject" => Ok(Self::Reject),
+            "encode" => Ok(Self::Encode),
+            _ => Err(format!("unknown name policy `{value}`")),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("character {character:?} not allowed in resource name `{name}`")]
+pub struct DisallowedNameCharacter {
+    pub name: String,
+    pub character: char,
+}
+
+/// Allowlist of the characters of resource names.
+///
+/// ASCII alphanumerics and the `/` separator are always allowed.
+#[derive(Debug, Clone)]
+pub struct NameCharset {
+    allowed: String,
+    policy: NamePolicy,
+}
+
+impl Default for NameCharset {
+    fn default() -> Self {
+        Self::new(DEFAULT_NAME_ALLOWED_CHARS, NamePolicy::default())
+    }
+}
+
+impl NameCharset {
+    pub fn new(allowed: impl Into<String>, policy: NamePolicy) -> Self {
+        Self {
+            allowed: allowed.into(),
+            policy,
+        }
+    }
+
+    pub fn policy(&self) -> NamePolicy {
+        self.policy
+    }
+
+    pub fn is_allowed(&self, c: char) -> bool {
+        c.is_ascii_alphanumeric() || c == '/' || self.allowed.contains(c)
+    }
+
+    /// Applies the [`NamePolicy::Encode`] policy to `name`, other policies leave the name
+    /// untouched.
+    ///
+    /// Valid percent-encoded sequences are kept as they are, so encoding an already encoded
+    /// name has no effect.
+    pub fn encode(&self, name: &str) -> String {
+        if self.policy != NamePolicy::Encode {
+            return name.to_owned();
+        }
+
+        let bytes = name.as_bytes();
+        let mut encoded = String::with_capacity(name.len());
+        for (idx, c) in name.char_indices() {
+            let escape = c == '%'
+                && bytes.len() > idx + 2
+                && bytes[idx + 1].is_ascii_hexdigit()
+                && bytes[idx + 2].is_ascii_hexdigit();
+
+            if escape || self.is_allowed(c) {
+                encoded.push(c);
+            } else {
+                let mut buf = [0; 4];
+                for byte in c.encode_utf8(&mut buf).bytes() {
+                    encoded.push_str(&format!("%{byte:02X}"));
+                }
+            }
+        }
+        encoded
+    }
+
+    /// Checks that `name` holds only allowed characters when the [`NamePolicy::Reject`] policy
+    /// is used, names are always accepted by the other policies.
+    pub fn check(&self, name: &str) -> Result<(), DisallowedNameCharacter> {
+        if self.policy != NamePolicy::Reject {
+            return Ok(());
+        }
+
+        match name.chars().find(|c| !self.is_allowed(*c)) {
+            Some(character) => Err(DisallowedNameCharacter {
+                name: name.to_owned(),
+                character,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the allowlist applied to the names of the resources, the default one if the
+/// configurable parameters are not loaded.
+pub fn name_charset() -> &'static NameCharset {
+    static DEFAULT_NAME_CHARSET: LazyLock<NameCharset> = LazyLock::new(NameCharset::default);
+
+    crate::params::try_configurables().map_or(&DEFAULT_NAME_CHARSET, |params| &params.name_charset)
+}
+
+/// Checks that a resource can be created with `name`, see [`NameCharset::check`].
+pub fn check_name(name: &str) -> Result<(), DisallowedNameCharacter> {
+    name_charset().check(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_any_character() {
+        let charset = NameCharset::default();
+
+        assert!(charset.check("my_sequence/imu:front").is_ok());
+        assert_eq!(
+            charset.encode("my_sequence/imu:front"),
+            "my_sequence/imu:front"
+        );
+    }
+
+    #[test]
+    fn reject_disallowed_character() {
+        let charset = NameCharset::new(DEFAULT_NAME_ALLOWED_CHARS, NamePolicy::Reject);
+
+        assert!(charset.check("my_sequence/topic-1.v2").is_ok());
+        assert_eq!(
+            charset.check("my_sequence/imu:front").unwrap_err(),
+            DisallowedNameCharacter {
+                name: "my_sequence/imu:front".to_owned(),
+                character: ':',
+            }
+        );
+        assert!(charset.check("my_sequence/imu\tfront").is_err());
+
+        // Names are not altered
+        assert_eq!(
+            charset.encode("my_sequence/imu:front"),
+            "my_sequence/imu:front"
+        );
+
+        // Configured characters are allowed
+        let charset = NameCharset::new(":", NamePolicy::Reject);
+        assert!(charset.check("my_sequence/imu:front").is_ok());
+        assert!(charset.check("my_sequence/imu-front").is_err());
+    }
+
+    #[test]
+    fn encode_disallowed_character() {
+        let charset = NameCharset::new(DEFAULT_NAME_ALLOWED_CHARS, NamePolicy::Encode);
+
+        assert!(charset.check("my_sequence/imu:front").is_ok());
+
+        let encoded = charset.encode("my_sequence/imu:front %");
+        assert_eq!(encoded, "my_sequence/imu%3Afront%20%25");
+        // Encoding is idempotent
+        assert_eq!(charset.encode(&encoded), encoded);
+
+        assert_eq!(
+            charset.encode("my_sequence/caffè"),
+            "my_sequence/caff%C3%A8"
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! Strategies used to generate the names of resources created without an explicit name.
+
+use super::DateTime;
+
+/// Generates names for resources whose name is assigned by the server.
+///
+/// The generated name is the last component of the resource locator (e.g. the topic name
+/// without the parent sequence). If a generated name collides with an existing resource a new
+/// one is requested increasing `attempt`, implementors must return a different name for each
+/// attempt.
+pub trait NameStrategy: Send + Sync {
+    fn generate(&self, attempt: usize) -> String;
+}
+
+/// Names resources after the creation datetime (millisecond precision), collisions are solved
+/// appending the attempt number.
+pub struct TimestampNameStrategy;
+
+impl NameStrategy for TimestampNameStrategy {
+    fn generate(&self, attempt: usize) -> String {
+        let name = DateTime::now().fmt_to_ms();
+        if attempt == 0 {
+            name
+        } else {
+            format!("{}_{}", name, attempt)
+        }
+    }
+}
+
+/// Names resources with a random UUID.
+pub struct UuidNameStrategy;
+
+impl NameStrategy for UuidNameStrategy {
+    fn generate(&self, _attempt: usize) -> String {
+        uuid::Uuid::new_v4().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn timestamp_names_are_unique() {
+        // Names generated in the same millisecond are disambiguated by the attempt number
+        let names: HashSet<String> = (0..100)
+            .map(|attempt| TimestampNameStrategy.generate(attempt))
+            .collect();
+
+        assert_eq!(names.len(), 100);
+    }
+
+    #[test]
+    fn uuid_names_are_unique() {
+        let names: HashSet<String> = (0..100).map(|_| UuidNameStrategy.generate(0)).collect();
+
+        assert_eq!(names.len(), 100);
+    }
+}
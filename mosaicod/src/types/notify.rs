@@ -29,7 +29,8 @@ pub struct Notify {
     pub target: Box<dyn super::Resource>,
     pub notify_type: NotifyType,
     pub msg: Option<String>,
-    pub created_at: super::DateTime,
+    /// Creation datetime, `None` if the stored timestamp is out of range
+    pub created_at: Option<super::DateTime>,
 }
 
 impl Notify {
@@ -39,7 +40,7 @@ impl Notify {
             notify_type: ntype,
             target,
             msg,
-            created_at: super::DateTime::now(),
+            created_at: Some(super::DateTime::now()),
         }
     }
 }
@@ -1,5 +1,7 @@
 use super::TimestampRange;
 use crate::{params, rw, traits};
+use arrow::datatypes::SchemaRef;
+use serde::{Deserialize, Serialize};
 use std::path;
 
 pub struct ResourceId {
@@ -16,6 +18,8 @@ pub enum ResourceType {
 pub struct TopicResourceLocator {
     locator: String,
     pub timestamp_range: Option<TimestampRange>,
+    /// Time range searched by a query, clamped to the data extent of the topic
+    pub searched_range: Option<TimestampRange>,
 }
 
 impl TopicResourceLocator {
@@ -23,6 +27,13 @@ impl TopicResourceLocator {
         self.timestamp_range = Some(ts);
         self
     }
+
+    /// Returns the name of the sequence containing the topic
+    pub fn sequence_name(&self) -> &str {
+        self.locator
+            .split_once('/')
+            .map_or(self.locator.as_str(), |(sequence, _)| sequence)
+    }
 }
 
 impl Resource for TopicResourceLocator {
@@ -88,20 +99,172 @@ pub struct TopicChunksStats {
     pub total_row_count: i64,
 }
 
+/// Naming scheme of the datafiles of a topic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkNaming {
+    /// Datafiles are named after the chunk index only, e.g. `data-00000.parquet`
+    #[default]
+    Index,
+    /// The chunk timestamp range is appended to the chunk index, e.g.
+    /// `data-00000_1000_2000.parquet`, so that the time covered by a chunk can be inferred by
+    /// listing the topic folder.
+    TimeRange,
+}
+
+/// Ordering of the rows of a topic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowOrdering {
+    /// Rows can be uploaded in any order
+    #[default]
+    Unordered,
+    /// Rows must be uploaded sorted by ascending timestamp, within and across chunks
+    TimeAscending,
+}
+
 /// Configuration properties defining the data semantic and encoding for a topic.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TopicProperties {
     pub serialization_format: rw::Format,
     pub ontology_tag: String,
+    /// If true the schema of the topic data is pinned, uploads with a schema different from
+    /// the established one are rejected, even if the change is additive.
+    pub schema_locked: bool,
+    /// Unit of the timestamps stored in the topic data.
+    pub time_precision: super::TimePrecision,
+    /// Naming scheme of the topic datafiles.
+    pub chunk_naming: ChunkNaming,
+    /// Ordering enforced on the uploaded rows.
+    pub ordering: RowOrdering,
+    /// If true the datafiles include the Parquet page index of every column, allowing the
+    /// queries to skip the pages not matching a value predicate at the cost of a slower write.
+    pub page_index: bool,
+    /// Quality level of the compression codec of the datafiles, if not provided the default
+    /// level of the serialization format is used.
+    pub compression_level: Option<i32>,
+    /// Schema of the topic data declared at creation, uploads with a different schema are
+    /// rejected. If not declared the schema is established by the first upload.
+    pub schema: Option<SchemaRef>,
+    /// Name of the column of the uploaded data holding the timestamps, stored as the
+    /// `timestamp_ns` column. If not set the uploaded data holds the `timestamp_ns` column.
+    pub time_column: Option<String>,
 }
 
+/// Error raised when a topic is created with an empty ontology tag and no default tag is
+/// configured.
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("empty ontology tag")]
+pub struct EmptyOntologyTag;
+
 impl TopicProperties {
+    /// Creates the properties without validating the ontology tag, for trusted internal use.
     pub fn new(serialization_format: rw::Format, ontology_tag: String) -> Self {
         Self {
             serialization_format,
             ontology_tag,
+            schema_locked: false,
+            time_precision: super::TimePrecision::default(),
+            chunk_naming: ChunkNaming::default(),
+            ordering: RowOrdering::default(),
+            page_index: false,
+            compression_level: None,
+            schema: None,
+            time_column: None,
         }
     }
+
+    /// Same as [`TopicProperties::new`] but rejects an empty (or blank) `ontology_tag`, unless
+    /// a `default_tag` is provided, which is used in its place.
+    pub fn try_new(
+        serialization_format: rw::Format,
+        ontology_tag: String,
+        default_tag: Option<&str>,
+    ) -> Result<Self, EmptyOntologyTag> {
+        let ontology_tag = if ontology_tag.trim().is_empty() {
+            default_tag.ok_or(EmptyOntologyTag)?.to_owned()
+        } else {
+            ontology_tag
+        };
+        Ok(Self::new(serialization_format, ontology_tag))
+    }
+
+    /// Pins (or unpins) the schema of the topic data.
+    pub fn with_schema_locked(mut self, schema_locked: bool) -> Self {
+        self.schema_locked = schema_locked;
+        self
+    }
+
+    /// Sets the unit of the timestamps stored in the topic data.
+    pub fn with_time_precision(mut self, time_precision: super::TimePrecision) -> Self {
+        self.time_precision = time_precision;
+        self
+    }
+
+    /// Sets the naming scheme of the topic datafiles.
+    pub fn with_chunk_naming(mut self, chunk_naming: ChunkNaming) -> Self {
+        self.chunk_naming = chunk_naming;
+        self
+    }
+
+    /// Sets the ordering enforced on the uploaded rows.
+    pub fn with_ordering(mut self, ordering: RowOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// Enables (or disables) the Parquet page index in the topic datafiles.
+    pub fn with_page_index(mut self, page_index: bool) -> Self {
+        self.page_index = page_index;
+        self
+    }
+
+    /// Sets the quality level of the compression codec of the datafiles, failing if the codec
+    /// of the serialization format has no levels or `level` is out of its range.
+    pub fn with_compression_level(
+        mut self,
+        level: Option<i32>,
+    ) -> Result<Self, rw::InvalidCompressionLevel> {
+        if let Some(level) = level {
+            self.serialization_format.compression_with_level(level)?;
+        }
+        self.compression_level = level;
+        Ok(self)
+    }
+
+    /// Declares the schema of the topic data.
+    pub fn with_schema(mut self, schema: Option<SchemaRef>) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Sets the name of the column of the uploaded data holding the timestamps.
+    pub fn with_time_column(mut self, time_column: Option<String>) -> Self {
+        self.time_column = time_column;
+        self
+    }
+
+    /// Overrides the serialization format, useful to derive properties from a template.
+    pub fn with_format(mut self, serialization_format: rw::Format) -> Self {
+        self.serialization_format = serialization_format;
+        self
+    }
+
+    /// Overrides the ontology tag, useful to derive properties from a template.
+    pub fn with_ontology_tag(mut self, ontology_tag: String) -> Self {
+        self.ontology_tag = ontology_tag;
+        self
+    }
+}
+
+impl std::fmt::Display for TopicProperties {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "format={} ontology={}",
+            self.serialization_format, self.ontology_tag
+        )
+    }
 }
 
 /// Represents system-level metadata and statistical information for a specific topic.
@@ -120,8 +283,32 @@ pub struct TopicSystemInfo {
     /// Total size in bytes of the data.
     /// Metadata and other system files are excluded in the count.
     pub total_size_bytes: usize,
-    /// Datetime of the topic creation
-    pub created_datetime: super::DateTime,
+    /// Datetime of the topic creation, `None` if out of range
+    pub created_datetime: Option<super::DateTime>,
+    /// Datetime of the last chunk written in the topic, equal to the creation datetime
+    /// if no data was written, `None` if out of range
+    pub last_write_datetime: Option<super::DateTime>,
+}
+
+/// Result of the comparison between the chunks registered in the repository
+/// and the datafiles actually found in the store for a topic.
+#[derive(Debug, Default)]
+pub struct TopicConsistencyReport {
+    /// Chunks registered in the repository but missing from the store
+    pub missing_chunks: Vec<String>,
+    /// Datafiles found in the store but not registered in the repository
+    pub extra_chunks: Vec<String>,
+    /// Chunks whose size in the store differs from the registered size
+    pub size_mismatched_chunks: Vec<String>,
+}
+
+impl TopicConsistencyReport {
+    /// Returns true if repository and store agree on the topic chunks
+    pub fn is_consistent(&self) -> bool {
+        self.missing_chunks.is_empty()
+            && self.extra_chunks.is_empty()
+            && self.size_mismatched_chunks.is_empty()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -163,6 +350,10 @@ where
     M: super::MetadataBlob,
 {
     pub user_metadata: M,
+    /// Time column inherited by the topics of the sequence not declaring one.
+    pub time_column: Option<String>,
+    /// Time precision inherited by the topics of the sequence not declaring one.
+    pub time_precision: Option<super::TimePrecision>,
 }
 
 impl<M> SequenceMetadata<M>
@@ -170,7 +361,23 @@ where
     M: super::MetadataBlob,
 {
     pub fn new(user_metadata: M) -> Self {
-        Self { user_metadata }
+        Self {
+            user_metadata,
+            time_column: None,
+            time_precision: None,
+        }
+    }
+
+    /// Sets the time column inherited by the topics of the sequence.
+    pub fn with_time_column(mut self, time_column: Option<String>) -> Self {
+        self.time_column = time_column;
+        self
+    }
+
+    /// Sets the time precision inherited by the topics of the sequence.
+    pub fn with_time_precision(mut self, time_precision: Option<super::TimePrecision>) -> Self {
+        self.time_precision = time_precision;
+        self
     }
 }
 
@@ -182,8 +389,8 @@ pub struct SequenceSystemInfo {
     /// all its topics are locked and the `sequence_finalize` action
     /// was called.
     pub is_locked: bool,
-    /// Datetime of the sequence creation
-    pub created_datetime: super::DateTime,
+    /// Datetime of the sequence creation, `None` if out of range
+    pub created_datetime: Option<super::DateTime>,
 }
 
 /// Groups a specific sequence with its associated topics and an optional time filter.
@@ -205,6 +412,11 @@ impl SequenceTopicGroup {
     }
 }
 
+/// Error raised when the same sequence appears in more than one [`SequenceTopicGroup`].
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("sequence `{0}` appears in more than one group")]
+pub struct DuplicateSequence(pub String);
+
 #[derive(Debug)]
 pub struct SequenceTopicGroups(Vec<SequenceTopicGroup>);
 
@@ -213,6 +425,14 @@ impl SequenceTopicGroups {
         Self(groups)
     }
 
+    /// Same as [`SequenceTopicGroups::new`] but rejects groups containing the same sequence
+    /// more than once.
+    pub fn try_new(groups: Vec<SequenceTopicGroup>) -> Result<Self, DuplicateSequence> {
+        let groups = Self(groups);
+        groups.ensure_unique_sequences()?;
+        Ok(groups)
+    }
+
     pub fn empty() -> Self {
         Self(Vec::new())
     }
@@ -237,6 +457,54 @@ impl SequenceTopicGroups {
 
         result
     }
+
+    /// Same as [`SequenceTopicGroups::merge`] but fails if any of the two groups contains the
+    /// same sequence more than once, instead of combining only the first occurrence.
+    pub fn merge_strict(self, group: Self) -> Result<Self, DuplicateSequence> {
+        self.ensure_unique_sequences()?;
+        group.ensure_unique_sequences()?;
+        Ok(self.merge(group))
+    }
+
+    fn ensure_unique_sequences(&self) -> Result<(), DuplicateSequence> {
+        let mut seen = std::collections::HashSet::new();
+        for grp in &self.0 {
+            if !seen.insert(grp.sequence.name()) {
+                return Err(DuplicateSequence(grp.sequence.name().clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Consumes the current group and a provided group to produce a new group in which
+    /// the topics of the matching sequences are intersected. Sequences without common topics
+    /// are dropped, the topics of the current group are kept.
+    pub fn intersect_topics(self, group: Self) -> Self {
+        let mut result = Self::empty();
+        for grp1 in self.0 {
+            let Some(found) = group
+                .0
+                .iter()
+                .find(|grp2| grp1.sequence.name() == grp2.sequence.name())
+            else {
+                continue;
+            };
+
+            let topics: Vec<TopicResourceLocator> = grp1
+                .topics
+                .into_iter()
+                .filter(|t1| found.topics.iter().any(|t2| t1.name() == t2.name()))
+                .collect();
+
+            if !topics.is_empty() {
+                result
+                    .0
+                    .push(SequenceTopicGroup::new(grp1.sequence, topics));
+            }
+        }
+
+        result
+    }
 }
 
 impl Default for SequenceTopicGroups {
@@ -271,6 +539,15 @@ pub trait Resource: std::fmt::Display + Send + Sync {
         path
     }
 
+    /// Returns the location of the lease file held by the instance writing the resource.
+    ///
+    /// The lease file may or may not exists, no check if performed by this function.
+    fn lease(&self) -> path::PathBuf {
+        let mut path = path::Path::new(self.name()).join("lease");
+        path.set_extension(params::ext::JSON);
+        path
+    }
+
     fn datafile(&self, chunk_number: usize, extension: &dyn traits::AsExtension) -> path::PathBuf {
         let filename = format!("data-{:05}", chunk_number);
         let mut path = path::Path::new(self.name()).join(filename);
@@ -280,18 +557,50 @@ pub trait Resource: std::fmt::Display + Send + Sync {
         path
     }
 
+    /// Returns the location of a datafile named after both the chunk index and the timestamp
+    /// `range` of the chunk data, see [`ChunkNaming::TimeRange`].
+    fn datafile_with_range(
+        &self,
+        chunk_number: usize,
+        range: &TimestampRange,
+        extension: &dyn traits::AsExtension,
+    ) -> path::PathBuf {
+        let filename = format!("data-{:05}_{}_{}", chunk_number, range.start, range.end);
+        let mut path = path::Path::new(self.name()).join(filename);
+
+        path.set_extension(extension.as_extension());
+
+        path
+    }
+
     fn is_sub_resource(&self, parent: &dyn Resource) -> bool {
         self.name().starts_with(parent.name())
     }
 }
 
+/// Returns the chunk index of a datafile path, regardless of the [`ChunkNaming`] used to
+/// produce it. Returns [`None`] if the path is not a datafile.
+pub fn datafile_index(datafile: impl AsRef<path::Path>) -> Option<usize> {
+    let stem = datafile
+        .as_ref()
+        .file_stem()?
+        .to_str()?
+        .strip_prefix("data-")?;
+    let index = stem.split('_').next()?;
+    index.parse().ok()
+}
+
 /// Returns a sanitized resource name by trimming whitespace and ensuring it does **not** start with a `/`.
 ///
 /// This function is useful when normalizing resource paths or identifiers to ensure consistency
 /// across the application by making them relative paths.
+///
+/// Characters missing from the configured [`super::NameCharset`] are percent-encoded if the
+/// [`super::NamePolicy::Encode`] policy is used, otherwise they are rejected when the resource
+/// is created (see [`super::check_name`]).
 fn sanitize_name(name: &str) -> String {
     let trimmed = name.trim();
-    trimmed.trim_start_matches('/').to_owned()
+    super::name_charset().encode(trimmed.trim_start_matches('/'))
 }
 
 #[cfg(test)]
@@ -313,4 +622,194 @@ mod tests {
 
     #[test]
     fn merge_sequence_topic_groups() {}
+
+    fn groups(groups: &[(&str, &[&str])]) -> SequenceTopicGroups {
+        groups
+            .iter()
+            .map(|(sequence, topics)| {
+                SequenceTopicGroup::new(
+                    SequenceResourceLocator::from(sequence),
+                    topics.iter().map(TopicResourceLocator::from).collect(),
+                )
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    fn names(groups: SequenceTopicGroups) -> Vec<(String, Vec<String>)> {
+        Vec::from(groups)
+            .into_iter()
+            .map(|grp| {
+                let topics = grp.topics.iter().map(|t| t.name().clone()).collect();
+                (grp.sequence.name().clone(), topics)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn intersect_topics_full_overlap() {
+        let a = groups(&[("seq", &["seq/a", "seq/b"])]);
+        let b = groups(&[("seq", &["seq/b", "seq/a"])]);
+
+        assert_eq!(
+            names(a.intersect_topics(b)),
+            vec![(
+                "seq".to_owned(),
+                vec!["seq/a".to_owned(), "seq/b".to_owned()]
+            )]
+        );
+    }
+
+    #[test]
+    fn intersect_topics_partial_overlap() {
+        let a = groups(&[("seq", &["seq/a", "seq/b"]), ("other", &["other/a"])]);
+        let b = groups(&[("seq", &["seq/b", "seq/c"])]);
+
+        assert_eq!(
+            names(a.intersect_topics(b)),
+            vec![("seq".to_owned(), vec!["seq/b".to_owned()])]
+        );
+    }
+
+    #[test]
+    fn intersect_topics_no_overlap() {
+        let a = groups(&[("seq", &["seq/a"]), ("other", &["other/a"])]);
+        let b = groups(&[("seq", &["seq/b"]), ("other", &["other/a"])]);
+
+        // `seq` has no common topics and is dropped
+        assert_eq!(
+            names(a.intersect_topics(b)),
+            vec![("other".to_owned(), vec!["other/a".to_owned()])]
+        );
+    }
+
+    #[test]
+    fn topic_properties_overrides() {
+        let template = TopicProperties::new(rw::Format::Default, "imu".to_owned());
+
+        let props = template.clone().with_format(rw::Format::Ragged);
+
+        assert_eq!(props.serialization_format, rw::Format::Ragged);
+        assert_eq!(props.ontology_tag, "imu");
+        assert_eq!(template.serialization_format, rw::Format::Default);
+    }
+
+    #[test]
+    fn topic_properties_reject_empty_tag() {
+        for tag in ["", "  "] {
+            assert_eq!(
+                TopicProperties::try_new(rw::Format::Default, tag.to_owned(), None).unwrap_err(),
+                EmptyOntologyTag
+            );
+        }
+
+        let props = TopicProperties::try_new(rw::Format::Default, "imu".to_owned(), None).unwrap();
+        assert_eq!(props.ontology_tag, "imu");
+    }
+
+    #[test]
+    fn topic_properties_compression_level() {
+        let props = TopicProperties::new(rw::Format::Ragged, "imu".to_owned())
+            .with_compression_level(Some(19))
+            .unwrap();
+        assert_eq!(props.compression_level, Some(19));
+
+        // ZSTD levels range from 1 to 22
+        let err = TopicProperties::new(rw::Format::Ragged, "imu".to_owned())
+            .with_compression_level(Some(23))
+            .unwrap_err();
+        assert_eq!(err.level, 23);
+    }
+
+    #[test]
+    fn topic_properties_default_tag() {
+        let props =
+            TopicProperties::try_new(rw::Format::Default, "".to_owned(), Some("untyped")).unwrap();
+        assert_eq!(props.ontology_tag, "untyped");
+
+        // A non-empty tag is never replaced
+        let props =
+            TopicProperties::try_new(rw::Format::Default, "imu".to_owned(), Some("untyped"))
+                .unwrap();
+        assert_eq!(props.ontology_tag, "imu");
+    }
+
+    #[test]
+    fn topic_properties_display() {
+        let props = TopicProperties::new(rw::Format::Ragged, "temperature".to_owned());
+
+        assert_eq!(props.to_string(), "format=ragged ontology=temperature");
+    }
+
+    #[test]
+    fn datafile_with_range_sorts_by_index() {
+        let locator = TopicResourceLocator::from("sequence/topic");
+
+        let datafiles: Vec<_> = [(2, -50, 10), (0, 1000, 2000), (10, 5, 7), (1, 2000, 3000)]
+            .into_iter()
+            .map(|(idx, start, end)| {
+                let range = TimestampRange::new(start.into(), end.into());
+                locator.datafile_with_range(idx, &range, &rw::Format::Default)
+            })
+            .collect();
+
+        assert_eq!(
+            datafiles[1].file_name().unwrap(),
+            "data-00000_1000_2000.parquet"
+        );
+        assert_eq!(
+            datafiles[0].file_name().unwrap(),
+            "data-00002_-50_10.parquet"
+        );
+
+        let mut indices: Vec<_> = datafiles.iter().filter_map(datafile_index).collect();
+        indices.sort();
+        assert_eq!(indices, vec![0, 1, 2, 10]);
+
+        // Lexicographic order of the names matches the chunk order
+        let mut names = datafiles.clone();
+        names.sort();
+        assert_eq!(
+            names.iter().filter_map(datafile_index).collect::<Vec<_>>(),
+            indices
+        );
+
+        // Datafiles named after the index only are parsed as well
+        assert_eq!(
+            datafile_index(locator.datafile(3, &rw::Format::Default)),
+            Some(3)
+        );
+        assert_eq!(datafile_index(locator.metadata()), None);
+    }
+
+    #[test]
+    fn try_new_rejects_duplicate_sequences() {
+        let duplicated = Vec::from(groups(&[("seq", &["seq/a"]), ("seq", &["seq/b"])]));
+        assert_eq!(
+            SequenceTopicGroups::try_new(duplicated).unwrap_err(),
+            DuplicateSequence("seq".to_owned())
+        );
+
+        let a = groups(&[("seq", &["seq/a"]), ("other", &["other/a"])]);
+        let b = groups(&[("seq", &["seq/b"]), ("seq", &["seq/c"])]);
+        assert_eq!(
+            a.merge_strict(b).unwrap_err(),
+            DuplicateSequence("seq".to_owned())
+        );
+    }
+
+    #[test]
+    fn try_new_accepts_unique_sequences() {
+        let unique = Vec::from(groups(&[("seq", &["seq/a"]), ("other", &["other/a"])]));
+        let a = SequenceTopicGroups::try_new(unique).unwrap();
+        let b = groups(&[("seq", &["seq/b"])]);
+
+        assert_eq!(
+            names(a.merge_strict(b).unwrap()),
+            vec![(
+                "seq".to_owned(),
+                vec!["seq/a".to_owned(), "seq/b".to_owned()]
+            )]
+        );
+    }
 }
@@ -0,0 +1,104 @@
+//! Lifecycle states of the resources and the transitions allowed between them.
+
+/// Lifecycle state of a resource, a topic or a sequence.
+///
+/// A topic is [`ResourceState::Open`] while being uploaded and [`ResourceState::Locked`] once
+/// its upload is closed gracefully. A sequence is locked once all its topics are locked. The
+/// finalization of a sequence makes it and its topics [`ResourceState::Finalized`].
+///
+/// The allowed transitions are:
+///
+/// | from     | to          | trigger                              |
+/// |----------|-------------|--------------------------------------|
+/// | `Open`   | `Locked`    | the upload of the topic is closed    |
+/// | `Locked` | `Open`      | a forced overwrite reopens the topic |
+/// | `Locked` | `Finalized` | the sequence is finalized            |
+///
+/// A transition to the current state has no effect and is always allowed, `Finalized` is a
+/// terminal state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceState {
+    Open,
+    Locked,
+    Finalized,
+}
+
+/// Error returned by a transition not allowed by the [`ResourceState`] lifecycle.
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("invalid transition from `{from}` to `{to}`")]
+pub struct InvalidTransition {
+    pub from: ResourceState,
+    pub to: ResourceState,
+}
+
+impl ResourceState {
+    /// Returns true if the lifecycle allows moving from this state to `to`.
+    pub fn can_transition_to(self, to: ResourceState) -> bool {
+        use ResourceState::*;
+        self == to
+            || matches!(
+                (self, to),
+                (Open, Locked) | (Locked, Open) | (Locked, Finalized)
+            )
+    }
+
+    /// Returns the state reached moving to `to`, failing with [`InvalidTransition`] if the move
+    /// is not allowed.
+    pub fn transition(self, to: ResourceState) -> Result<ResourceState, InvalidTransition> {
+        if self.can_transition_to(to) {
+            Ok(to)
+        } else {
+            Err(InvalidTransition { from: self, to })
+        }
+    }
+}
+
+impl std::fmt::Display for ResourceState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Open => "open",
+            Self::Locked => "locked",
+            Self::Finalized => "finalized",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legal_transitions() {
+        use ResourceState::*;
+
+        assert_eq!(Open.transition(Locked), Ok(Locked));
+        assert_eq!(Locked.transition(Open), Ok(Open));
+        assert_eq!(Locked.transition(Finalized), Ok(Finalized));
+
+        for state in [Open, Locked, Finalized] {
+            assert_eq!(state.transition(state), Ok(state));
+        }
+    }
+
+    #[test]
+    fn illegal_transitions() {
+        use ResourceState::*;
+
+        // A sequence with open topics can't be finalized
+        assert_eq!(
+            Open.transition(Finalized),
+            Err(InvalidTransition {
+                from: Open,
+                to: Finalized
+            })
+        );
+        // Finalized resources can't be written (reopened) nor unlocked
+        assert!(!Finalized.can_transition_to(Open));
+        assert!(!Finalized.can_transition_to(Locked));
+        assert_eq!(
+            Finalized.transition(Open).unwrap_err().to_string(),
+            "invalid transition from `finalized` to `open`"
+        );
+    }
+}
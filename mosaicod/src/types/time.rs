@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Timestamp format used by mosaico
@@ -30,6 +31,32 @@ impl Timestamp {
     pub fn min() -> Self {
         Self(i64::MIN)
     }
+
+    /// Converts the timestamp to a [`DateTime`].
+    ///
+    /// Returns **`None`** if the timestamp is outside the range representable
+    /// by a [`DateTime`].
+    pub fn try_into_datetime(self) -> Option<DateTime> {
+        chrono::DateTime::<chrono::Utc>::from_timestamp_millis(self.0).map(DateTime)
+    }
+
+    /// Returns the millisecond-precision timestamp shifted by `millis`, saturating at the
+    /// bounds of the representable range.
+    pub fn saturating_add_millis(self, millis: i64) -> Self {
+        Self(self.0.saturating_add(millis))
+    }
+
+    /// Converts a millisecond-precision timestamp to nanosecond precision, the precision of
+    /// the data timestamps, saturating at the bounds of the representable range.
+    pub fn millis_to_nanos(self) -> Self {
+        Self(self.0.saturating_mul(1_000_000))
+    }
+
+    /// Converts a timestamp expressed in `precision` units to nanoseconds, saturating at the
+    /// bounds of the representable range.
+    pub fn to_nanos(self, precision: TimePrecision) -> Self {
+        Self(self.0.saturating_mul(precision.nanos_per_unit()))
+    }
 }
 
 impl std::fmt::Display for Timestamp {
@@ -50,15 +77,164 @@ impl From<Timestamp> for i64 {
     }
 }
 
-impl From<Timestamp> for DateTime {
-    fn from(value: Timestamp) -> Self {
-        Self(
-            chrono::DateTime::<chrono::Utc>::from_timestamp_millis(value.0)
-                .expect("invalid timestamp"),
-        )
+/// Converts a system time to a millisecond-precision timestamp, sub-millisecond digits are
+/// truncated towards the past (so that times before the Unix Epoch map to negative
+/// timestamps consistently). Times beyond the representable range saturate.
+impl From<SystemTime> for Timestamp {
+    fn from(value: SystemTime) -> Self {
+        match value.duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => Self(i64::try_from(elapsed.as_millis()).unwrap_or(i64::MAX)),
+            Err(e) => {
+                let before = e.duration();
+                let millis = i64::try_from(before.as_millis()).unwrap_or(i64::MAX);
+                // A partial millisecond belongs to the previous millisecond
+                let partial = before.subsec_nanos() > before.subsec_millis() * 1_000_000;
+                Self((-millis).saturating_sub(partial as i64))
+            }
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("timestamp {0} is outside the range representable by the system time")]
+pub struct TimestampOutOfRange(pub Timestamp);
+
+impl TryFrom<Timestamp> for SystemTime {
+    type Error = TimestampOutOfRange;
+
+    fn try_from(value: Timestamp) -> Result<Self, Self::Error> {
+        let offset = std::time::Duration::from_millis(value.0.unsigned_abs());
+        if value.0 >= 0 {
+            UNIX_EPOCH.checked_add(offset)
+        } else {
+            UNIX_EPOCH.checked_sub(offset)
+        }
+        .ok_or(TimestampOutOfRange(value))
+    }
+}
+
+/// Source of the current time.
+///
+/// Time-dependent logic reads the current time from a clock instead of the system time,
+/// allowing tests to control the passing of time.
+pub trait Clock: Send + Sync {
+    /// Returns the current time as a millisecond-precision UTC timestamp
+    fn now(&self) -> Timestamp;
+}
+
+/// Clock reading the system time
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::now()
+    }
+}
+
+/// Clock whose time changes only when explicitly set or advanced
+#[cfg(test)]
+#[derive(Debug)]
+pub struct MockClock(std::sync::atomic::AtomicI64);
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(now: Timestamp) -> Self {
+        Self(std::sync::atomic::AtomicI64::new(now.into()))
     }
+
+    /// Sets the current time of the clock
+    pub fn set(&self, now: Timestamp) {
+        self.0
+            .store(now.into(), std::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// Moves the current time of the clock forward by `duration`
+    pub fn advance(&self, duration: std::time::Duration) {
+        self.0.fetch_add(
+            duration.as_millis() as i64,
+            std::sync::atomic::Ordering::SeqCst,
+        );
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> Timestamp {
+        Timestamp(self.0.load(std::sync::atomic::Ordering::SeqCst))
+    }
+}
+
+/// Unit of the timestamps stored in the data of a topic.
+///
+/// Timestamps exchanged with the system (query ranges, reported ranges) are always expressed
+/// in nanoseconds, the precision of a topic is used to convert them to the unit of the stored
+/// data and back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimePrecision {
+    Seconds,
+    Millis,
+    Micros,
+    #[default]
+    Nanos,
+}
+
+impl TimePrecision {
+    /// List of all the supported precisions.
+    pub const ALL: [TimePrecision; 4] = [Self::Seconds, Self::Millis, Self::Micros, Self::Nanos];
+
+    /// Number of nanoseconds in a single unit of this precision.
+    pub fn nanos_per_unit(&self) -> i64 {
+        match self {
+            Self::Seconds => 1_000_000_000,
+            Self::Millis => 1_000_000,
+            Self::Micros => 1_000,
+            Self::Nanos => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for TimePrecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Seconds => "seconds",
+            Self::Millis => "millis",
+            Self::Micros => "micros",
+            Self::Nanos => "nanos",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for TimePrecision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|precision| precision.to_string() == s)
+            .ok_or_else(|| format!("unknown time precision `{}`", s))
+    }
+}
+
+/// Row selected by a point lookup at a timestamp
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LookupMode {
+    /// Row exactly at the timestamp
+    Exact,
+    /// Row closest to the timestamp, the earlier one if two rows are equally close
+    Nearest,
+    /// Last row at or before the timestamp
+    Before,
+    /// First row at or after the timestamp
+    After,
 }
 
+/// Position of a chunk in the ordered list of chunks of a topic
+pub type ChunkIndex = usize;
+
 /// Represents a closed interval of time where both the start and end are included.
 ///
 /// This struct defines a range $[start, end]$. A timestamp is considered
@@ -73,6 +249,75 @@ impl TimestampRange {
     pub fn new(start: Timestamp, end: Timestamp) -> Self {
         Self { start, end }
     }
+
+    /// Returns the smallest range containing both `self` and `other`.
+    pub fn merge(&self, other: &Self) -> Self {
+        Self {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    /// Returns the range shared by `self` and `other`, if any.
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end);
+
+        if start > end {
+            return None;
+        }
+
+        Some(Self { start, end })
+    }
+
+    /// Converts a nanosecond range to the `precision` units of the data of a topic.
+    ///
+    /// The returned range contains exactly the values whose nanosecond conversion lies in
+    /// `self`, so the start is rounded up and the end is rounded down. Returns [`None`] if no
+    /// value of the given precision falls in the range.
+    pub fn to_precision(&self, precision: TimePrecision) -> Option<Self> {
+        let unit = precision.nanos_per_unit();
+
+        let start: i64 = self.start.into();
+        let end: i64 = self.end.into();
+
+        let mut start_units = start.div_euclid(unit);
+        if start.rem_euclid(unit) != 0 {
+            start_units += 1;
+        }
+        let end_units = end.div_euclid(unit);
+
+        if start_units > end_units {
+            return None;
+        }
+
+        Some(Self::new(start_units.into(), end_units.into()))
+    }
+
+    /// Converts a range expressed in `precision` units to nanoseconds.
+    pub fn to_nanos(&self, precision: TimePrecision) -> Self {
+        Self::new(self.start.to_nanos(precision), self.end.to_nanos(precision))
+    }
+
+    /// Splits the range into per-chunk work units.
+    ///
+    /// Each entry of `chunk_bounds` is the closed `(start, end)` interval covered by a chunk,
+    /// the returned list contains the intersection of `self` with every overlapping chunk,
+    /// paired with the index of the chunk in `chunk_bounds`. Chunks not covered by the range
+    /// are skipped.
+    pub fn split_by_chunks(
+        &self,
+        chunk_bounds: &[(Timestamp, Timestamp)],
+    ) -> Vec<(ChunkIndex, TimestampRange)> {
+        chunk_bounds
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, (start, end))| {
+                self.intersect(&Self::new(*start, *end))
+                    .map(|range| (idx, range))
+            })
+            .collect()
+    }
 }
 
 impl std::fmt::Display for TimestampRange {
@@ -88,6 +333,7 @@ impl std::fmt::Debug for TimestampRange {
 }
 
 /// `DateTime` format used by mosaico
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
 pub struct DateTime(chrono::DateTime<chrono::Utc>);
 
 impl DateTime {
@@ -98,6 +344,13 @@ impl DateTime {
     pub fn fmt_to_ms(&self) -> String {
         self.0.format("%Y%m%d%H%M%S%3f").to_string()
     }
+
+    /// Formats the datetime converted in the provided time zone.
+    ///
+    /// This is intended only for human-facing output, internally datetimes are always UTC.
+    pub fn to_local_string(&self, tz: chrono_tz::Tz) -> String {
+        self.0.with_timezone(&tz).to_string()
+    }
 }
 
 impl std::fmt::Display for DateTime {
@@ -105,3 +358,106 @@ impl std::fmt::Display for DateTime {
         write!(f, "{}", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_range_timestamp_to_datetime() {
+        assert!(Timestamp::max().try_into_datetime().is_none());
+        assert!(Timestamp::min().try_into_datetime().is_none());
+        assert!(Timestamp::from(0).try_into_datetime().is_some());
+    }
+
+    #[test]
+    fn system_time_roundtrip() {
+        // 2024-01-15 12:00:00.123 UTC
+        let time = UNIX_EPOCH + std::time::Duration::from_millis(1_705_320_000_123);
+        let ts = Timestamp::from(time);
+        assert_eq!(i64::from(ts), 1_705_320_000_123);
+        assert_eq!(SystemTime::try_from(ts).unwrap(), time);
+
+        // Sub-millisecond digits are truncated
+        let ts = Timestamp::from(time + std::time::Duration::from_micros(999));
+        assert_eq!(i64::from(ts), 1_705_320_000_123);
+    }
+
+    #[test]
+    fn pre_epoch_system_time() {
+        // 1969-12-31 23:59:58.500 UTC
+        let time = UNIX_EPOCH - std::time::Duration::from_millis(1_500);
+        let ts = Timestamp::from(time);
+        assert_eq!(i64::from(ts), -1_500);
+        assert_eq!(SystemTime::try_from(ts).unwrap(), time);
+
+        // Partial milliseconds are truncated towards the past
+        let time = UNIX_EPOCH - std::time::Duration::from_micros(1_500);
+        assert_eq!(i64::from(Timestamp::from(time)), -2);
+    }
+
+    #[test]
+    fn datetime_to_local_string() {
+        // 2024-01-15 12:00:00 UTC
+        let dt = Timestamp::from(1_705_320_000_000)
+            .try_into_datetime()
+            .unwrap();
+
+        assert_eq!(dt.to_string(), "2024-01-15 12:00:00 UTC");
+        assert_eq!(
+            dt.to_local_string(chrono_tz::America::New_York),
+            "2024-01-15 07:00:00 EST"
+        );
+        assert_eq!(
+            dt.to_local_string(chrono_tz::Asia::Tokyo),
+            "2024-01-15 21:00:00 JST"
+        );
+    }
+
+    #[test]
+    fn range_to_precision() {
+        let range = TimestampRange::new(Timestamp::from(1_500), Timestamp::from(4_000));
+
+        let micros = range.to_precision(TimePrecision::Micros).unwrap();
+        assert_eq!(micros.start, Timestamp::from(2));
+        assert_eq!(micros.end, Timestamp::from(4));
+
+        let back = micros.to_nanos(TimePrecision::Micros);
+        assert_eq!(back.start, Timestamp::from(2_000));
+        assert_eq!(back.end, Timestamp::from(4_000));
+
+        let nanos = range.to_precision(TimePrecision::Nanos).unwrap();
+        assert_eq!(nanos.start, range.start);
+        assert_eq!(nanos.end, range.end);
+
+        // No whole millisecond is included in the range
+        assert!(range.to_precision(TimePrecision::Millis).is_none());
+
+        let negative = TimestampRange::new(Timestamp::from(-2_500), Timestamp::from(-500));
+        let micros = negative.to_precision(TimePrecision::Micros).unwrap();
+        assert_eq!(micros.start, Timestamp::from(-2));
+        assert_eq!(micros.end, Timestamp::from(-1));
+    }
+
+    #[test]
+    fn split_by_chunks() {
+        let chunks = [
+            (Timestamp::from(0), Timestamp::from(99)),
+            (Timestamp::from(100), Timestamp::from(199)),
+            (Timestamp::from(200), Timestamp::from(299)),
+        ];
+
+        let range = TimestampRange::new(Timestamp::from(150), Timestamp::from(250));
+        let units = range.split_by_chunks(&chunks);
+
+        assert_eq!(units.len(), 2);
+
+        assert_eq!(units[0].0, 1);
+        assert_eq!(units[0].1.start, Timestamp::from(150));
+        assert_eq!(units[0].1.end, Timestamp::from(199));
+
+        assert_eq!(units[1].0, 2);
+        assert_eq!(units[1].1.start, Timestamp::from(200));
+        assert_eq!(units[1].1.end, Timestamp::from(250));
+    }
+}
@@ -0,0 +1,20 @@
+/// 64-bit FNV-1a hash, used since it is stable across platforms and compiler versions.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// SHA-256 digest of some content.
+pub type Digest = [u8; 32];
+
+/// SHA-256 digest of `bytes`, used where a collision-resistant hash of the content is needed.
+pub fn sha256(bytes: &[u8]) -> Digest {
+    use sha2::Digest as _;
+    sha2::Sha256::digest(bytes).into()
+}
+
+/// Lowercase hexadecimal representation of `bytes`.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
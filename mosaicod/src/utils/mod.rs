@@ -1,2 +1,3 @@
+pub mod hash;
 pub mod print;
 pub mod random;